@@ -0,0 +1,323 @@
+use crate::common::node::Node;
+use crate::ebr::{Domain, Guard, Ref, Shared};
+use crate::stack::Stack;
+
+use std::iter::FusedIterator;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::{Acquire, Relaxed};
+
+/// The next sub-bag index to be assigned to a thread.
+static NEXT_SUB_BAG_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The sub-bag index assigned to the current thread.
+    static SUB_BAG_INDEX: usize = NEXT_SUB_BAG_INDEX.fetch_add(1, Relaxed);
+}
+
+/// A concurrent unordered container.
+///
+/// scc::Bag is an array of sub-bags each of which occupies a dedicated cache line, and each
+/// thread is assigned a sub-bag. [`Bag::push`] always puts a value into the sub-bag of the
+/// thread, and [`Bag::pop`] takes a value out of the sub-bag of the thread, and only if it is
+/// empty, steals one from the other sub-bags. Therefore, a thread that pops as many values as
+/// it pushes never touches the sub-bags of other threads, which makes scc::Bag suitable for
+/// object pools where the order of values is irrelevant.
+///
+/// All the operations are lock-free. The values can be read by [`Bag::iter`] in place while the
+/// thread is pinned, therefore a popped value is not moved out of the Bag: [`Bag::pop`] returns a
+/// clone of it, and the value in the Bag is dropped once no thread can read it.
+///
+/// ## The key statistics for scc::Bag
+/// * The size of the metadata for a single value: one word.
+/// * The number of atomic read-modify-write operations for a single push: two.
+pub struct Bag<T> {
+    sub_bags: Box<[SubBag<T>]>,
+    #[cfg(feature = "metrics")]
+    remote_accesses: AtomicUsize,
+}
+
+/// SubBag is a Stack along with the number of values in it.
+#[repr(align(128))]
+struct SubBag<T> {
+    stack: Stack<T>,
+    len: AtomicUsize,
+}
+
+impl<T> Default for Bag<T> {
+    /// Creates an empty Bag.
+    ///
+    /// The number of sub-bags is the number of available threads rounded up to the next power of
+    /// two.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Bag;
+    ///
+    /// let bag: Bag<u64> = Default::default();
+    ///
+    /// assert!(bag.is_empty());
+    /// ```
+    fn default() -> Self {
+        let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Bag::new(num_threads)
+    }
+}
+
+impl<T> Bag<T> {
+    /// Creates an empty Bag that is optimized for the given number of threads.
+    ///
+    /// The number of sub-bags is the given number rounded up to the next power of two.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Bag;
+    ///
+    /// let bag: Bag<u64> = Bag::new(4);
+    ///
+    /// assert_eq!(bag.len(), 0);
+    /// ```
+    pub fn new(num_threads: usize) -> Bag<T> {
+        let num_sub_bags = num_threads.max(1).next_power_of_two();
+        Bag {
+            sub_bags: (0..num_sub_bags)
+                .map(|_| SubBag {
+                    stack: Stack::new(),
+                    len: AtomicUsize::new(0),
+                })
+                .collect(),
+            #[cfg(feature = "metrics")]
+            remote_accesses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pushes a value into the Bag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Bag;
+    ///
+    /// let bag: Bag<u64> = Default::default();
+    ///
+    /// bag.push(1);
+    /// assert_eq!(bag.pop(), Some(1));
+    /// ```
+    pub fn push(&self, value: T) {
+        let sub_bag = &self.sub_bags[self.local_index()];
+        // The counter is incremented in advance so that it never underflows.
+        sub_bag.len.fetch_add(1, Relaxed);
+        sub_bag.stack.push(value);
+    }
+
+    /// Pops a value from the Bag.
+    ///
+    /// It prefers the values pushed by the threads sharing the sub-bag with the current thread,
+    /// and steals a value from other sub-bags only if the sub-bag is empty. It returns a clone of
+    /// the value, and the value in the Bag is dropped once no thread can read it through
+    /// [`Bag::iter`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Bag;
+    ///
+    /// let bag: Bag<u64> = Default::default();
+    ///
+    /// assert!(bag.pop().is_none());
+    /// bag.push(1);
+    /// assert_eq!(bag.pop(), Some(1));
+    /// assert!(bag.pop().is_none());
+    /// ```
+    pub fn pop(&self) -> Option<T>
+    where
+        T: Clone + Send + 'static,
+    {
+        let local_index = self.local_index();
+        if let Some(value) = self.pop_from(local_index) {
+            return Some(value);
+        }
+        for offset in 1..self.sub_bags.len() {
+            #[cfg(feature = "metrics")]
+            self.remote_accesses.fetch_add(1, Relaxed);
+            let index = (local_index + offset) & (self.sub_bags.len() - 1);
+            if let Some(value) = self.pop_from(index) {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Returns the number of values in the Bag.
+    ///
+    /// It sums up the counters of the sub-bags, therefore it may be momentarily off while other
+    /// threads are modifying the Bag.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Bag;
+    ///
+    /// let bag: Bag<u64> = Default::default();
+    ///
+    /// bag.push(1);
+    /// bag.push(2);
+    /// assert_eq!(bag.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.sub_bags
+            .iter()
+            .map(|sub_bag| sub_bag.len.load(Relaxed))
+            .sum()
+    }
+
+    /// Returns `true` if the Bag is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Bag;
+    ///
+    /// let bag: Bag<u64> = Default::default();
+    ///
+    /// assert!(bag.is_empty());
+    /// bag.push(1);
+    /// assert!(!bag.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.sub_bags.iter().all(|sub_bag| sub_bag.stack.is_empty())
+    }
+
+    /// Returns an iterator over the values in the Bag.
+    ///
+    /// The values that the iterator yields stay readable while the [`Guard`] is alive even if
+    /// they are popped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] pins the thread in a [`Domain`] other than the global one.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Bag;
+    ///
+    /// let bag: Bag<u64> = Default::default();
+    ///
+    /// for value in 0..4 {
+    ///     bag.push(value);
+    /// }
+    ///
+    /// let guard = scc::pin();
+    /// let sum: u64 = bag.iter(&guard).map(|value| *value).sum();
+    /// assert_eq!(sum, 6);
+    /// ```
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> BagIter<'g, T> {
+        Domain::global().check_guard(guard);
+        BagIter {
+            bag: self,
+            index: 0,
+            current: Shared::null(),
+            guard,
+        }
+    }
+
+    /// Returns the number of times a thread has accessed a sub-bag other than its own.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Bag;
+    ///
+    /// let bag: Bag<u64> = Bag::new(2);
+    ///
+    /// bag.push(1);
+    /// assert_eq!(bag.pop(), Some(1));
+    /// assert_eq!(bag.remote_accesses(), 0);
+    ///
+    /// assert!(bag.pop().is_none());
+    /// assert_eq!(bag.remote_accesses(), 1);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn remote_accesses(&self) -> usize {
+        self.remote_accesses.load(Relaxed)
+    }
+
+    /// Returns the index of the sub-bag assigned to the current thread.
+    fn local_index(&self) -> usize {
+        SUB_BAG_INDEX.with(|index| *index & (self.sub_bags.len() - 1))
+    }
+
+    /// Pops a value from the sub-bag at the index.
+    fn pop_from(&self, index: usize) -> Option<T>
+    where
+        T: Clone + Send + 'static,
+    {
+        let sub_bag = &self.sub_bags[index];
+        let value = sub_bag.stack.pop()?;
+        sub_bag.len.fetch_sub(1, Relaxed);
+        Some(value)
+    }
+}
+
+/// BagIter is an iterator over the values of a [`Bag`].
+///
+/// It is returned by [`Bag::iter`].
+pub struct BagIter<'g, T> {
+    bag: &'g Bag<T>,
+    index: usize,
+    current: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<'g, T: 'g> Iterator for BagIter<'g, T> {
+    type Item = Ref<'g, T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.current = if let Some(current) = unsafe { self.current.as_ref() } {
+                // The current Node is not dropped while the Guard is alive.
+                current.next().load(Acquire, self.guard)
+            } else {
+                // Proceeds to the next sub-bag.
+                let sub_bag = self.bag.sub_bags.get(self.index)?;
+                self.index += 1;
+                sub_bag.stack.top(self.guard)
+            };
+            if let Some(current) = unsafe { self.current.as_ref() } {
+                return Some(Ref::new(current.value(), self.guard));
+            }
+        }
+    }
+}
+
+impl<'g, T: 'g> FusedIterator for BagIter<'g, T> {}
+
+#[cfg(all(test, feature = "metrics"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn local_fast_path() {
+        let num_threads = 8;
+        let bag: Bag<usize> = Bag::new(num_threads);
+        std::thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let bag = &bag;
+                s.spawn(move || {
+                    for value in 0..4096 {
+                        bag.push(thread_id * 4096 + value);
+                        assert!(bag.pop().is_some());
+                    }
+                });
+            }
+        });
+        // Each thread finds a value in its own sub-bag since it pushes before it pops.
+        assert_eq!(bag.remote_accesses(), 0);
+        assert!(bag.is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use alloc::alloc::{alloc, alloc_zeroed, dealloc, Layout};
+
+/// ArrayAllocator allocates memory for the Cells and DataArrays of hash tables.
+///
+/// The memory of a hash table may be reclaimed by the epoch-based garbage collector long after
+/// the hash table was dropped, therefore the functions do not take an allocator instance, and the
+/// implementation has to be reachable at any time through the type.
+///
+/// # Safety
+///
+/// `allocate` and `allocate_zeroed` must return either a null pointer or a pointer to a memory
+/// block that fits the given layout, and `deallocate` must accept any memory block returned by
+/// them with the same layout.
+///
+/// # Examples
+/// ```
+/// use scc::{ArrayAllocator, HashIndex};
+/// use std::alloc::{alloc, dealloc, Layout};
+/// use std::collections::hash_map::RandomState;
+/// use std::sync::atomic::AtomicUsize;
+/// use std::sync::atomic::Ordering::Relaxed;
+///
+/// static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+///
+/// struct CountingAllocator;
+///
+/// unsafe impl ArrayAllocator for CountingAllocator {
+///     fn allocate(layout: Layout) -> *mut u8 {
+///         ALLOCATED.fetch_add(layout.size(), Relaxed);
+///         unsafe { alloc(layout) }
+///     }
+///     unsafe fn deallocate(ptr: *mut u8, layout: Layout) {
+///         ALLOCATED.fetch_sub(layout.size(), Relaxed);
+///         dealloc(ptr, layout);
+///     }
+/// }
+///
+/// let hashindex: HashIndex<u64, u32, RandomState, 32, CountingAllocator> =
+///     HashIndex::new_in(64, RandomState::new());
+/// assert!(hashindex.insert(1, 0).is_ok());
+/// assert!(ALLOCATED.load(Relaxed) > 0);
+/// ```
+pub unsafe trait ArrayAllocator: 'static {
+    /// Allocates a memory block that fits the layout.
+    ///
+    /// It returns a null pointer if memory allocation fails.
+    fn allocate(layout: Layout) -> *mut u8;
+
+    /// Allocates a zero-initialized memory block that fits the layout.
+    ///
+    /// It returns a null pointer if memory allocation fails.
+    fn allocate_zeroed(layout: Layout) -> *mut u8 {
+        let ptr = Self::allocate(layout);
+        if !ptr.is_null() {
+            unsafe { ptr.write_bytes(0, layout.size()) };
+        }
+        ptr
+    }
+
+    /// Deallocates the memory block.
+    ///
+    /// # Safety
+    ///
+    /// The memory block must have been allocated by the allocator with the same layout.
+    unsafe fn deallocate(ptr: *mut u8, layout: Layout);
+}
+
+/// GlobalArrayAllocator is the default [`ArrayAllocator`] that forwards requests to the global
+/// allocator.
+#[derive(Debug, Default)]
+pub struct GlobalArrayAllocator;
+
+unsafe impl ArrayAllocator for GlobalArrayAllocator {
+    #[inline]
+    fn allocate(layout: Layout) -> *mut u8 {
+        unsafe { alloc(layout) }
+    }
+
+    #[inline]
+    fn allocate_zeroed(layout: Layout) -> *mut u8 {
+        unsafe { alloc_zeroed(layout) }
+    }
+
+    #[inline]
+    unsafe fn deallocate(ptr: *mut u8, layout: Layout) {
+        dealloc(ptr, layout);
+    }
+}
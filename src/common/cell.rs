@@ -1,45 +1,298 @@
-use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
-use std::borrow::Borrow;
-use std::mem::MaybeUninit;
-use std::sync::atomic::AtomicU32;
-use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
-use std::sync::{Condvar, Mutex};
+use super::allocator::{ArrayAllocator, GlobalArrayAllocator};
+use super::sync::{fence, spin_loop, AtomicBool, AtomicU32};
+use crate::ebr::{Atomic, Guard, Shared};
+use alloc::alloc::{handle_alloc_error, Layout};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cell::{RefCell, UnsafeCell};
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use core::future::Future;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+#[cfg(feature = "std")]
+use core::pin::Pin;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+#[cfg(feature = "std")]
+use core::task::{Context, Poll, Waker};
+#[cfg(all(feature = "std", not(loom)))]
+use std::sync::Mutex;
+#[cfg(all(feature = "std", not(loom)))]
+use std::thread::{self, Thread};
+
+/// A partial hash value is stored along with each entry to avoid comparing keys.
+///
+/// The `wide-partial-hash` feature widens it to 16 bits, making false positive matches, and
+/// therefore key comparisons, rarer at the cost of one more byte of metadata per entry.
+#[cfg(not(feature = "wide-partial-hash"))]
+pub type PartialHash = u8;
+#[cfg(feature = "wide-partial-hash")]
+pub type PartialHash = u16;
+
+/// Derives the partial hash value of an entry from its hash value.
+pub fn partial_hash(hash: u64) -> PartialHash {
+    (hash & u64::from(PartialHash::MAX)).try_into().unwrap()
+}
 
 /// Flags are embedded inside a partial hash value.
-const OCCUPIED: u8 = 1u8 << 6;
-const REMOVED: u8 = 1u8 << 7;
+const OCCUPIED: PartialHash = 1 << (PartialHash::BITS - 2);
+const REMOVED: PartialHash = 1 << (PartialHash::BITS - 1);
 
 /// State bits.
 const KILLED: u32 = 1_u32 << 31;
 const WAITING: u32 = 1_u32 << 30;
 const LOCK: u32 = 1_u32 << 29;
-const SLOCK_MAX: u32 = LOCK - 1;
+const WRITER_PENDING: u32 = 1_u32 << 28;
+const SLOCK_MAX: u32 = WRITER_PENDING - 1;
 const LOCK_MASK: u32 = LOCK | SLOCK_MAX;
 
+/// The number of wait queues shared by all the Cells.
+#[cfg(all(feature = "std", not(loom)))]
+const NUM_WAIT_QUEUES: usize = 64;
+
+/// Threads waiting for a Cell are put into the wait queue that the address of the Cell is
+/// mapped to, so that a Cell does not need to reserve space for its own wait queue.
+#[cfg(all(feature = "std", not(loom)))]
+static WAIT_QUEUES: [Mutex<Vec<Waiter>>; NUM_WAIT_QUEUES] =
+    [const { Mutex::new(Vec::new()) }; NUM_WAIT_QUEUES];
+
+/// The ID of the next task registered in a wait queue.
+#[cfg(all(feature = "std", not(loom)))]
+static NEXT_TASK_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of bytes that have been retired but not yet reclaimed by the epoch-based garbage
+/// collector, summed across all the containers.
+static DEFERRED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of bytes that are waiting to be reclaimed.
+pub fn deferred_bytes() -> usize {
+    DEFERRED_BYTES.load(Relaxed)
+}
+
+/// Defers dropping the given instance until no thread may be reading it, and accounts for
+/// `num_bytes` until it is dropped.
+///
+/// # Safety
+///
+/// The instance must be unreachable by threads that pin the epoch afterwards.
+pub unsafe fn defer_drop_accounted<T>(instance: Shared<T>, num_bytes: usize, guard: &Guard) {
+    defer_accounted(num_bytes, guard, move || drop(instance.into_owned()));
+}
+
+/// Defers executing the given closure until no thread may be reading the memory that it
+/// reclaims, and accounts for `num_bytes` until it is executed.
+///
+/// # Safety
+///
+/// The memory must be unreachable by threads that pin the epoch afterwards.
+pub unsafe fn defer_accounted<F: FnOnce()>(num_bytes: usize, guard: &Guard, f: F) {
+    DEFERRED_BYTES.fetch_add(num_bytes, Relaxed);
+    guard.defer_unchecked_sized(num_bytes, move || {
+        DEFERRED_BYTES.fetch_sub(num_bytes, Relaxed);
+        f();
+    });
+}
+
+/// A detached allocation along with the function that drops and deallocates it.
+struct Detached {
+    ptr: *mut u8,
+    deallocate: unsafe fn(*mut u8),
+}
+
+#[cfg(all(feature = "std", not(loom)))]
+thread_local! {
+    static RETIRE_BATCH: DetachedList = const { RefCell::new(None) };
+}
+
+/// The allocations detached while a [`RetireBatch`] is open, and their size in bytes.
+type DetachedList = RefCell<Option<(Vec<Detached>, usize)>>;
+
+/// Accesses the RetireBatch of the current thread.
+///
+/// Returns `None` if the thread-local storage has already been destroyed.
+#[cfg(all(feature = "std", not(loom)))]
+fn with_retire_batch<R, F: FnOnce(&DetachedList) -> R>(f: F) -> Option<R> {
+    RETIRE_BATCH.try_with(f).ok()
+}
+
+/// Returns `None` as there is no thread-local storage without the standard library, nor one per
+/// thread under the model checker, in which case detached allocations are retired one by one.
+#[cfg(any(not(feature = "std"), loom))]
+fn with_retire_batch<R, F: FnOnce(&DetachedList) -> R>(_f: F) -> Option<R> {
+    None
+}
+
+/// RetireBatch makes the DataArrays detached by the current thread retired as a single unit.
+///
+/// Operations that detach the DataArrays of a large number of Cells, e.g., clear or rehash, open
+/// a RetireBatch so that the epoch-based garbage collector receives one unit instead of one unit
+/// per Cell. The detached DataArrays are retired under the Guard of the outermost RetireBatch
+/// when it is dropped.
+pub struct RetireBatch<'g> {
+    guard: &'g Guard,
+    outermost: bool,
+}
+
+impl<'g> RetireBatch<'g> {
+    /// Opens a RetireBatch; a nested RetireBatch joins the outermost one.
+    pub fn open(guard: &'g Guard) -> RetireBatch<'g> {
+        let outermost = with_retire_batch(|batch| {
+            let mut batch = batch.borrow_mut();
+            if batch.is_none() {
+                batch.replace((Vec::new(), 0));
+                true
+            } else {
+                false
+            }
+        })
+        .unwrap_or(false);
+        RetireBatch { guard, outermost }
+    }
+
+    /// Adds a detached allocation to the open RetireBatch.
+    ///
+    /// Returns `false` if no RetireBatch is open.
+    fn push(detached: Detached, num_bytes: usize) -> bool {
+        with_retire_batch(|batch| {
+            if let Some((detached_list, batch_bytes)) = batch.borrow_mut().as_mut() {
+                detached_list.push(detached);
+                *batch_bytes += num_bytes;
+                true
+            } else {
+                false
+            }
+        })
+        .unwrap_or(false)
+    }
+}
+
+impl<'g> Drop for RetireBatch<'g> {
+    fn drop(&mut self) {
+        if !self.outermost {
+            return;
+        }
+        if let Some((detached_list, num_bytes)) =
+            with_retire_batch(|batch| batch.borrow_mut().take()).flatten()
+        {
+            if !detached_list.is_empty() {
+                unsafe {
+                    defer_accounted(num_bytes, self.guard, move || {
+                        for detached in detached_list {
+                            (detached.deallocate)(detached.ptr);
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of times the current thread has acquired a Cell lock.
+    pub static NUM_LOCK_ACQUISITIONS: core::cell::Cell<usize> =
+        const { core::cell::Cell::new(0) };
+
+    /// The number of DataArrays the current thread has probed for a key.
+    pub static NUM_PROBED_DATA_ARRAYS: core::cell::Cell<usize> =
+        const { core::cell::Cell::new(0) };
+}
+
+/// The size of a cache line.
+///
+/// The adjacent-line prefetcher of aarch64 processors effectively makes a pair of 64-byte cache
+/// lines a single unit.
+#[cfg(target_arch = "aarch64")]
+#[cfg_attr(loom, allow(dead_code))]
+pub const CACHE_LINE_SIZE: usize = 128;
+#[cfg(not(target_arch = "aarch64"))]
+#[cfg_attr(loom, allow(dead_code))]
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// Hints the processor to load the cache line at the given address.
+///
+/// It is a no-op if the `prefetch` feature is disabled or the target architecture is not x86,
+/// and the address is never dereferenced, therefore it may be null or dangling.
+#[inline(always)]
+pub fn prefetch<T>(ptr: *const T) {
+    #[cfg(all(
+        feature = "prefetch",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse"
+    ))]
+    unsafe {
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+        #[cfg(target_arch = "x86_64")]
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr.cast());
+    }
+    #[cfg(not(all(
+        feature = "prefetch",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse"
+    )))]
+    let _ = ptr;
+}
+
 /// Cell is a small fixed-size hash table that resolves hash conflicts using a linked list of entry arrays.
-pub struct Cell<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> {
-    /// wait_queue additionally stores the state of the Cell: locked or killed.
-    wait_queue: Atomic<WaitQueueEntry>,
+///
+/// Each Cell occupies a dedicated cache line so that locking a Cell does not invalidate the cache
+/// line of the adjacent Cells, whereas the entries are stored in separately allocated
+/// DataArrays.
+///
+/// A Cell of which all the bytes are zero is a valid empty Cell, and CellArray relies on it to
+/// allocate Cells as zeroed memory without constructing them; every field has to be an integer
+/// or a pointer of which the zero value means empty.
+#[cfg_attr(target_arch = "aarch64", repr(align(128)))]
+#[cfg_attr(not(target_arch = "aarch64"), repr(align(64)))]
+pub struct Cell<
+    K: Eq,
+    V,
+    const SIZE: usize,
+    const LOCK_FREE: bool,
+    A: ArrayAllocator = GlobalArrayAllocator,
+> {
     /// The state of the Cell.
     state: AtomicU32,
     /// The number of valid entries in the Cell.
-    num_entries: u32,
+    ///
+    /// It is only updated while the Cell is locked, and readers read it without locking the
+    /// Cell.
+    num_entries: AtomicU32,
     /// DataArray stores key-value pairs with their metadata.
-    data: Atomic<DataArray<K, V, SIZE>>,
+    data: Atomic<DataArray<K, V, SIZE, A>>,
 }
 
-impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Default for Cell<K, V, SIZE, LOCK_FREE> {
+// The metadata of a Cell fits into a single cache line, except for the atomic types of the model
+// checker that are larger.
+#[cfg(not(loom))]
+const _: () = assert!(
+    core::mem::size_of::<Cell<u8, (), 1, false>>() == CACHE_LINE_SIZE
+        && core::mem::align_of::<Cell<u8, (), 1, false>>() == CACHE_LINE_SIZE
+);
+
+// An all-zero Cell is empty: the pointer to the DataArray is a plain pointer that is null if
+// zero, and the other fields are integers.
+const _: () =
+    assert!(core::mem::size_of::<Atomic<DataArray<u8, (), 1>>>() == core::mem::size_of::<usize>());
+
+impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator> Default
+    for Cell<K, V, SIZE, LOCK_FREE, A>
+{
     fn default() -> Self {
-        Cell::<K, V, SIZE, LOCK_FREE> {
-            wait_queue: Atomic::null(),
+        Cell::<K, V, SIZE, LOCK_FREE, A> {
             state: AtomicU32::new(0),
-            num_entries: 0,
+            num_entries: AtomicU32::new(0),
             data: Atomic::null(),
         }
     }
 }
 
-impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Cell<K, V, SIZE, LOCK_FREE> {
+impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator>
+    Cell<K, V, SIZE, LOCK_FREE, A>
+{
     /// Returns true if the Cell has been killed.
     pub fn killed(&self) -> bool {
         (self.state.load(Relaxed) & KILLED) == KILLED
@@ -47,41 +300,93 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Cell<K, V, SIZE, LOCK_F
 
     /// Returns the number of entries in the Cell.
     pub fn num_entries(&self) -> usize {
-        self.num_entries as usize
+        self.num_entries.load(Relaxed) as usize
+    }
+
+    /// Sets the number of entries in the Cell.
+    ///
+    /// The Cell has to be locked, therefore the counter does not need to be updated with a
+    /// read-modify-write operation.
+    fn set_num_entries(&self, num_entries: usize) {
+        debug_assert_ne!(self.state.load(Relaxed) & LOCK, 0);
+        self.num_entries
+            .store(num_entries.try_into().unwrap(), Relaxed);
+    }
+
+    /// Returns the number of DataArrays linked to the Cell.
+    pub fn num_data_arrays(&self, guard: &Guard) -> usize {
+        let mut num_data_arrays = 0;
+        let mut current = self.data.load(Acquire, guard);
+        while let Some(data_array_ref) = unsafe { current.as_ref() } {
+            num_data_arrays += 1;
+            current = data_array_ref.link.load(Acquire, guard);
+        }
+        num_data_arrays
+    }
+
+    /// Prefetches the first DataArray of the Cell.
+    pub fn prefetch_data(&self, guard: &Guard) {
+        prefetch(self.data.load(Relaxed, guard).as_raw());
+    }
+
+    /// Returns the number of entries in the Cell that count towards the load factor.
+    ///
+    /// Entries beyond `SIZE` are stored in the linked DataArrays because of a skewed hash value
+    /// distribution, and enlarging the array would not spread them.
+    pub fn num_load_entries(&self) -> usize {
+        self.num_entries().min(SIZE)
     }
 
     /// Iterates the contents of the Cell.
-    pub fn iter<'g>(&'g self, guard: &'g Guard) -> CellIterator<'g, K, V, SIZE, LOCK_FREE> {
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> CellIterator<'g, K, V, SIZE, LOCK_FREE, A> {
         CellIterator::new(self, guard)
     }
 
     /// Searches for an entry associated with the given key.
-    pub fn search<'g, Q>(&self, key: &Q, partial_hash: u8, guard: &'g Guard) -> Option<&'g (K, V)>
+    pub fn search<'g, Q>(&self, key: &Q, hash: u64, guard: &'g Guard) -> Option<(&'g K, &'g V)>
     where
         K: Borrow<Q>,
         Q: Eq + ?Sized,
     {
-        if self.num_entries == 0 {
+        self.search_with(|k| k.borrow() == key, hash, guard)
+    }
+
+    /// Searches for an entry of which the key satisfies the given equivalence predicate.
+    ///
+    /// The stored hash value of a candidate entry is compared before its key if the `full-hash`
+    /// feature is enabled.
+    pub fn search_with<'g, F: FnMut(&K) -> bool>(
+        &self,
+        mut eq: F,
+        hash: u64,
+        guard: &'g Guard,
+    ) -> Option<(&'g K, &'g V)> {
+        if self.num_entries() == 0 {
             return None;
         }
 
         // In order to read the linked list correctly, an acquire fence is required.
         let read_order = if LOCK_FREE { Acquire } else { Relaxed };
         let mut data_array = self.data.load(read_order, guard);
-        let preferred_index = partial_hash as usize % SIZE;
-        let expected_hash = (partial_hash & (!REMOVED)) | OCCUPIED;
+        let expected_hash = (partial_hash(hash) & (!REMOVED)) | OCCUPIED;
         while !data_array.is_null() {
+            #[cfg(test)]
+            NUM_PROBED_DATA_ARRAYS.with(|n| n.set(n.get() + 1));
             let data_array_ref = unsafe { data_array.deref() };
-            for i in preferred_index..preferred_index + SIZE {
-                let index = i % SIZE;
-                if data_array_ref.partial_hash_array[index] == expected_hash {
-                    let entry_ptr = data_array_ref.data[index].as_ptr();
-                    if LOCK_FREE {
-                        std::sync::atomic::fence(Acquire);
-                    }
-                    if *unsafe { &(*entry_ptr) }.0.borrow() == *key {
-                        return Some(unsafe { &(*entry_ptr) });
-                    }
+            let mut candidates = data_array_ref.match_partial_hash(expected_hash);
+            while candidates != 0 {
+                let index = candidates.trailing_zeros() as usize;
+                candidates &= candidates - 1;
+                if LOCK_FREE {
+                    fence(Acquire);
+                }
+                #[cfg(feature = "full-hash")]
+                if data_array_ref.hash_array[index] != hash {
+                    continue;
+                }
+                if eq(unsafe { data_array_ref.key(index) }) {
+                    // The value is only read once the key matches.
+                    return Some(unsafe { data_array_ref.entry(index) });
                 }
             }
             data_array = data_array_ref.link.load(read_order, guard);
@@ -89,43 +394,44 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Cell<K, V, SIZE, LOCK_F
         None
     }
 
-    /// Gets a CellIterator pointing to an entry associated with the given key.
-    pub fn get<'g, Q>(
+    /// Gets a CellIterator pointing to an entry of which the key satisfies the given equivalence
+    /// predicate.
+    #[cfg(feature = "std")]
+    pub fn get_with<'g, F: FnMut(&K) -> bool>(
         &'g self,
-        key: &Q,
-        partial_hash: u8,
+        mut eq: F,
+        hash: u64,
         guard: &'g Guard,
-    ) -> Option<CellIterator<'g, K, V, SIZE, LOCK_FREE>>
-    where
-        K: Borrow<Q>,
-        Q: Eq + ?Sized,
-    {
-        if self.num_entries == 0 {
+    ) -> Option<CellIterator<'g, K, V, SIZE, LOCK_FREE, A>> {
+        if self.num_entries() == 0 {
             return None;
         }
 
         // In order to read the linked list correctly, an acquire fence is required.
         let read_order = if LOCK_FREE { Acquire } else { Relaxed };
         let mut data_array = self.data.load(read_order, guard);
-        let preferred_index = partial_hash as usize % SIZE;
-        let expected_hash = (partial_hash & (!REMOVED)) | OCCUPIED;
+        let expected_hash = (partial_hash(hash) & (!REMOVED)) | OCCUPIED;
         while !data_array.is_null() {
             let data_array_ref = unsafe { data_array.deref() };
-            for i in preferred_index..preferred_index + SIZE {
-                let index = i % SIZE;
-                if data_array_ref.partial_hash_array[index] == expected_hash {
-                    let entry_ptr = data_array_ref.data[index].as_ptr();
-                    if LOCK_FREE {
-                        std::sync::atomic::fence(Acquire);
-                    }
-                    if *unsafe { &(*entry_ptr) }.0.borrow() == *key {
-                        return Some(CellIterator {
-                            cell_ref: Some(self),
-                            current_array: data_array,
-                            current_index: index,
-                            guard_ref: guard,
-                        });
-                    }
+            let mut candidates = data_array_ref.match_partial_hash(expected_hash);
+            while candidates != 0 {
+                let index = candidates.trailing_zeros() as usize;
+                candidates &= candidates - 1;
+                if LOCK_FREE {
+                    fence(Acquire);
+                }
+                #[cfg(feature = "full-hash")]
+                if data_array_ref.hash_array[index] != hash {
+                    continue;
+                }
+                if eq(unsafe { data_array_ref.key(index) }) {
+                    return Some(CellIterator {
+                        cell_ref: Some(self),
+                        current_array: data_array,
+                        current_index: index,
+                        revisit: false,
+                        guard_ref: guard,
+                    });
                 }
             }
             data_array = data_array_ref.link.load(read_order, guard);
@@ -134,110 +440,254 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Cell<K, V, SIZE, LOCK_F
     }
 
     /// Waits for the owner thread to release the Cell.
+    ///
+    /// Returns `None` after the thread is woken up if `f` fails.
+    #[cfg(all(feature = "std", not(loom)))]
     fn wait<T, F: FnOnce() -> Option<T>>(&self, f: F, guard: &Guard) -> Option<T> {
-        // Inserts the condvar into the wait queue.
-        let mut current = self.wait_queue.load(Relaxed, guard);
-        let mut condvar = WaitQueueEntry::new(Atomic::from(current));
-
-        let mut next = Shared::from(&condvar as *const _);
-        while let Err(result) = self
-            .wait_queue
-            .compare_exchange(current, next, Release, Relaxed, guard)
+        let signaled = AtomicBool::new(false);
+        let wait_queue = self.wait_queue();
         {
-            current = result.current;
-            next = Shared::from(&condvar as *const _);
-            condvar.next = Atomic::from(result.current);
+            // Marks that there is a waiting thread while the wait queue is locked, so that a
+            // thread waking up the waiting threads reads the correct state.
+            let mut waiters = wait_queue.lock().unwrap();
+            waiters.push(Waiter {
+                cell: self.address(),
+                wakeup: Wakeup::Thread {
+                    signaled: &signaled,
+                    thread: thread::current(),
+                },
+            });
+            self.state.fetch_or(WAITING, Relaxed);
         }
 
-        // Marks that there is a waiting thread.
-        self.state.fetch_or(WAITING, Relaxed);
-
-        // Tries to lock again once the condvar is inserted into the wait queue.
+        // Tries to lock again once the thread is put into the wait queue.
         let locked = f();
         if locked.is_some() {
+            let mut waiters = wait_queue.lock().unwrap();
+            if let Some(index) = waiters.iter().position(
+                |w| matches!(w.wakeup, Wakeup::Thread { signaled: s, .. } if core::ptr::eq(s, &signaled)),
+            ) {
+                waiters.remove(index);
+            }
+            // Otherwise, `signaled` has been set while the wait queue was locked, and the waking
+            // thread has kept the WAITING flag if there are other waiting threads.
+            return locked;
+        }
+        if (self.state.load(Relaxed) & LOCK_MASK) == 0 {
+            // Locking failed even though no thread holds the lock, e.g., a writer is pending,
+            // therefore no thread may release the lock and wake up the waiting threads.
             self.wakeup(guard);
         }
 
         // Locking failed.
-        condvar.wait();
-        locked
+        while !signaled.load(Acquire) {
+            thread::park();
+        }
+        None
     }
 
-    /// Wakes up the threads in the wait queue.
-    fn wakeup(&self, guard: &Guard) {
-        let mut current = self.wait_queue.load(Acquire, guard);
-        let mut next = Shared::null();
-        while let Err(result) = self
-            .wait_queue
-            .compare_exchange(current, next, Acquire, Relaxed, guard)
+    /// Waits for the owner thread to release the Cell.
+    ///
+    /// Threads cannot be parked without the standard library or under the model checker,
+    /// therefore it only backs off before retrying `f`.
+    #[cfg(any(not(feature = "std"), loom))]
+    fn wait<T, F: FnOnce() -> Option<T>>(&self, f: F, _guard: &Guard) -> Option<T> {
+        super::yield_now();
+        f()
+    }
+
+    /// Registers the task awaiting `async_wait` to be woken up when the Cell is released.
+    ///
+    /// Returns the result of `f` retried after the task is put into the wait queue; the task is
+    /// removed from the wait queue if `f` succeeds.
+    #[cfg(all(feature = "std", not(loom)))]
+    fn register<T, F: FnOnce() -> Option<T>>(
+        &self,
+        async_wait: &mut AsyncWait,
+        f: F,
+        guard: &Guard,
+    ) -> Option<T> {
+        let id = NEXT_TASK_ID.fetch_add(1, Relaxed);
         {
-            current = result.current;
-            if current.is_null() {
-                return;
-            }
-            next = Shared::null();
+            let mut waiters = self.wait_queue().lock().unwrap();
+            waiters.push(Waiter {
+                cell: self.address(),
+                wakeup: Wakeup::Task { id, waker: None },
+            });
+            self.state.fetch_or(WAITING, Relaxed);
         }
+        async_wait.registered = Some((self.address(), id));
 
-        while !current.is_null() {
-            let cond_var_ref = unsafe { current.deref() };
-            let next_ptr = cond_var_ref.next.load(Acquire, guard);
-            cond_var_ref.signal();
-            current = next_ptr;
+        // Tries to lock again once the task is put into the wait queue.
+        let locked = f();
+        if locked.is_some() {
+            async_wait.deregister();
+            return locked;
         }
+        if (self.state.load(Relaxed) & LOCK_MASK) == 0 {
+            // No thread may release the lock and wake up the task.
+            self.wakeup(guard);
+        }
+        None
     }
 
-    /// Returns the max resizing factor.
+    /// Registers the task awaiting `async_wait` to be woken up when the Cell is released.
+    ///
+    /// There are no wait queues under the model checker, therefore it only backs off before
+    /// retrying `f`, and `async_wait` resolves immediately.
+    #[cfg(all(feature = "std", loom))]
+    fn register<T, F: FnOnce() -> Option<T>>(
+        &self,
+        _async_wait: &mut AsyncWait,
+        f: F,
+        _guard: &Guard,
+    ) -> Option<T> {
+        super::yield_now();
+        f()
+    }
+
+    /// Wakes up a thread and all the tasks waiting for the Cell.
+    ///
+    /// A woken thread always retries, whereas a woken task may be dropped before it retries,
+    /// therefore all the tasks are woken up lest the wakeup be lost. All the waiting threads are
+    /// woken up if the Cell has been killed.
+    #[cfg(all(feature = "std", not(loom)))]
+    fn wakeup(&self, _guard: &Guard) {
+        let address = self.address();
+        let mut waiters = self.wait_queue().lock().unwrap();
+        let killed = self.killed();
+        let mut thread_woken = false;
+        let mut woken = Vec::new();
+        let mut index = 0;
+        while index < waiters.len() {
+            let waiter = &waiters[index];
+            let is_thread = matches!(waiter.wakeup, Wakeup::Thread { .. });
+            if waiter.cell != address || (is_thread && thread_woken && !killed) {
+                index += 1;
+                continue;
+            }
+            thread_woken |= is_thread;
+            woken.push(waiters.remove(index).wakeup);
+        }
+        if waiters.iter().any(|w| w.cell == address) {
+            self.state.fetch_or(WAITING, Relaxed);
+        } else {
+            self.state.fetch_and(!WAITING, Relaxed);
+        }
+        let mut threads: Vec<Thread> = Vec::new();
+        let mut wakers: Vec<Waker> = Vec::new();
+        for wakeup in woken {
+            match wakeup {
+                Wakeup::Thread { signaled, thread } => {
+                    // The waiting thread does not return until `signaled` is set.
+                    unsafe { &*signaled }.store(true, Release);
+                    threads.push(thread);
+                }
+                Wakeup::Task { waker, .. } => wakers.extend(waker),
+            }
+        }
+        drop(waiters);
+        threads.iter().for_each(Thread::unpark);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// No thread waits for a Cell without the standard library or under the model checker.
+    #[cfg(any(not(feature = "std"), loom))]
+    fn wakeup(&self, _guard: &Guard) {}
+
+    /// Returns the address of the Cell that identifies its waiting threads.
+    #[cfg(all(feature = "std", not(loom)))]
+    fn address(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Returns the wait queue that the Cell is mapped to.
+    #[cfg(all(feature = "std", not(loom)))]
+    fn wait_queue(&self) -> &'static Mutex<Vec<Waiter>> {
+        wait_queue(self.address())
+    }
+
+    /// Returns log_2 of the max resizing factor.
     pub fn max_resizing_factor() -> usize {
         (SIZE.next_power_of_two().trailing_zeros() + 1) as usize
     }
 }
 
-impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Drop for Cell<K, V, SIZE, LOCK_FREE> {
+impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator> Drop
+    for Cell<K, V, SIZE, LOCK_FREE, A>
+{
     fn drop(&mut self) {
         // The Cell must have been killed.
         debug_assert!(self.killed());
     }
 }
 
-pub struct CellIterator<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> {
-    cell_ref: Option<&'g Cell<K, V, SIZE, LOCK_FREE>>,
-    current_array: Shared<'g, DataArray<K, V, SIZE>>,
+pub struct CellIterator<
+    'g,
+    K: Eq,
+    V,
+    const SIZE: usize,
+    const LOCK_FREE: bool,
+    A: ArrayAllocator = GlobalArrayAllocator,
+> {
+    cell_ref: Option<&'g Cell<K, V, SIZE, LOCK_FREE, A>>,
+    current_array: Shared<'g, DataArray<K, V, SIZE, A>>,
     current_index: usize,
+    /// The current slot has been refilled with an entry that has yet to be iterated over.
+    revisit: bool,
     guard_ref: &'g Guard,
 }
 
-impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool>
-    CellIterator<'g, K, V, SIZE, LOCK_FREE>
+impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator>
+    CellIterator<'g, K, V, SIZE, LOCK_FREE, A>
 {
     pub fn new(
-        cell: &'g Cell<K, V, SIZE, LOCK_FREE>,
+        cell: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
         guard: &'g Guard,
-    ) -> CellIterator<'g, K, V, SIZE, LOCK_FREE> {
+    ) -> CellIterator<'g, K, V, SIZE, LOCK_FREE, A> {
         CellIterator {
             cell_ref: Some(cell),
             current_array: Shared::null(),
             current_index: usize::MAX,
+            revisit: false,
             guard_ref: guard,
         }
     }
 
-    pub fn get(&self) -> Option<&'g (K, V)> {
+    #[cfg(feature = "std")]
+    pub fn get(&self) -> Option<(&'g K, &'g V)> {
+        if self.current_array.is_null() {
+            None
+        } else {
+            let data_array_ref = unsafe { self.current_array.deref() };
+            Some(unsafe { data_array_ref.entry(self.current_index) })
+        }
+    }
+
+    /// Returns the hash value stored along with the current entry.
+    #[cfg(feature = "full-hash")]
+    pub fn hash(&self) -> Option<u64> {
         if self.current_array.is_null() {
             None
         } else {
             let data_array_ref = unsafe { self.current_array.deref() };
-            let entry_ptr = data_array_ref.data[self.current_index].as_ptr();
-            return Some(unsafe { &(*entry_ptr) });
+            Some(data_array_ref.hash_array[self.current_index])
         }
     }
 }
 
-impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Iterator
-    for CellIterator<'g, K, V, SIZE, LOCK_FREE>
+impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator> Iterator
+    for CellIterator<'g, K, V, SIZE, LOCK_FREE, A>
 {
-    type Item = (&'g (K, V), u8);
+    type Item = ((&'g K, &'g V), PartialHash);
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(&cell_ref) = self.cell_ref.as_ref() {
+            if self.revisit {
+                self.revisit = false;
+                let array_ref = unsafe { self.current_array.deref() };
+                let hash = array_ref.partial_hash_array[self.current_index];
+                return Some((unsafe { array_ref.entry(self.current_index) }, hash));
+            }
             let read_order = if LOCK_FREE { Acquire } else { Relaxed };
             if self.current_array.is_null() {
                 // Starts scanning from the beginning.
@@ -251,15 +701,18 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Iterator
                 } else {
                     self.current_index + 1
                 };
-                for index in start_index..SIZE {
+                let mut occupied = array_ref.occupied_bitmap
+                    & u64::MAX.checked_shl(start_index as u32).unwrap_or(0);
+                while occupied != 0 {
+                    let index = occupied.trailing_zeros() as usize;
+                    occupied &= occupied - 1;
                     let hash = array_ref.partial_hash_array[index];
                     if (hash & OCCUPIED) != 0 && (hash & REMOVED) == 0 {
                         if LOCK_FREE {
-                            std::sync::atomic::fence(Acquire);
+                            fence(Acquire);
                         }
                         self.current_index = index;
-                        let entry_ptr = array_ref.data[index].as_ptr();
-                        return Some((unsafe { &(*entry_ptr) }, hash));
+                        return Some((unsafe { array_ref.entry(index) }, hash));
                     }
                 }
 
@@ -274,17 +727,30 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Iterator
     }
 }
 
-pub struct CellLocker<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> {
-    cell_ref: &'g Cell<K, V, SIZE, LOCK_FREE>,
+pub struct CellLocker<
+    'g,
+    K: Eq,
+    V,
+    const SIZE: usize,
+    const LOCK_FREE: bool,
+    A: ArrayAllocator = GlobalArrayAllocator,
+> {
+    cell_ref: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
     killed: bool,
+    /// An erase has emptied a DataArray that may be unlinked when the Cell is released.
+    compaction_pending: AtomicBool,
 }
 
-impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellLocker<'g, K, V, SIZE, LOCK_FREE> {
+impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator>
+    CellLocker<'g, K, V, SIZE, LOCK_FREE, A>
+{
     /// Locks the given Cell.
     pub fn lock(
-        cell: &'g Cell<K, V, SIZE, LOCK_FREE>,
+        cell: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
         guard: &'g Guard,
-    ) -> Option<CellLocker<'g, K, V, SIZE, LOCK_FREE>> {
+    ) -> Option<CellLocker<'g, K, V, SIZE, LOCK_FREE, A>> {
+        #[cfg(feature = "metrics")]
+        let mut contended = false;
         loop {
             for _ in 0..(SIZE * 4) {
                 if let Some(locker) = Self::try_lock(cell, guard) {
@@ -293,6 +759,12 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellLocker<'g, K, V
                     }
                     return Some(locker);
                 }
+                #[cfg(feature = "metrics")]
+                if !contended {
+                    contended = true;
+                    super::metrics::record_lock_contention();
+                }
+                spin_loop();
             }
             if let Some(locker) = cell.wait(|| Self::try_lock(cell, guard), guard) {
                 if locker.killed {
@@ -306,129 +778,187 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellLocker<'g, K, V
         }
     }
 
+    /// Tries to lock the Cell, or registers the task awaiting `async_wait` to be woken up when
+    /// the Cell is released.
+    ///
+    /// Returns `Ok(None)` if the Cell has been killed, and `Err(())` if `async_wait` needs to be
+    /// awaited before retrying.
+    #[cfg(feature = "std")]
+    pub fn try_lock_or_wait(
+        cell: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
+        async_wait: &mut AsyncWait,
+        guard: &'g Guard,
+    ) -> Result<Option<CellLocker<'g, K, V, SIZE, LOCK_FREE, A>>, ()> {
+        for _ in 0..(SIZE * 4) {
+            if let Some(locker) = Self::try_lock(cell, guard) {
+                return Ok((!locker.killed).then_some(locker));
+            }
+            spin_loop();
+        }
+        #[cfg(feature = "metrics")]
+        super::metrics::record_lock_contention();
+        if let Some(locker) = cell.register(async_wait, || Self::try_lock(cell, guard), guard) {
+            return Ok((!locker.killed).then_some(locker));
+        }
+        if cell.killed() {
+            return Ok(None);
+        }
+        Err(())
+    }
+
     /// Tries to lock the Cell.
     fn try_lock(
-        cell: &'g Cell<K, V, SIZE, LOCK_FREE>,
+        cell: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
         _guard: &'g Guard,
-    ) -> Option<CellLocker<'g, K, V, SIZE, LOCK_FREE>> {
+    ) -> Option<CellLocker<'g, K, V, SIZE, LOCK_FREE, A>> {
         let current = cell.state.load(Relaxed);
         if (current & LOCK_MASK) != 0 {
+            if (current & (LOCK | WRITER_PENDING)) == 0 {
+                // Keeps new readers from starving the writer.
+                cell.state.fetch_or(WRITER_PENDING, Relaxed);
+            }
             return None;
         }
         if cell
             .state
-            .compare_exchange(current, current | LOCK, Acquire, Relaxed)
+            .compare_exchange(
+                current,
+                (current & !WRITER_PENDING) | LOCK,
+                Acquire,
+                Relaxed,
+            )
             .is_ok()
         {
+            #[cfg(test)]
+            NUM_LOCK_ACQUISITIONS.with(|n| n.set(n.get() + 1));
             return Some(CellLocker {
                 cell_ref: cell,
                 killed: (current & KILLED) == KILLED,
+                compaction_pending: AtomicBool::new(false),
             });
         }
         None
     }
 
     /// Returns a reference to the Cell.
-    pub fn cell_ref(&self) -> &Cell<K, V, SIZE, LOCK_FREE> {
+    pub fn cell_ref(&self) -> &Cell<K, V, SIZE, LOCK_FREE, A> {
         self.cell_ref
     }
 
     /// Inserts a new key-value pair into the Cell.
+    ///
+    /// The hash value is stored along with the key-value pair if the `full-hash` feature is
+    /// enabled, and only the partial hash value derived from it is stored otherwise.
     pub fn insert(
         &'g self,
         key: K,
         value: V,
-        partial_hash: u8,
+        hash: u64,
         guard: &'g Guard,
-    ) -> (CellIterator<'g, K, V, SIZE, LOCK_FREE>, Option<(K, V)>) {
+    ) -> (CellIterator<'g, K, V, SIZE, LOCK_FREE, A>, Option<(K, V)>) {
         debug_assert!(!self.killed);
 
-        if self.cell_ref.num_entries == u32::MAX {
+        let num_entries = self.cell_ref.num_entries();
+        if num_entries == u32::MAX as usize {
             panic!("Entries overflow");
         }
 
-        let cell_mut_ref =
-            unsafe { &mut *(self.cell_ref as *const _ as *mut Cell<K, V, SIZE, LOCK_FREE>) };
         let mut data_array = self.cell_ref.data.load(Relaxed, guard);
         let data_array_head = data_array;
+        let partial_hash = partial_hash(hash);
         let preferred_index = partial_hash as usize % SIZE;
         let expected_hash = (partial_hash & (!REMOVED)) | OCCUPIED;
-        let mut free_data_array: Option<Shared<DataArray<K, V, SIZE>>> = None;
+        let mut free_data_array: Option<Shared<DataArray<K, V, SIZE, A>>> = None;
         let mut free_index = SIZE;
         while !data_array.is_null() {
             let data_array_ref = unsafe { data_array.deref_mut() };
-            for i in preferred_index..preferred_index + SIZE {
-                let index = i % SIZE;
-                let hash = data_array_ref.partial_hash_array[index];
-                if hash == expected_hash {
-                    let entry_ptr = data_array_ref.data[index].as_ptr();
-                    if unsafe { &(*entry_ptr) }.0 == key {
-                        return (
-                            CellIterator {
-                                cell_ref: Some(self.cell_ref),
-                                current_array: data_array,
-                                current_index: index,
-                                guard_ref: guard,
-                            },
-                            Some((key, value)),
-                        );
-                    }
-                } else if free_data_array.is_none() && hash == 0 {
-                    free_index = index;
+            let mut candidates = data_array_ref.match_partial_hash(expected_hash);
+            while candidates != 0 {
+                let index = candidates.trailing_zeros() as usize;
+                candidates &= candidates - 1;
+                #[cfg(feature = "full-hash")]
+                if data_array_ref.hash_array[index] != hash {
+                    continue;
+                }
+                if *unsafe { data_array_ref.key(index) } == key {
+                    return (
+                        CellIterator {
+                            cell_ref: Some(self.cell_ref),
+                            current_array: data_array,
+                            current_index: index,
+                            revisit: false,
+                            guard_ref: guard,
+                        },
+                        Some((key, value)),
+                    );
                 }
             }
-            if free_data_array.is_none() && free_index != SIZE {
-                free_data_array.replace(data_array);
+            if free_data_array.is_none() {
+                let free_slots = !data_array_ref.occupied_bitmap & DataArray::<K, V, SIZE>::FULL;
+                if free_slots != 0 {
+                    free_index = if free_slots & (1_u64 << preferred_index) != 0 {
+                        preferred_index
+                    } else {
+                        free_slots.trailing_zeros() as usize
+                    };
+                    free_data_array.replace(data_array);
+                }
             }
             data_array = data_array_ref.link.load(Relaxed, guard);
         }
 
         if let Some(mut free_data_array_shared) = free_data_array.take() {
             let data_array_ref = unsafe { free_data_array_shared.deref_mut() };
-            debug_assert_eq!(data_array_ref.partial_hash_array[free_index], 0u8);
-            unsafe {
-                data_array_ref.data[free_index]
-                    .as_mut_ptr()
-                    .write((key, value))
-            };
+            debug_assert_eq!(data_array_ref.partial_hash_array[free_index], 0);
+            unsafe { data_array_ref.write(free_index, key, value) };
+            #[cfg(feature = "full-hash")]
+            {
+                data_array_ref.hash_array[free_index] = hash;
+            }
             if LOCK_FREE {
                 // A release fence is required to make the contents fully visible to a reader having read the slot as occupied.
-                std::sync::atomic::fence(Release);
+                fence(Release);
             }
             data_array_ref.partial_hash_array[free_index] = expected_hash;
-            cell_mut_ref.num_entries += 1;
+            debug_assert!(data_array_ref.occupancy_consistent());
+            self.cell_ref.set_num_entries(num_entries + 1);
             return (
                 CellIterator {
                     cell_ref: Some(self.cell_ref),
                     current_array: free_data_array_shared,
                     current_index: free_index,
+                    revisit: false,
                     guard_ref: guard,
                 },
                 None,
             );
         } else {
             // Inserts a new DataArray at the head.
-            let mut new_data_array = Owned::new(DataArray::new());
-            unsafe {
-                new_data_array.data[preferred_index]
-                    .as_mut_ptr()
-                    .write((key, value))
-            };
+            let mut new_data_array_shared = DataArray::allocate();
+            let new_data_array = unsafe { new_data_array_shared.deref_mut() };
+            unsafe { new_data_array.write(preferred_index, key, value) };
+            #[cfg(feature = "full-hash")]
+            {
+                new_data_array.hash_array[preferred_index] = hash;
+            }
             if LOCK_FREE {
                 // A release fence is required to make the contents fully visible to a reader having read the slot as occupied.
-                std::sync::atomic::fence(Release);
+                fence(Release);
             }
             new_data_array.partial_hash_array[preferred_index] = expected_hash;
             // Relaxed is sufficient as it is unimportant to read the latest state of the partial hash value for readers.
             new_data_array.link.store(data_array_head, Relaxed);
             let write_order = if LOCK_FREE { Release } else { Relaxed };
-            self.cell_ref.data.swap(new_data_array, write_order, guard);
-            cell_mut_ref.num_entries += 1;
+            self.cell_ref
+                .data
+                .swap(new_data_array_shared, write_order, guard);
+            self.cell_ref.set_num_entries(num_entries + 1);
             return (
                 CellIterator {
                     cell_ref: Some(self.cell_ref),
                     current_array: self.cell_ref.data.load(Relaxed, guard),
                     current_index: preferred_index,
+                    revisit: false,
                     guard_ref: guard,
                 },
                 None,
@@ -437,7 +967,7 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellLocker<'g, K, V
     }
 
     /// Removes a new key-value pair being pointed by the given CellIterator.
-    pub fn erase(&self, iterator: &mut CellIterator<K, V, SIZE, LOCK_FREE>) -> Option<(K, V)> {
+    pub fn erase(&self, iterator: &mut CellIterator<K, V, SIZE, LOCK_FREE, A>) -> Option<(K, V)> {
         if self.killed {
             // The Cell has been killed.
             return None;
@@ -455,18 +985,93 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellLocker<'g, K, V
             return None;
         }
 
-        debug_assert!(self.cell_ref.num_entries > 0);
-        let cell_mut_ref =
-            unsafe { &mut *(self.cell_ref as *const _ as *mut Cell<K, V, SIZE, LOCK_FREE>) };
-        cell_mut_ref.num_entries -= 1;
-        let entry_ptr = data_array_ref.data[iterator.current_index].as_ptr();
+        let num_entries = self.cell_ref.num_entries();
+        debug_assert!(num_entries > 0);
+        self.cell_ref.set_num_entries(num_entries - 1);
         if LOCK_FREE {
             data_array_ref.partial_hash_array[iterator.current_index] |= REMOVED;
             None
         } else {
             data_array_ref.partial_hash_array[iterator.current_index] = 0;
-            let entry_mut_ptr = entry_ptr as *mut MaybeUninit<(K, V)>;
-            Some(unsafe { std::ptr::replace(entry_mut_ptr, MaybeUninit::uninit()).assume_init() })
+            let entry = unsafe { data_array_ref.take(iterator.current_index) };
+            if self.fill_vacancy(iterator) {
+                self.compaction_pending.store(true, Relaxed);
+            }
+            Some(entry)
+        }
+    }
+
+    /// Moves an entry from a DataArray that is not full into the vacant slot that the iterator
+    /// points to.
+    ///
+    /// All the DataArrays except at most one are kept full, so that the number of DataArrays to
+    /// probe is proportional to the number of entries in the Cell. The iterator revisits the slot
+    /// if the moved entry has yet to be iterated over. It returns `true` if a DataArray has been
+    /// emptied.
+    fn fill_vacancy(&self, iterator: &mut CellIterator<K, V, SIZE, LOCK_FREE, A>) -> bool {
+        debug_assert!(!LOCK_FREE);
+        let mut vacant_array = iterator.current_array;
+        let vacant_array_ref = unsafe { vacant_array.deref_mut() };
+        let vacant_index = iterator.current_index;
+        let mut vacant_array_passed = false;
+        let mut data_array = self.cell_ref.data.load(Relaxed, iterator.guard_ref);
+        while !data_array.is_null() {
+            if data_array == vacant_array {
+                // The vacant DataArray is only accessed through the existing reference.
+                vacant_array_passed = true;
+                data_array = vacant_array_ref.link.load(Relaxed, iterator.guard_ref);
+                continue;
+            }
+            let data_array_ref = unsafe { data_array.deref_mut() };
+            let occupied = data_array_ref.occupied_bitmap;
+            if occupied != 0 && occupied != DataArray::<K, V, SIZE, A>::FULL {
+                let index = (u64::BITS - 1 - occupied.leading_zeros()) as usize;
+                let (key, value) = unsafe { data_array_ref.take(index) };
+                unsafe { vacant_array_ref.write(vacant_index, key, value) };
+                #[cfg(feature = "full-hash")]
+                {
+                    vacant_array_ref.hash_array[vacant_index] = data_array_ref.hash_array[index];
+                }
+                vacant_array_ref.partial_hash_array[vacant_index] =
+                    data_array_ref.partial_hash_array[index];
+                data_array_ref.partial_hash_array[index] = 0;
+                debug_assert!(data_array_ref.occupancy_consistent());
+                debug_assert!(vacant_array_ref.occupancy_consistent());
+                iterator.revisit = vacant_array_passed;
+                return data_array_ref.occupied_bitmap == 0;
+            }
+            data_array = data_array_ref.link.load(Relaxed, iterator.guard_ref);
+        }
+        debug_assert!(vacant_array_ref.occupancy_consistent());
+        vacant_array_ref.occupied_bitmap == 0
+    }
+
+    /// Unlinks and drops empty DataArrays, except for the last remaining one.
+    ///
+    /// Their memory is reclaimed by the epoch-based garbage collector in the same manner as the
+    /// other detached DataArrays.
+    fn compact(&self) {
+        debug_assert!(!LOCK_FREE);
+        let guard = &crate::ebr::pin();
+        let mut prev_data_array: Shared<DataArray<K, V, SIZE, A>> = Shared::null();
+        let mut current_data_array = self.cell_ref.data.load(Relaxed, guard);
+        while !current_data_array.is_null() {
+            let current_data_array_ref = unsafe { current_data_array.deref() };
+            let next_data_array = current_data_array_ref.link.load(Relaxed, guard);
+            let empty = current_data_array_ref.occupied_bitmap == 0;
+            if empty && !(prev_data_array.is_null() && next_data_array.is_null()) {
+                if prev_data_array.is_null() {
+                    self.cell_ref.data.store(next_data_array, Relaxed);
+                } else {
+                    let prev_data_array_ref = unsafe { prev_data_array.deref() };
+                    prev_data_array_ref.link.store(next_data_array, Relaxed);
+                }
+                current_data_array_ref.link.store(Shared::null(), Relaxed);
+                DataArray::retire(current_data_array, guard);
+            } else {
+                prev_data_array = current_data_array;
+            }
+            current_data_array = next_data_array;
         }
     }
 
@@ -475,26 +1080,83 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellLocker<'g, K, V
         let data_array_shared = self.cell_ref.data.swap(Shared::null(), Relaxed, guard);
         if !data_array_shared.is_null() {
             if LOCK_FREE {
-                unsafe { guard.defer_destroy(data_array_shared) };
+                DataArray::defer_drop(data_array_shared, guard);
             } else {
-                drop(unsafe { data_array_shared.into_owned() });
+                DataArray::retire(data_array_shared, guard);
             }
         }
         self.killed = true;
 
-        let num_entries = self.cell_ref.num_entries;
-        let cell_mut_ref =
-            unsafe { &mut *(self.cell_ref as *const _ as *mut Cell<K, V, SIZE, LOCK_FREE>) };
-        cell_mut_ref.num_entries = 0;
-        num_entries as usize
+        let num_entries = self.cell_ref.num_entries();
+        self.cell_ref.set_num_entries(0);
+        num_entries
+    }
+
+    /// Detaches all the data from the Cell without killing it.
+    ///
+    /// It returns the detached data along with the number of entries in it, and the caller is
+    /// able to retire the data after unlocking the Cell.
+    #[cfg(feature = "std")]
+    pub fn take<'h>(&mut self, guard: &'h Guard) -> (Shared<'h, DataArray<K, V, SIZE, A>>, usize) {
+        debug_assert!(!LOCK_FREE);
+        let data_array_shared = self.cell_ref.data.swap(Shared::null(), Relaxed, guard);
+        let num_entries = self.cell_ref.num_entries();
+        self.cell_ref.set_num_entries(0);
+        (data_array_shared, num_entries)
     }
 }
 
-impl<'g, K: Clone + Eq, V: Clone, const SIZE: usize, const LOCK_FREE: bool>
-    CellLocker<'g, K, V, SIZE, LOCK_FREE>
+#[cfg(feature = "std")]
+impl<'g, K: Eq, V, const SIZE: usize, A: ArrayAllocator> CellLocker<'g, K, V, SIZE, false, A> {
+    /// Returns the key-value pair being pointed by the given CellIterator for modification.
+    ///
+    /// The value is only modified by the owner of the lock, and the CellIterator is borrowed
+    /// exclusively, therefore the value cannot be reached through them while the returned
+    /// reference is alive.
+    pub fn entry_mut<'l>(
+        &'l self,
+        iterator: &'l mut CellIterator<'_, K, V, SIZE, false, A>,
+    ) -> Option<(&'l K, &'l mut V)> {
+        let (key_ptr, value_ptr) = self.entry_ptr(iterator)?;
+        Some(unsafe { (&*key_ptr, &mut *value_ptr) })
+    }
+
+    /// Replaces the key-value pair being pointed by the given CellIterator, and returns the
+    /// replaced one.
+    ///
+    /// The new key must be equal to the replaced one.
+    pub fn replace(
+        &self,
+        iterator: &mut CellIterator<'_, K, V, SIZE, false, A>,
+        key: K,
+        value: V,
+    ) -> Option<(K, V)> {
+        let (key_ptr, value_ptr) = self.entry_ptr(iterator)?;
+        Some(unsafe { (key_ptr.replace(key), value_ptr.replace(value)) })
+    }
+
+    /// Returns pointers to the key and value being pointed by the given CellIterator.
+    fn entry_ptr(
+        &self,
+        iterator: &CellIterator<'_, K, V, SIZE, false, A>,
+    ) -> Option<(*mut K, *mut V)> {
+        if iterator.current_array.is_null() || iterator.current_index == usize::MAX {
+            // The iterator is fused.
+            return None;
+        }
+        if let Some(cell_ref) = iterator.cell_ref {
+            debug_assert!(core::ptr::eq(cell_ref, self.cell_ref));
+        }
+        let data_array_ref = unsafe { iterator.current_array.deref() };
+        Some(data_array_ref.entry_ptr(iterator.current_index))
+    }
+}
+
+impl<'g, K: Clone + Eq, V: Clone, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator>
+    CellLocker<'g, K, V, SIZE, LOCK_FREE, A>
 {
     /// Removes a new key-value pair associated with the given key with the instances kept intact.
-    pub fn mark_removed<Q>(&self, key: &Q, partial_hash: u8, guard: &Guard) -> bool
+    pub fn mark_removed<Q>(&self, key: &Q, hash: u64, guard: &Guard) -> bool
     where
         K: Borrow<Q>,
         Q: Eq + ?Sized,
@@ -507,19 +1169,21 @@ impl<'g, K: Clone + Eq, V: Clone, const SIZE: usize, const LOCK_FREE: bool>
         // Starts Searching the entry at the preferred index first.
         let mut data_array = self.cell_ref.data.load(Relaxed, guard);
         let mut removed = false;
-        let preferred_index = partial_hash as usize % SIZE;
-        let expected_hash = (partial_hash & (!REMOVED)) | OCCUPIED;
+        let expected_hash = (partial_hash(hash) & (!REMOVED)) | OCCUPIED;
         while !data_array.is_null() {
             let data_array_ref = unsafe { data_array.deref_mut() };
-            for i in preferred_index..preferred_index + SIZE {
-                let index = i % SIZE;
-                if data_array_ref.partial_hash_array[index] == expected_hash {
-                    let entry_ptr = data_array_ref.data[index].as_ptr();
-                    if *unsafe { &(*entry_ptr) }.0.borrow() == *key {
-                        data_array_ref.partial_hash_array[index] |= REMOVED;
-                        removed = true;
-                        break;
-                    }
+            let mut candidates = data_array_ref.match_partial_hash(expected_hash);
+            while candidates != 0 {
+                let index = candidates.trailing_zeros() as usize;
+                candidates &= candidates - 1;
+                #[cfg(feature = "full-hash")]
+                if data_array_ref.hash_array[index] != hash {
+                    continue;
+                }
+                if *unsafe { data_array_ref.key(index) }.borrow() == *key {
+                    data_array_ref.partial_hash_array[index] |= REMOVED;
+                    removed = true;
+                    break;
                 }
             }
             if removed {
@@ -529,25 +1193,35 @@ impl<'g, K: Clone + Eq, V: Clone, const SIZE: usize, const LOCK_FREE: bool>
         }
 
         if removed {
-            let cell_mut_ref =
-                unsafe { &mut *(self.cell_ref as *const _ as *mut Cell<K, V, SIZE, LOCK_FREE>) };
-            cell_mut_ref.num_entries -= 1;
-            self.optimize(data_array, self.cell_ref.num_entries, guard);
+            let num_entries = self.cell_ref.num_entries() - 1;
+            self.cell_ref.set_num_entries(num_entries);
+            self.optimize(data_array, num_entries, guard);
         }
         removed
     }
 
     /// Optimizes the linked list.
     ///
-    /// Two strategies.
+    /// Three strategies.
     ///  1. Clears the entire Cell if there is no valid entry.
     ///  2. Coalesces if the given data array is non-empty and the linked list is sparse.
     ///  3. Unlinks the given data array if the data array is empty.
-    fn optimize(&self, data_array: Shared<DataArray<K, V, SIZE>>, num_entries: u32, guard: &Guard) {
+    ///
+    /// Removed entries may still be read, therefore the valid entries are copied into new
+    /// DataArrays instead of being moved, and the old DataArrays are dropped once no reader can
+    /// hold a reference to them. The linked list is regarded as sparse if it consists of more
+    /// than twice as many DataArrays as the valid entries require, or if the entries fit into a
+    /// quarter of a DataArray, so that the copying cost is amortized over removals.
+    fn optimize(
+        &self,
+        data_array: Shared<DataArray<K, V, SIZE, A>>,
+        num_entries: usize,
+        guard: &Guard,
+    ) {
         if num_entries == 0 {
             // Clears the entire Cell.
             let deprecated_data_array = self.cell_ref.data.swap(Shared::null(), Relaxed, guard);
-            unsafe { guard.defer_destroy(deprecated_data_array) };
+            DataArray::defer_drop(deprecated_data_array, guard);
             return;
         }
 
@@ -555,46 +1229,70 @@ impl<'g, K: Clone + Eq, V: Clone, const SIZE: usize, const LOCK_FREE: bool>
             if (hash & REMOVED) == 0 {
                 // The given data array is still valid, therefore it tries to coalesce the linked list.
                 let head_data_array = self.cell_ref.data.load(Relaxed, guard);
-                let head_data_array_link_shared =
-                    unsafe { head_data_array.deref() }.link.load(Relaxed, guard);
-                if !head_data_array_link_shared.is_null() && (num_entries as usize) < SIZE / 4 {
-                    // Replaces the head with a new DataArray.
-                    let mut new_data_array = Owned::new(DataArray::new());
-                    let mut new_array_index = 0;
+                let num_data_arrays = self.cell_ref.num_data_arrays(guard);
+                let num_required = num_entries.div_ceil(SIZE);
+                if num_data_arrays > 1
+                    && (num_entries < SIZE / 4 || num_data_arrays > num_required * 2)
+                {
+                    // Replaces the linked list with new DataArrays.
+                    let mut new_data_arrays = Vec::with_capacity(num_required);
+                    let mut new_array_index = SIZE;
+                    let mut num_copied = 0;
                     let mut current_data_array = head_data_array;
                     while !current_data_array.is_null() {
                         let current_data_array_ref = unsafe { current_data_array.deref_mut() };
-                        for (index, hash) in
-                            current_data_array_ref.partial_hash_array.iter().enumerate()
-                        {
-                            if (hash & (REMOVED | OCCUPIED)) == OCCUPIED {
-                                let entry_ptr = current_data_array_ref.data[index].as_ptr();
-                                let entry_ref = unsafe { &(*entry_ptr) };
+                        let mut occupied = current_data_array_ref.occupied_bitmap;
+                        while occupied != 0 {
+                            let index = occupied.trailing_zeros() as usize;
+                            occupied &= occupied - 1;
+                            let hash = &current_data_array_ref.partial_hash_array[index];
+                            if (hash & REMOVED) == 0 {
+                                if new_array_index == SIZE {
+                                    new_data_arrays.push(DataArray::allocate());
+                                    new_array_index = 0;
+                                }
+                                let new_data_array =
+                                    unsafe { new_data_arrays.last_mut().unwrap().deref_mut() };
+                                let (key, value) = unsafe { current_data_array_ref.entry(index) };
                                 unsafe {
-                                    new_data_array.data[new_array_index]
-                                        .as_mut_ptr()
-                                        .write(entry_ref.clone())
+                                    new_data_array.write(
+                                        new_array_index,
+                                        key.clone(),
+                                        value.clone(),
+                                    )
                                 };
+                                #[cfg(feature = "full-hash")]
+                                {
+                                    new_data_array.hash_array[new_array_index] =
+                                        current_data_array_ref.hash_array[index];
+                                }
                                 new_data_array.partial_hash_array[new_array_index] = *hash;
                                 new_array_index += 1;
+                                num_copied += 1;
                             }
                         }
-                        if new_array_index == num_entries as usize {
+                        if num_copied == num_entries {
                             break;
                         }
                         current_data_array = current_data_array_ref.link.load(Relaxed, guard);
                     }
-                    let old_array_link = self.cell_ref.data.swap(new_data_array, Release, guard);
-                    unsafe {
-                        guard.defer_destroy(old_array_link);
+                    debug_assert_eq!(new_data_arrays.len(), num_required);
+                    let mut new_head: Shared<DataArray<K, V, SIZE, A>> = Shared::null();
+                    while let Some(new_data_array_shared) = new_data_arrays.pop() {
+                        let new_data_array_ref = unsafe { new_data_array_shared.deref() };
+                        debug_assert!(new_data_array_ref.occupancy_consistent());
+                        new_data_array_ref.link.store(new_head, Relaxed);
+                        new_head = new_data_array_shared;
                     }
+                    let old_array_link = self.cell_ref.data.swap(new_head, Release, guard);
+                    DataArray::defer_drop(old_array_link, guard);
                 }
                 return;
             }
         }
 
         // Unlinks the given data array from the linked list.
-        let mut prev_data_array: Shared<DataArray<K, V, SIZE>> = Shared::null();
+        let mut prev_data_array: Shared<DataArray<K, V, SIZE, A>> = Shared::null();
         let mut current_data_array = self.cell_ref.data.load(Relaxed, guard);
         while !current_data_array.is_null() {
             let current_data_array_ref = unsafe { current_data_array.deref() };
@@ -608,7 +1306,7 @@ impl<'g, K: Clone + Eq, V: Clone, const SIZE: usize, const LOCK_FREE: bool>
                     prev_data_array_ref.link.store(next_data_array, Relaxed);
                 }
                 current_data_array_ref.link.store(Shared::null(), Relaxed);
-                unsafe { guard.defer_destroy(current_data_array) };
+                DataArray::defer_drop(current_data_array, guard);
                 break;
             } else {
                 prev_data_array = current_data_array;
@@ -618,17 +1316,20 @@ impl<'g, K: Clone + Eq, V: Clone, const SIZE: usize, const LOCK_FREE: bool>
     }
 }
 
-impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Drop
-    for CellLocker<'g, K, V, SIZE, LOCK_FREE>
+impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator> Drop
+    for CellLocker<'g, K, V, SIZE, LOCK_FREE, A>
 {
     fn drop(&mut self) {
+        if !LOCK_FREE && !self.killed && self.compaction_pending.load(Relaxed) {
+            self.compact();
+        }
         let mut guard: Option<Guard> = None;
         let mut current = self.cell_ref.state.load(Relaxed);
         loop {
             let wakeup = if (current & WAITING) == WAITING {
                 // In order to prevent the Cell from being dropped while waking up other threads, pins the thread.
                 if guard.is_none() {
-                    guard.replace(crossbeam_epoch::pin());
+                    guard.replace(crate::ebr::pin());
                 }
                 true
             } else {
@@ -656,17 +1357,30 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Drop
     }
 }
 
-pub struct CellReader<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> {
-    cell_ref: &'g Cell<K, V, SIZE, LOCK_FREE>,
+#[cfg(feature = "std")]
+pub struct CellReader<
+    'g,
+    K: Eq,
+    V,
+    const SIZE: usize,
+    const LOCK_FREE: bool,
+    A: ArrayAllocator = GlobalArrayAllocator,
+> {
+    cell_ref: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
     killed: bool,
 }
 
-impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellReader<'g, K, V, SIZE, LOCK_FREE> {
+#[cfg(feature = "std")]
+impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator>
+    CellReader<'g, K, V, SIZE, LOCK_FREE, A>
+{
     /// Locks the given Cell.
     pub fn lock(
-        cell: &'g Cell<K, V, SIZE, LOCK_FREE>,
+        cell: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
         guard: &'g Guard,
-    ) -> Option<CellReader<'g, K, V, SIZE, LOCK_FREE>> {
+    ) -> Option<CellReader<'g, K, V, SIZE, LOCK_FREE, A>> {
+        #[cfg(feature = "metrics")]
+        let mut contended = false;
         loop {
             for _ in 0..(SIZE * 4) {
                 if let Some(reader) = Self::try_lock(cell, guard) {
@@ -675,6 +1389,12 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellReader<'g, K, V
                     }
                     return Some(reader);
                 }
+                #[cfg(feature = "metrics")]
+                if !contended {
+                    contended = true;
+                    super::metrics::record_lock_contention();
+                }
+                spin_loop();
             }
             if let Some(reader) = cell.wait(|| Self::try_lock(cell, guard), guard) {
                 if reader.killed {
@@ -685,16 +1405,58 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellReader<'g, K, V
             if cell.killed() {
                 return None;
             }
+            if let Some(reader) = Self::try_lock(cell, guard) {
+                if reader.killed {
+                    return None;
+                }
+                if (cell.state.load(Relaxed) & WAITING) == WAITING {
+                    // Only a single thread is woken up at a time, therefore the next one is woken
+                    // up as it may also be a reader.
+                    cell.wakeup(guard);
+                }
+                return Some(reader);
+            }
         }
     }
 
+    /// Tries to lock the Cell, or registers the task awaiting `async_wait` to be woken up when
+    /// the Cell is released.
+    ///
+    /// Returns `Ok(None)` if the Cell has been killed, and `Err(())` if `async_wait` needs to be
+    /// awaited before retrying.
+    #[cfg(feature = "std")]
+    pub fn try_lock_or_wait(
+        cell: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
+        async_wait: &mut AsyncWait,
+        guard: &'g Guard,
+    ) -> Result<Option<CellReader<'g, K, V, SIZE, LOCK_FREE, A>>, ()> {
+        for _ in 0..(SIZE * 4) {
+            if let Some(reader) = Self::try_lock(cell, guard) {
+                return Ok((!reader.killed).then_some(reader));
+            }
+            spin_loop();
+        }
+        #[cfg(feature = "metrics")]
+        super::metrics::record_lock_contention();
+        if let Some(reader) = cell.register(async_wait, || Self::try_lock(cell, guard), guard) {
+            return Ok((!reader.killed).then_some(reader));
+        }
+        if cell.killed() {
+            return Ok(None);
+        }
+        Err(())
+    }
+
     /// Tries to lock the Cell.
     fn try_lock(
-        cell: &'g Cell<K, V, SIZE, LOCK_FREE>,
+        cell: &'g Cell<K, V, SIZE, LOCK_FREE, A>,
         _guard: &'g Guard,
-    ) -> Option<CellReader<'g, K, V, SIZE, LOCK_FREE>> {
+    ) -> Option<CellReader<'g, K, V, SIZE, LOCK_FREE, A>> {
         let current = cell.state.load(Relaxed);
-        if (current & LOCK_MASK) >= SLOCK_MAX {
+        if (current & LOCK_MASK) >= SLOCK_MAX
+            || (current & (KILLED | WRITER_PENDING)) == WRITER_PENDING
+        {
+            // A writer waiting for the readers to leave takes precedence over new readers.
             return None;
         }
         if cell
@@ -702,6 +1464,8 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellReader<'g, K, V
             .compare_exchange(current, current + 1, Acquire, Relaxed)
             .is_ok()
         {
+            #[cfg(test)]
+            NUM_LOCK_ACQUISITIONS.with(|n| n.set(n.get() + 1));
             return Some(CellReader {
                 cell_ref: cell,
                 killed: (current & KILLED) == KILLED,
@@ -711,13 +1475,14 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellReader<'g, K, V
     }
 
     /// Returns a reference to the Cell.
-    pub fn cell_ref(&self) -> &Cell<K, V, SIZE, LOCK_FREE> {
+    pub fn cell_ref(&self) -> &Cell<K, V, SIZE, LOCK_FREE, A> {
         self.cell_ref
     }
 }
 
-impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Drop
-    for CellReader<'g, K, V, SIZE, LOCK_FREE>
+#[cfg(feature = "std")]
+impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator> Drop
+    for CellReader<'g, K, V, SIZE, LOCK_FREE, A>
 {
     fn drop(&mut self) {
         let mut guard: Option<Guard> = None;
@@ -726,7 +1491,7 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Drop
             let wakeup = if (current & WAITING) == WAITING {
                 // In order to prevent the Cell from being dropped while waking up other threads, pins the thread.
                 if guard.is_none() {
-                    guard.replace(crossbeam_epoch::pin());
+                    guard.replace(crate::ebr::pin());
                 }
                 true
             } else {
@@ -750,77 +1515,1065 @@ impl<'g, K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Drop
     }
 }
 
-pub struct DataArray<K: Eq, V, const SIZE: usize> {
-    /// The lower two-bit of a partial hash value represents the state of the corresponding entry.
-    partial_hash_array: [u8; SIZE],
-    data: [MaybeUninit<(K, V)>; SIZE],
-    link: Atomic<DataArray<K, V, SIZE>>,
+pub struct DataArray<K: Eq, V, const SIZE: usize, A: ArrayAllocator = GlobalArrayAllocator> {
+    /// The upper two-bit of a partial hash value represents the state of the corresponding entry.
+    partial_hash_array: [PartialHash; SIZE],
+    /// The `full-hash` feature stores the hash value of each key, so that relocating an entry to
+    /// a new array does not have to hash the key again, and a lookup only compares the keys of
+    /// the entries of which the hash value is equal.
+    #[cfg(feature = "full-hash")]
+    hash_array: [u64; SIZE],
+    /// Keys are stored apart from values, so that probing a DataArray only touches the partial
+    /// hash values and keys; a set of keys costs no more than the keys and the metadata.
+    ///
+    /// The owner of the lock on the Cell may modify an entry in place while the DataArray is
+    /// shared, therefore each slot is wrapped in an UnsafeCell.
+    key_array: [UnsafeCell<MaybeUninit<K>>; SIZE],
+    value_array: [UnsafeCell<MaybeUninit<V>>; SIZE],
+    /// A bit is set if the slot at the position holds an entry, including an entry that has been
+    /// marked removed but not dropped.
+    occupied_bitmap: u64,
+    link: Atomic<DataArray<K, V, SIZE, A>>,
+    /// DataArrays are allocated and deallocated through `A`.
+    _allocator: PhantomData<fn() -> A>,
 }
 
-impl<K: Eq, V, const SIZE: usize> DataArray<K, V, SIZE> {
-    fn new() -> DataArray<K, V, SIZE> {
+impl<K: Eq, V, const SIZE: usize, A: ArrayAllocator> DataArray<K, V, SIZE, A> {
+    fn new() -> DataArray<K, V, SIZE, A> {
+        debug_assert!(SIZE <= 64, "a DataArray holds at most 64 entries");
         DataArray {
             partial_hash_array: [0; SIZE],
-            data: unsafe { MaybeUninit::uninit().assume_init() },
+            #[cfg(feature = "full-hash")]
+            hash_array: [0; SIZE],
+            key_array: unsafe { MaybeUninit::uninit().assume_init() },
+            value_array: unsafe { MaybeUninit::uninit().assume_init() },
+            occupied_bitmap: 0,
             link: Atomic::null(),
+            _allocator: PhantomData,
+        }
+    }
+
+    /// Allocates a new DataArray through the allocator.
+    ///
+    /// It aborts if memory allocation fails.
+    fn allocate<'g>() -> Shared<'g, DataArray<K, V, SIZE, A>> {
+        let layout = Layout::new::<Self>();
+        let ptr = A::allocate(layout).cast::<Self>();
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+        unsafe { ptr.write(Self::new()) };
+        Shared::from(ptr as *const Self)
+    }
+
+    /// Drops the given DataArray and the linked DataArrays, and returns the memory to the
+    /// allocator.
+    ///
+    /// # Safety
+    ///
+    /// The DataArray must have been allocated by [`DataArray::allocate`], and must be unreachable.
+    unsafe fn deallocate(data_array: Shared<DataArray<K, V, SIZE, A>>) {
+        let ptr = data_array.as_raw() as *mut Self;
+        core::ptr::drop_in_place(ptr);
+        A::deallocate(ptr.cast(), Layout::new::<Self>());
+    }
+
+    /// The occupancy bitmap of a full DataArray.
+    const FULL: u64 = u64::MAX >> (64 - SIZE);
+
+    /// Checks that the occupancy bitmap agrees with the partial hash values.
+    fn occupancy_consistent(&self) -> bool {
+        let expected = self
+            .partial_hash_array
+            .iter()
+            .enumerate()
+            .filter(|(_, hash)| (**hash & OCCUPIED) == OCCUPIED)
+            .fold(0_u64, |bitmap, (index, _)| bitmap | (1_u64 << index));
+        self.occupied_bitmap == expected
+    }
+
+    /// Returns a reference to the key at the given position.
+    ///
+    /// # Safety
+    ///
+    /// The slot must be occupied.
+    unsafe fn key(&self, index: usize) -> &K {
+        &*self.key_array[index].get().cast::<K>()
+    }
+
+    /// Returns references to the key and value at the given position.
+    ///
+    /// # Safety
+    ///
+    /// The slot must be occupied.
+    unsafe fn entry(&self, index: usize) -> (&K, &V) {
+        (
+            &*self.key_array[index].get().cast::<K>(),
+            &*self.value_array[index].get().cast::<V>(),
+        )
+    }
+
+    /// Returns pointers to the key and value at the given position through which they can be
+    /// modified in place.
+    #[cfg(feature = "std")]
+    fn entry_ptr(&self, index: usize) -> (*mut K, *mut V) {
+        (
+            self.key_array[index].get().cast::<K>(),
+            self.value_array[index].get().cast::<V>(),
+        )
+    }
+
+    /// Writes the key and value into the given position.
+    ///
+    /// # Safety
+    ///
+    /// The slot must be vacant.
+    unsafe fn write(&mut self, index: usize, key: K, value: V) {
+        debug_assert_eq!(self.occupied_bitmap & (1_u64 << index), 0);
+        self.key_array[index].get_mut().as_mut_ptr().write(key);
+        self.value_array[index].get_mut().as_mut_ptr().write(value);
+        self.occupied_bitmap |= 1_u64 << index;
+    }
+
+    /// Moves the key and value out of the given position.
+    ///
+    /// # Safety
+    ///
+    /// The slot must be occupied, and it must be marked vacant afterwards.
+    unsafe fn take(&mut self, index: usize) -> (K, V) {
+        debug_assert_ne!(self.occupied_bitmap & (1_u64 << index), 0);
+        self.occupied_bitmap &= !(1_u64 << index);
+        (
+            self.key_array[index].get_mut().as_ptr().read(),
+            self.value_array[index].get_mut().as_ptr().read(),
+        )
+    }
+
+    /// Returns a bitmap of the slots of which the partial hash value is equal to the given one.
+    ///
+    /// The partial hash values are compared 16-at-a-time with SSE2 if available, and
+    /// 8-at-a-time in a word otherwise.
+    #[cfg(not(feature = "wide-partial-hash"))]
+    fn match_partial_hash(&self, partial_hash: PartialHash) -> u64 {
+        let mut bitmap = 0_u64;
+        let mut offset = 0;
+        #[cfg(all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            target_feature = "sse2"
+        ))]
+        while offset + 16 <= SIZE {
+            bitmap |=
+                u64::from(match_16(&self.partial_hash_array[offset..], partial_hash)) << offset;
+            offset += 16;
+        }
+        while offset + 8 <= SIZE {
+            bitmap |=
+                u64::from(match_8(&self.partial_hash_array[offset..], partial_hash)) << offset;
+            offset += 8;
+        }
+        for (index, hash) in self.partial_hash_array[offset..].iter().enumerate() {
+            if *hash == partial_hash {
+                bitmap |= 1_u64 << (offset + index);
+            }
+        }
+        bitmap
+    }
+
+    /// Returns a bitmap of the slots of which the partial hash value is equal to the given one.
+    ///
+    /// The partial hash values are compared 8-at-a-time with SSE2 if available, and
+    /// 4-at-a-time in a word otherwise.
+    #[cfg(feature = "wide-partial-hash")]
+    fn match_partial_hash(&self, partial_hash: PartialHash) -> u64 {
+        let mut bitmap = 0_u64;
+        let mut offset = 0;
+        #[cfg(all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            target_feature = "sse2"
+        ))]
+        while offset + 8 <= SIZE {
+            bitmap |= u64::from(match_wide_8(
+                &self.partial_hash_array[offset..],
+                partial_hash,
+            )) << offset;
+            offset += 8;
+        }
+        while offset + 4 <= SIZE {
+            bitmap |= u64::from(match_wide_4(
+                &self.partial_hash_array[offset..],
+                partial_hash,
+            )) << offset;
+            offset += 4;
+        }
+        for (index, hash) in self.partial_hash_array[offset..].iter().enumerate() {
+            if *hash == partial_hash {
+                bitmap |= 1_u64 << (offset + index);
+            }
+        }
+        bitmap
+    }
+
+    /// Invokes the given closure on each entry in the DataArray and the linked DataArrays.
+    #[cfg(feature = "std")]
+    pub fn for_each<F: FnMut(&K, &V)>(&self, mut f: F) {
+        let guard = unsafe { crate::ebr::unprotected() };
+        let mut current = Some(self);
+        while let Some(data_array_ref) = current {
+            let next = data_array_ref.link.load(Relaxed, guard);
+            prefetch(next.as_raw());
+            let mut occupied = data_array_ref.occupied_bitmap;
+            while occupied != 0 {
+                let index = occupied.trailing_zeros() as usize;
+                occupied &= occupied - 1;
+                let (key, value) = unsafe { data_array_ref.entry(index) };
+                f(key, value);
+            }
+            current = unsafe { next.as_ref() };
         }
     }
 }
 
-impl<K: Eq, V, const SIZE: usize> Drop for DataArray<K, V, SIZE> {
-    fn drop(&mut self) {
-        for (index, hash) in self.partial_hash_array.iter().enumerate() {
-            if (hash & OCCUPIED) == OCCUPIED {
-                let entry_mut_ptr = self.data[index].as_mut_ptr();
-                unsafe { std::ptr::drop_in_place(entry_mut_ptr) };
+impl<K: Eq, V, const SIZE: usize, A: ArrayAllocator> DataArray<K, V, SIZE, A> {
+    /// Drops the entries in the given DataArray and the linked DataArrays, and defers
+    /// deallocating them until no reader that does not lock the Cell may be reading them.
+    pub fn retire(data_array: Shared<DataArray<K, V, SIZE, A>>, guard: &Guard) {
+        let mut current = data_array;
+        while !current.is_null() {
+            // The DataArrays have been detached, and no other thread modifies them.
+            let data_array_mut =
+                unsafe { &mut *(current.as_raw() as *mut DataArray<K, V, SIZE, A>) };
+            data_array_mut.drop_entries();
+            current = data_array_mut.link.load(Relaxed, guard);
+        }
+        Self::defer_drop(data_array, guard);
+    }
+
+    /// Defers dropping the given detached DataArray and the linked DataArrays until no reader
+    /// that does not lock the Cell may be reading them.
+    ///
+    /// The entries left in the DataArrays are dropped along with them.
+    pub fn defer_drop(data_array: Shared<DataArray<K, V, SIZE, A>>, guard: &Guard) {
+        let mut num_data_arrays = 0;
+        let mut current = data_array;
+        while let Some(data_array_ref) = unsafe { current.as_ref() } {
+            num_data_arrays += 1;
+            current = data_array_ref.link.load(Relaxed, guard);
+        }
+        if num_data_arrays != 0 {
+            let num_bytes = num_data_arrays * core::mem::size_of::<Self>();
+            let detached = Detached {
+                ptr: data_array.as_raw() as *mut u8,
+                deallocate: Self::deallocate_detached,
+            };
+            if !RetireBatch::push(detached, num_bytes) {
+                unsafe { defer_accounted(num_bytes, guard, move || Self::deallocate(data_array)) };
             }
         }
+    }
+
+    /// Deallocates a DataArray added to a [`RetireBatch`].
+    unsafe fn deallocate_detached(ptr: *mut u8) {
+        Self::deallocate(Shared::from(ptr as *const Self));
+    }
+
+    /// Drops all the entries in the DataArray.
+    fn drop_entries(&mut self) {
+        debug_assert!(self.occupancy_consistent());
+        while self.occupied_bitmap != 0 {
+            let index = self.occupied_bitmap.trailing_zeros() as usize;
+            self.occupied_bitmap &= self.occupied_bitmap - 1;
+            self.partial_hash_array[index] = 0;
+            unsafe {
+                core::ptr::drop_in_place(self.key_array[index].get_mut().as_mut_ptr());
+                core::ptr::drop_in_place(self.value_array[index].get_mut().as_mut_ptr());
+            }
+        }
+    }
+}
+
+impl<K: Eq, V, const SIZE: usize, A: ArrayAllocator> Drop for DataArray<K, V, SIZE, A> {
+    fn drop(&mut self) {
+        self.drop_entries();
         // It has become unreachable, so has its child.
-        let guard = unsafe { crossbeam_epoch::unprotected() };
+        let guard = unsafe { crate::ebr::unprotected() };
         let link_shared = self.link.load(Relaxed, guard);
         if !link_shared.is_null() {
-            drop(unsafe { link_shared.into_owned() });
+            unsafe { Self::deallocate(link_shared) };
         }
     }
 }
 
-struct WaitQueueEntry {
-    mutex: Mutex<bool>,
-    condvar: Condvar,
-    next: Atomic<WaitQueueEntry>,
+// The UnsafeCell slots are only modified by the owner of the lock on the Cell.
+unsafe impl<K: Eq + Send, V: Send, const SIZE: usize, A: ArrayAllocator> Send
+    for DataArray<K, V, SIZE, A>
+{
+}
+unsafe impl<K: Eq + Sync, V: Sync, const SIZE: usize, A: ArrayAllocator> Sync
+    for DataArray<K, V, SIZE, A>
+{
+}
+
+/// Returns a bitmap of the first eight bytes that are equal to the given byte.
+#[cfg(not(feature = "wide-partial-hash"))]
+fn match_8(bytes: &[u8], byte: u8) -> u8 {
+    const LSB: u64 = 0x0101_0101_0101_0101;
+    const MSB: u64 = 0x8080_8080_8080_8080;
+    let mut word = [0_u8; 8];
+    word.copy_from_slice(&bytes[..8]);
+    let xor = u64::from_le_bytes(word) ^ (LSB * u64::from(byte));
+    // The most significant bit of each byte is set if the byte is non-zero, and no carry crosses
+    // byte boundaries.
+    let non_zero = ((xor & !MSB).wrapping_add(!MSB)) | xor;
+    let matched = !non_zero & MSB;
+    // Gathers the most significant bits of the bytes into the most significant byte.
+    ((matched >> 7).wrapping_mul(0x0102_0408_1020_4080) >> 56) as u8
+}
+
+/// Returns a bitmap of the first sixteen bytes that are equal to the given byte.
+#[cfg(all(
+    not(feature = "wide-partial-hash"),
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+fn match_16(bytes: &[u8], byte: u8) -> u16 {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::{_mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8};
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::{_mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8};
+    assert!(bytes.len() >= 16);
+    unsafe {
+        let loaded = _mm_loadu_si128(bytes.as_ptr().cast());
+        let matched = _mm_cmpeq_epi8(loaded, _mm_set1_epi8(byte as i8));
+        _mm_movemask_epi8(matched) as u16
+    }
+}
+
+/// Returns a bitmap of the first four 16-bit values that are equal to the given one.
+#[cfg(feature = "wide-partial-hash")]
+fn match_wide_4(values: &[u16], value: u16) -> u8 {
+    const LSB: u64 = 0x0001_0001_0001_0001;
+    const MSB: u64 = 0x8000_8000_8000_8000;
+    let word = values[..4]
+        .iter()
+        .rev()
+        .fold(0_u64, |word, v| (word << 16) | u64::from(*v));
+    let xor = word ^ (LSB * u64::from(value));
+    // The most significant bit of each value is set if the value is non-zero, and no carry
+    // crosses value boundaries.
+    let non_zero = ((xor & !MSB).wrapping_add(!MSB)) | xor;
+    let matched = !non_zero & MSB;
+    // Gathers the most significant bits of the values into the most significant byte.
+    ((matched >> 15).wrapping_mul(0x1000_2000_4000_8000) >> 60) as u8
+}
+
+/// Returns a bitmap of the first eight 16-bit values that are equal to the given one.
+#[cfg(all(
+    feature = "wide-partial-hash",
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+fn match_wide_8(values: &[u16], value: u16) -> u8 {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::{
+        _mm_cmpeq_epi16, _mm_loadu_si128, _mm_movemask_epi8, _mm_packs_epi16, _mm_set1_epi16,
+        _mm_setzero_si128,
+    };
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::{
+        _mm_cmpeq_epi16, _mm_loadu_si128, _mm_movemask_epi8, _mm_packs_epi16, _mm_set1_epi16,
+        _mm_setzero_si128,
+    };
+    assert!(values.len() >= 8);
+    unsafe {
+        let loaded = _mm_loadu_si128(values.as_ptr().cast());
+        let matched = _mm_cmpeq_epi16(loaded, _mm_set1_epi16(value as i16));
+        // Each matched 16-bit lane is narrowed to a byte of all ones.
+        _mm_movemask_epi8(_mm_packs_epi16(matched, _mm_setzero_si128())) as u8
+    }
+}
+
+/// Waiter is a thread or a task waiting for a Cell to be released.
+#[cfg(all(feature = "std", not(loom)))]
+struct Waiter {
+    /// The address of the Cell.
+    cell: usize,
+    wakeup: Wakeup,
+}
+
+// `signaled` is only accessed while the wait queue is locked, and the waiting thread does not
+// return until it is removed from the wait queue.
+#[cfg(all(feature = "std", not(loom)))]
+unsafe impl Send for Waiter {}
+
+/// Wakeup tells how to wake up a Waiter.
+#[cfg(all(feature = "std", not(loom)))]
+enum Wakeup {
+    /// A parked thread; the flag is set when the thread is allowed to retry.
+    Thread {
+        signaled: *const AtomicBool,
+        thread: Thread,
+    },
+    /// A task identified by the ID of its AsyncWait; the Waker is set once it is polled.
+    Task { id: usize, waker: Option<Waker> },
+}
+
+/// Returns the wait queue that the Cell at the given address is mapped to.
+#[cfg(all(feature = "std", not(loom)))]
+fn wait_queue(address: usize) -> &'static Mutex<Vec<Waiter>> {
+    let hash = (address >> 3).wrapping_mul(0x9E37_79B9_7F4A_7C15_u64 as usize);
+    &WAIT_QUEUES[hash >> (usize::BITS - NUM_WAIT_QUEUES.trailing_zeros())]
 }
 
-impl WaitQueueEntry {
-    fn new(wait_queue: Atomic<WaitQueueEntry>) -> WaitQueueEntry {
-        WaitQueueEntry {
-            mutex: Mutex::new(false),
-            condvar: Condvar::new(),
-            next: wait_queue,
+/// AsyncWait is a future that resolves once the Cell it is registered with is released.
+///
+/// It is registered by `try_lock_or_wait` while the Cell is protected by a Guard, and awaited
+/// after the Guard is dropped. The Cell is only identified by its address in the meantime,
+/// therefore it does not matter if the Cell is dropped before the task is woken up.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct AsyncWait {
+    /// The address of the Cell, and the ID of the task in the wait queue.
+    registered: Option<(usize, usize)>,
+}
+
+#[cfg(feature = "std")]
+impl AsyncWait {
+    /// Sets the Waker of the task, and returns `true` if the task is still in the wait queue.
+    #[cfg(not(loom))]
+    fn pending(&mut self, waker: &Waker) -> bool {
+        let Some((cell, id)) = self.registered else {
+            return false;
+        };
+        let mut waiters = wait_queue(cell).lock().unwrap();
+        for waiter in waiters.iter_mut().filter(|w| w.cell == cell) {
+            if let Wakeup::Task {
+                id: task_id,
+                waker: task_waker,
+            } = &mut waiter.wakeup
+            {
+                if *task_id == id {
+                    // The Waker is set while the wait queue is locked, therefore the task cannot
+                    // miss the wakeup.
+                    task_waker.replace(waker.clone());
+                    return true;
+                }
+            }
         }
+        false
+    }
+
+    /// Returns `false` as no task is put into a wait queue under the model checker.
+    #[cfg(loom)]
+    fn pending(&mut self, _waker: &Waker) -> bool {
+        false
     }
 
-    fn wait(&self) {
-        let mut completed = self.mutex.lock().unwrap();
-        while !*completed {
-            completed = self.condvar.wait(completed).unwrap();
+    /// Removes the task from the wait queue.
+    #[cfg(not(loom))]
+    fn deregister(&mut self) {
+        if let Some((cell, id)) = self.registered.take() {
+            let mut waiters = wait_queue(cell).lock().unwrap();
+            if let Some(index) = waiters.iter().position(|w| {
+                w.cell == cell
+                    && matches!(w.wakeup, Wakeup::Task { id: task_id, .. } if task_id == id)
+            }) {
+                waiters.remove(index);
+            }
         }
     }
 
-    fn signal(&self) {
-        let mut completed = self.mutex.lock().unwrap();
-        *completed = true;
-        self.condvar.notify_one();
+    /// No task is put into a wait queue under the model checker.
+    #[cfg(loom)]
+    fn deregister(&mut self) {
+        self.registered.take();
+    }
+}
+
+#[cfg(feature = "std")]
+impl Future for AsyncWait {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.pending(cx.waker()) {
+            return Poll::Pending;
+        }
+        self.registered.take();
+        Poll::Ready(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for AsyncWait {
+    fn drop(&mut self) {
+        self.deregister();
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::super::cell_array::CellArray;
+    use super::super::sync;
     use super::*;
     use std::convert::TryInto;
+    #[cfg(all(feature = "std", not(loom)))]
+    use std::sync::atomic::AtomicUsize;
+    #[cfg(feature = "std")]
     use std::sync::{Arc, Barrier};
+    #[cfg(feature = "std")]
     use std::thread;
 
     #[test]
+    fn zeroed_cell() {
+        // CellArray allocates Cells as zeroed memory.
+        let guard = crate::ebr::pin();
+        let cell: Cell<usize, usize, 32, false> = unsafe { MaybeUninit::zeroed().assume_init() };
+        let default_cell: Cell<usize, usize, 32, false> = Cell::default();
+        assert_eq!(cell.state.load(Relaxed), default_cell.state.load(Relaxed));
+        assert_eq!(cell.num_entries(), default_cell.num_entries());
+        assert!(cell.data.load(Relaxed, &guard).is_null());
+        CellLocker::lock(&default_cell, &guard)
+            .unwrap()
+            .purge(&guard);
+        assert!(!cell.killed());
+        assert_eq!(cell.num_data_arrays(&guard), 0);
+        assert!(cell.search(&1, 1, &guard).is_none());
+
+        let mut locker = CellLocker::lock(&cell, &guard).unwrap();
+        assert!(locker.insert(1, 1, 1, &guard).1.is_none());
+        assert_eq!(locker.cell_ref().search(&1, 1, &guard), Some((&1, &1)));
+        assert_eq!(locker.purge(&guard), 1);
+    }
+
+    #[test]
+    fn zero_sized_value() {
+        const SIZE: usize = 32;
+        assert_eq!(
+            std::mem::size_of::<DataArray<u64, (), SIZE>>(),
+            std::mem::size_of::<DataArray<u64, u64, SIZE>>() - std::mem::size_of::<u64>() * SIZE
+        );
+        assert_eq!(
+            std::mem::size_of::<DataArray<u8, (), SIZE>>(),
+            SIZE * (1 + std::mem::size_of::<PartialHash>())
+                + usize::from(cfg!(feature = "full-hash")) * SIZE * std::mem::size_of::<u64>()
+                + std::mem::size_of::<u64>()
+                + std::mem::size_of::<Atomic<DataArray<u8, (), SIZE>>>()
+        );
+    }
+
+    #[test]
+    fn match_partial_hash() {
+        fn check<const SIZE: usize>() {
+            const STEP: PartialHash = PartialHash::MAX / 4;
+            let mut data_array: DataArray<usize, usize, SIZE> = DataArray::new();
+            for seed in 0..256_usize {
+                for (index, hash) in data_array.partial_hash_array.iter_mut().enumerate() {
+                    *hash = ((seed * 7 + index * 13) % 5) as PartialHash * STEP;
+                }
+                for partial_hash in [0, STEP, STEP * 2, STEP * 3, STEP * 4, PartialHash::MAX] {
+                    let expected = data_array
+                        .partial_hash_array
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, hash)| **hash == partial_hash)
+                        .fold(0_u64, |bitmap, (index, _)| bitmap | (1_u64 << index));
+                    assert_eq!(data_array.match_partial_hash(partial_hash), expected);
+                }
+            }
+            data_array.partial_hash_array = [0; SIZE];
+        }
+        check::<1>();
+        check::<13>();
+        check::<32>();
+        check::<64>();
+    }
+
+    #[cfg(all(feature = "std", feature = "full-hash"))]
+    #[test]
+    fn full_hash_filter() {
+        const SIZE: usize = 32;
+        let cell: Cell<usize, usize, SIZE, false> = Default::default();
+        let guard = crate::ebr::pin();
+
+        // The hash values share the partial hash value, and only one of them is stored.
+        let hash = 0x15_u64;
+        let colliding_hash = hash | (1_u64 << 32);
+        assert_eq!(partial_hash(hash), partial_hash(colliding_hash));
+        let xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        for key in 0..SIZE {
+            assert!(xlocker.insert(key, key, hash, &guard).1.is_none());
+        }
+        drop(xlocker);
+
+        // Keys are not compared if the stored hash value differs.
+        let num_comparisons = AtomicUsize::new(0);
+        let eq = |k: &usize| {
+            num_comparisons.fetch_add(1, Relaxed);
+            *k == SIZE
+        };
+        assert!(cell.search_with(eq, colliding_hash, &guard).is_none());
+        assert!(cell.get_with(eq, colliding_hash, &guard).is_none());
+        assert_eq!(num_comparisons.load(Relaxed), 0);
+        assert!(cell.search_with(eq, hash, &guard).is_none());
+        assert_eq!(num_comparisons.load(Relaxed), SIZE);
+
+        let mut xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        assert!(!xlocker.mark_removed(&0, colliding_hash, &guard));
+        assert!(xlocker.mark_removed(&0, hash, &guard));
+        xlocker.purge(&guard);
+    }
+
+    #[test]
+    fn colliding_partial_hashes() {
+        const SIZE: usize = 32;
+        let cell: Cell<usize, usize, SIZE, true> = Default::default();
+        let guard = crate::ebr::pin();
+        let num_entries = SIZE * 3 + 5;
+        let hash: u64 = 0x15;
+        let xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        for key in 0..num_entries {
+            assert!(xlocker.insert(key, key, hash, &guard).1.is_none());
+        }
+        assert!(xlocker.insert(0, 0, hash, &guard).1.is_some());
+        drop(xlocker);
+        assert_eq!(cell.num_entries(), num_entries);
+        for key in 0..num_entries {
+            assert_eq!(cell.search(&key, hash, &guard), Some((&key, &key)));
+            assert!(cell.search(&key, hash ^ 1, &guard).is_none());
+        }
+        assert!(cell.search(&num_entries, hash, &guard).is_none());
+
+        let xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        for key in (0..num_entries).step_by(2) {
+            assert!(xlocker.mark_removed(&key, hash, &guard));
+            assert!(!xlocker.mark_removed(&key, hash, &guard));
+        }
+        drop(xlocker);
+        for key in 0..num_entries {
+            assert_eq!(cell.search(&key, hash, &guard).is_some(), key % 2 == 1);
+        }
+
+        let mut xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        xlocker.purge(&guard);
+    }
+
+    #[test]
+    fn compaction() {
+        const SIZE: usize = 32;
+        let cell: Cell<usize, usize, SIZE, false> = Default::default();
+        let guard = unsafe { crate::ebr::unprotected() };
+        let num_data_arrays = |cell: &Cell<usize, usize, SIZE, false>| {
+            let mut num_data_arrays = 0;
+            let mut current = cell.data.load(Relaxed, guard);
+            while let Some(data_array_ref) = unsafe { current.as_ref() } {
+                num_data_arrays += 1;
+                current = data_array_ref.link.load(Relaxed, guard);
+            }
+            num_data_arrays
+        };
+        let xlocker = CellLocker::lock(&cell, guard).unwrap();
+        for key in 0..SIZE * 3 {
+            assert!(xlocker.insert(key, key, 0, guard).1.is_none());
+        }
+        drop(xlocker);
+        assert_eq!(num_data_arrays(&cell), 3);
+
+        // Emptying the DataArrays except for one shrinks the linked list to one DataArray.
+        let xlocker = CellLocker::lock(&cell, guard).unwrap();
+        let mut iterator = cell.iter(guard);
+        while let Some(((key, _), _)) = iterator.next() {
+            if *key >= SIZE {
+                assert!(xlocker.erase(&mut iterator).is_some());
+            }
+        }
+        drop(xlocker);
+        assert_eq!(num_data_arrays(&cell), 1);
+        for key in 0..SIZE * 3 {
+            assert_eq!(cell.search(&key, 0, guard).is_some(), key < SIZE);
+        }
+
+        // The last DataArray is kept.
+        let xlocker = CellLocker::lock(&cell, guard).unwrap();
+        let mut iterator = cell.iter(guard);
+        while iterator.next().is_some() {
+            assert!(xlocker.erase(&mut iterator).is_some());
+        }
+        drop(xlocker);
+        assert_eq!(num_data_arrays(&cell), 1);
+        assert_eq!(cell.num_entries(), 0);
+
+        let mut xlocker = CellLocker::lock(&cell, guard).unwrap();
+        xlocker.purge(guard);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn probe_length() {
+        const SIZE: usize = 32;
+        let guard = unsafe { crate::ebr::unprotected() };
+        let probe_length = |cell: &Cell<usize, usize, SIZE, false>| {
+            NUM_PROBED_DATA_ARRAYS.with(|n| n.set(0));
+            assert!(cell.search(&usize::MAX, 0, guard).is_none());
+            NUM_PROBED_DATA_ARRAYS.with(|n| n.get())
+        };
+        let mut random = 0x2545_F491_4F6C_DD1D_u64;
+        let mut next_random = |bound: usize| {
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            (random % bound as u64) as usize
+        };
+
+        // Insertions and removals are interleaved, and the number of DataArrays to probe always
+        // equals the number of DataArrays that the entries require.
+        let cell: Cell<usize, usize, SIZE, false> = Default::default();
+        let mut live_keys = std::collections::BTreeSet::new();
+        let mut next_key = 0;
+        for _ in 0..256 {
+            let xlocker = CellLocker::lock(&cell, guard).unwrap();
+            for _ in 0..next_random(SIZE * 2) {
+                assert!(xlocker.insert(next_key, next_key, 0, guard).1.is_none());
+                live_keys.insert(next_key);
+                next_key += 1;
+            }
+            for _ in 0..next_random(SIZE * 2).min(live_keys.len()) {
+                let key = *live_keys.iter().nth(next_random(live_keys.len())).unwrap();
+                let mut iterator = cell.get_with(|k| *k == key, 0, guard).unwrap();
+                assert_eq!(xlocker.erase(&mut iterator), Some((key, key)));
+                live_keys.remove(&key);
+            }
+
+            // Every entry is iterated over exactly once while entries are being erased.
+            let num_live_keys = live_keys.len();
+            let mut iterated = Vec::new();
+            let mut iterator = cell.iter(guard);
+            while let Some(((key, _), _)) = iterator.next() {
+                iterated.push(*key);
+                if next_random(4) == 0 {
+                    let key = *key;
+                    assert_eq!(xlocker.erase(&mut iterator), Some((key, key)));
+                    live_keys.remove(&key);
+                }
+            }
+            iterated.sort_unstable();
+            iterated.dedup();
+            assert_eq!(iterated.len(), num_live_keys);
+            drop(xlocker);
+
+            assert_eq!(cell.num_entries(), live_keys.len());
+            assert_eq!(probe_length(&cell), live_keys.len().div_ceil(SIZE));
+            assert!(cell.num_data_arrays(guard) <= live_keys.len().div_ceil(SIZE).max(1));
+        }
+        for key in 0..next_key {
+            assert_eq!(
+                cell.search(&key, 0, guard),
+                live_keys.get(&key).map(|k| (k, k))
+            );
+        }
+        let mut xlocker = CellLocker::lock(&cell, guard).unwrap();
+        xlocker.purge(guard);
+
+        // Removed entries in a lock-free Cell are kept until the DataArrays are replaced, and the
+        // number of DataArrays stays within twice the number that the entries require.
+        let cell: Cell<usize, usize, SIZE, true> = Default::default();
+        let guard = crate::ebr::pin();
+        let mut live_keys = std::collections::BTreeSet::new();
+        for _ in 0..256 {
+            let xlocker = CellLocker::lock(&cell, &guard).unwrap();
+            for _ in 0..next_random(SIZE * 2) {
+                assert!(xlocker.insert(next_key, next_key, 0, &guard).1.is_none());
+                live_keys.insert(next_key);
+                next_key += 1;
+            }
+            for _ in 0..next_random(SIZE * 2).min(live_keys.len()) {
+                let key = *live_keys.iter().nth(next_random(live_keys.len())).unwrap();
+                assert!(xlocker.mark_removed(&key, 0, &guard));
+                live_keys.remove(&key);
+                let num_required = live_keys.len().div_ceil(SIZE);
+                assert!(cell.num_data_arrays(&guard) <= (num_required * 2).max(1));
+            }
+            drop(xlocker);
+            assert_eq!(cell.num_entries(), live_keys.len());
+        }
+        let mut xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        xlocker.purge(&guard);
+    }
+
+    #[test]
+    fn occupancy_bitmap() {
+        const SIZE: usize = 32;
+        let cell: Cell<usize, usize, SIZE, false> = Default::default();
+        let guard = unsafe { crate::ebr::unprotected() };
+        let check = |cell: &Cell<usize, usize, SIZE, false>| {
+            let mut num_occupied = 0;
+            let mut current = cell.data.load(Relaxed, guard);
+            while let Some(data_array_ref) = unsafe { current.as_ref() } {
+                assert!(data_array_ref.occupancy_consistent());
+                num_occupied += data_array_ref.occupied_bitmap.count_ones() as usize;
+                current = data_array_ref.link.load(Relaxed, guard);
+            }
+            assert_eq!(num_occupied, cell.num_entries());
+            assert_eq!(cell.iter(guard).count(), cell.num_entries());
+        };
+
+        // Removed slots are reused, and the iterator skips the holes.
+        let xlocker = CellLocker::lock(&cell, guard).unwrap();
+        for key in 0..SIZE * 2 {
+            let partial_hash = (key % SIZE).try_into().unwrap();
+            assert!(xlocker.insert(key, key, partial_hash, guard).1.is_none());
+        }
+        check(&cell);
+        let mut iterator = cell.iter(guard);
+        while let Some(((key, _), _)) = iterator.next() {
+            if *key % 3 != 0 {
+                assert!(xlocker.erase(&mut iterator).is_some());
+            }
+        }
+        check(&cell);
+        for key in SIZE * 2..SIZE * 3 {
+            assert!(xlocker.insert(key, key, 0, guard).1.is_none());
+        }
+        drop(xlocker);
+        check(&cell);
+        for key in 0..SIZE * 2 {
+            let partial_hash = (key % SIZE).try_into().unwrap();
+            assert_eq!(
+                cell.search(&key, partial_hash, guard).is_some(),
+                key % 3 == 0
+            );
+        }
+        for key in SIZE * 2..SIZE * 3 {
+            assert!(cell.search(&key, 0, guard).is_some());
+        }
+
+        let mut xlocker = CellLocker::lock(&cell, guard).unwrap();
+        xlocker.purge(guard);
+    }
+
+    // Readers are not starved by a writer repeatedly locking the Cell as long as waiting threads
+    // are queued, which requires the standard library.
+    #[cfg(all(feature = "std", not(loom)))]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reader_writer() {
+        const SIZE: usize = 32;
+        let num_readers = 8;
+        let num_iterations = 1024;
+        let cell: Cell<usize, usize, SIZE, false> = Default::default();
+        let barrier = Barrier::new(num_readers);
+        let start_barrier = Barrier::new(num_readers + 1);
+        let num_active_readers = AtomicUsize::new(0);
+        let writer_active = AtomicBool::new(false);
+        let readers_done = AtomicUsize::new(0);
+        let num_writes = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..num_readers {
+                s.spawn(|| {
+                    let guard = crate::ebr::pin();
+
+                    // All the readers hold the shared lock at the same time.
+                    let reader = CellReader::lock(&cell, &guard).unwrap();
+                    num_active_readers.fetch_add(1, Relaxed);
+                    barrier.wait();
+                    assert_eq!(num_active_readers.load(Relaxed), num_readers);
+                    barrier.wait();
+                    num_active_readers.fetch_sub(1, Relaxed);
+                    drop(reader);
+                    start_barrier.wait();
+
+                    for _ in 0..num_iterations {
+                        let reader = CellReader::lock(&cell, &guard).unwrap();
+                        num_active_readers.fetch_add(1, Relaxed);
+                        assert!(!writer_active.load(Relaxed));
+                        thread::yield_now();
+                        num_active_readers.fetch_sub(1, Relaxed);
+                        drop(reader);
+                    }
+                    readers_done.fetch_add(1, Relaxed);
+                });
+            }
+            s.spawn(|| {
+                let guard = crate::ebr::pin();
+                // The writer starts after all the readers have released the shared lock, otherwise
+                // a pending writer would keep the readers that have yet to lock the Cell from
+                // reaching the barrier.
+                start_barrier.wait();
+
+                // The writer keeps acquiring the lock while the readers are busy.
+                while readers_done.load(Relaxed) != num_readers {
+                    let locker = CellLocker::lock(&cell, &guard).unwrap();
+                    writer_active.store(true, Relaxed);
+                    assert_eq!(num_active_readers.load(Relaxed), 0);
+                    thread::yield_now();
+                    writer_active.store(false, Relaxed);
+                    drop(locker);
+                    if readers_done.load(Relaxed) == 0 {
+                        num_writes.fetch_add(1, Relaxed);
+                    }
+                }
+            });
+        });
+        // The writer is not starved by the readers.
+        assert!(num_writes.load(Relaxed) > 0);
+        assert_eq!(cell.state.load(Relaxed) & (LOCK_MASK | WRITER_PENDING), 0);
+
+        let guard = crate::ebr::pin();
+        let mut xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        xlocker.purge(&guard);
+    }
+
+    #[test]
+    fn layout() {
+        fn assert_cache_line<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool>() {
+            assert_eq!(
+                std::mem::size_of::<Cell<K, V, SIZE, LOCK_FREE>>(),
+                CACHE_LINE_SIZE
+            );
+            assert_eq!(
+                std::mem::align_of::<Cell<K, V, SIZE, LOCK_FREE>>(),
+                CACHE_LINE_SIZE
+            );
+            let array: CellArray<K, V, SIZE, LOCK_FREE> = CellArray::new(SIZE * 4, Atomic::null());
+            for index in 0..array.array_size() {
+                let address = array.cell(index) as *const _ as usize;
+                assert_eq!(address % CACHE_LINE_SIZE, 0);
+            }
+        }
+        assert_cache_line::<usize, usize, 32, false>();
+        assert_cache_line::<String, [u64; 16], 32, true>();
+        assert_cache_line::<u8, (), 8, false>();
+    }
+
+    #[cfg(all(feature = "std", not(loom)))]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn parking() {
+        const SIZE: usize = 32;
+        let num_threads = 64;
+        let num_iterations = 256;
+        let cell: Arc<Cell<usize, usize, SIZE, false>> = Arc::new(Default::default());
+        let num_writes = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for thread_id in 0..num_threads {
+            let cell = cell.clone();
+            let num_writes = num_writes.clone();
+            let sender = sender.clone();
+            thread::spawn(move || {
+                let guard = crate::ebr::pin();
+                for _ in 0..num_iterations {
+                    if thread_id % 4 == 0 {
+                        let reader = CellReader::lock(&cell, &guard).unwrap();
+                        thread::yield_now();
+                        drop(reader);
+                    } else {
+                        let locker = CellLocker::lock(&cell, &guard).unwrap();
+                        let count = num_writes.load(Relaxed);
+                        thread::yield_now();
+                        num_writes.store(count + 1, Relaxed);
+                        drop(locker);
+                    }
+                }
+                sender.send(()).unwrap();
+            });
+        }
+        // A lost wakeup leaves a thread parked forever.
+        for _ in 0..num_threads {
+            assert!(receiver
+                .recv_timeout(std::time::Duration::from_secs(60))
+                .is_ok());
+        }
+        assert_eq!(
+            num_writes.load(Relaxed),
+            num_threads * 3 / 4 * num_iterations
+        );
+        assert_eq!(cell.state.load(Relaxed) & (LOCK_MASK | WAITING), 0);
+        assert!(cell
+            .wait_queue()
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|w| w.cell != cell.address()));
+
+        let guard = crate::ebr::pin();
+        let mut xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        xlocker.purge(&guard);
+    }
+
+    #[cfg(all(feature = "std", not(loom)))]
+    #[test]
+    fn async_wait() {
+        struct CountingWaker(AtomicUsize);
+
+        impl std::task::Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Relaxed);
+            }
+        }
+
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut context = Context::from_waker(&waker);
+        let guard = crate::ebr::pin();
+        let cell: Cell<usize, usize, 32, false> = Cell::default();
+        let locker = CellLocker::lock(&cell, &guard).unwrap();
+
+        let mut dropped_wait = AsyncWait::default();
+        let mut async_wait = AsyncWait::default();
+        assert!(CellReader::try_lock_or_wait(&cell, &mut dropped_wait, &guard).is_err());
+        assert!(CellReader::try_lock_or_wait(&cell, &mut async_wait, &guard).is_err());
+        assert!(Pin::new(&mut dropped_wait).poll(&mut context).is_pending());
+        assert!(Pin::new(&mut async_wait).poll(&mut context).is_pending());
+
+        // The task that is dropped before being woken up does not take the wakeup away.
+        drop(dropped_wait);
+        drop(locker);
+        assert_eq!(counter.0.load(Relaxed), 1);
+        assert!(Pin::new(&mut async_wait).poll(&mut context).is_ready());
+        let mut async_wait = AsyncWait::default();
+        let reader = CellReader::try_lock_or_wait(&cell, &mut async_wait, &guard);
+        assert!(matches!(reader, Ok(Some(_))));
+        drop(reader);
+        assert_eq!(cell.state.load(Relaxed) & (LOCK_MASK | WAITING), 0);
+        assert!(cell
+            .wait_queue()
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|w| w.cell != cell.address()));
+        let mut xlocker = CellLocker::lock(&cell, &guard).unwrap();
+        xlocker.purge(&guard);
+    }
+
+    #[test]
+    fn model_two_writers() {
+        // Two writers insert different keys into the same Cell.
+        sync::model(|| {
+            let cell: sync::Arc<Cell<usize, usize, 32, false>> = sync::Arc::new(Cell::default());
+            let cell_clone = cell.clone();
+            let writer = sync::thread::spawn(move || {
+                let guard = crate::ebr::pin();
+                let locker = CellLocker::lock(&cell_clone, &guard).unwrap();
+                assert!(locker.insert(1, 1, 1, &guard).1.is_none());
+            });
+            {
+                let guard = crate::ebr::pin();
+                let locker = CellLocker::lock(&cell, &guard).unwrap();
+                assert!(locker.insert(2, 2, 2, &guard).1.is_none());
+            }
+            writer.join().unwrap();
+
+            let guard = crate::ebr::pin();
+            assert_eq!(cell.state.load(Relaxed) & LOCK_MASK, 0);
+            assert_eq!(cell.num_entries(), 2);
+            assert_eq!(cell.search(&1, 1, &guard), Some((&1, &1)));
+            assert_eq!(cell.search(&2, 2, &guard), Some((&2, &2)));
+            CellLocker::lock(&cell, &guard).unwrap().purge(&guard);
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
     fn cell_locker() {
         const SIZE: usize = 32;
         let num_threads = (SIZE * 2) as usize;
@@ -834,7 +2587,7 @@ mod test {
             let data_ptr = std::sync::atomic::AtomicPtr::new(&mut data);
             thread_handles.push(thread::spawn(move || {
                 barrier_copied.wait();
-                let guard = crossbeam_epoch::pin();
+                let guard = crate::ebr::pin();
                 for i in 0..4096 {
                     let xlocker = CellLocker::lock(&*cell_copied, &guard).unwrap();
                     let mut sum: u64 = 0;
@@ -856,7 +2609,7 @@ mod test {
                                 .cell_ref()
                                 .search(&thread_id, (thread_id % SIZE).try_into().unwrap(), &guard)
                                 .unwrap(),
-                            &(thread_id, 0usize)
+                            (&thread_id, &0usize)
                         );
                     }
                     drop(xlocker);
@@ -867,7 +2620,7 @@ mod test {
                             .cell_ref()
                             .search(&thread_id, (thread_id % SIZE).try_into().unwrap(), &guard)
                             .unwrap(),
-                        &(thread_id, 0usize)
+                        (&thread_id, &0usize)
                     );
                 }
             }));
@@ -882,17 +2635,17 @@ mod test {
         assert_eq!(sum % 256, 0);
         assert_eq!(cell.num_entries(), num_threads);
 
-        let guard = unsafe { crossbeam_epoch::unprotected() };
+        let guard = unsafe { crate::ebr::unprotected() };
         for thread_id in 0..SIZE {
             assert_eq!(
                 cell.search(&thread_id, (thread_id % SIZE).try_into().unwrap(), guard),
-                Some(&(thread_id, 0))
+                Some((&thread_id, &0))
             );
         }
         let mut iterated = 0;
         for entry in cell.iter(guard) {
-            assert!(entry.0 .0 < num_threads);
-            assert_eq!(entry.0 .1, 0);
+            assert!(*entry.0 .0 < num_threads);
+            assert_eq!(*entry.0 .1, 0);
             iterated += 1;
         }
         assert_eq!(cell.num_entries(), iterated);
@@ -1,67 +1,268 @@
-use super::cell::{Cell, CellLocker};
-use crossbeam_epoch::{Atomic, Guard, Shared};
-use std::alloc::{alloc_zeroed, dealloc, Layout};
-use std::borrow::Borrow;
-use std::convert::TryInto;
-use std::hash::Hash;
-use std::sync::atomic::AtomicUsize;
-use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use super::allocator::{ArrayAllocator, GlobalArrayAllocator};
+use super::cell::{defer_drop_accounted, Cell, CellLocker, DataArray, PartialHash, RetireBatch};
+use super::hash_table::TryReserveError;
+#[cfg(feature = "metrics")]
+use super::metrics::{
+    take_group_lock_contentions, GroupCounters, GroupMetrics, CELLS_PER_GROUP,
+    LONG_PROBE_DATA_ARRAYS,
+};
+use super::sync::{AtomicPtr, AtomicUsize};
+use crate::ebr::{Atomic, Guard, Shared};
+use alloc::alloc::{handle_alloc_error, Layout};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::convert::TryInto;
+use core::hash::Hash;
+#[cfg(feature = "std")]
+use core::ops::Range;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
+
+/// The maximum number of entries that HashMap relocates from the old array for each Cell of the
+/// rehash budget.
+pub const RELOCATION_CHUNK_SIZE: usize = 8;
+
+/// The number of partially drained Cells in the old array that can be handed over to the next
+/// relocation at the same time.
+const NUM_DRAINING_SLOTS: usize = 8;
+
+/// log_2 of the number of Cells in a segment.
+#[cfg(not(test))]
+const LB_SEGMENT_SIZE: u8 = 12;
+
+/// log_2 of the number of Cells in a segment.
+///
+/// Segments are much smaller in tests, so that moderately sized arrays consist of many segments.
+#[cfg(test)]
+const LB_SEGMENT_SIZE: u8 = 4;
+
+#[cfg(test)]
+thread_local! {
+    /// Makes memory allocation for CellArray fail in the current thread if set.
+    pub static FAIL_ALLOCATION: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+
+    /// Overrides the maximum capacity of CellArray in the current thread if not zero.
+    ///
+    /// It has to be a power of two that is equal to or greater than the Cell size.
+    pub static CAPACITY_CEILING: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
 
 /// CellArray is used by HashIndex and HashMap.
 ///
-/// It is a special purpose array since it does not construct instances of C,
-/// instead only does it allocate a large chunk of zeroed heap memory.
-pub struct CellArray<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> {
-    array: Option<Box<Cell<K, V, SIZE, LOCK_FREE>>>,
-    array_ptr_offset: usize,
+/// It is a special purpose array since it does not construct instances of C, instead only does
+/// it allocate zeroed heap memory. The Cells are allocated in segments of 4096 Cells, and a
+/// segment is allocated when a Cell in it is accessed for the first time, so that reserving a
+/// large capacity only costs the segment table until the Cells are used. The segments are
+/// allocated through `A`.
+pub struct CellArray<
+    K: Eq,
+    V,
+    const SIZE: usize,
+    const LOCK_FREE: bool,
+    A: ArrayAllocator = GlobalArrayAllocator,
+> {
+    segments: Box<[AtomicPtr<Cell<K, V, SIZE, LOCK_FREE, A>>]>,
+    num_allocated_segments: AtomicUsize,
     array_capacity: usize,
     lb_capacity: u8,
-    old_array: Atomic<CellArray<K, V, SIZE, LOCK_FREE>>,
+    old_array: Atomic<CellArray<K, V, SIZE, LOCK_FREE, A>>,
+    generation: usize,
+    max_growth_ratio: usize,
     rehashing: AtomicUsize,
     rehashed: AtomicUsize,
+    draining: [AtomicUsize; NUM_DRAINING_SLOTS],
+    #[cfg(feature = "std")]
+    clearing: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    group_counters: Box<[GroupCounters]>,
+    #[cfg(feature = "tracing")]
+    container: usize,
 }
 
-impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellArray<K, V, SIZE, LOCK_FREE> {
+impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator>
+    CellArray<K, V, SIZE, LOCK_FREE, A>
+{
     /// Creates a new Array of given capacity.
     ///
     /// total_cell_capacity is the desired number of cell entries that the CellArray can accommodate.
     /// The given array instance is attached to the newly created Array instance.
+    ///
+    /// It panics if memory allocation fails.
     pub fn new(
         total_cell_capacity: usize,
-        old_array: Atomic<CellArray<K, V, SIZE, LOCK_FREE>>,
-    ) -> CellArray<K, V, SIZE, LOCK_FREE> {
-        let lb_capacity = Self::calculate_lb_array_size(total_cell_capacity);
-        let array_capacity = 1usize << lb_capacity;
-        let (array, array_ptr_offset) = unsafe {
-            let size_of_cell = std::mem::size_of::<Cell<K, V, SIZE, LOCK_FREE>>();
-            let allocation_size = (array_capacity + 1) * size_of_cell;
-            let ptr = alloc_zeroed(Layout::from_size_align_unchecked(allocation_size, 1));
-            if ptr.is_null() {
-                // Memory allocation failure: panic.
-                panic!("memory allocation failure: {} bytes", allocation_size)
+        old_array: Atomic<CellArray<K, V, SIZE, LOCK_FREE, A>>,
+    ) -> CellArray<K, V, SIZE, LOCK_FREE, A> {
+        match Self::try_new(total_cell_capacity, old_array) {
+            Ok(array) => array,
+            Err(TryReserveError::CapacityOverflow) => {
+                panic!("memory allocation failure: capacity overflow")
             }
-            let mut offset = ptr.align_offset(size_of_cell.next_power_of_two());
-            if offset == usize::MAX {
-                offset = 0;
+            Err(TryReserveError::AllocError { layout }) => {
+                panic!("memory allocation failure: {} bytes", layout.size())
             }
-            let array_ptr = ptr.add(offset) as *mut Cell<K, V, SIZE, LOCK_FREE>;
-            (Some(Box::from_raw(array_ptr)), offset)
-        };
-        CellArray {
-            array,
-            array_ptr_offset,
+        }
+    }
+
+    /// Creates a new Array of given capacity, or returns an error if memory allocation fails.
+    pub fn try_new(
+        total_cell_capacity: usize,
+        old_array: Atomic<CellArray<K, V, SIZE, LOCK_FREE, A>>,
+    ) -> Result<CellArray<K, V, SIZE, LOCK_FREE, A>, TryReserveError> {
+        let lb_capacity = Self::calculate_lb_array_size(total_cell_capacity);
+        let array_capacity = 1usize << lb_capacity;
+        Layout::array::<Cell<K, V, SIZE, LOCK_FREE, A>>(array_capacity)
+            .map_err(|_| TryReserveError::CapacityOverflow)?;
+        let num_segments = 1_usize << lb_capacity.saturating_sub(LB_SEGMENT_SIZE);
+        let segment_layout = Self::segment_layout(array_capacity);
+        #[cfg(test)]
+        if FAIL_ALLOCATION.with(core::cell::Cell::get) {
+            return Err(TryReserveError::AllocError {
+                layout: segment_layout,
+            });
+        }
+        // The first segment is allocated in advance, so that a small CellArray is allocated at
+        // once. The Cells are not constructed since zeroed memory is a valid empty Cell, and
+        // therefore creating a CellArray does not take longer as the capacity grows, except for
+        // the segment table.
+        let first_segment = Self::allocate_cells(segment_layout);
+        if first_segment.is_null() {
+            return Err(TryReserveError::AllocError {
+                layout: segment_layout,
+            });
+        }
+        let segments: Box<[AtomicPtr<Cell<K, V, SIZE, LOCK_FREE, A>>]> =
+            (0..num_segments).map(|_| AtomicPtr::default()).collect();
+        segments[0].store(first_segment.cast(), Relaxed);
+        // The old array is kept alive by the caller.
+        let generation = unsafe { old_array.load(Relaxed, crate::ebr::unprotected()).as_ref() }
+            .map_or(0, |old_array_ref| old_array_ref.generation.wrapping_add(1));
+        Ok(CellArray {
+            segments,
+            num_allocated_segments: AtomicUsize::new(1),
             array_capacity,
             lb_capacity,
             old_array,
+            generation,
+            max_growth_ratio: 1 << Cell::<K, V, SIZE, LOCK_FREE, A>::max_resizing_factor(),
             rehashing: AtomicUsize::new(0),
             rehashed: AtomicUsize::new(0),
-        }
+            draining: [0; NUM_DRAINING_SLOTS].map(AtomicUsize::new),
+            #[cfg(feature = "std")]
+            clearing: AtomicUsize::new(0),
+            #[cfg(feature = "metrics")]
+            group_counters: (0..array_capacity.div_ceil(CELLS_PER_GROUP))
+                .map(|_| GroupCounters::default())
+                .collect(),
+            #[cfg(feature = "tracing")]
+            container: 0,
+        })
     }
 
     /// Returns a reference to a Cell at the given position.
-    pub fn cell(&self, index: usize) -> &Cell<K, V, SIZE, LOCK_FREE> {
-        let array_ptr = &(**self.array.as_ref().unwrap()) as *const Cell<K, V, SIZE, LOCK_FREE>;
-        unsafe { &(*(array_ptr.add(index))) }
+    ///
+    /// The segment that the Cell belongs to is allocated if it has not been allocated.
+    #[inline]
+    pub fn cell(&self, index: usize) -> &Cell<K, V, SIZE, LOCK_FREE, A> {
+        debug_assert!(index < self.array_capacity);
+        let segment_index = index >> LB_SEGMENT_SIZE;
+        // The index is always less than the array size.
+        let mut segment_ptr = unsafe { self.segments.get_unchecked(segment_index) }.load(Acquire);
+        if segment_ptr.is_null() {
+            segment_ptr = self.allocate_segment(segment_index);
+        }
+        unsafe { &*segment_ptr.add(index & ((1_usize << LB_SEGMENT_SIZE) - 1)) }
+    }
+
+    /// Returns a reference to a Cell at the given position if the segment that the Cell belongs
+    /// to has been allocated.
+    ///
+    /// A Cell in a segment that has yet to be allocated has never been accessed, and therefore is
+    /// empty.
+    pub fn initialized_cell(&self, index: usize) -> Option<&Cell<K, V, SIZE, LOCK_FREE, A>> {
+        let segment_ptr = self.segments[index >> LB_SEGMENT_SIZE].load(Acquire);
+        if segment_ptr.is_null() {
+            return None;
+        }
+        Some(unsafe { &*segment_ptr.add(index & ((1_usize << LB_SEGMENT_SIZE) - 1)) })
+    }
+
+    /// Prefetches the Cell at the given position and its first DataArray.
+    ///
+    /// It does nothing if the position is out of range or the segment that the Cell belongs to
+    /// has yet to be allocated.
+    #[inline]
+    pub fn prefetch(&self, index: usize, guard: &Guard) {
+        if index < self.array_capacity {
+            if let Some(cell_ref) = self.initialized_cell(index) {
+                cell_ref.prefetch_data(guard);
+            }
+        }
+    }
+
+    /// Records the events of an operation that locked a Cell for the key of which the Cell in
+    /// the CellArray is at the given position.
+    ///
+    /// The lock contention events recorded in the current thread since the last call are
+    /// attributed to the group of Cells.
+    #[cfg(feature = "metrics")]
+    pub fn record_group_events(
+        &self,
+        index: usize,
+        cell_ref: &Cell<K, V, SIZE, LOCK_FREE, A>,
+        old_array_hit: bool,
+        guard: &Guard,
+    ) {
+        self.group_counters[index / CELLS_PER_GROUP].record(
+            take_group_lock_contentions(),
+            cell_ref.num_data_arrays(guard) >= LONG_PROBE_DATA_ARRAYS,
+            old_array_hit,
+        );
+    }
+
+    /// Takes a snapshot of the counters of all the groups of Cells.
+    #[cfg(feature = "metrics")]
+    pub fn group_metrics(&self) -> Vec<GroupMetrics> {
+        self.group_counters
+            .iter()
+            .enumerate()
+            .map(|(group_index, counters)| {
+                let first_cell = group_index * CELLS_PER_GROUP;
+                counters.snapshot(
+                    first_cell,
+                    (self.array_capacity - first_cell).min(CELLS_PER_GROUP),
+                )
+            })
+            .collect()
+    }
+
+    /// Resets the counters of all the groups of Cells.
+    #[cfg(feature = "metrics")]
+    pub fn reset_group_counters(&self) {
+        self.group_counters.iter().for_each(GroupCounters::reset);
+    }
+
+    /// Returns the number of allocated segments.
+    pub fn num_allocated_segments(&self) -> usize {
+        self.num_allocated_segments.load(Relaxed)
+    }
+
+    /// Returns the number of bytes allocated for the CellArray and the first DataArray of each
+    /// Cell, and the number of bytes allocated for the DataArrays linked to them.
+    pub fn memory_usage(&self, guard: &Guard) -> (usize, usize) {
+        let data_array_size = core::mem::size_of::<DataArray<K, V, SIZE, A>>();
+        let mut array_bytes = self.allocated_bytes();
+        let mut overflow_bytes = 0;
+        for index in 0..self.array_capacity {
+            if let Some(num_data_arrays) = self
+                .initialized_cell(index)
+                .map(|cell| cell.num_data_arrays(guard))
+                .filter(|n| *n != 0)
+            {
+                array_bytes += data_array_size;
+                overflow_bytes += (num_data_arrays - 1) * data_array_size;
+            }
+        }
+        (array_bytes, overflow_bytes)
     }
 
     /// Returns the recommended sampling size.
@@ -79,36 +280,84 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellArray<K, V, SIZE, L
         self.array_capacity * SIZE
     }
 
+    /// Returns the number of total cell entries of a CellArray created with the given capacity.
+    pub fn num_cell_entries_for(total_cell_capacity: usize) -> usize {
+        (1_usize << Self::calculate_lb_array_size(total_cell_capacity)) * SIZE
+    }
+
+    /// Returns the maximum number of cell entries that a CellArray can accommodate.
+    ///
+    /// It is the largest power of two that keeps the Cells within `isize::MAX` bytes, therefore
+    /// it depends on the pointer width of the target; for instance, a CellArray of cache-line
+    /// sized Cells consists of 2^24 Cells at most on 32-bit targets.
+    pub fn max_capacity() -> usize {
+        #[cfg(test)]
+        {
+            let ceiling = CAPACITY_CEILING.with(core::cell::Cell::get);
+            if ceiling != 0 {
+                return ceiling;
+            }
+        }
+        let cell_size = core::mem::size_of::<Cell<K, V, SIZE, LOCK_FREE, A>>().max(1);
+        let max_array_size = isize::MAX as usize / cell_size;
+        let lb_max_array_size = usize::BITS - 1 - max_array_size.leading_zeros();
+        let lb_max_capacity = (lb_max_array_size + SIZE.trailing_zeros()).min(usize::BITS - 1);
+        1_usize << lb_max_capacity
+    }
+
+    /// Returns the number of arrays that preceded the CellArray.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
     /// Returns a shared pointer to the old array.
-    pub fn old_array<'g>(&self, guard: &'g Guard) -> Shared<'g, CellArray<K, V, SIZE, LOCK_FREE>> {
-        self.old_array.load(Relaxed, &guard)
+    ///
+    /// The pointer is loaded with Acquire, so that a thread seeing the old array cleared also
+    /// sees all the Cells in it killed.
+    pub fn old_array<'g>(
+        &self,
+        guard: &'g Guard,
+    ) -> Shared<'g, CellArray<K, V, SIZE, LOCK_FREE, A>> {
+        self.old_array.load(Acquire, guard)
     }
 
     /// Calculates the cell index for the hash value.
     pub fn calculate_cell_index(&self, hash: u64) -> usize {
-        (hash >> (64 - self.lb_capacity)).try_into().unwrap()
+        // A CellArray consisting of a single Cell does not use the hash value.
+        hash.checked_shr(64 - u32::from(self.lb_capacity))
+            .unwrap_or(0)
+            .try_into()
+            .unwrap()
     }
 
     /// Drops the old array.
     pub fn drop_old_array(&self, immediate_drop: bool, guard: &Guard) {
-        let old_array = self.old_array.swap(Shared::null(), Relaxed, guard);
+        let old_array = self.old_array.swap(Shared::null(), AcqRel, guard);
         if !old_array.is_null() {
             unsafe {
                 if immediate_drop {
                     // There is no possibility that the old array contains valid cells.
                     drop(old_array.into_owned());
                 } else {
-                    guard.defer_destroy(old_array);
+                    // The DataArrays in the old array have been retired when the Cells were
+                    // killed.
+                    let num_bytes = old_array.deref().allocated_bytes();
+                    defer_drop_accounted(old_array, num_bytes, guard);
+                    // The old array is as large as the current array, and the thread may not
+                    // retire anything else for a long time, therefore the deferred drop is
+                    // handed over to the global garbage collector right away instead of waiting
+                    // in the thread-local buffer.
+                    guard.flush();
                 }
             }
         }
     }
 
     /// Kills the Cell.
-    pub fn kill_cell<Q, F: Fn(&Q) -> (u64, u8), C: Fn(&K, &V) -> Option<(K, V)>>(
+    pub fn kill_cell<Q, F: Fn(&Q) -> (u64, PartialHash), C: Fn(&K, &V) -> Option<(K, V)>>(
         &self,
-        cell_locker: &mut CellLocker<K, V, SIZE, LOCK_FREE>,
-        old_array: &CellArray<K, V, SIZE, LOCK_FREE>,
+        cell_locker: &mut CellLocker<K, V, SIZE, LOCK_FREE, A>,
+        old_array: &CellArray<K, V, SIZE, LOCK_FREE, A>,
         old_cell_index: usize,
         hasher: &F,
         copier: &C,
@@ -117,11 +366,47 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellArray<K, V, SIZE, L
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
+        self.drain_cell(
+            cell_locker,
+            old_array,
+            old_cell_index,
+            hasher,
+            copier,
+            usize::MAX,
+            guard,
+        );
+    }
+
+    /// Relocates at most `max_entries` entries from the Cell, and kills the Cell once it is empty.
+    ///
+    /// It returns `true` if the Cell has been killed. A partially drained Cell stays alive, and
+    /// the relocated entries are found in the current array, therefore it is only allowed for
+    /// HashMap of which the relocated entries are removed from the old array.
+    #[allow(clippy::too_many_arguments)]
+    pub fn drain_cell<Q, F: Fn(&Q) -> (u64, PartialHash), C: Fn(&K, &V) -> Option<(K, V)>>(
+        &self,
+        cell_locker: &mut CellLocker<K, V, SIZE, LOCK_FREE, A>,
+        old_array: &CellArray<K, V, SIZE, LOCK_FREE, A>,
+        old_cell_index: usize,
+        hasher: &F,
+        copier: &C,
+        max_entries: usize,
+        guard: &Guard,
+    ) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        debug_assert!(!LOCK_FREE || max_entries == usize::MAX);
         if cell_locker.cell_ref().killed() {
-            return;
-        } else if cell_locker.cell_ref().num_entries() == 0 {
+            return true;
+        }
+
+        if cell_locker.cell_ref().num_entries() == 0 {
+            #[cfg(feature = "metrics")]
+            super::metrics::record_cell_rehashed();
             cell_locker.purge(guard);
-            return;
+            return true;
         }
 
         let shrink = old_array.array_size() > self.array_size();
@@ -135,22 +420,37 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellArray<K, V, SIZE, L
         } else {
             old_cell_index * ratio
         };
-        debug_assert!(ratio <= (1 << Cell::<K, V, SIZE, LOCK_FREE>::max_resizing_factor()));
+        debug_assert!(shrink || ratio <= self.max_growth_ratio);
 
-        let mut target_cells: Vec<CellLocker<K, V, SIZE, LOCK_FREE>> =
-            Vec::with_capacity(1 << Cell::<K, V, SIZE, LOCK_FREE>::max_resizing_factor());
+        let mut target_cells: Vec<CellLocker<K, V, SIZE, LOCK_FREE, A>> =
+            Vec::with_capacity(1 << Cell::<K, V, SIZE, LOCK_FREE, A>::max_resizing_factor());
+        let mut num_relocated = 0;
         let mut iter = cell_locker.cell_ref().iter(guard);
         while let Some(entry) = iter.next() {
-            let (new_cell_index, partial_hash) = if !shrink {
-                let (hash, partial_hash) = hasher(entry.0 .0.borrow());
-                let new_cell_index = self.calculate_cell_index(hash);
-                debug_assert!((new_cell_index - target_cell_index) < ratio);
-                (new_cell_index, partial_hash)
+            if num_relocated == max_entries {
+                return false;
+            }
+            num_relocated += 1;
+            // The stored hash value spares hashing the key with the `full-hash` feature. A
+            // shrinking array does not need the hash value to locate the target Cell, and the
+            // partial hash value is all that is stored without the feature.
+            #[cfg(feature = "full-hash")]
+            let hash = iter.hash().unwrap();
+            #[cfg(not(feature = "full-hash"))]
+            let hash = if shrink {
+                u64::from(entry.1)
             } else {
+                hasher(entry.0 .0.borrow()).0
+            };
+            let new_cell_index = if shrink {
                 debug_assert!(
                     self.calculate_cell_index(hasher(entry.0 .0.borrow()).0) == target_cell_index
                 );
-                (target_cell_index, entry.1)
+                target_cell_index
+            } else {
+                let new_cell_index = self.calculate_cell_index(hash);
+                debug_assert!((new_cell_index - target_cell_index) < ratio);
+                new_cell_index
             };
 
             while target_cells.len() <= new_cell_index - target_cell_index {
@@ -160,7 +460,7 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellArray<K, V, SIZE, L
                 );
             }
 
-            let new_entry = if let Some(entry) = copier(&entry.0 .0, &entry.0 .1) {
+            let new_entry = if let Some(entry) = copier(entry.0 .0, entry.0 .1) {
                 // HashIndex.
                 debug_assert!(LOCK_FREE);
                 entry
@@ -170,18 +470,101 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellArray<K, V, SIZE, L
                 cell_locker.erase(&mut iter).unwrap()
             };
             let result = target_cells[new_cell_index - target_cell_index]
-                .insert(new_entry.0, new_entry.1, partial_hash, guard)
+                .insert(new_entry.0, new_entry.1, hash, guard)
                 .1;
             debug_assert!(result.is_none());
         }
+        #[cfg(feature = "metrics")]
+        super::metrics::record_cell_rehashed();
         cell_locker.purge(guard);
+        true
     }
 
-    /// Relocates a fixed number of Cells from the old array to the current array.
-    pub fn partial_rehash<Q, F: Fn(&Q) -> (u64, u8), C: Fn(&K, &V) -> Option<(K, V)>>(
+    /// Relocates the key-value pairs in the old array that belong to the Cell at the given index.
+    pub fn relocate_into<Q, F: Fn(&Q) -> (u64, PartialHash), C: Fn(&K, &V) -> Option<(K, V)>>(
+        &self,
+        cell_index: usize,
+        hasher: &F,
+        copier: &C,
+        guard: &Guard,
+    ) where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let old_array_shared = self.old_array(guard);
+        if old_array_shared.is_null() {
+            return;
+        }
+        let old_array_ref = unsafe { old_array_shared.deref() };
+        let array_size = self.array_size();
+        let old_array_size = old_array_ref.array_size();
+        let old_cell_range = if old_array_size > array_size {
+            let ratio = old_array_size / array_size;
+            cell_index * ratio..(cell_index + 1) * ratio
+        } else {
+            let old_cell_index = cell_index / (array_size / old_array_size);
+            old_cell_index..old_cell_index + 1
+        };
+        for old_cell_index in old_cell_range {
+            if let Some(mut locker) = CellLocker::lock(old_array_ref.cell(old_cell_index), guard) {
+                self.kill_cell(
+                    &mut locker,
+                    old_array_ref,
+                    old_cell_index,
+                    hasher,
+                    copier,
+                    guard,
+                );
+            }
+        }
+    }
+
+    /// Returns `true` if all the Cells in the old array have been claimed for relocation, and no
+    /// partially drained Cell is waiting to be continued.
+    pub fn all_cells_claimed(&self, guard: &Guard) -> bool {
+        let old_array = self.old_array(guard);
+        old_array.is_null()
+            || (self.rehashing.load(Relaxed) >= unsafe { old_array.deref() }.array_size()
+                && self.draining.iter().all(|slot| slot.load(Relaxed) == 0))
+    }
+
+    /// Claims at most `max_cells` Cells to clear, and returns the range of the claimed Cells.
+    ///
+    /// The Cells are claimed in order, and the range ends at the array size once the last Cell
+    /// has been claimed.
+    #[cfg(feature = "std")]
+    pub fn claim_cells_to_clear(&self, max_cells: usize) -> Range<usize> {
+        let array_size = self.array_size();
+        let mut start = self.clearing.load(Relaxed);
+        loop {
+            let end = start.saturating_add(max_cells).min(array_size);
+            match self.clearing.compare_exchange(start, end, Relaxed, Relaxed) {
+                Ok(_) => return start..end,
+                Err(result) => start = result,
+            }
+        }
+    }
+
+    /// Lets the next call to [`CellArray::claim_cells_to_clear`] start from the first Cell if no
+    /// other thread has done so since the Cells up to `end` were claimed.
+    #[cfg(feature = "std")]
+    pub fn restart_clearing(&self, end: usize) {
+        let _result = self.clearing.compare_exchange(end, 0, Relaxed, Relaxed);
+    }
+
+    /// Relocates at most `max_cells` Cells from the old array to the current array.
+    ///
+    /// HashMap relocates at most [`RELOCATION_CHUNK_SIZE`] entries for each Cell of the budget, so
+    /// that no single call relocates an unbounded number of entries; a Cell left partially drained
+    /// is continued by the next call. HashIndex relocates whole Cells since it copies the entries
+    /// instead of moving them.
+    ///
+    /// It returns `true` if there is no old array, or the old array has been dropped.
+    pub fn partial_rehash<Q, F: Fn(&Q) -> (u64, PartialHash), C: Fn(&K, &V) -> Option<(K, V)>>(
         &self,
         hasher: F,
         copier: C,
+        max_cells: usize,
         guard: &Guard,
     ) -> bool
     where
@@ -195,70 +578,469 @@ impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> CellArray<K, V, SIZE, L
 
         let old_array_ref = unsafe { old_array.deref() };
         let old_array_size = old_array_ref.array_size();
-        let mut current = self.rehashing.load(Relaxed);
-        loop {
-            if current >= old_array_size {
-                return false;
-            }
-            match self
-                .rehashing
-                .compare_exchange(current, current + SIZE, Acquire, Relaxed)
-            {
-                Ok(_) => break,
-                Err(result) => current = result,
-            }
+        if max_cells == 0 {
+            return false;
         }
+        let _batch = RetireBatch::open(guard);
+        let mut max_entries = if LOCK_FREE {
+            usize::MAX
+        } else {
+            max_cells.saturating_mul(RELOCATION_CHUNK_SIZE)
+        };
+        let mut num_visited_cells = 0;
+        let mut num_relocated_cells = 0;
 
-        for old_cell_index in current..(current + SIZE).min(old_array_size) {
-            let old_cell_ref = old_array_ref.cell(old_cell_index);
-            if old_cell_ref.killed() {
+        // Partially drained Cells are continued before new Cells are claimed.
+        for slot in &self.draining {
+            if max_entries == 0 || num_visited_cells == max_cells {
+                break;
+            }
+            if slot.load(Relaxed) == 0 {
                 continue;
             }
-            if let Some(mut locker) = CellLocker::lock(old_cell_ref, guard) {
-                self.kill_cell(
-                    &mut locker,
-                    old_array_ref,
+            let draining = slot.swap(0, Acquire);
+            if draining == 0 {
+                continue;
+            }
+            num_visited_cells += 1;
+            if self.relocate_old_cell(
+                old_array_ref,
+                draining - 1,
+                &hasher,
+                &copier,
+                &mut max_entries,
+                guard,
+            ) {
+                num_relocated_cells += 1;
+            }
+        }
+
+        while max_entries != 0 && num_visited_cells < max_cells {
+            let mut old_cell_index = self.rehashing.load(Relaxed);
+            loop {
+                if old_cell_index >= old_array_size {
+                    break;
+                }
+                match self.rehashing.compare_exchange(
                     old_cell_index,
-                    &hasher,
-                    &copier,
-                    guard,
-                );
+                    old_cell_index + 1,
+                    Acquire,
+                    Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(result) => old_cell_index = result,
+                }
+            }
+            if old_cell_index >= old_array_size {
+                break;
+            }
+            num_visited_cells += 1;
+            old_array_ref.prefetch(old_cell_index + 1, guard);
+            if self.relocate_old_cell(
+                old_array_ref,
+                old_cell_index,
+                &hasher,
+                &copier,
+                &mut max_entries,
+                guard,
+            ) {
+                num_relocated_cells += 1;
             }
         }
 
-        let completed = self.rehashed.fetch_add(SIZE, Release) + SIZE;
-        if old_array_size <= completed {
+        if num_relocated_cells == 0 {
+            return false;
+        }
+        let completed = self.rehashed.fetch_add(num_relocated_cells, Release) + num_relocated_cells;
+        #[cfg(feature = "tracing")]
+        self.trace_rehash(old_array_ref, completed - num_relocated_cells, completed);
+        if old_array_size == completed {
             self.drop_old_array(false, guard);
             return true;
         }
         false
     }
 
+    /// Relocates the entries in the Cell in the old array within the budget, and returns `true`
+    /// if the Cell has been relocated entirely.
+    ///
+    /// The number of relocated entries is deducted from the budget. A Cell that is left
+    /// partially drained is handed over to the next call to [`CellArray::partial_rehash`], or
+    /// relocated entirely if no slot is available to hand it over.
+    fn relocate_old_cell<Q, F: Fn(&Q) -> (u64, PartialHash), C: Fn(&K, &V) -> Option<(K, V)>>(
+        &self,
+        old_array_ref: &CellArray<K, V, SIZE, LOCK_FREE, A>,
+        old_cell_index: usize,
+        hasher: &F,
+        copier: &C,
+        max_entries: &mut usize,
+        guard: &Guard,
+    ) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        // A Cell in a segment that has yet to be allocated is empty.
+        let old_cell_ref = match old_array_ref.initialized_cell(old_cell_index) {
+            Some(old_cell_ref) if !old_cell_ref.killed() => old_cell_ref,
+            _ => return true,
+        };
+        let Some(mut locker) = CellLocker::lock(old_cell_ref, guard) else {
+            return true;
+        };
+        let num_entries = locker.cell_ref().num_entries();
+        if self.drain_cell(
+            &mut locker,
+            old_array_ref,
+            old_cell_index,
+            hasher,
+            copier,
+            *max_entries,
+            guard,
+        ) {
+            if !LOCK_FREE {
+                *max_entries = max_entries.saturating_sub(num_entries);
+            }
+            return true;
+        }
+        *max_entries = 0;
+        for slot in &self.draining {
+            if slot
+                .compare_exchange(0, old_cell_index + 1, Release, Relaxed)
+                .is_ok()
+            {
+                return false;
+            }
+        }
+        self.kill_cell(
+            &mut locker,
+            old_array_ref,
+            old_cell_index,
+            hasher,
+            copier,
+            guard,
+        );
+        true
+    }
+
+    /// Allows the CellArray to be larger than the old array by up to the given ratio.
+    ///
+    /// The ratio bounds the number of Cells that relocating a Cell in the old array locks. By
+    /// default, it is the conservative resizing factor.
+    pub fn with_max_growth_ratio(mut self, max_growth_ratio: usize) -> Self {
+        self.max_growth_ratio = max_growth_ratio;
+        self
+    }
+
+    /// Sets the address of the container recorded in the events emitted while the old array is
+    /// relocated.
+    #[cfg(feature = "tracing")]
+    pub fn with_container(mut self, container: usize) -> Self {
+        self.container = container;
+        self
+    }
+
+    /// Emits an event each time another quarter of the old array has been relocated, and when
+    /// the whole old array has been relocated.
+    #[cfg(feature = "tracing")]
+    fn trace_rehash(
+        &self,
+        old_array_ref: &CellArray<K, V, SIZE, LOCK_FREE, A>,
+        previously_completed: usize,
+        completed: usize,
+    ) {
+        let total = old_array_ref.array_size();
+        if completed == total {
+            super::trace::trace_event!(
+                DEBUG,
+                container = self.container,
+                old_capacity = old_array_ref.num_cell_entries(),
+                new_capacity = self.num_cell_entries(),
+                "resize finished"
+            );
+        } else if previously_completed.saturating_mul(4) / total
+            != completed.saturating_mul(4) / total
+        {
+            super::trace::trace_event!(
+                TRACE,
+                container = self.container,
+                rehashed = completed,
+                total,
+                "rehash progress"
+            );
+        }
+    }
+
+    /// Returns the memory layout of a segment.
+    fn segment_layout(array_capacity: usize) -> Layout {
+        let segment_size = array_capacity.min(1_usize << LB_SEGMENT_SIZE);
+        Layout::array::<Cell<K, V, SIZE, LOCK_FREE, A>>(segment_size).unwrap()
+    }
+
+    /// Returns the number of bytes allocated for the CellArray itself, the segment table, and the
+    /// allocated segments.
+    fn allocated_bytes(&self) -> usize {
+        core::mem::size_of::<Self>()
+            + self.segments.len()
+                * core::mem::size_of::<AtomicPtr<Cell<K, V, SIZE, LOCK_FREE, A>>>()
+            + self.num_allocated_segments() * Self::segment_layout(self.array_capacity).size()
+    }
+
+    /// Allocates the segment at the given position unless another thread has allocated it.
+    #[cold]
+    fn allocate_segment(&self, segment_index: usize) -> *mut Cell<K, V, SIZE, LOCK_FREE, A> {
+        let segment_layout = Self::segment_layout(self.array_capacity);
+        let new_segment_ptr = Self::allocate_cells(segment_layout);
+        if new_segment_ptr.is_null() {
+            handle_alloc_error(segment_layout);
+        }
+        match self.segments[segment_index].compare_exchange(
+            core::ptr::null_mut(),
+            new_segment_ptr.cast(),
+            Release,
+            Acquire,
+        ) {
+            Ok(_) => {
+                self.num_allocated_segments.fetch_add(1, Relaxed);
+                new_segment_ptr.cast()
+            }
+            Err(segment_ptr) => {
+                unsafe { A::deallocate(new_segment_ptr, segment_layout) };
+                segment_ptr
+            }
+        }
+    }
+
+    /// Allocates a segment of empty Cells, and returns a null pointer if memory allocation fails.
+    ///
+    /// Zeroed memory is a valid empty Cell, except for the atomic types of the model checker that
+    /// have to be constructed.
+    fn allocate_cells(segment_layout: Layout) -> *mut u8 {
+        let segment_ptr = A::allocate_zeroed(segment_layout);
+        #[cfg(loom)]
+        if !segment_ptr.is_null() {
+            let cell_ptr = segment_ptr.cast::<Cell<K, V, SIZE, LOCK_FREE, A>>();
+            let num_cells =
+                segment_layout.size() / core::mem::size_of::<Cell<K, V, SIZE, LOCK_FREE, A>>();
+            for index in 0..num_cells {
+                unsafe { cell_ptr.add(index).write(Cell::default()) };
+            }
+        }
+        segment_ptr
+    }
+
     /// Calculates log_2 of the array size from the given cell capacity.
+    ///
+    /// The capacity is capped at [`CellArray::max_capacity`].
     fn calculate_lb_array_size(total_cell_capacity: usize) -> u8 {
-        let adjusted_total_cell_capacity = total_cell_capacity.min((usize::MAX / 2) - (SIZE - 1));
-        let required_cells = ((adjusted_total_cell_capacity + SIZE - 1) / SIZE).next_power_of_two();
+        let adjusted_total_cell_capacity = total_cell_capacity.min(Self::max_capacity());
+        let required_cells = adjusted_total_cell_capacity
+            .div_ceil(SIZE)
+            .next_power_of_two();
         let lb_capacity =
-            ((std::mem::size_of::<usize>() * 8) - (required_cells.leading_zeros() as usize) - 1)
-                .max(1);
+            (core::mem::size_of::<usize>() * 8) - (required_cells.leading_zeros() as usize) - 1;
 
         // 2^lb_capacity * C::cell_size() >= capacity
-        debug_assert!(lb_capacity > 0);
-        debug_assert!(lb_capacity < (std::mem::size_of::<usize>() * 8));
+        debug_assert!(lb_capacity < (core::mem::size_of::<usize>() * 8));
         debug_assert!((1usize << lb_capacity) * SIZE >= adjusted_total_cell_capacity);
         lb_capacity.try_into().unwrap()
     }
 }
 
-impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool> Drop for CellArray<K, V, SIZE, LOCK_FREE> {
+impl<K: Eq, V, const SIZE: usize, const LOCK_FREE: bool, A: ArrayAllocator> Drop
+    for CellArray<K, V, SIZE, LOCK_FREE, A>
+{
     fn drop(&mut self) {
-        let size_of_cell = std::mem::size_of::<Cell<K, V, SIZE, LOCK_FREE>>();
-        unsafe {
-            let array = self.array.take().unwrap();
-            dealloc(
-                (Box::into_raw(array) as *mut u8).sub(self.array_ptr_offset),
-                Layout::from_size_align_unchecked((self.array_capacity + 1) * size_of_cell, 1),
-            )
+        let segment_layout = Self::segment_layout(self.array_capacity);
+        for segment in self.segments.iter() {
+            let segment_ptr = segment.load(Relaxed);
+            if !segment_ptr.is_null() {
+                unsafe { A::deallocate(segment_ptr.cast(), segment_layout) };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ebr::Owned;
+
+    #[test]
+    fn max_capacity() {
+        const SIZE: usize = 32;
+        type Array = CellArray<usize, usize, SIZE, false>;
+        let max_capacity = Array::max_capacity();
+        assert!(max_capacity.is_power_of_two());
+        assert!(Layout::array::<Cell<usize, usize, SIZE, false>>(max_capacity / SIZE).is_ok());
+        assert!(Layout::array::<Cell<usize, usize, SIZE, false>>(max_capacity / SIZE * 2).is_err());
+        assert_eq!(Array::num_cell_entries_for(usize::MAX), max_capacity);
+        assert_eq!(Array::num_cell_entries_for(max_capacity), max_capacity);
+        assert_eq!(Array::num_cell_entries_for(max_capacity - 1), max_capacity);
+
+        CAPACITY_CEILING.with(|c| c.set(SIZE * 64));
+        assert_eq!(Array::max_capacity(), SIZE * 64);
+        assert_eq!(Array::num_cell_entries_for(SIZE * 64 - 1), SIZE * 64);
+        assert_eq!(Array::num_cell_entries_for(SIZE * 64 + 1), SIZE * 64);
+        assert_eq!(Array::num_cell_entries_for(usize::MAX), SIZE * 64);
+        let array = Array::new(usize::MAX, Atomic::null());
+        assert_eq!(array.array_size(), 64);
+        assert_eq!(array.num_cell_entries(), SIZE * 64);
+        CAPACITY_CEILING.with(|c| c.set(0));
+    }
+
+    #[test]
+    fn drain_cell() {
+        const SIZE: usize = 32;
+        let hasher = |key: &usize| {
+            let hash = (*key as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            (hash, hash as PartialHash)
+        };
+        let guard = crate::ebr::pin();
+        let old_array: CellArray<usize, usize, SIZE, false> =
+            CellArray::new(SIZE * 4, Atomic::null());
+        let keys: Vec<usize> = (0..)
+            .filter(|key| old_array.calculate_cell_index(hasher(key).0) == 0)
+            .take(SIZE * 2 + 3)
+            .collect();
+        let locker = CellLocker::lock(old_array.cell(0), &guard).unwrap();
+        for key in &keys {
+            let result = locker.insert(*key, *key, hasher(key).0, &guard).1;
+            assert!(result.is_none());
+        }
+        drop(locker);
+
+        let array: CellArray<usize, usize, SIZE, false> =
+            CellArray::new(SIZE * 16, Atomic::from(Owned::new(old_array)));
+        let old_array_ref = unsafe { array.old_array(&guard).deref() };
+        let mut num_remaining = keys.len();
+        loop {
+            let mut locker = CellLocker::lock(old_array_ref.cell(0), &guard).unwrap();
+            let killed = array.drain_cell(
+                &mut locker,
+                old_array_ref,
+                0,
+                &hasher,
+                &|_, _| None,
+                RELOCATION_CHUNK_SIZE,
+                &guard,
+            );
+            drop(locker);
+            let old_cell_ref = old_array_ref.cell(0);
+            assert_eq!(old_cell_ref.killed(), killed);
+            assert_eq!(
+                old_cell_ref.num_entries(),
+                num_remaining.saturating_sub(RELOCATION_CHUNK_SIZE)
+            );
+            num_remaining = old_cell_ref.num_entries();
+
+            // Every key is either in the old Cell or in the current array.
+            for key in &keys {
+                let (hash, _) = hasher(key);
+                let in_old_cell = old_cell_ref.search(key, hash, &guard).is_some();
+                let in_array = array
+                    .cell(array.calculate_cell_index(hash))
+                    .search(key, hash, &guard)
+                    .is_some();
+                assert!(in_old_cell ^ in_array);
+            }
+            if killed {
+                break;
+            }
+        }
+        assert_eq!(num_remaining, 0);
+
+        for index in 0..array.array_size() {
+            CellLocker::lock(array.cell(index), &guard)
+                .unwrap()
+                .purge(&guard);
+        }
+        array.drop_old_array(true, &guard);
+    }
+
+    #[test]
+    fn partial_rehash_chunks() {
+        const SIZE: usize = 32;
+        let hasher = |key: &usize| {
+            let hash = (*key as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            (hash, hash as PartialHash)
+        };
+        let guard = crate::ebr::pin();
+        let old_array: CellArray<usize, usize, SIZE, false> =
+            CellArray::new(SIZE * 4, Atomic::null());
+        let keys: Vec<usize> = (0..)
+            .filter(|key| old_array.calculate_cell_index(hasher(key).0) == 0)
+            .take(SIZE * 2 + 3)
+            .collect();
+        let locker = CellLocker::lock(old_array.cell(0), &guard).unwrap();
+        for key in &keys {
+            let result = locker.insert(*key, *key, hasher(key).0, &guard).1;
+            assert!(result.is_none());
+        }
+        drop(locker);
+
+        // The overflowing Cell is relocated a chunk at a time, and stays alive in the meantime.
+        let array: CellArray<usize, usize, SIZE, false> =
+            CellArray::new(SIZE * 16, Atomic::from(Owned::new(old_array)));
+        let old_array_ref = unsafe { array.old_array(&guard).deref() };
+        let old_cell_ref = old_array_ref.cell(0);
+        let mut num_remaining = keys.len();
+        while num_remaining != 0 {
+            assert!(!array.partial_rehash(hasher, |_, _| None, 1, &guard));
+            num_remaining = num_remaining.saturating_sub(RELOCATION_CHUNK_SIZE);
+            assert_eq!(old_cell_ref.killed(), num_remaining == 0);
+            assert_eq!(old_cell_ref.num_entries(), num_remaining);
+            for key in &keys {
+                let (hash, _) = hasher(key);
+                let in_old_cell = old_cell_ref.search(key, hash, &guard).is_some();
+                let in_array = array
+                    .cell(array.calculate_cell_index(hash))
+                    .search(key, hash, &guard)
+                    .is_some();
+                assert!(in_old_cell ^ in_array);
+            }
+        }
+        while !array.partial_rehash(hasher, |_, _| None, 1, &guard) {}
+        assert!(array.old_array(&guard).is_null());
+        for key in &keys {
+            let (hash, _) = hasher(key);
+            assert!(array
+                .cell(array.calculate_cell_index(hash))
+                .search(key, hash, &guard)
+                .is_some());
+        }
+
+        for index in 0..array.array_size() {
+            CellLocker::lock(array.cell(index), &guard)
+                .unwrap()
+                .purge(&guard);
+        }
+    }
+
+    #[test]
+    fn prefetch() {
+        let guard = crate::ebr::pin();
+        let array: CellArray<usize, usize, 32, false> =
+            CellArray::new(32 << (LB_SEGMENT_SIZE + 1), Atomic::null());
+        assert_eq!(array.num_allocated_segments(), 1);
+
+        // Prefetching neither allocates a segment nor dereferences a null DataArray.
+        for index in [0, 1, 1 << LB_SEGMENT_SIZE, array.array_size()] {
+            array.prefetch(index, &guard);
+        }
+        assert_eq!(array.num_allocated_segments(), 1);
+        super::super::cell::prefetch(std::ptr::null::<u8>());
+
+        let locker = CellLocker::lock(array.cell(1), &guard).unwrap();
+        assert!(locker.insert(1, 1, 0, &guard).1.is_none());
+        drop(locker);
+        array.prefetch(1, &guard);
+        assert_eq!(array.cell(1).search(&1, 0, &guard), Some((&1, &1)));
+
+        for index in 0..array.array_size() {
+            if let Some(cell_ref) = array.initialized_cell(index) {
+                CellLocker::lock(cell_ref, &guard).unwrap().purge(&guard);
+            }
         }
     }
 }
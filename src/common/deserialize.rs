@@ -0,0 +1,107 @@
+//! Deserialization of key-value pairs into an existing container.
+
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{DeserializeSeed, Deserializer, Error, MapAccess, Visitor};
+use serde::Deserialize;
+
+/// DuplicateKeyPolicy determines what happens to a deserialized key-value pair of which the key
+/// already exists in the container.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DuplicateKeyPolicy {
+    /// Replace: the existing value is replaced with the deserialized value.
+    #[default]
+    Replace,
+    /// Keep: the existing value is kept, and the deserialized key-value pair is dropped.
+    Keep,
+    /// Reject: deserialization fails, and the existing value is kept.
+    ///
+    /// The key-value pairs deserialized before the duplicate key remain in the container.
+    Reject,
+}
+
+/// The maximum number of bytes reserved in advance for the entries a deserializer claims to have.
+const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+
+/// Returns the number of entries to reserve for, given the size hint of a deserializer.
+///
+/// The hint comes from untrusted input, therefore the reservation is capped in the same way as
+/// `serde` does for its own containers; the container still grows as entries are inserted.
+pub fn cautious_size_hint<T>(hint: Option<usize>) -> usize {
+    let max_entries = MAX_PREALLOC_BYTES / core::mem::size_of::<T>().max(1);
+    hint.unwrap_or(0).min(max_entries)
+}
+
+/// A container that key-value pairs can be deserialized into.
+pub trait DeserializeTarget<K, V> {
+    /// Tries to make room for the given number of additional key-value pairs.
+    fn reserve_for(&self, additional: usize);
+
+    /// Inserts the key-value pair, and returns `false` if the key already exists.
+    ///
+    /// The existing value is replaced only if the policy is [`DuplicateKeyPolicy::Replace`].
+    fn insert_deserialized(&self, key: K, value: V, policy: DuplicateKeyPolicy) -> bool;
+}
+
+/// DeserializeInto is a [`DeserializeSeed`] that deserializes a map into an existing container.
+///
+/// The container keeps its build hasher and configuration, and grows in advance if the
+/// deserializer knows the number of entries. The deserialized value is the number of keys that
+/// already existed in the container.
+pub struct DeserializeInto<'c, C, K, V> {
+    container: &'c C,
+    policy: DuplicateKeyPolicy,
+    _phantom: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'c, C, K, V> DeserializeInto<'c, C, K, V> {
+    /// Creates a DeserializeInto for the container.
+    pub(crate) fn new(container: &'c C, policy: DuplicateKeyPolicy) -> Self {
+        DeserializeInto {
+            container,
+            policy,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'c, 'de, C, K, V> DeserializeSeed<'de> for DeserializeInto<'c, C, K, V>
+where
+    C: DeserializeTarget<K, V>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'c, 'de, C, K, V> Visitor<'de> for DeserializeInto<'c, C, K, V>
+where
+    C: DeserializeTarget<K, V>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<usize, M::Error> {
+        self.container
+            .reserve_for(cautious_size_hint::<(K, V)>(map.size_hint()));
+        let mut num_duplicates = 0;
+        while let Some((key, value)) = map.next_entry()? {
+            if !self.container.insert_deserialized(key, value, self.policy) {
+                if self.policy == DuplicateKeyPolicy::Reject {
+                    return Err(M::Error::custom("duplicate key"));
+                }
+                num_duplicates += 1;
+            }
+        }
+        Ok(num_duplicates)
+    }
+}
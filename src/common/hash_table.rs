@@ -1,166 +1,954 @@
-use super::cell::Cell;
+use super::allocator::ArrayAllocator;
+use super::cell::{deferred_bytes, partial_hash, Cell, CellIterator, CellLocker, PartialHash};
 use super::cell_array::CellArray;
-use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
-use std::borrow::Borrow;
-use std::convert::TryInto;
-use std::hash::{BuildHasher, Hash, Hasher};
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+#[cfg(feature = "background-worker")]
+use super::maintenance::Signal;
+#[cfg(feature = "metrics")]
+use super::metrics::{Counter, Counters, GroupMetrics};
+use super::sync::{spin_loop, AtomicBool, AtomicU64};
+use super::trace::trace_event;
+use crate::ebr::{Atomic, Guard, Owned, Shared};
+use core::alloc::Layout;
+use core::borrow::Borrow;
+use core::fmt;
+#[cfg(not(feature = "std"))]
+use core::hash::Hasher;
+use core::hash::{BuildHasher, Hash};
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// The default maximum load factor.
+pub const DEFAULT_MAX_LOAD_FACTOR: f32 = 0.875;
+
+/// The default maximum resizing factor for confident size estimates.
+pub const DEFAULT_MAX_RESIZING_FACTOR: usize = 1024;
+
+/// The minimum number of sampled entries for a size estimate to be confident.
+const CONFIDENT_SAMPLE_SIZE: usize = 1024;
+
+/// The maximum number of times that resizing yields to threads relocating the last Cells in the
+/// old array.
+pub const MAX_REHASH_WAITS: usize = 64;
+
+/// The maximum number of times that a thread backs off before giving up on the resizing flag.
+#[cfg(not(loom))]
+const MAX_RESIZING_FLAG_BACKOFFS: u32 = 6;
+
+/// Backing off only multiplies the interleavings that the model checker explores.
+#[cfg(loom)]
+const MAX_RESIZING_FLAG_BACKOFFS: u32 = 0;
+
+/// TryReserveError is returned when the capacity of a container cannot be increased.
+///
+/// The container is left unchanged when the error is returned.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TryReserveError {
+    /// CapacityOverflow: the required capacity exceeds the maximum capacity.
+    CapacityOverflow,
+    /// AllocError: the memory allocator failed to allocate memory of the given layout.
+    AllocError {
+        /// The layout of the allocation request that failed.
+        layout: Layout,
+    },
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => {
+                write!(f, "the required capacity exceeds the maximum capacity")
+            }
+            TryReserveError::AllocError { layout } => {
+                write!(f, "memory allocation of {} bytes failed", layout.size())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryReserveError {}
+
+/// The default [`BuildHasher`] of the containers.
+#[cfg(all(feature = "std", not(feature = "fast-hash")))]
+pub type DefaultBuildHasher = std::collections::hash_map::RandomState;
+
+/// The default [`BuildHasher`] of the containers with the `fast-hash` feature.
+#[cfg(feature = "fast-hash")]
+pub type DefaultBuildHasher = foldhash::fast::RandomState;
+
+/// Returns `true` if the default [`BuildHasher`] needs the bits of its hash values mixed.
+///
+/// foldhash already spreads the bits of the key over the whole hash value.
+#[cfg(feature = "std")]
+pub const DEFAULT_HASH_FINALIZER: bool = !cfg!(feature = "fast-hash");
+
+/// The default [`BuildHasher`] of the containers without the standard library.
+#[cfg(not(feature = "std"))]
+pub type DefaultBuildHasher = NoDefaultBuildHasher;
+
+/// NoDefaultBuildHasher stands in for the default [`BuildHasher`] without the standard library.
+///
+/// It cannot be instantiated, therefore a container without the standard library has to be
+/// given a [`BuildHasher`] explicitly.
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy, Debug)]
+pub enum NoDefaultBuildHasher {}
+
+#[cfg(not(feature = "std"))]
+impl BuildHasher for NoDefaultBuildHasher {
+    type Hasher = NoDefaultBuildHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Hasher for NoDefaultBuildHasher {
+    fn finish(&self) -> u64 {
+        match *self {}
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {
+        match *self {}
+    }
+}
+
+/// ShrinkPolicy determines when a container considers shrinking its array.
+///
+/// The array shrinks to fit when the estimated load factor is equal to or smaller than 1/16 at
+/// the time shrinking is considered.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ShrinkPolicy {
+    /// Never: the array never shrinks.
+    Never,
+    /// OnInsertSampling: shrinking is considered when the array is resized after sampling Cells,
+    /// that is, when an insertion finds a full Cell or a removal empties a sampled Cell.
+    #[default]
+    OnInsertSampling,
+    /// Aggressive: in addition to [`ShrinkPolicy::OnInsertSampling`], shrinking is considered
+    /// after every `idle_ops` removals.
+    Aggressive {
+        /// The number of removals between evaluations.
+        idle_ops: usize,
+    },
+}
+
+/// MemoryFootprint is a breakdown of the heap memory used by a HashMap or a HashIndex.
+///
+/// The numbers are computed from the layout of the allocated memory, and do not include memory
+/// that the keys and values own.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MemoryFootprint {
+    current_array_bytes: usize,
+    old_array_bytes: usize,
+    overflow_bytes: usize,
+    estimated_deferred_bytes: usize,
+}
+
+impl MemoryFootprint {
+    /// Returns the number of bytes used by the current array, including the first DataArray of
+    /// each non-empty Cell.
+    pub fn current_array_bytes(&self) -> usize {
+        self.current_array_bytes
+    }
+
+    /// Returns the number of bytes used by the old array that is being relocated.
+    pub fn old_array_bytes(&self) -> usize {
+        self.old_array_bytes
+    }
+
+    /// Returns the number of bytes used by DataArrays linked to a Cell because of hash
+    /// collisions.
+    pub fn overflow_bytes(&self) -> usize {
+        self.overflow_bytes
+    }
+
+    /// Returns the number of bytes that have been retired but not yet reclaimed.
+    ///
+    /// Retired memory is reclaimed once no thread may be reading it. The number is shared by all
+    /// the HashMap and HashIndex instances in the process, and is therefore only an estimate of
+    /// the memory that the container is holding on to.
+    pub fn estimated_deferred_bytes(&self) -> usize {
+        self.estimated_deferred_bytes
+    }
+
+    /// Returns the sum of all the numbers.
+    pub fn total_bytes(&self) -> usize {
+        self.current_array_bytes
+            + self.old_array_bytes
+            + self.overflow_bytes
+            + self.estimated_deferred_bytes
+    }
+}
+
+/// ResizingFlag allows only a single thread to resize the array at a time.
+///
+/// It occupies a dedicated cache line, so that threads contending for the flag do not invalidate
+/// the cache line that the array pointer and the other read-mostly fields are on.
+#[cfg_attr(target_arch = "aarch64", repr(align(128)))]
+#[cfg_attr(not(target_arch = "aarch64"), repr(align(64)))]
+#[derive(Default)]
+pub struct ResizingFlag {
+    acquired: AtomicBool,
+}
+
+impl ResizingFlag {
+    /// Tries to acquire the flag, and returns `true` if the flag was acquired.
+    ///
+    /// The thread backs off exponentially while another thread holds the flag, and only
+    /// attempts to take the flag once it has been observed free, so that threads failing to
+    /// acquire the flag do not keep taking the cache line exclusively. A thread that loses the
+    /// race for the free flag gives up at once without writing the flag.
+    pub fn try_acquire(&self) -> bool {
+        for num_backoffs in 0..=MAX_RESIZING_FLAG_BACKOFFS {
+            if !self.acquired.load(Relaxed) {
+                return self
+                    .acquired
+                    .compare_exchange(false, true, Acquire, Relaxed)
+                    .is_ok();
+            }
+            if num_backoffs != MAX_RESIZING_FLAG_BACKOFFS {
+                for _ in 0..(1_u32 << num_backoffs) {
+                    spin_loop();
+                }
+            }
+        }
+        false
+    }
+
+    /// Releases the flag.
+    ///
+    /// The flag is released with the release ordering regardless of whether the array was
+    /// replaced, so that the next thread acquiring the flag sees the resize history as updated
+    /// by the previous one.
+    pub fn release(&self) {
+        self.acquired.store(false, Release);
+    }
+
+    /// Waits for the flag to be released up to the given number of times, and returns `true` if
+    /// the flag was observed released.
+    ///
+    /// The thread spins with exponential backoff at first, and then yields.
+    pub fn wait(&self, max_waits: usize) -> bool {
+        for num_waits in 0..max_waits {
+            if !self.acquired.load(Acquire) {
+                return true;
+            }
+            if num_waits < MAX_RESIZING_FLAG_BACKOFFS as usize {
+                for _ in 0..(1_u32 << num_waits) {
+                    spin_loop();
+                }
+            } else {
+                super::yield_now();
+            }
+        }
+        !self.acquired.load(Acquire)
+    }
+}
+
+/// ResizeHistory remembers the direction and the time of the last resize.
+///
+/// It is only updated by the thread that holds the resizing flag. Without the standard library
+/// there is no clock to measure the interval, therefore no resize is dampened.
+pub struct ResizeHistory {
+    #[cfg(feature = "std")]
+    epoch: Instant,
+    /// The number of microseconds from `epoch` to the last resize in the upper bits, and the
+    /// direction of the last resize in the lower two bits.
+    last_resize: AtomicU64,
+}
+
+impl ResizeHistory {
+    /// The array grew.
+    const GREW: u64 = 1;
+    /// The array shrank.
+    const SHRANK: u64 = 2;
+
+    /// Returns `true` if resizing the array from `capacity` to `new_capacity` does not reverse
+    /// the last resize within `min_interval`.
+    fn permits(&self, capacity: usize, new_capacity: usize, min_interval: Duration) -> bool {
+        if min_interval.is_zero() || cfg!(not(feature = "std")) {
+            return true;
+        }
+        let last_resize = self.last_resize.load(Relaxed);
+        let last_direction = last_resize & 3;
+        if last_direction == 0 || last_direction == Self::direction(capacity, new_capacity) {
+            return true;
+        }
+        self.elapsed_micros().saturating_sub(last_resize >> 2) >= min_interval.as_micros() as u64
+    }
+
+    /// Records a resize from `capacity` to `new_capacity`.
+    fn record(&self, capacity: usize, new_capacity: usize) {
+        self.last_resize.store(
+            (self.elapsed_micros() << 2) | Self::direction(capacity, new_capacity),
+            Relaxed,
+        );
+    }
+
+    /// Returns the number of microseconds elapsed since `epoch`.
+    #[cfg(feature = "std")]
+    fn elapsed_micros(&self) -> u64 {
+        self.epoch.elapsed().as_micros() as u64
+    }
+
+    /// Returns zero as there is no clock without the standard library.
+    #[cfg(not(feature = "std"))]
+    fn elapsed_micros(&self) -> u64 {
+        0
+    }
+
+    /// Returns the direction of a resize.
+    fn direction(capacity: usize, new_capacity: usize) -> u64 {
+        if new_capacity > capacity {
+            Self::GREW
+        } else {
+            Self::SHRANK
+        }
+    }
+}
+
+impl Default for ResizeHistory {
+    fn default() -> Self {
+        ResizeHistory {
+            #[cfg(feature = "std")]
+            epoch: Instant::now(),
+            last_resize: AtomicU64::new(0),
+        }
+    }
+}
 
 /// `HashTable` define common functions for `HashIndex` and `HashMap`.
-pub trait HashTable<K, V, H, const CELL_SIZE: usize, const LOCK_FREE: bool>
+pub trait HashTable<K, V, H, const CELL_SIZE: usize, const LOCK_FREE: bool, A>
 where
-    K: Eq + Hash + Sync,
-    V: Sync,
+    K: Eq + Hash,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
     /// Returns the hash value of the given key.
-    fn hash<Q>(&self, key: &Q) -> (u64, u8)
+    fn hash<Q>(&self, key: &Q) -> (u64, PartialHash)
     where
         K: Borrow<Q>,
         Q: Hash + ?Sized,
     {
-        // Generates a hash value.
-        let mut h = self.hasher().build_hasher();
-        key.hash(&mut h);
-        let mut hash = h.finish();
+        self.finalize(self.hasher().hash_one(key))
+    }
 
+    /// Derives a partial hash value from the given hash value, mixing its bits beforehand unless
+    /// the hash finalizer is disabled.
+    #[inline]
+    fn finalize(&self, hash: u64) -> (u64, PartialHash) {
+        if self.hash_finalizer() {
+            Self::mix(hash)
+        } else {
+            (hash, partial_hash(hash))
+        }
+    }
+
+    /// Mixes the bits of the given hash value, and derives a partial hash value from it.
+    fn mix(mut hash: u64) -> (u64, PartialHash) {
         // Bitmix: https://mostlymangling.blogspot.com/2019/01/better-stronger-mixer-and-test-procedure.html
         hash = hash ^ (hash.rotate_right(25) ^ hash.rotate_right(50));
         hash = hash.overflowing_mul(0xA24BAED4963EE407u64).0;
         hash = hash ^ (hash.rotate_right(24) ^ hash.rotate_right(49));
         hash = hash.overflowing_mul(0x9FB21C651E98DF25u64).0;
         hash = hash ^ (hash >> 28);
-        (hash, (hash & ((1 << 8) - 1)).try_into().unwrap())
+        (hash, partial_hash(hash))
     }
 
     /// Returns a reference to its build hasher.
     fn hasher(&self) -> &H;
 
+    /// Copies the entry when it is relocated to the new array.
+    ///
+    /// Entries that are not copied are moved.
+    fn copy_entry(key: &K, value: &V) -> Option<(K, V)>;
+
     /// Returns a reference to the `CellArray` pointer.
-    fn cell_array_ptr(&self) -> &Atomic<CellArray<K, V, CELL_SIZE, LOCK_FREE>>;
+    fn cell_array_ptr(&self) -> &Atomic<CellArray<K, V, CELL_SIZE, LOCK_FREE, A>>;
 
     /// Returns a reference to the `CellArray` instance.
     fn cell_array_ref(
-        cell_array_shared: Shared<CellArray<K, V, CELL_SIZE, LOCK_FREE>>,
-    ) -> &CellArray<K, V, CELL_SIZE, LOCK_FREE> {
+        cell_array_shared: Shared<'_, CellArray<K, V, CELL_SIZE, LOCK_FREE, A>>,
+    ) -> &'_ CellArray<K, V, CELL_SIZE, LOCK_FREE, A> {
         unsafe { cell_array_shared.deref() }
     }
 
+    /// Returns a shared pointer to the current array, allocating the array if it has yet to be
+    /// allocated.
+    ///
+    /// It panics if memory allocation fails.
+    fn current_array_or_alloc<'g>(
+        &self,
+        guard: &'g Guard,
+    ) -> Shared<'g, CellArray<K, V, CELL_SIZE, LOCK_FREE, A>> {
+        let current_array = self.cell_array_ptr().load(Acquire, guard);
+        if current_array.is_null() {
+            self.alloc_array(guard)
+        } else {
+            current_array
+        }
+    }
+
+    /// Allocates the array of the minimum capacity unless another thread has allocated it.
+    #[cold]
+    fn alloc_array<'g>(
+        &self,
+        guard: &'g Guard,
+    ) -> Shared<'g, CellArray<K, V, CELL_SIZE, LOCK_FREE, A>> {
+        let new_array = Owned::new(CellArray::<K, V, CELL_SIZE, LOCK_FREE, A>::new(
+            self.minimum_capacity(),
+            Atomic::null(),
+        ));
+        match self.cell_array_ptr().compare_exchange(
+            Shared::null(),
+            new_array,
+            AcqRel,
+            Acquire,
+            guard,
+        ) {
+            Ok(new_array) => new_array,
+            Err(error) => error.current,
+        }
+    }
+
     /// Returns the minimum allowed capacity.
+    ///
+    /// It includes the capacity reserved by [`HashTable::try_grow`].
     fn minimum_capacity(&self) -> usize;
 
+    /// Returns a reference to the capacity reserved by [`HashTable::try_grow`].
+    fn reserved_capacity_ref(&self) -> &AtomicUsize;
+
     /// Returns a reference to the resizing flag.
-    fn resizing_flag_ref(&self) -> &AtomicBool;
+    fn resizing_flag_ref(&self) -> &ResizingFlag;
+
+    /// Returns the maximum load factor.
+    fn max_load_factor(&self) -> f32 {
+        DEFAULT_MAX_LOAD_FACTOR
+    }
+
+    /// Returns the number of Cells in the old array that each operation relocates in passing.
+    fn rehash_budget(&self) -> usize;
+
+    /// Returns a reference to the resize history.
+    fn resize_history(&self) -> &ResizeHistory;
+
+    /// Returns the minimum interval between resizes in opposite directions.
+    fn resize_dampening(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// Returns the maximum resizing factor for confident size estimates.
+    fn max_resizing_factor(&self) -> usize {
+        DEFAULT_MAX_RESIZING_FACTOR
+    }
+
+    /// Returns the shrink policy.
+    fn shrink_policy(&self) -> ShrinkPolicy {
+        ShrinkPolicy::OnInsertSampling
+    }
+
+    /// Returns `true` if the bits of hash values are mixed before use.
+    fn hash_finalizer(&self) -> bool {
+        true
+    }
+
+    /// Returns a reference to the operation counters.
+    #[cfg(feature = "metrics")]
+    fn counters(&self) -> &Counters;
+
+    /// Returns a reference to the Signal that wakes up maintenance workers if there is one.
+    #[cfg(feature = "background-worker")]
+    fn signal(&self) -> Option<&Signal> {
+        None
+    }
+
+    /// Pins the current thread in the reclamation domain of the container.
+    fn pin(&self) -> Guard {
+        crate::ebr::pin()
+    }
 
     /// Returns the number of entries.
     fn num_entries(&self) -> usize {
-        let guard = crossbeam_epoch::pin();
+        let guard = self.pin();
         let current_array = self.cell_array_ptr().load(Acquire, &guard);
-        let current_array_ref = Self::cell_array_ref(current_array);
+        let current_array_ref = match unsafe { current_array.as_ref() } {
+            Some(current_array_ref) => current_array_ref,
+            None => return 0,
+        };
         let mut num_entries = 0;
         for i in 0..current_array_ref.array_size() {
-            num_entries += current_array_ref.cell(i).num_entries();
+            num_entries += current_array_ref
+                .initialized_cell(i)
+                .map_or(0, Cell::num_entries);
         }
         let old_array = current_array_ref.old_array(&guard);
         if !old_array.is_null() {
             let old_array_ref = Self::cell_array_ref(old_array);
             for i in 0..old_array_ref.array_size() {
-                num_entries += old_array_ref.cell(i).num_entries();
+                num_entries += old_array_ref
+                    .initialized_cell(i)
+                    .map_or(0, Cell::num_entries);
             }
         }
         num_entries
     }
 
+    /// Measures the memory footprint.
+    fn measure_memory(&self) -> MemoryFootprint {
+        let guard = self.pin();
+        let current_array = self.cell_array_ptr().load(Acquire, &guard);
+        let (current_array_bytes, mut overflow_bytes) = unsafe { current_array.as_ref() }
+            .map_or((0, 0), |current_array_ref| {
+                current_array_ref.memory_usage(&guard)
+            });
+        let mut old_array_bytes = 0;
+        let old_array = unsafe { current_array.as_ref() }
+            .map_or_else(Shared::null, |current_array_ref| {
+                current_array_ref.old_array(&guard)
+            });
+        if !old_array.is_null() {
+            let (array_bytes, old_overflow_bytes) =
+                Self::cell_array_ref(old_array).memory_usage(&guard);
+            old_array_bytes = array_bytes;
+            overflow_bytes += old_overflow_bytes;
+        }
+        MemoryFootprint {
+            current_array_bytes,
+            old_array_bytes,
+            overflow_bytes,
+            estimated_deferred_bytes: deferred_bytes(),
+        }
+    }
+
+    /// Takes a snapshot of the counters of the groups of Cells in the current array.
+    #[cfg(feature = "metrics")]
+    fn group_metrics(&self) -> Vec<GroupMetrics> {
+        let guard = self.pin();
+        let current_array = self.cell_array_ptr().load(Acquire, &guard);
+        unsafe { current_array.as_ref() }.map_or_else(Vec::new, CellArray::group_metrics)
+    }
+
+    /// Resets the counters of the groups of Cells in the current array.
+    #[cfg(feature = "metrics")]
+    fn reset_group_metrics(&self) {
+        let guard = self.pin();
+        let current_array = self.cell_array_ptr().load(Acquire, &guard);
+        if let Some(current_array_ref) = unsafe { current_array.as_ref() } {
+            current_array_ref.reset_group_counters();
+        }
+    }
+
     /// Returns the number of slots.
+    ///
+    /// It returns the minimum capacity if the array has yet to be allocated.
     fn num_slots(&self) -> usize {
-        let guard = crossbeam_epoch::pin();
+        let guard = self.pin();
         let current_array = self.cell_array_ptr().load(Acquire, &guard);
-        let current_array_ref = Self::cell_array_ref(current_array);
-        current_array_ref.num_cell_entries()
+        unsafe { current_array.as_ref() }
+            .map_or_else(|| self.minimum_capacity(), CellArray::num_cell_entries)
     }
 
-    /// Estimates the number of entries using the given number of cells.
-    fn estimate(
-        array_ref: &CellArray<K, V, CELL_SIZE, LOCK_FREE>,
+    /// Samples the given number of Cells with a stride across the array.
+    ///
+    /// It returns the number of sampled entries that count towards the load factor, the number of
+    /// all the sampled entries, and the number of sampled Cells that overflow.
+    fn sample_cells(
+        array_ref: &CellArray<K, V, CELL_SIZE, LOCK_FREE, A>,
         num_cells_to_sample: usize,
-    ) -> usize {
+    ) -> (usize, usize, usize) {
+        let stride = array_ref.array_size() / num_cells_to_sample;
+        let mut num_load_entries = 0;
         let mut num_entries = 0;
+        let mut num_overflowing_cells = 0;
         for i in 0..num_cells_to_sample {
-            num_entries += array_ref.cell(i).num_entries();
+            let num_cell_entries = array_ref
+                .initialized_cell(i * stride)
+                .map_or(0, Cell::num_entries);
+            num_load_entries += num_cell_entries.min(CELL_SIZE);
+            num_entries += num_cell_entries;
+            if num_cell_entries > CELL_SIZE {
+                num_overflowing_cells += 1;
+            }
         }
-        num_entries * (array_ref.array_size() / num_cells_to_sample)
+        (num_load_entries, num_entries, num_overflowing_cells)
+    }
+
+    /// Estimates the number of entries in the array and its old array using the given number of
+    /// cells.
+    ///
+    /// The first estimate does not count entries overflowing a Cell, so that it reflects the
+    /// global load factor rather than a local hash collision. The second estimate counts them,
+    /// and is only returned if the sample is confident: the sampled Cells in the array contain at
+    /// least `CONFIDENT_SAMPLE_SIZE` entries and the majority of them overflow, in which case the
+    /// overflowing entries reflect a global shortage of capacity.
+    fn estimate(
+        array_ref: &CellArray<K, V, CELL_SIZE, LOCK_FREE, A>,
+        num_cells_to_sample: usize,
+        guard: &Guard,
+    ) -> (usize, Option<usize>) {
+        let scale = array_ref.array_size() / num_cells_to_sample;
+        let (num_load_entries, num_entries, num_overflowing_cells) =
+            Self::sample_cells(array_ref, num_cells_to_sample);
+        let confident =
+            num_entries >= CONFIDENT_SAMPLE_SIZE && num_overflowing_cells * 2 > num_cells_to_sample;
+        let mut estimated_num_load_entries = num_load_entries * scale;
+        let mut estimated_num_entries = num_entries.saturating_mul(scale);
+
+        let old_array = array_ref.old_array(guard);
+        if !old_array.is_null() {
+            // Cells in the old array that have been relocated are empty.
+            let old_array_ref = Self::cell_array_ref(old_array);
+            let num_old_cells_to_sample = num_cells_to_sample.min(old_array_ref.array_size());
+            let old_scale = old_array_ref.array_size() / num_old_cells_to_sample;
+            let (num_load_entries, num_entries, _) =
+                Self::sample_cells(old_array_ref, num_old_cells_to_sample);
+            estimated_num_load_entries += num_load_entries * old_scale;
+            estimated_num_entries =
+                estimated_num_entries.saturating_add(num_entries.saturating_mul(old_scale));
+        }
+        (
+            estimated_num_load_entries,
+            confident.then_some(estimated_num_entries),
+        )
+    }
+
+    /// Relocates all the Cells in the old array, and returns `true` if the old array has been
+    /// dropped.
+    ///
+    /// It yields while other threads are relocating the last Cells, and gives up after a bounded
+    /// number of attempts.
+    fn rehash_old_array(
+        &self,
+        array_ref: &CellArray<K, V, CELL_SIZE, LOCK_FREE, A>,
+        guard: &Guard,
+    ) -> bool {
+        let mut num_waits = 0;
+        while !array_ref.partial_rehash(
+            |key: &K| self.hash(key),
+            Self::copy_entry,
+            CELL_SIZE,
+            guard,
+        ) {
+            if array_ref.all_cells_claimed(guard) {
+                if num_waits == MAX_REHASH_WAITS {
+                    return false;
+                }
+                num_waits += 1;
+                super::yield_now();
+            }
+        }
+        true
+    }
+
+    /// Inserts a key-value pair into the locked Cell.
+    ///
+    /// An event is emitted if the Cell has to link a new DataArray to store the key-value pair.
+    fn insert_into_cell<'g>(
+        &self,
+        cell_locker: &'g CellLocker<'g, K, V, CELL_SIZE, LOCK_FREE, A>,
+        key: K,
+        value: V,
+        hash: u64,
+        guard: &'g Guard,
+    ) -> (
+        CellIterator<'g, K, V, CELL_SIZE, LOCK_FREE, A>,
+        Option<(K, V)>,
+    ) {
+        #[cfg(feature = "tracing")]
+        let num_data_arrays = cell_locker.cell_ref().num_data_arrays(guard);
+        let result = cell_locker.insert(key, value, hash, guard);
+        // An empty Cell allocating its first DataArray does not overflow.
+        #[cfg(feature = "tracing")]
+        if num_data_arrays != 0 && cell_locker.cell_ref().num_data_arrays(guard) > num_data_arrays {
+            trace_event!(
+                TRACE,
+                container = super::trace::address(self),
+                num_data_arrays = num_data_arrays + 1,
+                "cell overflow"
+            );
+        }
+        result
     }
 
     /// Resizes the array.
     fn resize(&self, guard: &Guard) {
-        // Initial rough size estimation using a small number of cells.
         let current_array = self.cell_array_ptr().load(Acquire, guard);
-        let current_array_ref = Self::cell_array_ref(current_array);
-        let old_array = current_array_ref.old_array(&guard);
-        if !old_array.is_null() {
-            // With a deprecated array present, it cannot be resized.
+        let current_array_ref = match unsafe { current_array.as_ref() } {
+            Some(current_array_ref) => current_array_ref,
+            None => return,
+        };
+        if !current_array_ref.old_array(guard).is_null()
+            && !self.rehash_old_array(current_array_ref, guard)
+        {
+            // A CellArray keeps a single old array, and therefore the old array has to be
+            // dropped before the array is resized again.
             return;
         }
 
-        if !self.resizing_flag_ref().swap(true, Acquire) {
-            let memory_ordering = Relaxed;
-            let mut mutex_guard = scopeguard::guard(memory_ordering, |memory_ordering| {
-                self.resizing_flag_ref().store(false, memory_ordering);
+        if self.resizing_flag_ref().try_acquire() {
+            let _resizing_flag_guard = scopeguard::guard((), |()| {
+                self.resizing_flag_ref().release();
             });
             if current_array != self.cell_array_ptr().load(Acquire, guard) {
                 return;
             }
 
             // The resizing policies are as follows.
-            //  - The load factor reaches 7/8, then the array grows up to 64x.
-            //  - The load factor reaches 1/16, then the array shrinks to fit.
+            //  - The load factor reaches the maximum load factor, 7/8 by default, then the
+            //    array grows up to 64x, or up to the maximum resizing factor if the size
+            //    estimate is confident.
+            //  - The load factor reaches 1/16, then the array shrinks to fit unless the shrink
+            //    policy is Never.
+            //  - The array is not resized in the opposite direction of the last resize within
+            //    the resize dampening interval.
             let capacity = current_array_ref.num_cell_entries();
             let num_cells = current_array_ref.array_size();
-            let num_cells_to_sample = (num_cells / 8).max(2).min(4096);
-            let estimated_num_entries = Self::estimate(current_array_ref, num_cells_to_sample);
-            let new_capacity = if estimated_num_entries >= (capacity / 8) * 7 {
-                let max_capacity = 1usize << (std::mem::size_of::<usize>() * 8 - 1);
-                if capacity == max_capacity {
+            let num_cells_to_sample = (num_cells / 8).clamp(2, 4096).min(num_cells);
+            let conservative_resizing_factor = (1_usize
+                << Cell::<K, V, CELL_SIZE, LOCK_FREE, A>::max_resizing_factor())
+            .min(self.max_resizing_factor());
+            let (estimated_num_entries, max_resizing_factor) =
+                match Self::estimate(current_array_ref, num_cells_to_sample, guard) {
+                    (_, Some(estimated_num_entries)) => {
+                        (estimated_num_entries, self.max_resizing_factor())
+                    }
+                    (estimated_num_entries, None) => {
+                        (estimated_num_entries, conservative_resizing_factor)
+                    }
+                };
+            let max_load_factor = f64::from(self.max_load_factor());
+            let new_capacity = if estimated_num_entries as f64 >= capacity as f64 * max_load_factor
+            {
+                let max_capacity = CellArray::<K, V, CELL_SIZE, LOCK_FREE, A>::max_capacity();
+                if capacity >= max_capacity {
                     // Do not resize if the capacity cannot be increased.
                     capacity
                 } else {
+                    // The target capacity is the estimated number of entries * 15/8, scaled
+                    // by the ratio of the default maximum load factor to the maximum load factor.
+                    let target_capacity = estimated_num_entries as f64 * 15.0 / 8.0
+                        * f64::from(DEFAULT_MAX_LOAD_FACTOR)
+                        / max_load_factor;
                     let mut new_capacity = capacity;
-                    while new_capacity < (estimated_num_entries / 8) * 15 {
-                        // Doubles the new capacity until it can accommodate the estimated number of entries * 15/8.
-                        if new_capacity == max_capacity {
+                    while (new_capacity as f64) < target_capacity {
+                        // Doubles the new capacity until it can accommodate the target capacity.
+                        if new_capacity >= max_capacity {
                             break;
                         }
-                        if new_capacity / capacity
-                            >= Cell::<K, V, CELL_SIZE, LOCK_FREE>::max_resizing_factor()
-                        {
+                        if new_capacity / capacity >= max_resizing_factor {
                             break;
                         }
                         new_capacity *= 2;
                     }
                     new_capacity
                 }
-            } else if estimated_num_entries <= capacity / 16 {
+            } else if self.shrink_policy() != ShrinkPolicy::Never
+                && estimated_num_entries <= capacity / 16
+            {
                 // Shrinks to fit.
                 estimated_num_entries
                     .next_power_of_two()
                     .max(self.minimum_capacity())
+                    .min(CellArray::<K, V, CELL_SIZE, LOCK_FREE, A>::max_capacity())
             } else {
                 capacity
             };
 
             // Array::new may not be able to allocate the requested number of cells.
-            if new_capacity != capacity {
-                self.cell_array_ptr().store(
-                    Owned::new(CellArray::<K, V, CELL_SIZE, LOCK_FREE>::new(
-                        new_capacity,
-                        Atomic::from(current_array),
-                    )),
-                    Release,
+            if new_capacity != capacity
+                && self
+                    .resize_history()
+                    .permits(capacity, new_capacity, self.resize_dampening())
+            {
+                self.resize_history().record(capacity, new_capacity);
+                let new_array = CellArray::<K, V, CELL_SIZE, LOCK_FREE, A>::new(
+                    new_capacity,
+                    Atomic::from(current_array),
+                )
+                .with_max_growth_ratio(max_resizing_factor.next_power_of_two());
+                #[cfg(feature = "tracing")]
+                let new_array = new_array.with_container(super::trace::address(self));
+                trace_event!(
+                    DEBUG,
+                    container = super::trace::address(self),
+                    old_capacity = capacity,
+                    new_capacity = new_array.num_cell_entries(),
+                    reason = if new_capacity > capacity {
+                        "grow"
+                    } else {
+                        "shrink"
+                    },
+                    "resize started"
+                );
+                self.cell_array_ptr().store(Owned::new(new_array), Release);
+                #[cfg(feature = "metrics")]
+                self.counters().add(Counter::Resizes, 1);
+                #[cfg(feature = "background-worker")]
+                if let Some(signal) = self.signal() {
+                    signal.notify_resized();
+                }
+            }
+        } else if self.rehash_budget() != 0 {
+            // Another thread is deciding whether to replace the array. Instead of leaving the
+            // relocation to the threads that happen to access the Cells, the thread waits for the
+            // decision, and then helps relocate a chunk of the rehash budget; the Cells are
+            // claimed by an atomic cursor, and the thread relocating the last Cell drops the old
+            // array.
+            self.resizing_flag_ref().wait(MAX_REHASH_WAITS);
+            let new_array = self.cell_array_ptr().load(Acquire, guard);
+            if new_array != current_array {
+                Self::cell_array_ref(new_array).partial_rehash(
+                    |key: &K| self.hash(key),
+                    Self::copy_entry,
+                    self.rehash_budget(),
+                    guard,
                 );
-                // The release fence assures that future calls to the function see the latest state.
-                *mutex_guard = Release;
             }
         }
     }
+
+    /// Grows the array so that it can accommodate the given number of additional entries.
+    ///
+    /// The array grows by at most the conservative resizing factor at a time, and the old array
+    /// is relocated before the next step, so that relocating a Cell locks a bounded number of
+    /// Cells in the new array. The target capacity is kept as the minimum capacity once it has
+    /// been reached. If an allocation fails, the steps taken so far are kept.
+    fn try_grow<C: Fn(&K, &V) -> Option<(K, V)>>(
+        &self,
+        additional: usize,
+        copier: C,
+    ) -> Result<(), TryReserveError> {
+        let required_capacity = self
+            .num_entries()
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        let target_capacity = required_capacity as f64 / f64::from(self.max_load_factor());
+        let max_capacity = CellArray::<K, V, CELL_SIZE, LOCK_FREE, A>::max_capacity();
+        if target_capacity > max_capacity as f64 {
+            return Err(TryReserveError::CapacityOverflow);
+        }
+        // The minimum capacity may exceed the maximum capacity that the array can reach.
+        let target_capacity = (target_capacity.ceil() as usize)
+            .max(self.minimum_capacity())
+            .min(max_capacity);
+        let max_growth_ratio =
+            1_usize << Cell::<K, V, CELL_SIZE, LOCK_FREE, A>::max_resizing_factor();
+
+        let guard = self.pin();
+        loop {
+            let current_array = self.current_array_or_alloc(&guard);
+            let current_array_ref = Self::cell_array_ref(current_array);
+            let current_capacity = current_array_ref.num_cell_entries();
+            if current_capacity >= target_capacity {
+                // Keeps the array from shrinking below the reserved capacity.
+                self.reserved_capacity_ref()
+                    .fetch_max(target_capacity, Relaxed);
+                return Ok(());
+            }
+            if !current_array_ref.old_array(&guard).is_null() {
+                // The old array has to be rehashed before the array is replaced, and other threads
+                // may be relocating the last Cells of it.
+                if !current_array_ref.partial_rehash(
+                    |key: &K| self.hash(key),
+                    &copier,
+                    CELL_SIZE,
+                    &guard,
+                ) {
+                    super::yield_now();
+                }
+                continue;
+            }
+            if !self.resizing_flag_ref().try_acquire() {
+                super::yield_now();
+                continue;
+            }
+            let _resizing_flag_guard = scopeguard::guard((), |()| {
+                self.resizing_flag_ref().release();
+            });
+            if current_array != self.cell_array_ptr().load(Acquire, &guard) {
+                continue;
+            }
+            let step_capacity =
+                target_capacity.min(current_capacity.saturating_mul(max_growth_ratio));
+            let new_array = CellArray::<K, V, CELL_SIZE, LOCK_FREE, A>::try_new(
+                step_capacity,
+                Atomic::from(current_array),
+            )?;
+            self.resize_history()
+                .record(current_capacity, new_array.num_cell_entries());
+            #[cfg(feature = "tracing")]
+            let new_array = new_array.with_container(super::trace::address(self));
+            trace_event!(
+                DEBUG,
+                container = super::trace::address(self),
+                old_capacity = current_capacity,
+                new_capacity = new_array.num_cell_entries(),
+                reason = "reserve",
+                "resize started"
+            );
+            self.cell_array_ptr().store(Owned::new(new_array), Release);
+            #[cfg(feature = "metrics")]
+            self.counters().add(Counter::Resizes, 1);
+            #[cfg(feature = "background-worker")]
+            if let Some(signal) = self.signal() {
+                signal.notify_resized();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn resizing_flag() {
+        assert_eq!(
+            std::mem::align_of::<ResizingFlag>(),
+            std::mem::size_of::<ResizingFlag>()
+        );
+
+        let flag = ResizingFlag::default();
+        assert!(flag.try_acquire());
+        assert!(!flag.try_acquire());
+        assert!(!flag.wait(4));
+        flag.release();
+        assert!(flag.wait(1));
+
+        // The flag protects a non-atomic read-modify-write of the counter.
+        let num_threads = 4;
+        let num_iterations = 4096;
+        let counter = AtomicUsize::new(0);
+        let num_acquisitions = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..num_threads {
+                s.spawn(|| {
+                    for i in 0..num_iterations {
+                        let mut acquired = flag.try_acquire();
+                        while !acquired && i % 2 == 0 {
+                            flag.wait(usize::MAX);
+                            acquired = flag.try_acquire();
+                        }
+                        if acquired {
+                            let count = counter.load(Relaxed);
+                            std::thread::yield_now();
+                            counter.store(count + 1, Relaxed);
+                            num_acquisitions.fetch_add(1, Relaxed);
+                            flag.release();
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.load(Relaxed), num_acquisitions.load(Relaxed));
+        assert!(num_acquisitions.load(Relaxed) >= num_threads * num_iterations / 2);
+    }
 }
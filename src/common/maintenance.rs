@@ -0,0 +1,132 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The default interval between incremental rehashing steps.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(1);
+
+/// SignalState is the state of a Signal.
+#[derive(Default)]
+struct SignalState {
+    resized: bool,
+    closed: bool,
+}
+
+/// Signal wakes up maintenance workers when the array of a container is replaced.
+#[derive(Default)]
+pub struct Signal {
+    state: Mutex<SignalState>,
+    condvar: Condvar,
+}
+
+impl Signal {
+    /// Notifies the workers that the array has been replaced.
+    pub fn notify_resized(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.resized = true;
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Notifies the workers that the container is being dropped.
+    pub fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Wakes up the workers in order for them to check the stop flag.
+    fn wakeup(&self, stop: &AtomicBool) {
+        if let Ok(_state) = self.state.lock() {
+            stop.store(true, Relaxed);
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Waits until the array is replaced, or the timeout expires if one is given.
+    ///
+    /// It returns `false` if the worker has to stop.
+    fn wait(&self, stop: &AtomicBool, timeout: Option<Duration>) -> bool {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+        loop {
+            if stop.load(Relaxed) || state.closed {
+                return false;
+            }
+            if state.resized {
+                state.resized = false;
+                return true;
+            }
+            state = match timeout {
+                Some(timeout) => match self.condvar.wait_timeout(state, timeout) {
+                    Ok((state, result)) if result.timed_out() => {
+                        return !stop.load(Relaxed) && !state.closed
+                    }
+                    Ok((state, _)) => state,
+                    Err(_) => return false,
+                },
+                None => match self.condvar.wait(state) {
+                    Ok(state) => state,
+                    Err(_) => return false,
+                },
+            };
+        }
+    }
+}
+
+/// MaintenanceHandle owns a background thread that rehashes the old array of a container.
+///
+/// The thread is stopped and joined when the MaintenanceHandle is dropped. See
+/// [`HashMap::spawn_maintenance`](crate::HashMap::spawn_maintenance).
+pub struct MaintenanceHandle {
+    signal: Arc<Signal>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MaintenanceHandle {
+    /// Spawns a maintenance thread.
+    ///
+    /// `step` returns `None` if the container has been dropped, `Some(true)` if the old array
+    /// still remains after a bounded amount of work, and `Some(false)` if there is no old array.
+    pub(crate) fn spawn<F: FnMut() -> Option<bool> + Send + 'static>(
+        signal: Arc<Signal>,
+        interval: Duration,
+        mut step: F,
+    ) -> MaintenanceHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (signal_cloned, stop_cloned) = (signal.clone(), stop.clone());
+        let thread = std::thread::Builder::new()
+            .name("scc-maintenance".to_string())
+            .spawn(move || loop {
+                let timeout = match step() {
+                    None => return,
+                    Some(true) => Some(interval),
+                    Some(false) => None,
+                };
+                if !signal_cloned.wait(&stop_cloned, timeout) {
+                    return;
+                }
+            })
+            .expect("failed to spawn a maintenance thread");
+        MaintenanceHandle {
+            signal,
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        self.signal.wakeup(&self.stop);
+        if let Some(thread) = self.thread.take() {
+            let _result = thread.join();
+        }
+    }
+}
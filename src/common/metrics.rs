@@ -0,0 +1,442 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+
+/// The number of stripes.
+const NUM_STRIPES: usize = 8;
+
+/// The number of counters in a stripe.
+const NUM_COUNTERS: usize = 6;
+
+/// The number of cache counters in a stripe, excluding the age histogram.
+const NUM_CACHE_COUNTERS: usize = 5;
+
+/// The number of buckets in the age histogram of evicted entries.
+const NUM_AGE_BUCKETS: usize = 16;
+
+/// The number of counters of a group of Cells.
+const NUM_GROUP_COUNTERS: usize = 3;
+
+/// The number of Cells in a group of Cells sharing a set of counters.
+pub const CELLS_PER_GROUP: usize = 1024;
+
+/// The number of DataArrays in a Cell from which probing the Cell is regarded as long.
+pub const LONG_PROBE_DATA_ARRAYS: usize = 2;
+
+/// The next stripe index to be assigned to a thread.
+static NEXT_STRIPE_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The stripe index assigned to the current thread.
+    static STRIPE_INDEX: usize = NEXT_STRIPE_INDEX.fetch_add(1, Relaxed) % NUM_STRIPES;
+
+    /// Lock contention events yet to be attributed to a container.
+    static PENDING_LOCK_CONTENTIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+
+    /// Lock contention events yet to be attributed to a group of Cells.
+    static PENDING_GROUP_LOCK_CONTENTIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+
+    /// Rehashed Cells yet to be attributed to a container.
+    static PENDING_CELLS_REHASHED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Records a lock contention event in the current thread.
+pub fn record_lock_contention() {
+    PENDING_LOCK_CONTENTIONS.with(|n| n.set(n.get() + 1));
+    PENDING_GROUP_LOCK_CONTENTIONS.with(|n| n.set(n.get() + 1));
+}
+
+/// Records a rehashed Cell in the current thread.
+pub fn record_cell_rehashed() {
+    PENDING_CELLS_REHASHED.with(|n| n.set(n.get() + 1));
+}
+
+/// Takes the lock contention events recorded in the current thread that are yet to be attributed
+/// to a group of Cells.
+pub fn take_group_lock_contentions() -> usize {
+    PENDING_GROUP_LOCK_CONTENTIONS.with(|n| n.replace(0))
+}
+
+/// Counter identifies a counter in a stripe.
+#[derive(Clone, Copy)]
+pub enum Counter {
+    Inserts = 0,
+    Removes = 1,
+    FailedInserts = 2,
+    LockContentions = 3,
+    Resizes = 4,
+    CellsRehashed = 5,
+}
+
+/// Stripe is a set of counters occupying a dedicated cache line.
+#[repr(align(128))]
+struct Stripe<const N: usize> {
+    counts: [AtomicUsize; N],
+}
+
+impl<const N: usize> Default for Stripe<N> {
+    fn default() -> Self {
+        Stripe {
+            counts: std::array::from_fn(|_| AtomicUsize::new(0)),
+        }
+    }
+}
+
+/// Stripes is a striped set of counters.
+///
+/// Each thread updates its own stripe, and the stripes are summed up when a snapshot is taken.
+#[derive(Default)]
+struct Stripes<const N: usize> {
+    stripes: [Stripe<N>; NUM_STRIPES],
+}
+
+impl<const N: usize> Stripes<N> {
+    /// Adds the given value to the counter at the index.
+    fn add(&self, index: usize, value: usize) {
+        if value != 0 {
+            STRIPE_INDEX.with(|stripe_index| {
+                self.stripes[*stripe_index].counts[index].fetch_add(value, Relaxed);
+            });
+        }
+    }
+
+    /// Sums up the stripes.
+    fn sum(&self) -> [usize; N] {
+        let mut sum = [0; N];
+        for stripe in self.stripes.iter() {
+            for (sum, count) in sum.iter_mut().zip(stripe.counts.iter()) {
+                *sum += count.load(Relaxed);
+            }
+        }
+        sum
+    }
+
+    /// Resets all the counters.
+    fn reset(&self) {
+        for stripe in self.stripes.iter() {
+            for count in stripe.counts.iter() {
+                count.store(0, Relaxed);
+            }
+        }
+    }
+}
+
+/// Counters is a striped set of counters owned by a container.
+#[derive(Default)]
+pub struct Counters {
+    stripes: Stripes<NUM_COUNTERS>,
+}
+
+impl Counters {
+    /// Adds the given value to the counter.
+    pub fn add(&self, counter: Counter, value: usize) {
+        self.stripes.add(counter as usize, value);
+    }
+
+    /// Returns a Recorder that attributes the events recorded in the current thread to the
+    /// Counters until it is dropped.
+    pub fn recorder(&self) -> Recorder<'_> {
+        PENDING_LOCK_CONTENTIONS.with(|n| n.set(0));
+        PENDING_GROUP_LOCK_CONTENTIONS.with(|n| n.set(0));
+        PENDING_CELLS_REHASHED.with(|n| n.set(0));
+        Recorder { counters: self }
+    }
+
+    /// Takes a snapshot of the counters.
+    pub fn snapshot(&self) -> Metrics {
+        let sum = self.stripes.sum();
+        Metrics {
+            inserts: sum[Counter::Inserts as usize],
+            removes: sum[Counter::Removes as usize],
+            failed_inserts: sum[Counter::FailedInserts as usize],
+            lock_contentions: sum[Counter::LockContentions as usize],
+            resizes: sum[Counter::Resizes as usize],
+            cells_rehashed: sum[Counter::CellsRehashed as usize],
+        }
+    }
+
+    /// Resets all the counters.
+    pub fn reset(&self) {
+        self.stripes.reset();
+    }
+}
+
+/// GroupCounter identifies a counter of a group of Cells.
+#[derive(Clone, Copy)]
+enum GroupCounter {
+    LockContentions = 0,
+    LongProbes = 1,
+    OldArrayHits = 2,
+}
+
+/// GroupCounters is a set of counters of a group of Cells occupying a dedicated cache line.
+///
+/// The counters are not striped since operations on different groups do not share them.
+#[derive(Default)]
+pub struct GroupCounters {
+    stripe: Stripe<NUM_GROUP_COUNTERS>,
+}
+
+impl GroupCounters {
+    /// Records the events of an operation that locked a Cell in the group.
+    pub fn record(&self, lock_contentions: usize, long_probe: bool, old_array_hit: bool) {
+        self.add(GroupCounter::LockContentions, lock_contentions);
+        self.add(GroupCounter::LongProbes, usize::from(long_probe));
+        self.add(GroupCounter::OldArrayHits, usize::from(old_array_hit));
+    }
+
+    /// Takes a snapshot of the counters of the group of Cells.
+    pub fn snapshot(&self, first_cell: usize, num_cells: usize) -> GroupMetrics {
+        let count = |counter: GroupCounter| self.stripe.counts[counter as usize].load(Relaxed);
+        GroupMetrics {
+            first_cell,
+            num_cells,
+            lock_contentions: count(GroupCounter::LockContentions),
+            long_probes: count(GroupCounter::LongProbes),
+            old_array_hits: count(GroupCounter::OldArrayHits),
+        }
+    }
+
+    /// Resets all the counters.
+    pub fn reset(&self) {
+        for count in self.stripe.counts.iter() {
+            count.store(0, Relaxed);
+        }
+    }
+
+    /// Adds the given value to the counter.
+    fn add(&self, counter: GroupCounter, value: usize) {
+        if value != 0 {
+            self.stripe.counts[counter as usize].fetch_add(value, Relaxed);
+        }
+    }
+}
+
+/// CacheCounter identifies a cache counter in a stripe.
+#[derive(Clone, Copy)]
+pub enum CacheCounter {
+    Hits = 0,
+    Misses = 1,
+    Puts = 2,
+    Evictions = 3,
+    Expirations = 4,
+}
+
+/// CacheCounters is a striped set of counters owned by a [`HashCache`](crate::HashCache).
+#[derive(Default)]
+pub struct CacheCounters {
+    stripes: Stripes<{ NUM_CACHE_COUNTERS + NUM_AGE_BUCKETS }>,
+}
+
+impl CacheCounters {
+    /// Adds the given value to the counter.
+    pub fn add(&self, counter: CacheCounter, value: usize) {
+        self.stripes.add(counter as usize, value);
+    }
+
+    /// Records an eviction of an entry of the given age.
+    pub fn record_eviction(&self, age: u64) {
+        let bucket = ((u64::BITS - age.leading_zeros()) as usize).min(NUM_AGE_BUCKETS - 1);
+        self.stripes.add(CacheCounter::Evictions as usize, 1);
+        self.stripes.add(NUM_CACHE_COUNTERS + bucket, 1);
+    }
+
+    /// Takes a snapshot of the counters.
+    pub fn snapshot(&self) -> CacheStatistics {
+        let sum = self.stripes.sum();
+        let mut age_histogram = [0; NUM_AGE_BUCKETS];
+        age_histogram.copy_from_slice(&sum[NUM_CACHE_COUNTERS..]);
+        CacheStatistics {
+            hits: sum[CacheCounter::Hits as usize],
+            misses: sum[CacheCounter::Misses as usize],
+            puts: sum[CacheCounter::Puts as usize],
+            evictions: sum[CacheCounter::Evictions as usize],
+            expirations: sum[CacheCounter::Expirations as usize],
+            age_histogram,
+        }
+    }
+
+    /// Resets all the counters.
+    pub fn reset(&self) {
+        self.stripes.reset();
+    }
+}
+
+/// Recorder moves the events recorded in the current thread into the Counters when dropped.
+pub struct Recorder<'c> {
+    counters: &'c Counters,
+}
+
+impl<'c> Drop for Recorder<'c> {
+    fn drop(&mut self) {
+        self.counters.add(
+            Counter::LockContentions,
+            PENDING_LOCK_CONTENTIONS.with(|n| n.replace(0)),
+        );
+        self.counters.add(
+            Counter::CellsRehashed,
+            PENDING_CELLS_REHASHED.with(|n| n.replace(0)),
+        );
+    }
+}
+
+/// Metrics is a snapshot of the operation counters of a container.
+///
+/// It is returned by [`HashMap::metrics`](crate::HashMap::metrics) and
+/// [`HashIndex::metrics`](crate::HashIndex::metrics).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Metrics {
+    inserts: usize,
+    removes: usize,
+    failed_inserts: usize,
+    lock_contentions: usize,
+    resizes: usize,
+    cells_rehashed: usize,
+}
+
+impl Metrics {
+    /// Returns the number of key-value pairs inserted.
+    pub fn inserts(&self) -> usize {
+        self.inserts
+    }
+
+    /// Returns the number of key-value pairs removed.
+    pub fn removes(&self) -> usize {
+        self.removes
+    }
+
+    /// Returns the number of insertions that failed because the key existed.
+    pub fn failed_inserts(&self) -> usize {
+        self.failed_inserts
+    }
+
+    /// Returns the number of Cell lock acquisitions that did not succeed at the first attempt.
+    ///
+    /// Only the lock acquisitions made by single-key operations are taken into account.
+    pub fn lock_contentions(&self) -> usize {
+        self.lock_contentions
+    }
+
+    /// Returns the number of times the array was replaced.
+    pub fn resizes(&self) -> usize {
+        self.resizes
+    }
+
+    /// Returns the number of Cells relocated from an old array.
+    ///
+    /// Only the Cells relocated by single-key operations are taken into account.
+    pub fn cells_rehashed(&self) -> usize {
+        self.cells_rehashed
+    }
+}
+
+/// GroupMetrics is a snapshot of the counters of a group of Cells in the array of a container.
+///
+/// It is returned by [`HashMap::metrics_by_group`](crate::HashMap::metrics_by_group) and
+/// [`HashIndex::metrics_by_group`](crate::HashIndex::metrics_by_group). Only the operations that
+/// lock a Cell for modification are taken into account.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GroupMetrics {
+    first_cell: usize,
+    num_cells: usize,
+    lock_contentions: usize,
+    long_probes: usize,
+    old_array_hits: usize,
+}
+
+impl GroupMetrics {
+    /// Returns the index of the first Cell in the group.
+    pub fn first_cell(&self) -> usize {
+        self.first_cell
+    }
+
+    /// Returns the number of Cells in the group.
+    pub fn num_cells(&self) -> usize {
+        self.num_cells
+    }
+
+    /// Returns the number of Cell lock acquisitions in the group that did not succeed at the
+    /// first attempt.
+    pub fn lock_contentions(&self) -> usize {
+        self.lock_contentions
+    }
+
+    /// Returns the number of times a Cell in the group was locked while it had two or more
+    /// linked entry arrays, i.e., the key had to be looked up in more than one entry array.
+    pub fn long_probes(&self) -> usize {
+        self.long_probes
+    }
+
+    /// Returns the number of times a key belonging to the group was found in the old array.
+    pub fn old_array_hits(&self) -> usize {
+        self.old_array_hits
+    }
+}
+
+/// CacheStatistics is a snapshot of the counters of a [`HashCache`](crate::HashCache).
+///
+/// It is returned by [`HashCache::cache_statistics`](crate::HashCache::cache_statistics).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CacheStatistics {
+    hits: usize,
+    misses: usize,
+    puts: usize,
+    evictions: usize,
+    expirations: usize,
+    age_histogram: [usize; NUM_AGE_BUCKETS],
+}
+
+impl CacheStatistics {
+    /// Returns the number of lookups that found the key.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns the number of lookups that did not find the key, or found it expired.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Returns the number of key-value pairs put.
+    pub fn puts(&self) -> usize {
+        self.puts
+    }
+
+    /// Returns the number of key-value pairs evicted in order to make room.
+    pub fn evictions(&self) -> usize {
+        self.evictions
+    }
+
+    /// Returns the number of expired key-value pairs removed.
+    pub fn expirations(&self) -> usize {
+        self.expirations
+    }
+
+    /// Returns the ratio of hits to lookups, or `0.0` if there were no lookups.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+
+    /// Returns the ratio of misses to lookups, or `0.0` if there were no lookups.
+    pub fn miss_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.misses as f64 / lookups as f64
+        }
+    }
+
+    /// Returns the histogram of the ages of evicted key-value pairs.
+    ///
+    /// The age of a key-value pair is the number of puts since it was last used; the `i`-th
+    /// bucket counts the key-value pairs of which the age is in `[2^(i-1), 2^i)`, the first
+    /// bucket counts those of age `0`, and the last bucket counts all the older ones.
+    pub fn age_histogram(&self) -> &[usize] {
+        &self.age_histogram
+    }
+}
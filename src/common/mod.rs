@@ -1,4 +1,32 @@
+pub mod allocator;
 pub mod cell;
 pub mod cell_array;
+#[cfg(feature = "serde")]
+pub mod deserialize;
 pub mod hash_table;
+#[cfg(feature = "std")]
 pub mod linked_list;
+#[cfg(feature = "background-worker")]
+pub mod maintenance;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod node;
+pub mod sync;
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod unwind;
+
+/// Gives other threads a chance to make progress before retrying.
+///
+/// Without the standard library there is no scheduler to yield to, therefore it only hints
+/// the processor that the thread is spinning. Under the model checker, another thread is
+/// scheduled.
+#[inline]
+pub fn yield_now() {
+    #[cfg(all(feature = "std", not(loom)))]
+    std::thread::yield_now();
+    #[cfg(loom)]
+    loom::thread::yield_now();
+    #[cfg(all(not(feature = "std"), not(loom)))]
+    core::hint::spin_loop();
+}
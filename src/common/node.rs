@@ -0,0 +1,31 @@
+use crate::ebr::Atomic;
+
+/// Node is a value along with the link to the next node.
+///
+/// The value is never moved out of a Node, and it is dropped along with the Node by the
+/// epoch-based garbage collector after the Node is unlinked, therefore a thread that reached the
+/// Node while pinned can read the value until it is unpinned.
+pub struct Node<T> {
+    value: T,
+    next: Atomic<Node<T>>,
+}
+
+impl<T> Node<T> {
+    /// Creates a new Node.
+    pub fn new(value: T) -> Node<T> {
+        Node {
+            value,
+            next: Atomic::null(),
+        }
+    }
+
+    /// Returns a reference to the value.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns a reference to the link to the next node.
+    pub fn next(&self) -> &Atomic<Node<T>> {
+        &self.next
+    }
+}
@@ -0,0 +1,64 @@
+//! Synchronization primitives that the Cell lock and the resize protocol are built on.
+//!
+//! With `--cfg loom`, they and the pointers managed by [`ebr`](crate::ebr) are replaced with
+//! those of [`loom`](https://docs.rs/loom), so that the tests calling [`model`] explore the
+//! interleavings of the threads they spawn up to a preemption bound. The epochs of
+//! [`ebr`](crate::ebr) are not modeled, and retired objects are only dropped at the end of each
+//! execution.
+//!
+//! The models are checked by `RUSTFLAGS="--cfg loom" cargo test --lib --release model_`, and
+//! `LOOM_MAX_PREEMPTIONS` overrides the default preemption bound.
+//! Without `--cfg loom`, they are ordinary multi-threaded tests that only see the interleavings
+//! that the operating system happens to produce.
+
+#[cfg(not(loom))]
+pub use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize};
+#[cfg(loom)]
+pub use loom::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize};
+
+#[cfg(all(test, not(loom)))]
+pub use std::{sync::Arc, thread};
+
+#[cfg(all(test, loom))]
+pub use loom::{sync::Arc, thread};
+
+/// The number of times a model is run without the model checker.
+#[cfg(all(test, not(loom)))]
+const NUM_MODEL_RUNS: usize = 64;
+
+/// The maximum number of preemptions in an execution explored by the model checker unless
+/// `LOOM_MAX_PREEMPTIONS` is set.
+#[cfg(all(test, loom))]
+const PREEMPTION_BOUND: usize = 3;
+
+/// Signals the processor, or the model checker, that the thread is spinning.
+#[inline]
+pub fn spin_loop() {
+    #[cfg(not(loom))]
+    core::hint::spin_loop();
+    #[cfg(loom)]
+    loom::hint::spin_loop();
+}
+
+/// Runs the model under the model checker with `--cfg loom`.
+///
+/// Otherwise, the model is only repeated a number of times on real threads as a stress test,
+/// which does not check any particular interleaving.
+#[cfg(test)]
+pub fn model<F: Fn() + Send + Sync + 'static>(f: F) {
+    #[cfg(not(loom))]
+    for _ in 0..NUM_MODEL_RUNS {
+        f();
+    }
+    #[cfg(loom)]
+    {
+        let mut builder = loom::model::Builder::new();
+        if builder.preemption_bound.is_none() {
+            builder.preemption_bound = Some(PREEMPTION_BOUND);
+        }
+        builder.check(move || {
+            f();
+            crate::ebr::flush_local();
+        });
+    }
+}
@@ -0,0 +1,210 @@
+//! Structured events emitted at the lifecycle points of the hash containers.
+//!
+//! With the `tracing` feature, the events are emitted through
+//! [`tracing`](https://docs.rs/tracing) with the `scc` target, and each event carries the address
+//! of the container in the `container` field. Without the feature, the call sites expand to
+//! nothing.
+//!
+//! * `resize started`: a new array has been installed; `old_capacity`, `new_capacity`, and
+//!   `reason`, which is one of `grow`, `shrink`, and `reserve`.
+//! * `rehash progress`: another quarter of the old array has been relocated; `rehashed` and
+//!   `total` in Cells.
+//! * `resize finished`: the old array has been relocated and dropped; `old_capacity` and
+//!   `new_capacity`.
+//! * `cell overflow`: a Cell has linked a new entry array to store an entry; `num_data_arrays`.
+//! * `eviction`: [`HashCache`](crate::HashCache) has evicted an entry; `expired`.
+
+/// Emits an event at the given level with the supplied fields and message.
+///
+/// It has to be invoked as a statement.
+macro_rules! trace_event {
+    ($level:ident, $($args:tt)+) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::event!(target: "scc", ::tracing::Level::$level, $($args)+);
+    };
+}
+
+pub(crate) use trace_event;
+
+/// Returns the address of the container that is recorded in the `container` field.
+#[cfg(feature = "tracing")]
+pub fn address<T: ?Sized>(container: &T) -> usize {
+    (container as *const T).cast::<()>() as usize
+}
+
+#[cfg(all(test, feature = "std", feature = "tracing"))]
+mod test {
+    use std::collections::hash_map::RandomState;
+    use std::fmt::Debug;
+    use std::hash::BuildHasherDefault;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// A captured event: the message and the other fields in their `Debug` representation.
+    #[derive(Debug, Default)]
+    struct CapturedEvent {
+        message: String,
+        fields: Vec<(&'static str, String)>,
+    }
+
+    impl CapturedEvent {
+        /// Returns the `Debug` representation of the field.
+        fn field(&self, name: &str) -> Option<&str> {
+            self.fields
+                .iter()
+                .find(|(field, _)| *field == name)
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    impl Visit for CapturedEvent {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() == "message" {
+                self.message = format!("{value:?}");
+            } else {
+                self.fields.push((field.name(), format!("{value:?}")));
+            }
+        }
+    }
+
+    /// Captures the events of the `scc` target.
+    #[derive(Clone, Default)]
+    struct CapturingSubscriber {
+        events: Arc<Mutex<Vec<CapturedEvent>>>,
+    }
+
+    impl CapturingSubscriber {
+        /// Runs the closure with the subscriber set as the default, and returns the captured
+        /// events.
+        fn capture<F: FnOnce()>(f: F) -> Vec<CapturedEvent> {
+            let subscriber = Self::default();
+            tracing::subscriber::with_default(subscriber.clone(), f);
+            let mut events = subscriber.events.lock().unwrap();
+            std::mem::take(&mut *events)
+        }
+    }
+
+    impl Subscriber for CapturingSubscriber {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == "scc"
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut captured = CapturedEvent::default();
+            event.record(&mut captured);
+            self.events.lock().unwrap().push(captured);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn forced_resize() {
+        let hashmap: crate::HashMap<usize, usize, RandomState> =
+            crate::HashMap::new(64, RandomState::new());
+        let container = format!("{}", super::address(&hashmap));
+        let events = CapturingSubscriber::capture(|| {
+            for key in 0..4096 {
+                assert!(hashmap.insert(key, key).is_ok());
+            }
+            assert!(hashmap.try_reserve(1 << 16).is_ok());
+            for key in 0..4096 {
+                assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+            }
+        });
+
+        let started = events
+            .iter()
+            .filter(|event| event.message == "resize started")
+            .collect::<Vec<_>>();
+        assert!(started
+            .iter()
+            .all(|event| event.field("container") == Some(container.as_str())));
+        assert!(started
+            .iter()
+            .any(|event| event.field("reason") == Some("\"grow\"")));
+        assert!(started
+            .iter()
+            .any(|event| event.field("reason") == Some("\"reserve\"")));
+
+        // Every resize but the last one has to finish before the array is resized again.
+        let finished = events
+            .iter()
+            .filter(|event| event.message == "resize finished")
+            .collect::<Vec<_>>();
+        assert!(finished.len() + 1 >= started.len());
+        for (started, finished) in started.iter().zip(finished.iter()) {
+            assert_eq!(started.field("container"), finished.field("container"));
+            assert_eq!(
+                started.field("old_capacity"),
+                finished.field("old_capacity")
+            );
+            assert_eq!(
+                started.field("new_capacity"),
+                finished.field("new_capacity")
+            );
+        }
+    }
+
+    #[test]
+    fn overflow_and_eviction() {
+        // All the keys are mapped to the same Cell.
+        #[derive(Default)]
+        struct ConstantHasher;
+
+        impl std::hash::Hasher for ConstantHasher {
+            fn finish(&self) -> u64 {
+                0
+            }
+            fn write(&mut self, _: &[u8]) {}
+        }
+
+        let hashmap: crate::HashMap<usize, usize, BuildHasherDefault<ConstantHasher>> =
+            crate::HashMap::new(64, BuildHasherDefault::default());
+        let container = format!("{}", super::address(&hashmap));
+        let events = CapturingSubscriber::capture(|| {
+            for key in 0..64 {
+                assert!(hashmap.insert(key, key).is_ok());
+            }
+        });
+        let overflows = events
+            .iter()
+            .filter(|event| event.message == "cell overflow")
+            .collect::<Vec<_>>();
+        assert!(!overflows.is_empty());
+        assert!(overflows
+            .iter()
+            .all(|event| event.field("container") == Some(container.as_str())));
+        assert_eq!(overflows[0].field("num_data_arrays"), Some("2"));
+
+        let hashcache: crate::HashCache<usize, usize, RandomState> =
+            crate::HashCache::new(64, RandomState::new());
+        let container = format!("{}", super::address(&hashcache));
+        let events = CapturingSubscriber::capture(|| {
+            for key in 0..1024 {
+                assert!(hashcache.put(key, key).is_ok());
+            }
+        });
+        let evictions = events
+            .iter()
+            .filter(|event| event.message == "eviction")
+            .collect::<Vec<_>>();
+        assert_eq!(evictions.len(), 1024 - hashcache.len());
+        assert!(evictions
+            .iter()
+            .all(|event| event.field("container") == Some(container.as_str())
+                && event.field("expired") == Some("false")));
+    }
+}
@@ -0,0 +1,57 @@
+use std::any::Any;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+/// DeferredPanic holds back the panic raised by a user closure until the remaining work, e.g.,
+/// passing the other removed key-value pairs to a hook, has been done.
+///
+/// A panic raised while the thread is already unwinding is discarded, since it would otherwise
+/// abort the process.
+#[derive(Default)]
+pub struct DeferredPanic {
+    payload: Option<Box<dyn Any + Send>>,
+}
+
+impl DeferredPanic {
+    /// Invokes the closure, and keeps the first panic raised by it.
+    pub fn run<F: FnOnce()>(&mut self, f: F) {
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(f)) {
+            if self.payload.is_none() {
+                self.payload = Some(payload);
+            }
+        }
+    }
+
+    /// Resumes the kept panic unless the thread is already unwinding.
+    pub fn propagate(self) {
+        if let Some(payload) = self.payload {
+            if !std::thread::panicking() {
+                resume_unwind(payload);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deferred_panic() {
+        let mut num_calls = 0;
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut deferred_panic = DeferredPanic::default();
+            for i in 0..4 {
+                deferred_panic.run(|| {
+                    num_calls += 1;
+                    if i % 2 == 1 {
+                        panic!("{}", i);
+                    }
+                });
+            }
+            deferred_panic.propagate();
+        }));
+        assert_eq!(num_calls, 4);
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<String>().unwrap(), "1");
+    }
+}
@@ -0,0 +1,1983 @@
+//! Epoch-based memory reclamation.
+//!
+//! The module provides the subset of epoch-based reclamation primitives that the containers in
+//! the crate need. Unlike a general purpose collector, the epoch domain is private to the crate,
+//! therefore threads that pin unrelated epoch-based data structures do not delay the reclamation
+//! of the entries removed from scc containers.
+//!
+//! * [`Guard`]: a thread is pinned while it holds a [`Guard`], and objects that are retired while
+//!   the thread is pinned are not dropped until the [`Guard`] is dropped.
+//! * [`Domain`]: a reclamation domain that has its own global epoch; containers created with a
+//!   [`Domain`] are not affected by threads that are pinned in other domains.
+//! * [`Atomic`], [`Shared`], and [`Owned`]: tagged pointers that are loaded, stored, and retired
+//!   under a [`Guard`].
+//!
+//! A retired object is dropped once the global epoch has been advanced twice since the object was
+//! retired; the global epoch can only be advanced when every pinned thread has observed the
+//! current epoch.
+//!
+//! # Examples
+//! ```
+//! use scc::ebr::{self, Atomic, Owned};
+//! use std::sync::atomic::Ordering::{AcqRel, Acquire};
+//!
+//! let atomic: Atomic<usize> = Atomic::new(1);
+//!
+//! let guard = ebr::pin();
+//! let old = atomic.swap(Owned::new(2), AcqRel, &guard);
+//! assert_eq!(unsafe { old.deref() }, &1);
+//! unsafe { guard.defer_destroy(old) };
+//! assert_eq!(unsafe { atomic.load(Acquire, &guard).deref() }, &2);
+//!
+//! drop(guard);
+//! unsafe { drop(atomic.into_owned()) };
+//! ```
+
+use crate::common::sync;
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::Ordering::{self, Acquire, Relaxed, Release, SeqCst};
+use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize};
+
+/// The global epoch advances in steps of two; the least significant bit of the epoch of a
+/// [`Participant`] indicates that the thread is pinned.
+const PINNED: usize = 1;
+
+/// A retired object is dropped when the global epoch is ahead of its retirement epoch by two
+/// steps.
+const EXPIRY: usize = 4;
+
+/// The number of pins between two attempts to advance the global epoch.
+const PINS_PER_ADVANCE: usize = 128;
+
+/// The number of retired objects in a thread-local bag that triggers an attempt to reclaim them.
+const DEFERRED_PER_COLLECT: usize = 64;
+
+/// The collector of the global domain that [`pin`] pins the current thread in.
+static GLOBAL: Collector = Collector::new();
+
+/// The number of times [`flush`] pins the thread and tries to advance the global epoch.
+const FLUSH_ROUNDS: usize = 4;
+
+/// Threads drop retired objects as they pin themselves and retire objects.
+///
+/// The state of the collector outlives an execution of the model checker, therefore retired
+/// objects are only dropped at the end of each execution so that the executions explored by the
+/// model checker do not depend on one another.
+const AUTO_COLLECT: bool = !cfg!(loom);
+
+#[cfg(feature = "std")]
+thread_local! {
+    static LOCAL: Local = Local::register();
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// The participants of the current thread in [`Domain`] instances.
+    static DOMAIN_LOCALS: RefCell<Vec<DomainLocal>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of objects the current thread has retired.
+    pub static NUM_RETIRED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Pins the current thread.
+///
+/// Objects retired by any thread after the current thread is pinned are not dropped until the
+/// returned [`Guard`] is dropped.
+///
+/// # Examples
+/// ```
+/// use scc::ebr;
+///
+/// let guard = ebr::pin();
+/// guard.flush();
+/// ```
+#[inline]
+pub fn pin() -> Guard {
+    match local_participant() {
+        Some(participant) => Guard::pin(participant, false, None),
+        None => Guard::pin(GLOBAL.acquire(), true, None),
+    }
+}
+
+/// Advances the global epoch as far as possible, and drops the retired objects that are no longer
+/// reachable.
+///
+/// The objects retired by the current thread and by exited threads are reclaimed; those retired
+/// by other running threads are reclaimed the next time the threads are pinned. The global epoch
+/// cannot advance past a thread that stays pinned, including the current thread if it holds a
+/// [`Guard`].
+///
+/// Returns the estimated number of bytes reclaimed, which only accounts for the retired objects
+/// themselves and not for the heap memory that they own.
+///
+/// # Examples
+/// ```
+/// use scc::ebr::{self, Atomic, Shared};
+/// use std::sync::atomic::Ordering::AcqRel;
+///
+/// let atomic: Atomic<[u64; 4]> = Atomic::new([0; 4]);
+///
+/// let guard = ebr::pin();
+/// let old = atomic.swap(Shared::null(), AcqRel, &guard);
+/// unsafe { guard.defer_destroy(old) };
+/// drop(guard);
+///
+/// let mut reclaimed = 0;
+/// while reclaimed < 32 {
+///     reclaimed += ebr::flush();
+/// }
+/// ```
+pub fn flush() -> usize {
+    flush_rounds(pin)
+}
+
+/// Drops every object that the current thread has retired.
+///
+/// The atomic variables of the model checker have to be dropped within the execution that
+/// created them, while the thread-local participant outlives the execution.
+#[cfg(all(test, loom))]
+pub(crate) fn flush_local() {
+    while local_participant().map_or(false, |p| p.num_pending.load(Relaxed) != 0) {
+        flush();
+    }
+}
+
+/// Returns the estimated number of bytes retained by retired objects that have yet to be dropped.
+///
+/// The value is a hint for monitoring; it is read without synchronizing with the threads that
+/// retire or drop objects.
+///
+/// # Examples
+/// ```
+/// use scc::ebr::{self, Atomic, Shared};
+/// use std::sync::atomic::Ordering::AcqRel;
+///
+/// let atomic: Atomic<[u64; 1024]> = Atomic::new([0; 1024]);
+///
+/// let guard = ebr::pin();
+/// let old = atomic.swap(Shared::null(), AcqRel, &guard);
+/// unsafe { guard.defer_destroy(old) };
+/// assert!(ebr::pending_garbage_hint() >= 8192);
+/// ```
+pub fn pending_garbage_hint() -> usize {
+    GLOBAL.pending_garbage_hint()
+}
+
+/// Returns a snapshot of the state of the epoch-based reclamation mechanism.
+///
+/// The values are gathered without synchronizing with the threads that retire or drop objects,
+/// therefore they are only consistent when no other thread is working on the containers.
+///
+/// # Examples
+/// ```
+/// use scc::ebr::{self, Atomic, Shared};
+/// use std::sync::atomic::Ordering::AcqRel;
+///
+/// let atomic: Atomic<[u64; 4]> = Atomic::new([0; 4]);
+///
+/// let guard = ebr::pin();
+/// let old = atomic.swap(Shared::null(), AcqRel, &guard);
+/// unsafe { guard.defer_destroy(old) };
+///
+/// let statistics = ebr::reclamation_statistics();
+/// assert!(statistics.pending_objects() >= 1);
+/// assert!(statistics.pending_bytes() >= 32);
+/// ```
+pub fn reclamation_statistics() -> ReclamationStatistics {
+    GLOBAL.statistics()
+}
+
+/// Returns a [`Guard`] that does not pin the current thread.
+///
+/// Objects retired under the returned [`Guard`] are dropped immediately.
+///
+/// # Safety
+///
+/// Loading a pointer under the returned [`Guard`] is only safe if no other thread can retire the
+/// object concurrently, e.g., in a `Drop` implementation.
+#[inline]
+pub unsafe fn unprotected() -> &'static Guard {
+    struct Unprotected(Guard);
+    unsafe impl Sync for Unprotected {}
+    static UNPROTECTED: Unprotected = Unprotected(Guard {
+        participant: ptr::null(),
+        release: false,
+        _collector: None,
+    });
+    &UNPROTECTED.0
+}
+
+/// [`Domain`] is a reclamation domain that has its own global epoch.
+///
+/// Containers share the global domain that [`pin`] pins the current thread in by default, and
+/// objects removed from any of them are not dropped while a thread stays pinned in the global
+/// domain. Containers created with a [`Domain`] retire their objects in the [`Domain`] instead,
+/// therefore their memory is reclaimed regardless of threads that are pinned in other domains.
+/// Cloning a [`Domain`] is cheap, and containers that are accessed together can share one.
+///
+/// [`Guard`] instances are specific to a domain; operations on a container that accept a
+/// [`Guard`] panic if the [`Guard`] was not obtained from the [`Domain`] of the container.
+///
+/// # Examples
+/// ```
+/// use scc::ebr::Domain;
+/// use scc::HashIndex;
+/// use std::collections::hash_map::RandomState;
+///
+/// let domain = Domain::new();
+/// let hashindex: HashIndex<u64, u32, RandomState> =
+///     HashIndex::new_with_domain(domain.clone(), 64, RandomState::new());
+/// assert!(hashindex.insert(1, 10).is_ok());
+///
+/// // A long-lived Guard of the global domain does not delay the reclamation of the value.
+/// let global_guard = scc::pin();
+/// assert!(hashindex.remove(&1));
+/// while domain.reclamation_statistics().pending_objects() != 0 {
+///     domain.flush();
+/// }
+///
+/// let guard = domain.pin();
+/// assert!(hashindex.read_with(&1, |_, v| *v, &guard).is_none());
+/// ```
+#[derive(Clone)]
+pub struct Domain {
+    collector: Option<Arc<Collector>>,
+}
+
+impl Domain {
+    /// Creates a new [`Domain`] that is isolated from all the other domains.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr::Domain;
+    ///
+    /// let domain = Domain::new();
+    /// let guard = domain.pin();
+    /// ```
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Domain {
+        Domain {
+            collector: Some(Arc::new(Collector::new())),
+        }
+    }
+
+    /// Returns the global domain that [`pin`] pins the current thread in.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr::Domain;
+    ///
+    /// let guard = Domain::global().pin();
+    /// ```
+    pub const fn global() -> Domain {
+        Domain { collector: None }
+    }
+
+    /// Pins the current thread in the [`Domain`].
+    ///
+    /// Objects retired in the [`Domain`] after the current thread is pinned are not dropped until
+    /// the returned [`Guard`] is dropped.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr::Domain;
+    ///
+    /// let domain = Domain::new();
+    /// let guard = domain.pin();
+    /// guard.flush();
+    /// ```
+    #[inline]
+    pub fn pin(&self) -> Guard {
+        let Some(collector) = self.collector.as_ref() else {
+            return pin();
+        };
+        match domain_participant(collector) {
+            Some(participant) => Guard::pin(participant, false, Some(collector.clone())),
+            None => Guard::pin(collector.acquire(), true, Some(collector.clone())),
+        }
+    }
+
+    /// Advances the global epoch of the [`Domain`] as far as possible, and drops the retired
+    /// objects that are no longer reachable.
+    ///
+    /// See [`flush`] for details.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr::Domain;
+    ///
+    /// let domain = Domain::new();
+    /// domain.pin().defer(|| ());
+    /// while domain.reclamation_statistics().pending_objects() != 0 {
+    ///     domain.flush();
+    /// }
+    /// ```
+    pub fn flush(&self) -> usize {
+        flush_rounds(|| self.pin())
+    }
+
+    /// Returns a snapshot of the state of the [`Domain`].
+    ///
+    /// See [`reclamation_statistics`] for details.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr::Domain;
+    ///
+    /// let domain = Domain::new();
+    /// assert_eq!(domain.reclamation_statistics().pending_objects(), 0);
+    /// ```
+    pub fn reclamation_statistics(&self) -> ReclamationStatistics {
+        self.collector().statistics()
+    }
+
+    /// Returns `true` if no thread is pinned in the [`Domain`].
+    ///
+    /// Threads that are pinned afterwards cannot reach objects that had been unreachable before.
+    pub(crate) fn is_quiescent(&self) -> bool {
+        self.collector().is_quiescent()
+    }
+
+    /// Panics if the [`Guard`] pins the current thread in another domain.
+    #[inline]
+    pub(crate) fn check_guard(&self, guard: &Guard) {
+        if let Some(participant) = unsafe { guard.participant.as_ref() } {
+            assert!(
+                ptr::eq(participant.collector, self.collector()),
+                "the Guard belongs to another reclamation domain"
+            );
+        }
+    }
+
+    fn collector(&self) -> &Collector {
+        self.collector.as_deref().unwrap_or(&GLOBAL)
+    }
+}
+
+impl fmt::Debug for Domain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Domain")
+            .field("global", &self.collector.is_none())
+            .finish()
+    }
+}
+
+/// [`ReclamationStatistics`] is a snapshot of the state of the epoch-based reclamation mechanism,
+/// returned by [`reclamation_statistics`].
+///
+/// Entries removed from the containers are retired, and stay pending until they are dropped; a
+/// pending count that does not fall back to zero after every [`Guard`] is dropped and [`flush`] is
+/// called indicates that a thread stays pinned or has retired objects without being pinned again.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReclamationStatistics {
+    pending_objects: usize,
+    pending_bytes: usize,
+    epoch_advancements: usize,
+    oldest_pending_age: usize,
+}
+
+impl ReclamationStatistics {
+    /// Returns the number of retired objects that have yet to be dropped.
+    pub fn pending_objects(&self) -> usize {
+        self.pending_objects
+    }
+
+    /// Returns the estimated number of bytes retained by the retired objects that have yet to be
+    /// dropped.
+    ///
+    /// The estimate only accounts for the retired objects themselves and not for the heap memory
+    /// that they own.
+    pub fn pending_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+
+    /// Returns the number of times the global epoch has been advanced since the process started.
+    pub fn epoch_advancements(&self) -> usize {
+        self.epoch_advancements
+    }
+
+    /// Returns the number of epoch advancements since the oldest pending object was retired, or
+    /// zero if there is no pending object.
+    ///
+    /// An object is dropped two epoch advancements after it is retired, therefore an age that
+    /// keeps growing indicates that the global epoch is blocked or that the owner thread of the
+    /// object is not pinned again.
+    pub fn oldest_pending_age(&self) -> usize {
+        self.oldest_pending_age
+    }
+}
+
+/// [`Guard`] keeps the current thread pinned.
+///
+/// [`Guard`] can be nested; the thread stays pinned until the last [`Guard`] is dropped.
+pub struct Guard {
+    participant: *const Participant,
+    release: bool,
+    _collector: Option<Arc<Collector>>,
+}
+
+impl Guard {
+    /// Defers dropping the object that the pointer points to until no thread can read it.
+    ///
+    /// # Safety
+    ///
+    /// The object must have been unlinked from the data structure, and must not be retired twice.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr::{self, Atomic, Shared};
+    /// use std::sync::atomic::Ordering::AcqRel;
+    ///
+    /// let atomic: Atomic<usize> = Atomic::new(1);
+    ///
+    /// let guard = ebr::pin();
+    /// let old = atomic.swap(Shared::null(), AcqRel, &guard);
+    /// unsafe { guard.defer_destroy(old) };
+    /// ```
+    #[inline]
+    pub unsafe fn defer_destroy<T>(&self, ptr: Shared<'_, T>) {
+        let raw = ptr.as_raw() as *mut T;
+        if raw.is_null() {
+            return;
+        }
+        self.retire(Deferred::new(
+            raw.cast(),
+            drop_box::<T>,
+            mem::size_of::<T>(),
+        ));
+    }
+
+    /// Defers executing the closure until no thread can read the objects retired so far.
+    ///
+    /// # Safety
+    ///
+    /// The closure may outlive the references that it captures, and may be executed in another
+    /// thread.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr;
+    ///
+    /// let guard = ebr::pin();
+    /// unsafe { guard.defer_unchecked(|| println!("reclaimed")) };
+    /// ```
+    #[inline]
+    pub unsafe fn defer_unchecked<F: FnOnce()>(&self, f: F) {
+        self.defer_unchecked_sized(mem::size_of::<F>(), f);
+    }
+
+    /// Defers executing the closure that reclaims `num_bytes` of memory.
+    ///
+    /// # Safety
+    ///
+    /// See [`Guard::defer_unchecked`].
+    pub(crate) unsafe fn defer_unchecked_sized<F: FnOnce()>(&self, num_bytes: usize, f: F) {
+        let boxed = Box::into_raw(Box::new(f));
+        self.retire(Deferred::new(boxed.cast(), call_box::<F>, num_bytes));
+    }
+
+    /// Defers executing the closure until no thread can read the objects retired so far.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr;
+    /// use std::sync::atomic::AtomicBool;
+    /// use std::sync::atomic::Ordering::Relaxed;
+    /// use std::sync::Arc;
+    ///
+    /// let executed = Arc::new(AtomicBool::new(false));
+    /// let executed_cloned = executed.clone();
+    /// ebr::pin().defer(move || executed_cloned.store(true, Relaxed));
+    /// while !executed.load(Relaxed) {
+    ///     ebr::pin().flush();
+    /// }
+    /// ```
+    #[inline]
+    pub fn defer<F: FnOnce() + Send + 'static>(&self, f: F) {
+        unsafe { self.defer_unchecked(f) }
+    }
+
+    /// Tries to advance the global epoch, and drops the retired objects that are no longer
+    /// reachable.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr;
+    ///
+    /// ebr::pin().flush();
+    /// ```
+    pub fn flush(&self) {
+        if !AUTO_COLLECT {
+            return;
+        }
+        if let Some(participant) = unsafe { self.participant.as_ref() } {
+            participant.collect(true);
+        }
+    }
+
+    /// Pins the current thread; `collector` keeps the [`Domain`] of the participant alive.
+    fn pin(participant: &Participant, release: bool, collector: Option<Arc<Collector>>) -> Guard {
+        let num_guards = participant.num_guards.get();
+        participant.num_guards.set(num_guards + 1);
+        if num_guards == 0 {
+            let global_epoch = participant.collector().epoch.load(Relaxed);
+            participant.epoch.store(global_epoch | PINNED, Relaxed);
+            fence(SeqCst);
+            let num_pins = participant.num_pins.get().wrapping_add(1);
+            participant.num_pins.set(num_pins);
+            if AUTO_COLLECT && num_pins % PINS_PER_ADVANCE == 0 {
+                participant.collect(false);
+            }
+        }
+        Guard {
+            participant,
+            release,
+            _collector: collector,
+        }
+    }
+
+    fn retire(&self, deferred: Deferred) {
+        #[cfg(test)]
+        NUM_RETIRED.with(|n| n.set(n.get() + 1));
+        match unsafe { self.participant.as_ref() } {
+            Some(participant) => {
+                let num_deferred = participant.push(deferred);
+                if AUTO_COLLECT && num_deferred % DEFERRED_PER_COLLECT == 0 {
+                    participant.collect(false);
+                }
+            }
+            None => {
+                deferred.execute();
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guard")
+            .field("unprotected", &self.participant.is_null())
+            .finish()
+    }
+}
+
+impl Drop for Guard {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(participant) = unsafe { self.participant.as_ref() } {
+            let num_guards = participant.num_guards.get() - 1;
+            participant.num_guards.set(num_guards);
+            if num_guards == 0 {
+                participant.epoch.store(0, Release);
+                if self.release {
+                    participant.release();
+                }
+            }
+        }
+    }
+}
+
+/// A pointer type that can be stored in an [`Atomic`].
+pub trait Pointer<T> {
+    /// Converts the pointer into a tagged raw pointer.
+    fn into_ptr(self) -> *mut T;
+
+    /// Converts a tagged raw pointer back into the pointer.
+    ///
+    /// # Safety
+    ///
+    /// The raw pointer must have been returned by `into_ptr` of the same type.
+    unsafe fn from_ptr(ptr: *mut T) -> Self;
+}
+
+/// [`Atomic`] is an atomic tagged pointer to an object retired through a [`Guard`].
+///
+/// The tag is stored in the low bits of the pointer that the alignment of `T` leaves unused, and
+/// the pointer is never converted into an integer and back, so that it retains its provenance.
+pub struct Atomic<T> {
+    data: sync::AtomicPtr<T>,
+    _phantom: PhantomData<*mut T>,
+}
+
+unsafe impl<T: Send + Sync> Send for Atomic<T> {}
+unsafe impl<T: Send + Sync> Sync for Atomic<T> {}
+
+impl<T> Atomic<T> {
+    /// Creates a null [`Atomic`].
+    #[cfg(not(loom))]
+    #[inline]
+    pub const fn null() -> Atomic<T> {
+        Atomic {
+            data: sync::AtomicPtr::new(ptr::null_mut()),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Creates a null [`Atomic`].
+    ///
+    /// The atomic types of the model checker cannot be created in constant expressions.
+    #[cfg(loom)]
+    #[inline]
+    pub fn null() -> Atomic<T> {
+        Atomic {
+            data: sync::AtomicPtr::new(ptr::null_mut()),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Allocates `value` on the heap and creates an [`Atomic`] pointing to it.
+    #[inline]
+    pub fn new(value: T) -> Atomic<T> {
+        Atomic::from(Owned::new(value))
+    }
+
+    /// Loads the pointer.
+    #[inline]
+    pub fn load<'g>(&self, order: Ordering, _guard: &'g Guard) -> Shared<'g, T> {
+        unsafe { Shared::from_ptr(self.data.load(order)) }
+    }
+
+    /// Stores the pointer.
+    #[inline]
+    pub fn store<P: Pointer<T>>(&self, new: P, order: Ordering) {
+        self.data.store(new.into_ptr(), order);
+    }
+
+    /// Stores the pointer, and returns the previous one.
+    #[inline]
+    pub fn swap<'g, P: Pointer<T>>(
+        &self,
+        new: P,
+        order: Ordering,
+        _guard: &'g Guard,
+    ) -> Shared<'g, T> {
+        unsafe { Shared::from_ptr(self.data.swap(new.into_ptr(), order)) }
+    }
+
+    /// Stores `new` if the current pointer is `current`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the current pointer and `new` if the current pointer is not `current`.
+    #[inline]
+    pub fn compare_exchange<'g, P: Pointer<T>>(
+        &self,
+        current: Shared<'_, T>,
+        new: P,
+        success: Ordering,
+        failure: Ordering,
+        _guard: &'g Guard,
+    ) -> Result<Shared<'g, T>, CompareExchangeError<'g, T, P>> {
+        let new = new.into_ptr();
+        self.data
+            .compare_exchange(current.into_ptr(), new, success, failure)
+            .map(|_| unsafe { Shared::from_ptr(new) })
+            .map_err(|current| unsafe {
+                CompareExchangeError {
+                    current: Shared::from_ptr(current),
+                    new: P::from_ptr(new),
+                }
+            })
+    }
+
+    /// Stores `new` if the current pointer is `current`, and may fail spuriously.
+    ///
+    /// # Errors
+    ///
+    /// Returns the current pointer and `new` if the pointer was not replaced.
+    #[inline]
+    pub fn compare_exchange_weak<'g, P: Pointer<T>>(
+        &self,
+        current: Shared<'_, T>,
+        new: P,
+        success: Ordering,
+        failure: Ordering,
+        _guard: &'g Guard,
+    ) -> Result<Shared<'g, T>, CompareExchangeError<'g, T, P>> {
+        let new = new.into_ptr();
+        self.data
+            .compare_exchange_weak(current.into_ptr(), new, success, failure)
+            .map(|_| unsafe { Shared::from_ptr(new) })
+            .map_err(|current| unsafe {
+                CompareExchangeError {
+                    current: Shared::from_ptr(current),
+                    new: P::from_ptr(new),
+                }
+            })
+    }
+
+    /// Takes ownership of the pointee.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must not be null, and no other thread may be able to read it.
+    #[inline]
+    pub unsafe fn into_owned(self) -> Owned<T> {
+        #[cfg(not(loom))]
+        let ptr = self.data.into_inner();
+        #[cfg(loom)]
+        let ptr = self.data.unsync_load();
+        debug_assert!(!untagged(ptr).is_null());
+        Owned::from_ptr(ptr)
+    }
+}
+
+impl<T> Clone for Atomic<T> {
+    /// Copies the pointer value; the pointee is not cloned.
+    #[inline]
+    fn clone(&self) -> Self {
+        Atomic {
+            data: sync::AtomicPtr::new(self.data.load(Relaxed)),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for Atomic<T> {
+    #[inline]
+    fn default() -> Self {
+        Atomic::null()
+    }
+}
+
+impl<T> fmt::Debug for Atomic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Atomic")
+            .field(&self.data.load(Relaxed))
+            .finish()
+    }
+}
+
+impl<T> From<Owned<T>> for Atomic<T> {
+    #[inline]
+    fn from(owned: Owned<T>) -> Self {
+        Atomic {
+            data: sync::AtomicPtr::new(owned.into_ptr()),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> From<Box<T>> for Atomic<T> {
+    #[inline]
+    fn from(boxed: Box<T>) -> Self {
+        Atomic::from(Owned::from(boxed))
+    }
+}
+
+impl<'g, T> From<Shared<'g, T>> for Atomic<T> {
+    #[inline]
+    fn from(shared: Shared<'g, T>) -> Self {
+        Atomic {
+            data: sync::AtomicPtr::new(shared.into_ptr()),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// The error returned by a failed compare-and-exchange operation on an [`Atomic`].
+pub struct CompareExchangeError<'g, T, P: Pointer<T>> {
+    /// The pointer value at the time of the operation.
+    pub current: Shared<'g, T>,
+
+    /// The pointer that was not stored.
+    pub new: P,
+}
+
+impl<'g, T, P: Pointer<T> + fmt::Debug> fmt::Debug for CompareExchangeError<'g, T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompareExchangeError")
+            .field("current", &self.current)
+            .field("new", &self.new)
+            .finish()
+    }
+}
+
+/// [`Owned`] is a tagged pointer to a heap-allocated object that is exclusively owned.
+pub struct Owned<T> {
+    data: *mut T,
+    _phantom: PhantomData<Box<T>>,
+}
+
+unsafe impl<T: Send> Send for Owned<T> {}
+unsafe impl<T: Sync> Sync for Owned<T> {}
+
+impl<T> Owned<T> {
+    /// Allocates `value` on the heap.
+    #[inline]
+    pub fn new(value: T) -> Owned<T> {
+        Owned::from(Box::new(value))
+    }
+
+    /// Converts the [`Owned`] into a [`Shared`] that is valid while the [`Guard`] is alive.
+    #[inline]
+    pub fn into_shared(self, _guard: &Guard) -> Shared<'_, T> {
+        unsafe { Shared::from_ptr(self.into_ptr()) }
+    }
+
+    /// Converts the [`Owned`] into a [`Box`].
+    #[inline]
+    pub fn into_box(self) -> Box<T> {
+        let raw = untagged(self.data);
+        mem::forget(self);
+        unsafe { Box::from_raw(raw) }
+    }
+
+    /// Returns the tag.
+    #[inline]
+    pub fn tag(&self) -> usize {
+        tag_of(self.data)
+    }
+
+    /// Returns the same pointer with the tag replaced.
+    #[inline]
+    pub fn with_tag(self, tag: usize) -> Owned<T> {
+        let data = self.into_ptr();
+        unsafe { Owned::from_ptr(tagged(data, tag)) }
+    }
+}
+
+impl<T> Pointer<T> for Owned<T> {
+    #[inline]
+    fn into_ptr(self) -> *mut T {
+        let data = self.data;
+        mem::forget(self);
+        data
+    }
+
+    #[inline]
+    unsafe fn from_ptr(ptr: *mut T) -> Self {
+        Owned {
+            data: ptr,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for Owned<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*untagged(self.data) }
+    }
+}
+
+impl<T> DerefMut for Owned<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *untagged(self.data) }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Owned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Owned").field(self.deref()).finish()
+    }
+}
+
+impl<T> From<Box<T>> for Owned<T> {
+    #[inline]
+    fn from(boxed: Box<T>) -> Self {
+        let raw = Box::into_raw(boxed);
+        debug_assert_eq!(tag_of(raw), 0);
+        unsafe { Owned::from_ptr(raw) }
+    }
+}
+
+impl<T> Drop for Owned<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { drop(Box::from_raw(untagged(self.data))) };
+    }
+}
+
+/// [`Shared`] is a tagged pointer that is valid while the [`Guard`] it was loaded under is alive.
+pub struct Shared<'g, T> {
+    data: *mut T,
+    _phantom: PhantomData<(&'g (), *const T)>,
+}
+
+impl<'g, T> Shared<'g, T> {
+    /// Returns a null pointer.
+    #[inline]
+    pub const fn null() -> Shared<'g, T> {
+        Shared {
+            data: ptr::null_mut(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the pointer is null, ignoring the tag.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        untagged(self.data).is_null()
+    }
+
+    /// Returns the raw pointer without the tag.
+    #[inline]
+    pub fn as_raw(&self) -> *const T {
+        untagged(self.data)
+    }
+
+    /// Dereferences the pointer.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be valid.
+    #[inline]
+    pub unsafe fn deref(&self) -> &'g T {
+        debug_assert!(!self.is_null());
+        &*untagged(self.data)
+    }
+
+    /// Mutably dereferences the pointer.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be valid, and no other reference to the object may exist.
+    #[inline]
+    pub unsafe fn deref_mut(&mut self) -> &'g mut T {
+        debug_assert!(!self.is_null());
+        &mut *untagged(self.data)
+    }
+
+    /// Converts the pointer into a reference if it is not null.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be either null or valid.
+    #[inline]
+    pub unsafe fn as_ref(&self) -> Option<&'g T> {
+        untagged(self.data).as_ref()
+    }
+
+    /// Takes ownership of the pointee.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must not be null, and no other thread may be able to read it.
+    #[inline]
+    pub unsafe fn into_owned(self) -> Owned<T> {
+        debug_assert!(!self.is_null());
+        Owned::from_ptr(self.data)
+    }
+
+    /// Returns the tag.
+    #[inline]
+    pub fn tag(&self) -> usize {
+        tag_of(self.data)
+    }
+
+    /// Returns the same pointer with the tag replaced.
+    #[inline]
+    pub fn with_tag(&self, tag: usize) -> Shared<'g, T> {
+        unsafe { Shared::from_ptr(tagged(self.data, tag)) }
+    }
+}
+
+impl<'g, T> Pointer<T> for Shared<'g, T> {
+    #[inline]
+    fn into_ptr(self) -> *mut T {
+        self.data
+    }
+
+    #[inline]
+    unsafe fn from_ptr(ptr: *mut T) -> Self {
+        Shared {
+            data: ptr,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'g, T> Clone for Shared<'g, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'g, T> Copy for Shared<'g, T> {}
+
+impl<'g, T> PartialEq for Shared<'g, T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<'g, T> Eq for Shared<'g, T> {}
+
+impl<'g, T> Default for Shared<'g, T> {
+    #[inline]
+    fn default() -> Self {
+        Shared::null()
+    }
+}
+
+impl<'g, T> fmt::Debug for Shared<'g, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("raw", &self.as_raw())
+            .field("tag", &self.tag())
+            .finish()
+    }
+}
+
+impl<'g, T> From<*const T> for Shared<'g, T> {
+    #[inline]
+    fn from(raw: *const T) -> Self {
+        debug_assert_eq!(tag_of(raw), 0);
+        unsafe { Shared::from_ptr(raw.cast_mut()) }
+    }
+}
+
+/// [`Ref`] is a reference to an object in a container that stays valid while the [`Guard`] it
+/// was obtained under is alive.
+///
+/// An object removed from a container is retired instead of being dropped, and the epoch-based
+/// reclamation mechanism drops it only after every [`Guard`] that could have observed it has been
+/// dropped. A [`Ref`] borrows the [`Guard`], therefore the referenced object outlives the [`Ref`]
+/// even if it is removed from the container in the meantime; the [`Ref`] then refers to a stale
+/// object that is no longer reachable through the container.
+///
+/// A [`Ref`] cannot outlive its [`Guard`].
+/// ```compile_fail
+/// use scc::HashIndex;
+/// use std::collections::hash_map::RandomState;
+///
+/// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+/// assert!(hashindex.insert(1, 10).is_ok());
+///
+/// let value = {
+///     let guard = scc::pin();
+///     hashindex.peek(&1, &guard).unwrap()
+/// };
+/// assert_eq!(*value, 10);
+/// ```
+///
+/// A [`Ref`] cannot be sent to another thread, as the thread that holds the [`Guard`] has to
+/// stay pinned.
+/// ```compile_fail
+/// use scc::HashIndex;
+/// use std::collections::hash_map::RandomState;
+///
+/// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+/// assert!(hashindex.insert(1, 10).is_ok());
+///
+/// let guard = scc::pin();
+/// let value = hashindex.peek(&1, &guard).unwrap();
+/// std::thread::scope(|s| {
+///     s.spawn(move || assert_eq!(*value, 10));
+/// });
+/// ```
+///
+/// # Examples
+/// ```
+/// use scc::{HashIndex, Ref};
+/// use std::collections::hash_map::RandomState;
+///
+/// fn sum<'g>(values: &[Ref<'g, u32>]) -> u32 {
+///     values.iter().map(|value| **value).sum()
+/// }
+///
+/// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+/// assert!(hashindex.insert(1, 10).is_ok());
+/// assert!(hashindex.insert(2, 20).is_ok());
+///
+/// let guard = scc::pin();
+/// let values: Vec<Ref<u32>> = (1..=2).filter_map(|key| hashindex.peek(&key, &guard)).collect();
+///
+/// // The removed value is not dropped while the Guard is alive.
+/// assert!(hashindex.remove(&1));
+/// assert_eq!(sum(&values), 30);
+/// ```
+pub struct Ref<'g, T> {
+    ptr: ptr::NonNull<T>,
+    _guard: PhantomData<(&'g T, &'g Guard)>,
+}
+
+impl<'g, T> Ref<'g, T> {
+    /// Creates a [`Ref`] from a reference that is valid while the [`Guard`] is alive.
+    #[inline]
+    pub(crate) fn new(value: &'g T, _guard: &'g Guard) -> Ref<'g, T> {
+        Ref {
+            ptr: ptr::NonNull::from(value),
+            _guard: PhantomData,
+        }
+    }
+
+    /// Returns the reference with the lifetime of the [`Guard`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    /// assert!(hashindex.insert(1, 10).is_ok());
+    ///
+    /// let guard = scc::pin();
+    /// let value: &u32 = hashindex.peek(&1, &guard).unwrap().get();
+    /// assert_eq!(*value, 10);
+    /// ```
+    #[inline]
+    pub fn get(&self) -> &'g T {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Returns a raw pointer to the object.
+    #[inline]
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+}
+
+impl<'g, T> Clone for Ref<'g, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'g, T> Copy for Ref<'g, T> {}
+
+impl<'g, T: fmt::Debug> fmt::Debug for Ref<'g, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Ref").field(self.get()).finish()
+    }
+}
+
+impl<'g, T> Deref for Ref<'g, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<'g, T: PartialEq> PartialEq for Ref<'g, T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+/// Returns the mask of the low bits of a pointer to `T` that can hold a tag.
+#[inline]
+const fn tag_mask<T>() -> usize {
+    mem::align_of::<T>() - 1
+}
+
+/// Removes the tag from the pointer.
+#[inline]
+fn untagged<T>(ptr: *mut T) -> *mut T {
+    ptr.map_addr(|addr| addr & !tag_mask::<T>())
+}
+
+/// Replaces the tag of the pointer, keeping its provenance.
+#[inline]
+fn tagged<T>(ptr: *mut T, tag: usize) -> *mut T {
+    untagged(ptr).map_addr(|addr| addr | (tag & tag_mask::<T>()))
+}
+
+/// Returns the tag of the pointer.
+#[inline]
+fn tag_of<T>(ptr: *const T) -> usize {
+    ptr.addr() & tag_mask::<T>()
+}
+
+/// Drops a boxed object retired through [`Guard::defer_destroy`].
+unsafe fn drop_box<T>(data: *mut u8) {
+    drop(Box::from_raw(data.cast::<T>()));
+}
+
+/// Executes a boxed closure retired through [`Guard::defer_unchecked`].
+unsafe fn call_box<F: FnOnce()>(data: *mut u8) {
+    let f = Box::from_raw(data.cast::<F>());
+    f();
+}
+
+/// [`Deferred`] is a retired object and the function that drops it.
+struct Deferred {
+    data: *mut u8,
+    call: unsafe fn(*mut u8),
+    epoch: usize,
+    size: usize,
+}
+
+// A Deferred is executed by the thread that retired it unless the thread exits before it
+// expires, as in the case of the objects passed to other epoch-based collectors.
+unsafe impl Send for Deferred {}
+
+impl Deferred {
+    /// Creates a [`Deferred`]; the retirement epoch is set when it is pushed to a participant.
+    fn new(data: *mut u8, call: unsafe fn(*mut u8), size: usize) -> Deferred {
+        Deferred {
+            data,
+            call,
+            epoch: 0,
+            size,
+        }
+    }
+
+    fn is_expired(&self, global_epoch: usize) -> bool {
+        global_epoch.wrapping_sub(self.epoch) >= EXPIRY
+    }
+
+    /// Drops the retired object, and returns its size.
+    fn execute(self) -> usize {
+        let this = ManuallyDrop::new(self);
+        unsafe { (this.call)(this.data) };
+        this.size
+    }
+}
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        unsafe { (self.call)(self.data) };
+    }
+}
+
+/// [`Collector`] is the state of a reclamation domain.
+struct Collector {
+    /// The global epoch of the domain.
+    epoch: AtomicUsize,
+
+    /// The head of the list of participants.
+    participants: AtomicPtr<Participant>,
+
+    /// Retired objects left behind by exited threads.
+    orphans: Orphans,
+
+    /// The estimated number of bytes retained by `orphans`.
+    orphan_bytes: AtomicUsize,
+}
+
+impl Collector {
+    const fn new() -> Collector {
+        Collector {
+            epoch: AtomicUsize::new(0),
+            participants: AtomicPtr::new(ptr::null_mut()),
+            orphans: Orphans::new(),
+            orphan_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Acquires an unowned participant, or registers a new one.
+    fn acquire(&self) -> &Participant {
+        let mut current = self.participants.load(Acquire);
+        while let Some(participant) = unsafe { current.as_ref() } {
+            if !participant.owned.load(Relaxed)
+                && participant
+                    .owned
+                    .compare_exchange(false, true, Acquire, Relaxed)
+                    .is_ok()
+            {
+                return participant;
+            }
+            current = participant.next.load(Acquire);
+        }
+        // The pointer keeps the permission to free the Participant when the Collector is dropped.
+        let participant_ptr = Box::into_raw(Box::new(Participant {
+            collector: self,
+            epoch: AtomicUsize::new(0),
+            owned: AtomicBool::new(true),
+            next: AtomicPtr::new(ptr::null_mut()),
+            num_guards: Cell::new(0),
+            num_pins: Cell::new(0),
+            deferred: UnsafeCell::new(VecDeque::new()),
+            pending: AtomicUsize::new(0),
+            num_pending: AtomicUsize::new(0),
+            oldest_epoch: AtomicUsize::new(PINNED),
+        }));
+        let participant = unsafe { &*participant_ptr };
+        let mut head = self.participants.load(Relaxed);
+        loop {
+            participant.next.store(head, Relaxed);
+            match self
+                .participants
+                .compare_exchange_weak(head, participant_ptr, Release, Relaxed)
+            {
+                Ok(_) => return participant,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Advances the global epoch if every pinned participant has observed it, and returns the
+    /// global epoch.
+    fn try_advance(&self) -> usize {
+        let global_epoch = self.epoch.load(Relaxed);
+        fence(SeqCst);
+        let mut current = self.participants.load(Acquire);
+        while let Some(participant) = unsafe { current.as_ref() } {
+            let epoch = participant.epoch.load(Relaxed);
+            if epoch & PINNED != 0 && epoch & !PINNED != global_epoch {
+                return global_epoch;
+            }
+            current = participant.next.load(Acquire);
+        }
+        fence(Acquire);
+        let new_epoch = global_epoch.wrapping_add(PINNED + 1);
+        match self
+            .epoch
+            .compare_exchange(global_epoch, new_epoch, Release, Relaxed)
+        {
+            Ok(_) => new_epoch,
+            Err(current) => current,
+        }
+    }
+
+    /// Returns `true` if no participant is pinned.
+    fn is_quiescent(&self) -> bool {
+        fence(SeqCst);
+        let mut current = self.participants.load(Acquire);
+        while let Some(participant) = unsafe { current.as_ref() } {
+            if participant.epoch.load(Relaxed) & PINNED != 0 {
+                return false;
+            }
+            current = participant.next.load(Acquire);
+        }
+        true
+    }
+
+    /// Returns the estimated number of bytes retained by retired objects.
+    fn pending_garbage_hint(&self) -> usize {
+        let mut pending = self.orphan_bytes.load(Relaxed);
+        let mut current = self.participants.load(Acquire);
+        while let Some(participant) = unsafe { current.as_ref() } {
+            pending += participant.pending.load(Relaxed);
+            current = participant.next.load(Acquire);
+        }
+        pending
+    }
+
+    /// Returns a snapshot of the state of the domain.
+    fn statistics(&self) -> ReclamationStatistics {
+        let global_epoch = self.epoch.load(Relaxed);
+        let mut pending_objects = 0;
+        let mut pending_bytes = 0;
+        let mut oldest_pending_age = 0;
+        let mut update_oldest = |epoch: usize| {
+            let age = global_epoch.wrapping_sub(epoch) / (PINNED + 1);
+            oldest_pending_age = oldest_pending_age.max(age);
+        };
+        let mut current = self.participants.load(Acquire);
+        while let Some(participant) = unsafe { current.as_ref() } {
+            pending_objects += participant.num_pending.load(Relaxed);
+            pending_bytes += participant.pending.load(Relaxed);
+            let epoch = participant.oldest_epoch.load(Relaxed);
+            if epoch & PINNED == 0 {
+                update_oldest(epoch);
+            }
+            current = participant.next.load(Acquire);
+        }
+        {
+            let orphans = self.orphans.lock();
+            pending_objects += orphans.len();
+            pending_bytes += self.orphan_bytes.load(Relaxed);
+            orphans.iter().for_each(|d| update_oldest(d.epoch));
+        }
+        ReclamationStatistics {
+            pending_objects,
+            pending_bytes,
+            epoch_advancements: global_epoch / (PINNED + 1),
+            oldest_pending_age,
+        }
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        // Every Guard of the domain has been dropped, therefore the remaining retired objects are
+        // unreachable.
+        let mut current = *self.participants.get_mut();
+        while !current.is_null() {
+            let participant = unsafe { Box::from_raw(current) };
+            current = participant.next.load(Relaxed);
+        }
+    }
+}
+
+/// [`Orphans`] is the list of retired objects left behind by exited threads.
+///
+/// The list is protected by a spin lock without the standard library.
+struct Orphans {
+    #[cfg(feature = "std")]
+    list: std::sync::Mutex<Vec<Deferred>>,
+
+    #[cfg(not(feature = "std"))]
+    locked: AtomicBool,
+
+    #[cfg(not(feature = "std"))]
+    list: UnsafeCell<Vec<Deferred>>,
+}
+
+/// [`OrphansGuard`] releases the lock of [`Orphans`] when dropped.
+#[cfg(feature = "std")]
+type OrphansGuard<'o> = std::sync::MutexGuard<'o, Vec<Deferred>>;
+
+#[cfg(feature = "std")]
+impl Orphans {
+    const fn new() -> Orphans {
+        Orphans {
+            list: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Locks the list.
+    fn lock(&self) -> OrphansGuard<'_> {
+        self.list
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Locks the list if no other thread holds the lock.
+    fn try_lock(&self) -> Option<OrphansGuard<'_>> {
+        self.list.try_lock().ok()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Orphans {
+    const fn new() -> Orphans {
+        Orphans {
+            locked: AtomicBool::new(false),
+            list: UnsafeCell::new(Vec::new()),
+        }
+    }
+
+    /// Locks the list.
+    fn lock(&self) -> OrphansGuard<'_> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Locks the list if no other thread holds the lock.
+    fn try_lock(&self) -> Option<OrphansGuard<'_>> {
+        self.locked
+            .compare_exchange(false, true, Acquire, Relaxed)
+            .ok()
+            .map(|_| OrphansGuard { orphans: self })
+    }
+}
+
+// The list is only accessed while the lock is held.
+#[cfg(not(feature = "std"))]
+unsafe impl Sync for Orphans {}
+
+/// [`OrphansGuard`] releases the spin lock of [`Orphans`] when dropped.
+#[cfg(not(feature = "std"))]
+struct OrphansGuard<'o> {
+    orphans: &'o Orphans,
+}
+
+#[cfg(not(feature = "std"))]
+impl<'o> Deref for OrphansGuard<'o> {
+    type Target = Vec<Deferred>;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.orphans.list.get() }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<'o> DerefMut for OrphansGuard<'o> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.orphans.list.get() }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<'o> Drop for OrphansGuard<'o> {
+    fn drop(&mut self) {
+        self.orphans.locked.store(false, Release);
+    }
+}
+
+/// [`Participant`] is the per-thread state that the global epoch tracks.
+///
+/// Participants of the global domain are never deallocated, and those of a [`Domain`] are
+/// deallocated along with the [`Domain`]; a participant released by an exited thread is reused by
+/// the next thread that registers.
+struct Participant {
+    /// The collector of the domain that the participant belongs to.
+    collector: *const Collector,
+
+    /// The epoch that the thread observed when it was pinned, or zero.
+    epoch: AtomicUsize,
+
+    /// The participant is owned by a thread.
+    owned: AtomicBool,
+
+    /// The next participant in the list.
+    next: AtomicPtr<Participant>,
+
+    /// The number of [`Guard`] instances of the owner thread.
+    num_guards: Cell<usize>,
+
+    /// The number of times the owner thread has been pinned.
+    num_pins: Cell<usize>,
+
+    /// The retired objects of the owner thread.
+    deferred: UnsafeCell<VecDeque<Deferred>>,
+
+    /// The estimated number of bytes retained by the retired objects of the owner thread.
+    ///
+    /// It is only updated by the owner thread.
+    pending: AtomicUsize,
+
+    /// The number of retired objects of the owner thread.
+    ///
+    /// It is only updated by the owner thread.
+    num_pending: AtomicUsize,
+
+    /// The retirement epoch of the oldest retired object of the owner thread, or [`PINNED`] if
+    /// there is none.
+    ///
+    /// It is only updated by the owner thread.
+    oldest_epoch: AtomicUsize,
+}
+
+// The Cell and UnsafeCell fields are only accessed by the owner thread.
+unsafe impl Sync for Participant {}
+
+impl Participant {
+    /// Returns the collector of the domain that the participant belongs to.
+    fn collector(&self) -> &Collector {
+        // A participant never outlives its collector.
+        unsafe { &*self.collector }
+    }
+
+    /// Hands over the retired objects to the domain, and releases the participant.
+    fn release(&self) {
+        let deferred = mem::take(unsafe { &mut *self.deferred.get() });
+        if !deferred.is_empty() {
+            let collector = self.collector();
+            let mut orphans = collector.orphans.lock();
+            orphans.extend(deferred);
+            collector
+                .orphan_bytes
+                .fetch_add(self.pending.swap(0, Relaxed), Relaxed);
+            self.num_pending.store(0, Relaxed);
+            self.oldest_epoch.store(PINNED, Relaxed);
+        }
+        self.owned.store(false, Release);
+    }
+
+    /// Pushes a retired object, and returns the number of retired objects.
+    fn push(&self, mut deferred: Deferred) -> usize {
+        deferred.epoch = self.collector().epoch.load(Relaxed);
+        let bag = unsafe { &mut *self.deferred.get() };
+        self.pending
+            .store(self.pending.load(Relaxed) + deferred.size, Relaxed);
+        if bag.is_empty() {
+            self.oldest_epoch.store(deferred.epoch, Relaxed);
+        }
+        bag.push_back(deferred);
+        self.num_pending.store(bag.len(), Relaxed);
+        bag.len()
+    }
+
+    /// Tries to advance the global epoch, and drops expired objects.
+    ///
+    /// Objects left behind by exited threads are reclaimed only if `orphans` is `true` or the
+    /// global list is not contended. Returns the estimated number of bytes reclaimed.
+    fn collect(&self, orphans: bool) -> usize {
+        let mut reclaimed = 0;
+        let collector = self.collector();
+        let global_epoch = collector.try_advance();
+
+        // Objects are retired in epoch order, therefore the expired ones form a prefix. They are
+        // taken out before being dropped as dropping them may retire further objects.
+        let bag = unsafe { &mut *self.deferred.get() };
+        let num_expired = bag
+            .iter()
+            .take_while(|d| d.is_expired(global_epoch))
+            .count();
+        if num_expired != 0 {
+            let expired: Vec<Deferred> = bag.drain(..num_expired).collect();
+            let size: usize = expired.iter().map(|d| d.size).sum();
+            self.pending
+                .store(self.pending.load(Relaxed) - size, Relaxed);
+            self.num_pending.store(bag.len(), Relaxed);
+            self.oldest_epoch
+                .store(bag.front().map_or(PINNED, |d| d.epoch), Relaxed);
+            expired.into_iter().for_each(|d| reclaimed += d.execute());
+        }
+
+        let orphans = if orphans {
+            Some(collector.orphans.lock())
+        } else {
+            collector.orphans.try_lock()
+        };
+        if let Some(mut orphans) = orphans {
+            if orphans.iter().any(|d| d.is_expired(global_epoch)) {
+                let (expired, remaining): (Vec<Deferred>, Vec<Deferred>) = mem::take(&mut *orphans)
+                    .into_iter()
+                    .partition(|d| d.is_expired(global_epoch));
+                *orphans = remaining;
+                collector
+                    .orphan_bytes
+                    .fetch_sub(expired.iter().map(|d| d.size).sum(), Relaxed);
+                drop(orphans);
+                expired.into_iter().for_each(|d| reclaimed += d.execute());
+            }
+        }
+        reclaimed
+    }
+}
+
+/// Pins the current thread with `pin` in each round, and drops the retired objects that are no
+/// longer reachable.
+fn flush_rounds<P: Fn() -> Guard>(pin: P) -> usize {
+    let mut reclaimed = 0;
+    for _ in 0..FLUSH_ROUNDS {
+        // The thread is pinned again in each round so that it does not prevent the global epoch
+        // from advancing.
+        let guard = pin();
+        if let Some(participant) = unsafe { guard.participant.as_ref() } {
+            reclaimed += participant.collect(true);
+        }
+    }
+    reclaimed
+}
+
+/// Returns the participant of the current thread in the global domain.
+///
+/// Returns `None` if the thread-local participant has already been released as the thread is
+/// exiting.
+#[cfg(feature = "std")]
+#[inline]
+fn local_participant() -> Option<&'static Participant> {
+    LOCAL.try_with(|local| local.participant).ok()
+}
+
+/// Returns `None` as there is no thread-local storage without the standard library, in which
+/// case each [`Guard`] acquires a participant of its own.
+#[cfg(not(feature = "std"))]
+#[inline]
+fn local_participant() -> Option<&'static Participant> {
+    None
+}
+
+/// Returns the participant of the current thread in the domain of the collector, registering a
+/// new one if the thread has none.
+///
+/// Returns `None` if the thread-local participants have already been released as the thread is
+/// exiting.
+#[cfg(feature = "std")]
+fn domain_participant(collector: &Arc<Collector>) -> Option<&Participant> {
+    DOMAIN_LOCALS
+        .try_with(|locals| {
+            let mut locals = locals.borrow_mut();
+            locals.retain(|local| local.collector.strong_count() != 0);
+            match locals
+                .iter()
+                .find(|local| ptr::eq(local.collector.as_ptr(), Arc::as_ptr(collector)))
+            {
+                Some(local) => unsafe { &*local.participant },
+                None => {
+                    let participant = collector.acquire();
+                    locals.push(DomainLocal {
+                        collector: Arc::downgrade(collector),
+                        participant,
+                    });
+                    participant
+                }
+            }
+        })
+        .ok()
+}
+
+/// Returns `None` as there is no thread-local storage without the standard library.
+#[cfg(not(feature = "std"))]
+fn domain_participant(_collector: &Arc<Collector>) -> Option<&Participant> {
+    None
+}
+
+/// [`Local`] owns the participant of a thread in the global domain.
+#[cfg(feature = "std")]
+struct Local {
+    participant: &'static Participant,
+}
+
+#[cfg(feature = "std")]
+impl Local {
+    fn register() -> Local {
+        Local {
+            participant: GLOBAL.acquire(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for Local {
+    fn drop(&mut self) {
+        if self.participant.num_guards.get() == 0 {
+            self.participant.release();
+        }
+    }
+}
+
+/// [`DomainLocal`] owns the participant of a thread in a [`Domain`].
+#[cfg(feature = "std")]
+struct DomainLocal {
+    collector: Weak<Collector>,
+    participant: *const Participant,
+}
+
+#[cfg(feature = "std")]
+impl Drop for DomainLocal {
+    fn drop(&mut self) {
+        // The participant has been deallocated if the Domain is gone.
+        if let Some(_collector) = self.collector.upgrade() {
+            let participant = unsafe { &*self.participant };
+            if participant.num_guards.get() == 0 {
+                participant.release();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::thread;
+
+    struct R(Arc<AtomicUsize>);
+
+    impl Drop for R {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    #[test]
+    fn deferred_until_unpinned() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let atomic = Atomic::new(R(dropped.clone()));
+        let reader = pin();
+        let reader_ref = unsafe { atomic.load(Acquire, &reader).deref() };
+        thread::scope(|s| {
+            s.spawn(|| {
+                let guard = pin();
+                let old = atomic.swap(Shared::null(), Relaxed, &guard);
+                unsafe { guard.defer_destroy(old) };
+                for _ in 0..1024 {
+                    pin().flush();
+                }
+            })
+            .join()
+            .unwrap();
+        });
+        assert_eq!(dropped.load(Relaxed), 0);
+        assert!(Arc::ptr_eq(&reader_ref.0, &dropped));
+        drop(reader);
+        while dropped.load(Relaxed) == 0 {
+            pin().flush();
+        }
+        assert_eq!(dropped.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn flush() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let guard = pin();
+        for _ in 0..16 {
+            let retired = Owned::new((R(dropped.clone()), [0_u8; 4096])).into_shared(&guard);
+            unsafe { guard.defer_destroy(retired) };
+        }
+        assert!(pending_garbage_hint() >= 16 * 4096);
+        drop(guard);
+        let mut reclaimed = 0;
+        while dropped.load(Relaxed) != 16 {
+            reclaimed += super::flush();
+        }
+        assert!(reclaimed >= 16 * 4096);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn statistics() {
+        let participant = LOCAL.with(|local| local.participant);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let guard = pin();
+        for _ in 0..16 {
+            let retired = Owned::new(R(dropped.clone())).into_shared(&guard);
+            unsafe { guard.defer_destroy(retired) };
+        }
+        assert_eq!(participant.num_pending.load(Relaxed), 16);
+        assert_eq!(participant.oldest_epoch.load(Relaxed) & PINNED, 0);
+        let statistics = reclamation_statistics();
+        assert!(statistics.pending_objects() >= 16);
+        assert!(statistics.pending_bytes() >= 16 * mem::size_of::<R>());
+
+        drop(guard);
+        while dropped.load(Relaxed) != 16 {
+            super::flush();
+        }
+        assert_eq!(participant.num_pending.load(Relaxed), 0);
+        assert_eq!(participant.pending.load(Relaxed), 0);
+        assert_eq!(participant.oldest_epoch.load(Relaxed), PINNED);
+        assert!(reclamation_statistics().epoch_advancements() >= 2);
+    }
+
+    #[test]
+    fn domain_isolation() {
+        let domain_a = Domain::new();
+        let domain_b = Domain::new();
+        let dropped_a = Arc::new(AtomicUsize::new(0));
+        let dropped_b = Arc::new(AtomicUsize::new(0));
+
+        // The thread stays pinned in the global domain and in domain A.
+        let global_guard = pin();
+        let guard_a = domain_a.pin();
+        let retired = Owned::new(R(dropped_a.clone())).into_shared(&guard_a);
+        unsafe { guard_a.defer_destroy(retired) };
+        let guard_b = domain_b.pin();
+        let retired = Owned::new(R(dropped_b.clone())).into_shared(&guard_b);
+        unsafe { guard_b.defer_destroy(retired) };
+        drop(guard_b);
+
+        while dropped_b.load(Relaxed) == 0 {
+            domain_b.flush();
+        }
+        for _ in 0..16 {
+            domain_a.flush();
+        }
+        assert_eq!(dropped_a.load(Relaxed), 0);
+        assert_eq!(domain_b.reclamation_statistics().pending_objects(), 0);
+
+        drop(guard_a);
+        while dropped_a.load(Relaxed) == 0 {
+            domain_a.flush();
+        }
+        drop(global_guard);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn domain_drop() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::new();
+        let guard = domain.pin();
+        let retired = Owned::new(R(dropped.clone())).into_shared(&guard);
+        unsafe { guard.defer_destroy(retired) };
+        thread::scope(|s| {
+            s.spawn(|| domain.pin().defer(|| ()));
+        });
+        // The exited thread may still be destroying its thread-local storage, which refers to the
+        // Collector, and temporarily holds it while releasing the participant; the fence makes the
+        // release visible once the thread has let go of the Collector.
+        while Arc::weak_count(domain.collector.as_ref().unwrap()) > 1 {
+            thread::yield_now();
+        }
+        fence(Acquire);
+        assert_eq!(domain.reclamation_statistics().pending_objects(), 2);
+        drop(guard);
+        drop(domain);
+        assert_eq!(dropped.load(Relaxed), 1);
+        DOMAIN_LOCALS.with(|locals| {
+            assert!(locals
+                .borrow()
+                .iter()
+                .all(|local| local.collector.strong_count() == 0));
+        });
+    }
+
+    #[test]
+    fn nested_guards() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let outer = pin();
+        let inner = pin();
+        let retired = Owned::new(R(dropped.clone())).into_shared(&inner);
+        unsafe { inner.defer_destroy(retired) };
+        drop(inner);
+        for _ in 0..16 {
+            outer.flush();
+        }
+        assert_eq!(dropped.load(Relaxed), 0);
+        drop(outer);
+        while dropped.load(Relaxed) == 0 {
+            pin().flush();
+        }
+    }
+
+    #[test]
+    fn tagged_pointer() {
+        let atomic: Atomic<u64> = Atomic::new(7);
+        let guard = pin();
+        let ptr = atomic.load(Relaxed, &guard);
+        assert_eq!(ptr.tag(), 0);
+        let tagged = ptr.with_tag(3);
+        assert_eq!(tagged.tag(), 3);
+        assert_eq!(tagged.as_raw(), ptr.as_raw());
+        assert!(atomic
+            .compare_exchange(ptr, tagged, Relaxed, Relaxed, &guard)
+            .is_ok());
+        let err = atomic
+            .compare_exchange(ptr, Shared::null(), Relaxed, Relaxed, &guard)
+            .unwrap_err();
+        assert_eq!(err.current, tagged);
+        assert_eq!(unsafe { *atomic.load(Relaxed, &guard).deref() }, 7);
+        drop(guard);
+        drop(unsafe { atomic.into_owned() });
+    }
+
+    #[test]
+    fn tagged_owned() {
+        // The tagged pointer retains its provenance, so it remains writable and deallocatable.
+        let mut owned = Owned::new(1_u64).with_tag(1);
+        *owned += 1;
+        assert_eq!(owned.tag(), 1);
+        let atomic = Atomic::from(owned);
+        let guard = pin();
+        let ptr = atomic.load(Relaxed, &guard);
+        assert_eq!(ptr.tag(), 1);
+        assert_eq!(unsafe { *ptr.deref() }, 2);
+        drop(guard);
+        assert_eq!(*unsafe { atomic.into_owned() }.with_tag(0).into_box(), 2);
+    }
+
+    #[test]
+    fn exited_thread() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let dropped_cloned = dropped.clone();
+        thread::spawn(move || {
+            let guard = pin();
+            guard.defer(move || drop(R(dropped_cloned)));
+        })
+        .join()
+        .unwrap();
+        while dropped.load(Relaxed) == 0 {
+            pin().flush();
+        }
+    }
+}
@@ -0,0 +1,1024 @@
+use crate::common::hash_table::{
+    DefaultBuildHasher, ShrinkPolicy, TryReserveError, DEFAULT_HASH_FINALIZER,
+};
+#[cfg(feature = "metrics")]
+use crate::common::metrics::{CacheCounter, CacheCounters, CacheStatistics};
+use crate::common::trace::trace_event;
+use crate::ebr::{Atomic, Guard, Owned, Shared};
+use crate::hash_map::HashMap;
+
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed};
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::time::{Duration, Instant};
+
+/// A scalable concurrent cache data structure.
+///
+/// scc::HashCache is a bounded variant of [`HashMap`]: the array is never resized, and each Cell
+/// never grows beyond a single entry array. Each key-value pair is stamped with a logical clock
+/// value when it is put or read by [`HashCache::get`], and when a new key-value pair is put into
+/// a full Cell, the least recently used key-value pair in the Cell is evicted.
+///
+/// Eviction only takes the Cell that the new key belongs to into account, therefore no global
+/// lock or list is involved, and the eviction policy is an approximation of LRU that samples the
+/// key-value pairs in a Cell. The number of key-value pairs never exceeds the capacity, however,
+/// a key-value pair can be evicted before the HashCache is full if the Cell is full.
+///
+/// The number of key-value pairs can be further limited by [`HashCache::set_capacity`]; excess
+/// key-value pairs are evicted in the least recently used order of each Cell by subsequent
+/// [`HashCache::put`] calls.
+///
+/// A key-value pair put by [`HashCache::put_with_ttl`] expires after the given duration; expired
+/// key-value pairs are regarded as absent, evicted ahead of any other key-value pairs in the
+/// same Cell, and removed lazily when accessed or by [`HashCache::purge_expired`].
+///
+/// ## The key statistics for scc::HashCache
+/// * The number of key-value pairs in a Cell that an eviction process chooses from: 32.
+/// * The size of the recency metadata for a single key-value pair: 8-byte.
+/// * The size of the expiry metadata for a single key-value pair: 16-byte.
+pub struct HashCache<K, V, H = DefaultBuildHasher>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+{
+    hash_map: HashMap<K, CacheEntry<V>, H>,
+    clock: AtomicU64,
+    eviction_hook: Atomic<Box<EvictionHookFn<K, V>>>,
+    time_source: Box<TimeSourceFn>,
+    purge_cursor: AtomicUsize,
+    capacity_bound: AtomicUsize,
+    num_entries: AtomicUsize,
+    shrink_cursor: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    statistics: CacheCounters,
+}
+
+/// EvictionHookFn is the type of eviction hooks.
+type EvictionHookFn<K, V> = dyn Fn(&K, &V) + Send + Sync;
+
+/// TimeSourceFn is the type of the function that tells the current time.
+type TimeSourceFn = dyn Fn() -> Instant + Send + Sync;
+
+/// CacheEntry is a value along with the clock value of the last access and the expiry time.
+struct CacheEntry<V> {
+    value: V,
+    accessed: AtomicU64,
+    expiry: Option<Instant>,
+}
+
+impl<V> CacheEntry<V> {
+    /// Returns `true` if the entry has expired at the given time.
+    fn expired(&self, now: Instant) -> bool {
+        self.expiry.is_some_and(|expiry| expiry <= now)
+    }
+
+    /// Returns the eviction rank of the entry; the entry with the smallest rank is evicted first.
+    ///
+    /// Expired entries are evicted ahead of the least recently used one.
+    fn rank(&self, now: Instant) -> u64 {
+        if self.expired(now) {
+            0
+        } else {
+            self.accessed.load(Relaxed).saturating_add(1)
+        }
+    }
+}
+
+impl<K, V> Default for HashCache<K, V, DefaultBuildHasher>
+where
+    K: Eq + Hash,
+{
+    /// Creates a HashCache instance with the default parameters.
+    ///
+    /// The default hash builder is [`DefaultBuildHasher`](crate::DefaultBuildHasher), and the
+    /// default capacity is 64.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// let result = hashcache.capacity();
+    /// assert_eq!(result, 64);
+    /// ```
+    fn default() -> Self {
+        HashCache::from_hash_map(
+            HashMap::new(64, DefaultBuildHasher::default())
+                .with_hash_finalizer(DEFAULT_HASH_FINALIZER),
+        )
+    }
+}
+
+impl<K, V, H> HashCache<K, V, H>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+{
+    /// Creates an empty HashCache instance with the given capacity and build hasher.
+    ///
+    /// The actual capacity is equal to or greater than the given capacity, and it never changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashcache: HashCache<u64, u32, RandomState> = HashCache::new(1000, RandomState::new());
+    ///
+    /// let result = hashcache.capacity();
+    /// assert_eq!(result, 1024);
+    /// ```
+    pub fn new(capacity: usize, build_hasher: H) -> HashCache<K, V, H> {
+        HashCache::from_hash_map(HashMap::new(capacity, build_hasher))
+    }
+
+    /// Creates a HashCache instance on top of the given empty HashMap.
+    fn from_hash_map(hash_map: HashMap<K, CacheEntry<V>, H>) -> HashCache<K, V, H> {
+        let hash_map = hash_map.with_shrink_policy(ShrinkPolicy::Never);
+        let capacity_bound = AtomicUsize::new(hash_map.capacity());
+        HashCache {
+            hash_map,
+            clock: AtomicU64::new(0),
+            eviction_hook: Atomic::null(),
+            time_source: Box::new(Instant::now),
+            purge_cursor: AtomicUsize::new(0),
+            capacity_bound,
+            num_entries: AtomicUsize::new(0),
+            shrink_cursor: AtomicUsize::new(0),
+            #[cfg(feature = "metrics")]
+            statistics: CacheCounters::default(),
+        }
+    }
+
+    /// Replaces the function that tells the current time to determine whether a key-value pair
+    /// has expired.
+    ///
+    /// The default time source is [`Instant::now`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    /// use std::collections::hash_map::RandomState;
+    /// use std::sync::atomic::AtomicU64;
+    /// use std::sync::atomic::Ordering::Relaxed;
+    /// use std::sync::Arc;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let base = Instant::now();
+    /// let elapsed = Arc::new(AtomicU64::new(0));
+    /// let elapsed_cloned = elapsed.clone();
+    /// let hashcache: HashCache<u64, u32, RandomState> = HashCache::new(64, RandomState::new())
+    ///     .with_time_source(move || base + Duration::from_secs(elapsed_cloned.load(Relaxed)));
+    ///
+    /// assert!(hashcache.put_with_ttl(1, 0, Duration::from_secs(10)).is_ok());
+    /// elapsed.store(10, Relaxed);
+    /// assert!(!hashcache.contains(&1));
+    /// ```
+    pub fn with_time_source<F: Fn() -> Instant + Send + Sync + 'static>(
+        mut self,
+        time_source: F,
+    ) -> Self {
+        self.time_source = Box::new(time_source);
+        self
+    }
+
+    /// Puts a key-value pair into the HashCache.
+    ///
+    /// It returns the evicted key-value pair if the Cell that the key belongs to is full. The
+    /// key-value pair never expires. Key-value pairs evicted in order to enforce
+    /// [`HashCache::capacity_bound`] are not returned, but passed to the eviction hook.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error along with the supplied key-value pair if the key exists and has not
+    /// expired.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert_eq!(hashcache.put(1, 0), Ok(None));
+    /// assert_eq!(hashcache.put(1, 1), Err((1, 1)));
+    ///
+    /// let mut num_evicted = 0;
+    /// for key in 2..=1024 {
+    ///     if hashcache.put(key, 0).unwrap().is_some() {
+    ///         num_evicted += 1;
+    ///     }
+    /// }
+    /// assert_eq!(hashcache.len() + num_evicted, 1024);
+    /// assert!(hashcache.len() <= hashcache.capacity());
+    /// ```
+    pub fn put(&self, key: K, value: V) -> Result<Option<(K, V)>, (K, V)> {
+        self.put_entry(key, value, None)
+    }
+
+    /// Puts a key-value pair that expires after the given duration into the HashCache.
+    ///
+    /// The key-value pair expires when the time source reaches the current time plus `ttl`. It
+    /// returns the evicted key-value pair if the Cell that the key belongs to is full; expired
+    /// key-value pairs in the Cell are evicted first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error along with the supplied key-value pair if the key exists and has not
+    /// expired.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    /// use std::time::Duration;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert_eq!(hashcache.put_with_ttl(1, 0, Duration::from_secs(60)), Ok(None));
+    /// assert_eq!(hashcache.put_with_ttl(1, 1, Duration::ZERO), Err((1, 1)));
+    ///
+    /// assert_eq!(hashcache.put_with_ttl(2, 0, Duration::ZERO), Ok(None));
+    /// assert!(hashcache.get(&2, |_, _| ()).is_none());
+    /// ```
+    pub fn put_with_ttl(&self, key: K, value: V, ttl: Duration) -> Result<Option<(K, V)>, (K, V)> {
+        let now = (self.time_source)();
+        self.put_entry(key, value, Some(now.checked_add(ttl).unwrap_or(now)))
+    }
+
+    /// Reads the value associated with the key, or puts a value constructed by `init` if the key
+    /// does not exist or has expired.
+    ///
+    /// The Cell that the key belongs to stays locked while `init` and `reader` are running,
+    /// therefore `init` is invoked at most once even if multiple threads request the same
+    /// missing key at the same time; the other threads are blocked until the value is
+    /// constructed, and then read it. Since threads accessing other keys in the same Cell are
+    /// blocked as well, `init` and `reader` must not access the HashCache.
+    ///
+    /// It returns the result of `reader`, and `true` if the key was missing and `init` has been
+    /// invoked. The put key-value pair never expires, and evicted key-value pairs are passed to
+    /// the eviction hook.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert_eq!(hashcache.get_or_put_with(1, || 10, |value| *value), (10, true));
+    /// assert_eq!(hashcache.get_or_put_with(1, || 11, |value| *value), (10, false));
+    /// ```
+    pub fn get_or_put_with<F: FnOnce() -> V, R, G: FnOnce(&V) -> R>(
+        &self,
+        key: K,
+        init: F,
+        reader: G,
+    ) -> (R, bool) {
+        let now = (self.time_source)();
+        let (result, inserted) = self.hash_map.read_or_insert_bounded(
+            key,
+            || self.new_entry(init),
+            |entry| self.touch(entry, now),
+            |_, entry| entry.rank(now),
+            |_, entry| reader(&entry.value),
+            |key, entry| {
+                self.num_entries.fetch_sub(1, Relaxed);
+                self.report_eviction(&key, &entry, now);
+            },
+        );
+        self.complete_get_or_put(inserted, now);
+        (result, inserted)
+    }
+
+    /// Reads the value associated with the key, or puts a value constructed by `init` if the key
+    /// does not exist or has expired, without blocking the thread.
+    ///
+    /// It is an asynchronous counterpart of [`HashCache::get_or_put_with`]; a task requesting a
+    /// key of which the Cell is locked is suspended instead of blocking the thread until the
+    /// Cell is released, therefore `init` is invoked at most once even if multiple tasks request
+    /// the same missing key at the same time. The Cell stays locked while `init` and `reader` are
+    /// running, and they are not allowed to access the HashCache.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// let future_get_or_put = hashcache.get_or_put_with_async(1, || 10, |value| *value);
+    /// ```
+    pub async fn get_or_put_with_async<F: FnOnce() -> V, R, G: FnOnce(&V) -> R>(
+        &self,
+        key: K,
+        init: F,
+        reader: G,
+    ) -> (R, bool) {
+        let now = (self.time_source)();
+        let (result, inserted) = self
+            .hash_map
+            .read_or_insert_bounded_async(
+                key,
+                || self.new_entry(init),
+                |entry| self.touch(entry, now),
+                |_, entry| entry.rank(now),
+                |_, entry| reader(&entry.value),
+                |key, entry| {
+                    self.num_entries.fetch_sub(1, Relaxed);
+                    self.report_eviction(&key, &entry, now);
+                },
+            )
+            .await;
+        self.complete_get_or_put(inserted, now);
+        (result, inserted)
+    }
+
+    /// Reads a key-value pair, and marks it as recently used.
+    ///
+    /// An expired key-value pair is regarded as absent, and removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.get(&1, |_, value| *value).is_none());
+    /// assert!(hashcache.put(1, 10).is_ok());
+    /// assert_eq!(hashcache.get(&1, |_, value| *value).unwrap(), 10);
+    /// ```
+    pub fn get<Q, R, F: FnOnce(&Q, &V) -> R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let now = (self.time_source)();
+        let result = self.hash_map.read(key, |key, entry| {
+            if entry.expired(now) {
+                return None;
+            }
+            // Reading the clock does not contend with other readers.
+            entry.accessed.fetch_max(self.clock.load(Relaxed), Relaxed);
+            Some(f(key, &entry.value))
+        });
+        #[cfg(feature = "metrics")]
+        self.statistics.add(
+            if matches!(result, Some(Some(_))) {
+                CacheCounter::Hits
+            } else {
+                CacheCounter::Misses
+            },
+            1,
+        );
+        let result = result?;
+        if result.is_none() {
+            self.remove_expired(key, now);
+        }
+        result
+    }
+
+    /// Reads a key-value pair without marking it as recently used.
+    ///
+    /// An expired key-value pair is regarded as absent, and removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.peek(&1, |_, value| *value).is_none());
+    /// assert!(hashcache.put(1, 10).is_ok());
+    /// assert_eq!(hashcache.peek(&1, |_, value| *value).unwrap(), 10);
+    /// ```
+    pub fn peek<Q, R, F: FnOnce(&Q, &V) -> R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let now = (self.time_source)();
+        let result = self.hash_map.read(key, |key, entry| {
+            if entry.expired(now) {
+                None
+            } else {
+                Some(f(key, &entry.value))
+            }
+        })?;
+        if result.is_none() {
+            self.remove_expired(key, now);
+        }
+        result
+    }
+
+    /// Checks if the key exists without marking it as recently used.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(!hashcache.contains(&1));
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert!(hashcache.contains(&1));
+    /// ```
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.peek(key, |_, _| ()).is_some()
+    }
+
+    /// Removes a key-value pair, and returns the value.
+    ///
+    /// The value is returned even if the key-value pair has expired.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.remove(&1).is_none());
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert_eq!(hashcache.remove(&1).unwrap(), 0);
+    /// ```
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entry = self.hash_map.remove(key)?;
+        self.num_entries.fetch_sub(1, Relaxed);
+        Some(entry.value)
+    }
+
+    /// Clears all the key-value pairs.
+    ///
+    /// It returns the number of removed key-value pairs.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert_eq!(hashcache.clear(), 1);
+    /// ```
+    pub fn clear(&self) -> usize {
+        let guard = crate::ebr::pin();
+        match self.eviction_hook(&guard) {
+            Some(eviction_hook) => self.hash_map.clear_with(|key, entry| {
+                // The counter is updated even if the eviction hook panics.
+                self.num_entries.fetch_sub(1, Relaxed);
+                eviction_hook(key, &entry.value);
+            }),
+            None => {
+                let num_removed = self.hash_map.clear();
+                self.num_entries.fetch_sub(num_removed, Relaxed);
+                num_removed
+            }
+        }
+    }
+
+    /// Removes expired key-value pairs from up to `budget` Cells, and returns the number of
+    /// removed key-value pairs.
+    ///
+    /// A shared cursor is kept in the HashCache, therefore successive calls examine different
+    /// Cells, and the whole HashCache is examined once every `capacity / 32` Cells. At least a
+    /// single Cell is examined.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    /// use std::time::Duration;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.put_with_ttl(1, 0, Duration::ZERO).is_ok());
+    /// assert!(hashcache.put(2, 0).is_ok());
+    /// assert_eq!(hashcache.len(), 2);
+    ///
+    /// assert_eq!(hashcache.purge_expired(hashcache.capacity()), 1);
+    /// assert_eq!(hashcache.len(), 1);
+    /// ```
+    pub fn purge_expired(&self, budget: usize) -> usize {
+        let now = (self.time_source)();
+        let guard = crate::ebr::pin();
+        let eviction_hook = self.eviction_hook(&guard);
+        self.hash_map.prune_chunked(
+            &self.purge_cursor,
+            budget,
+            |_, entry| !entry.expired(now),
+            |key, entry| {
+                self.num_entries.fetch_sub(1, Relaxed);
+                #[cfg(feature = "metrics")]
+                self.statistics.add(CacheCounter::Expirations, 1);
+                if let Some(eviction_hook) = eviction_hook {
+                    eviction_hook(&key, &entry.value);
+                }
+            },
+        )
+    }
+
+    /// Returns a snapshot of the cache statistics.
+    ///
+    /// Lookups made by [`HashCache::get`] and [`HashCache::get_or_put_with`] are counted as
+    /// hits or misses, whereas [`HashCache::peek`] and [`HashCache::contains`] are not counted.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert!(hashcache.get(&1, |_, _| ()).is_some());
+    /// assert!(hashcache.get(&2, |_, _| ()).is_none());
+    ///
+    /// let statistics = hashcache.cache_statistics();
+    /// assert_eq!(statistics.puts(), 1);
+    /// assert_eq!(statistics.hits(), 1);
+    /// assert_eq!(statistics.misses(), 1);
+    /// assert_eq!(statistics.hit_ratio(), 0.5);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn cache_statistics(&self) -> CacheStatistics {
+        self.statistics.snapshot()
+    }
+
+    /// Resets the cache statistics.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{CacheStatistics, HashCache};
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// hashcache.reset_cache_statistics();
+    /// assert_eq!(hashcache.cache_statistics(), CacheStatistics::default());
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn reset_cache_statistics(&self) {
+        self.statistics.reset();
+    }
+
+    /// Sets the eviction hook that is invoked for each evicted key-value pair.
+    ///
+    /// The eviction hook is invoked exactly once for each key-value pair that is evicted by
+    /// [`HashCache::put`], [`HashCache::put_with_ttl`], or [`HashCache::get_or_put_with`],
+    /// removed by [`HashCache::clear`], or removed after expiry, after the Cell is unlocked, and
+    /// therefore it is allowed to access the HashCache. Key-value pairs removed by [`HashCache::remove`] or dropped along with the
+    /// HashCache are not reported.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    /// use std::sync::atomic::AtomicUsize;
+    /// use std::sync::atomic::Ordering::Relaxed;
+    /// use std::sync::Arc;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    /// let num_evicted = Arc::new(AtomicUsize::new(0));
+    /// let num_evicted_cloned = num_evicted.clone();
+    /// hashcache.set_eviction_hook(move |_, _| {
+    ///     num_evicted_cloned.fetch_add(1, Relaxed);
+    /// });
+    ///
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert!(hashcache.put(2, 0).is_ok());
+    /// assert_eq!(hashcache.remove(&1), Some(0));
+    /// assert_eq!(hashcache.clear(), 1);
+    /// assert_eq!(num_evicted.load(Relaxed), 1);
+    /// ```
+    pub fn set_eviction_hook<F: Fn(&K, &V) + Send + Sync + 'static>(&self, hook: F) {
+        let eviction_hook: Box<EvictionHookFn<K, V>> = Box::new(hook);
+        let guard = crate::ebr::pin();
+        let old_eviction_hook = self
+            .eviction_hook
+            .swap(Owned::new(eviction_hook), AcqRel, &guard);
+        if !old_eviction_hook.is_null() {
+            unsafe { guard.defer_destroy(old_eviction_hook) };
+        }
+    }
+
+    /// Clears the eviction hook.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// hashcache.set_eviction_hook(|_, _| unreachable!());
+    /// hashcache.clear_eviction_hook();
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert_eq!(hashcache.clear(), 1);
+    /// ```
+    pub fn clear_eviction_hook(&self) {
+        let guard = crate::ebr::pin();
+        let old_eviction_hook = self.eviction_hook.swap(Shared::null(), AcqRel, &guard);
+        if !old_eviction_hook.is_null() {
+            unsafe { guard.defer_destroy(old_eviction_hook) };
+        }
+    }
+
+    /// Returns the number of key-value pairs.
+    ///
+    /// It scans the entire array to calculate the number of key-value pairs, and expired
+    /// key-value pairs that have not been removed yet are counted.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert_eq!(hashcache.len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.hash_map.len()
+    }
+
+    /// Returns `true` if the HashCache contains no key-value pairs.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.is_empty());
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert!(!hashcache.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the estimated number of key-value pairs.
+    ///
+    /// It reads a counter that is updated by each operation, therefore it does not scan the
+    /// array, but may be momentarily off while other threads are modifying the HashCache.
+    /// Expired key-value pairs that have not been removed yet are counted.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// assert!(hashcache.put(1, 0).is_ok());
+    /// assert_eq!(hashcache.estimated_len(), 1);
+    /// ```
+    pub fn estimated_len(&self) -> usize {
+        self.num_entries.load(Relaxed)
+    }
+
+    /// Returns the capacity of the HashCache.
+    ///
+    /// It is the capacity of the underlying array, and the number of key-value pairs in the
+    /// HashCache never exceeds it; see [`HashCache::capacity_bound`] for the number of
+    /// key-value pairs that the HashCache is allowed to keep.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashcache: HashCache<u64, u32, RandomState> =
+    ///     HashCache::new(1000000, RandomState::new());
+    /// assert_eq!(hashcache.capacity(), 1048576);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.hash_map.capacity()
+    }
+
+    /// Returns the maximum number of key-value pairs that the HashCache is allowed to keep.
+    ///
+    /// It is equal to the capacity unless changed by [`HashCache::set_capacity`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    /// assert_eq!(hashcache.capacity_bound(), 64);
+    ///
+    /// assert!(hashcache.set_capacity(16).is_ok());
+    /// assert_eq!(hashcache.capacity_bound(), 16);
+    /// assert_eq!(hashcache.capacity(), 64);
+    /// ```
+    pub fn capacity_bound(&self) -> usize {
+        self.capacity_bound.load(Relaxed)
+    }
+
+    /// Changes the maximum number of key-value pairs that the HashCache is allowed to keep.
+    ///
+    /// Shrinking does not evict key-value pairs by itself; instead, each subsequent
+    /// [`HashCache::put`] evicts up to two key-value pairs, the least recently used one in each
+    /// visited Cell, as long as the estimated number of key-value pairs exceeds the new bound.
+    /// Growing beyond the capacity resizes the array so that it can accommodate the new bound.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the array cannot be resized; the bound is left unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashCache;
+    ///
+    /// let hashcache: HashCache<u64, u32> = Default::default();
+    ///
+    /// for key in 0..32 {
+    ///     assert!(hashcache.put(key, 0).is_ok());
+    /// }
+    /// assert!(hashcache.set_capacity(8).is_ok());
+    /// for key in 32..64 {
+    ///     assert!(hashcache.put(key, 0).is_ok());
+    /// }
+    /// assert!(hashcache.len() <= 8);
+    ///
+    /// assert!(hashcache.set_capacity(4096).is_ok());
+    /// assert!(hashcache.capacity() >= 4096);
+    /// ```
+    pub fn set_capacity(&self, capacity: usize) -> Result<(), TryReserveError> {
+        if capacity > self.hash_map.capacity() {
+            self.hash_map
+                .try_reserve(capacity.saturating_sub(self.hash_map.len()))?;
+        } else {
+            self.hash_map.release_reserved_capacity();
+        }
+        self.capacity_bound.store(capacity, Relaxed);
+        Ok(())
+    }
+
+    /// Puts a key-value pair along with its expiry time.
+    fn put_entry(
+        &self,
+        mut key: K,
+        mut value: V,
+        expiry: Option<Instant>,
+    ) -> Result<Option<(K, V)>, (K, V)> {
+        let now = (self.time_source)();
+        loop {
+            let entry = CacheEntry {
+                value,
+                accessed: AtomicU64::new(self.clock.fetch_add(1, Relaxed)),
+                expiry,
+            };
+            // The counter is incremented in advance so that it never underflows.
+            self.num_entries.fetch_add(1, Relaxed);
+            match self
+                .hash_map
+                .insert_bounded(key, entry, |_, entry| entry.rank(now))
+            {
+                Ok(evicted) => {
+                    #[cfg(feature = "metrics")]
+                    self.statistics.add(CacheCounter::Puts, 1);
+                    let evicted = evicted.map(|(key, entry)| {
+                        self.num_entries.fetch_sub(1, Relaxed);
+                        self.report_eviction(&key, &entry, now);
+                        (key, entry.value)
+                    });
+                    self.shrink(now);
+                    return Ok(evicted);
+                }
+                Err((existing_key, entry)) => {
+                    self.num_entries.fetch_sub(1, Relaxed);
+                    // The existing key-value pair may have expired or been removed since.
+                    self.remove_expired(&existing_key, now);
+                    if self
+                        .hash_map
+                        .read(&existing_key, |_, entry| !entry.expired(now))
+                        .unwrap_or(false)
+                    {
+                        return Err((existing_key, entry.value));
+                    }
+                    key = existing_key;
+                    value = entry.value;
+                }
+            }
+        }
+    }
+
+    /// Constructs a new CacheEntry with the value returned by `init`.
+    fn new_entry<F: FnOnce() -> V>(&self, init: F) -> CacheEntry<V> {
+        let value = init();
+        // The counter is incremented before the entry is inserted so that it never underflows,
+        // and only once `init` has returned.
+        self.num_entries.fetch_add(1, Relaxed);
+        CacheEntry {
+            value,
+            accessed: AtomicU64::new(self.clock.fetch_add(1, Relaxed)),
+            expiry: None,
+        }
+    }
+
+    /// Marks the CacheEntry as recently used, and returns `true` if it has not expired.
+    fn touch(&self, entry: &CacheEntry<V>, now: Instant) -> bool {
+        if entry.expired(now) {
+            return false;
+        }
+        entry.accessed.fetch_max(self.clock.load(Relaxed), Relaxed);
+        true
+    }
+
+    /// Updates the statistics, and shrinks the HashCache if a key-value pair has been put by
+    /// `get_or_put_with`.
+    fn complete_get_or_put(&self, inserted: bool, now: Instant) {
+        #[cfg(feature = "metrics")]
+        if inserted {
+            self.statistics.add(CacheCounter::Misses, 1);
+            self.statistics.add(CacheCounter::Puts, 1);
+        } else {
+            self.statistics.add(CacheCounter::Hits, 1);
+        }
+        if inserted {
+            self.shrink(now);
+        }
+    }
+
+    /// Removes the key-value pair if it has expired at the given time.
+    fn remove_expired<Q>(&self, key: &Q, now: Instant)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some((key, entry)) = self
+            .hash_map
+            .remove_entry_if(key, |entry| entry.expired(now))
+        {
+            self.num_entries.fetch_sub(1, Relaxed);
+            self.report_eviction(&key, &entry, now);
+        }
+    }
+
+    /// Evicts up to two key-value pairs if the number of key-value pairs exceeds the bound.
+    fn shrink(&self, now: Instant) {
+        let capacity_bound = self.capacity_bound.load(Relaxed);
+        if capacity_bound >= self.hash_map.capacity() {
+            // The size of each Cell is bounded, and the counter may be momentarily off.
+            return;
+        }
+        for _ in 0..2 {
+            if self.num_entries.load(Relaxed) <= capacity_bound {
+                return;
+            }
+            match self
+                .hash_map
+                .evict_next(&self.shrink_cursor, |_, entry| entry.rank(now))
+            {
+                Some((key, entry)) => {
+                    self.num_entries.fetch_sub(1, Relaxed);
+                    self.report_eviction(&key, &entry, now);
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Passes the evicted key-value pair to the eviction hook if one is set.
+    fn report_eviction(&self, key: &K, entry: &CacheEntry<V>, _now: Instant) {
+        #[cfg(feature = "metrics")]
+        if entry.expired(_now) {
+            self.statistics.add(CacheCounter::Expirations, 1);
+        } else {
+            let age = self
+                .clock
+                .load(Relaxed)
+                .saturating_sub(entry.accessed.load(Relaxed));
+            self.statistics.record_eviction(age);
+        }
+        trace_event!(
+            TRACE,
+            container = crate::common::trace::address(self),
+            expired = entry.expired(_now),
+            "eviction"
+        );
+        let guard = crate::ebr::pin();
+        if let Some(eviction_hook) = self.eviction_hook(&guard) {
+            eviction_hook(key, &entry.value);
+        }
+    }
+
+    /// Returns a reference to the eviction hook if one is set.
+    fn eviction_hook<'g>(&self, guard: &'g Guard) -> Option<&'g EvictionHookFn<K, V>> {
+        unsafe { self.eviction_hook.load(Acquire, guard).as_ref() }.map(AsRef::as_ref)
+    }
+}
+
+impl<K, V, H> Drop for HashCache<K, V, H>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+{
+    fn drop(&mut self) {
+        // The HashCache has become unreachable, therefore pinning is unnecessary.
+        let guard = unsafe { crate::ebr::unprotected() };
+        let eviction_hook = self.eviction_hook.swap(Shared::null(), Relaxed, guard);
+        if !eviction_hook.is_null() {
+            drop(unsafe { eviction_hook.into_owned() });
+        }
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod test {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn cache_statistics() {
+        let hashcache: HashCache<usize, usize, RandomState> =
+            HashCache::new(1024, RandomState::new());
+        for key in 0..100 {
+            assert!(hashcache.put(key, key).is_ok());
+        }
+        std::thread::scope(|s| {
+            for thread_id in 0..4 {
+                let hashcache = &hashcache;
+                s.spawn(move || {
+                    for key in (thread_id..400).step_by(4) {
+                        assert_eq!(hashcache.get(&key, |_, value| *value).is_some(), key < 100);
+                    }
+                });
+            }
+        });
+        let statistics = hashcache.cache_statistics();
+        assert_eq!(statistics.puts(), 100);
+        assert_eq!(statistics.hits(), 100);
+        assert_eq!(statistics.misses(), 300);
+        assert_eq!(statistics.hit_ratio(), 0.25);
+        assert_eq!(statistics.miss_ratio(), 0.75);
+        assert_eq!(statistics.evictions(), 0);
+
+        // peek is not counted, and expired key-value pairs are counted as misses.
+        for key in 1000..1010 {
+            assert!(hashcache.put_with_ttl(key, key, Duration::ZERO).is_ok());
+            assert!(hashcache.peek(&key, |_, _| ()).is_none());
+            assert!(hashcache.put_with_ttl(key, key, Duration::ZERO).is_ok());
+            assert!(hashcache.get(&key, |_, _| ()).is_none());
+        }
+        assert_eq!(
+            hashcache.get_or_put_with(0, || 1, |value| *value),
+            (0, false)
+        );
+        assert_eq!(
+            hashcache.get_or_put_with(1010, || 0, |value| *value),
+            (0, true)
+        );
+        let statistics = hashcache.cache_statistics();
+        assert_eq!(statistics.puts(), 121);
+        assert_eq!(statistics.hits(), 101);
+        assert_eq!(statistics.misses(), 311);
+        assert_eq!(statistics.expirations(), 20);
+
+        // Evicted key-value pairs are put into the age histogram.
+        hashcache.reset_cache_statistics();
+        assert_eq!(hashcache.cache_statistics(), CacheStatistics::default());
+        assert_eq!(hashcache.cache_statistics().hit_ratio(), 0.0);
+        let len = hashcache.len();
+        for key in 2000..8000 {
+            assert!(hashcache.put(key, key).is_ok());
+        }
+        let statistics = hashcache.cache_statistics();
+        assert_eq!(statistics.puts(), 6000);
+        assert_eq!(
+            len + statistics.puts(),
+            hashcache.len() + statistics.evictions()
+        );
+        assert_eq!(
+            statistics.age_histogram().iter().sum::<usize>(),
+            statistics.evictions()
+        );
+        assert_eq!(statistics.age_histogram()[0], 0);
+    }
+}
@@ -1,16 +1,36 @@
-use crate::common::cell::{CellIterator, CellLocker};
+use crate::common::allocator::{ArrayAllocator, GlobalArrayAllocator};
+use crate::common::cell::{CellIterator, CellLocker, RetireBatch};
 use crate::common::cell_array::CellArray;
-use crate::common::hash_table::HashTable;
+#[cfg(feature = "serde")]
+use crate::common::deserialize::{DeserializeInto, DeserializeTarget, DuplicateKeyPolicy};
+#[cfg(feature = "std")]
+use crate::common::hash_table::DEFAULT_HASH_FINALIZER;
+use crate::common::hash_table::{
+    DefaultBuildHasher, HashTable, MemoryFootprint, ResizeHistory, ResizingFlag, TryReserveError,
+    DEFAULT_MAX_RESIZING_FACTOR,
+};
+#[cfg(feature = "metrics")]
+use crate::common::metrics::{Counter, Counters, GroupMetrics, Metrics};
+use crate::ebr::{Atomic, Domain, Guard, Owned, Ref, Shared};
 
-use crossbeam_epoch::{Atomic, Guard, Shared};
-use std::borrow::Borrow;
-use std::collections::hash_map::RandomState;
-use std::hash::{BuildHasher, Hash};
-use std::iter::FusedIterator;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering::{Acquire, Relaxed};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::{Acquire, Relaxed};
+use core::time::Duration;
+#[cfg(feature = "rayon")]
+use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator};
+#[cfg(feature = "serde")]
+use serde::de::{DeserializeSeed, Deserializer};
+#[cfg(feature = "serde")]
+use serde::ser::{SerializeMap, Serializer};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-const CELL_SIZE: usize = 32;
+#[cfg(any(feature = "std", test))]
 const DEFAULT_CAPACITY: usize = 64;
 
 /// A scalable concurrent hash index data structure.
@@ -26,29 +46,68 @@ const DEFAULT_CAPACITY: usize = 64;
 /// * The expected size of metadata for a single key-value pair: 2-byte.
 /// * The expected number of atomic operations required for an operation on a single key: 0 or 2.
 /// * The expected number of atomic variables accessed during a single key operation: 1.
-/// * The number of entries managed by a single metadata cell without a linked list: 32.
-/// * The number of entries a single linked list entry manages: 32.
+/// * The number of entries managed by a single metadata cell without a linked list: `CELL`.
+/// * The number of entries a single linked list entry manages: `CELL`.
 /// * The expected maximum linked list length when resize is triggered: log(capacity) / 8.
-pub struct HashIndex<K, V, H = RandomState>
+///
+/// ## Memory allocation
+/// The cells and entry arrays are allocated through `A`, an [`ArrayAllocator`] that defaults
+/// to the global allocator; a HashIndex using another allocator is created by
+/// [`HashIndex::new_in`].
+///
+/// ## Dropping a HashIndex
+/// References to the entries, e.g., a [`Ref`], stay valid while the [`Guard`] is alive even if
+/// the HashIndex is dropped in the meantime. The entries are dropped along with the HashIndex if
+/// no thread is pinned in its reclamation [`Domain`]; otherwise, they are dropped after every
+/// thread pinned at the time is unpinned.
+///
+/// ## The number of entries in a cell
+/// `CELL` is the number of entries that a single cell manages; wider cells need fewer locks
+/// for small values, and narrower cells make a cell a smaller unit of locking and rehashing for
+/// large values. It must be a power of two between 4 and 64.
+///
+/// ```
+/// use scc::HashIndex;
+/// use std::collections::hash_map::RandomState;
+///
+/// let hashindex: HashIndex<u64, [u8; 256], RandomState, 8> =
+///     HashIndex::new(64, RandomState::new());
+///
+/// assert!(hashindex.insert(1, [0; 256]).is_ok());
+/// assert_eq!(hashindex.read(&1, |_, v| v[0]), Some(0));
+/// ```
+pub struct HashIndex<K, V, H = DefaultBuildHasher, const CELL: usize = 32, A = GlobalArrayAllocator>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
-    array: Atomic<CellArray<K, V, CELL_SIZE, true>>,
+    array: Atomic<CellArray<K, V, CELL, true, A>>,
     minimum_capacity: usize,
-    resizing_flag: AtomicBool,
+    reserved_capacity: AtomicUsize,
+    resizing_flag: ResizingFlag,
+    rehash_budget: usize,
+    max_resizing_factor: usize,
+    resize_dampening: Duration,
+    resize_history: ResizeHistory,
+    hash_finalizer: bool,
+    #[cfg(feature = "metrics")]
+    metrics: Counters,
+    domain: Domain,
     build_hasher: H,
 }
 
-impl<K, V> Default for HashIndex<K, V, RandomState>
+#[cfg(feature = "std")]
+impl<K, V, const CELL: usize> Default for HashIndex<K, V, DefaultBuildHasher, CELL>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
 {
     /// Creates a HashIndex instance with the default parameters.
     ///
-    /// The default hash builder is RandomState, and the default capacity is 64.
+    /// The default hash builder is [`DefaultBuildHasher`](crate::DefaultBuildHasher), and the
+    /// default capacity is 64.
     ///
     /// # Panics
     ///
@@ -58,21 +117,18 @@ where
     /// use scc::HashIndex;
     ///
     /// let hashindex: HashIndex<u64, u32, _> = Default::default();
+    ///
+    /// let result = hashindex.capacity();
+    /// assert_eq!(result, 64);
     /// ```
     fn default() -> Self {
-        HashIndex {
-            array: Atomic::new(CellArray::<K, V, CELL_SIZE, true>::new(
-                DEFAULT_CAPACITY,
-                Atomic::null(),
-            )),
-            minimum_capacity: DEFAULT_CAPACITY,
-            resizing_flag: AtomicBool::new(false),
-            build_hasher: RandomState::new(),
-        }
+        let mut hashindex = HashIndex::new(DEFAULT_CAPACITY, Default::default());
+        hashindex.hash_finalizer = DEFAULT_HASH_FINALIZER;
+        hashindex
     }
 }
 
-impl<K, V, H> HashIndex<K, V, H>
+impl<K, V, H, const CELL: usize> HashIndex<K, V, H, CELL>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
@@ -80,11 +136,8 @@ where
 {
     /// Creates an empty HashIndex instance with the given capacity and build hasher.
     ///
-    /// The actual capacity is equal to or greater than the given capacity.
-    ///
-    /// # Panics
-    ///
-    /// Panics if memory allocation fails.
+    /// The actual capacity is equal to or greater than the given capacity. No memory is allocated
+    /// for the array until the first key-value pair is inserted.
     ///
     /// # Examples
     /// ```
@@ -96,24 +149,272 @@ where
     /// let result = hashindex.capacity();
     /// assert_eq!(result, 1024);
     ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(4, RandomState::new());
+    /// let result = hashindex.capacity();
+    /// assert_eq!(result, 32);
+    /// ```
+    pub fn new(capacity: usize, build_hasher: H) -> HashIndex<K, V, H, CELL> {
+        HashIndex::new_in(capacity, build_hasher)
+    }
+
+    /// Creates an empty HashIndex instance that retires its entries in the given [`Domain`].
+    ///
+    /// Removed entries are reclaimed regardless of threads that are pinned in other domains, and
+    /// the [`Guard`] instances passed to the HashIndex have to be obtained from
+    /// [`Domain::pin`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::ebr::Domain;
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let domain = Domain::new();
+    /// let hashindex: HashIndex<u64, u32, RandomState> =
+    ///     HashIndex::new_with_domain(domain.clone(), 1000, RandomState::new());
+    ///
+    /// let guard = domain.pin();
+    /// assert!(hashindex.insert_with_guard(1, 0, &guard).is_ok());
+    /// assert_eq!(hashindex.read_with(&1, |_, v| *v, &guard), Some(0));
+    /// ```
+    pub fn new_with_domain(
+        domain: Domain,
+        capacity: usize,
+        build_hasher: H,
+    ) -> HashIndex<K, V, H, CELL> {
+        let mut hashindex = HashIndex::new_in(capacity, build_hasher);
+        hashindex.domain = domain;
+        hashindex
+    }
+}
+
+impl<K, V, H, const CELL: usize, A> HashIndex<K, V, H, CELL, A>
+where
+    K: Clone + Eq + Hash + Sync,
+    V: Clone + Sync,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// `CELL` is a power of two that a DataArray candidate bitmap can accommodate.
+    const CELL_SIZE_CHECK: () = assert!(
+        CELL.is_power_of_two() && CELL >= 4 && CELL <= 64,
+        "the number of entries in a cell must be a power of two between 4 and 64"
+    );
+
+    /// Creates an empty HashIndex instance with the given capacity and build hasher that
+    /// allocates the Cells and entry arrays through `A`.
+    ///
+    /// The actual capacity is equal to or greater than the given capacity. No memory is allocated
+    /// for the array until the first key-value pair is inserted.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{GlobalArrayAllocator, HashIndex};
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState, 32, GlobalArrayAllocator> =
+    ///     HashIndex::new_in(1000, RandomState::new());
     ///
-    /// let hashindex: HashIndex<u64, u32, _> = Default::default();
     /// let result = hashindex.capacity();
-    /// assert_eq!(result, 64);
+    /// assert_eq!(result, 1024);
     /// ```
-    pub fn new(capacity: usize, build_hasher: H) -> HashIndex<K, V, H> {
-        let initial_capacity = capacity.max(DEFAULT_CAPACITY);
+    pub fn new_in(capacity: usize, build_hasher: H) -> HashIndex<K, V, H, CELL, A> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::CELL_SIZE_CHECK;
+        let minimum_capacity = CellArray::<K, V, CELL, true, A>::num_cell_entries_for(capacity);
         HashIndex {
-            array: Atomic::new(CellArray::<K, V, CELL_SIZE, true>::new(
-                initial_capacity,
-                Atomic::null(),
-            )),
-            minimum_capacity: initial_capacity,
-            resizing_flag: AtomicBool::new(false),
+            array: Atomic::null(),
+            minimum_capacity,
+            reserved_capacity: AtomicUsize::new(0),
+            resizing_flag: ResizingFlag::default(),
+            rehash_budget: CELL,
+            max_resizing_factor: DEFAULT_MAX_RESIZING_FACTOR,
+            resize_dampening: Duration::ZERO,
+            resize_history: ResizeHistory::default(),
+            hash_finalizer: true,
+            #[cfg(feature = "metrics")]
+            metrics: Counters::default(),
+            domain: Domain::global(),
             build_hasher,
         }
     }
 
+    /// Sets the number of Cells in the old array that each operation relocates in passing.
+    ///
+    /// While the array is being resized, operations on the HashIndex relocate `cells_per_op`
+    /// Cells, `CELL` by default, from the old array, and removals relocate the Cell that the key
+    /// belongs to. New keys are inserted into the current array without relocating the Cell in the
+    /// old array. With `0`, operations never relocate Cells that they do not access, and the old
+    /// array is only dropped by [`HashIndex::rehash`] or [`HashIndex::clear`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> =
+    ///     HashIndex::new(64, RandomState::new()).with_rehash_budget(0);
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashindex.insert(key, 0).is_ok());
+    ///     while hashindex.rehash(4) {}
+    /// }
+    /// assert!(hashindex.capacity() >= 4096);
+    /// ```
+    pub fn with_rehash_budget(mut self, cells_per_op: usize) -> Self {
+        self.rehash_budget = cells_per_op;
+        self
+    }
+
+    /// Sets the maximum factor by which the array grows at once.
+    ///
+    /// The array grows by up to 64x at once, or by up to `max_resizing_factor`, 1024 by default,
+    /// if the size estimate is confident; see [`HashMap::with_max_resizing_factor`](crate::HashMap::with_max_resizing_factor).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_resizing_factor` is less than 2.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> =
+    ///     HashIndex::new(64, RandomState::new()).with_max_resizing_factor(4096);
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashindex.insert(key, 0).is_ok());
+    /// }
+    /// assert!(hashindex.capacity() >= 4096);
+    /// ```
+    pub fn with_max_resizing_factor(mut self, max_resizing_factor: usize) -> Self {
+        assert!(
+            max_resizing_factor >= 2,
+            "the maximum resizing factor must be at least 2"
+        );
+        self.max_resizing_factor = max_resizing_factor;
+        self
+    }
+
+    /// Sets the minimum interval between resizes in opposite directions.
+    ///
+    /// Once the array grows, it does not shrink within `min_interval`, and vice versa; see
+    /// [`HashMap::with_resize_dampening`](crate::HashMap::with_resize_dampening). Resizes are
+    /// not dampened without the `std` feature, since there is no clock to measure the interval.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    /// use std::time::Duration;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new())
+    ///     .with_resize_dampening(Duration::from_secs(3600));
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashindex.insert(key, 0).is_ok());
+    /// }
+    /// let capacity = hashindex.capacity();
+    /// for key in 0..4096 {
+    ///     assert!(hashindex.remove(&key));
+    /// }
+    /// # #[cfg(feature = "std")]
+    /// assert_eq!(hashindex.capacity(), capacity);
+    /// ```
+    pub fn with_resize_dampening(mut self, min_interval: Duration) -> Self {
+        self.resize_dampening = min_interval;
+        self
+    }
+
+    /// Enables or disables the hash finalizer that mixes the bits of hash values.
+    ///
+    /// See [`HashMap::with_hash_finalizer`](crate::HashMap::with_hash_finalizer).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HashIndex is not empty, because the entries would not be found anymore.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> =
+    ///     HashIndex::new(64, RandomState::new()).with_hash_finalizer(false);
+    ///
+    /// assert!(hashindex.insert(1, 0).is_ok());
+    /// assert_eq!(hashindex.read(&1, |_, v| *v), Some(0));
+    /// ```
+    pub fn with_hash_finalizer(mut self, enabled: bool) -> Self {
+        assert_eq!(self.len(), 0, "the HashIndex must be empty");
+        self.hash_finalizer = enabled;
+        self
+    }
+
+    /// Relocates up to the given number of Cells from the old array, and returns whether work
+    /// remains.
+    ///
+    /// The old array is left behind while the HashIndex is being resized, and it is dropped once
+    /// all the Cells in it are relocated. It returns `true` if the old array remains after the
+    /// call.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    /// assert!(!hashindex.rehash(1));
+    /// ```
+    pub fn rehash(&self, cells: usize) -> bool {
+        let guard = self.domain.pin();
+        let current_array_ref = match unsafe { self.array.load(Acquire, &guard).as_ref() } {
+            Some(current_array_ref) => current_array_ref,
+            None => return false,
+        };
+        if current_array_ref.old_array(&guard).is_null() {
+            return false;
+        }
+        !current_array_ref.partial_rehash(
+            |key| self.hash(key),
+            |key, value| Some((key.clone(), value.clone())),
+            cells,
+            &guard,
+        ) && !current_array_ref.old_array(&guard).is_null()
+    }
+
+    /// Tries to grow the HashIndex so that it can accommodate the given number of additional
+    /// key-value pairs.
+    ///
+    /// The HashIndex grows by at most 64x at a time, and does not shrink below the reserved
+    /// capacity once entries are removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TryReserveError::CapacityOverflow`] if the required capacity exceeds the maximum
+    /// capacity of the target platform, or [`TryReserveError::AllocError`] if memory allocation
+    /// fails. The capacity is left unchanged if the required capacity is too large, and the steps
+    /// taken before a failed allocation are kept.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{HashIndex, TryReserveError};
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    /// assert_eq!(hashindex.capacity(), 64);
+    ///
+    /// assert!(hashindex.try_reserve(1000).is_ok());
+    /// assert!(hashindex.capacity() >= 1000);
+    ///
+    /// let result = hashindex.try_reserve(usize::MAX);
+    /// assert_eq!(result, Err(TryReserveError::CapacityOverflow));
+    /// ```
+    pub fn try_reserve(&self, additional: usize) -> Result<(), TryReserveError> {
+        self.try_grow(additional, |key, value| Some((key.clone(), value.clone())))
+    }
+
     /// Inserts a key-value pair into the HashIndex.
     ///
     /// Returns an error with the given key-value pair attached if the key exists.
@@ -125,8 +426,9 @@ where
     /// # Examples
     /// ```
     /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
     ///
-    /// let hashindex: HashIndex<u64, u32> = Default::default();
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
     ///
     /// let result = hashindex.insert(1, 0);
     /// assert!(result.is_ok());
@@ -140,11 +442,51 @@ where
     /// }
     /// ```
     pub fn insert(&self, key: K, value: V) -> Result<(), (K, V)> {
-        let guard = crossbeam_epoch::pin();
-        let (cell_locker, key, partial_hash) = self.acquire(key, &guard);
-        if let Some((key, value)) = cell_locker.insert(key, value, partial_hash, &guard).1 {
+        self.insert_with_guard(key, value, &self.domain.pin())
+    }
+
+    /// Inserts a key-value pair into the HashIndex under the supplied [`Guard`].
+    ///
+    /// It is identical to [`HashIndex::insert`] except that the thread is not pinned again,
+    /// therefore a single [`Guard`] can be reused across a number of operations.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error with the given key-value pair attached if the key exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] was not obtained from the [`Domain`] of the HashIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    ///
+    /// let guard = scc::pin();
+    /// for i in 0..16 {
+    ///     assert!(hashindex.insert_with_guard(i, 0, &guard).is_ok());
+    /// }
+    /// assert!(hashindex.insert_with_guard(1, 1, &guard).is_err());
+    /// ```
+    pub fn insert_with_guard(&self, key: K, value: V, guard: &Guard) -> Result<(), (K, V)> {
+        self.domain.check_guard(guard);
+        let duplicate = match self.acquire(&key, guard) {
+            Some((cell_locker, hash)) => {
+                self.insert_into_cell(&cell_locker, key, value, hash, guard)
+                    .1
+            }
+            None => Some((key, value)),
+        };
+        if let Some((key, value)) = duplicate {
+            #[cfg(feature = "metrics")]
+            self.metrics.add(Counter::FailedInserts, 1);
             return Err((key, value));
         }
+        #[cfg(feature = "metrics")]
+        self.metrics.add(Counter::Inserts, 1);
         Ok(())
     }
 
@@ -155,8 +497,9 @@ where
     /// # Examples
     /// ```
     /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
     ///
-    /// let hashindex: HashIndex<u64, u32> = Default::default();
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
     ///
     /// let result = hashindex.insert(1, 0);
     /// assert!(result.is_ok());
@@ -169,27 +512,60 @@ where
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
-        let (hash, partial_hash) = self.hash(key);
-        let guard = crossbeam_epoch::pin();
-        let (cell_locker, cell_index) = self.lock(hash, &guard);
-        if cell_locker.mark_removed(key, partial_hash, &guard) {
-            if cell_locker.cell_ref().num_entries() == 0 && cell_index < CELL_SIZE {
+        self.remove_with_guard(key, &self.domain.pin())
+    }
+
+    /// Removes a key-value pair under the supplied [`Guard`].
+    ///
+    /// It is identical to [`HashIndex::remove`] except that the thread is not pinned again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] was not obtained from the [`Domain`] of the HashIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    ///
+    /// let guard = scc::pin();
+    /// assert!(hashindex.insert_with_guard(1, 0, &guard).is_ok());
+    /// assert!(hashindex.remove_with_guard(&1, &guard));
+    /// assert!(!hashindex.remove_with_guard(&1, &guard));
+    /// ```
+    pub fn remove_with_guard<Q>(&self, key: &Q, guard: &Guard) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.domain.check_guard(guard);
+        let (hash, _) = self.hash(key);
+        if self.array.load(Acquire, guard).is_null() {
+            return false;
+        }
+        let (cell_locker, cell_index) = self.lock(hash, guard);
+        if cell_locker.mark_removed(key, hash, guard) {
+            #[cfg(feature = "metrics")]
+            self.metrics.add(Counter::Removes, 1);
+            if cell_locker.cell_ref().num_entries() == 0 && cell_index < CELL {
                 drop(cell_locker);
-                let current_array = self.array.load(Acquire, &guard);
+                let current_array = self.array.load(Acquire, guard);
                 let current_array_ref = Self::cell_array_ref(current_array);
-                if current_array_ref.old_array(&guard).is_null()
-                    && current_array_ref.num_cell_entries() > self.minimum_capacity
+                if current_array_ref.old_array(guard).is_null()
+                    && current_array_ref.num_cell_entries() > self.minimum_capacity()
                 {
                     // Triggers resize if the estimated load factor is smaller than 1/16.
                     let sample_size = current_array_ref.sample_size();
                     let mut num_entries = 0;
                     for i in 0..sample_size {
                         num_entries += current_array_ref.cell(i).num_entries();
-                        if num_entries >= sample_size * CELL_SIZE / 16 {
+                        if num_entries >= sample_size * CELL / 16 {
                             return true;
                         }
                     }
-                    self.resize(&guard);
+                    self.resize(guard);
                 }
             }
             return true;
@@ -206,8 +582,9 @@ where
     /// # Examples
     /// ```
     /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
     ///
-    /// let hashindex: HashIndex<u64, u32> = Default::default();
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
     ///
     /// let result = hashindex.insert(1, 0);
     /// assert!(result.is_ok());
@@ -224,41 +601,83 @@ where
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
-        let (hash, partial_hash) = self.hash(key);
-        let guard = crossbeam_epoch::pin();
+        self.read_with(key, f, &self.domain.pin())
+    }
 
-        // An acquire fence is required to correctly load the contents of the array.
-        let mut current_array_shared = self.array.load(Acquire, &guard);
-        loop {
-            let current_array_ref = Self::cell_array_ref(current_array_shared);
-            let old_array_shared = current_array_ref.old_array(&guard);
-            if !old_array_shared.is_null()
-                && !current_array_ref.partial_rehash(
-                    |key| self.hash(key),
-                    |key, value| Some((key.clone(), value.clone())),
-                    &guard,
-                )
-            {
-                let old_array_ref = Self::cell_array_ref(old_array_shared);
-                let cell_index = old_array_ref.calculate_cell_index(hash);
-                let cell_ref = old_array_ref.cell(cell_index);
-                if let Some(entry) = cell_ref.search(key, partial_hash, &guard) {
-                    return Some(f(entry.0.borrow(), &entry.1));
-                }
-            }
-            let cell_index = current_array_ref.calculate_cell_index(hash);
-            let cell_ref = current_array_ref.cell(cell_index);
-            if let Some(entry) = cell_ref.search(key, partial_hash, &guard) {
-                return Some(f(entry.0.borrow(), &entry.1));
-            }
-            let new_current_array_shared = self.array.load(Acquire, &guard);
-            if new_current_array_shared == current_array_shared {
-                break;
-            }
-            // The pointer value has changed.
-            current_array_shared = new_current_array_shared;
-        }
-        None
+    /// Reads a key-value pair under the supplied [`Guard`].
+    ///
+    /// It is identical to [`HashIndex::read`] except that the thread is not pinned again, which
+    /// saves the cost of pinning in a loop of lookups.
+    ///
+    /// # Errors
+    ///
+    /// Returns None if the key does not exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] was not obtained from the [`Domain`] of the HashIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    ///
+    /// assert!(hashindex.insert(1, 10).is_ok());
+    /// assert!(hashindex.insert(2, 20).is_ok());
+    ///
+    /// let guard = scc::pin();
+    /// let sum: u32 = (1..=2)
+    ///     .filter_map(|key| hashindex.read_with(&key, |_, &value| value, &guard))
+    ///     .sum();
+    /// assert_eq!(sum, 30);
+    /// ```
+    pub fn read_with<Q, R, F: FnOnce(&Q, &V) -> R>(&self, key: &Q, f: F, guard: &Guard) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.domain.check_guard(guard);
+        self.search(key, guard)
+            .map(|(key, value)| f(key.borrow(), value))
+    }
+
+    /// Returns a [`Ref`] to the value associated with the key.
+    ///
+    /// The value is not copied; the [`Ref`] stays valid while the [`Guard`] is alive, even if the
+    /// key-value pair is removed in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// Returns None if the key does not exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] was not obtained from the [`Domain`] of the HashIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, String, RandomState> = HashIndex::new(64, RandomState::new());
+    ///
+    /// assert!(hashindex.insert(1, String::from("one")).is_ok());
+    ///
+    /// let guard = scc::pin();
+    /// assert_eq!(hashindex.peek(&1, &guard).unwrap().as_str(), "one");
+    /// assert!(hashindex.peek(&2, &guard).is_none());
+    /// ```
+    pub fn peek<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Option<Ref<'g, V>>
+    where
+        K: Borrow<Q>,
+        K: 'g,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.domain.check_guard(guard);
+        self.search(key, guard)
+            .map(|(_, value)| Ref::new(value, guard))
     }
 
     /// Checks if the key exists.
@@ -266,8 +685,9 @@ where
     /// # Examples
     /// ```
     /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
     ///
-    /// let hashindex: HashIndex<u64, u32> = Default::default();
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
     ///
     /// let result = hashindex.contains(&1);
     /// assert!(!result);
@@ -291,8 +711,9 @@ where
     /// # Examples
     /// ```
     /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
     ///
-    /// let hashindex: HashIndex<u64, u32> = Default::default();
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
     ///
     /// let result = hashindex.insert(1, 0);
     /// assert!(result.is_ok());
@@ -308,23 +729,26 @@ where
     /// ```
     pub fn clear(&self) -> usize {
         let mut num_removed = 0;
-        let guard = crossbeam_epoch::pin();
+        let guard = self.domain.pin();
+        let _batch = RetireBatch::open(&guard);
         let mut current_array_shared = self.array.load(Acquire, &guard);
-        loop {
+        while !current_array_shared.is_null() {
             let current_array_ref = Self::cell_array_ref(current_array_shared);
             let old_array_shared = current_array_ref.old_array(&guard);
             if !old_array_shared.is_null() {
                 while !current_array_ref.partial_rehash(
                     |key| self.hash(key),
                     |key, value| Some((key.clone(), value.clone())),
+                    CELL,
                     &guard,
                 ) {
                     continue;
                 }
             }
             for index in 0..current_array_ref.array_size() {
-                if let Some(mut cell_locker) =
-                    CellLocker::lock(current_array_ref.cell(index), &guard)
+                if let Some(mut cell_locker) = current_array_ref
+                    .initialized_cell(index)
+                    .and_then(|cell_ref| CellLocker::lock(cell_ref, &guard))
                 {
                     num_removed += cell_locker.cell_ref().num_entries();
                     cell_locker.purge(&guard);
@@ -337,6 +761,8 @@ where
             }
             current_array_shared = new_current_array_shared;
         }
+        #[cfg(feature = "metrics")]
+        self.metrics.add(Counter::Removes, num_removed);
         num_removed
     }
 
@@ -349,8 +775,9 @@ where
     /// # Examples
     /// ```
     /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
     ///
-    /// let hashindex: HashIndex<u64, u32> = Default::default();
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
     ///
     /// let result = hashindex.insert(1, 0);
     /// assert!(result.is_ok());
@@ -362,32 +789,125 @@ where
         self.num_entries()
     }
 
-    /// Returns the capacity of the HashIndex.
+    /// Returns a snapshot of the operation counters of the HashIndex.
     ///
     /// # Examples
     /// ```
     /// use scc::HashIndex;
     /// use std::collections::hash_map::RandomState;
     ///
-    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(1000000, RandomState::new());
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
     ///
-    /// let result = hashindex.capacity();
-    /// assert_eq!(result, 1048576);
+    /// assert!(hashindex.insert(1, 0).is_ok());
+    /// assert!(hashindex.insert(1, 1).is_err());
+    /// assert!(hashindex.remove(&1));
+    ///
+    /// let metrics = hashindex.metrics();
+    /// assert_eq!(metrics.inserts(), 1);
+    /// assert_eq!(metrics.failed_inserts(), 1);
+    /// assert_eq!(metrics.removes(), 1);
     /// ```
-    pub fn capacity(&self) -> usize {
-        self.num_slots()
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.snapshot()
     }
 
-    /// Returns a Visitor.
+    /// Resets the operation counters of the HashIndex.
     ///
-    /// It is guaranteed to go through all the key-value pairs pertaining in the HashIndex at the moment,
-    /// however the same key-value pair can be visited more than once if the HashIndex is being resized.
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    ///
+    /// assert!(hashindex.insert(1, 0).is_ok());
+    /// hashindex.reset_metrics();
+    /// assert_eq!(hashindex.metrics().inserts(), 0);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&self) {
+        self.metrics.reset();
+        self.reset_group_metrics();
+    }
+
+    /// Returns a snapshot of the counters of each group of Cells in the current array.
+    ///
+    /// The Cells are divided into groups of 1024 Cells, and each group counts the lock
+    /// contentions, the long probes, and the old array hits of the operations that lock a Cell
+    /// in the group for modification, e.g., insertions. It returns an empty `Vec` if the array
+    /// has yet to be allocated.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{GroupMetrics, HashIndex};
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> =
+    ///     HashIndex::new(1 << 16, RandomState::new());
+    /// assert!(hashindex.metrics_by_group().is_empty());
+    ///
+    /// assert!(hashindex.insert(1, 0).is_ok());
+    ///
+    /// let groups = hashindex.metrics_by_group();
+    /// assert_eq!(groups.len(), 2);
+    /// assert_eq!(groups.iter().map(GroupMetrics::long_probes).sum::<usize>(), 0);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn metrics_by_group(&self) -> Vec<GroupMetrics> {
+        self.group_metrics()
+    }
+
+    /// Returns the capacity of the HashIndex.
     ///
     /// # Examples
     /// ```
     /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
     ///
-    /// let hashindex: HashIndex<u64, u32> = Default::default();
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(1000000, RandomState::new());
+    ///
+    /// let result = hashindex.capacity();
+    /// assert_eq!(result, 1048576);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.num_slots()
+    }
+
+    /// Returns a breakdown of the heap memory used by the HashIndex.
+    ///
+    /// It visits every Cell in the current and old arrays, therefore it is as costly as
+    /// [`HashIndex::len`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    /// let empty = hashindex.memory_footprint();
+    /// assert_eq!(empty.overflow_bytes(), 0);
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashindex.insert(key, 0).is_ok());
+    /// }
+    /// assert!(hashindex.memory_footprint().current_array_bytes() > empty.current_array_bytes());
+    /// ```
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        self.measure_memory()
+    }
+
+    /// Returns a Visitor.
+    ///
+    /// It is guaranteed to go through all the key-value pairs pertaining in the HashIndex at the moment,
+    /// however the same key-value pair can be visited more than once if the HashIndex is being resized.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
     ///
     /// let result = hashindex.insert(1, 0);
     /// assert!(result.is_ok());
@@ -400,29 +920,267 @@ where
     ///     assert_eq!(iter, (&1, &0));
     /// }
     /// ```
-    pub fn iter(&self) -> Visitor<K, V, H> {
+    pub fn iter(&self) -> Visitor<'_, K, V, H, CELL, A> {
+        Visitor {
+            hash_index: self,
+            current_array: Shared::null(),
+            current_index: 0,
+            current_cell_iterator: None,
+            guard: None,
+            external_guard: None,
+            started: false,
+        }
+    }
+
+    /// Returns a [`Visitor`] that scans the HashIndex under the supplied [`Guard`].
+    ///
+    /// It is identical to [`HashIndex::iter`] except that the [`Visitor`] does not pin the
+    /// thread on its own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] was not obtained from the [`Domain`] of the HashIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    ///
+    /// let guard = scc::pin();
+    /// assert!(hashindex.insert_with_guard(1, 0, &guard).is_ok());
+    ///
+    /// let mut iter = hashindex.iter_with(&guard);
+    /// assert_eq!(iter.next(), Some((&1, &0)));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn iter_with<'h>(&'h self, guard: &'h Guard) -> Visitor<'h, K, V, H, CELL, A> {
+        self.domain.check_guard(guard);
         Visitor {
             hash_index: self,
             current_array: Shared::null(),
             current_index: 0,
             current_cell_iterator: None,
             guard: None,
+            external_guard: Some(guard),
+            started: false,
         }
     }
 
-    /// Acquires a Cell for inserting a new key-value pair.
+    /// Returns the reclamation [`Domain`] of the HashIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    ///
+    /// let guard = hashindex.domain().pin();
+    /// assert!(hashindex.insert_with_guard(1, 0, &guard).is_ok());
+    /// ```
+    pub fn domain(&self) -> &Domain {
+        &self.domain
+    }
+
+    /// Returns the generation of the current array.
+    ///
+    /// The generation starts from zero, and is increased each time the array is replaced by
+    /// resizing. Cell indexes are valid only while the generation stays the same.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    /// assert_eq!(hashindex.array_generation(), 0);
+    ///
+    /// hashindex.try_reserve(1 << 10).unwrap();
+    /// assert_eq!(hashindex.array_generation(), 1);
+    /// ```
+    pub fn array_generation(&self) -> usize {
+        let guard = self.domain.pin();
+        unsafe { self.array.load(Acquire, &guard).as_ref() }.map_or(0, CellArray::generation)
+    }
+
+    /// Returns `true` if the old array is yet to be dropped.
+    ///
+    /// The old array is dropped once all the Cells in it are relocated to the current array, and
+    /// its memory is reclaimed as soon as no thread may be reading it.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> =
+    ///     HashIndex::new(64, RandomState::new()).with_rehash_budget(0);
+    /// assert!(!hashindex.has_old_array());
+    ///
+    /// hashindex.try_reserve(1 << 16).unwrap();
+    /// assert!(hashindex.has_old_array());
+    ///
+    /// while hashindex.rehash(1) {}
+    /// assert!(!hashindex.has_old_array());
+    /// ```
+    pub fn has_old_array(&self) -> bool {
+        let guard = self.domain.pin();
+        let current_array = self.array.load(Acquire, &guard);
+        !current_array.is_null()
+            && !Self::cell_array_ref(current_array)
+                .old_array(&guard)
+                .is_null()
+    }
+
+    /// Returns the number of Cells in the current array.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    /// assert_eq!(hashindex.cell_count() * 32, hashindex.capacity());
+    /// ```
+    pub fn cell_count(&self) -> usize {
+        let guard = self.domain.pin();
+        unsafe { self.array.load(Acquire, &guard).as_ref() }
+            .map_or(self.minimum_capacity / CELL, CellArray::array_size)
+    }
+
+    /// Returns a snapshot of the key-value pairs in the Cell at the given index.
+    ///
+    /// This is a low-level API for building custom scans that process one Cell at a time, e.g.,
+    /// an incremental maintenance task. Key-value pairs belonging to the Cell that have yet to be
+    /// relocated from the old array are relocated before the snapshot is taken. No Cells are
+    /// locked for reading, and the key-value pairs are kept alive while the [`CellEntries`] is
+    /// alive.
+    ///
+    /// It returns `None` if the array generation is not `generation`, or `index` is not less than
+    /// [`HashIndex::cell_count`]. Once the generation has changed, the progress of a scan has to
+    /// be reset, and the generation has to be checked again after a scan is complete since the
+    /// array can be replaced while the scan is in progress.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindex: HashIndex<u64, u32, RandomState> = HashIndex::new(64, RandomState::new());
+    ///
+    /// for key in 0..16 {
+    ///     assert!(hashindex.insert(key, 0).is_ok());
+    /// }
+    ///
+    /// let generation = hashindex.array_generation();
+    /// let mut num_entries = 0;
+    /// for index in 0..hashindex.cell_count() {
+    ///     num_entries += hashindex.cell(generation, index).unwrap().count();
+    /// }
+    /// assert_eq!(num_entries, 16);
+    ///
+    /// hashindex.try_reserve(1 << 16).unwrap();
+    /// assert!(hashindex.cell(generation, 0).is_none());
+    /// ```
+    pub fn cell(&self, generation: usize, index: usize) -> Option<CellEntries<'_, K, V>> {
+        let guard = self.domain.pin();
+        let current_array_ref = match unsafe { self.array.load(Acquire, &guard).as_ref() } {
+            Some(current_array_ref) => current_array_ref,
+            None if generation == 0 && index < self.minimum_capacity / CELL => {
+                return Some(CellEntries {
+                    _guard: guard,
+                    entries: Vec::new().into_iter(),
+                    _phantom: PhantomData,
+                });
+            }
+            None => return None,
+        };
+        if current_array_ref.generation() != generation || index >= current_array_ref.array_size() {
+            return None;
+        }
+        current_array_ref.relocate_into(
+            index,
+            &|key: &K| self.hash(key),
+            &|key, value| Some((key.clone(), value.clone())),
+            &guard,
+        );
+        let entries: Vec<(*const K, *const V)> = current_array_ref
+            .cell(index)
+            .iter(&guard)
+            .map(|((key, value), _)| (key as *const K, value as *const V))
+            .collect();
+        Some(CellEntries {
+            _guard: guard,
+            entries: entries.into_iter(),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Searches for a key-value pair without locking any Cells.
+    ///
+    /// The key-value pair stays valid until the guard is dropped.
+    pub(crate) fn search<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Option<(&'g K, &'g V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        #[cfg(feature = "metrics")]
+        let _recorder = self.metrics.recorder();
+        let (hash, _) = self.hash(key);
+
+        // An acquire fence is required to correctly load the contents of the array.
+        let mut current_array_shared = self.array.load(Acquire, guard);
+        while !current_array_shared.is_null() {
+            let current_array_ref = Self::cell_array_ref(current_array_shared);
+            let old_array_shared = current_array_ref.old_array(guard);
+            if !old_array_shared.is_null()
+                && !current_array_ref.partial_rehash(
+                    |key| self.hash(key),
+                    |key, value| Some((key.clone(), value.clone())),
+                    self.rehash_budget,
+                    guard,
+                )
+            {
+                let old_array_ref = Self::cell_array_ref(old_array_shared);
+                let cell_index = old_array_ref.calculate_cell_index(hash);
+                let cell_ref = old_array_ref.cell(cell_index);
+                if let Some(entry) = cell_ref.search(key, hash, guard) {
+                    return Some(entry);
+                }
+            }
+            let cell_index = current_array_ref.calculate_cell_index(hash);
+            let cell_ref = current_array_ref.cell(cell_index);
+            if let Some(entry) = cell_ref.search(key, hash, guard) {
+                return Some(entry);
+            }
+            let new_current_array_shared = self.array.load(Acquire, guard);
+            if new_current_array_shared == current_array_shared {
+                break;
+            }
+            // The pointer value has changed.
+            current_array_shared = new_current_array_shared;
+        }
+        None
+    }
+
+    /// Acquires a Cell for inserting a new key-value pair, and returns it along with the hash
+    /// value of the key.
+    ///
+    /// It returns `None` if the key is found in the old array.
     fn acquire<'g>(
         &self,
-        key: K,
+        key: &K,
         guard: &'g Guard,
-    ) -> (CellLocker<'g, K, V, CELL_SIZE, true>, K, u8) {
-        let (hash, partial_hash) = self.hash(&key);
+    ) -> Option<(CellLocker<'g, K, V, CELL, true, A>, u64)> {
+        let (hash, _) = self.hash(key);
         let mut resize_triggered = false;
         loop {
-            let (cell_locker, cell_index) = self.lock(hash, guard);
+            let (cell_locker, cell_index) = self.lock_for_insert(key, hash, guard)?;
             if !resize_triggered
-                && cell_index < CELL_SIZE
-                && cell_locker.cell_ref().num_entries() > (CELL_SIZE / 16) * 15
+                && cell_index < CELL
+                && cell_locker.cell_ref().num_entries() > CELL * 15 / 16
             {
                 drop(cell_locker);
                 resize_triggered = true;
@@ -431,10 +1189,10 @@ where
                 if current_array_ref.old_array(&guard).is_null() {
                     // Triggers resize if the estimated load factor is greater than 7/8.
                     let sample_size = current_array_ref.sample_size();
-                    let threshold = sample_size * (CELL_SIZE / 8) * 7;
+                    let threshold = sample_size * CELL * 7 / 8;
                     let mut num_entries = 0;
                     for i in 0..sample_size {
-                        num_entries += current_array_ref.cell(i).num_entries();
+                        num_entries += current_array_ref.cell(i).num_load_entries();
                         if num_entries > threshold {
                             self.resize(guard);
                             break;
@@ -443,26 +1201,89 @@ where
                 }
                 continue;
             }
-            return (cell_locker, key, partial_hash);
+            return Some((cell_locker, hash));
+        }
+    }
+
+    /// Locks the Cell in the current array that a new key-value pair is inserted into.
+    ///
+    /// Unlike [`HashIndex::lock`], the Cell in the old array is not killed, and it is only
+    /// searched for the key. New keys are never inserted into the old array, and the entries in
+    /// a Cell being killed are inserted into the current array before the Cell is emptied,
+    /// therefore the key cannot be inserted twice. It returns `None` if the key is found in the
+    /// old array.
+    fn lock_for_insert<'g>(
+        &self,
+        key: &K,
+        hash: u64,
+        guard: &'g Guard,
+    ) -> Option<(CellLocker<'g, K, V, CELL, true, A>, usize)> {
+        #[cfg(feature = "metrics")]
+        let _recorder = self.metrics.recorder();
+        loop {
+            // An acquire fence is required to correctly load the contents of the array.
+            let current_array_shared = self.current_array_or_alloc(guard);
+            let current_array_ref = Self::cell_array_ref(current_array_shared);
+            let old_array_shared = current_array_ref.old_array(guard);
+            if !old_array_shared.is_null() {
+                if current_array_ref.partial_rehash(
+                    |key| self.hash(key),
+                    |key, value| Some((key.clone(), value.clone())),
+                    self.rehash_budget,
+                    guard,
+                ) {
+                    continue;
+                }
+                let old_array_ref = Self::cell_array_ref(old_array_shared);
+                let cell_index = old_array_ref.calculate_cell_index(hash);
+                let old_cell_ref = old_array_ref.cell(cell_index);
+                if old_cell_ref.search(key, hash, guard).is_some() {
+                    #[cfg(feature = "metrics")]
+                    current_array_ref.record_group_events(
+                        current_array_ref.calculate_cell_index(hash),
+                        old_cell_ref,
+                        true,
+                        guard,
+                    );
+                    return None;
+                }
+            }
+            let cell_index = current_array_ref.calculate_cell_index(hash);
+            if let Some(cell_locker) = CellLocker::lock(current_array_ref.cell(cell_index), guard) {
+                #[cfg(feature = "metrics")]
+                current_array_ref.record_group_events(
+                    cell_index,
+                    cell_locker.cell_ref(),
+                    false,
+                    guard,
+                );
+                return Some((cell_locker, cell_index));
+            }
+            // Reaching here indicates that self.array is updated.
         }
     }
 
     /// Locks a cell.
+    ///
+    /// The Cell in the old array that the hash value belongs to is killed beforehand.
     fn lock<'g>(
         &self,
         hash: u64,
         guard: &'g Guard,
-    ) -> (CellLocker<'g, K, V, CELL_SIZE, true>, usize) {
+    ) -> (CellLocker<'g, K, V, CELL, true, A>, usize) {
+        #[cfg(feature = "metrics")]
+        let _recorder = self.metrics.recorder();
         // The description about the loop can be found in HashMap::acquire.
         loop {
             // An acquire fence is required to correctly load the contents of the array.
-            let current_array_shared = self.array.load(Acquire, &guard);
+            let current_array_shared = self.current_array_or_alloc(guard);
             let current_array_ref = Self::cell_array_ref(current_array_shared);
             let old_array_shared = current_array_ref.old_array(&guard);
             if !old_array_shared.is_null() {
                 if current_array_ref.partial_rehash(
                     |key| self.hash(key),
                     |key, value| Some((key.clone(), value.clone())),
+                    self.rehash_budget,
                     &guard,
                 ) {
                     continue;
@@ -485,6 +1306,13 @@ where
             }
             let cell_index = current_array_ref.calculate_cell_index(hash);
             if let Some(cell_locker) = CellLocker::lock(current_array_ref.cell(cell_index), guard) {
+                #[cfg(feature = "metrics")]
+                current_array_ref.record_group_events(
+                    cell_index,
+                    cell_locker.cell_ref(),
+                    false,
+                    guard,
+                );
                 return (cell_locker, cell_index);
             }
             // Reaching here indicates that self.array is updated.
@@ -492,92 +1320,380 @@ where
     }
 }
 
-impl<K, V, H> Drop for HashIndex<K, V, H>
+#[cfg(feature = "serde")]
+impl<K, V, H, const CELL: usize, A> HashIndex<K, V, H, CELL, A>
+where
+    K: Clone + Eq + Hash + Sync,
+    V: Clone + Sync,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Deserializes a map into the HashIndex.
+    ///
+    /// The key-value pairs are inserted into the HashIndex as they are deserialized, therefore the
+    /// HashIndex keeps its build hasher and capacity. The HashIndex grows in advance if the
+    /// deserializer knows the number of entries. Existing values are replaced by removing and
+    /// inserting the key, and readers may not find the key in the meantime; see
+    /// [`HashIndex::deserialize_seed`] for the other [`DuplicateKeyPolicy`] options.
+    ///
+    /// Returns the number of keys that already existed in the HashIndex.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the deserializer fails, in which case the key-value pairs deserialized
+    /// so far remain in the HashIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndex;
+    ///
+    /// let hashindex: HashIndex<u64, u32> = HashIndex::default();
+    /// assert!(hashindex.insert(1, 0).is_ok());
+    ///
+    /// let mut deserializer = serde_json::Deserializer::from_str(r#"{"1": 1, "2": 2}"#);
+    /// assert_eq!(hashindex.deserialize_into(&mut deserializer).unwrap(), 1);
+    /// assert_eq!(hashindex.read(&1, |_, v| *v), Some(1));
+    /// assert_eq!(hashindex.read(&2, |_, v| *v), Some(2));
+    /// ```
+    pub fn deserialize_into<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<usize, D::Error>
+    where
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+    {
+        self.deserialize_seed(DuplicateKeyPolicy::default())
+            .deserialize(deserializer)
+    }
+
+    /// Returns a [`DeserializeSeed`] that deserializes a map into the HashIndex with the given
+    /// [`DuplicateKeyPolicy`].
+    ///
+    /// The seed behaves as [`HashIndex::deserialize_into`] does except for the duplicate keys, and
+    /// can be used to deserialize a map nested in another structure.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{DuplicateKeyPolicy, HashIndex};
+    /// use serde::de::DeserializeSeed;
+    ///
+    /// let hashindex: HashIndex<u64, u32> = HashIndex::default();
+    /// assert!(hashindex.insert(1, 0).is_ok());
+    ///
+    /// let mut deserializer = serde_json::Deserializer::from_str(r#"{"1": 1, "2": 2}"#);
+    /// let seed = hashindex.deserialize_seed(DuplicateKeyPolicy::Keep);
+    /// assert_eq!(seed.deserialize(&mut deserializer).unwrap(), 1);
+    /// assert_eq!(hashindex.read(&1, |_, v| *v), Some(0));
+    /// ```
+    pub fn deserialize_seed(&self, policy: DuplicateKeyPolicy) -> DeserializeInto<'_, Self, K, V> {
+        DeserializeInto::new(self, policy)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K, V, H, const CELL: usize, A> DeserializeTarget<K, V> for HashIndex<K, V, H, CELL, A>
+where
+    K: Clone + Eq + Hash + Sync,
+    V: Clone + Sync,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    fn reserve_for(&self, additional: usize) {
+        let _result = self.try_reserve(additional);
+    }
+
+    fn insert_deserialized(&self, key: K, value: V, policy: DuplicateKeyPolicy) -> bool {
+        let guard = self.domain.pin();
+        match self.insert_with_guard(key, value, &guard) {
+            Ok(()) => true,
+            Err((key, value)) => {
+                if policy == DuplicateKeyPolicy::Replace {
+                    // Values in a HashIndex are immutable.
+                    self.remove_with_guard(&key, &guard);
+                    let _result = self.insert_with_guard(key, value, &guard);
+                }
+                false
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K, V, H, const CELL: usize, A> Serialize for HashIndex<K, V, H, CELL, A>
+where
+    K: Clone + Eq + Hash + Sync + Serialize,
+    V: Clone + Sync + Serialize,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Serializes the HashIndex as a map.
+    ///
+    /// The length of the map is calculated before the entries are serialized, and an entry may be
+    /// visited more than once while the HashIndex is being resized, therefore the HashIndex must
+    /// not be modified during serialization.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, V, H, const CELL: usize, A> Deserialize<'de> for HashIndex<K, V, H, CELL, A>
+where
+    K: Clone + Eq + Hash + Sync + Deserialize<'de>,
+    V: Clone + Sync + Deserialize<'de>,
+    H: BuildHasher + Default,
+    A: ArrayAllocator,
+{
+    /// Deserializes a HashIndex from a map.
+    ///
+    /// Values of duplicate keys replace the previous ones.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hashindex = HashIndex::new_in(0, H::default());
+        hashindex.deserialize_into(deserializer)?;
+        Ok(hashindex)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K, V, H, const CELL: usize> FromParallelIterator<(K, V)> for HashIndex<K, V, H, CELL>
+where
+    K: Clone + Eq + Hash + Send + Sync,
+    V: Clone + Send + Sync,
+    H: BuildHasher + Default + Send + Sync,
+{
+    /// Creates a HashIndex from a parallel iterator.
+    ///
+    /// The key-value pairs are inserted by the rayon worker threads in parallel; see
+    /// [`HashIndex::par_extend`](ParallelExtend::par_extend).
+    ///
+    /// # Examples
+    /// ```
+    /// use rayon::prelude::*;
+    /// use scc::HashIndex;
+    ///
+    /// let hashindex: HashIndex<u64, u64> = (0..1024_u64).into_par_iter().map(|k| (k, k)).collect();
+    /// assert_eq!(hashindex.len(), 1024);
+    /// assert_eq!(hashindex.read(&7, |_, v| *v), Some(7));
+    /// ```
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(par_iter: I) -> Self {
+        let mut hashindex = HashIndex::new(0, H::default());
+        hashindex.par_extend(par_iter);
+        hashindex
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K, V, H, const CELL: usize, A> ParallelExtend<(K, V)> for HashIndex<K, V, H, CELL, A>
+where
+    K: Clone + Eq + Hash + Send + Sync,
+    V: Clone + Send + Sync,
+    H: BuildHasher + Sync,
+    A: ArrayAllocator,
+{
+    /// Inserts all the key-value pairs of a parallel iterator into the HashIndex.
+    ///
+    /// The HashIndex grows in advance if the length of the parallel iterator is known. Each rayon
+    /// worker inserts the key-value pairs of its partition under its own [`Guard`] through the
+    /// concurrent insertion path. Key-value pairs of which the key already exists are dropped.
+    ///
+    /// # Examples
+    /// ```
+    /// use rayon::prelude::*;
+    /// use scc::HashIndex;
+    ///
+    /// let mut hashindex: HashIndex<u64, u64> = HashIndex::default();
+    /// assert!(hashindex.insert(0, 1).is_ok());
+    ///
+    /// hashindex.par_extend((0..1024_u64).into_par_iter().map(|k| (k, k)));
+    /// assert_eq!(hashindex.len(), 1024);
+    /// assert_eq!(hashindex.read(&0, |_, v| *v), Some(1));
+    /// ```
+    fn par_extend<I: IntoParallelIterator<Item = (K, V)>>(&mut self, par_iter: I) {
+        let par_iter = par_iter.into_par_iter();
+        if let Some(len) = par_iter.opt_len() {
+            let _result = self.try_reserve(len);
+        }
+        // A worker may be handed a long split, therefore it is pinned again every so often so as
+        // not to hold back the reclamation of the arrays and entries retired in the meantime.
+        const REPIN_INTERVAL: usize = 256;
+        let hashindex = &*self;
+        par_iter.for_each_init(
+            || (None, 0),
+            |(guard, num_inserted): &mut (Option<Guard>, usize), (key, value)| {
+                if *num_inserted == REPIN_INTERVAL {
+                    *guard = None;
+                    *num_inserted = 0;
+                }
+                let guard = guard.get_or_insert_with(|| hashindex.domain.pin());
+                let _result = hashindex.insert_with_guard(key, value, guard);
+                *num_inserted += 1;
+            },
+        );
+    }
+}
+
+impl<K, V, H, const CELL: usize, A> Drop for HashIndex<K, V, H, CELL, A>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
     fn drop(&mut self) {
         // The HashIndex has become unreachable, therefore pinning is unnecessary.
-        let guard = unsafe { crossbeam_epoch::unprotected() };
-        let current_array = self.array.load(Acquire, guard);
-        let current_array_ref = Self::cell_array_ref(current_array);
-        current_array_ref.drop_old_array(true, guard);
+        let guard = unsafe { crate::ebr::unprotected() };
         let array = self.array.swap(Shared::null(), Relaxed, guard);
-        if !array.is_null() {
-            let array = unsafe { array.into_owned() };
-            for index in 0..array.array_size() {
-                if let Some(mut cell_locker) = CellLocker::lock(array.cell(index), guard) {
-                    cell_locker.purge(&guard);
-                }
+        if array.is_null() {
+            return;
+        }
+        let array = unsafe { array.into_owned() };
+        if self.domain.is_quiescent() {
+            // No thread is pinned in the domain, therefore no reader can reach the entries.
+            Self::drop_array(array);
+        } else {
+            // Readers may still be holding references to the entries obtained before the
+            // HashIndex was dropped, e.g., a Ref, until they are unpinned.
+            let guard = self.domain.pin();
+            unsafe { guard.defer_unchecked(move || Self::drop_array(array)) };
+        }
+    }
+}
+
+impl<K, V, H, const CELL: usize, A> HashIndex<K, V, H, CELL, A>
+where
+    K: Clone + Eq + Hash + Sync,
+    V: Clone + Sync,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Drops the array detached from a dropped HashIndex along with all the entries in it.
+    fn drop_array(array: Owned<CellArray<K, V, CELL, true, A>>) {
+        let guard = unsafe { crate::ebr::unprotected() };
+        array.drop_old_array(true, guard);
+        for index in 0..array.array_size() {
+            if let Some(mut cell_locker) = array
+                .initialized_cell(index)
+                .and_then(|cell_ref| CellLocker::lock(cell_ref, guard))
+            {
+                cell_locker.purge(guard);
             }
         }
     }
 }
 
-impl<K, V, H> HashTable<K, V, H, CELL_SIZE, true> for HashIndex<K, V, H>
+impl<K, V, H, const CELL: usize, A> HashTable<K, V, H, CELL, true, A>
+    for HashIndex<K, V, H, CELL, A>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
     fn hasher(&self) -> &H {
         &self.build_hasher
     }
-    fn cell_array_ptr(&self) -> &Atomic<CellArray<K, V, CELL_SIZE, true>> {
+    fn pin(&self) -> Guard {
+        self.domain.pin()
+    }
+    fn copy_entry(key: &K, value: &V) -> Option<(K, V)> {
+        Some((key.clone(), value.clone()))
+    }
+    fn cell_array_ptr(&self) -> &Atomic<CellArray<K, V, CELL, true, A>> {
         &self.array
     }
     fn minimum_capacity(&self) -> usize {
         self.minimum_capacity
+            .max(self.reserved_capacity.load(Relaxed))
     }
-    fn resizing_flag_ref(&self) -> &AtomicBool {
+    fn reserved_capacity_ref(&self) -> &AtomicUsize {
+        &self.reserved_capacity
+    }
+    fn resizing_flag_ref(&self) -> &ResizingFlag {
         &self.resizing_flag
     }
+    fn rehash_budget(&self) -> usize {
+        self.rehash_budget
+    }
+    fn max_resizing_factor(&self) -> usize {
+        self.max_resizing_factor
+    }
+    fn resize_history(&self) -> &ResizeHistory {
+        &self.resize_history
+    }
+    fn resize_dampening(&self) -> Duration {
+        self.resize_dampening
+    }
+    fn hash_finalizer(&self) -> bool {
+        self.hash_finalizer
+    }
+    #[cfg(feature = "metrics")]
+    fn counters(&self) -> &Counters {
+        &self.metrics
+    }
 }
 
 /// Visitor traverses all the key-value pairs in the HashIndex.
 ///
 /// It is guaranteed to visit all the key-value pairs that outlive the Visitor.
 /// However, the same key-value pair can be visited more than once.
-pub struct Visitor<'h, K, V, H>
+pub struct Visitor<'h, K, V, H, const CELL: usize = 32, A = GlobalArrayAllocator>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
-    hash_index: &'h HashIndex<K, V, H>,
-    current_array: Shared<'h, CellArray<K, V, CELL_SIZE, true>>,
+    hash_index: &'h HashIndex<K, V, H, CELL, A>,
+    current_array: Shared<'h, CellArray<K, V, CELL, true, A>>,
     current_index: usize,
-    current_cell_iterator: Option<CellIterator<'h, K, V, CELL_SIZE, true>>,
+    current_cell_iterator: Option<CellIterator<'h, K, V, CELL, true, A>>,
     guard: Option<Guard>,
+    external_guard: Option<&'h Guard>,
+    started: bool,
 }
 
-impl<'h, K, V, H> Visitor<'h, K, V, H>
+impl<'h, K, V, H, const CELL: usize, A> Visitor<'h, K, V, H, CELL, A>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
     fn guard_ref(&self) -> &'h Guard {
+        if let Some(guard) = self.external_guard {
+            return guard;
+        }
         // The Rust type system cannot prove that self.guard outlives.
-        unsafe { std::mem::transmute::<_, &'h Guard>(self.guard.as_ref().unwrap()) }
+        unsafe { core::mem::transmute::<_, &'h Guard>(self.guard.as_ref().unwrap()) }
     }
 }
 
-impl<'h, K, V, H> Iterator for Visitor<'h, K, V, H>
+impl<'h, K, V, H, const CELL: usize, A> Iterator for Visitor<'h, K, V, H, CELL, A>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
     type Item = (&'h K, &'h V);
     fn next(&mut self) -> Option<Self::Item> {
-        if self.guard.is_none() {
+        if !self.started {
             // Starts scanning.
-            self.guard.replace(crossbeam_epoch::pin());
+            self.started = true;
+            if self.external_guard.is_none() {
+                self.guard.replace(self.hash_index.domain.pin());
+            }
             let current_array = self.hash_index.array.load(Acquire, self.guard_ref());
-            let current_array_ref = HashIndex::<K, V, H>::cell_array_ref(current_array);
+            if current_array.is_null() {
+                // The array has yet to be allocated.
+                return None;
+            }
+            let current_array_ref = HashIndex::<K, V, H, CELL, A>::cell_array_ref(current_array);
             let old_array = current_array_ref.old_array(self.guard_ref());
             self.current_array = if !old_array.is_null() {
                 old_array
@@ -585,19 +1701,19 @@ where
                 current_array
             };
             self.current_cell_iterator.replace(CellIterator::new(
-                HashIndex::<K, V, H>::cell_array_ref(self.current_array).cell(0),
+                HashIndex::<K, V, H, CELL, A>::cell_array_ref(self.current_array).cell(0),
                 self.guard_ref(),
             ));
         }
-        loop {
+        while !self.current_array.is_null() {
             if let Some(iterator) = self.current_cell_iterator.as_mut() {
                 // Proceeds to the next entry in the Cell.
                 if let Some(entry) = iterator.next() {
-                    return Some((&entry.0 .0, &entry.0 .1));
+                    return Some(entry.0);
                 }
             }
             // Proceeds to the next Cell.
-            let array_ref = HashIndex::<K, V, H>::cell_array_ref(self.current_array);
+            let array_ref = HashIndex::<K, V, H, CELL, A>::cell_array_ref(self.current_array);
             self.current_index += 1;
             if self.current_index == array_ref.array_size() {
                 let current_array = self.hash_index.array.load(Acquire, self.guard_ref());
@@ -605,14 +1721,15 @@ where
                     // Finished scanning the entire array.
                     break;
                 }
-                let current_array_ref = HashIndex::<K, V, H>::cell_array_ref(current_array);
+                let current_array_ref =
+                    HashIndex::<K, V, H, CELL, A>::cell_array_ref(current_array);
                 let old_array = current_array_ref.old_array(self.guard_ref());
                 if self.current_array == old_array {
                     // Starts scanning the current array.
                     self.current_array = current_array;
                     self.current_index = 0;
                     self.current_cell_iterator.replace(CellIterator::new(
-                        HashIndex::<K, V, H>::cell_array_ref(self.current_array).cell(0),
+                        HashIndex::<K, V, H, CELL, A>::cell_array_ref(self.current_array).cell(0),
                         self.guard_ref(),
                     ));
                     continue;
@@ -625,11 +1742,12 @@ where
                 };
                 self.current_index = 0;
                 self.current_cell_iterator.replace(CellIterator::new(
-                    HashIndex::<K, V, H>::cell_array_ref(self.current_array).cell(0),
+                    HashIndex::<K, V, H, CELL, A>::cell_array_ref(self.current_array).cell(0),
                     self.guard_ref(),
                 ));
                 continue;
             } else {
+                array_ref.prefetch(self.current_index + 1, self.guard_ref());
                 self.current_cell_iterator.replace(CellIterator::new(
                     array_ref.cell(self.current_index),
                     self.guard_ref(),
@@ -640,10 +1758,186 @@ where
     }
 }
 
-impl<'h, K, V, H> FusedIterator for Visitor<'h, K, V, H>
+impl<'h, K, V, H, const CELL: usize, A> FusedIterator for Visitor<'h, K, V, H, CELL, A>
 where
     K: Clone + Eq + Hash + Sync,
     V: Clone + Sync,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
 }
+
+/// CellEntries is a snapshot of the key-value pairs in a single Cell of a [`HashIndex`].
+///
+/// It is returned by [`HashIndex::cell`].
+pub struct CellEntries<'h, K, V> {
+    _guard: Guard,
+    entries: alloc::vec::IntoIter<(*const K, *const V)>,
+    _phantom: PhantomData<(&'h K, &'h V)>,
+}
+
+impl<'h, K, V> Iterator for CellEntries<'h, K, V> {
+    type Item = (&'h K, &'h V);
+    fn next(&mut self) -> Option<Self::Item> {
+        // The key-value pairs are not dropped until the guard is dropped.
+        self.entries
+            .next()
+            .map(|(key_ptr, value_ptr)| unsafe { (&*key_ptr, &*value_ptr) })
+    }
+}
+
+impl<'h, K, V> FusedIterator for CellEntries<'h, K, V> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::cell_array::{CAPACITY_CEILING, FAIL_ALLOCATION};
+    #[cfg(feature = "std")]
+    use crate::ebr::NUM_RETIRED;
+    use std::collections::hash_map::RandomState;
+    #[cfg(feature = "std")]
+    use std::sync::atomic::AtomicUsize;
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics() {
+        let hashindex: HashIndex<u64, u64, RandomState> =
+            HashIndex::new(DEFAULT_CAPACITY, RandomState::new());
+        for key in 0..4096 {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        for key in 0..16 {
+            assert!(hashindex.insert(key, key).is_err());
+        }
+        for key in 0..1024 {
+            assert!(hashindex.remove(&key));
+        }
+        let metrics = hashindex.metrics();
+        assert_eq!(metrics.inserts(), 4096);
+        assert_eq!(metrics.failed_inserts(), 16);
+        assert_eq!(metrics.removes(), 1024);
+        assert!(metrics.resizes() > 0);
+        assert!(metrics.cells_rehashed() > 0);
+
+        hashindex.reset_metrics();
+        assert_eq!(hashindex.metrics(), Metrics::default());
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    #[test]
+    fn no_metrics() {
+        // Counters span multiple cache lines, therefore a HashIndex without them is laid out exactly
+        // as its remaining fields are.
+        #[allow(dead_code)]
+        struct Layout {
+            array: Atomic<CellArray<u64, u64, 32, true, GlobalArrayAllocator>>,
+            minimum_capacity: usize,
+            reserved_capacity: AtomicUsize,
+            resizing_flag: ResizingFlag,
+            rehash_budget: usize,
+            max_resizing_factor: usize,
+            resize_dampening: Duration,
+            resize_history: ResizeHistory,
+            hash_finalizer: bool,
+            domain: Domain,
+            build_hasher: RandomState,
+        }
+        assert_eq!(
+            std::mem::size_of::<HashIndex<u64, u64, RandomState>>(),
+            std::mem::size_of::<Layout>()
+        );
+    }
+
+    #[test]
+    fn capacity_ceiling() {
+        let max_capacity = 2048;
+        CAPACITY_CEILING.with(|c| c.set(max_capacity));
+        let hashindex: HashIndex<u64, u64, RandomState> =
+            HashIndex::new(usize::MAX, RandomState::new());
+        assert_eq!(hashindex.capacity(), max_capacity);
+
+        // The required capacity is exactly the maximum capacity.
+        assert_eq!(hashindex.try_reserve(max_capacity * 7 / 8), Ok(()));
+        assert_eq!(hashindex.capacity(), max_capacity);
+        assert_eq!(
+            hashindex.try_reserve(max_capacity * 7 / 8 + 1),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(
+            hashindex.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+
+        // The array stops growing at the maximum capacity.
+        let num_entries = max_capacity as u64 * 4;
+        for key in 0..num_entries {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        assert_eq!(hashindex.capacity(), max_capacity);
+        for key in 0..num_entries {
+            assert_eq!(hashindex.read(&key, |_, value| *value), Some(key));
+        }
+        CAPACITY_CEILING.with(|c| c.set(0));
+    }
+
+    #[test]
+    fn try_reserve() {
+        let hashindex: HashIndex<u64, u64, RandomState> =
+            HashIndex::new(DEFAULT_CAPACITY, RandomState::new());
+        for key in 0..64 {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        let capacity = hashindex.capacity();
+
+        FAIL_ALLOCATION.with(|f| f.set(true));
+        let result = hashindex.try_reserve(4096);
+        FAIL_ALLOCATION.with(|f| f.set(false));
+        assert!(matches!(result, Err(TryReserveError::AllocError { .. })));
+        assert_eq!(hashindex.capacity(), capacity);
+
+        assert!(hashindex.try_reserve(4096).is_ok());
+        assert!(hashindex.capacity() >= 4096 + 64);
+        for key in 0..64 {
+            assert_eq!(hashindex.read(&key, |_, value| *value), Some(key));
+        }
+    }
+
+    // Detached DataArrays are only batched with thread-local storage.
+    #[cfg(feature = "std")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn batched_retirement() {
+        static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+        struct D;
+        impl D {
+            fn new() -> D {
+                LIVE.fetch_add(1, Relaxed);
+                D
+            }
+        }
+        impl Clone for D {
+            fn clone(&self) -> Self {
+                D::new()
+            }
+        }
+        impl Drop for D {
+            fn drop(&mut self) {
+                LIVE.fetch_sub(1, Relaxed);
+            }
+        }
+
+        let num_entries = 65536;
+        let hashindex: HashIndex<usize, D, RandomState> =
+            HashIndex::new(DEFAULT_CAPACITY, RandomState::new());
+        for key in 0..num_entries {
+            assert!(hashindex.insert(key, D::new()).is_ok());
+        }
+        let num_retired = NUM_RETIRED.with(|n| n.get());
+        assert_eq!(hashindex.clear(), num_entries);
+        assert!(NUM_RETIRED.with(|n| n.get()) - num_retired <= 4);
+        while LIVE.load(Relaxed) != 0 {
+            crate::ebr::flush();
+        }
+    }
+}
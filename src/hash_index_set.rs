@@ -0,0 +1,321 @@
+use crate::common::hash_table::DefaultBuildHasher;
+use crate::ebr::Guard;
+use crate::hash_index::{HashIndex, Visitor};
+
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// A read-optimized concurrent hash set data structure.
+///
+/// scc::HashIndexSet is a thin wrapper of [`HashIndex`] of which the value type is `()`. Read
+/// operations, such as [`HashIndexSet::contains`] and [`HashIndexSet::peek`], never lock a Cell,
+/// whereas [`HashIndexSet::insert`] and [`HashIndexSet::remove`] lock a Cell as HashIndex does.
+/// It is suitable for membership checks that vastly outnumber updates; otherwise,
+/// [`HashSet`](crate::HashSet) is preferred since removed keys are only reclaimed after the
+/// Cell is rebuilt.
+pub struct HashIndexSet<T, H = DefaultBuildHasher>
+where
+    T: Clone + Eq + Hash + Sync,
+    H: BuildHasher,
+{
+    hash_index: HashIndex<T, (), H>,
+}
+
+impl<T> Default for HashIndexSet<T, DefaultBuildHasher>
+where
+    T: Clone + Eq + Hash + Sync,
+{
+    /// Creates a HashIndexSet instance with the default parameters.
+    ///
+    /// The default hash builder is [`DefaultBuildHasher`](crate::DefaultBuildHasher), and the
+    /// default capacity is 64.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<u64> = Default::default();
+    ///
+    /// let result = hashindexset.capacity();
+    /// assert_eq!(result, 64);
+    /// ```
+    fn default() -> Self {
+        HashIndexSet {
+            hash_index: HashIndex::default(),
+        }
+    }
+}
+
+impl<T, H> HashIndexSet<T, H>
+where
+    T: Clone + Eq + Hash + Sync,
+    H: BuildHasher,
+{
+    /// Creates an empty HashIndexSet instance with the given capacity and build hasher.
+    ///
+    /// The actual capacity is equal to or greater than the given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindexset: HashIndexSet<u64, RandomState> =
+    ///     HashIndexSet::new(1000, RandomState::new());
+    ///
+    /// let result = hashindexset.capacity();
+    /// assert_eq!(result, 1024);
+    /// ```
+    pub fn new(capacity: usize, build_hasher: H) -> HashIndexSet<T, H> {
+        HashIndexSet {
+            hash_index: HashIndex::new(capacity, build_hasher),
+        }
+    }
+
+    /// Inserts a key into the HashIndexSet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error along with the supplied key if the key exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails, or the number of entries in the target cell reaches u32::MAX.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<u64> = Default::default();
+    ///
+    /// assert!(hashindexset.insert(1).is_ok());
+    /// assert_eq!(hashindexset.insert(1).err().unwrap(), 1);
+    /// ```
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        self.hash_index.insert(key, ()).map_err(|(key, _)| key)
+    }
+
+    /// Removes a key from the HashIndexSet.
+    ///
+    /// It returns `true` if the key existed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<u64> = Default::default();
+    ///
+    /// assert!(!hashindexset.remove(&1));
+    /// assert!(hashindexset.insert(1).is_ok());
+    /// assert!(hashindexset.remove(&1));
+    /// ```
+    pub fn remove<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.hash_index.remove(key)
+    }
+
+    /// Checks if the key exists without locking any Cells.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<u64> = Default::default();
+    ///
+    /// assert!(!hashindexset.contains(&1));
+    /// assert!(hashindexset.insert(1).is_ok());
+    /// assert!(hashindexset.contains(&1));
+    /// ```
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.hash_index.contains(key)
+    }
+
+    /// Returns a PeekGuard giving access to the key stored in the HashIndexSet.
+    ///
+    /// The key is never dropped while the PeekGuard is alive even if it is removed from the
+    /// HashIndexSet in the meantime. No Cells are locked, however, holding a PeekGuard for a long
+    /// time delays memory reclamation in all the containers.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<String> = Default::default();
+    ///
+    /// assert!(hashindexset.insert("one".to_string()).is_ok());
+    ///
+    /// let peeked = hashindexset.peek("one").unwrap();
+    /// assert!(hashindexset.remove("one"));
+    /// assert_eq!(peeked.as_str(), "one");
+    /// ```
+    pub fn peek<Q>(&self, key: &Q) -> Option<PeekGuard<'_, T>>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let guard = crate::ebr::pin();
+        let key_ptr = self
+            .hash_index
+            .search(key, &guard)
+            .map(|(key, _)| key as *const T)?;
+        Some(PeekGuard {
+            _guard: guard,
+            key_ptr,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Clears all the keys.
+    ///
+    /// It returns the number of removed keys.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<u64> = Default::default();
+    ///
+    /// assert!(hashindexset.insert(1).is_ok());
+    /// assert_eq!(hashindexset.clear(), 1);
+    /// assert!(!hashindexset.contains(&1));
+    /// ```
+    pub fn clear(&self) -> usize {
+        self.hash_index.clear()
+    }
+
+    /// Returns the number of keys.
+    ///
+    /// It scans the entire array to calculate the number of keys.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<u64> = Default::default();
+    ///
+    /// assert!(hashindexset.insert(1).is_ok());
+    /// assert_eq!(hashindexset.len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.hash_index.len()
+    }
+
+    /// Returns `true` if the HashIndexSet contains no keys.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<u64> = Default::default();
+    ///
+    /// assert!(hashindexset.is_empty());
+    /// assert!(hashindexset.insert(1).is_ok());
+    /// assert!(!hashindexset.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the HashIndexSet.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashindexset: HashIndexSet<u64, RandomState> =
+    ///     HashIndexSet::new(1000000, RandomState::new());
+    /// assert_eq!(hashindexset.capacity(), 1048576);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.hash_index.capacity()
+    }
+
+    /// Returns an iterator over the keys.
+    ///
+    /// The iterator never locks a Cell. It is guaranteed to visit all the keys that outlive the
+    /// iterator, however, the same key can be visited more than once.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashIndexSet;
+    ///
+    /// let hashindexset: HashIndexSet<u64> = Default::default();
+    ///
+    /// for key in 0..4 {
+    ///     assert!(hashindexset.insert(key).is_ok());
+    /// }
+    ///
+    /// let sum: u64 = hashindexset.iter().sum();
+    /// assert_eq!(sum, 6);
+    /// ```
+    pub fn iter(&self) -> HashIndexSetIter<'_, T, H> {
+        HashIndexSetIter {
+            visitor: self.hash_index.iter(),
+        }
+    }
+}
+
+/// PeekGuard keeps a key in a [`HashIndexSet`] from being dropped.
+///
+/// It is returned by [`HashIndexSet::peek`].
+pub struct PeekGuard<'h, T> {
+    _guard: Guard,
+    key_ptr: *const T,
+    _phantom: PhantomData<&'h T>,
+}
+
+impl<'h, T> Deref for PeekGuard<'h, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // The key is not dropped until the guard is dropped.
+        unsafe { &*self.key_ptr }
+    }
+}
+
+/// HashIndexSetIter is an iterator over the keys of a [`HashIndexSet`].
+///
+/// It is returned by [`HashIndexSet::iter`].
+pub struct HashIndexSetIter<'h, T, H>
+where
+    T: Clone + Eq + Hash + Sync,
+    H: BuildHasher,
+{
+    visitor: Visitor<'h, T, (), H>,
+}
+
+impl<'h, T, H> Iterator for HashIndexSetIter<'h, T, H>
+where
+    T: Clone + Eq + Hash + Sync,
+    H: BuildHasher,
+{
+    type Item = &'h T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.visitor.next().map(|(key, _)| key)
+    }
+}
+
+impl<'h, T, H> FusedIterator for HashIndexSetIter<'h, T, H>
+where
+    T: Clone + Eq + Hash + Sync,
+    H: BuildHasher,
+{
+}
@@ -1,17 +1,46 @@
-use crate::common::cell::{Cell, CellIterator, CellLocker, CellReader};
+use crate::common::allocator::{ArrayAllocator, GlobalArrayAllocator};
+use crate::common::cell::{
+    AsyncWait, Cell, CellIterator, CellLocker, CellReader, DataArray, RetireBatch,
+};
 use crate::common::cell_array::CellArray;
-use crate::common::hash_table::HashTable;
+#[cfg(feature = "serde")]
+use crate::common::deserialize::{DeserializeInto, DeserializeTarget, DuplicateKeyPolicy};
+#[cfg(feature = "rayon")]
+use crate::common::hash_table::MAX_REHASH_WAITS;
+use crate::common::hash_table::{
+    DefaultBuildHasher, HashTable, MemoryFootprint, ResizeHistory, ResizingFlag, ShrinkPolicy,
+    TryReserveError, DEFAULT_HASH_FINALIZER, DEFAULT_MAX_LOAD_FACTOR, DEFAULT_MAX_RESIZING_FACTOR,
+};
+#[cfg(feature = "background-worker")]
+use crate::common::maintenance::{self, MaintenanceHandle, Signal};
+#[cfg(feature = "metrics")]
+use crate::common::metrics::{Counter, Counters, GroupMetrics, Metrics};
+use crate::common::unwind::DeferredPanic;
+use crate::ebr::{Atomic, Guard, Owned, Shared};
 
-use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "serde")]
+use serde::de::{DeserializeSeed, Deserializer};
+#[cfg(feature = "serde")]
+use serde::ser::{SerializeMap, Serializer};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::collections::hash_map::RandomState;
-use std::hash::{BuildHasher, Hash};
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::iter::FusedIterator;
-use std::sync::atomic::Ordering::{Acquire, Relaxed};
-use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed};
+#[cfg(feature = "background-worker")]
+use std::sync::Arc;
+use std::time::Duration;
 
 const CELL_SIZE: usize = 32;
 const DEFAULT_CAPACITY: usize = 64;
+const DEBUG_MAX_ENTRIES: usize = 32;
+const DEFAULT_LEN_SAMPLING_CELLS: usize = 64;
 
 /// A scalable concurrent hash map data structure.
 ///
@@ -35,27 +64,223 @@ const DEFAULT_CAPACITY: usize = 64;
 /// * The number of entries managed by a single metadata cell without a linked list: 32.
 /// * The number of entries a single linked list entry manages: 8.
 /// * The expected maximum linked list length when resize is triggered: log(capacity) / 8.
-pub struct HashMap<K, V, H = RandomState>
+///
+/// ## Memory allocation
+/// The cells and entry arrays are allocated through `A`, an [`ArrayAllocator`] that defaults
+/// to the global allocator; a HashMap using another allocator is created by
+/// [`HashMap::new_in`]. Memory that is reclaimed after the HashMap was dropped is also returned
+/// to `A`.
+///
+/// ## Send and Sync
+/// * `HashMap<K, V, H, A>` is Send if `K: Send`, `V: Send`, and `H: Send`.
+/// * `HashMap<K, V, H, A>` is Sync if `K: Send + Sync`, `V: Send + Sync`, and `H: Sync`; key-value
+///   pairs are moved in and out by any thread, and shared references to them are handed to
+///   multiple threads at once by [`HashMap::read`].
+/// * Neither is required to use a HashMap on a single thread, e.g., `HashMap<u64, Cell<u64>>`.
+///
+/// ```
+/// use scc::HashMap;
+/// use std::cell::Cell;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+///
+/// let hashmap: HashMap<u64, Cell<u64>> = Default::default();
+/// assert!(hashmap.insert(1, Cell::new(0)).is_ok());
+/// assert_send(&hashmap);
+/// ```
+///
+/// A HashMap containing an `Rc` cannot be sent to another thread.
+/// ```compile_fail
+/// use scc::HashMap;
+/// use std::rc::Rc;
+///
+/// fn assert_send<T: Send>() {}
+///
+/// assert_send::<HashMap<Rc<u64>, u64>>();
+/// ```
+///
+/// A HashMap containing a `MutexGuard` cannot be shared among threads.
+/// ```compile_fail
+/// use scc::HashMap;
+/// use std::sync::MutexGuard;
+///
+/// fn assert_sync<T: Sync>() {}
+///
+/// assert_sync::<HashMap<u64, MutexGuard<'static, u64>>>();
+/// ```
+pub struct HashMap<K, V, H = DefaultBuildHasher, A = GlobalArrayAllocator>
 where
-    K: Eq + Hash + Sync,
-    V: Sync,
+    K: Eq + Hash,
     H: BuildHasher,
+    A: ArrayAllocator,
 {
-    array: Atomic<CellArray<K, V, CELL_SIZE, false>>,
+    array: Atomic<CellArray<K, V, CELL_SIZE, false, A>>,
     minimum_capacity: usize,
     additional_capacity: AtomicUsize,
-    resizing_flag: AtomicBool,
+    reserved_capacity: AtomicUsize,
+    resizing_flag: ResizingFlag,
+    len_sampling_cells: usize,
+    len_sampling_deterministic: bool,
+    max_load_factor: f32,
+    shrink_policy: ShrinkPolicy,
+    rehash_budget: usize,
+    max_resizing_factor: usize,
+    resize_dampening: Duration,
+    resize_history: ResizeHistory,
+    hash_finalizer: bool,
+    num_removals: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    metrics: Counters,
+    #[cfg(feature = "background-worker")]
+    maintenance_signal: Arc<Signal>,
+    #[cfg(feature = "background-worker")]
+    maintenance_interval: Duration,
+    event_hook: Atomic<EventHook<K, V>>,
     build_hasher: H,
 }
 
-impl<K, V> Default for HashMap<K, V, RandomState>
+impl<K, V, H, A> PartialEq for HashMap<K, V, H, A>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Compares two HashMaps.
+    ///
+    /// The numbers of entries are compared first, and then each entry in `self` is looked up in
+    /// `other`. It is a best-effort comparison of snapshots if either of them is being modified
+    /// concurrently.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap1: HashMap<u64, u32> = Default::default();
+    /// let hashmap2: HashMap<u64, u32> = Default::default();
+    /// assert_eq!(hashmap1, hashmap2);
+    ///
+    /// assert!(hashmap1.insert(1, 0).is_ok());
+    /// assert_ne!(hashmap1, hashmap2);
+    ///
+    /// assert!(hashmap2.insert(1, 0).is_ok());
+    /// assert_eq!(hashmap1, hashmap2);
+    /// ```
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        self.scan(|key, value| other.read(key, |_, v| v == value).unwrap_or(false))
+    }
+}
+
+// Moving a HashMap to another thread moves the key-value pairs it owns.
+unsafe impl<K, V, H, A> Send for HashMap<K, V, H, A>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    H: BuildHasher + Send,
+    A: ArrayAllocator,
+{
+}
+
+// Any thread can move key-value pairs into or out of a shared HashMap, and multiple
+// threads can hold shared references to the same key-value pair.
+unsafe impl<K, V, H, A> Sync for HashMap<K, V, H, A>
+where
+    K: Eq + Hash + Send + Sync,
+    V: Send + Sync,
+    H: BuildHasher + Sync,
+    A: ArrayAllocator,
+{
+}
+
+impl<K, V, H, A> From<std::collections::HashMap<K, V, H>> for HashMap<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher + Clone,
+    A: ArrayAllocator,
+{
+    /// Creates a HashMap from a [`std::collections::HashMap`] by moving all the entries in.
+    ///
+    /// The HashMap is sized to accommodate all the entries, and it inherits the hash builder of
+    /// the given map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let mut std_hashmap = std::collections::HashMap::new();
+    /// std_hashmap.insert(1, 0);
+    ///
+    /// let hashmap: HashMap<u64, u32, _> = HashMap::from(std_hashmap);
+    /// assert_eq!(hashmap.read(&1, |_, value| *value), Some(0));
+    /// ```
+    fn from(std_hashmap: std::collections::HashMap<K, V, H>) -> Self {
+        let hashmap = HashMap::new_in(std_hashmap.len(), std_hashmap.hasher().clone());
+
+        // The HashMap is not shared with any other threads, therefore no resizing happens.
+        let guard = crate::ebr::pin();
+        let array_ref = Self::cell_array_ref(hashmap.array.load(Acquire, &guard));
+        for (key, value) in std_hashmap {
+            let hash = hashmap.hash(&key).0;
+            let cell_index = array_ref.calculate_cell_index(hash);
+            let locker = CellLocker::lock(array_ref.cell(cell_index), &guard).unwrap();
+            let result = locker.insert(key, value, hash, &guard).1;
+            debug_assert!(result.is_none());
+        }
+        hashmap
+    }
+}
+
+impl<K, V, H, A> IntoIterator for HashMap<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, H, A>;
+
+    /// Creates a consuming iterator that moves the key-value pairs out of the HashMap.
+    ///
+    /// The key-value pairs remaining in the iterator are dropped along with it.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let entries: Vec<(u64, u32)> = hashmap.into_iter().collect();
+    /// assert_eq!(entries, vec![(1, 0)]);
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            hash_map: self,
+            array_ptr: std::ptr::null(),
+            cell_index: 0,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<K, V> Default for HashMap<K, V, DefaultBuildHasher>
 where
-    K: Eq + Hash + Sync,
-    V: Sync,
+    K: Eq + Hash,
 {
     /// Creates a HashMap instance with the default parameters.
     ///
-    /// The default hash builder is RandomState, and the default capacity is 64.
+    /// The default hash builder is [`DefaultBuildHasher`](crate::DefaultBuildHasher), and the
+    /// default capacity is 64.
     ///
     /// # Panics
     ///
@@ -78,16 +303,33 @@ where
             )),
             minimum_capacity: DEFAULT_CAPACITY,
             additional_capacity: AtomicUsize::new(0),
-            resizing_flag: AtomicBool::new(false),
-            build_hasher: RandomState::new(),
+            reserved_capacity: AtomicUsize::new(0),
+            resizing_flag: ResizingFlag::default(),
+            len_sampling_cells: DEFAULT_LEN_SAMPLING_CELLS,
+            len_sampling_deterministic: true,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            shrink_policy: ShrinkPolicy::default(),
+            rehash_budget: CELL_SIZE,
+            max_resizing_factor: DEFAULT_MAX_RESIZING_FACTOR,
+            resize_dampening: Duration::ZERO,
+            resize_history: ResizeHistory::default(),
+            hash_finalizer: DEFAULT_HASH_FINALIZER,
+            num_removals: AtomicUsize::new(0),
+            #[cfg(feature = "metrics")]
+            metrics: Counters::default(),
+            #[cfg(feature = "background-worker")]
+            maintenance_signal: Arc::default(),
+            #[cfg(feature = "background-worker")]
+            maintenance_interval: maintenance::DEFAULT_INTERVAL,
+            event_hook: Atomic::null(),
+            build_hasher: DefaultBuildHasher::default(),
         }
     }
 }
 
 impl<K, V, H> HashMap<K, V, H>
 where
-    K: Eq + Hash + Sync,
-    V: Sync,
+    K: Eq + Hash,
     H: BuildHasher,
 {
     /// Creates an empty HashMap instance with the given capacity and build hasher.
@@ -111,11 +353,45 @@ where
     /// let hashmap: HashMap<u64, u32> = Default::default();
     /// let result = hashmap.capacity();
     /// assert_eq!(result, 64);
+    ///
+    /// let hashmap: HashMap<u64, u32, RandomState> = HashMap::new(4, RandomState::new());
+    /// let result = hashmap.capacity();
+    /// assert_eq!(result, 32);
     /// ```
     pub fn new(capacity: usize, build_hasher: H) -> HashMap<K, V, H> {
-        let initial_capacity = capacity.max(DEFAULT_CAPACITY);
-        let array = Owned::new(CellArray::<K, V, CELL_SIZE, false>::new(
-            initial_capacity,
+        HashMap::new_in(capacity, build_hasher)
+    }
+}
+
+impl<K, V, H, A> HashMap<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Creates an empty HashMap instance with the given capacity and build hasher that allocates
+    /// the Cells and entry arrays through `A`.
+    ///
+    /// The actual capacity is equal to or greater than the given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{GlobalArrayAllocator, HashMap};
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashmap: HashMap<u64, u32, RandomState, GlobalArrayAllocator> =
+    ///     HashMap::new_in(1000, RandomState::new());
+    ///
+    /// let result = hashmap.capacity();
+    /// assert_eq!(result, 1024);
+    /// ```
+    pub fn new_in(capacity: usize, build_hasher: H) -> HashMap<K, V, H, A> {
+        let array = Owned::new(CellArray::<K, V, CELL_SIZE, false, A>::new(
+            capacity,
             Atomic::null(),
         ));
         let current_capacity = array.num_cell_entries();
@@ -123,19 +399,268 @@ where
             array: Atomic::from(array),
             minimum_capacity: current_capacity,
             additional_capacity: AtomicUsize::new(0),
-            resizing_flag: AtomicBool::new(false),
+            reserved_capacity: AtomicUsize::new(0),
+            resizing_flag: ResizingFlag::default(),
+            len_sampling_cells: DEFAULT_LEN_SAMPLING_CELLS,
+            len_sampling_deterministic: true,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            shrink_policy: ShrinkPolicy::default(),
+            rehash_budget: CELL_SIZE,
+            max_resizing_factor: DEFAULT_MAX_RESIZING_FACTOR,
+            resize_dampening: Duration::ZERO,
+            resize_history: ResizeHistory::default(),
+            hash_finalizer: true,
+            num_removals: AtomicUsize::new(0),
+            #[cfg(feature = "metrics")]
+            metrics: Counters::default(),
+            #[cfg(feature = "background-worker")]
+            maintenance_signal: Arc::default(),
+            #[cfg(feature = "background-worker")]
+            maintenance_interval: maintenance::DEFAULT_INTERVAL,
+            event_hook: Atomic::null(),
             build_hasher,
         }
     }
 
+    /// Configures how [`HashMap::estimated_len`] samples Cells.
+    ///
+    /// `num_cells` Cells, 64 by default, are sampled at a fixed stride. If `deterministic` is
+    /// `true`, which is the default, the sample always starts from the first Cell, therefore the
+    /// estimate only changes when the sampled Cells are modified; otherwise, the sample starts
+    /// from a random Cell on each call.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default().with_len_sampling(4, false);
+    ///
+    /// let statistics = hashmap.statistics();
+    /// assert_eq!(statistics.len_sampling_cells(), 4);
+    /// assert!(!statistics.len_sampling_deterministic());
+    /// ```
+    pub fn with_len_sampling(mut self, num_cells: usize, deterministic: bool) -> Self {
+        self.len_sampling_cells = num_cells.max(1);
+        self.len_sampling_deterministic = deterministic;
+        self
+    }
+
+    /// Sets the maximum load factor of the HashMap.
+    ///
+    /// The HashMap grows when the estimated load factor reaches the maximum load factor, 7/8 by
+    /// default. A smaller maximum load factor leads to shorter linked lists in Cells at the cost
+    /// of memory, and a larger one packs more entries into each Cell.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the maximum load factor is not in `(0, 1]`.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default().with_max_load_factor(0.5);
+    ///
+    /// for key in 0..1024 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    /// assert!(hashmap.capacity() >= 2048);
+    /// ```
+    pub fn with_max_load_factor(mut self, max_load_factor: f32) -> Self {
+        assert!(
+            max_load_factor > 0.0 && max_load_factor <= 1.0,
+            "the maximum load factor must be in (0, 1]"
+        );
+        self.max_load_factor = max_load_factor;
+        self
+    }
+
+    /// Sets the shrink policy of the HashMap.
+    ///
+    /// The default policy is [`ShrinkPolicy::OnInsertSampling`]. [`ShrinkPolicy::Never`] keeps
+    /// the HashMap from shrinking, which avoids shrinking and growing repeatedly under workloads
+    /// that periodically remove most of the entries, and [`ShrinkPolicy::Aggressive`] releases
+    /// memory even when removals leave few entries in every Cell.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idle_ops` of [`ShrinkPolicy::Aggressive`] is zero.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{HashMap, ShrinkPolicy};
+    ///
+    /// let hashmap: HashMap<u64, u32> =
+    ///     HashMap::default().with_shrink_policy(ShrinkPolicy::Never);
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    /// let capacity = hashmap.capacity();
+    /// for key in 0..4096 {
+    ///     assert!(hashmap.remove(&key).is_some());
+    /// }
+    /// assert_eq!(hashmap.capacity(), capacity);
+    /// ```
+    pub fn with_shrink_policy(mut self, shrink_policy: ShrinkPolicy) -> Self {
+        if let ShrinkPolicy::Aggressive { idle_ops } = shrink_policy {
+            assert_ne!(idle_ops, 0, "idle_ops must be greater than zero");
+        }
+        self.shrink_policy = shrink_policy;
+        self
+    }
+
+    /// Sets the number of Cells in the old array that each operation relocates in passing.
+    ///
+    /// While the array is being resized, operations on the HashMap relocate `cells_per_op` Cells,
+    /// 32 by default, from the old array, moving at most 8 key-value pairs for each of them; a
+    /// Cell holding more key-value pairs is relocated over several operations. New keys are inserted into the current array without
+    /// relocating the Cell in the old array that the key belongs to. With `0`, operations never
+    /// relocate Cells in the old array, and the old array is only dropped by [`HashMap::rehash`],
+    /// maintenance threads, or operations that require the old array to be completely
+    /// relocated, e.g., [`HashMap::transfer`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default().with_rehash_budget(0);
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    ///     while hashmap.rehash(4) {}
+    /// }
+    /// assert!(hashmap.capacity() >= 4096);
+    /// ```
+    pub fn with_rehash_budget(mut self, cells_per_op: usize) -> Self {
+        self.rehash_budget = cells_per_op;
+        self
+    }
+
+    /// Enables or disables the hash finalizer that mixes the bits of hash values.
+    ///
+    /// The HashMap selects a Cell with the most significant bits of a hash value and compares
+    /// the least significant bits before comparing keys, therefore it mixes the bits of every
+    /// hash value with a few multiplications by default so that a [`BuildHasher`] with poor
+    /// diffusion, e.g., the identity function, does not cluster keys. The finalizer can be
+    /// disabled if the [`BuildHasher`] already spreads the bits of keys over the whole hash
+    /// value, which [`HashMap::default`] does with the `fast-hash` feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HashMap is not empty, because the entries would not be found anymore.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashmap: HashMap<u64, u32, RandomState> =
+    ///     HashMap::new(64, RandomState::new()).with_hash_finalizer(false);
+    ///
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// assert_eq!(hashmap.read(&1, |_, v| *v), Some(0));
+    /// ```
+    pub fn with_hash_finalizer(mut self, enabled: bool) -> Self {
+        assert_eq!(self.len(), 0, "the HashMap must be empty");
+        self.hash_finalizer = enabled;
+        self
+    }
+
+    /// Sets the maximum factor by which the array grows at once.
+    ///
+    /// The HashMap estimates the number of entries from a sample of Cells when it resizes the
+    /// array. The array grows by up to 64x at once, or by up to `max_resizing_factor`, 1024 by
+    /// default, if the sample contains many entries and most of the sampled Cells overflow, e.g.,
+    /// because a large number of entries were inserted while the old array was being relocated.
+    /// A factor smaller than 64 limits both cases.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_resizing_factor` is less than 2.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default().with_max_resizing_factor(4096);
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    /// assert!(hashmap.capacity() >= 4096);
+    /// ```
+    pub fn with_max_resizing_factor(mut self, max_resizing_factor: usize) -> Self {
+        assert!(
+            max_resizing_factor >= 2,
+            "the maximum resizing factor must be at least 2"
+        );
+        self.max_resizing_factor = max_resizing_factor;
+        self
+    }
+
+    /// Sets the minimum interval between resizes in opposite directions.
+    ///
+    /// A workload that keeps the load factor of the HashMap around a resizing threshold may grow
+    /// and shrink the array back and forth, and each resize relocates all the entries. Once the
+    /// array grows, it does not shrink within `min_interval`, and vice versa. Resizing in the
+    /// opposite direction is not dampened by default.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    /// use std::time::Duration;
+    ///
+    /// let hashmap: HashMap<u64, u32> =
+    ///     HashMap::default().with_resize_dampening(Duration::from_secs(3600));
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    /// let capacity = hashmap.capacity();
+    /// for key in 0..4096 {
+    ///     assert!(hashmap.remove(&key).is_some());
+    /// }
+    /// assert_eq!(hashmap.capacity(), capacity);
+    /// ```
+    pub fn with_resize_dampening(mut self, min_interval: Duration) -> Self {
+        self.resize_dampening = min_interval;
+        self
+    }
+
+    /// Sets the interval between incremental rehashing steps of maintenance threads.
+    ///
+    /// A maintenance thread spawned by [`HashMap::spawn_maintenance`] relocates a bounded number
+    /// of Cells from the old array, and then waits for the given interval, 1ms by default, before
+    /// taking the next step.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    /// use std::time::Duration;
+    ///
+    /// let hashmap: HashMap<u64, u32> =
+    ///     HashMap::default().with_maintenance_interval(Duration::from_millis(10));
+    /// ```
+    #[cfg(feature = "background-worker")]
+    pub fn with_maintenance_interval(mut self, interval: Duration) -> Self {
+        self.maintenance_interval = interval;
+        self
+    }
+
     /// Temporarily increases the minimum capacity of the HashMap.
     ///
     /// The reserved space is not exclusively owned by the Ticket, there thus can be overtaken.
     /// Unused space is immediately reclaimed when the Ticket is dropped.
     ///
+    /// If the HashMap is being resized, the entries left in the old array are relocated before
+    /// the capacity is increased, and so are they when the Ticket is dropped, therefore the
+    /// thread must not hold an [`Accessor`] at the time.
+    ///
     /// # Errors
     ///
-    /// Returns None if the given value is too large.
+    /// Returns None if the given value is too large, i.e., the minimum capacity would exceed the
+    /// maximum capacity of the target platform.
     ///
     /// # Examples
     /// ```
@@ -155,10 +680,15 @@ where
     ///
     /// assert_eq!(hashmap.capacity(), 1024);
     /// ```
-    pub fn reserve(&self, capacity: usize) -> Option<Ticket<K, V, H>> {
+    pub fn reserve(&self, capacity: usize) -> Option<Ticket<'_, K, V, H, A>> {
+        let max_capacity = CellArray::<K, V, CELL_SIZE, false, A>::max_capacity();
         let mut current_additional_capacity = self.additional_capacity.load(Relaxed);
         loop {
-            if usize::MAX - self.minimum_capacity - current_additional_capacity <= capacity {
+            let new_minimum_capacity = self
+                .minimum_capacity
+                .checked_add(current_additional_capacity)
+                .and_then(|c| c.checked_add(capacity));
+            if new_minimum_capacity.is_none_or(|c| c > max_capacity) {
                 // The given value is too large.
                 return None;
             }
@@ -168,21 +698,59 @@ where
                 Relaxed,
                 Relaxed,
             ) {
-                Ok(_) => {
-                    let guard = crossbeam_epoch::pin();
-                    self.resize(&guard);
-                    return Some(Ticket {
-                        hash_map: self,
-                        increment: capacity,
-                    });
-                }
+                Ok(_) => break,
                 Err(current) => current_additional_capacity = current,
             }
         }
+        self.resize(&crate::ebr::pin());
+        Some(Ticket {
+            hash_map: self,
+            increment: capacity,
+        })
+    }
+
+    /// Tries to grow the HashMap so that it can accommodate the given number of additional
+    /// key-value pairs.
+    ///
+    /// The HashMap grows by at most 64x at a time, so that no single operation relocates an
+    /// unbounded number of key-value pairs. The reserved capacity is kept as the minimum capacity,
+    /// therefore the HashMap does not shrink below it once key-value pairs are removed; unlike
+    /// [`HashMap::reserve`], the reservation is not released.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TryReserveError::CapacityOverflow`] if the required capacity exceeds the maximum
+    /// capacity of the target platform, or [`TryReserveError::AllocError`] if memory allocation
+    /// fails. The capacity is left unchanged if the required capacity is too large, and the steps
+    /// taken before a failed allocation are kept.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{HashMap, TryReserveError};
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    /// assert_eq!(hashmap.capacity(), 64);
+    ///
+    /// assert!(hashmap.try_reserve(1000).is_ok());
+    /// assert!(hashmap.capacity() >= 1000);
+    ///
+    /// let result = hashmap.try_reserve(usize::MAX);
+    /// assert_eq!(result, Err(TryReserveError::CapacityOverflow));
+    /// ```
+    pub fn try_reserve(&self, additional: usize) -> Result<(), TryReserveError> {
+        self.try_grow(additional, |_, _| None)
+    }
+
+    /// Releases the capacity reserved by [`HashMap::try_reserve`].
+    pub(crate) fn release_reserved_capacity(&self) {
+        self.reserved_capacity.store(0, Relaxed);
     }
 
     /// Inserts a key-value pair into the HashMap.
     ///
+    /// The returned Accessor keeps the Cell locked in both cases, therefore the newly inserted or
+    /// the existing key-value pair can be inspected or updated without being looked up again.
+    ///
     /// # Errors
     ///
     /// Returns an error with a mutable reference to the existing key-value pair.
@@ -198,49 +766,39 @@ where
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
+    /// if let Ok(mut result) = result {
     ///     assert_eq!(result.get(), (&1, &mut 0));
     /// } else {
     ///     assert!(false);
     /// }
     ///
     /// let result = hashmap.insert(1, 1);
-    /// if let Err((accessor, key, value)) = result {
+    /// if let Err((mut accessor, key, value)) = result {
     ///     assert_eq!(accessor.get(), (&1, &mut 0));
     ///     assert_eq!(key, 1);
     ///     assert_eq!(value, 1);
+    ///     *accessor.get().1 = value;
     /// } else {
     ///     assert!(false);
     /// }
+    ///
+    /// let result = hashmap.read(&1, |_, value| *value);
+    /// assert_eq!(result.unwrap(), 1);
     /// ```
-    pub fn insert<'h>(
-        &'h self,
-        key: K,
-        value: V,
-    ) -> Result<Accessor<K, V, H>, (Accessor<K, V, H>, K, V)> {
-        let (mut accessor, key, partial_hash) = self.lock(key);
-        if accessor.cell_iterator.is_some() {
-            return Err((accessor, key, value));
-        }
-        let (iterator, result) =
-            accessor
-                .cell_locker
-                .as_ref()
-                .unwrap()
-                .insert(key, value, partial_hash, unsafe {
-                    crossbeam_epoch::unprotected()
-                });
-        accessor.cell_iterator.replace(unsafe {
-            std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false>>(iterator)
-        });
-        debug_assert!(result.is_none());
-        drop(result);
-        Ok(accessor)
+    pub fn insert(&self, key: K, value: V) -> InsertResult<'_, K, V, H, A> {
+        let (accessor, key, hash) = self.lock(key);
+        self.insert_locked(accessor, key, hash, value)
     }
 
-    /// Constructs the value in-place.
+    /// Inserts a key-value pair into the HashMap, and returns an Accessor to the stored entry.
     ///
-    /// The given closure is never invoked if the key exists.
+    /// It is equivalent to [`HashMap::insert`] except for the order of the fields of the error;
+    /// the Cell is locked only once, and the returned Accessor keeps it locked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error with the given key-value pair and an Accessor owning the existing
+    /// key-value pair, so that the caller can decide to overwrite it.
     ///
     /// # Panics
     ///
@@ -252,45 +810,159 @@ where
     ///
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
-    /// let mut current = 0;
-    /// let result = hashmap.emplace(1, || { current += 1; current });
-    /// if let Ok(result) = result {
-    ///     assert_eq!(result.get(), (&1, &mut 1));
-    /// } else {
-    ///     assert!(false);
-    /// }
+    /// let mut accessor = hashmap.insert_and_get(1, 0).ok().unwrap();
+    /// assert_eq!(accessor.get(), (&1, &mut 0));
+    /// drop(accessor);
     ///
-    /// let result = hashmap.emplace(1, || { current += 1; current });
-    /// if let Err((result, key)) = result {
-    ///     assert_eq!(result.get(), (&1, &mut 1));
+    /// if let Err((key, value, mut accessor)) = hashmap.insert_and_get(1, 1) {
     ///     assert_eq!(key, 1);
-    ///     assert_eq!(current, 1);
+    ///     *accessor.get().1 = value;
     /// } else {
     ///     assert!(false);
     /// }
+    ///
+    /// assert_eq!(hashmap.read(&1, |_, value| *value), Some(1));
     /// ```
-    pub fn emplace<'h, F: FnOnce() -> V>(
-        &'h self,
-        key: K,
-        constructor: F,
-    ) -> Result<Accessor<K, V, H>, (Accessor<K, V, H>, K)> {
-        let (mut accessor, key, partial_hash) = self.lock(key);
-        if accessor.cell_iterator.is_some() {
+    pub fn insert_and_get(&self, key: K, value: V) -> InsertAndGetResult<'_, K, V, H, A> {
+        self.insert(key, value)
+            .map_err(|(accessor, key, value)| (key, value, accessor))
+    }
+
+    /// Locks the Cell for the key, and returns an EntryGuard reserving the key.
+    ///
+    /// The EntryGuard keeps the Cell locked until it is dropped, therefore no other threads are
+    /// able to insert, update, or remove the key in the meantime; they block until the EntryGuard
+    /// is dropped. Key-value pairs in the same Cell are blocked as well.
+    ///
+    /// Holding an EntryGuard while accessing the HashMap is prone to deadlocks.
+    /// * Any access to a key in the same Cell from the thread owning the EntryGuard never returns,
+    ///   including a second call to `lock_entry` with the same key.
+    /// * Threads holding multiple EntryGuards at a time can deadlock with each other unless they
+    ///   lock the keys in the same order; even then, two keys that belong to the same Cell cannot
+    ///   be locked at a time.
+    /// * Resizing the HashMap relocates the locked Cell after the EntryGuard is dropped, and
+    ///   operations on the other Cells may be blocked until then.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails, or the number of entries in the target cell reaches u32::MAX.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let entry_guard = hashmap.lock_entry(1);
+    /// assert!(entry_guard.existing().is_none());
+    /// assert!(entry_guard.insert(0).is_ok());
+    ///
+    /// let entry_guard = hashmap.lock_entry(1);
+    /// assert_eq!(entry_guard.existing(), Some(&0));
+    /// entry_guard.abandon();
+    ///
+    /// assert_eq!(hashmap.read(&1, |_, value| *value), Some(0));
+    /// ```
+    pub fn lock_entry(&self, key: K) -> EntryGuard<'_, K, V, H, A> {
+        let (accessor, key, hash) = self.lock(key);
+        EntryGuard {
+            accessor,
+            key,
+            hash,
+        }
+    }
+
+    /// Constructs the value in-place.
+    ///
+    /// The given closure is never invoked if the key exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails, or the number of entries in the target cell reaches u32::MAX.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let mut current = 0;
+    /// let result = hashmap.emplace(1, || { current += 1; current });
+    /// if let Ok(mut result) = result {
+    ///     assert_eq!(result.get(), (&1, &mut 1));
+    /// } else {
+    ///     assert!(false);
+    /// }
+    ///
+    /// let result = hashmap.emplace(1, || { current += 1; current });
+    /// if let Err((mut result, key)) = result {
+    ///     assert_eq!(result.get(), (&1, &mut 1));
+    ///     assert_eq!(key, 1);
+    ///     assert_eq!(current, 1);
+    /// } else {
+    ///     assert!(false);
+    /// }
+    /// ```
+    pub fn emplace<'h, F: FnOnce() -> V>(
+        &'h self,
+        key: K,
+        constructor: F,
+    ) -> EmplaceResult<'h, K, V, H, A> {
+        let (mut accessor, key, hash) = self.lock(key);
+        if accessor.cell_iterator.is_some() {
             return Err((accessor, key));
         }
-        let (iterator, result) = accessor.cell_locker.as_ref().unwrap().insert(
+        let (iterator, result) = self.insert_into_cell(
+            accessor.cell_locker.as_ref().unwrap(),
             key,
             constructor(),
-            partial_hash,
-            unsafe { crossbeam_epoch::unprotected() },
+            hash,
+            unsafe { crate::ebr::unprotected() },
         );
         debug_assert!(result.is_none());
         accessor.cell_iterator.replace(unsafe {
-            std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false>>(iterator)
+            std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false, A>>(iterator)
         });
+        accessor.event.set(Some(EventKind::Inserted));
         Ok(accessor)
     }
 
+    /// Gets the key-value pair associated with the key, or constructs the value in-place.
+    ///
+    /// The given closure is only invoked if the key does not exist, and the returned Accessor
+    /// owns either the existing or the newly inserted key-value pair.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails, or the number of entries in the target cell reaches u32::MAX.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let mut current = 0;
+    /// let mut result = hashmap.get_or_insert_with(1, || { current += 1; current });
+    /// assert_eq!(result.get(), (&1, &mut 1));
+    /// *result.get().1 = 2;
+    /// drop(result);
+    ///
+    /// let mut result = hashmap.get_or_insert_with(1, || { current += 1; current });
+    /// assert_eq!(result.get(), (&1, &mut 2));
+    /// assert_eq!(current, 1);
+    /// ```
+    pub fn get_or_insert_with<'h, F: FnOnce() -> V>(
+        &'h self,
+        key: K,
+        constructor: F,
+    ) -> Accessor<'h, K, V, H, A> {
+        match self.emplace(key, constructor) {
+            Ok(accessor) => accessor,
+            Err((accessor, _)) => accessor,
+        }
+    }
+
     /// Upserts a key-value pair into the HashMap.
     ///
     /// # Panics
@@ -304,33 +976,36 @@ where
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
+    /// if let Ok(mut result) = result {
     ///     assert_eq!(result.get(), (&1, &mut 0));
     /// } else {
     ///     assert!(false);
     /// }
     ///
-    /// let result = hashmap.upsert(1, 1);
+    /// let mut result = hashmap.upsert(1, 1);
     /// assert_eq!(result.get(), (&1, &mut 1));
     /// ```
-    pub fn upsert<'h>(&'h self, key: K, value: V) -> Accessor<K, V, H> {
-        let (mut accessor, key, partial_hash) = self.lock(key);
+    pub fn upsert<'h>(&'h self, key: K, value: V) -> Accessor<'h, K, V, H, A> {
+        let (mut accessor, key, hash) = self.lock(key);
         if accessor.cell_iterator.is_some() {
-            drop(std::mem::replace(accessor.get().1, value));
+            drop(std::mem::replace(accessor.entry().1, value));
+            accessor.event.set(Some(EventKind::Updated));
             return accessor;
         }
-        let (iterator, result) =
-            accessor
-                .cell_locker
-                .as_ref()
-                .unwrap()
-                .insert(key, value, partial_hash, unsafe {
-                    crossbeam_epoch::unprotected()
-                });
+        let (iterator, result) = self.insert_into_cell(
+            accessor.cell_locker.as_ref().unwrap(),
+            key,
+            value,
+            hash,
+            unsafe { crate::ebr::unprotected() },
+        );
         debug_assert!(result.is_none());
         accessor.cell_iterator.replace(unsafe {
-            std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false>>(iterator)
+            std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false, A>>(iterator)
         });
+        #[cfg(feature = "metrics")]
+        self.metrics.add(Counter::Inserts, 1);
+        accessor.event.set(Some(EventKind::Inserted));
         accessor
     }
 
@@ -350,26 +1025,157 @@ where
     /// assert!(result.is_none());
     ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
+    /// if let Ok(mut result) = result {
     ///     assert_eq!(result.get(), (&1, &mut 0));
     /// }
     ///
     /// let result = hashmap.get(&1);
     /// assert_eq!(result.unwrap().get(), (&1, &mut 0));
     /// ```
-    pub fn get<'h, Q>(&'h self, key: &Q) -> Option<Accessor<'h, K, V, H>>
+    pub fn get<'h, Q>(&'h self, key: &Q) -> Option<Accessor<'h, K, V, H, A>>
     where
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
-        let (hash, partial_hash) = self.hash(key);
-        let accessor = self.acquire(key, hash, partial_hash);
+        let (hash, _) = self.hash(key);
+        let accessor = self.acquire(hash, |k| k.borrow() == key);
         if accessor.cell_iterator.is_none() {
             return None;
         }
         Some(accessor)
     }
 
+    /// Gets a mutable reference to the value using a precomputed hash value and an equivalence
+    /// predicate.
+    ///
+    /// The same requirements as [`HashMap::read_with_hash`] apply to `hash` and `eq`.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    ///
+    /// let build_hasher = RandomState::new();
+    /// let hashmap: HashMap<u64, u32, RandomState> = HashMap::new(64, build_hasher.clone());
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let mut hasher = build_hasher.build_hasher();
+    /// 1_u64.hash(&mut hasher);
+    /// let hash = hasher.finish();
+    ///
+    /// let result = hashmap.entry_with_hash(hash, |key| *key == 1);
+    /// *result.unwrap().get().1 = 2;
+    ///
+    /// let result = hashmap.read(&1, |_, value| *value);
+    /// assert_eq!(result.unwrap(), 2);
+    /// ```
+    pub fn entry_with_hash<'h, E: FnMut(&K) -> bool>(
+        &'h self,
+        hash: u64,
+        eq: E,
+    ) -> Option<Accessor<'h, K, V, H, A>> {
+        let (hash, _) = self.finalize(hash);
+        let accessor = self.acquire(hash, eq);
+        if accessor.cell_iterator.is_some() {
+            Some(accessor)
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the value associated with the key if the current value satisfies the predicate.
+    ///
+    /// The predicate is evaluated and the value is replaced while the Cell is locked, and the
+    /// previous value is returned by move.
+    ///
+    /// # Errors
+    ///
+    /// Returns the given value with [`CasError::NotFound`] if the key does not exist, or with
+    /// [`CasError::Mismatch`] if the predicate rejects the current value.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{CasError, HashMap};
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.compare_exchange(&1, |value| *value == 0, 1);
+    /// assert_eq!(result, Err(CasError::NotFound(1)));
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let result = hashmap.compare_exchange(&1, |value| *value == 1, 2);
+    /// assert_eq!(result, Err(CasError::Mismatch(2)));
+    ///
+    /// let result = hashmap.compare_exchange(&1, |value| *value == 0, 2);
+    /// assert_eq!(result, Ok(0));
+    ///
+    /// let result = hashmap.read(&1, |_, value| *value);
+    /// assert_eq!(result.unwrap(), 2);
+    /// ```
+    pub fn compare_exchange<Q, F: FnOnce(&V) -> bool>(
+        &self,
+        key: &Q,
+        expected: F,
+        new_value: V,
+    ) -> Result<V, CasError<V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(mut accessor) = self.get(key) {
+            if expected(accessor.entry().1) {
+                Ok(std::mem::replace(accessor.get().1, new_value))
+            } else {
+                Err(CasError::Mismatch(new_value))
+            }
+        } else {
+            Err(CasError::NotFound(new_value))
+        }
+    }
+
+    /// Replaces the value associated with the key, and returns the previous value.
+    ///
+    /// The value is replaced while the Cell is locked, and the previous value is returned by
+    /// move.
+    ///
+    /// # Errors
+    ///
+    /// Returns the given key-value pair without inserting it if the key does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.replace(1, 1);
+    /// assert_eq!(result, Err((1, 1)));
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let result = hashmap.replace(1, 1);
+    /// assert_eq!(result, Ok(0));
+    ///
+    /// let result = hashmap.read(&1, |_, value| *value);
+    /// assert_eq!(result.unwrap(), 1);
+    /// ```
+    pub fn replace(&self, key: K, value: V) -> Result<V, (K, V)> {
+        if let Some(mut accessor) = self.get(&key) {
+            Ok(std::mem::replace(accessor.get().1, value))
+        } else {
+            Err((key, value))
+        }
+    }
+
     /// Removes a key-value pair.
     ///
     /// # Errors
@@ -386,7 +1192,7 @@ where
     /// assert!(result.is_none());
     ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
+    /// if let Ok(mut result) = result {
     ///     assert_eq!(result.get(), (&1, &mut 0));
     /// }
     ///
@@ -402,11 +1208,15 @@ where
             .map_or_else(|| None, |accessor| accessor.erase())
     }
 
-    /// Reads a key-value pair.
+    /// Removes a key-value pair if the value satisfies the given predicate.
+    ///
+    /// The predicate is given a mutable reference to the value while the Cell is locked, therefore
+    /// it is able to update the value before deciding whether or not to remove it.
     ///
     /// # Errors
     ///
-    /// Returns None if the key does not exist.
+    /// Returns None if the key does not exist, or the predicate declines to remove the key-value
+    /// pair; use [`HashMap::remove_if_result`] in order to distinguish between the two cases.
     ///
     /// # Examples
     /// ```
@@ -415,138 +1225,224 @@ where
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
-    ///     assert_eq!(result.get(), (&1, &mut 0));
-    /// }
+    /// assert!(result.is_ok());
+    /// drop(result);
     ///
-    /// let result = hashmap.read(&1, |key, value| *value);
-    /// assert_eq!(result.unwrap(), 0);
+    /// let result = hashmap.remove_if(&1, |value| { *value += 1; *value == 2 });
+    /// assert!(result.is_none());
+    ///
+    /// let result = hashmap.remove_if(&1, |value| { *value += 1; *value == 2 });
+    /// assert_eq!(result.unwrap(), 2);
     /// ```
-    pub fn read<Q, R, F: FnOnce(&Q, &V) -> R>(&self, key: &Q, f: F) -> Option<R>
+    pub fn remove_if<Q, F: FnOnce(&mut V) -> bool>(&self, key: &Q, condition: F) -> Option<V>
     where
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
-        let (hash, partial_hash) = self.hash(key);
-        let guard = crossbeam_epoch::pin();
-
-        // An acquire fence is required to correctly load the contents of the array.
-        let mut current_array_shared = self.array.load(Acquire, &guard);
-        loop {
-            let current_array_ref = Self::cell_array_ref(current_array_shared);
-            let old_array_shared = current_array_ref.old_array(&guard);
-            if !old_array_shared.is_null()
-                && !current_array_ref.partial_rehash(|key| self.hash(key), |_, _| None, &guard)
-            {
-                let old_array_ref = Self::cell_array_ref(old_array_shared);
-                let cell_index = old_array_ref.calculate_cell_index(hash);
-                if let Some(reader) = CellReader::lock(old_array_ref.cell(cell_index), &guard) {
-                    if let Some((key, value)) = reader.cell_ref().search(key, partial_hash, &guard)
-                    {
-                        return Some(f(key.borrow(), value));
-                    }
-                }
-            }
-            let cell_index = current_array_ref.calculate_cell_index(hash);
-            if let Some(reader) = CellReader::lock(current_array_ref.cell(cell_index), &guard) {
-                if let Some((key, value)) = reader.cell_ref().search(key, partial_hash, &guard) {
-                    return Some(f(key.borrow(), value));
-                }
-            }
-            let new_current_array_shared = self.array.load(Acquire, &guard);
-            if new_current_array_shared == current_array_shared {
-                break;
-            }
-            // The pointer value has changed.
-            current_array_shared = new_current_array_shared;
+        match self.remove_if_result(key, condition) {
+            RemoveIfResult::Removed(value) => Some(value),
+            _ => None,
         }
-        None
     }
 
-    /// Checks if the key exists.
+    /// Removes a key-value pair if the value satisfies the given predicate.
+    ///
+    /// It is identical to [`HashMap::remove_if`] except that it returns a [`RemoveIfResult`] that
+    /// tells whether the key was not found or the predicate declined to remove the key-value pair.
     ///
     /// # Examples
     /// ```
-    /// use scc::HashMap;
+    /// use scc::{HashMap, RemoveIfResult};
     ///
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
-    /// let result = hashmap.contains(&1);
-    /// assert!(!result);
+    /// let result = hashmap.remove_if_result(&1, |_| true);
+    /// assert_eq!(result, RemoveIfResult::NotFound);
     ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
-    ///     assert_eq!(result.get(), (&1, &mut 0));
-    /// }
+    /// assert!(result.is_ok());
+    /// drop(result);
     ///
-    /// let result = hashmap.contains(&1);
-    /// assert!(result);
+    /// let result = hashmap.remove_if_result(&1, |value| *value != 0);
+    /// assert_eq!(result, RemoveIfResult::Declined);
+    ///
+    /// let result = hashmap.remove_if_result(&1, |value| *value == 0);
+    /// assert_eq!(result, RemoveIfResult::Removed(0));
     /// ```
-    pub fn contains<Q>(&self, key: &Q) -> bool
+    pub fn remove_if_result<Q, F: FnOnce(&mut V) -> bool>(
+        &self,
+        key: &Q,
+        condition: F,
+    ) -> RemoveIfResult<V>
     where
         K: Borrow<Q>,
         Q: Eq + Hash + ?Sized,
     {
-        self.read(key, |_, _| ()).is_some()
+        if let Some(mut accessor) = self.get(key) {
+            if condition(accessor.get().1) {
+                RemoveIfResult::Removed(self.erase(accessor).1)
+            } else {
+                RemoveIfResult::Declined
+            }
+        } else {
+            RemoveIfResult::NotFound
+        }
     }
 
-    /// Retains the key-value pairs that satisfy the given predicate.
+    /// Moves the value associated with a key to another key atomically.
     ///
-    /// It returns the number of entries remaining and removed.
+    /// The Cells for the two keys are locked at the same time, therefore no other operations on
+    /// either key are able to observe a state where the value is associated with both keys or
+    /// neither of them. The Cells are locked in the order of their indexes after the old array is
+    /// completely rehashed, and a single lock is taken if the keys belong to the same Cell.
+    ///
+    /// An [`Event::Removed`] for `from` and an [`Event::Inserted`] for `to` are emitted if an
+    /// event hook is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TransferError::NotFound`] if `from` does not exist, or
+    /// [`TransferError::Occupied`] if `to` exists; the HashMap is left unchanged, and `to` is
+    /// dropped. `to` is regarded as existing if it is equal to `from`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails, or the number of entries in the target cell reaches u32::MAX.
     ///
     /// # Examples
     /// ```
-    /// use scc::HashMap;
+    /// use scc::{HashMap, TransferError};
     ///
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
-    /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
-    ///     assert_eq!(result.get(), (&1, &mut 0));
-    /// }
-    ///
-    /// let result = hashmap.insert(2, 0);
-    /// if let Ok(result) = result {
-    ///     assert_eq!(result.get(), (&2, &mut 0));
-    /// }
-    ///
-    /// let result = hashmap.retain(|key, value| *key == 1 && *value == 0);
-    /// assert_eq!(result, (1, 1));
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// assert!(hashmap.insert(2, 1).is_ok());
     ///
-    /// let result = hashmap.get(&1);
-    /// assert_eq!(result.unwrap().get(), (&1, &mut 0));
+    /// assert_eq!(hashmap.transfer(&1, 2), Err(TransferError::Occupied));
+    /// assert_eq!(hashmap.transfer(&3, 4), Err(TransferError::NotFound));
+    /// assert!(hashmap.transfer(&1, 3).is_ok());
     ///
-    /// let result = hashmap.get(&2);
-    /// assert!(result.is_none());
+    /// assert!(!hashmap.contains(&1));
+    /// assert_eq!(hashmap.read(&3, |_, value| *value), Some(0));
     /// ```
-    pub fn retain<F: Fn(&K, &mut V) -> bool>(&self, f: F) -> (usize, usize) {
-        let mut retained_entries = 0;
-        let mut removed_entries = 0;
-        let mut accessor = self.iter();
-        while let Some((key, value)) = accessor.next() {
-            if !f(key, value) {
-                accessor
-                    .cell_locker
-                    .as_ref()
-                    .unwrap()
-                    .erase(accessor.cell_iterator.as_mut().unwrap());
-                removed_entries += 1;
-            } else {
-                retained_entries += 1;
-            }
+    pub fn transfer<Q>(&self, from: &Q, to: K) -> Result<(), TransferError>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        #[cfg(feature = "metrics")]
+        let _recorder = self.metrics.recorder();
+        let (from_hash, _) = self.hash(from);
+        let (to_hash, _) = self.hash::<K>(&to);
+        let guard = crate::ebr::pin();
+        let (from_locker, to_locker) = self.lock_pair(from_hash, to_hash, &guard);
+        let to_locker = to_locker.as_ref().unwrap_or(&from_locker);
+        if to_locker
+            .cell_ref()
+            .search::<K>(&to, to_hash, &guard)
+            .is_some()
+        {
+            return Err(TransferError::Occupied);
         }
+        let mut from_iterator = from_locker
+            .cell_ref()
+            .get_with(|key| key.borrow() == from, from_hash, &guard)
+            .ok_or(TransferError::NotFound)?;
+        let (from_key, value) = from_locker.erase(&mut from_iterator).unwrap();
+        let (to_iterator, result) = to_locker.insert(to, value, to_hash, &guard);
+        debug_assert!(result.is_none());
 
-        let guard = crossbeam_epoch::pin();
-        let current_array = self.array.load(Acquire, &guard);
-        let current_array_ref = Self::cell_array_ref(current_array);
-        if retained_entries <= current_array_ref.num_cell_entries() / 8 {
-            self.resize(&guard);
+        // The key-value pair is cloned before the Cells are unlocked.
+        let cloned = self
+            .event_hook(&guard)
+            .and_then(|event_hook| to_iterator.get().map(|(k, v)| (event_hook.cloner)(k, v)));
+        drop(from_locker);
+        #[cfg(feature = "metrics")]
+        {
+            self.metrics.add(Counter::Removes, 1);
+            self.metrics.add(Counter::Inserts, 1);
         }
-
-        (retained_entries, removed_entries)
+        if let Some((to_key, value)) = cloned {
+            self.emit(Event::Removed(&from_key, &value));
+            self.emit(Event::Inserted(&to_key, &value));
+        }
+        Ok(())
     }
 
-    /// Clears all the key-value pairs.
+    /// Swaps the values associated with two keys atomically.
+    ///
+    /// The Cells for the two keys are locked at the same time in the same manner as
+    /// [`HashMap::transfer`]. An [`Event::Updated`] is emitted for each key if an event hook is
+    /// set and the keys are different.
+    ///
+    /// It returns `true` if both keys exist; otherwise the HashMap is left unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// assert!(!hashmap.swap_values(&1, &2));
+    ///
+    /// assert!(hashmap.insert(2, 1).is_ok());
+    /// assert!(hashmap.swap_values(&1, &2));
+    /// assert_eq!(hashmap.read(&1, |_, value| *value), Some(1));
+    /// assert_eq!(hashmap.read(&2, |_, value| *value), Some(0));
+    /// ```
+    pub fn swap_values<Q>(&self, a: &Q, b: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        #[cfg(feature = "metrics")]
+        let _recorder = self.metrics.recorder();
+        let (a_hash, _) = self.hash(a);
+        let (b_hash, _) = self.hash(b);
+        let guard = crate::ebr::pin();
+        let (a_locker, b_locker) = self.lock_pair(a_hash, b_hash, &guard);
+        let b_locker = b_locker.as_ref().unwrap_or(&a_locker);
+        let (mut a_iterator, mut b_iterator) = match (
+            a_locker
+                .cell_ref()
+                .get_with(|key| key.borrow() == a, a_hash, &guard),
+            b_locker
+                .cell_ref()
+                .get_with(|key| key.borrow() == b, b_hash, &guard),
+        ) {
+            (Some(a_iterator), Some(b_iterator)) => (a_iterator, b_iterator),
+            _ => return false,
+        };
+        if a_iterator.get().map(|(key, _)| key as *const K)
+            == b_iterator.get().map(|(key, _)| key as *const K)
+        {
+            return true;
+        }
+        let (a_key, a_value) = a_locker.entry_mut(&mut a_iterator).unwrap();
+        let (b_key, b_value) = b_locker.entry_mut(&mut b_iterator).unwrap();
+        std::mem::swap(a_value, b_value);
+
+        // The key-value pairs are cloned before the Cells are unlocked.
+        let cloned = self.event_hook(&guard).map(|event_hook| {
+            (
+                (event_hook.cloner)(a_key, a_value),
+                (event_hook.cloner)(b_key, b_value),
+            )
+        });
+        drop(a_locker);
+        if let Some(((a_key, a_value), (b_key, b_value))) = cloned {
+            self.emit(Event::Updated(&a_key, &a_value));
+            self.emit(Event::Updated(&b_key, &b_value));
+        }
+        true
+    }
+
+    /// Reads a key-value pair.
+    ///
+    /// # Errors
+    ///
+    /// Returns None if the key does not exist.
     ///
     /// # Examples
     /// ```
@@ -555,33 +1451,73 @@ where
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
+    /// if let Ok(mut result) = result {
     ///     assert_eq!(result.get(), (&1, &mut 0));
     /// }
     ///
-    /// let result = hashmap.insert(2, 0);
-    /// if let Ok(result) = result {
-    ///     assert_eq!(result.get(), (&2, &mut 0));
-    /// }
+    /// let result = hashmap.read(&1, |key, value| *value);
+    /// assert_eq!(result.unwrap(), 0);
+    /// ```
+    pub fn read<Q, R, F: FnOnce(&Q, &V) -> R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (hash, _) = self.hash(key);
+        self.read_entry(hash, |k| k.borrow() == key, |k, v| f(k.borrow(), v))
+    }
+
+    /// Reads a key-value pair without blocking the thread.
     ///
-    /// let result = hashmap.clear();
-    /// assert_eq!(result, 2);
+    /// It is an asynchronous counterpart of [`HashMap::read`]; the task is suspended instead of
+    /// blocking the thread while the Cell is locked by another thread, and it is woken up when
+    /// the Cell is released. Entries being relocated from the old array are read where they are
+    /// instead of being relocated, as relocating them blocks the thread.
     ///
-    /// let result = hashmap.get(&1);
-    /// assert!(result.is_none());
+    /// # Errors
     ///
-    /// let result = hashmap.get(&2);
-    /// assert!(result.is_none());
+    /// Returns None if the key does not exist.
+    ///
+    /// # Examples
     /// ```
-    pub fn clear(&self) -> usize {
-        self.retain(|_, _| false).1
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    ///
+    /// let future_read = hashmap.read_async(&1, |_, value| *value);
+    /// ```
+    pub async fn read_async<Q, R, F: FnOnce(&Q, &V) -> R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (hash, _) = self.hash(key);
+        let mut eq = |k: &K| k.borrow() == key;
+        loop {
+            let mut async_wait = AsyncWait::default();
+            {
+                let guard = crate::ebr::pin();
+                let result = self.try_read_entry(hash, &mut eq, &mut async_wait, &guard);
+                if let Ok(found) = result {
+                    return found.map(|(_reader, (k, v))| f(k.borrow(), v));
+                }
+            }
+            async_wait.await;
+        }
     }
 
-    /// Returns the number of entries in the HashMap.
+    /// Reads a copy of a key-value pair after releasing the Cell.
     ///
-    /// It scans the entire metadata cell array to calculate the number of valid entries,
-    /// making its time complexity O(N).
-    /// Apart from being inefficient, it may return a smaller number when the HashMap is being resized.
+    /// The key-value pair is copied out of the Cell while the Cell is shared-locked, and then `f`
+    /// is invoked with the copy after the Cell is released, therefore `f` never blocks writers
+    /// even if it takes long. The copy is never torn, however the key-value pair may have been
+    /// updated or removed by the time `f` is invoked.
+    ///
+    /// # Errors
+    ///
+    /// Returns None if the key does not exist.
     ///
     /// # Examples
     /// ```
@@ -589,34 +1525,125 @@ where
     ///
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
-    /// let result = hashmap.insert(1, 0);
-    /// assert!(result.is_ok());
+    /// assert!(hashmap.insert(1, 0).is_ok());
     ///
-    /// let result = hashmap.len();
-    /// assert_eq!(result, 1);
+    /// let result = hashmap.read_optimistic(&1, |key, value| *value);
+    /// assert_eq!(result.unwrap(), 0);
+    /// assert!(hashmap.read_optimistic(&2, |_, _| ()).is_none());
     /// ```
-    pub fn len(&self) -> usize {
-        self.num_entries()
+    pub fn read_optimistic<Q, R, F: FnOnce(&Q, &V) -> R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q> + Copy,
+        V: Copy,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (hash, _) = self.hash(key);
+        let (k, v) = self.read_entry(hash, |k| k.borrow() == key, |k, v| (*k, *v))?;
+        Some(f(k.borrow(), &v))
     }
 
-    /// Returns the capacity of the HashMap.
+    /// Reads multiple key-value pairs, taking shared access to each Cell only once.
+    ///
+    /// The given keys are grouped by the Cell that they belong to, and `f` is invoked with each
+    /// key and the value associated with it, or `None` if the key does not exist. `f` is invoked
+    /// in the order of Cells rather than in the order of the given keys; keys that do not exist
+    /// are passed to `f` last. `f` is invoked while the Cell is shared-locked, therefore it must
+    /// not modify the HashMap.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// for key in 0..4 {
+    ///     assert!(hashmap.insert(key, key as u32).is_ok());
+    /// }
+    ///
+    /// let mut sum = 0;
+    /// let mut num_missing = 0;
+    /// hashmap.get_multi(&[0, 1, 2, 3, 4], |_, value| match value {
+    ///     Some(value) => sum += *value,
+    ///     None => num_missing += 1,
+    /// });
+    /// assert_eq!(sum, 6);
+    /// assert_eq!(num_missing, 1);
+    /// ```
+    pub fn get_multi<'k, I, F>(&self, keys: I, mut f: F)
+    where
+        K: 'k,
+        I: IntoIterator<Item = &'k K>,
+        F: FnMut(&K, Option<&V>),
+    {
+        let mut pending: Vec<(u64, &K)> = keys
+            .into_iter()
+            .map(|key| (self.hash(key).0, key))
+            .collect();
+        let guard = crate::ebr::pin();
+
+        // An acquire fence is required to correctly load the contents of the array.
+        let mut current_array_shared = self.array.load(Acquire, &guard);
+        while !pending.is_empty() {
+            let current_array_ref = Self::cell_array_ref(current_array_shared);
+            let old_array_shared = current_array_ref.old_array(&guard);
+            if !old_array_shared.is_null() {
+                let old_array_ref = Self::cell_array_ref(old_array_shared);
+                Self::read_multi(old_array_ref, &mut pending, &mut f, &guard);
+            }
+            Self::read_multi(current_array_ref, &mut pending, &mut f, &guard);
+            let new_current_array_shared = self.array.load(Acquire, &guard);
+            if new_current_array_shared == current_array_shared {
+                break;
+            }
+            // The pointer value has changed.
+            current_array_shared = new_current_array_shared;
+        }
+        for (_, key) in pending {
+            f(key, None);
+        }
+    }
+
+    /// Reads a key-value pair using a precomputed hash value and an equivalence predicate.
+    ///
+    /// `hash` must be the value that the [`BuildHasher`] of the HashMap produces for the key,
+    /// since the HashMap uses the [`BuildHasher`] to relocate the key when it is resized, and
+    /// `eq` must be consistent with the [`Eq`] implementation of the key type. It is useful when
+    /// the hash value of a key is known in advance, e.g., the key is an interned identifier
+    /// hashed by an identity [`BuildHasher`].
     ///
     /// # Examples
     /// ```
     /// use scc::HashMap;
     /// use std::collections::hash_map::RandomState;
+    /// use std::hash::{BuildHasher, Hash, Hasher};
     ///
-    /// let hashmap: HashMap<u64, u32, RandomState> = HashMap::new(1000000, RandomState::new());
-    /// assert_eq!(hashmap.capacity(), 1048576);
+    /// let build_hasher = RandomState::new();
+    /// let hashmap: HashMap<u64, u32, RandomState> = HashMap::new(64, build_hasher.clone());
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let mut hasher = build_hasher.build_hasher();
+    /// 1_u64.hash(&mut hasher);
+    /// let hash = hasher.finish();
+    ///
+    /// let result = hashmap.read_with_hash(hash, |key| *key == 1, |_, value| *value);
+    /// assert_eq!(result.unwrap(), 0);
     /// ```
-    pub fn capacity(&self) -> usize {
-        self.num_slots()
+    pub fn read_with_hash<R, E: FnMut(&K) -> bool, F: FnOnce(&K, &V) -> R>(
+        &self,
+        hash: u64,
+        eq: E,
+        f: F,
+    ) -> Option<R> {
+        let (hash, _) = self.finalize(hash);
+        self.read_entry(hash, eq, f)
     }
 
-    /// Returns an Accessor.
+    /// Checks if the key exists.
     ///
-    /// It is guaranteed to go through all the key-value pairs pertaining in the HashMap at the moment,
-    /// however the same key-value pair can be visited more than once if the HashMap is being resized.
+    /// It only takes shared access to the Cell that may contain the key.
     ///
     /// # Examples
     /// ```
@@ -624,315 +1651,224 @@ where
     ///
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
+    /// let result = hashmap.contains(&1);
+    /// assert!(!result);
+    ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
+    /// if let Ok(mut result) = result {
     ///     assert_eq!(result.get(), (&1, &mut 0));
     /// }
     ///
-    /// let mut iter = hashmap.iter();
-    /// assert_eq!(iter.next(), Some((&1, &mut 0)));
-    /// assert_eq!(iter.next(), None);
-    ///
-    /// for iter in hashmap.iter() {
-    ///     assert_eq!(iter, (&1, &mut 0));
-    /// }
+    /// let result = hashmap.contains(&1);
+    /// assert!(result);
     /// ```
-    pub fn iter(&self) -> Accessor<K, V, H> {
-        Accessor {
-            hash_map: &self,
-            array_ptr: std::ptr::null(),
-            cell_index: 0,
-            cell_locker: None,
-            cell_iterator: None,
-            guard: None,
-        }
-    }
-
-    /// Locks a Cell for inserting a new key-value pair.
-    fn lock(&self, key: K) -> (Accessor<K, V, H>, K, u8) {
-        let (hash, partial_hash) = self.hash(&key);
-        let mut resize_triggered = false;
-        loop {
-            let accessor = self.acquire(&key, hash, partial_hash);
-            if !resize_triggered
-                && accessor.cell_index < CELL_SIZE
-                && accessor
-                    .cell_locker
-                    .as_ref()
-                    .unwrap()
-                    .cell_ref()
-                    .num_entries()
-                    >= CELL_SIZE
-            {
-                drop(accessor);
-                resize_triggered = true;
-                let guard = crossbeam_epoch::pin();
-                let current_array = self.array.load(Acquire, &guard);
-                let current_array_ref = Self::cell_array_ref(current_array);
-                if current_array_ref.old_array(&guard).is_null() {
-                    // Triggers resize if the estimated load factor is greater than 7/8.
-                    let sample_size = current_array_ref.sample_size();
-                    let threshold = sample_size * (CELL_SIZE / 8) * 7;
-                    let mut num_entries = 0;
-                    for i in 0..sample_size {
-                        num_entries += current_array_ref.cell(i).num_entries();
-                        if num_entries > threshold {
-                            self.resize(&guard);
-                            break;
-                        }
-                    }
-                }
-                continue;
-            }
-            return (accessor, key, partial_hash);
-        }
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.read(key, |_, _| ()).is_some()
     }
 
-    /// Acquires a cell.
-    fn acquire<'h, Q>(&'h self, key: &Q, hash: u64, partial_hash: u8) -> Accessor<'h, K, V, H>
+    /// Checks if the key exists without blocking the thread.
+    ///
+    /// It is an asynchronous counterpart of [`HashMap::contains`] that takes shared access to
+    /// the Cell in the same manner as [`HashMap::read_async`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let future_contains = hashmap.contains_async(&1);
+    /// ```
+    pub async fn contains_async<Q>(&self, key: &Q) -> bool
     where
         K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Eq + Hash + ?Sized,
     {
-        // The proper guard is used to read the array pointer.
-        let guard = crossbeam_epoch::pin();
-        // Once a Cell is locked, protection is not required.
-        let unprotected_guard = unsafe { crossbeam_epoch::unprotected() };
+        self.read_async(key, |_, _| ()).await.is_some()
+    }
 
-        // It is guaranteed that the thread reads a consistent snapshot of the current and
-        // old array pair by a release fence in the resize function, hence the following
-        // procedure is correct.
-        //  - The thread reads self.array, and it kills the target cell in the old array
-        //    if there is one attached to it, and inserts the key into array.
-        // There are two cases.
-        //  1. The thread reads an old version of self.array.
-        //    If there is another thread having read the latest version of self.array,
-        //    trying to insert the same key, it will try to kill the Cell in the old version
-        //    of self.array, thus competing with each other.
-        //  2. The thread reads the latest version of self.array.
-        //    If the array is deprecated while inserting the key, it falls into case 1.
+    /// Finds any key-value pair that satisfies the given predicate, and reads it.
+    ///
+    /// It scans the HashMap from a randomly chosen Cell, and stops at the first key-value pair
+    /// that satisfies the predicate. It takes shared access to at most a single Cell at a time,
+    /// and the Cell remains shared-locked while the given closure is being invoked.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.any(|_, value| *value == 1, |key, _| *key);
+    /// assert!(result.is_none());
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let result = hashmap.insert(2, 1);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let result = hashmap.any(|_, value| *value == 1, |key, _| *key);
+    /// assert_eq!(result, Some(2));
+    /// ```
+    pub fn any<P: Fn(&K, &V) -> bool, R, F: FnOnce(&K, &V) -> R>(
+        &self,
+        pred: P,
+        f: F,
+    ) -> Option<R> {
+        let guard = crate::ebr::pin();
+        let start_index = RandomState::new().build_hasher().finish() as usize;
+
+        // The old array is scanned first, and then the current array is scanned, so that no
+        // key-value pairs being relocated can be missed.
+        let mut current_array_shared = self.array.load(Acquire, &guard);
         loop {
-            // An acquire fence is required to correctly load the contents of the array.
-            let current_array = self.array.load(Acquire, &guard);
-            let current_array_ref = unsafe { &*current_array.as_raw() };
-            let old_array = current_array_ref.old_array(&guard);
-            if !old_array.is_null() {
-                if current_array_ref.partial_rehash(|key| self.hash(key), |_, _| None, &guard) {
-                    continue;
-                }
-                let old_array_ref = unsafe { &*old_array.as_raw() };
-                let cell_index = old_array_ref.calculate_cell_index(hash);
-                if let Some(mut locker) =
-                    CellLocker::lock(old_array_ref.cell(cell_index), unprotected_guard)
+            let current_array_ref = Self::cell_array_ref(current_array_shared);
+            let old_array_shared = current_array_ref.old_array(&guard);
+            if !old_array_shared.is_null() {
+                let old_array_ref = Self::cell_array_ref(old_array_shared);
+                if let Some((_reader, (key, value))) =
+                    Self::find_any(old_array_ref, start_index, &pred, &guard)
                 {
-                    if let Some(iterator) =
-                        locker.cell_ref().get(key, partial_hash, unprotected_guard)
-                    {
-                        let iterator = Some(unsafe {
-                            std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false>>(
-                                iterator,
-                            )
-                        });
-                        return Accessor {
-                            hash_map: &self,
-                            array_ptr: old_array.as_raw(),
-                            cell_index,
-                            cell_locker: Some(locker),
-                            cell_iterator: iterator,
-                            guard: None,
-                        };
-                    }
-                    // Kills the Cell.
-                    current_array_ref.kill_cell(
-                        &mut locker,
-                        Self::cell_array_ref(old_array),
-                        cell_index,
-                        &|key| self.hash(key),
-                        &|_, _| None,
-                        &guard,
-                    );
+                    return Some(f(key, value));
                 }
             }
-            let cell_index = current_array_ref.calculate_cell_index(hash);
-            if let Some(locker) =
-                CellLocker::lock(current_array_ref.cell(cell_index), unprotected_guard)
+            if let Some((_reader, (key, value))) =
+                Self::find_any(current_array_ref, start_index, &pred, &guard)
             {
-                if let Some(iterator) = locker.cell_ref().get(key, partial_hash, unprotected_guard)
-                {
-                    let iterator = Some(unsafe {
-                        std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false>>(iterator)
-                    });
-                    return Accessor {
-                        hash_map: &self,
-                        array_ptr: current_array.as_raw(),
-                        cell_index,
-                        cell_locker: Some(locker),
-                        cell_iterator: iterator,
-                        guard: None,
-                    };
-                }
-                return Accessor {
-                    hash_map: &self,
-                    array_ptr: current_array.as_raw(),
-                    cell_index,
-                    cell_locker: Some(locker),
-                    cell_iterator: None,
-                    guard: None,
-                };
+                return Some(f(key, value));
             }
-
-            // Reaching here indicates that self.array is updated.
+            let new_current_array_shared = self.array.load(Acquire, &guard);
+            if new_current_array_shared == current_array_shared {
+                break;
+            }
+            // The pointer value has changed.
+            current_array_shared = new_current_array_shared;
         }
+        None
     }
 
-    /// Erases a key-value pair owned by the Accessor.
-    fn erase<'h>(&'h self, mut accessor: Accessor<'h, K, V, H>) -> V {
-        let mut iterator = accessor.cell_iterator.take().unwrap();
-        let value = accessor
-            .cell_locker
-            .as_ref()
-            .unwrap()
-            .erase(&mut iterator)
-            .unwrap()
-            .1;
-        if accessor
-            .cell_locker
-            .as_ref()
-            .unwrap()
-            .cell_ref()
-            .num_entries()
-            == 0
-            && accessor.cell_index < CELL_SIZE
-        {
-            drop(accessor);
-            let guard = crossbeam_epoch::pin();
-            let current_array = self.array.load(Acquire, &guard);
-            let current_array_ref = Self::cell_array_ref(current_array);
-            if current_array_ref.old_array(&guard).is_null()
-                && current_array_ref.num_cell_entries() > self.minimum_capacity()
-            {
-                // Triggers resize if the estimated load factor is smaller than 1/16.
-                let sample_size = current_array_ref.sample_size();
-                let mut num_entries = 0;
-                for i in 0..sample_size {
-                    num_entries += current_array_ref.cell(i).num_entries();
-                    if num_entries >= sample_size * CELL_SIZE / 16 {
-                        return value;
+    /// Retains the key-value pairs that satisfy the given predicate.
+    ///
+    /// It returns the number of entries remaining and removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// if let Ok(mut result) = result {
+    ///     assert_eq!(result.get(), (&1, &mut 0));
+    /// }
+    ///
+    /// let result = hashmap.insert(2, 0);
+    /// if let Ok(mut result) = result {
+    ///     assert_eq!(result.get(), (&2, &mut 0));
+    /// }
+    ///
+    /// let result = hashmap.retain(|key, value| *key == 1 && *value == 0);
+    /// assert_eq!(result, (1, 1));
+    ///
+    /// let result = hashmap.get(&1);
+    /// assert_eq!(result.unwrap().get(), (&1, &mut 0));
+    ///
+    /// let result = hashmap.get(&2);
+    /// assert!(result.is_none());
+    /// ```
+    pub fn retain<F: Fn(&K, &mut V) -> bool>(&self, f: F) -> (usize, usize) {
+        self.prune(f, |_, _| ())
+    }
+
+    /// Retains the key-value pairs that satisfy the given predicate, and moves the others out.
+    ///
+    /// Each key-value pair failing the predicate is removed and handed over to `sink` exactly
+    /// once, even if the HashMap is being resized. `sink` is invoked while the Cell is locked,
+    /// therefore it must not access the HashMap. The predicate may be invoked more than once for
+    /// a retained key-value pair if the HashMap is being resized.
+    ///
+    /// It returns the number of entries remaining and removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// for key in 0..4 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    ///
+    /// let mut pruned = Vec::new();
+    /// let result = hashmap.prune(|key, _| *key % 2 == 0, |key, value| pruned.push((key, value)));
+    /// assert_eq!(result, (2, 2));
+    ///
+    /// pruned.sort_unstable();
+    /// assert_eq!(pruned, vec![(1, 0), (3, 0)]);
+    /// ```
+    pub fn prune<P: FnMut(&K, &mut V) -> bool, S: FnMut(K, V)>(
+        &self,
+        mut keep: P,
+        mut sink: S,
+    ) -> (usize, usize) {
+        let guard = crate::ebr::pin();
+        let _batch = RetireBatch::open(&guard);
+        let event_hook = self.event_hook(&guard);
+        let mut retained_entries = 0;
+
+        // Events are emitted after all the Cells are unlocked, even if `keep` or `sink` panics;
+        // the guard is dropped after the Accessor.
+        let mut removed = scopeguard::guard((Vec::new(), 0), |(removed, _removed_entries)| {
+            #[cfg(feature = "metrics")]
+            self.metrics.add(Counter::Removes, _removed_entries);
+            if let Some(event_hook) = event_hook {
+                let mut deferred_panic = DeferredPanic::default();
+                for (key, value) in removed.iter() {
+                    deferred_panic.run(|| (event_hook.hook)(Event::Removed(key, value)));
+                }
+                deferred_panic.propagate();
+            }
+        });
+        let mut accessor = self.iter();
+        while let Some((key, value)) = accessor.next() {
+            if !keep(key, value) {
+                removed.1 += 1;
+                if let Some((key, value)) = accessor
+                    .cell_locker
+                    .as_ref()
+                    .unwrap()
+                    .erase(accessor.cell_iterator.as_mut().unwrap())
+                {
+                    if let Some(event_hook) = event_hook {
+                        removed.0.push((event_hook.cloner)(&key, &value));
                     }
+                    sink(key, value);
                 }
-                self.resize(&guard);
+            } else {
+                retained_entries += 1;
             }
         }
-        value
-    }
-
-    /// Returns a reference to the entry.
-    fn entry<'h>(&'h self, entry_ptr: *const (K, V)) -> (&'h K, &'h mut V) {
-        unsafe {
-            let key_ptr = &(*entry_ptr).0 as *const K;
-            let value_ptr = &(*entry_ptr).1 as *const V;
-            let value_mut_ptr = value_ptr as *mut V;
-            (&(*key_ptr), &mut (*value_mut_ptr))
-        }
-    }
-}
+        drop(accessor);
+        let removed_entries = removed.1;
+        drop(removed);
 
-impl<K, V, H> Drop for HashMap<K, V, H>
-where
-    K: Eq + Hash + Sync,
-    V: Sync,
-    H: BuildHasher,
-{
-    fn drop(&mut self) {
-        // The HashMap has become unreachable, therefore pinning is unnecessary.
-        self.clear();
-        let guard = unsafe { crossbeam_epoch::unprotected() };
-        let current_array = self.array.load(Acquire, guard);
+        let current_array = self.array.load(Acquire, &guard);
         let current_array_ref = Self::cell_array_ref(current_array);
-        current_array_ref.drop_old_array(true, guard);
-        let array = self.array.swap(Shared::null(), Relaxed, guard);
-        if !array.is_null() {
-            drop(unsafe { array.into_owned() });
+        if retained_entries <= current_array_ref.num_cell_entries() / 8 {
+            self.resize(&guard);
         }
-    }
-}
-
-impl<K, V, H> HashTable<K, V, H, CELL_SIZE, false> for HashMap<K, V, H>
-where
-    K: Eq + Hash + Sync,
-    V: Sync,
-    H: BuildHasher,
-{
-    fn hasher(&self) -> &H {
-        &self.build_hasher
-    }
-    fn cell_array_ptr(&self) -> &Atomic<CellArray<K, V, CELL_SIZE, false>> {
-        &self.array
-    }
-    fn minimum_capacity(&self) -> usize {
-        self.minimum_capacity + self.additional_capacity.load(Relaxed)
-    }
-    fn resizing_flag_ref(&self) -> &AtomicBool {
-        &self.resizing_flag
-    }
-}
-
-/// Ticket keeps the increased minimum capacity of the HashMap during its lifetime.
-///
-/// The minimum capacity is lowered when the Ticket is dropped, thereby allowing unused space to be reclaimed.
-pub struct Ticket<'h, K, V, H>
-where
-    K: Eq + Hash + Sync,
-    V: Sync,
-    H: BuildHasher,
-{
-    hash_map: &'h HashMap<K, V, H>,
-    increment: usize,
-}
 
-impl<'h, K, V, H> Drop for Ticket<'h, K, V, H>
-where
-    K: Eq + Hash + Sync,
-    V: Sync,
-    H: BuildHasher,
-{
-    fn drop(&mut self) {
-        let result = self
-            .hash_map
-            .additional_capacity
-            .fetch_sub(self.increment, Relaxed);
-        let guard = crossbeam_epoch::pin();
-        self.hash_map.resize(&guard);
-        debug_assert!(result >= self.increment);
+        (retained_entries, removed_entries)
     }
-}
-
-/// Accessor owns a key-value pair in the HashMap, and it implements Iterator.
-///
-/// It is !Send, thus disallowing other threads to have references to it.
-/// It acquires an exclusive lock on the Cell managing the key.
-/// A thread having multiple Accessor instances poses a possibility of deadlock.
-pub struct Accessor<'h, K, V, H>
-where
-    K: Eq + Hash + Sync,
-    V: Sync,
-    H: BuildHasher,
-{
-    hash_map: &'h HashMap<K, V, H>,
-    array_ptr: *const CellArray<K, V, CELL_SIZE, false>,
-    cell_index: usize,
-    cell_locker: Option<CellLocker<'h, K, V, CELL_SIZE, false>>,
-    cell_iterator: Option<CellIterator<'h, K, V, CELL_SIZE, false>>,
-    guard: Option<&'h Guard>,
-}
 
-impl<'h, K, V, H> Accessor<'h, K, V, H>
-where
-    K: Eq + Hash + Sync,
-    V: Sync,
-    H: BuildHasher,
-{
-    /// Returns a reference to the key-value pair.
+    /// Clears all the key-value pairs.
     ///
     /// # Examples
     /// ```
@@ -940,25 +1876,89 @@ where
     ///
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
-    /// let result = hashmap.get(&1);
-    /// assert!(result.is_none());
-    ///
     /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
+    /// if let Ok(mut result) = result {
     ///     assert_eq!(result.get(), (&1, &mut 0));
-    ///     (*result.get().1) = 2;
     /// }
     ///
+    /// let result = hashmap.insert(2, 0);
+    /// if let Ok(mut result) = result {
+    ///     assert_eq!(result.get(), (&2, &mut 0));
+    /// }
+    ///
+    /// let result = hashmap.clear();
+    /// assert_eq!(result, 2);
+    ///
     /// let result = hashmap.get(&1);
-    /// assert_eq!(result.unwrap().get(), (&1, &mut 2));
+    /// assert!(result.is_none());
+    ///
+    /// let result = hashmap.get(&2);
+    /// assert!(result.is_none());
     /// ```
-    pub fn get(&self) -> (&'h K, &'h mut V) {
-        let itr_ref = self.cell_iterator.as_ref().unwrap();
-        let entry_ref = itr_ref.get().unwrap();
-        self.hash_map.entry(entry_ref as *const _)
+    pub fn clear(&self) -> usize {
+        self.clear_with(|_, _| ())
     }
 
-    /// Erases the key-value pair owned by the Accessor.
+    /// Clears all the key-value pairs, and passes each removed key-value pair to `visitor`.
+    ///
+    /// `visitor` is invoked after the Cell is unlocked, right before the key-value pair is
+    /// dropped.
+    pub(crate) fn clear_with<F: FnMut(&K, &V)>(&self, mut visitor: F) -> usize {
+        let guard = crate::ebr::pin();
+        let _batch = RetireBatch::open(&guard);
+        let mut num_removed = 0;
+        // All the Cells are cleared even if `visitor` or the event hook panics.
+        let mut deferred_panic = DeferredPanic::default();
+        let mut current_array_shared = self.array.load(Acquire, &guard);
+        loop {
+            // Cells in the old array are cleared first, so that no key-value pairs being
+            // relocated can be missed.
+            let current_array_ref = Self::cell_array_ref(current_array_shared);
+            let old_array_shared = current_array_ref.old_array(&guard);
+            if !old_array_shared.is_null() {
+                let old_array_ref = Self::cell_array_ref(old_array_shared);
+                for cell_index in 0..old_array_ref.array_size() {
+                    num_removed += self.clear_cell(
+                        old_array_ref,
+                        cell_index,
+                        &mut visitor,
+                        &mut deferred_panic,
+                        &guard,
+                    );
+                }
+            }
+            for cell_index in 0..current_array_ref.array_size() {
+                num_removed += self.clear_cell(
+                    current_array_ref,
+                    cell_index,
+                    &mut visitor,
+                    &mut deferred_panic,
+                    &guard,
+                );
+            }
+            let new_current_array_shared = self.array.load(Acquire, &guard);
+            if new_current_array_shared == current_array_shared {
+                break;
+            }
+            // The pointer value has changed.
+            current_array_shared = new_current_array_shared;
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.add(Counter::Removes, num_removed);
+        self.resize(&guard);
+        deferred_panic.propagate();
+        num_removed
+    }
+
+    /// Clears up to the given number of Cells, and returns whether work remains.
+    ///
+    /// The cost of clearing the HashMap can be spread over multiple calls; each call locks at
+    /// most `budget` Cells, and at least a single Cell, in turn. The cleared key-value pairs are
+    /// dropped by the calling thread after each Cell is unlocked, as in [`HashMap::clear`]. A
+    /// shared cursor is kept in the current array, therefore concurrent calls clear disjoint sets
+    /// of Cells, and a new pass starts if the array is replaced. It returns `false` once a full
+    /// pass over the HashMap has been completed, and the HashMap is then shrunk if possible;
+    /// key-value pairs inserted in the meantime may remain.
     ///
     /// # Examples
     /// ```
@@ -966,48 +1966,2361 @@ where
     ///
     /// let hashmap: HashMap<u64, u32> = Default::default();
     ///
-    /// let result = hashmap.insert(1, 0);
-    /// if let Ok(result) = result {
-    ///     assert_eq!(result.get(), (&1, &mut 0));
-    ///     let result = result.erase();
-    ///     assert_eq!(result.unwrap(), 0);
+    /// for key in 0..256 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
     /// }
     ///
-    /// let result = hashmap.get(&1);
-    /// assert!(result.is_none());
+    /// while hashmap.clear_chunked(4) {}
+    /// assert_eq!(hashmap.len(), 0);
     /// ```
-    pub fn erase(self) -> Option<V> {
-        if self.cell_iterator.is_none() {
-            return None;
+    pub fn clear_chunked(&self, budget: usize) -> bool {
+        let guard = crate::ebr::pin();
+        let _batch = RetireBatch::open(&guard);
+        let mut budget = budget.max(1);
+        let current_array_shared = self.array.load(Acquire, &guard);
+        let current_array_ref = Self::cell_array_ref(current_array_shared);
+        while !current_array_ref.old_array(&guard).is_null() {
+            // Each call to partial_rehash locks at most CELL_SIZE Cells in the old array.
+            current_array_ref.partial_rehash(|key| self.hash(key), |_, _| None, CELL_SIZE, &guard);
+            if budget <= CELL_SIZE {
+                return true;
+            }
+            budget -= CELL_SIZE;
         }
-        Some(self.hash_map.erase(self))
-    }
-}
 
-impl<'h, K, V, H> Iterator for Accessor<'h, K, V, H>
-where
-    K: Eq + Hash + Sync,
-    V: Sync,
-    H: BuildHasher,
-{
-    type Item = (&'h K, &'h mut V);
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.guard.is_none() {
-            // It always owns a CellLocker preventing the array from being dropped,
-            // therefore a dummy Guard is sufficient.
-            self.guard
-                .replace(unsafe { crossbeam_epoch::unprotected() });
+        let claimed = current_array_ref.claim_cells_to_clear(budget);
+        let end_index = claimed.end;
+        // The claimed Cells are cleared even if the event hook panics.
+        let mut deferred_panic = DeferredPanic::default();
+        for cell_index in claimed {
+            let _num_removed = self.clear_cell(
+                current_array_ref,
+                cell_index,
+                &mut |_, _| (),
+                &mut deferred_panic,
+                &guard,
+            );
+            #[cfg(feature = "metrics")]
+            self.metrics.add(Counter::Removes, _num_removed);
+        }
+        deferred_panic.propagate();
+        if end_index < current_array_ref.array_size()
+            || current_array_shared != self.array.load(Acquire, &guard)
+        {
+            // The pass continues, or starts over in the new array if the array was replaced.
+            return true;
+        }
 
-            // A valid Guard is required to load Arrays.
-            let guard = crossbeam_epoch::pin();
-            loop {
-                let current_array = self.hash_map.array.load(Acquire, &guard);
-                let current_array_ref = unsafe { &*current_array.as_raw() };
-                let old_array = current_array_ref.old_array(&guard);
-                if !old_array.is_null() {
-                    let old_array_ref = unsafe { &*old_array.as_raw() };
-                    for index in 0..old_array_ref.array_size() {
-                        if let Some(locker) = CellLocker::lock(
+        // A full pass has been completed, and the next call starts a new pass.
+        current_array_ref.restart_clearing(end_index);
+        self.resize(&guard);
+        false
+    }
+
+    /// Returns the number of entries in the HashMap.
+    ///
+    /// It scans the entire metadata cell array to calculate the number of valid entries,
+    /// making its time complexity O(N).
+    /// Apart from being inefficient, it may return a smaller number when the HashMap is being resized.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    ///
+    /// let result = hashmap.len();
+    /// assert_eq!(result, 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.num_entries()
+    }
+
+    /// Estimates the number of entries in the HashMap by sampling Cells.
+    ///
+    /// The number of Cells to sample and the way Cells are chosen are configured by
+    /// [`HashMap::with_len_sampling`]. All the Cells are inspected if the number of Cells to
+    /// sample is not smaller than the number of Cells in the HashMap.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// assert_eq!(hashmap.estimated_len(), 1);
+    /// ```
+    pub fn estimated_len(&self) -> usize {
+        let guard = crate::ebr::pin();
+        let current_array = self.array.load(Acquire, &guard);
+        let current_array_ref = Self::cell_array_ref(current_array);
+        let old_array = current_array_ref.old_array(&guard);
+        let mut estimated_len = self.sample(current_array_ref);
+        if !old_array.is_null() {
+            estimated_len += self.sample(Self::cell_array_ref(old_array));
+        }
+        estimated_len
+    }
+
+    /// Returns the statistics of the HashMap.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let statistics = hashmap.statistics();
+    /// assert_eq!(statistics.capacity(), 64);
+    /// assert_eq!(statistics.estimated_len(), 1);
+    /// assert_eq!(statistics.len_sampling_cells(), 64);
+    /// assert!(statistics.len_sampling_deterministic());
+    /// ```
+    pub fn statistics(&self) -> Statistics {
+        Statistics {
+            capacity: self.capacity(),
+            estimated_len: self.estimated_len(),
+            len_sampling_cells: self.len_sampling_cells,
+            len_sampling_deterministic: self.len_sampling_deterministic,
+        }
+    }
+
+    /// Returns a snapshot of the operation counters of the HashMap.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// assert!(hashmap.insert(1, 1).is_err());
+    /// assert_eq!(hashmap.remove(&1), Some(0));
+    ///
+    /// let metrics = hashmap.metrics();
+    /// assert_eq!(metrics.inserts(), 1);
+    /// assert_eq!(metrics.failed_inserts(), 1);
+    /// assert_eq!(metrics.removes(), 1);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.snapshot()
+    }
+
+    /// Resets the operation counters of the HashMap.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// hashmap.reset_metrics();
+    /// assert_eq!(hashmap.metrics().inserts(), 0);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&self) {
+        self.metrics.reset();
+        self.reset_group_metrics();
+    }
+
+    /// Returns a snapshot of the counters of each group of Cells in the current array.
+    ///
+    /// The Cells are divided into groups of 1024 Cells, and each group counts the lock
+    /// contentions, the long probes, and the old array hits of the operations that lock a Cell
+    /// in the group for modification, e.g., insertions. The counters belong to the current
+    /// array, and therefore start from zero whenever the array is replaced.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{GroupMetrics, HashMap};
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashmap: HashMap<u64, u32, RandomState> = HashMap::new(1 << 16, RandomState::new());
+    ///
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    ///
+    /// let groups = hashmap.metrics_by_group();
+    /// assert_eq!(groups.len(), 2);
+    /// assert_eq!(groups[1].first_cell(), 1024);
+    /// assert_eq!(groups.iter().map(GroupMetrics::num_cells).sum::<usize>() * 32, 1 << 16);
+    /// assert_eq!(groups.iter().map(GroupMetrics::old_array_hits).sum::<usize>(), 0);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn metrics_by_group(&self) -> Vec<GroupMetrics> {
+        self.group_metrics()
+    }
+
+    /// Sets the event hook that is invoked after each mutation of the HashMap.
+    ///
+    /// The event hook is invoked after the Cell is unlocked, therefore it is allowed to access
+    /// the HashMap. The key-value pair given to the event hook is either the one that has just
+    /// been moved out of the HashMap or a clone, and therefore it may no longer reflect the
+    /// state of the HashMap. Events on the same key emitted by different threads can be
+    /// delivered out of order.
+    ///
+    /// The following events are emitted.
+    /// * [`Event::Inserted`]: [`HashMap::insert`], [`HashMap::emplace`], [`HashMap::upsert`],
+    ///   and [`HashMap::get_or_insert_with`] when the key-value pair is newly inserted; the event
+    ///   is emitted when the returned Accessor is dropped.
+    /// * [`Event::Updated`]: [`HashMap::upsert`] of an existing key, [`HashMap::compare_exchange`],
+    ///   [`HashMap::replace`], and Accessors of which [`Accessor::get`] is called; the event is
+    ///   emitted when the Accessor is dropped.
+    /// * [`Event::Removed`]: [`HashMap::remove`], [`HashMap::remove_if`],
+    ///   [`HashMap::remove_if_result`], [`Accessor::erase`], [`HashMap::retain`],
+    ///   [`HashMap::prune`], [`HashMap::clear`], and [`HashMap::clear_chunked`].
+    /// * [`HashMap::transfer`] emits [`Event::Removed`] and [`Event::Inserted`], and
+    ///   [`HashMap::swap_values`] emits [`Event::Updated`] for each key.
+    ///
+    /// Key-value pairs moved out by [`HashMap::drain`], or visited by [`HashMap::iter`] or
+    /// `par_for_each_mut` are not reported.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{Event, HashMap};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    /// let events = Arc::new(Mutex::new(Vec::new()));
+    /// let events_cloned = events.clone();
+    /// hashmap.set_event_hook(move |event: Event<&u64, &u32>| {
+    ///     let event = match event {
+    ///         Event::Inserted(key, value) => Event::Inserted(*key, *value),
+    ///         Event::Updated(key, value) => Event::Updated(*key, *value),
+    ///         Event::Removed(key, value) => Event::Removed(*key, *value),
+    ///     };
+    ///     events_cloned.lock().unwrap().push(event);
+    /// });
+    ///
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// hashmap.upsert(1, 1);
+    /// assert_eq!(hashmap.remove(&1), Some(1));
+    ///
+    /// assert_eq!(
+    ///     *events.lock().unwrap(),
+    ///     vec![Event::Inserted(1, 0), Event::Updated(1, 1), Event::Removed(1, 1)]
+    /// );
+    /// ```
+    pub fn set_event_hook<F: Fn(Event<&K, &V>) + Send + Sync + 'static>(&self, hook: F)
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let event_hook = Owned::new(EventHook {
+            hook: Box::new(hook),
+            cloner: |key, value| (key.clone(), value.clone()),
+        });
+        let guard = crate::ebr::pin();
+        let old_event_hook = self.event_hook.swap(event_hook, AcqRel, &guard);
+        if !old_event_hook.is_null() {
+            unsafe { guard.defer_destroy(old_event_hook) };
+        }
+    }
+
+    /// Clears the event hook.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// hashmap.set_event_hook(|_| unreachable!());
+    /// hashmap.clear_event_hook();
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// ```
+    pub fn clear_event_hook(&self) {
+        let guard = crate::ebr::pin();
+        let old_event_hook = self.event_hook.swap(Shared::null(), AcqRel, &guard);
+        if !old_event_hook.is_null() {
+            unsafe { guard.defer_destroy(old_event_hook) };
+        }
+    }
+
+    /// Returns the capacity of the HashMap.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashmap: HashMap<u64, u32, RandomState> = HashMap::new(1000000, RandomState::new());
+    /// assert_eq!(hashmap.capacity(), 1048576);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.num_slots()
+    }
+
+    /// Returns a breakdown of the heap memory used by the HashMap.
+    ///
+    /// It visits every Cell in the current and old arrays, therefore it is as costly as
+    /// [`HashMap::len`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default();
+    /// let empty = hashmap.memory_footprint();
+    /// assert_eq!(empty.old_array_bytes(), 0);
+    ///
+    /// for key in 0..4096 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    /// assert!(hashmap.memory_footprint().current_array_bytes() > empty.current_array_bytes());
+    /// ```
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        self.measure_memory()
+    }
+
+    /// Returns the index of the bucket, a Cell in the current array, that the key belongs to.
+    ///
+    /// The key does not have to exist in the HashMap. The bucket index is only stable within an
+    /// array generation: the HashMap moves key-value pairs to different buckets when it is
+    /// resized, and therefore the index has to be used along with
+    /// [`HashMap::array_generation`] in order to detect staleness.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// let generation = hashmap.array_generation();
+    /// let bucket = hashmap.bucket_of(&1);
+    ///
+    /// let mut found = false;
+    /// hashmap.for_each_in_bucket(bucket, |key, _| found |= *key == 1);
+    /// assert!(found || hashmap.array_generation() != generation);
+    /// ```
+    pub fn bucket_of<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (hash, _) = self.hash(key);
+        let guard = crate::ebr::pin();
+        let current_array_ref = Self::cell_array_ref(self.array.load(Acquire, &guard));
+        current_array_ref.calculate_cell_index(hash)
+    }
+
+    /// Invokes the given closure for each key-value pair in the bucket.
+    ///
+    /// Key-value pairs belonging to the bucket that have yet to be relocated from the old array
+    /// are relocated before the bucket is scanned, so that all of them are passed to `f`. `f` is
+    /// invoked while the Cell is shared-locked, therefore it must not modify the HashMap. Nothing
+    /// is passed to `f` if the index is out of the range of the current array.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// for key in 0..4 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    ///
+    /// let mut num_entries = 0;
+    /// hashmap.for_each_in_bucket(hashmap.bucket_of(&0), |_, _| num_entries += 1);
+    /// assert!(num_entries >= 1);
+    /// ```
+    pub fn for_each_in_bucket<F: FnMut(&K, &V)>(&self, index: usize, mut f: F) {
+        let guard = crate::ebr::pin();
+        let current_array_ref = Self::cell_array_ref(self.array.load(Acquire, &guard));
+        let array_size = current_array_ref.array_size();
+        if index >= array_size {
+            return;
+        }
+        // Kills the Cells in the old array of which the key-value pairs belong to the bucket.
+        current_array_ref.relocate_into(index, &|key: &K| self.hash(key), &|_, _| None, &guard);
+        let cell_ref = current_array_ref.cell(index);
+        let reader = CellReader::lock(cell_ref, &guard);
+        if reader.is_some() {
+            for ((key, value), _) in cell_ref.iter(&guard) {
+                f(key, value);
+            }
+        }
+    }
+
+    /// Relocates up to the given number of Cells from the old array, and returns whether work
+    /// remains.
+    ///
+    /// At most 8 key-value pairs are moved for each Cell, so a Cell holding more key-value pairs
+    /// is relocated over several calls. The old array is left behind while the HashMap is being resized, and it is dropped once
+    /// all the Cells in it are relocated. It returns `true` if the old array remains after the
+    /// call, and is meant to be called in a loop by a thread that takes over relocation from
+    /// operations configured with [`HashMap::with_rehash_budget`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default().with_rehash_budget(0);
+    /// assert!(!hashmap.rehash(1));
+    ///
+    /// for key in 0..1024 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    /// while hashmap.rehash(1) {}
+    /// assert!(!hashmap.rehash(1));
+    /// ```
+    pub fn rehash(&self, cells: usize) -> bool {
+        let guard = crate::ebr::pin();
+        let current_array_ref = Self::cell_array_ref(self.array.load(Acquire, &guard));
+        if current_array_ref.old_array(&guard).is_null() {
+            return false;
+        }
+        !current_array_ref.partial_rehash(|key: &K| self.hash(key), |_, _| None, cells, &guard)
+            && !current_array_ref.old_array(&guard).is_null()
+    }
+
+    /// Returns the generation of the current array.
+    ///
+    /// The generation starts from zero, and is increased each time the array is replaced by
+    /// resizing. Bucket indexes obtained from [`HashMap::bucket_of`] are valid only while the
+    /// generation stays the same.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    /// assert_eq!(hashmap.array_generation(), 0);
+    ///
+    /// hashmap.try_reserve(1 << 10).unwrap();
+    /// assert_eq!(hashmap.array_generation(), 1);
+    /// ```
+    pub fn array_generation(&self) -> usize {
+        let guard = crate::ebr::pin();
+        Self::cell_array_ref(self.array.load(Acquire, &guard)).generation()
+    }
+
+    /// Returns `true` if the old array is yet to be dropped.
+    ///
+    /// The old array is dropped once all the Cells in it are relocated to the current array, and
+    /// its memory is reclaimed as soon as no thread may be reading it.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default().with_rehash_budget(0);
+    /// assert!(!hashmap.has_old_array());
+    ///
+    /// hashmap.try_reserve(1 << 16).unwrap();
+    /// assert!(hashmap.has_old_array());
+    ///
+    /// while hashmap.rehash(1) {}
+    /// assert!(!hashmap.has_old_array());
+    /// ```
+    pub fn has_old_array(&self) -> bool {
+        let guard = crate::ebr::pin();
+        !Self::cell_array_ref(self.array.load(Acquire, &guard))
+            .old_array(&guard)
+            .is_null()
+    }
+
+    /// Writes a summary of the HashMap to the given output without formatting any entries.
+    ///
+    /// The summary consists of the capacity, the number of entries, and whether the HashMap is
+    /// being resized.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let mut summary = String::new();
+    /// assert!(hashmap.fmt_summary(&mut summary).is_ok());
+    /// assert_eq!(summary, "HashMap { capacity: 64, len: 1, resizing: false }");
+    /// ```
+    pub fn fmt_summary<W: fmt::Write>(&self, output: &mut W) -> fmt::Result {
+        let resizing = {
+            let guard = crate::ebr::pin();
+            let current_array = self.array.load(Acquire, &guard);
+            !Self::cell_array_ref(current_array)
+                .old_array(&guard)
+                .is_null()
+        };
+        write!(
+            output,
+            "HashMap {{ capacity: {}, len: {}, resizing: {} }}",
+            self.capacity(),
+            self.len(),
+            resizing
+        )
+    }
+
+    /// Converts the HashMap into a [`std::collections::HashMap`] by moving all the entries out.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let std_hashmap = hashmap.into_std();
+    /// assert_eq!(std_hashmap.get(&1), Some(&0));
+    /// ```
+    pub fn into_std(self) -> std::collections::HashMap<K, V> {
+        let mut std_hashmap = std::collections::HashMap::with_capacity(self.len());
+        self.prune(
+            |_, _| false,
+            |key, value| {
+                std_hashmap.insert(key, value);
+            },
+        );
+        std_hashmap
+    }
+
+    /// Returns an Accessor.
+    ///
+    /// It is guaranteed to go through all the key-value pairs pertaining in the HashMap at the moment,
+    /// however the same key-value pair can be visited more than once if the HashMap is being resized.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// if let Ok(mut result) = result {
+    ///     assert_eq!(result.get(), (&1, &mut 0));
+    /// }
+    ///
+    /// let mut iter = hashmap.iter();
+    /// assert_eq!(iter.next(), Some((&1, &mut 0)));
+    /// assert_eq!(iter.next(), None);
+    ///
+    /// for iter in hashmap.iter() {
+    ///     assert_eq!(iter, (&1, &mut 0));
+    /// }
+    /// ```
+    pub fn iter(&self) -> Accessor<'_, K, V, H, A> {
+        Accessor {
+            hash_map: &self,
+            array_ptr: std::ptr::null(),
+            cell_index: 0,
+            cell_locker: None,
+            cell_iterator: None,
+            guard: None,
+            event: std::cell::Cell::new(None),
+        }
+    }
+
+    /// Returns a read-only iterator over clones of the key-value pairs.
+    ///
+    /// See [`Entries`] for the guarantees it provides.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let entries: Vec<(u64, u32)> = hashmap.entries().collect();
+    /// assert_eq!(entries, vec![(1, 0)]);
+    /// ```
+    pub fn entries(&self) -> Entries<'_, K, V, H, A>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        Entries {
+            scanner: ClonedScanner::new(self, |key, value| (key.clone(), value.clone())),
+        }
+    }
+
+    /// Returns a read-only iterator over clones of the keys.
+    ///
+    /// See [`Entries`] for the guarantees it provides.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let keys: Vec<u64> = hashmap.keys().collect();
+    /// assert_eq!(keys, vec![1]);
+    /// ```
+    pub fn keys(&self) -> Keys<'_, K, V, H, A>
+    where
+        K: Clone,
+    {
+        Keys {
+            scanner: ClonedScanner::new(self, |key, _| key.clone()),
+        }
+    }
+
+    /// Returns a read-only iterator over clones of the values.
+    ///
+    /// See [`Entries`] for the guarantees it provides.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// let values: Vec<u32> = hashmap.values().collect();
+    /// assert_eq!(values, vec![0]);
+    /// ```
+    pub fn values(&self) -> Values<'_, K, V, H, A>
+    where
+        V: Clone,
+    {
+        Values {
+            scanner: ClonedScanner::new(self, |_, value| value.clone()),
+        }
+    }
+
+    /// Returns an iterator that moves the key-value pairs out of the HashMap.
+    ///
+    /// It takes exclusive access to a single Cell at a time in the same way as [`HashMap::iter`],
+    /// therefore the HashMap must not be accessed by the thread owning the iterator. The
+    /// key-value pairs that have not been yielded stay in the HashMap when the iterator is
+    /// dropped, and key-value pairs inserted into a Cell that has already been drained also stay.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// for key in 0..4 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    ///
+    /// let mut drained: Vec<(u64, u32)> = hashmap.drain().collect();
+    /// drained.sort_unstable();
+    /// assert_eq!(drained, vec![(0, 0), (1, 0), (2, 0), (3, 0)]);
+    /// assert_eq!(hashmap.len(), 0);
+    /// ```
+    pub fn drain(&self) -> Drain<'_, K, V, H, A> {
+        Drain {
+            accessor: self.iter(),
+            finished: false,
+        }
+    }
+
+    /// Finds a key-value pair satisfying the predicate in the given array, and returns it along
+    /// with the CellReader protecting it.
+    fn find_any<'g, P: Fn(&K, &V) -> bool>(
+        array_ref: &'g CellArray<K, V, CELL_SIZE, false, A>,
+        start_index: usize,
+        pred: &P,
+        guard: &'g Guard,
+    ) -> Option<ReaderEntry<'g, K, V, A>> {
+        let array_size = array_ref.array_size();
+        for offset in 0..array_size {
+            let cell_ref = array_ref.cell((start_index + offset) % array_size);
+            if let Some(reader) = CellReader::lock(cell_ref, guard) {
+                if let Some((entry, _)) = cell_ref
+                    .iter(guard)
+                    .find(|((key, value), _)| pred(key, value))
+                {
+                    return Some((reader, entry));
+                }
+            }
+        }
+        None
+    }
+
+    /// Clears the Cell, and returns the number of removed entries.
+    ///
+    /// The removed entries are passed to `visitor` and dropped after the Cell is unlocked in
+    /// order not to block other threads waiting for the Cell. A panic raised by `visitor` or the
+    /// event hook is kept in `deferred_panic`.
+    fn clear_cell<F: FnMut(&K, &V)>(
+        &self,
+        array_ref: &CellArray<K, V, CELL_SIZE, false, A>,
+        cell_index: usize,
+        visitor: &mut F,
+        deferred_panic: &mut DeferredPanic,
+        guard: &Guard,
+    ) -> usize {
+        let cell_ref = match array_ref.initialized_cell(cell_index) {
+            Some(cell_ref) => cell_ref,
+            None => return 0,
+        };
+        if let Some(mut locker) = CellLocker::lock(cell_ref, guard) {
+            let (data_array, num_entries) = locker.take(guard);
+            drop(locker);
+            // The DataArray is retired even if `visitor` or the event hook panics, and the
+            // other key-value pairs are still visited.
+            if let Some(data_array) = unsafe { data_array.as_ref() } {
+                let event_hook = self.event_hook(guard);
+                data_array.for_each(|key, value| {
+                    deferred_panic.run(|| visitor(key, value));
+                    if let Some(event_hook) = event_hook {
+                        deferred_panic.run(|| (event_hook.hook)(Event::Removed(key, value)));
+                    }
+                });
+            }
+            DataArray::retire(data_array, guard);
+            num_entries
+        } else {
+            0
+        }
+    }
+
+    /// Reads a key-value pair of which the key satisfies the given equivalence predicate.
+    fn read_entry<R, E: FnMut(&K) -> bool, F: FnOnce(&K, &V) -> R>(
+        &self,
+        hash: u64,
+        mut eq: E,
+        f: F,
+    ) -> Option<R> {
+        #[cfg(feature = "metrics")]
+        let _recorder = self.metrics.recorder();
+        let guard = crate::ebr::pin();
+
+        // An acquire fence is required to correctly load the contents of the array.
+        let mut current_array_shared = self.array.load(Acquire, &guard);
+        loop {
+            let current_array_ref = Self::cell_array_ref(current_array_shared);
+            let old_array_shared = current_array_ref.old_array(&guard);
+            if !old_array_shared.is_null()
+                && !current_array_ref.partial_rehash(
+                    |key| self.hash(key),
+                    |_, _| None,
+                    self.rehash_budget,
+                    &guard,
+                )
+            {
+                let old_array_ref = Self::cell_array_ref(old_array_shared);
+                let cell_index = old_array_ref.calculate_cell_index(hash);
+                if let Some(reader) = CellReader::lock(old_array_ref.cell(cell_index), &guard) {
+                    if let Some((key, value)) = reader.cell_ref().search_with(&mut eq, hash, &guard)
+                    {
+                        return Some(f(key, value));
+                    }
+                }
+            }
+            let cell_index = current_array_ref.calculate_cell_index(hash);
+            if let Some(reader) = CellReader::lock(current_array_ref.cell(cell_index), &guard) {
+                if let Some((key, value)) = reader.cell_ref().search_with(&mut eq, hash, &guard) {
+                    return Some(f(key, value));
+                }
+            }
+            let new_current_array_shared = self.array.load(Acquire, &guard);
+            if new_current_array_shared == current_array_shared {
+                break;
+            }
+            // The pointer value has changed.
+            current_array_shared = new_current_array_shared;
+        }
+        None
+    }
+
+    /// Reads a key-value pair of which the key satisfies the given equivalence predicate without
+    /// blocking the thread, and returns it along with the CellReader protecting it.
+    ///
+    /// It returns `Err(())` if a Cell is locked by another thread, in which case `async_wait` is
+    /// registered to be woken up when the Cell is released. The old array is searched without
+    /// being rehashed, as relocating entries blocks the thread.
+    fn try_read_entry<'g, E: FnMut(&K) -> bool>(
+        &self,
+        hash: u64,
+        eq: &mut E,
+        async_wait: &mut AsyncWait,
+        guard: &'g Guard,
+    ) -> Result<Option<ReaderEntry<'g, K, V, A>>, ()> {
+        #[cfg(feature = "metrics")]
+        let _recorder = self.metrics.recorder();
+
+        // An acquire fence is required to correctly load the contents of the array.
+        let mut current_array_shared = self.array.load(Acquire, guard);
+        loop {
+            let current_array_ref = Self::cell_array_ref(current_array_shared);
+            let old_array_shared = current_array_ref.old_array(guard);
+            if !old_array_shared.is_null() {
+                let old_array_ref = Self::cell_array_ref(old_array_shared);
+                let cell_ref = old_array_ref.cell(old_array_ref.calculate_cell_index(hash));
+                if let Some(reader) = CellReader::try_lock_or_wait(cell_ref, async_wait, guard)? {
+                    if let Some(entry) = cell_ref.search_with(&mut *eq, hash, guard) {
+                        return Ok(Some((reader, entry)));
+                    }
+                }
+            }
+            let cell_ref = current_array_ref.cell(current_array_ref.calculate_cell_index(hash));
+            if let Some(reader) = CellReader::try_lock_or_wait(cell_ref, async_wait, guard)? {
+                if let Some(entry) = cell_ref.search_with(&mut *eq, hash, guard) {
+                    return Ok(Some((reader, entry)));
+                }
+            }
+            let new_current_array_shared = self.array.load(Acquire, guard);
+            if new_current_array_shared == current_array_shared {
+                break;
+            }
+            // The pointer value has changed.
+            current_array_shared = new_current_array_shared;
+        }
+        Ok(None)
+    }
+
+    /// Estimates the number of entries in the given array by sampling Cells at a fixed stride.
+    fn sample(&self, array_ref: &CellArray<K, V, CELL_SIZE, false, A>) -> usize {
+        let array_size = array_ref.array_size();
+        let num_cells_to_sample = self.len_sampling_cells.min(array_size);
+        let stride = array_size / num_cells_to_sample;
+        let start_index = if self.len_sampling_deterministic {
+            0
+        } else {
+            RandomState::new().build_hasher().finish() as usize % array_size
+        };
+        let mut num_entries = 0;
+        for i in 0..num_cells_to_sample {
+            num_entries += array_ref
+                .initialized_cell((start_index + i * stride) % array_size)
+                .map_or(0, Cell::num_entries);
+        }
+        num_entries * array_size / num_cells_to_sample
+    }
+
+    /// Reads the pending keys found in the given array, Cell by Cell, and leaves the others.
+    fn read_multi<F: FnMut(&K, Option<&V>)>(
+        array_ref: &CellArray<K, V, CELL_SIZE, false, A>,
+        pending: &mut Vec<(u64, &K)>,
+        f: &mut F,
+        guard: &Guard,
+    ) {
+        pending.sort_unstable_by_key(|(hash, _)| array_ref.calculate_cell_index(*hash));
+        let mut not_found = Vec::with_capacity(pending.len());
+        let mut start = 0;
+        while start < pending.len() {
+            let cell_index = array_ref.calculate_cell_index(pending[start].0);
+            let end = pending[start..]
+                .iter()
+                .position(|(hash, _)| array_ref.calculate_cell_index(*hash) != cell_index)
+                .map_or(pending.len(), |offset| start + offset);
+            let cell_ref = array_ref.cell(cell_index);
+            if let Some(_reader) = CellReader::lock(cell_ref, guard) {
+                for &(hash, key) in &pending[start..end] {
+                    if let Some((_, value)) = cell_ref.search(key, hash, guard) {
+                        f(key, Some(value));
+                    } else {
+                        not_found.push((hash, key));
+                    }
+                }
+            } else {
+                not_found.extend_from_slice(&pending[start..end]);
+            }
+            start = end;
+        }
+        *pending = not_found;
+    }
+
+    /// Scans the HashMap taking shared access to a single Cell at a time until `f` returns
+    /// `false`.
+    ///
+    /// It returns `false` if the scan is stopped by `f`. An entry being relocated can be passed
+    /// to `f` twice.
+    pub(crate) fn scan<F: FnMut(&K, &V) -> bool>(&self, mut f: F) -> bool {
+        let guard = crate::ebr::pin();
+        let current_array_shared = self.array.load(Acquire, &guard);
+        let current_array_ref = Self::cell_array_ref(current_array_shared);
+        let old_array_shared = current_array_ref.old_array(&guard);
+        for array_ref in [old_array_shared, current_array_shared]
+            .iter()
+            .filter(|array_shared| !array_shared.is_null())
+            .map(|array_shared| Self::cell_array_ref(*array_shared))
+        {
+            for cell_index in 0..array_ref.array_size() {
+                let cell_ref = match array_ref.initialized_cell(cell_index) {
+                    Some(cell_ref) => cell_ref,
+                    None => continue,
+                };
+                if let Some(_reader) = CellReader::lock(cell_ref, &guard) {
+                    for ((key, value), _) in cell_ref.iter(&guard) {
+                        if !f(key, value) {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Scans all the keys, passing clones of the keys in a Cell to the given closure after the
+    /// Cell is unlocked.
+    ///
+    /// The closure may therefore access any containers, including the HashMap itself.
+    pub(crate) fn scan_detached<F: FnMut(&[K])>(&self, mut f: F)
+    where
+        K: Clone,
+    {
+        let guard = crate::ebr::pin();
+        let current_array_shared = self.array.load(Acquire, &guard);
+        let current_array_ref = Self::cell_array_ref(current_array_shared);
+        let old_array_shared = current_array_ref.old_array(&guard);
+        let mut keys = Vec::with_capacity(CELL_SIZE);
+        for array_ref in [old_array_shared, current_array_shared]
+            .iter()
+            .filter(|array_shared| !array_shared.is_null())
+            .map(|array_shared| Self::cell_array_ref(*array_shared))
+        {
+            for cell_index in 0..array_ref.array_size() {
+                let cell_ref = match array_ref.initialized_cell(cell_index) {
+                    Some(cell_ref) => cell_ref,
+                    None => continue,
+                };
+                let reader = CellReader::lock(cell_ref, &guard);
+                if reader.is_some() {
+                    keys.extend(cell_ref.iter(&guard).map(|((key, _), _)| key.clone()));
+                }
+                drop(reader);
+                if !keys.is_empty() {
+                    f(&keys);
+                    keys.clear();
+                }
+            }
+        }
+    }
+
+    /// Locks a Cell for inserting a new key-value pair.
+    fn lock(&self, key: K) -> (Accessor<'_, K, V, H, A>, K, u64) {
+        let (hash, _) = self.hash(&key);
+        let mut resize_triggered = false;
+        // A Cell is regarded as full if it contains as many entries as CELL_SIZE, scaled by the
+        // ratio of the maximum load factor to the default maximum load factor.
+        let cell_threshold = ((CELL_SIZE as f32 * self.max_load_factor / DEFAULT_MAX_LOAD_FACTOR)
+            as usize)
+            .min(CELL_SIZE);
+        loop {
+            let accessor = self.acquire(hash, |k| *k == key);
+            if !resize_triggered
+                && accessor.cell_index < CELL_SIZE
+                && accessor
+                    .cell_locker
+                    .as_ref()
+                    .unwrap()
+                    .cell_ref()
+                    .num_entries()
+                    >= cell_threshold
+            {
+                drop(accessor);
+                resize_triggered = true;
+                let guard = crate::ebr::pin();
+                let current_array = self.array.load(Acquire, &guard);
+                let current_array_ref = Self::cell_array_ref(current_array);
+                if current_array_ref.old_array(&guard).is_null() {
+                    // Triggers resize if the estimated load factor is greater than the maximum
+                    // load factor.
+                    let sample_size = current_array_ref.sample_size();
+                    let threshold =
+                        ((sample_size * CELL_SIZE) as f32 * self.max_load_factor) as usize;
+                    let mut num_entries = 0;
+                    for i in 0..sample_size {
+                        num_entries += current_array_ref.cell(i).num_load_entries();
+                        if num_entries > threshold {
+                            self.resize(&guard);
+                            break;
+                        }
+                    }
+                }
+                continue;
+            }
+            return (accessor, key, hash);
+        }
+    }
+
+    /// Locks the Cells for the two hash values in the order of their indexes.
+    ///
+    /// The old array is completely rehashed beforehand, so that the Cells in the current array
+    /// own all the key-value pairs for the hash values. The second CellLocker is `None` if the
+    /// hash values belong to the same Cell.
+    fn lock_pair<'g>(&self, hash_a: u64, hash_b: u64, guard: &'g Guard) -> LockerPair<'g, K, V, A> {
+        loop {
+            let current_array = self.array.load(Acquire, guard);
+            let current_array_ref = Self::cell_array_ref(current_array);
+            if !current_array_ref.old_array(guard).is_null() {
+                if !current_array_ref.partial_rehash(
+                    |key: &K| self.hash(key),
+                    |_, _| None,
+                    CELL_SIZE,
+                    guard,
+                ) {
+                    std::thread::yield_now();
+                }
+                continue;
+            }
+            let index_a = current_array_ref.calculate_cell_index(hash_a);
+            let index_b = current_array_ref.calculate_cell_index(hash_b);
+            let first_locker = if let Some(locker) =
+                CellLocker::lock(current_array_ref.cell(index_a.min(index_b)), guard)
+            {
+                locker
+            } else {
+                // The Cell has been killed as the array is being replaced.
+                continue;
+            };
+            if index_a == index_b {
+                return (first_locker, None);
+            }
+            if let Some(second_locker) =
+                CellLocker::lock(current_array_ref.cell(index_a.max(index_b)), guard)
+            {
+                if index_a < index_b {
+                    return (first_locker, Some(second_locker));
+                }
+                return (second_locker, Some(first_locker));
+            }
+        }
+    }
+
+    /// Acquires a cell.
+    fn acquire<'h, F: FnMut(&K) -> bool>(&'h self, hash: u64, eq: F) -> Accessor<'h, K, V, H, A> {
+        match self.try_acquire(hash, eq, None) {
+            Ok(accessor) => accessor,
+            Err(()) => unreachable!(),
+        }
+    }
+
+    /// Acquires a cell, or registers the task awaiting `async_wait` to be woken up when the
+    /// Cell is released if `async_wait` is given.
+    ///
+    /// It returns `Err(())` if `async_wait` needs to be awaited before retrying. The old array is
+    /// not rehashed if `async_wait` is given, as relocating entries blocks the thread.
+    fn try_acquire<'h, F: FnMut(&K) -> bool>(
+        &'h self,
+        hash: u64,
+        mut eq: F,
+        mut async_wait: Option<&mut AsyncWait>,
+    ) -> Result<Accessor<'h, K, V, H, A>, ()> {
+        #[cfg(feature = "metrics")]
+        let _recorder = self.metrics.recorder();
+        // The proper guard is used to read the array pointer.
+        let guard = crate::ebr::pin();
+        // Once a Cell is locked, protection is not required.
+        let unprotected_guard = unsafe { crate::ebr::unprotected() };
+
+        // It is guaranteed that the thread reads a consistent snapshot of the current and
+        // old array pair by a release fence in the resize function, hence the following
+        // procedure is correct.
+        //  - The thread reads self.array, and it searches the target cell in the old array
+        //    for the key if there is one attached to it, and inserts the key into the current
+        //    array without relocating the entries in the old cell. New keys are never inserted
+        //    into the old array, and the entries being relocated are inserted into the current
+        //    array while the old cell is locked, therefore the key cannot be inserted twice.
+        // There are two cases.
+        //  1. The thread reads an old version of self.array.
+        //    If there is another thread having read the latest version of self.array,
+        //    trying to insert the same key, it will try to kill the Cell in the old version
+        //    of self.array, thus competing with each other.
+        //  2. The thread reads the latest version of self.array.
+        //    If the array is deprecated while inserting the key, it falls into case 1.
+        loop {
+            // An acquire fence is required to correctly load the contents of the array.
+            let current_array = self.array.load(Acquire, &guard);
+            let current_array_ref = unsafe { &*current_array.as_raw() };
+            let old_array = current_array_ref.old_array(&guard);
+            if !old_array.is_null() {
+                if async_wait.is_none()
+                    && current_array_ref.partial_rehash(
+                        |key: &K| self.hash(key),
+                        |_, _| None,
+                        self.rehash_budget,
+                        &guard,
+                    )
+                {
+                    continue;
+                }
+                let old_array_ref = unsafe { &*old_array.as_raw() };
+                let cell_index = old_array_ref.calculate_cell_index(hash);
+                if let Some(locker) = Self::lock_cell(
+                    old_array_ref.cell(cell_index),
+                    async_wait.as_deref_mut(),
+                    unprotected_guard,
+                )? {
+                    let iterator = locker.cell_ref().get_with(&mut eq, hash, unprotected_guard);
+                    #[cfg(feature = "metrics")]
+                    if iterator.is_some() {
+                        current_array_ref.record_group_events(
+                            current_array_ref.calculate_cell_index(hash),
+                            locker.cell_ref(),
+                            true,
+                            unprotected_guard,
+                        );
+                    }
+                    if let Some(iterator) = iterator {
+                        let iterator = Some(unsafe {
+                            std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false, A>>(
+                                iterator,
+                            )
+                        });
+                        return Ok(Accessor {
+                            hash_map: &self,
+                            array_ptr: old_array.as_raw(),
+                            cell_index,
+                            cell_locker: Some(locker),
+                            cell_iterator: iterator,
+                            guard: None,
+                            event: std::cell::Cell::new(None),
+                        });
+                    }
+                    // The Cell is left to partial_rehash, and the key is inserted into the
+                    // current array since it is not in the Cell.
+                }
+            }
+            let cell_index = current_array_ref.calculate_cell_index(hash);
+            if let Some(locker) = Self::lock_cell(
+                current_array_ref.cell(cell_index),
+                async_wait.as_deref_mut(),
+                unprotected_guard,
+            )? {
+                #[cfg(feature = "metrics")]
+                current_array_ref.record_group_events(
+                    cell_index,
+                    locker.cell_ref(),
+                    false,
+                    unprotected_guard,
+                );
+                if let Some(iterator) = locker.cell_ref().get_with(&mut eq, hash, unprotected_guard)
+                {
+                    let iterator = Some(unsafe {
+                        std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false, A>>(
+                            iterator,
+                        )
+                    });
+                    return Ok(Accessor {
+                        hash_map: &self,
+                        array_ptr: current_array.as_raw(),
+                        cell_index,
+                        cell_locker: Some(locker),
+                        cell_iterator: iterator,
+                        guard: None,
+                        event: std::cell::Cell::new(None),
+                    });
+                }
+                return Ok(Accessor {
+                    hash_map: &self,
+                    array_ptr: current_array.as_raw(),
+                    cell_index,
+                    cell_locker: Some(locker),
+                    cell_iterator: None,
+                    guard: None,
+                    event: std::cell::Cell::new(None),
+                });
+            }
+
+            // Reaching here indicates that self.array is updated.
+        }
+    }
+
+    /// Locks the Cell, or registers the task awaiting `async_wait` to be woken up when the Cell is
+    /// released if `async_wait` is given.
+    fn lock_cell<'g>(
+        cell_ref: &'g Cell<K, V, CELL_SIZE, false, A>,
+        async_wait: Option<&mut AsyncWait>,
+        guard: &'g Guard,
+    ) -> Result<Option<CellLocker<'g, K, V, CELL_SIZE, false, A>>, ()> {
+        match async_wait {
+            Some(async_wait) => CellLocker::try_lock_or_wait(cell_ref, async_wait, guard),
+            None => Ok(CellLocker::lock(cell_ref, guard)),
+        }
+    }
+
+    /// Inserts a key-value pair into the Cell locked by the Accessor if the key does not exist.
+    fn insert_locked<'h>(
+        &'h self,
+        mut accessor: Accessor<'h, K, V, H, A>,
+        key: K,
+        hash: u64,
+        value: V,
+    ) -> InsertResult<'h, K, V, H, A> {
+        if accessor.cell_iterator.is_some() {
+            #[cfg(feature = "metrics")]
+            self.metrics.add(Counter::FailedInserts, 1);
+            return Err((accessor, key, value));
+        }
+        let (iterator, result) = self.insert_into_cell(
+            accessor.cell_locker.as_ref().unwrap(),
+            key,
+            value,
+            hash,
+            unsafe { crate::ebr::unprotected() },
+        );
+        accessor.cell_iterator.replace(unsafe {
+            std::mem::transmute::<_, CellIterator<'h, K, V, CELL_SIZE, false, A>>(iterator)
+        });
+        debug_assert!(result.is_none());
+        drop(result);
+        #[cfg(feature = "metrics")]
+        self.metrics.add(Counter::Inserts, 1);
+        accessor.event.set(Some(EventKind::Inserted));
+        Ok(accessor)
+    }
+
+    /// Inserts a key-value pair without letting the Cell grow beyond a single entry array.
+    ///
+    /// If the Cell is full, the entry for which `rank` returns the smallest value among the
+    /// entries in the Cell is evicted in order to make room for the new key-value pair. The array
+    /// is never resized by the method.
+    pub(crate) fn insert_bounded<R: FnMut(&K, &V) -> u64>(
+        &self,
+        key: K,
+        value: V,
+        rank: R,
+    ) -> Result<Option<(K, V)>, (K, V)> {
+        let (hash, _) = self.hash(&key);
+        let accessor = self.acquire(hash, |k| *k == key);
+        if accessor.cell_iterator.is_some() {
+            #[cfg(feature = "metrics")]
+            self.metrics.add(Counter::FailedInserts, 1);
+            return Err((key, value));
+        }
+        let evicted = self.evict_locked(&accessor, rank);
+        drop(self.insert_locked(accessor, key, hash, value));
+        if let Some((key, value)) = evicted.as_ref() {
+            self.emit(Event::Removed(key, value));
+        }
+        Ok(evicted)
+    }
+
+    /// Reads the key-value pair, or inserts a new key-value pair constructed by `constructor`
+    /// without letting the Cell grow beyond a single entry array.
+    ///
+    /// The Cell is kept locked while `constructor` and `reader` are running, therefore other
+    /// threads accessing the same Cell are blocked until `reader` returns. The existing entry is
+    /// replaced if `valid` returns `false`. It returns the result of `reader`, and whether a new
+    /// key-value pair has been inserted.
+    ///
+    /// The replaced or evicted entries are passed to `sink` after the Cell is unlocked, even if
+    /// `reader` panics. Nothing is removed if `valid` or `constructor` panics.
+    pub(crate) fn read_or_insert_bounded<
+        C: FnOnce() -> V,
+        P: FnOnce(&V) -> bool,
+        R: FnMut(&K, &V) -> u64,
+        F: FnOnce(&K, &V) -> T,
+        S: FnMut(K, V),
+        T,
+    >(
+        &self,
+        key: K,
+        constructor: C,
+        valid: P,
+        rank: R,
+        reader: F,
+        sink: S,
+    ) -> (T, bool) {
+        let (hash, _) = self.hash(&key);
+        let accessor = self.acquire(hash, |k| *k == key);
+        self.read_or_insert_locked(accessor, key, hash, constructor, valid, rank, reader, sink)
+    }
+
+    /// Reads the key-value pair, or inserts a new key-value pair constructed by `constructor`
+    /// without letting the Cell grow beyond a single entry array, and without blocking the thread.
+    ///
+    /// It is an asynchronous counterpart of [`HashMap::read_or_insert_bounded`]; the task is
+    /// suspended instead of blocking the thread until the Cell is locked, and the Cell is kept
+    /// locked while `constructor` and `reader` are running.
+    pub(crate) async fn read_or_insert_bounded_async<
+        C: FnOnce() -> V,
+        P: FnOnce(&V) -> bool,
+        R: FnMut(&K, &V) -> u64,
+        F: FnOnce(&K, &V) -> T,
+        S: FnMut(K, V),
+        T,
+    >(
+        &self,
+        key: K,
+        constructor: C,
+        valid: P,
+        rank: R,
+        reader: F,
+        sink: S,
+    ) -> (T, bool) {
+        let (hash, _) = self.hash(&key);
+        loop {
+            let mut async_wait = AsyncWait::default();
+            {
+                // The result is dropped before the task is suspended, as the Accessor is not Send.
+                let result = self.try_acquire(hash, |k| *k == key, Some(&mut async_wait));
+                if let Ok(accessor) = result {
+                    return self.read_or_insert_locked(
+                        accessor,
+                        key,
+                        hash,
+                        constructor,
+                        valid,
+                        rank,
+                        reader,
+                        sink,
+                    );
+                }
+            }
+            async_wait.await;
+        }
+    }
+
+    /// Reads the key-value pair in the Cell locked by the Accessor, or inserts a new key-value
+    /// pair constructed by `constructor`.
+    #[allow(clippy::too_many_arguments)]
+    fn read_or_insert_locked<
+        C: FnOnce() -> V,
+        P: FnOnce(&V) -> bool,
+        R: FnMut(&K, &V) -> u64,
+        F: FnOnce(&K, &V) -> T,
+        S: FnMut(K, V),
+        T,
+    >(
+        &self,
+        accessor: Accessor<'_, K, V, H, A>,
+        key: K,
+        hash: u64,
+        constructor: C,
+        valid: P,
+        rank: R,
+        reader: F,
+        mut sink: S,
+    ) -> (T, bool) {
+        let mut removed = scopeguard::guard(Vec::new(), |mut removed| {
+            self.drain_removed(&mut removed, &mut sink);
+        });
+        // The Accessor is rebound so that it is dropped before the guard.
+        let mut accessor = accessor;
+        if accessor.cell_iterator.is_some() {
+            let (existing_key, existing_value) = accessor.entry();
+            if valid(existing_value) {
+                return (reader(existing_key, existing_value), false);
+            }
+        }
+        let value = constructor();
+        if let Some(mut iterator) = accessor.cell_iterator.take() {
+            if let Some(entry) = accessor.cell_locker.as_ref().unwrap().erase(&mut iterator) {
+                #[cfg(feature = "metrics")]
+                self.metrics.add(Counter::Removes, 1);
+                removed.push(entry);
+            }
+        }
+        removed.extend(self.evict_locked(&accessor, rank));
+        let result = match self.insert_locked(accessor, key, hash, value) {
+            Ok(mut accessor) => {
+                let (key, value) = accessor.entry();
+                reader(key, value)
+            }
+            Err(_) => unreachable!(),
+        };
+        (result, true)
+    }
+
+    /// Evicts the entry for which `rank` returns the smallest value from the Cell locked by the
+    /// Accessor if the Cell is full.
+    fn evict_locked<R: FnMut(&K, &V) -> u64>(
+        &self,
+        accessor: &Accessor<'_, K, V, H, A>,
+        mut rank: R,
+    ) -> Option<(K, V)> {
+        let locker = accessor.cell_locker.as_ref().unwrap();
+        if locker.cell_ref().num_entries() < CELL_SIZE {
+            return None;
+        }
+        let guard = unsafe { crate::ebr::unprotected() };
+        let position = locker
+            .cell_ref()
+            .iter(guard)
+            .enumerate()
+            .min_by_key(|(_, ((key, value), _))| rank(key, value))
+            .map(|(position, _)| position)?;
+        let mut iterator = locker.cell_ref().iter(guard);
+        iterator.nth(position)?;
+        let evicted = locker.erase(&mut iterator);
+        #[cfg(feature = "metrics")]
+        self.metrics.add(Counter::Removes, 1);
+        evicted
+    }
+
+    /// Inserts a key-value pair, or replaces both the key and the value of the existing entry.
+    ///
+    /// It returns the replaced key-value pair.
+    pub(crate) fn replace_entry(&self, key: K, value: V) -> Option<(K, V)> {
+        let (mut accessor, key, hash) = self.lock(key);
+        if let Some(iterator) = accessor.cell_iterator.as_mut() {
+            // Equal keys have the same hash value, therefore the key can be replaced in place.
+            let replaced = accessor
+                .cell_locker
+                .as_ref()
+                .unwrap()
+                .replace(iterator, key, value);
+            accessor.event.set(Some(EventKind::Updated));
+            return replaced;
+        }
+        drop(self.insert_locked(accessor, key, hash, value));
+        None
+    }
+
+    /// Removes a key-value pair, and returns it.
+    pub(crate) fn remove_entry<Q>(&self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.remove_entry_if(key, |_| true)
+    }
+
+    /// Removes a key-value pair if the value satisfies the given predicate, and returns it.
+    pub(crate) fn remove_entry_if<Q, F: FnOnce(&V) -> bool>(
+        &self,
+        key: &Q,
+        condition: F,
+    ) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut accessor = self.get(key)?;
+        if condition(accessor.get().1) {
+            Some(self.erase(accessor))
+        } else {
+            None
+        }
+    }
+
+    /// Evicts the entry for which `rank` returns the smallest value in the next non-empty Cell,
+    /// and returns it.
+    ///
+    /// The Cells are claimed by advancing `cursor`, which wraps around at the end of the current
+    /// array; Cells in the old array are not examined.
+    pub(crate) fn evict_next<R: FnMut(&K, &V) -> u64>(
+        &self,
+        cursor: &AtomicUsize,
+        mut rank: R,
+    ) -> Option<(K, V)> {
+        let guard = crate::ebr::pin();
+        let current_array_ref = Self::cell_array_ref(self.array.load(Acquire, &guard));
+        let array_size = current_array_ref.array_size();
+        for _ in 0..array_size {
+            let cell_index = cursor.fetch_add(1, Relaxed) % array_size;
+            let evicted = if let Some(locker) =
+                CellLocker::lock(current_array_ref.cell(cell_index), &guard)
+            {
+                let victim = locker
+                    .cell_ref()
+                    .iter(&guard)
+                    .enumerate()
+                    .min_by_key(|(_, ((key, value), _))| rank(key, value))
+                    .map(|(position, _)| position);
+                victim.and_then(|position| {
+                    let mut iterator = locker.cell_ref().iter(&guard);
+                    iterator.nth(position)?;
+                    locker.erase(&mut iterator)
+                })
+            } else {
+                None
+            };
+            if let Some((key, value)) = evicted {
+                #[cfg(feature = "metrics")]
+                self.metrics.add(Counter::Removes, 1);
+                self.emit(Event::Removed(&key, &value));
+                return Some((key, value));
+            }
+        }
+        None
+    }
+
+    /// Removes the key-value pairs that fail the predicate from up to `budget` Cells.
+    ///
+    /// The Cells are claimed by advancing `cursor`, which wraps around at the end of the current
+    /// array; Cells in the old array are not examined. The removed key-value pairs are passed to
+    /// `sink` after the Cell is unlocked, even if `keep` or `sink` panics. It returns the number
+    /// of removed key-value pairs.
+    pub(crate) fn prune_chunked<P: FnMut(&K, &V) -> bool, S: FnMut(K, V)>(
+        &self,
+        cursor: &AtomicUsize,
+        budget: usize,
+        mut keep: P,
+        sink: S,
+    ) -> usize {
+        let guard = crate::ebr::pin();
+        let current_array_ref = Self::cell_array_ref(self.array.load(Acquire, &guard));
+        let array_size = current_array_ref.array_size();
+        let budget = budget.clamp(1, array_size);
+        let start_index = cursor.fetch_add(budget, Relaxed);
+        let mut num_removed = 0;
+        // The guard is dropped after the CellLocker if `keep` panics.
+        let mut removed = scopeguard::guard((Vec::new(), sink), |(mut removed, mut sink)| {
+            self.drain_removed(&mut removed, &mut sink);
+        });
+        for offset in 0..budget {
+            let cell_index = start_index.wrapping_add(offset) % array_size;
+            current_array_ref.prefetch((cell_index + 1) % array_size, &guard);
+            if let Some(locker) = CellLocker::lock(current_array_ref.cell(cell_index), &guard) {
+                let mut iterator = locker.cell_ref().iter(&guard);
+                while let Some(((key, value), _)) = iterator.next() {
+                    if !keep(key, value) {
+                        if let Some(entry) = locker.erase(&mut iterator) {
+                            #[cfg(feature = "metrics")]
+                            self.metrics.add(Counter::Removes, 1);
+                            removed.0.push(entry);
+                        }
+                    }
+                }
+            }
+            num_removed += removed.0.len();
+            let (removed, sink) = &mut *removed;
+            self.drain_removed(removed, sink);
+        }
+        num_removed
+    }
+
+    /// Passes the removed key-value pairs to `sink` after emitting the events for them.
+    ///
+    /// All the key-value pairs are passed to `sink` even if the event hook or `sink` panics, and
+    /// then the panic is propagated.
+    fn drain_removed<S: FnMut(K, V)>(&self, removed: &mut Vec<(K, V)>, sink: &mut S) {
+        let mut deferred_panic = DeferredPanic::default();
+        for (key, value) in removed.drain(..) {
+            deferred_panic.run(|| self.emit(Event::Removed(&key, &value)));
+            deferred_panic.run(|| sink(key, value));
+        }
+        deferred_panic.propagate();
+    }
+
+    /// Erases a key-value pair owned by the Accessor.
+    fn erase<'h>(&'h self, mut accessor: Accessor<'h, K, V, H, A>) -> (K, V) {
+        accessor.event.set(None);
+        let mut iterator = accessor.cell_iterator.take().unwrap();
+        let (key, value) = accessor
+            .cell_locker
+            .as_ref()
+            .unwrap()
+            .erase(&mut iterator)
+            .unwrap();
+        #[cfg(feature = "metrics")]
+        self.metrics.add(Counter::Removes, 1);
+        let cell_emptied = accessor
+            .cell_locker
+            .as_ref()
+            .unwrap()
+            .cell_ref()
+            .num_entries()
+            == 0
+            && accessor.cell_index < CELL_SIZE;
+        drop(accessor);
+        self.emit(Event::Removed(&key, &value));
+        let consider_shrinking = match self.shrink_policy {
+            ShrinkPolicy::Never => false,
+            ShrinkPolicy::OnInsertSampling => cell_emptied,
+            ShrinkPolicy::Aggressive { idle_ops } => {
+                cell_emptied || self.num_removals.fetch_add(1, Relaxed) % idle_ops == idle_ops - 1
+            }
+        };
+        if consider_shrinking {
+            let guard = crate::ebr::pin();
+            let current_array = self.array.load(Acquire, &guard);
+            let current_array_ref = Self::cell_array_ref(current_array);
+            if current_array_ref.old_array(&guard).is_null()
+                && current_array_ref.num_cell_entries() > self.minimum_capacity()
+            {
+                // Triggers resize if the estimated load factor is smaller than 1/16.
+                let sample_size = current_array_ref.sample_size();
+                let mut num_entries = 0;
+                for i in 0..sample_size {
+                    num_entries += current_array_ref.cell(i).num_entries();
+                    if num_entries >= sample_size * CELL_SIZE / 16 {
+                        return (key, value);
+                    }
+                }
+                self.resize(&guard);
+            }
+        }
+        (key, value)
+    }
+
+    /// Returns a reference to the event hook if one is set.
+    fn event_hook<'g>(&self, guard: &'g Guard) -> Option<&'g EventHook<K, V>> {
+        unsafe { self.event_hook.load(Acquire, guard).as_ref() }
+    }
+
+    /// Passes the event to the event hook if one is set.
+    fn emit(&self, event: Event<&K, &V>) {
+        let guard = crate::ebr::pin();
+        if let Some(event_hook) = self.event_hook(&guard) {
+            (event_hook.hook)(event);
+        }
+    }
+}
+
+impl<K, V, H, A> HashMap<K, V, H, A>
+where
+    K: fmt::Debug + Eq + Hash,
+    V: fmt::Debug,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Writes at most the given number of entries to the given output.
+    ///
+    /// It takes shared access to a single Cell at a time, therefore the output is a best-effort
+    /// snapshot of the HashMap; an entry being relocated can be written twice. An ellipsis is
+    /// appended if the HashMap has more entries than `max_entries`.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// for key in 0..2 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    ///
+    /// let mut output = String::new();
+    /// assert!(hashmap.fmt_entries(&mut output, 1).is_ok());
+    /// assert!(output == "{0: 0, ..}" || output == "{1: 0, ..}");
+    /// ```
+    pub fn fmt_entries<W: fmt::Write>(&self, output: &mut W, max_entries: usize) -> fmt::Result {
+        output.write_str("{")?;
+        let mut num_entries = 0;
+        let mut result = Ok(());
+        self.scan(|key, value| {
+            let separator = if num_entries == 0 { "" } else { ", " };
+            if num_entries == max_entries {
+                result = write!(output, "{}..", separator);
+                return false;
+            }
+            result = write!(output, "{}{:?}: {:?}", separator, key, value);
+            num_entries += 1;
+            result.is_ok()
+        });
+        result?;
+        output.write_str("}")
+    }
+}
+
+impl<K, V, H, A> fmt::Debug for HashMap<K, V, H, A>
+where
+    K: fmt::Debug + Eq + Hash,
+    V: fmt::Debug,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Formats at most 32 entries of the HashMap.
+    ///
+    /// Use [`HashMap::fmt_entries`] in order to specify the maximum number of entries.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// assert!(result.is_ok());
+    /// drop(result);
+    ///
+    /// assert_eq!(format!("{:?}", hashmap), "{1: 0}");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_entries(f, DEBUG_MAX_ENTRIES)
+    }
+}
+
+#[cfg(feature = "background-worker")]
+impl<K, V, H, A> HashMap<K, V, H, A>
+where
+    K: 'static + Eq + Hash + Send + Sync,
+    V: 'static + Send + Sync,
+    H: 'static + BuildHasher + Send + Sync,
+    A: ArrayAllocator,
+{
+    /// Spawns a thread that rehashes the old array in the background.
+    ///
+    /// The old array is otherwise only rehashed when operations happen to touch it, therefore a
+    /// rarely modified HashMap may keep both arrays for a long time after it is resized. The
+    /// thread relocates a bounded number of Cells at a time until the old array is dropped, and
+    /// then sleeps until the HashMap is resized again; see
+    /// [`HashMap::with_maintenance_interval`] for the interval between steps.
+    ///
+    /// The thread only holds a weak reference to the HashMap, and exits when the HashMap is
+    /// dropped. It is stopped and joined when the returned [`MaintenanceHandle`] is dropped.
+    /// Standard threads do not support scheduling priorities, therefore the thread runs at the
+    /// default priority.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system fails to spawn a thread.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    /// use std::sync::Arc;
+    ///
+    /// let hashmap: Arc<HashMap<u64, u32>> = Arc::new(Default::default());
+    /// let handle = hashmap.spawn_maintenance();
+    ///
+    /// for key in 0..1024 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    /// drop(handle);
+    /// ```
+    pub fn spawn_maintenance(self: &Arc<Self>) -> MaintenanceHandle {
+        let hash_map = Arc::downgrade(self);
+        MaintenanceHandle::spawn(
+            self.maintenance_signal.clone(),
+            self.maintenance_interval,
+            move || {
+                let hash_map = hash_map.upgrade()?;
+                let guard = crate::ebr::pin();
+                let current_array_ref = Self::cell_array_ref(hash_map.array.load(Acquire, &guard));
+                if current_array_ref.old_array(&guard).is_null() {
+                    return Some(false);
+                }
+                Some(!current_array_ref.partial_rehash(
+                    |key: &K| hash_map.hash(key),
+                    |_, _| None,
+                    CELL_SIZE,
+                    &guard,
+                ))
+            },
+        )
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K, V, H, A> HashMap<K, V, H, A>
+where
+    K: Eq + Hash + Send + Sync,
+    V: Send + Sync,
+    H: BuildHasher + Sync,
+    A: ArrayAllocator,
+{
+    /// Applies the given closure to all the key-value pairs in parallel.
+    ///
+    /// The Cells are distributed over the rayon thread pool by recursively splitting the range of
+    /// Cells to process, therefore an idle thread steals Cells from busy threads even when the
+    /// key-value pairs are unevenly distributed. Each Cell is locked while the closure is applied
+    /// to the key-value pairs in it, and the HashMap is not resized until the method returns,
+    /// thus the closure is invoked exactly once for each key-value pair.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// for key in 0..16 {
+    ///     assert!(hashmap.insert(key, 0).is_ok());
+    /// }
+    ///
+    /// hashmap.par_for_each_mut(|key, value| *value = *key as u32 + 1);
+    ///
+    /// for key in 0..16 {
+    ///     assert_eq!(hashmap.read(&key, |_, value| *value).unwrap(), key as u32 + 1);
+    /// }
+    /// ```
+    pub fn par_for_each_mut<F: Fn(&K, &mut V) + Sync>(&self, f: F) {
+        let guard = crate::ebr::pin();
+
+        // Takes the resizing flag in order to prevent the array from being replaced. While
+        // another thread holds the flag, the thread helps relocate the old array, or waits for
+        // the other thread to decide whether to replace the array.
+        let current_array_ref = loop {
+            let current_array = self.array.load(Acquire, &guard);
+            let current_array_ref = Self::cell_array_ref(current_array);
+            if self.resizing_flag.try_acquire() {
+                if current_array == self.array.load(Acquire, &guard) {
+                    break current_array_ref;
+                }
+                self.resizing_flag.release();
+            } else if current_array_ref.partial_rehash(
+                |key| self.hash(key),
+                |_, _| None,
+                CELL_SIZE,
+                &guard,
+            ) {
+                self.resizing_flag.wait(MAX_REHASH_WAITS);
+            }
+        };
+        let _resizing_flag_guard = scopeguard::guard((), |()| self.resizing_flag.release());
+        while !current_array_ref.partial_rehash(
+            |key| self.hash(key),
+            |_, _| None,
+            CELL_SIZE,
+            &guard,
+        ) {
+            // All the key-value pairs in the old array have to be relocated beforehand.
+            continue;
+        }
+
+        // Each rayon worker pins its own Guard for the Cells it processes.
+        (0..current_array_ref.array_size())
+            .into_par_iter()
+            .for_each_init(crate::ebr::pin, |guard, cell_index| {
+                if let Some(locker) = CellLocker::lock(current_array_ref.cell(cell_index), guard) {
+                    let mut iterator = locker.cell_ref().iter(guard);
+                    while iterator.next().is_some() {
+                        let (key, value) = locker.entry_mut(&mut iterator).unwrap();
+                        f(key, value);
+                    }
+                }
+            });
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K, V, H, A> HashMap<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Deserializes a map into the HashMap.
+    ///
+    /// The key-value pairs are inserted into the HashMap as they are deserialized, therefore the
+    /// HashMap keeps its build hasher, capacity, and event hook, and an [`Event::Inserted`] is
+    /// emitted for each new key. The HashMap grows in advance if the deserializer knows the number
+    /// of entries. Existing values are replaced; see [`HashMap::deserialize_seed`] for the other
+    /// [`DuplicateKeyPolicy`] options.
+    ///
+    /// Returns the number of keys that already existed in the HashMap.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the deserializer fails, in which case the key-value pairs deserialized
+    /// so far remain in the HashMap.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default();
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    ///
+    /// let mut deserializer = serde_json::Deserializer::from_str(r#"{"1": 1, "2": 2}"#);
+    /// assert_eq!(hashmap.deserialize_into(&mut deserializer).unwrap(), 1);
+    /// assert_eq!(hashmap.read(&1, |_, v| *v), Some(1));
+    /// assert_eq!(hashmap.read(&2, |_, v| *v), Some(2));
+    /// ```
+    pub fn deserialize_into<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<usize, D::Error>
+    where
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+    {
+        self.deserialize_seed(DuplicateKeyPolicy::default())
+            .deserialize(deserializer)
+    }
+
+    /// Returns a [`DeserializeSeed`] that deserializes a map into the HashMap with the given
+    /// [`DuplicateKeyPolicy`].
+    ///
+    /// The seed behaves as [`HashMap::deserialize_into`] does except for the duplicate keys, and
+    /// can be used to deserialize a map nested in another structure.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::{DuplicateKeyPolicy, HashMap};
+    /// use serde::de::DeserializeSeed;
+    ///
+    /// let hashmap: HashMap<u64, u32> = HashMap::default();
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    ///
+    /// let mut deserializer = serde_json::Deserializer::from_str(r#"{"1": 1, "2": 2}"#);
+    /// let seed = hashmap.deserialize_seed(DuplicateKeyPolicy::Keep);
+    /// assert_eq!(seed.deserialize(&mut deserializer).unwrap(), 1);
+    /// assert_eq!(hashmap.read(&1, |_, v| *v), Some(0));
+    ///
+    /// let mut deserializer = serde_json::Deserializer::from_str(r#"{"1": 1}"#);
+    /// let seed = hashmap.deserialize_seed(DuplicateKeyPolicy::Reject);
+    /// assert!(seed.deserialize(&mut deserializer).is_err());
+    /// ```
+    pub fn deserialize_seed(&self, policy: DuplicateKeyPolicy) -> DeserializeInto<'_, Self, K, V> {
+        DeserializeInto::new(self, policy)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K, V, H, A> DeserializeTarget<K, V> for HashMap<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    fn reserve_for(&self, additional: usize) {
+        let _result = self.try_reserve(additional);
+    }
+
+    fn insert_deserialized(&self, key: K, value: V, policy: DuplicateKeyPolicy) -> bool {
+        match self.insert(key, value) {
+            Ok(_) => true,
+            Err((mut accessor, _, value)) => {
+                if policy == DuplicateKeyPolicy::Replace {
+                    *accessor.get().1 = value;
+                }
+                false
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K, V, H, A> Serialize for HashMap<K, V, H, A>
+where
+    K: Eq + Hash + Serialize,
+    V: Serialize,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Serializes the HashMap as a map.
+    ///
+    /// The length of the map is calculated before the entries are serialized, therefore the
+    /// HashMap must not be modified during serialization if the format requires the length of a
+    /// map to be exact.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        let mut result = Ok(());
+        self.scan(|key, value| {
+            result = map.serialize_entry(key, value);
+            result.is_ok()
+        });
+        result?;
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, V, H, A> Deserialize<'de> for HashMap<K, V, H, A>
+where
+    K: Eq + Hash + Deserialize<'de>,
+    V: Deserialize<'de>,
+    H: BuildHasher + Default,
+    A: ArrayAllocator,
+{
+    /// Deserializes a HashMap from a map.
+    ///
+    /// Values of duplicate keys replace the previous ones.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hashmap = HashMap::new_in(0, H::default());
+        hashmap.deserialize_into(deserializer)?;
+        Ok(hashmap)
+    }
+}
+
+impl<K, V, H, A> Drop for HashMap<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    fn drop(&mut self) {
+        #[cfg(feature = "background-worker")]
+        self.maintenance_signal.close();
+
+        // The HashMap has become unreachable, therefore pinning is unnecessary.
+        self.clear();
+        let guard = unsafe { crate::ebr::unprotected() };
+        let current_array = self.array.load(Acquire, guard);
+        let current_array_ref = Self::cell_array_ref(current_array);
+        current_array_ref.drop_old_array(true, guard);
+        let array = self.array.swap(Shared::null(), Relaxed, guard);
+        if !array.is_null() {
+            drop(unsafe { array.into_owned() });
+        }
+        let event_hook = self.event_hook.swap(Shared::null(), Relaxed, guard);
+        if !event_hook.is_null() {
+            drop(unsafe { event_hook.into_owned() });
+        }
+    }
+}
+
+impl<K, V, H, A> HashTable<K, V, H, CELL_SIZE, false, A> for HashMap<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    fn hasher(&self) -> &H {
+        &self.build_hasher
+    }
+    fn copy_entry(_: &K, _: &V) -> Option<(K, V)> {
+        None
+    }
+    fn cell_array_ptr(&self) -> &Atomic<CellArray<K, V, CELL_SIZE, false, A>> {
+        &self.array
+    }
+    fn minimum_capacity(&self) -> usize {
+        (self.minimum_capacity + self.additional_capacity.load(Relaxed))
+            .max(self.reserved_capacity.load(Relaxed))
+    }
+    fn reserved_capacity_ref(&self) -> &AtomicUsize {
+        &self.reserved_capacity
+    }
+    fn resizing_flag_ref(&self) -> &ResizingFlag {
+        &self.resizing_flag
+    }
+    fn max_load_factor(&self) -> f32 {
+        self.max_load_factor
+    }
+    fn rehash_budget(&self) -> usize {
+        self.rehash_budget
+    }
+    fn max_resizing_factor(&self) -> usize {
+        self.max_resizing_factor
+    }
+    fn resize_history(&self) -> &ResizeHistory {
+        &self.resize_history
+    }
+    fn resize_dampening(&self) -> Duration {
+        self.resize_dampening
+    }
+    fn shrink_policy(&self) -> ShrinkPolicy {
+        self.shrink_policy
+    }
+    fn hash_finalizer(&self) -> bool {
+        self.hash_finalizer
+    }
+    #[cfg(feature = "metrics")]
+    fn counters(&self) -> &Counters {
+        &self.metrics
+    }
+    #[cfg(feature = "background-worker")]
+    fn signal(&self) -> Option<&Signal> {
+        Some(&self.maintenance_signal)
+    }
+}
+
+/// CasError is returned by [`HashMap::compare_exchange`] when the value is not replaced.
+///
+/// The value that was supposed to replace the current value is handed back.
+#[derive(Debug, Eq, PartialEq)]
+pub enum CasError<V> {
+    /// NotFound: the key does not exist.
+    NotFound(V),
+    /// Mismatch: the current value does not satisfy the predicate.
+    Mismatch(V),
+}
+
+impl<V> CasError<V> {
+    /// Returns the value that was supposed to replace the current value.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.compare_exchange(&1, |_| true, 1);
+    /// assert_eq!(result.err().unwrap().into_inner(), 1);
+    /// ```
+    pub fn into_inner(self) -> V {
+        match self {
+            CasError::NotFound(value) | CasError::Mismatch(value) => value,
+        }
+    }
+}
+
+/// TransferError is returned by [`HashMap::transfer`] when the value is not moved.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransferError {
+    /// NotFound: the source key does not exist.
+    NotFound,
+    /// Occupied: the destination key exists.
+    Occupied,
+}
+
+/// Statistics is a snapshot of the state of a HashMap, returned by [`HashMap::statistics`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Statistics {
+    capacity: usize,
+    estimated_len: usize,
+    len_sampling_cells: usize,
+    len_sampling_deterministic: bool,
+}
+
+impl Statistics {
+    /// Returns the capacity of the HashMap.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the estimated number of entries in the HashMap.
+    pub fn estimated_len(&self) -> usize {
+        self.estimated_len
+    }
+
+    /// Returns the number of Cells sampled in order to estimate the number of entries.
+    pub fn len_sampling_cells(&self) -> usize {
+        self.len_sampling_cells
+    }
+
+    /// Returns `true` if the sampled Cells are chosen deterministically.
+    pub fn len_sampling_deterministic(&self) -> bool {
+        self.len_sampling_deterministic
+    }
+}
+
+/// RemoveIfResult is the result of [`HashMap::remove_if_result`].
+#[derive(Debug, Eq, PartialEq)]
+pub enum RemoveIfResult<V> {
+    /// Removed: the key-value pair has been removed, and the value is handed over.
+    Removed(V),
+    /// Declined: the predicate declined to remove the key-value pair.
+    Declined,
+    /// NotFound: the key does not exist.
+    NotFound,
+}
+
+/// Event describes a committed mutation of a HashMap, and is passed to the event hook.
+///
+/// See [`HashMap::set_event_hook`] for the events emitted by each operation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event<K, V> {
+    /// Inserted: a new key-value pair has been inserted.
+    Inserted(K, V),
+    /// Updated: the value associated with the key may have been updated.
+    Updated(K, V),
+    /// Removed: the key-value pair has been removed.
+    Removed(K, V),
+}
+
+/// EventKind is the kind of the event that an Accessor emits when dropped.
+#[derive(Clone, Copy)]
+enum EventKind {
+    Inserted,
+    Updated,
+}
+
+/// InsertResult is the result of an insertion; the error carries the Accessor owning the existing
+/// key-value pair along with the key and value that were not inserted.
+type InsertResult<'h, K, V, H, A> =
+    Result<Accessor<'h, K, V, H, A>, (Accessor<'h, K, V, H, A>, K, V)>;
+
+/// InsertAndGetResult is the result of [`HashMap::insert_and_get`]; the error carries the key and
+/// value that were not inserted along with the Accessor owning the existing key-value pair.
+type InsertAndGetResult<'h, K, V, H, A> =
+    Result<Accessor<'h, K, V, H, A>, (K, V, Accessor<'h, K, V, H, A>)>;
+
+/// EmplaceResult is the result of an emplacement; the error carries the Accessor owning the
+/// existing key-value pair along with the key that was not inserted.
+type EmplaceResult<'h, K, V, H, A> =
+    Result<Accessor<'h, K, V, H, A>, (Accessor<'h, K, V, H, A>, K)>;
+
+/// LockerPair is the pair of CellLockers returned by [`HashMap::lock_pair`]; the second one is
+/// `None` if both hash values belong to the same Cell.
+type LockerPair<'g, K, V, A> = (
+    CellLocker<'g, K, V, CELL_SIZE, false, A>,
+    Option<CellLocker<'g, K, V, CELL_SIZE, false, A>>,
+);
+
+/// ReaderEntry is a key-value pair along with the CellReader protecting it.
+type ReaderEntry<'g, K, V, A> = (CellReader<'g, K, V, CELL_SIZE, false, A>, (&'g K, &'g V));
+
+/// HookFn is the type of event hooks.
+type HookFn<K, V> = dyn Fn(Event<&K, &V>) + Send + Sync;
+
+/// EventHook is an event hook along with the function that clones key-value pairs for it.
+struct EventHook<K, V> {
+    hook: Box<HookFn<K, V>>,
+    cloner: fn(&K, &V) -> (K, V),
+}
+
+/// Ticket keeps the increased minimum capacity of the HashMap during its lifetime.
+///
+/// The minimum capacity is lowered when the Ticket is dropped, thereby allowing unused space to be reclaimed.
+pub struct Ticket<'h, K, V, H, A = GlobalArrayAllocator>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    hash_map: &'h HashMap<K, V, H, A>,
+    increment: usize,
+}
+
+impl<'h, K, V, H, A> Drop for Ticket<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    fn drop(&mut self) {
+        let result = self
+            .hash_map
+            .additional_capacity
+            .fetch_sub(self.increment, Relaxed);
+        let guard = crate::ebr::pin();
+        self.hash_map.resize(&guard);
+        debug_assert!(result >= self.increment);
+    }
+}
+
+/// Accessor owns a key-value pair in the HashMap, and it implements Iterator.
+///
+/// It is !Send, thus disallowing other threads to have references to it.
+/// It acquires an exclusive lock on the Cell managing the key.
+/// A thread having multiple Accessor instances poses a possibility of deadlock.
+pub struct Accessor<'h, K, V, H, A = GlobalArrayAllocator>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    hash_map: &'h HashMap<K, V, H, A>,
+    array_ptr: *const CellArray<K, V, CELL_SIZE, false, A>,
+    cell_index: usize,
+    cell_locker: Option<CellLocker<'h, K, V, CELL_SIZE, false, A>>,
+    cell_iterator: Option<CellIterator<'h, K, V, CELL_SIZE, false, A>>,
+    guard: Option<&'h Guard>,
+    event: std::cell::Cell<Option<EventKind>>,
+}
+
+impl<'h, K, V, H, A> Accessor<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Returns a reference to the key-value pair.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.get(&1);
+    /// assert!(result.is_none());
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// if let Ok(mut result) = result {
+    ///     assert_eq!(result.get(), (&1, &mut 0));
+    ///     (*result.get().1) = 2;
+    /// }
+    ///
+    /// let result = hashmap.get(&1);
+    /// assert_eq!(result.unwrap().get(), (&1, &mut 2));
+    /// ```
+    pub fn get(&mut self) -> (&K, &mut V) {
+        if self.event.get().is_none() {
+            // Mutable access is reported as an update.
+            self.event.set(Some(EventKind::Updated));
+        }
+        self.entry()
+    }
+
+    /// Erases the key-value pair owned by the Accessor.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let result = hashmap.insert(1, 0);
+    /// if let Ok(mut result) = result {
+    ///     assert_eq!(result.get(), (&1, &mut 0));
+    ///     let result = result.erase();
+    ///     assert_eq!(result.unwrap(), 0);
+    /// }
+    ///
+    /// let result = hashmap.get(&1);
+    /// assert!(result.is_none());
+    /// ```
+    pub fn erase(self) -> Option<V> {
+        if self.cell_iterator.is_none() {
+            return None;
+        }
+        Some(self.hash_map.erase(self).1)
+    }
+
+    /// Returns a reference to the key-value pair without reporting an update.
+    fn entry(&mut self) -> (&K, &mut V) {
+        let cell_locker = self.cell_locker.as_ref().unwrap();
+        let cell_iterator = self.cell_iterator.as_mut().unwrap();
+        cell_locker.entry_mut(cell_iterator).unwrap()
+    }
+}
+
+impl<'h, K, V, H, A> Drop for Accessor<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    fn drop(&mut self) {
+        if let Some(event_kind) = self.event.take() {
+            let guard = crate::ebr::pin();
+            if let Some(event_hook) = self.hash_map.event_hook(&guard) {
+                if let Some(entry_ref) = self.cell_iterator.as_ref().and_then(CellIterator::get) {
+                    // The key-value pair is cloned before the Cell is unlocked.
+                    let (key, value) = (event_hook.cloner)(entry_ref.0, entry_ref.1);
+                    self.cell_iterator.take();
+                    self.cell_locker.take();
+                    let event = match event_kind {
+                        EventKind::Inserted => Event::Inserted(&key, &value),
+                        EventKind::Updated => Event::Updated(&key, &value),
+                    };
+                    (event_hook.hook)(event);
+                }
+            }
+        }
+    }
+}
+
+impl<'h, K, V, H, A> Iterator for Accessor<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    type Item = (&'h K, &'h mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.guard.is_none() {
+            // It always owns a CellLocker preventing the array from being dropped,
+            // therefore a dummy Guard is sufficient.
+            self.guard.replace(unsafe { crate::ebr::unprotected() });
+
+            // A valid Guard is required to load Arrays.
+            let guard = crate::ebr::pin();
+            loop {
+                let current_array = self.hash_map.array.load(Acquire, &guard);
+                let current_array_ref = unsafe { &*current_array.as_raw() };
+                let old_array = current_array_ref.old_array(&guard);
+                if !old_array.is_null() {
+                    let old_array_ref = unsafe { &*old_array.as_raw() };
+                    for index in 0..old_array_ref.array_size() {
+                        if let Some(locker) = CellLocker::lock(
                             old_array_ref.cell(index),
                             self.guard.as_ref().unwrap(),
                         ) {
@@ -1017,92 +4330,1564 @@ where
                             break;
                         }
                     }
-                    if self.cell_locker.is_some() {
-                        break;
-                    }
-                }
-                for index in 0..current_array_ref.array_size() {
-                    if let Some(locker) = CellLocker::lock(
-                        current_array_ref.cell(index),
-                        self.guard.as_ref().unwrap(),
-                    ) {
-                        self.array_ptr = current_array.as_raw();
-                        self.cell_index = index;
-                        self.cell_locker.replace(locker);
-                        break;
-                    }
+                    if self.cell_locker.is_some() {
+                        break;
+                    }
+                }
+                for index in 0..current_array_ref.array_size() {
+                    if let Some(locker) = CellLocker::lock(
+                        current_array_ref.cell(index),
+                        self.guard.as_ref().unwrap(),
+                    ) {
+                        self.array_ptr = current_array.as_raw();
+                        self.cell_index = index;
+                        self.cell_locker.replace(locker);
+                        break;
+                    }
+                }
+                if self.cell_locker.is_some() {
+                    break;
+                }
+                // Reaching here indicates that self.array is updated.
+            }
+        }
+
+        if self.cell_iterator.is_none() {
+            // Starts scanning.
+            self.cell_iterator.replace(CellIterator::new(
+                unsafe {
+                    std::mem::transmute::<_, &'h Cell<K, V, CELL_SIZE, false, A>>(
+                        self.cell_locker.as_ref().unwrap().cell_ref(),
+                    )
+                },
+                self.guard.as_ref().unwrap(),
+            ));
+        }
+        loop {
+            if let Some(iterator) = self.cell_iterator.as_mut() {
+                // Proceeds to the next entry in the Cell.
+                if let Some(_) = iterator.next() {
+                    let (key, value) = self.entry();
+                    // Each entry is yielded only once while the Cell is locked.
+                    return Some(unsafe { (&*(key as *const K), &mut *(value as *mut V)) });
+                }
+            }
+            // Proceeds to the next Cell.
+            let mut array_ref = unsafe { &*self.array_ptr };
+            self.cell_index += 1;
+            self.cell_iterator.take();
+
+            if self.cell_index == array_ref.array_size() {
+                let current_array = self
+                    .hash_map
+                    .array
+                    .load(Acquire, self.guard.as_ref().unwrap());
+                if self.array_ptr == current_array.as_raw() {
+                    // Finished scanning the entire array.
+                    break;
+                }
+
+                // Proceeds to the current array.
+                array_ref = unsafe { current_array.deref() };
+                self.array_ptr = current_array.as_raw();
+                self.cell_index = 0;
+            }
+
+            // Proceeds to the next Cell in the current array.
+            for index in self.cell_index..array_ref.array_size() {
+                array_ref.prefetch(index + 1, self.guard.as_ref().unwrap());
+                let cell_ref = array_ref.cell(index);
+                if let Some(locker) = CellLocker::lock(cell_ref, self.guard.as_ref().unwrap()) {
+                    self.cell_index = index;
+                    self.cell_locker.replace(locker);
+                    self.cell_iterator
+                        .replace(CellIterator::new(cell_ref, self.guard.as_ref().unwrap()));
+                    break;
+                }
+            }
+            if self.cell_iterator.is_some() {
+                continue;
+            }
+            break;
+        }
+        self.cell_locker.take();
+        None
+    }
+}
+
+impl<'h, K, V, H, A> FusedIterator for Accessor<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+}
+
+/// EntryGuard keeps the Cell for a key locked, reserving the key for the owner.
+///
+/// See [`HashMap::lock_entry`] for the deadlock hazards.
+pub struct EntryGuard<'h, K, V, H, A = GlobalArrayAllocator>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    accessor: Accessor<'h, K, V, H, A>,
+    key: K,
+    hash: u64,
+}
+
+impl<'h, K, V, H, A> EntryGuard<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    /// Returns a reference to the value associated with the reserved key if it exists.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let entry_guard = hashmap.lock_entry(1);
+    /// assert!(entry_guard.existing().is_none());
+    /// ```
+    pub fn existing(&self) -> Option<&V> {
+        let (_, value) = self.accessor.cell_iterator.as_ref()?.get()?;
+        Some(value)
+    }
+
+    /// Inserts the reserved key with the given value, and unlocks the Cell once the returned
+    /// Accessor is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error along with the Accessor owning the existing key-value pair, and the
+    /// reserved key and the given value if the key exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails, or the number of entries in the target cell reaches u32::MAX.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let entry_guard = hashmap.lock_entry(1);
+    /// let mut accessor = entry_guard.insert(0).ok().unwrap();
+    /// assert_eq!(accessor.get(), (&1, &mut 0));
+    /// ```
+    pub fn insert(self, value: V) -> InsertResult<'h, K, V, H, A> {
+        let hash_map = self.accessor.hash_map;
+        hash_map.insert_locked(self.accessor, self.key, self.hash, value)
+    }
+
+    /// Abandons the reservation, and unlocks the Cell.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashMap;
+    ///
+    /// let hashmap: HashMap<u64, u32> = Default::default();
+    ///
+    /// let entry_guard = hashmap.lock_entry(1);
+    /// entry_guard.abandon();
+    /// assert!(hashmap.insert(1, 0).is_ok());
+    /// ```
+    pub fn abandon(self) {}
+}
+
+/// ClonedScanner scans the HashMap taking shared access to a single Cell at a time, and buffers
+/// projected entries of the Cell.
+struct ClonedScanner<'h, K, V, H, A, T>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    hash_map: &'h HashMap<K, V, H, A>,
+    current_array_ptr: *const CellArray<K, V, CELL_SIZE, false, A>,
+    array_ptr: *const CellArray<K, V, CELL_SIZE, false, A>,
+    cell_index: usize,
+    buffer: Vec<T>,
+    project: fn(&K, &V) -> T,
+    finished: bool,
+    estimated_remaining: usize,
+    guard: Guard,
+}
+
+impl<'h, K, V, H, A, T> ClonedScanner<'h, K, V, H, A, T>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    fn new(hash_map: &'h HashMap<K, V, H, A>, project: fn(&K, &V) -> T) -> Self {
+        ClonedScanner {
+            hash_map,
+            current_array_ptr: std::ptr::null(),
+            array_ptr: std::ptr::null(),
+            cell_index: 0,
+            buffer: Vec::new(),
+            project,
+            finished: false,
+            estimated_remaining: hash_map.estimated_len(),
+            guard: crate::ebr::pin(),
+        }
+    }
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            if let Some(item) = self.buffer.pop() {
+                self.estimated_remaining = self.estimated_remaining.saturating_sub(1);
+                return Some(item);
+            }
+            if self.finished {
+                return None;
+            }
+            if self.array_ptr.is_null() {
+                // Scans the old array and the current array if the array has been replaced.
+                let current_array = self.hash_map.array.load(Acquire, &self.guard);
+                if current_array.as_raw() == self.current_array_ptr {
+                    self.finished = true;
+                    return None;
+                }
+                let old_array =
+                    HashMap::<K, V, H, A>::cell_array_ref(current_array).old_array(&self.guard);
+                self.current_array_ptr = current_array.as_raw();
+                self.array_ptr = if old_array.is_null() {
+                    current_array.as_raw()
+                } else {
+                    old_array.as_raw()
+                };
+                self.cell_index = 0;
+            }
+
+            // The Guard keeps the arrays alive.
+            let array_ref = unsafe { &*self.array_ptr };
+            if self.cell_index == array_ref.array_size() {
+                if self.array_ptr == self.current_array_ptr {
+                    self.array_ptr = std::ptr::null();
+                } else {
+                    self.array_ptr = self.current_array_ptr;
+                    self.cell_index = 0;
+                }
+                continue;
+            }
+            let cell_ref = array_ref.cell(self.cell_index);
+            self.cell_index += 1;
+            if let Some(_reader) = CellReader::lock(cell_ref, &self.guard) {
+                for ((key, value), _) in cell_ref.iter(&self.guard) {
+                    self.buffer.push((self.project)(key, value));
+                }
+            }
+        }
+    }
+
+    /// Returns the number of buffered items as the lower bound, and the number of entries
+    /// estimated by sampling Cells when the scanner was created, less the number of items
+    /// yielded so far, as the upper bound.
+    ///
+    /// The upper bound is only an estimate until the scan finishes, since entries can be inserted
+    /// or visited twice while the scan is in progress.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.finished {
+            (self.buffer.len(), Some(self.buffer.len()))
+        } else {
+            (
+                self.buffer.len(),
+                Some(self.estimated_remaining.max(self.buffer.len())),
+            )
+        }
+    }
+}
+
+/// Entries is a read-only iterator over the key-value pairs in the HashMap.
+///
+/// It takes shared access to a single Cell at a time, and yields clones of the key-value pairs.
+/// It is guaranteed to visit all the key-value pairs that outlive the Entries, however the same
+/// key-value pair can be visited more than once if the HashMap is being resized.
+pub struct Entries<'h, K, V, H, A = GlobalArrayAllocator>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    scanner: ClonedScanner<'h, K, V, H, A, (K, V)>,
+}
+
+impl<'h, K, V, H, A> Iterator for Entries<'h, K, V, H, A>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scanner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.scanner.size_hint()
+    }
+}
+
+impl<'h, K, V, H, A> FusedIterator for Entries<'h, K, V, H, A>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+}
+
+/// Keys is a read-only iterator over the keys in the HashMap.
+///
+/// It provides the same guarantees as [`Entries`].
+pub struct Keys<'h, K, V, H, A = GlobalArrayAllocator>
+where
+    K: Clone + Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    scanner: ClonedScanner<'h, K, V, H, A, K>,
+}
+
+impl<'h, K, V, H, A> Iterator for Keys<'h, K, V, H, A>
+where
+    K: Clone + Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    type Item = K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scanner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.scanner.size_hint()
+    }
+}
+
+impl<'h, K, V, H, A> FusedIterator for Keys<'h, K, V, H, A>
+where
+    K: Clone + Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+}
+
+/// Values is a read-only iterator over the values in the HashMap.
+///
+/// It provides the same guarantees as [`Entries`].
+pub struct Values<'h, K, V, H, A = GlobalArrayAllocator>
+where
+    K: Eq + Hash,
+    V: Clone,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    scanner: ClonedScanner<'h, K, V, H, A, V>,
+}
+
+impl<'h, K, V, H, A> Iterator for Values<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    V: Clone,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    type Item = V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scanner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.scanner.size_hint()
+    }
+}
+
+impl<'h, K, V, H, A> FusedIterator for Values<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    V: Clone,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+}
+
+/// Drain moves the key-value pairs out of a shared HashMap.
+///
+/// It owns the lock on a single Cell at a time, and the key-value pairs that have not been
+/// yielded stay in the HashMap when it is dropped.
+pub struct Drain<'h, K, V, H, A = GlobalArrayAllocator>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    accessor: Accessor<'h, K, V, H, A>,
+    finished: bool,
+}
+
+impl<'h, K, V, H, A> Iterator for Drain<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            if self.accessor.next().is_none() {
+                self.finished = true;
+                let hash_map = self.accessor.hash_map;
+                hash_map.resize(&crate::ebr::pin());
+                break;
+            }
+            if let Some(entry) = self
+                .accessor
+                .cell_locker
+                .as_ref()
+                .unwrap()
+                .erase(self.accessor.cell_iterator.as_mut().unwrap())
+            {
+                #[cfg(feature = "metrics")]
+                self.accessor.hash_map.metrics.add(Counter::Removes, 1);
+                return Some(entry);
+            }
+        }
+        None
+    }
+}
+
+impl<'h, K, V, H, A> FusedIterator for Drain<'h, K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+}
+
+/// IntoIter moves the key-value pairs out of an owned HashMap.
+///
+/// The key-value pairs that have not been yielded are dropped along with it.
+pub struct IntoIter<K, V, H, A = GlobalArrayAllocator>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    hash_map: HashMap<K, V, H, A>,
+    array_ptr: *const CellArray<K, V, CELL_SIZE, false, A>,
+    cell_index: usize,
+    buffer: Vec<(K, V)>,
+}
+
+impl<K, V, H, A> Iterator for IntoIter<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        // The HashMap is exclusively owned, therefore pinning is unnecessary, and the array
+        // cannot be replaced.
+        let guard = unsafe { crate::ebr::unprotected() };
+        loop {
+            if let Some(entry) = self.buffer.pop() {
+                return Some(entry);
+            }
+            let current_array = self.hash_map.array.load(Acquire, guard);
+            if self.array_ptr.is_null() {
+                // Cells in the old array are drained first.
+                let old_array =
+                    HashMap::<K, V, H, A>::cell_array_ref(current_array).old_array(guard);
+                self.array_ptr = if old_array.is_null() {
+                    current_array.as_raw()
+                } else {
+                    old_array.as_raw()
+                };
+            }
+            let array_ref = unsafe { &*self.array_ptr };
+            if self.cell_index == array_ref.array_size() {
+                if self.array_ptr == current_array.as_raw() {
+                    return None;
+                }
+                self.array_ptr = current_array.as_raw();
+                self.cell_index = 0;
+                continue;
+            }
+            let cell_ref = array_ref.cell(self.cell_index);
+            self.cell_index += 1;
+            if let Some(locker) = CellLocker::lock(cell_ref, guard) {
+                let mut iterator = cell_ref.iter(guard);
+                while iterator.next().is_some() {
+                    if let Some(entry) = locker.erase(&mut iterator) {
+                        self.buffer.push(entry);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<K, V, H, A> FusedIterator for IntoIter<K, V, H, A>
+where
+    K: Eq + Hash,
+    H: BuildHasher,
+    A: ArrayAllocator,
+{
+}
+
+unsafe impl<K, V, H, A> Send for IntoIter<K, V, H, A>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    H: BuildHasher + Send,
+    A: ArrayAllocator,
+{
+}
+
+unsafe impl<K, V, H, A> Sync for IntoIter<K, V, H, A>
+where
+    K: Eq + Hash + Send + Sync,
+    V: Send + Sync,
+    H: BuildHasher + Sync,
+    A: ArrayAllocator,
+{
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::cell::NUM_LOCK_ACQUISITIONS;
+    use crate::common::cell_array::{CAPACITY_CEILING, FAIL_ALLOCATION};
+    use crate::common::sync;
+    use crate::ebr::NUM_RETIRED;
+    use std::future::Future;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering::Release;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    /// Runs the future to completion, parking the current thread while it is pending.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(std::thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: std::sync::Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn insert_accessor() {
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(4096, RandomState::new());
+        for key in 0..256 {
+            let num_locks = NUM_LOCK_ACQUISITIONS.with(|n| n.get());
+            let mut result = hashmap.insert(key, 0);
+            assert_eq!(result.as_mut().ok().unwrap().get(), (&key, &mut 0));
+            drop(result);
+            assert_eq!(NUM_LOCK_ACQUISITIONS.with(|n| n.get()), num_locks + 1);
+
+            let num_locks = NUM_LOCK_ACQUISITIONS.with(|n| n.get());
+            if let Err((mut accessor, key, value)) = hashmap.insert(key, 1) {
+                assert_eq!(accessor.get(), (&key, &mut 0));
+                *accessor.get().1 = value;
+            } else {
+                unreachable!();
+            }
+            assert_eq!(NUM_LOCK_ACQUISITIONS.with(|n| n.get()), num_locks + 1);
+        }
+        for key in 0..256 {
+            assert_eq!(hashmap.read(&key, |_, value| *value), Some(1));
+        }
+    }
+
+    #[test]
+    fn modify_in_place() {
+        let hashmap: HashMap<u64, u64> = HashMap::default();
+        for key in 0..64 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+
+        // The pairs are in the same Cell or in different Cells.
+        for key in 0..32 {
+            assert!(hashmap.swap_values(&key, &(63 - key)));
+        }
+        assert!(hashmap.swap_values(&0, &0));
+        for (_, value) in hashmap.iter() {
+            *value += 1;
+        }
+        for key in 0..64 {
+            assert_eq!(hashmap.read(&key, |_, value| *value), Some(64 - key));
+        }
+
+        assert_eq!(hashmap.replace(0, 0), Ok(64));
+        assert_eq!(
+            hashmap.compare_exchange(&1, |value| *value == 63, 1),
+            Ok(63)
+        );
+        assert_eq!(hashmap.replace_entry(2, 2), Some((2, 62)));
+        assert_eq!(*hashmap.upsert(3, 3).get().1, 3);
+        for key in 0..4 {
+            assert_eq!(hashmap.read(&key, |_, value| *value), Some(key));
+        }
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(4096, RandomState::new());
+        for key in 0..256 {
+            let num_locks = NUM_LOCK_ACQUISITIONS.with(|n| n.get());
+            let mut accessor = hashmap.insert_and_get(key, 0).ok().unwrap();
+            assert_eq!(accessor.get(), (&key, &mut 0));
+            drop(accessor);
+            assert_eq!(NUM_LOCK_ACQUISITIONS.with(|n| n.get()), num_locks + 1);
+
+            // The existing entry is overwritten through the Accessor in the error.
+            let num_locks = NUM_LOCK_ACQUISITIONS.with(|n| n.get());
+            if let Err((key, value, mut accessor)) = hashmap.insert_and_get(key, 1) {
+                assert_eq!(accessor.get(), (&key, &mut 0));
+                *accessor.get().1 = value;
+            } else {
+                unreachable!();
+            }
+            assert_eq!(NUM_LOCK_ACQUISITIONS.with(|n| n.get()), num_locks + 1);
+        }
+        for key in 0..256 {
+            assert_eq!(hashmap.read(&key, |_, value| *value), Some(1));
+        }
+    }
+
+    #[test]
+    fn capacity_ceiling() {
+        let max_capacity = 2048;
+        CAPACITY_CEILING.with(|c| c.set(max_capacity));
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(usize::MAX, RandomState::new());
+        assert_eq!(hashmap.capacity(), max_capacity);
+        assert!(hashmap.reserve(1).is_none());
+        drop(hashmap);
+
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(64, RandomState::new());
+        assert!(hashmap.reserve(max_capacity - 63).is_none());
+        let ticket = hashmap.reserve(max_capacity - 64);
+        assert!(ticket.is_some());
+        assert_eq!(hashmap.capacity(), max_capacity);
+        assert!(hashmap.reserve(1).is_none());
+        drop(ticket);
+        assert_eq!(hashmap.capacity(), 64);
+
+        assert_eq!(
+            hashmap.try_reserve(max_capacity * 7 / 8 + 1),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(hashmap.try_reserve(max_capacity * 7 / 8), Ok(()));
+        assert_eq!(hashmap.capacity(), max_capacity);
+
+        // The array stops growing at the maximum capacity.
+        let num_entries = max_capacity as u64 * 4;
+        for key in 0..num_entries {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        assert_eq!(hashmap.capacity(), max_capacity);
+        for key in 0..num_entries {
+            assert_eq!(hashmap.read(&key, |_, value| *value), Some(key));
+        }
+        CAPACITY_CEILING.with(|c| c.set(0));
+    }
+
+    /// The model checker requires every execution of a model to be deterministic, therefore the
+    /// models hash keys with fixed seeds.
+    type FixedState = std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+
+    #[test]
+    fn model_upsert_rehash() {
+        // A writer updates an entry in the only Cell of the old array while another thread
+        // relocates the Cell.
+        sync::model(|| {
+            let hashmap: sync::Arc<HashMap<usize, usize, FixedState>> = sync::Arc::new(
+                HashMap::new(CELL_SIZE, FixedState::default()).with_rehash_budget(0),
+            );
+            assert!(hashmap.insert(0, 0).is_ok());
+            let ticket = hashmap.reserve(CELL_SIZE * 2);
+            assert!(ticket.is_some());
+            assert!(hashmap.rehash(0));
+
+            let hashmap_clone = hashmap.clone();
+            let writer = sync::thread::spawn(move || {
+                drop(hashmap_clone.upsert(0, 1));
+            });
+            while hashmap.rehash(1) {}
+            writer.join().unwrap();
+
+            assert!(!hashmap.rehash(1));
+            assert_eq!(hashmap.read(&0, |_, v| *v), Some(1));
+            assert_eq!(hashmap.len(), 1);
+        });
+    }
+
+    #[test]
+    fn model_concurrent_resize() {
+        // Two threads try to grow the array at the same time, and only one of them resizes it
+        // at a time.
+        sync::model(|| {
+            let hashmap: sync::Arc<HashMap<usize, usize, FixedState>> =
+                sync::Arc::new(HashMap::new(CELL_SIZE, FixedState::default()));
+            assert!(hashmap.insert(0, 0).is_ok());
+
+            let hashmap_clone = hashmap.clone();
+            let resizer = sync::thread::spawn(move || {
+                assert_eq!(hashmap_clone.try_reserve(CELL_SIZE * 4), Ok(()));
+            });
+            assert_eq!(hashmap.try_reserve(CELL_SIZE * 2), Ok(()));
+            resizer.join().unwrap();
+
+            assert!(hashmap.capacity() >= CELL_SIZE * 4);
+            assert_eq!(hashmap.read(&0, |_, v| *v), Some(0));
+            assert!(hashmap.resizing_flag.try_acquire());
+            hashmap.resizing_flag.release();
+        });
+    }
+
+    #[test]
+    fn hash_finalizer() {
+        use crate::common::cell::partial_hash;
+        use std::hash::BuildHasherDefault;
+
+        #[derive(Default)]
+        struct IdentityHasher(u64);
+
+        impl Hasher for IdentityHasher {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+            fn write(&mut self, _: &[u8]) {
+                unreachable!();
+            }
+            fn write_u64(&mut self, i: u64) {
+                self.0 = i;
+            }
+        }
+
+        let hashmap: HashMap<u64, u64> = HashMap::default();
+        assert_eq!(hashmap.hash_finalizer(), DEFAULT_HASH_FINALIZER);
+
+        let key = 0x0123_4567_89AB_CDEF;
+        let hashmap: HashMap<u64, u64, BuildHasherDefault<IdentityHasher>> =
+            HashMap::new(64, BuildHasherDefault::default());
+        assert!(hashmap.hash_finalizer());
+        assert_ne!(hashmap.hash(&key).0, key);
+
+        let hashmap = hashmap.with_hash_finalizer(false);
+        assert_eq!(hashmap.hash(&key), (key, partial_hash(key)));
+        for key in 0..4096_u64 {
+            let key = key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        for key in 0..4096_u64 {
+            let key = key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the HashMap must be empty")]
+    fn hash_finalizer_non_empty() {
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(64, RandomState::new());
+        assert!(hashmap.insert(1, 1).is_ok());
+        let _ = hashmap.with_hash_finalizer(false);
+    }
+
+    #[test]
+    fn try_reserve() {
+        let hashmap: HashMap<u64, u64> = Default::default();
+        for key in 0..64 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        let capacity = hashmap.capacity();
+
+        FAIL_ALLOCATION.with(|f| f.set(true));
+        let result = hashmap.try_reserve(4096);
+        FAIL_ALLOCATION.with(|f| f.set(false));
+        assert!(matches!(result, Err(TryReserveError::AllocError { .. })));
+        assert_eq!(hashmap.capacity(), capacity);
+        assert!(hashmap.insert(64, 64).is_ok());
+
+        assert!(hashmap.try_reserve(4096).is_ok());
+        assert!(hashmap.capacity() >= 4096 + 65);
+        for key in 0..65 {
+            assert_eq!(hashmap.read(&key, |_, value| *value), Some(key));
+        }
+
+        // The array grows in bounded steps, and does not shrink below the reserved capacity.
+        let array_generation = hashmap.array_generation();
+        assert!(hashmap.try_reserve(1 << 20).is_ok());
+        assert!(hashmap.capacity() >= 1 << 20);
+        assert!(hashmap.array_generation() - array_generation >= 2);
+        for key in 0..65 {
+            assert_eq!(hashmap.remove(&key), Some(key));
+        }
+        hashmap.resize(&crate::ebr::pin());
+        assert!(hashmap.capacity() >= 1 << 20);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics() {
+        let hashmap: HashMap<u64, u64> = Default::default();
+        for key in 0..4096 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        for key in 0..16 {
+            assert!(hashmap.insert(key, key).is_err());
+        }
+        for key in 0..1024 {
+            assert_eq!(hashmap.remove(&key), Some(key));
+        }
+        hashmap.upsert(0, 0);
+        let metrics = hashmap.metrics();
+        assert_eq!(metrics.inserts(), 4097);
+        assert_eq!(metrics.failed_inserts(), 16);
+        assert_eq!(metrics.removes(), 1024);
+        assert!(metrics.resizes() > 0);
+        assert!(metrics.cells_rehashed() > 0);
+
+        // A reader waits for the Cell locked by the Accessor.
+        let accessor = hashmap.get(&2048).unwrap();
+        std::thread::scope(|s| {
+            let reader = s.spawn(|| hashmap.read(&2048, |_, value| *value));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            drop(accessor);
+            assert_eq!(reader.join().unwrap(), Some(2048));
+        });
+        assert!(hashmap.metrics().lock_contentions() > 0);
+
+        hashmap.reset_metrics();
+        assert_eq!(hashmap.metrics(), Metrics::default());
+        assert_eq!(hashmap.clear(), 3073);
+        assert_eq!(hashmap.metrics().removes(), 3073);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics_by_group() {
+        use crate::common::metrics::CELLS_PER_GROUP;
+        use std::hash::BuildHasherDefault;
+
+        // Even keys are mapped to the same hash value, and therefore to the same Cell.
+        #[derive(Default)]
+        struct SkewedHasher(u64);
+
+        impl Hasher for SkewedHasher {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+            fn write(&mut self, _: &[u8]) {
+                unreachable!();
+            }
+            fn write_u64(&mut self, i: u64) {
+                self.0 = if i % 2 == 0 { 0 } else { i };
+            }
+        }
+
+        type SkewedHashMap = HashMap<u64, u64, BuildHasherDefault<SkewedHasher>>;
+        let hot_group = |hashmap: &SkewedHashMap| {
+            let guard = crate::ebr::pin();
+            SkewedHashMap::cell_array_ref(hashmap.array.load(Acquire, &guard))
+                .calculate_cell_index(hashmap.hash(&0).0)
+                / CELLS_PER_GROUP
+        };
+
+        let hashmap: SkewedHashMap = HashMap::new(1 << 17, BuildHasherDefault::default())
+            .with_shrink_policy(ShrinkPolicy::Never)
+            .with_rehash_budget(0);
+        for key in 0..8192 {
+            assert!(hashmap.insert(key * 2 + 1, key).is_ok());
+        }
+        for key in 0..256 {
+            assert!(hashmap.insert(key * 2, key).is_ok());
+        }
+        let hot = hot_group(&hashmap);
+        let groups = hashmap.metrics_by_group();
+        assert_eq!(groups.len(), 4);
+        for (index, group) in groups.iter().enumerate() {
+            assert_eq!(group.first_cell(), index * CELLS_PER_GROUP);
+            assert_eq!(group.num_cells(), CELLS_PER_GROUP);
+            assert_eq!(group.lock_contentions(), 0);
+            assert_eq!(group.old_array_hits(), 0);
+            if index == hot {
+                assert!(group.long_probes() > 192, "{:?}", group);
+            } else {
+                assert_eq!(group.long_probes(), 0, "{:?}", group);
+            }
+        }
+
+        // An insertion waits for the hot Cell locked by the Accessor.
+        let accessor = hashmap.get(&0).unwrap();
+        std::thread::scope(|s| {
+            let inserter = s.spawn(|| hashmap.insert(512, 256).is_ok());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            drop(accessor);
+            assert!(inserter.join().unwrap());
+        });
+        for (index, group) in hashmap.metrics_by_group().iter().enumerate() {
+            assert_eq!(group.lock_contentions() > 0, index == hot, "{:?}", group);
+        }
+
+        // The counters belong to the new array, and the hot keys are found in the old array.
+        assert!(hashmap.try_reserve(1 << 18).is_ok());
+        let is_reset = |group: &GroupMetrics| {
+            group.lock_contentions() + group.long_probes() + group.old_array_hits() == 0
+        };
+        let groups = hashmap.metrics_by_group();
+        assert_eq!(groups.len(), 16);
+        assert!(groups.iter().all(is_reset));
+        for key in 0..256 {
+            hashmap.upsert(key * 2, key + 1);
+        }
+        let hot = hot_group(&hashmap);
+        for (index, group) in hashmap.metrics_by_group().iter().enumerate() {
+            if index == hot {
+                assert!(group.old_array_hits() >= 256, "{:?}", group);
+                assert!(group.long_probes() >= 256, "{:?}", group);
+            } else {
+                assert_eq!(group.old_array_hits(), 0, "{:?}", group);
+                assert_eq!(group.long_probes(), 0, "{:?}", group);
+            }
+        }
+
+        hashmap.reset_metrics();
+        assert!(hashmap.metrics_by_group().iter().all(is_reset));
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    #[test]
+    fn no_metrics() {
+        // Counters span multiple cache lines, therefore a HashMap without them is laid out exactly
+        // as its remaining fields are.
+        #[allow(dead_code)]
+        struct Layout {
+            array: Atomic<CellArray<u64, u64, CELL_SIZE, false, GlobalArrayAllocator>>,
+            minimum_capacity: usize,
+            additional_capacity: AtomicUsize,
+            reserved_capacity: AtomicUsize,
+            resizing_flag: ResizingFlag,
+            len_sampling_cells: usize,
+            len_sampling_deterministic: bool,
+            max_load_factor: f32,
+            shrink_policy: ShrinkPolicy,
+            rehash_budget: usize,
+            max_resizing_factor: usize,
+            resize_dampening: Duration,
+            resize_history: ResizeHistory,
+            hash_finalizer: bool,
+            num_removals: AtomicUsize,
+            #[cfg(feature = "background-worker")]
+            maintenance_signal: Arc<Signal>,
+            #[cfg(feature = "background-worker")]
+            maintenance_interval: Duration,
+            event_hook: Atomic<EventHook<u64, u64>>,
+            build_hasher: DefaultBuildHasher,
+        }
+        assert_eq!(
+            std::mem::size_of::<HashMap<u64, u64>>(),
+            std::mem::size_of::<Layout>()
+        );
+    }
+
+    #[test]
+    fn lock_entry() {
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(4096, RandomState::new());
+        let guard = crate::ebr::pin();
+        let array_ref = HashMap::<u64, u64>::cell_array_ref(hashmap.array.load(Acquire, &guard));
+        let cell_index = |key: &u64| array_ref.calculate_cell_index(hashmap.hash(key).0);
+        let other_key = (1..).find(|key| cell_index(key) != cell_index(&0)).unwrap();
+
+        let entry_guard = hashmap.lock_entry(0);
+        let inserted = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            // A key in another Cell is not blocked.
+            s.spawn(|| assert!(hashmap.insert(other_key, 1).is_ok()))
+                .join()
+                .unwrap();
+
+            // The reserved key is blocked until the EntryGuard is dropped.
+            let blocked = s.spawn(|| {
+                let result = hashmap.insert(0, 1).is_ok();
+                inserted.store(true, Release);
+                result
+            });
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(!inserted.load(Acquire));
+            assert!(entry_guard.insert(0).is_ok());
+            assert!(!blocked.join().unwrap());
+        });
+        assert_eq!(hashmap.read(&0, |_, value| *value), Some(0));
+        assert_eq!(hashmap.read(&other_key, |_, value| *value), Some(1));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn transfer() {
+        let num_threads = 4;
+        let num_iterations = 4096;
+        let hashmap: HashMap<u64, u64> = Default::default();
+        assert!(hashmap.insert(0, 0).is_ok());
+        let finished = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let (hashmap, finished) = (&hashmap, &finished);
+                s.spawn(move || {
+                    for i in 0..num_iterations {
+                        // The value moves back and forth between 0 and 1 while resizing.
+                        let from = (thread_id + i) as u64 % 2;
+                        let _result = hashmap.transfer(&from, 1 - from);
+                        let key = (thread_id * num_iterations + i) as u64 + 2;
+                        assert!(hashmap.insert(key, key).is_ok());
+                        if i % 2 == 0 {
+                            assert!(hashmap.remove(&key).is_some());
+                        }
+                    }
+                    finished.fetch_add(1, Release);
+                });
+            }
+            let (hashmap, finished) = (&hashmap, &finished);
+            s.spawn(move || {
+                let (hash_a, _) = hashmap.hash(&0);
+                let (hash_b, _) = hashmap.hash(&1);
+                while finished.load(Acquire) != num_threads {
+                    let guard = crate::ebr::pin();
+                    let (locker_a, locker_b) = hashmap.lock_pair(hash_a, hash_b, &guard);
+                    let locker_b = locker_b.as_ref().unwrap_or(&locker_a);
+                    let found_a = locker_a.cell_ref().search(&0, hash_a, &guard);
+                    let found_b = locker_b.cell_ref().search(&1, hash_b, &guard);
+                    assert!(found_a.is_some() != found_b.is_some());
                 }
-                if self.cell_locker.is_some() {
+            });
+        });
+        assert_eq!(hashmap.len(), num_threads * num_iterations / 2 + 1);
+    }
+
+    #[cfg(feature = "background-worker")]
+    #[test]
+    fn spawn_maintenance() {
+        let hashmap: Arc<HashMap<u64, u64>> =
+            Arc::new(HashMap::default().with_maintenance_interval(Duration::from_micros(100)));
+        for key in 0..1024 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        let handle = hashmap.spawn_maintenance();
+        for _ in 0..2 {
+            // The HashMap is idle after the array is replaced.
+            assert!(hashmap.try_reserve(hashmap.capacity() * 4).is_ok());
+            let deadline = std::time::Instant::now() + Duration::from_secs(10);
+            loop {
+                let guard = crate::ebr::pin();
+                let current_array = hashmap.array.load(Acquire, &guard);
+                if HashMap::<u64, u64>::cell_array_ref(current_array)
+                    .old_array(&guard)
+                    .is_null()
+                {
                     break;
                 }
-                // Reaching here indicates that self.array is updated.
+                assert!(std::time::Instant::now() < deadline);
+                std::thread::sleep(Duration::from_millis(1));
             }
         }
+        for key in 0..1024 {
+            assert_eq!(hashmap.read(&key, |_, value| *value), Some(key));
+        }
+        drop(handle);
 
-        if self.cell_iterator.is_none() {
-            // Starts scanning.
-            self.cell_iterator.replace(CellIterator::new(
-                unsafe {
-                    std::mem::transmute::<_, &'h Cell<K, V, CELL_SIZE, false>>(
-                        self.cell_locker.as_ref().unwrap().cell_ref(),
-                    )
-                },
-                self.guard.as_ref().unwrap(),
-            ));
+        // The thread exits when the HashMap is dropped.
+        let handle = hashmap.spawn_maintenance();
+        drop(hashmap);
+        drop(handle);
+    }
+
+    #[test]
+    fn contains() {
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(4096, RandomState::new());
+        for key in 0..1024 {
+            assert!(hashmap.insert(key, 0).is_ok());
+        }
+
+        // Replaces the array as resize does, so that all the keys are in the old array.
+        let guard = crate::ebr::pin();
+        let current_array = hashmap.array.load(Acquire, &guard);
+        hashmap.array.store(
+            Owned::new(CellArray::<u64, u64, CELL_SIZE, false>::new(
+                hashmap.capacity() * 2,
+                Atomic::from(current_array),
+            )),
+            Release,
+        );
+        assert!(
+            !HashMap::<u64, u64>::cell_array_ref(hashmap.array.load(Acquire, &guard))
+                .old_array(&guard)
+                .is_null()
+        );
+
+        for key in 0..1024 {
+            assert!(hashmap.contains(&key));
+            assert!(!hashmap.contains(&(key + 1024)));
+        }
+        assert!(
+            HashMap::<u64, u64>::cell_array_ref(hashmap.array.load(Acquire, &guard))
+                .old_array(&guard)
+                .is_null()
+        );
+    }
+
+    #[test]
+    fn contains_async() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(4096, RandomState::new());
+        for key in 0..1024 {
+            assert!(hashmap.insert(key, 0).is_ok());
+        }
+        assert_send(&hashmap.contains_async(&0));
+
+        // Replaces the array as resize does, so that all the keys are in the old array.
+        let guard = crate::ebr::pin();
+        let current_array = hashmap.array.load(Acquire, &guard);
+        hashmap.array.store(
+            Owned::new(CellArray::<u64, u64, CELL_SIZE, false>::new(
+                hashmap.capacity() * 2,
+                Atomic::from(current_array),
+            )),
+            Release,
+        );
+        for key in 0..1024 {
+            assert!(block_on(hashmap.contains_async(&key)));
+            assert!(!block_on(hashmap.contains_async(&(key + 1024))));
+        }
+
+        // The keys are read in the old array without being relocated.
+        assert!(
+            !HashMap::<u64, u64>::cell_array_ref(hashmap.array.load(Acquire, &guard))
+                .old_array(&guard)
+                .is_null()
+        );
+        drop(guard);
+
+        // The task waits for the Cell locked by the Accessor without blocking the thread.
+        let accessor = hashmap.get(&0).unwrap();
+        let released = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            let reader = s.spawn(|| {
+                let contains = block_on(hashmap.contains_async(&0));
+                (contains, released.load(Relaxed))
+            });
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            released.store(true, Relaxed);
+            drop(accessor);
+            assert_eq!(reader.join().unwrap(), (true, true));
+        });
+    }
+
+    #[test]
+    fn for_each_in_bucket() {
+        for new_capacity in [4096 * 4, 1024] {
+            let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(4096, RandomState::new());
+            for key in 0..1024 {
+                assert!(hashmap.insert(key, 0).is_ok());
+            }
+
+            // Replaces the array as resize does, so that all the keys are in the old array.
+            let guard = crate::ebr::pin();
+            let current_array = hashmap.array.load(Acquire, &guard);
+            hashmap.array.store(
+                Owned::new(CellArray::<u64, u64, CELL_SIZE, false>::new(
+                    new_capacity,
+                    Atomic::from(current_array),
+                )),
+                Release,
+            );
+            assert_eq!(hashmap.array_generation(), 1);
+
+            for key in 0..1024 {
+                let mut found = false;
+                hashmap.for_each_in_bucket(hashmap.bucket_of(&key), |k, _| found |= *k == key);
+                assert!(found);
+            }
+        }
+    }
+
+    #[test]
+    fn get_multi() {
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(4096, RandomState::new());
+        for key in 0..1024 {
+            assert!(hashmap.insert(key, key * 2).is_ok());
+        }
+        let keys: Vec<u64> = (0..64).map(|i| i * 32).chain(1024..1032).collect();
+        let guard = crate::ebr::pin();
+        let array_ref = HashMap::<u64, u64>::cell_array_ref(hashmap.array.load(Acquire, &guard));
+        let mut cell_indexes: Vec<usize> = keys
+            .iter()
+            .map(|key| array_ref.calculate_cell_index(hashmap.hash(key).0))
+            .collect();
+        cell_indexes.sort_unstable();
+        cell_indexes.dedup();
+
+        let num_locks = NUM_LOCK_ACQUISITIONS.with(|n| n.get());
+        let mut results = Vec::new();
+        hashmap.get_multi(&keys, |key, value| results.push((*key, value.copied())));
+        assert_eq!(
+            NUM_LOCK_ACQUISITIONS.with(|n| n.get()) - num_locks,
+            cell_indexes.len()
+        );
+        assert!(cell_indexes.len() < keys.len());
+
+        results.sort_unstable();
+        let mut expected: Vec<(u64, Option<u64>)> = keys
+            .iter()
+            .map(|key| (*key, hashmap.read(key, |_, value| *value)))
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn clear_chunked() {
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(16384, RandomState::new());
+        let array_size = hashmap.capacity() / CELL_SIZE;
+        for key in 0..4096 {
+            assert!(hashmap.insert(key, 0).is_ok());
         }
+        let budget = 16;
+        let mut num_calls = 0;
         loop {
-            if let Some(iterator) = self.cell_iterator.as_mut() {
-                // Proceeds to the next entry in the Cell.
-                if let Some(_) = iterator.next() {
-                    return Some(self.get());
-                }
+            let num_locks = NUM_LOCK_ACQUISITIONS.with(|n| n.get());
+            let work_remains = hashmap.clear_chunked(budget);
+            assert!(NUM_LOCK_ACQUISITIONS.with(|n| n.get()) - num_locks <= budget);
+            num_calls += 1;
+            if !work_remains {
+                break;
             }
-            // Proceeds to the next Cell.
-            let mut array_ref = unsafe { &*self.array_ptr };
-            self.cell_index += 1;
-            self.cell_iterator.take();
+        }
+        assert_eq!(num_calls, array_size / budget);
+        assert_eq!(hashmap.len(), 0);
+        assert!(hashmap.clear_chunked(budget));
+    }
 
-            if self.cell_index == array_ref.array_size() {
-                let current_array = self
-                    .hash_map
-                    .array
-                    .load(Acquire, self.guard.as_ref().unwrap());
-                if self.array_ptr == current_array.as_raw() {
-                    // Finished scanning the entire array.
-                    break;
-                }
+    #[test]
+    fn clear_chunked_non_send() {
+        let value = std::rc::Rc::new(0_u64);
+        let hashmap: HashMap<u64, std::rc::Rc<u64>> = HashMap::default();
+        for key in 0..256 {
+            assert!(hashmap.insert(key, value.clone()).is_ok());
+        }
+        while hashmap.clear_chunked(4) {}
+        assert_eq!(hashmap.len(), 0);
+        assert_eq!(std::rc::Rc::strong_count(&value), 1);
+    }
 
-                // Proceeds to the current array.
-                array_ref = unsafe { current_array.deref() };
-                self.array_ptr = current_array.as_raw();
-                self.cell_index = 0;
+    #[test]
+    fn clear_chunked_shrink() {
+        let hashmap: HashMap<u64, u64> = HashMap::default();
+        for key in 0..16384 {
+            assert!(hashmap.insert(key, 0).is_ok());
+        }
+        let array_size = hashmap.capacity() / CELL_SIZE;
+        let budget = 16;
+        for _ in 0..(array_size / budget * 15 / 16) {
+            assert!(hashmap.clear_chunked(budget));
+        }
+
+        // The cursor is beyond the end of the shrunk array.
+        let array_generation = hashmap.array_generation();
+        hashmap.resize(&crate::ebr::pin());
+        assert_ne!(hashmap.array_generation(), array_generation);
+        assert!(hashmap.capacity() / CELL_SIZE < array_size * 15 / 16);
+        while hashmap.clear_chunked(budget) {}
+        assert_eq!(hashmap.len(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn key_comparisons() {
+        thread_local! {
+            static NUM_COMPARISONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+
+        // Equality only counts comparisons, therefore it is consistent with the derived Hash.
+        #[allow(clippy::derived_hash_with_manual_eq)]
+        #[derive(Hash)]
+        struct LongKey([u64; 32]);
+
+        impl PartialEq for LongKey {
+            fn eq(&self, other: &Self) -> bool {
+                NUM_COMPARISONS.with(|n| n.set(n.get() + 1));
+                self.0 == other.0
             }
+        }
 
-            // Proceeds to the next Cell in the current array.
-            for index in self.cell_index..array_ref.array_size() {
-                let cell_ref = array_ref.cell(index);
-                if let Some(locker) = CellLocker::lock(cell_ref, self.guard.as_ref().unwrap()) {
-                    self.cell_index = index;
-                    self.cell_locker.replace(locker);
-                    self.cell_iterator
-                        .replace(CellIterator::new(cell_ref, self.guard.as_ref().unwrap()));
-                    break;
-                }
+        impl Eq for LongKey {}
+
+        let num_keys = 65536;
+        let hashmap: HashMap<LongKey, ()> = Default::default();
+        for key in 0..num_keys {
+            assert!(hashmap.insert(LongKey([key; 32]), ()).is_ok());
+        }
+
+        // Reads absent keys, so that every key comparison is caused by a false positive match of
+        // partial hash values.
+        NUM_COMPARISONS.with(|n| n.set(0));
+        for key in num_keys..num_keys * 2 {
+            assert!(!hashmap.contains(&LongKey([key; 32])));
+        }
+        let num_comparisons = NUM_COMPARISONS.with(|n| n.get());
+        let max_comparisons = if cfg!(feature = "wide-partial-hash") {
+            num_keys / 256
+        } else {
+            num_keys / 2
+        };
+        assert!(
+            num_comparisons <= max_comparisons as usize,
+            "{} > {}",
+            num_comparisons,
+            max_comparisons
+        );
+    }
+
+    #[test]
+    fn rehash_without_hashing() {
+        thread_local! {
+            static NUM_HASHES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+
+        #[derive(Eq, PartialEq)]
+        struct CountedKey(u64);
+
+        impl Hash for CountedKey {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                NUM_HASHES.with(|n| n.set(n.get() + 1));
+                self.0.hash(state);
             }
-            if self.cell_iterator.is_some() {
-                continue;
+        }
+
+        let num_keys = 4096;
+        let hashmap: HashMap<CountedKey, u64, RandomState> =
+            HashMap::new(num_keys, RandomState::new());
+        for key in 0..num_keys as u64 {
+            assert!(hashmap.insert(CountedKey(key), key).is_ok());
+        }
+        NUM_HASHES.with(|n| n.set(0));
+        assert!(hashmap.try_reserve(num_keys * 16).is_ok());
+        while hashmap.rehash(CELL_SIZE) {}
+        let num_hashes = NUM_HASHES.with(|n| n.get());
+
+        // Relocated keys are hashed again unless their hash values are stored.
+        if cfg!(feature = "full-hash") {
+            assert_eq!(num_hashes, 0);
+        } else {
+            assert_eq!(num_hashes, num_keys);
+        }
+        for key in 0..num_keys as u64 {
+            assert_eq!(hashmap.read(&CountedKey(key), |_, v| *v), Some(key));
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn lazy_segments() {
+        let hashmap: HashMap<usize, usize, RandomState> = HashMap::new(1 << 26, RandomState::new());
+        let num_allocated_segments = || {
+            let guard = crate::ebr::pin();
+            HashMap::<usize, usize>::cell_array_ref(hashmap.array.load(Acquire, &guard))
+                .num_allocated_segments()
+        };
+        assert_eq!(hashmap.capacity(), 1 << 26);
+        assert_eq!(num_allocated_segments(), 1);
+
+        let num_threads = 4;
+        let num_keys = 16;
+        std::thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let hashmap = &hashmap;
+                s.spawn(move || {
+                    for key in thread_id * num_keys..(thread_id + 1) * num_keys {
+                        assert!(hashmap.insert(key, key).is_ok());
+                    }
+                });
             }
-            break;
+        });
+        assert!(num_allocated_segments() <= num_threads * num_keys + 1);
+        for key in 0..num_threads * num_keys {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
         }
-        self.cell_locker.take();
-        None
+        assert_eq!(hashmap.len(), num_threads * num_keys);
+        let num_segments = num_allocated_segments();
+        assert_eq!(hashmap.clear(), num_threads * num_keys);
+        assert_eq!(hashmap.len(), 0);
+        assert_eq!(num_allocated_segments(), num_segments);
     }
-}
 
-impl<'h, K, V, H> FusedIterator for Accessor<'h, K, V, H>
-where
-    K: Eq + Hash + Sync,
-    V: Sync,
-    H: BuildHasher,
-{
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn segmented_array() {
+        let num_keys = 1 << 14;
+        let hashmap: HashMap<usize, usize> = HashMap::default().with_rehash_budget(1);
+        let num_allocated_segments = || {
+            let guard = crate::ebr::pin();
+            HashMap::<usize, usize>::cell_array_ref(hashmap.array.load(Acquire, &guard))
+                .num_allocated_segments()
+        };
+
+        // The array grows into a segmented array while the keys are being inserted.
+        let num_threads = 4;
+        std::thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let hashmap = &hashmap;
+                s.spawn(move || {
+                    for key in (thread_id..num_keys).step_by(num_threads) {
+                        assert!(hashmap.insert(key, key).is_ok());
+                    }
+                });
+            }
+        });
+        while hashmap.rehash(64) {}
+        assert!(num_allocated_segments() > 1);
+        assert_eq!(hashmap.len(), num_keys);
+        for key in 0..num_keys {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+
+        // The array shrinks while most of the keys are being removed.
+        std::thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let hashmap = &hashmap;
+                s.spawn(move || {
+                    for key in (thread_id..num_keys).step_by(num_threads) {
+                        if key % 64 != 0 {
+                            assert!(hashmap.remove(&key).is_some());
+                        }
+                    }
+                });
+            }
+        });
+        while hashmap.rehash(64) {}
+        assert_eq!(hashmap.len(), num_keys / 64);
+        for key in 0..num_keys {
+            assert_eq!(hashmap.contains(&key), key % 64 == 0);
+        }
+        assert_eq!(hashmap.clear(), num_keys / 64);
+        assert_eq!(hashmap.len(), 0);
+    }
+
+    #[test]
+    fn resize_estimation() {
+        // Picks keys such that each Cell of an array of `1 << lb_num_cells` Cells, or fewer,
+        // receives the same number of keys, so that the estimates are exact.
+        let even_keys =
+            |hashmap: &HashMap<u64, u64, RandomState>, lb_num_cells: u32, keys_per_cell: usize| {
+                let mut counts = vec![0; 1 << lb_num_cells];
+                let mut keys = Vec::with_capacity(counts.len() * keys_per_cell);
+                let mut key = 0;
+                while keys.len() < counts.len() * keys_per_cell {
+                    let cell_index = (hashmap.hash(&key).0 >> (64 - lb_num_cells)) as usize;
+                    if counts[cell_index] < keys_per_cell {
+                        counts[cell_index] += 1;
+                        keys.push(key);
+                    }
+                    key += 1;
+                }
+                keys
+            };
+        let insert_all = |hashmap: &HashMap<u64, u64, RandomState>, keys: &[u64]| {
+            for key in keys {
+                assert!(hashmap.insert(*key, *key).is_ok());
+            }
+        };
+
+        // The load factor is 1/2, and the array stays put.
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(4096, RandomState::new());
+        insert_all(&hashmap, &even_keys(&hashmap, 7, 16));
+        hashmap.resize(&crate::ebr::pin());
+        assert_eq!(hashmap.capacity(), 4096);
+
+        // The load factor is 29/32, and the array grows to accommodate 15/8 times the entries.
+        let hashmap: HashMap<u64, u64, RandomState> =
+            HashMap::new(4096, RandomState::new()).with_rehash_budget(0);
+        let keys = even_keys(&hashmap, 7, 29);
+        insert_all(&hashmap, &keys);
+        assert_eq!(hashmap.capacity(), 4096);
+        hashmap.resize(&crate::ebr::pin());
+        assert_eq!(hashmap.capacity(), 8192);
+
+        // The entries in the old array are taken into account.
+        let guard = crate::ebr::pin();
+        let current_array_ref =
+            HashMap::<u64, u64>::cell_array_ref(hashmap.array.load(Acquire, &guard));
+        assert!(!current_array_ref.old_array(&guard).is_null());
+        assert_eq!(
+            HashMap::<u64, u64>::estimate(current_array_ref, 32, &guard),
+            (keys.len(), None)
+        );
+        while hashmap.rehash(CELL_SIZE) {}
+        for key in &keys {
+            assert_eq!(hashmap.read(key, |_, v| *v), Some(*key));
+        }
+
+        // The load factor drops to 1/16 once the reserved capacity is released, and the array
+        // shrinks to fit.
+        let hashmap: HashMap<u64, u64, RandomState> = HashMap::new(64, RandomState::new());
+        let ticket = hashmap.reserve(4096);
+        assert_eq!(hashmap.capacity(), 8192);
+        while hashmap.rehash(CELL_SIZE) {}
+        insert_all(&hashmap, &even_keys(&hashmap, 8, 2));
+        drop(ticket);
+        assert_eq!(hashmap.capacity(), 512);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn batched_retirement() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct D;
+        impl Drop for D {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Relaxed);
+            }
+        }
+
+        let num_entries = 65536;
+        let hashmap: HashMap<usize, D> = HashMap::default();
+        for key in 0..num_entries {
+            assert!(hashmap.insert(key, D).is_ok());
+        }
+        let num_retired = NUM_RETIRED.with(|n| n.get());
+        assert_eq!(
+            hashmap.retain(|key, _| *key < 1024),
+            (1024, num_entries - 1024)
+        );
+        while hashmap.rehash(usize::MAX) {}
+        assert_eq!(DROPPED.load(Relaxed), num_entries - 1024);
+        assert!(NUM_RETIRED.with(|n| n.get()) - num_retired <= 4);
+
+        let num_retired = NUM_RETIRED.with(|n| n.get());
+        assert_eq!(hashmap.clear(), 1024);
+        while hashmap.rehash(usize::MAX) {}
+        assert_eq!(DROPPED.load(Relaxed), num_entries);
+        assert!(NUM_RETIRED.with(|n| n.get()) - num_retired <= 4);
+    }
 }
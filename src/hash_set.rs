@@ -0,0 +1,745 @@
+#[cfg(feature = "serde")]
+use crate::common::deserialize::cautious_size_hint;
+use crate::common::hash_table::DefaultBuildHasher;
+use crate::hash_map::{Accessor, HashMap};
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::{FromIterator, FusedIterator};
+
+/// A scalable concurrent hash set data structure.
+///
+/// scc::HashSet is a thin wrapper of [`HashMap`] of which the value type is `()`, therefore it
+/// inherits all the characteristics of scc::HashMap. Since `()` is zero-sized, each entry only
+/// costs the key and the metadata.
+///
+/// ## The key statistics for scc::HashSet
+/// * The expected size of metadata for a single key: 2-byte.
+/// * The expected number of atomic operations required for an operation on a single key: 2.
+/// * The expected number of atomic variables accessed during a single key operation: 1.
+pub struct HashSet<T, H = DefaultBuildHasher>
+where
+    T: Eq + Hash,
+    H: BuildHasher,
+{
+    hash_map: HashMap<T, (), H>,
+}
+
+impl<T> Default for HashSet<T, DefaultBuildHasher>
+where
+    T: Eq + Hash,
+{
+    /// Creates a HashSet instance with the default parameters.
+    ///
+    /// The default hash builder is [`DefaultBuildHasher`](crate::DefaultBuildHasher), and the
+    /// default capacity is 64.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// let result = hashset.capacity();
+    /// assert_eq!(result, 64);
+    /// ```
+    fn default() -> Self {
+        HashSet {
+            hash_map: HashMap::default(),
+        }
+    }
+}
+
+impl<T, H> HashSet<T, H>
+where
+    T: Eq + Hash,
+    H: BuildHasher,
+{
+    /// Creates an empty HashSet instance with the given capacity and build hasher.
+    ///
+    /// The actual capacity is equal to or greater than the given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashset: HashSet<u64, RandomState> = HashSet::new(1000, RandomState::new());
+    ///
+    /// let result = hashset.capacity();
+    /// assert_eq!(result, 1024);
+    /// ```
+    pub fn new(capacity: usize, build_hasher: H) -> HashSet<T, H> {
+        HashSet {
+            hash_map: HashMap::new(capacity, build_hasher),
+        }
+    }
+
+    /// Configures how [`HashSet::estimated_len`] samples Cells.
+    ///
+    /// See [`HashMap::with_len_sampling`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = HashSet::default().with_len_sampling(4, false);
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(hashset.estimated_len() > 0);
+    /// ```
+    pub fn with_len_sampling(self, num_cells: usize, deterministic: bool) -> Self {
+        HashSet {
+            hash_map: self.hash_map.with_len_sampling(num_cells, deterministic),
+        }
+    }
+
+    /// Inserts a key into the HashSet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error along with the supplied key if the key exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails, or the number of entries in the target cell reaches u32::MAX.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert_eq!(hashset.insert(1).err().unwrap(), 1);
+    /// ```
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        self.hash_map
+            .insert(key, ())
+            .map(|_| ())
+            .map_err(|(_, key, _)| key)
+    }
+
+    /// Removes a key from the HashSet.
+    ///
+    /// It returns `true` if the key existed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(!hashset.remove(&1));
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(hashset.remove(&1));
+    /// ```
+    pub fn remove<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.hash_map.remove(key).is_some()
+    }
+
+    /// Inserts a key into the HashSet, replacing the stored key that is equal to it.
+    ///
+    /// It returns the replaced key, which is useful when the equality of keys does not take all
+    /// the fields into account. The Cell is locked only once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails, or the number of entries in the target cell reaches u32::MAX.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.replace(1).is_none());
+    /// assert_eq!(hashset.replace(1), Some(1));
+    /// assert_eq!(hashset.len(), 1);
+    /// ```
+    pub fn replace(&self, key: T) -> Option<T> {
+        self.hash_map.replace_entry(key, ()).map(|(key, _)| key)
+    }
+
+    /// Removes a key from the HashSet, and returns the stored key.
+    ///
+    /// The Cell is locked only once.
+    ///
+    /// # Errors
+    ///
+    /// Returns None if the key does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<String> = Default::default();
+    ///
+    /// assert!(hashset.take("one").is_none());
+    /// assert!(hashset.insert("one".to_string()).is_ok());
+    /// assert_eq!(hashset.take("one").unwrap(), "one");
+    /// assert!(!hashset.contains("one"));
+    /// ```
+    pub fn take<Q>(&self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.hash_map.remove_entry(key).map(|(key, _)| key)
+    }
+
+    /// Checks if the key exists.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(!hashset.contains(&1));
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(hashset.contains(&1));
+    /// ```
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.hash_map.contains(key)
+    }
+
+    /// Finds any key that satisfies the given predicate, and reads it.
+    ///
+    /// It scans the HashSet from a randomly chosen Cell, and stops at the first key that satisfies
+    /// the predicate. The Cell remains shared-locked while the given closure is being invoked.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.any(|key| *key % 2 == 0, |key| *key).is_none());
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(hashset.insert(2).is_ok());
+    /// assert_eq!(hashset.any(|key| *key % 2 == 0, |key| *key), Some(2));
+    /// ```
+    pub fn any<P: Fn(&T) -> bool, R, F: FnOnce(&T) -> R>(&self, pred: P, f: F) -> Option<R> {
+        self.hash_map.any(|key, _| pred(key), |key, _| f(key))
+    }
+
+    /// Retains keys that satisfy the given predicate.
+    ///
+    /// It returns the number of keys remaining and removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(hashset.insert(2).is_ok());
+    ///
+    /// let result = hashset.retain(|key| *key == 1);
+    /// assert_eq!(result, (1, 1));
+    ///
+    /// assert!(hashset.contains(&1));
+    /// assert!(!hashset.contains(&2));
+    /// ```
+    pub fn retain<F: Fn(&T) -> bool>(&self, f: F) -> (usize, usize) {
+        self.hash_map.retain(|key, _| f(key))
+    }
+
+    /// Clears all the keys.
+    ///
+    /// It returns the number of removed keys.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert_eq!(hashset.clear(), 1);
+    /// assert!(!hashset.contains(&1));
+    /// ```
+    pub fn clear(&self) -> usize {
+        self.hash_map.clear()
+    }
+
+    /// Returns the number of keys.
+    ///
+    /// It scans the entire array to calculate the number of keys.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert_eq!(hashset.len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.hash_map.len()
+    }
+
+    /// Returns `true` if the HashSet contains no keys.
+    ///
+    /// Unlike [`HashSet::len`], it stops scanning the HashSet as soon as it finds a key.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.is_empty());
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(!hashset.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.hash_map.scan(|_, _| false)
+    }
+
+    /// Estimates the number of keys in the HashSet by sampling Cells.
+    ///
+    /// See [`HashMap::estimated_len`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert_eq!(hashset.estimated_len(), 1);
+    /// ```
+    pub fn estimated_len(&self) -> usize {
+        self.hash_map.estimated_len()
+    }
+
+    /// Returns the capacity of the HashSet.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hashset: HashSet<u64, RandomState> = HashSet::new(1000000, RandomState::new());
+    /// assert_eq!(hashset.capacity(), 1048576);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.hash_map.capacity()
+    }
+
+    /// Returns an iterator over the keys.
+    ///
+    /// The iterator locks a Cell at a time, and therefore the other threads are blocked when
+    /// they try to access keys in the Cell that the iterator is visiting.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// for key in 0..4 {
+    ///     assert!(hashset.insert(key).is_ok());
+    /// }
+    ///
+    /// let sum: u64 = hashset.iter().sum();
+    /// assert_eq!(sum, 6);
+    /// ```
+    pub fn iter(&self) -> HashSetIter<'_, T, H> {
+        HashSetIter {
+            accessor: self.hash_map.iter(),
+        }
+    }
+
+    /// Invokes the given closure for each key.
+    ///
+    /// The closure is invoked while a Cell is shared-locked, therefore it must not modify the
+    /// HashSet.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// for key in 0..4 {
+    ///     assert!(hashset.insert(key).is_ok());
+    /// }
+    ///
+    /// let mut sum = 0;
+    /// hashset.for_each(|key| sum += *key);
+    /// assert_eq!(sum, 6);
+    /// ```
+    pub fn for_each<F: FnMut(&T)>(&self, mut f: F) {
+        self.hash_map.scan(|key, _| {
+            f(key);
+            true
+        });
+    }
+
+    /// Inserts clones of all the keys in the HashSet and the other HashSet into the target.
+    ///
+    /// It returns the number of keys newly inserted into the target. Keys are copied a Cell at a
+    /// time, and no Cells are locked while the target is being updated, therefore the target may
+    /// be the HashSet or the other HashSet.
+    ///
+    /// The result is not a snapshot: keys that are inserted or removed while the method is
+    /// running may or may not be reflected in the target.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    /// let other: HashSet<u64> = Default::default();
+    /// let target: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(hashset.insert(2).is_ok());
+    /// assert!(other.insert(2).is_ok());
+    /// assert!(other.insert(3).is_ok());
+    ///
+    /// assert_eq!(hashset.union_into(&other, &target), 3);
+    /// assert_eq!(target.len(), 3);
+    /// ```
+    pub fn union_into<H2: BuildHasher, H3: BuildHasher>(
+        &self,
+        other: &HashSet<T, H2>,
+        target: &HashSet<T, H3>,
+    ) -> usize
+    where
+        T: Clone,
+    {
+        let mut num_inserted = 0;
+        let mut insert = |keys: &[T]| {
+            for key in keys {
+                if target.insert(key.clone()).is_ok() {
+                    num_inserted += 1;
+                }
+            }
+        };
+        self.hash_map.scan_detached(&mut insert);
+        other.hash_map.scan_detached(&mut insert);
+        num_inserted
+    }
+
+    /// Invokes the given closure for each key that exists in both the HashSet and the other
+    /// HashSet.
+    ///
+    /// The smaller HashSet is scanned, and each key is looked up in the larger HashSet. Keys are
+    /// copied a Cell at a time, and the closure is invoked without any Cells locked.
+    ///
+    /// The result is not a snapshot: keys that are inserted or removed while the method is
+    /// running may or may not be passed to the closure, and a key is passed to the closure if it
+    /// was found in both HashSets at the moment each of them was examined.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    /// let other: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(hashset.insert(2).is_ok());
+    /// assert!(other.insert(2).is_ok());
+    /// assert!(other.insert(3).is_ok());
+    ///
+    /// let mut intersection = Vec::new();
+    /// hashset.intersection_for_each(&other, |key| intersection.push(*key));
+    /// assert_eq!(intersection, vec![2]);
+    /// ```
+    pub fn intersection_for_each<H2: BuildHasher, F: FnMut(&T)>(
+        &self,
+        other: &HashSet<T, H2>,
+        mut f: F,
+    ) where
+        T: Clone,
+    {
+        if self.len() <= other.len() {
+            self.hash_map.scan_detached(|keys| {
+                keys.iter()
+                    .filter(|key| other.contains(key))
+                    .for_each(&mut f);
+            });
+        } else {
+            other.hash_map.scan_detached(|keys| {
+                keys.iter()
+                    .filter(|key| self.contains(key))
+                    .for_each(&mut f);
+            });
+        }
+    }
+
+    /// Invokes the given closure for each key that exists in the HashSet but not in the other
+    /// HashSet.
+    ///
+    /// The HashSet is scanned, and each key is looked up in the other HashSet. Keys are copied a
+    /// Cell at a time, and the closure is invoked without any Cells locked.
+    ///
+    /// The result is not a snapshot: keys that are inserted or removed while the method is
+    /// running may or may not be passed to the closure.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    /// let other: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert!(hashset.insert(2).is_ok());
+    /// assert!(other.insert(2).is_ok());
+    /// assert!(other.insert(3).is_ok());
+    ///
+    /// let mut difference = Vec::new();
+    /// hashset.difference_for_each(&other, |key| difference.push(*key));
+    /// assert_eq!(difference, vec![1]);
+    /// ```
+    pub fn difference_for_each<H2: BuildHasher, F: FnMut(&T)>(
+        &self,
+        other: &HashSet<T, H2>,
+        mut f: F,
+    ) where
+        T: Clone,
+    {
+        self.hash_map.scan_detached(|keys| {
+            keys.iter()
+                .filter(|key| !other.contains(key))
+                .for_each(&mut f);
+        });
+    }
+}
+
+impl<T, H> fmt::Debug for HashSet<T, H>
+where
+    T: fmt::Debug + Eq + Hash,
+    H: BuildHasher,
+{
+    /// Formats all the keys of the HashSet.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = Default::default();
+    ///
+    /// assert!(hashset.insert(1).is_ok());
+    /// assert_eq!(format!("{:?}", hashset), "{1}");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug_set = f.debug_set();
+        self.for_each(|key| {
+            debug_set.entry(key);
+        });
+        debug_set.finish()
+    }
+}
+
+impl<T, H> FromIterator<T> for HashSet<T, H>
+where
+    T: Eq + Hash,
+    H: BuildHasher + Default,
+{
+    /// Creates a HashSet from an iterator.
+    ///
+    /// The HashSet is sized according to the lower bound of the size hint before any keys are
+    /// inserted.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let hashset: HashSet<u64> = (0..4).collect();
+    /// assert_eq!(hashset.len(), 4);
+    /// ```
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut hashset = HashSet::new(0, H::default());
+        hashset.extend(iter);
+        hashset
+    }
+}
+
+impl<T, H> Extend<T> for HashSet<T, H>
+where
+    T: Eq + Hash,
+    H: BuildHasher,
+{
+    /// Inserts all the keys of an iterator into the HashSet.
+    ///
+    /// The HashSet grows according to the lower bound of the size hint before any keys are
+    /// inserted. Keys that already exist are dropped.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let mut hashset: HashSet<u64> = Default::default();
+    ///
+    /// hashset.extend(0..4);
+    /// hashset.extend(2..6);
+    /// assert_eq!(hashset.len(), 6);
+    /// ```
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let _result = self.hash_map.try_reserve(iter.size_hint().0);
+        iter.for_each(|key| {
+            let _result = self.insert(key);
+        });
+    }
+}
+
+impl<'a, T, H> Extend<&'a T> for HashSet<T, H>
+where
+    T: 'a + Copy + Eq + Hash,
+    H: BuildHasher,
+{
+    /// Inserts copies of all the keys of an iterator into the HashSet.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::HashSet;
+    ///
+    /// let mut hashset: HashSet<u64> = Default::default();
+    ///
+    /// hashset.extend([1, 2, 3].iter());
+    /// assert!(hashset.contains(&3));
+    /// ```
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T, H> serde::Serialize for HashSet<T, H>
+where
+    T: Eq + Hash + serde::Serialize,
+    H: BuildHasher,
+{
+    /// Serializes the HashSet as a sequence.
+    ///
+    /// The length of the sequence is calculated before the keys are serialized, therefore the
+    /// HashSet must not be modified during serialization if the format requires the length of a
+    /// sequence to be exact.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        let mut result = Ok(());
+        self.hash_map.scan(|key, _| {
+            result = seq.serialize_element(key);
+            result.is_ok()
+        });
+        result?;
+        seq.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, H> serde::Deserialize<'de> for HashSet<T, H>
+where
+    T: Eq + Hash + serde::Deserialize<'de>,
+    H: BuildHasher + Default,
+{
+    /// Deserializes a HashSet from a sequence.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SeqVisitor<T, H>(std::marker::PhantomData<(T, H)>);
+
+        impl<'de, T, H> serde::de::Visitor<'de> for SeqVisitor<T, H>
+        where
+            T: Eq + Hash + serde::Deserialize<'de>,
+            H: BuildHasher + Default,
+        {
+            type Value = HashSet<T, H>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a sequence")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let hashset = HashSet::new(0, H::default());
+                let _result = hashset
+                    .hash_map
+                    .try_reserve(cautious_size_hint::<T>(seq.size_hint()));
+                while let Some(key) = seq.next_element()? {
+                    let _result = hashset.insert(key);
+                }
+                Ok(hashset)
+            }
+        }
+
+        deserializer.deserialize_seq(SeqVisitor(std::marker::PhantomData))
+    }
+}
+
+/// HashSetIter is an iterator over the keys of a [`HashSet`].
+///
+/// It is returned by [`HashSet::iter`].
+pub struct HashSetIter<'h, T, H>
+where
+    T: Eq + Hash,
+    H: BuildHasher,
+{
+    accessor: Accessor<'h, T, (), H>,
+}
+
+impl<'h, T, H> Iterator for HashSetIter<'h, T, H>
+where
+    T: Eq + Hash,
+    H: BuildHasher,
+{
+    type Item = &'h T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.accessor.next().map(|(key, _)| key)
+    }
+}
+
+impl<'h, T, H> FusedIterator for HashSetIter<'h, T, H>
+where
+    T: Eq + Hash,
+    H: BuildHasher,
+{
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn from_iter_resizes() {
+        // The array is reserved in steps of at most 64x: 64, 4096, 262144, and then 2097152.
+        let num_keys = 1_000_000;
+        let hashset: HashSet<usize> = (0..num_keys).collect();
+        assert_eq!(hashset.len(), num_keys);
+        assert!(hashset.hash_map.array_generation() <= 3);
+
+        let mut hashset: HashSet<usize> = Default::default();
+        hashset.extend((0..num_keys).collect::<Vec<_>>().iter());
+        assert_eq!(hashset.len(), num_keys);
+        assert!(hashset.hash_map.array_generation() <= 3);
+    }
+}
@@ -3,32 +3,207 @@
 //! # scc::HashMap
 //! [`scc::HashMap`] is a concurrent hash map that dynamically grows and shrinks without blocking other operations.
 //!
+//! # scc::HashSet
+//! [`scc::HashSet`] is a concurrent hash set that is built on scc::HashMap.
+//!
+//! # scc::HashCache
+//! [`scc::HashCache`] is a concurrent cache that is built on scc::HashMap, and evicts least recently used entries.
+//!
 //! # scc::HashIndex
 //! [`scc::HashIndex`] is a read-optimized concurrent hash index that is similar to scc::HashMap.
 //!
+//! # scc::HashIndexSet
+//! [`scc::HashIndexSet`] is a read-optimized concurrent hash set that is built on scc::HashIndex.
+//!
 //! # scc::TreeIndex
 //! [`scc::TreeIndex`] is a read-optimized concurrent B+ tree index.
 //!
+//! # scc::Queue
+//! [`scc::Queue`] is a concurrent MPMC queue.
+//!
+//! # scc::Stack
+//! [`scc::Stack`] is a concurrent stack.
+//!
+//! # scc::Bag
+//! [`scc::Bag`] is a concurrent unordered container.
+//!
+//! # scc::ebr
+//! [`scc::ebr`] is the epoch-based memory reclamation mechanism that the containers rely on.
+//!
 //! [`scc::HashMap`]: hash_map::HashMap
+//! [`scc::HashSet`]: hash_set::HashSet
+//! [`scc::HashCache`]: hash_cache::HashCache
 //! [`scc::HashIndex`]: hash_index::HashIndex
+//! [`scc::HashIndexSet`]: hash_index_set::HashIndexSet
 //! [`scc::TreeIndex`]: tree_index::TreeIndex
+//! [`scc::Queue`]: queue::Queue
+//! [`scc::Stack`]: stack::Stack
+//! [`scc::Bag`]: bag::Bag
+//! [`scc::ebr`]: ebr
+//!
+//! # `no_std` support
+//! The crate can be built without the standard library by disabling the default `std` feature
+//! and enabling the `alloc` feature, in which case [`scc::ebr`], [`scc::HashIndex`],
+//! [`scc::Queue`], and [`scc::Stack`] are available. [`scc::HashIndex`] then requires an
+//! explicit [`BuildHasher`](core::hash::BuildHasher), since the default `RandomState` is only
+//! provided by the standard library.
+//!
+//! # Hashing
+//! The hash containers use [`DefaultBuildHasher`] unless a
+//! [`BuildHasher`](core::hash::BuildHasher) is specified, which is `RandomState` of the standard
+//! library, i.e., SipHash keyed with random seeds, and the containers additionally mix the bits
+//! of each hash value so that a weak [`BuildHasher`](core::hash::BuildHasher) does not cluster
+//! keys. [`DefaultBuildHasher`] is a [`BuildHasher`](core::hash::BuildHasher), not a
+//! [`Hasher`](core::hash::Hasher) like `std::hash::DefaultHasher`.
+//!
+//! The opt-in `fast-hash` feature makes [`DefaultBuildHasher`] an alias of the
+//! [`foldhash`](https://docs.rs/foldhash) `fast::RandomState`, which hashes integer keys with a
+//! couple of multiplications, and lets the containers created by `Default::default()` skip the
+//! additional bit mixing, since foldhash already spreads the bits of keys over the whole hash
+//! value. Its seeds are random, therefore the hash values cannot be predicted in advance, but,
+//! unlike SipHash, it is not designed to withstand an attacker who can observe the behavior of
+//! the containers and keep submitting keys; the feature should be left disabled if the keys come
+//! from untrusted sources.
+//!
+//! # Tracing
+//! The opt-in `tracing` feature makes the hash containers emit [`tracing`](https://docs.rs/tracing)
+//! events with the `scc` target when the array is resized, when the relocation of entries from
+//! the old array makes progress or completes, when a Cell overflows into a linked entry array,
+//! and when `HashCache` evicts an entry. Each event carries the address of the container in the
+//! `container` field. Without the feature, no code is generated for the events.
+//!
+//! # Miri
+//! The pointers managed by [`scc::ebr`] keep their provenance when tagged, so the unit tests are
+//! compatible with the strict provenance checks of [Miri](https://github.com/rust-lang/miri). The
+//! long-running tests are ignored under Miri, and the rest are run by
+//! `MIRIFLAGS="-Zmiri-strict-provenance" cargo +nightly miri test --lib`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(not(feature = "alloc"))]
+compile_error!("either the `std` or the `alloc` feature must be enabled");
+
+extern crate alloc;
 
 // Common modules.
+pub mod ebr;
+pub use ebr::flush;
+pub use ebr::pin;
+pub use ebr::Guard;
+pub use ebr::Ref;
+
 mod common;
+pub use common::allocator::ArrayAllocator;
+pub use common::allocator::GlobalArrayAllocator;
+#[cfg(feature = "serde")]
+pub use common::deserialize::DeserializeInto;
+#[cfg(feature = "serde")]
+pub use common::deserialize::DuplicateKeyPolicy;
+pub use common::hash_table::DefaultBuildHasher;
+pub use common::hash_table::MemoryFootprint;
+#[cfg(not(feature = "std"))]
+pub use common::hash_table::NoDefaultBuildHasher;
+pub use common::hash_table::ShrinkPolicy;
+pub use common::hash_table::TryReserveError;
+#[cfg(feature = "background-worker")]
+pub use common::maintenance::MaintenanceHandle;
+#[cfg(feature = "metrics")]
+pub use common::metrics::CacheStatistics;
+#[cfg(feature = "metrics")]
+pub use common::metrics::GroupMetrics;
+#[cfg(feature = "metrics")]
+pub use common::metrics::Metrics;
 
 // scc::HashMap.
+#[cfg(feature = "std")]
 mod hash_map;
+#[cfg(feature = "std")]
 pub use hash_map::Accessor;
+#[cfg(feature = "std")]
+pub use hash_map::CasError;
+#[cfg(feature = "std")]
+pub use hash_map::Drain;
+#[cfg(feature = "std")]
+pub use hash_map::Entries;
+#[cfg(feature = "std")]
+pub use hash_map::EntryGuard;
+#[cfg(feature = "std")]
+pub use hash_map::Event;
+#[cfg(feature = "std")]
 pub use hash_map::HashMap;
+#[cfg(feature = "std")]
+pub use hash_map::IntoIter;
+#[cfg(feature = "std")]
+pub use hash_map::Keys;
+#[cfg(feature = "std")]
+pub use hash_map::RemoveIfResult;
+#[cfg(feature = "std")]
+pub use hash_map::Statistics;
+#[cfg(feature = "std")]
 pub use hash_map::Ticket;
+#[cfg(feature = "std")]
+pub use hash_map::TransferError;
+#[cfg(feature = "std")]
+pub use hash_map::Values;
+
+// scc::HashSet.
+#[cfg(feature = "std")]
+mod hash_set;
+#[cfg(feature = "std")]
+pub use hash_set::HashSet;
+#[cfg(feature = "std")]
+pub use hash_set::HashSetIter;
+
+// scc::HashCache.
+#[cfg(feature = "std")]
+mod hash_cache;
+#[cfg(feature = "std")]
+pub use hash_cache::HashCache;
 
 // scc::HashIndex.
 mod hash_index;
+pub use hash_index::CellEntries;
 pub use hash_index::HashIndex;
 pub use hash_index::Visitor;
 
+// scc::HashIndexSet.
+#[cfg(feature = "std")]
+mod hash_index_set;
+#[cfg(feature = "std")]
+pub use hash_index_set::HashIndexSet;
+#[cfg(feature = "std")]
+pub use hash_index_set::HashIndexSetIter;
+#[cfg(feature = "std")]
+pub use hash_index_set::PeekGuard;
+
 // scc::TreeIndex.
+#[cfg(feature = "std")]
 mod tree_index;
+#[cfg(feature = "std")]
 pub use tree_index::Range;
+#[cfg(feature = "std")]
 pub use tree_index::Scanner;
+#[cfg(feature = "std")]
 pub use tree_index::TreeIndex;
+#[cfg(feature = "std")]
+pub use tree_index::TreeStatistics;
+#[cfg(feature = "debug-tools")]
+pub use tree_index::TreeValidationError;
+
+// scc::Queue.
+mod queue;
+pub use queue::Queue;
+pub use queue::QueueIter;
+
+// scc::Stack.
+mod stack;
+pub use stack::Stack;
+pub use stack::StackDrain;
+
+// scc::Bag.
+#[cfg(feature = "std")]
+mod bag;
+#[cfg(feature = "std")]
+pub use bag::Bag;
+#[cfg(feature = "std")]
+pub use bag::BagIter;
@@ -0,0 +1,296 @@
+use crate::common::node::Node;
+use crate::ebr::{Atomic, Domain, Guard, Owned, Ref, Shared};
+
+use core::iter::FusedIterator;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
+
+/// A concurrent unbounded MPMC queue.
+///
+/// scc::Queue is a Michael-Scott queue of which the nodes are reclaimed by the same epoch-based
+/// reclamation mechanism that the other containers use, and all the operations are lock-free.
+/// The values can be read by [`Queue::peek`] and [`Queue::iter`] in place while the thread is
+/// pinned, therefore a popped value is not moved out of the Queue: [`Queue::pop`] returns a clone
+/// of it, and the value in the Queue is dropped later once no thread can read it.
+///
+/// ## The key statistics for scc::Queue
+/// * The size of the metadata for a single value: one word, and another word if `Option<T>` is
+///   larger than `T`.
+/// * The number of atomic read-modify-write operations for a single push: three.
+pub struct Queue<T> {
+    head: Atomic<Node<Option<T>>>,
+    tail: Atomic<Node<Option<T>>>,
+    len: AtomicUsize,
+}
+
+impl<T> Default for Queue<T> {
+    /// Creates an empty Queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Queue;
+    ///
+    /// let queue: Queue<u64> = Default::default();
+    ///
+    /// assert!(queue.is_empty());
+    /// ```
+    fn default() -> Self {
+        Queue::new()
+    }
+}
+
+impl<T> Queue<T> {
+    /// Creates an empty Queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Queue;
+    ///
+    /// let queue: Queue<u64> = Queue::new();
+    ///
+    /// assert_eq!(queue.len(), 0);
+    /// ```
+    pub fn new() -> Queue<T> {
+        let sentinel = Owned::new(Node::new(None));
+        let guard = unsafe { crate::ebr::unprotected() };
+        let sentinel = sentinel.into_shared(guard);
+        Queue {
+            head: Atomic::from(sentinel),
+            tail: Atomic::from(sentinel),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pushes a value to the back of the Queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Queue;
+    ///
+    /// let queue: Queue<u64> = Default::default();
+    ///
+    /// queue.push(1);
+    /// queue.push(2);
+    /// assert_eq!(queue.pop(), Some(1));
+    /// ```
+    pub fn push(&self, value: T) {
+        let guard = crate::ebr::pin();
+        let new_node = Owned::new(Node::new(Some(value))).into_shared(&guard);
+        // The counter is incremented in advance so that it never underflows.
+        self.len.fetch_add(1, Relaxed);
+        loop {
+            let tail = self.tail.load(Acquire, &guard);
+            let tail_ref = unsafe { tail.deref() };
+            let next = tail_ref.next().load(Acquire, &guard);
+            if !next.is_null() {
+                // The tail is lagging behind.
+                let _result = self
+                    .tail
+                    .compare_exchange(tail, next, Release, Relaxed, &guard);
+                continue;
+            }
+            if tail_ref
+                .next()
+                .compare_exchange(Shared::null(), new_node, Release, Relaxed, &guard)
+                .is_ok()
+            {
+                let _result = self
+                    .tail
+                    .compare_exchange(tail, new_node, Release, Relaxed, &guard);
+                return;
+            }
+        }
+    }
+
+    /// Pops the value at the front of the Queue.
+    ///
+    /// It returns a clone of the value. The value in the Queue is kept until the next value is
+    /// popped, and then it is dropped once no thread can read it through [`Queue::peek`] or
+    /// [`Queue::iter`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Queue;
+    ///
+    /// let queue: Queue<u64> = Default::default();
+    ///
+    /// assert!(queue.pop().is_none());
+    /// queue.push(1);
+    /// assert_eq!(queue.pop(), Some(1));
+    /// assert!(queue.pop().is_none());
+    /// ```
+    pub fn pop(&self) -> Option<T>
+    where
+        T: Clone + Send + 'static,
+    {
+        let guard = crate::ebr::pin();
+        loop {
+            let head = self.head.load(Acquire, &guard);
+            let next = unsafe { head.deref() }.next().load(Acquire, &guard);
+            let next_ref = unsafe { next.as_ref() }?;
+            let tail = self.tail.load(Acquire, &guard);
+            if head == tail {
+                // The tail has to be moved forward before the head passes it.
+                let _result = self
+                    .tail
+                    .compare_exchange(tail, next, Release, Relaxed, &guard);
+            }
+            if self
+                .head
+                .compare_exchange(head, next, AcqRel, Acquire, &guard)
+                .is_ok()
+            {
+                // The popped Node becomes the sentinel, and the old sentinel is retired along
+                // with the value that was popped before.
+                unsafe { guard.defer_destroy(head) };
+                self.len.fetch_sub(1, Relaxed);
+                return next_ref.value().clone();
+            }
+        }
+    }
+
+    /// Returns a reference to the value at the front of the Queue.
+    ///
+    /// The value stays readable while the [`Guard`] is alive even if it is popped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] pins the thread in a [`Domain`] other than the global one.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Queue;
+    ///
+    /// let queue: Queue<u64> = Default::default();
+    /// let guard = scc::pin();
+    ///
+    /// assert!(queue.peek(&guard).is_none());
+    /// queue.push(1);
+    /// assert_eq!(queue.peek(&guard).map(|value| *value), Some(1));
+    /// assert_eq!(queue.pop(), Some(1));
+    /// assert!(queue.peek(&guard).is_none());
+    /// ```
+    pub fn peek<'g>(&'g self, guard: &'g Guard) -> Option<Ref<'g, T>> {
+        self.iter(guard).next()
+    }
+
+    /// Returns `true` if the Queue is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Queue;
+    ///
+    /// let queue: Queue<u64> = Default::default();
+    ///
+    /// assert!(queue.is_empty());
+    /// queue.push(1);
+    /// assert!(!queue.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        let guard = crate::ebr::pin();
+        let head = self.head.load(Acquire, &guard);
+        unsafe { head.deref() }
+            .next()
+            .load(Acquire, &guard)
+            .is_null()
+    }
+
+    /// Returns the number of values in the Queue.
+    ///
+    /// It reads a counter that is updated after each push and pop, therefore it may be
+    /// momentarily off while other threads are modifying the Queue.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Queue;
+    ///
+    /// let queue: Queue<u64> = Default::default();
+    ///
+    /// queue.push(1);
+    /// queue.push(2);
+    /// assert_eq!(queue.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.len.load(Relaxed)
+    }
+
+    /// Returns an iterator over the values from the front to the back of the Queue.
+    ///
+    /// The values that the iterator yields stay readable while the [`Guard`] is alive even if
+    /// they are popped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] pins the thread in a [`Domain`] other than the global one.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Queue;
+    ///
+    /// let queue: Queue<u64> = Default::default();
+    ///
+    /// for value in 0..4 {
+    ///     queue.push(value);
+    /// }
+    ///
+    /// let guard = scc::pin();
+    /// let sum: u64 = queue.iter(&guard).map(|value| *value).sum();
+    /// assert_eq!(sum, 6);
+    /// ```
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> QueueIter<'g, T> {
+        Domain::global().check_guard(guard);
+        QueueIter {
+            current: self.head.load(Acquire, guard),
+            guard,
+        }
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // The Queue has become unreachable, therefore pinning is unnecessary.
+        let guard = unsafe { crate::ebr::unprotected() };
+        let mut current = self.head.swap(Shared::null(), Relaxed, guard);
+        while !current.is_null() {
+            let node = unsafe { current.into_owned() };
+            current = node.next().load(Relaxed, guard);
+            drop(node);
+        }
+    }
+}
+
+/// QueueIter is an iterator over the values of a [`Queue`].
+///
+/// It is returned by [`Queue::iter`].
+pub struct QueueIter<'g, T> {
+    current: Shared<'g, Node<Option<T>>>,
+    guard: &'g Guard,
+}
+
+impl<'g, T: 'g> Iterator for QueueIter<'g, T> {
+    type Item = Ref<'g, T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        // The current Node is not dropped while the Guard is alive.
+        let next = unsafe { self.current.as_ref() }?
+            .next()
+            .load(Acquire, self.guard);
+        self.current = next;
+        // Only the sentinel lacks a value, and the iterator starts from it.
+        let value = unsafe { next.as_ref() }?.value().as_ref()?;
+        Some(Ref::new(value, self.guard))
+    }
+}
+
+impl<'g, T: 'g> FusedIterator for QueueIter<'g, T> {}
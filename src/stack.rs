@@ -0,0 +1,272 @@
+use crate::common::node::Node;
+use crate::ebr::{Atomic, Domain, Guard, Owned, Ref, Shared};
+
+use core::iter::FusedIterator;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
+
+/// A concurrent unbounded stack.
+///
+/// scc::Stack is a Treiber stack of which the nodes are reclaimed by the same epoch-based
+/// reclamation mechanism that the other containers use, therefore a node is never freed while
+/// another thread may be reading it, and the ABA problem does not arise. [`Stack::pop_all`]
+/// detaches all the values with a single atomic operation, which makes it suitable for batch
+/// draining, e.g., a free-list of buffers.
+///
+/// All the operations are lock-free. The value at the top can be read by [`Stack::peek`] in place
+/// while the thread is pinned, therefore a popped value is not moved out of the Stack:
+/// [`Stack::pop`] and [`Stack::pop_all`] return clones of the values, and the values in the Stack
+/// are dropped once no thread can read them.
+///
+/// ## The key statistics for scc::Stack
+/// * The size of the metadata for a single value: one word.
+/// * The number of atomic read-modify-write operations for a single push: one.
+pub struct Stack<T> {
+    top: Atomic<Node<T>>,
+}
+
+impl<T> Default for Stack<T> {
+    /// Creates an empty Stack.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Stack;
+    ///
+    /// let stack: Stack<u64> = Default::default();
+    ///
+    /// assert!(stack.is_empty());
+    /// ```
+    fn default() -> Self {
+        Stack::new()
+    }
+}
+
+impl<T> Stack<T> {
+    /// Creates an empty Stack.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Stack;
+    ///
+    /// let stack: Stack<u64> = Stack::new();
+    ///
+    /// assert!(stack.pop().is_none());
+    /// ```
+    pub fn new() -> Stack<T> {
+        Stack {
+            top: Atomic::null(),
+        }
+    }
+
+    /// Pushes a value onto the Stack.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory allocation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Stack;
+    ///
+    /// let stack: Stack<u64> = Default::default();
+    ///
+    /// stack.push(1);
+    /// stack.push(2);
+    /// assert_eq!(stack.pop(), Some(2));
+    /// ```
+    pub fn push(&self, value: T) {
+        let guard = crate::ebr::pin();
+        let new_node = Owned::new(Node::new(value)).into_shared(&guard);
+        let new_node_ref = unsafe { new_node.deref() };
+        let mut top = self.top.load(Relaxed, &guard);
+        loop {
+            new_node_ref.next().store(top, Relaxed);
+            match self
+                .top
+                .compare_exchange(top, new_node, Release, Relaxed, &guard)
+            {
+                Ok(_) => return,
+                Err(error) => top = error.current,
+            }
+        }
+    }
+
+    /// Pops the value at the top of the Stack.
+    ///
+    /// It returns a clone of the value, and the value in the Stack is dropped once no thread can
+    /// read it through [`Stack::peek`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Stack;
+    ///
+    /// let stack: Stack<u64> = Default::default();
+    ///
+    /// stack.push(1);
+    /// stack.push(2);
+    /// assert_eq!(stack.pop(), Some(2));
+    /// assert_eq!(stack.pop(), Some(1));
+    /// assert!(stack.pop().is_none());
+    /// ```
+    pub fn pop(&self) -> Option<T>
+    where
+        T: Clone + Send + 'static,
+    {
+        let guard = crate::ebr::pin();
+        let mut top = self.top.load(Acquire, &guard);
+        loop {
+            let top_ref = unsafe { top.as_ref() }?;
+            let next = top_ref.next().load(Relaxed, &guard);
+            match self
+                .top
+                .compare_exchange(top, next, Acquire, Acquire, &guard)
+            {
+                Ok(_) => {
+                    let value = top_ref.value().clone();
+                    unsafe { guard.defer_destroy(top) };
+                    return Some(value);
+                }
+                Err(error) => top = error.current,
+            }
+        }
+    }
+
+    /// Detaches all the values from the Stack with a single atomic operation.
+    ///
+    /// The returned [`StackDrain`] yields clones of the detached values from the top to the
+    /// bottom, and the detached values are dropped once no thread can read them through
+    /// [`Stack::peek`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Stack;
+    ///
+    /// let stack: Stack<u64> = Default::default();
+    ///
+    /// for value in 0..4 {
+    ///     stack.push(value);
+    /// }
+    ///
+    /// let drained: Vec<u64> = stack.pop_all().collect();
+    /// assert_eq!(drained, vec![3, 2, 1, 0]);
+    /// assert!(stack.is_empty());
+    /// ```
+    pub fn pop_all(&self) -> StackDrain<T>
+    where
+        T: Clone + Send + 'static,
+    {
+        let guard = crate::ebr::pin();
+        let top = self.top.swap(Shared::null(), AcqRel, &guard);
+        StackDrain {
+            current: top.as_raw(),
+        }
+    }
+
+    /// Returns a reference to the value at the top of the Stack.
+    ///
+    /// The value stays readable while the [`Guard`] is alive even if it is popped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Guard`] pins the thread in a [`Domain`] other than the global one.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Stack;
+    ///
+    /// let stack: Stack<u64> = Default::default();
+    /// let guard = scc::pin();
+    ///
+    /// assert!(stack.peek(&guard).is_none());
+    /// stack.push(1);
+    /// assert_eq!(stack.peek(&guard).map(|value| *value), Some(1));
+    /// assert_eq!(stack.pop(), Some(1));
+    /// assert!(stack.peek(&guard).is_none());
+    /// ```
+    pub fn peek<'g>(&'g self, guard: &'g Guard) -> Option<Ref<'g, T>> {
+        Domain::global().check_guard(guard);
+        let top_ref = unsafe { self.top.load(Acquire, guard).as_ref() }?;
+        Some(Ref::new(top_ref.value(), guard))
+    }
+
+    /// Returns `true` if the Stack is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::Stack;
+    ///
+    /// let stack: Stack<u64> = Default::default();
+    ///
+    /// assert!(stack.is_empty());
+    /// stack.push(1);
+    /// assert!(!stack.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        let guard = crate::ebr::pin();
+        self.top.load(Relaxed, &guard).is_null()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Stack<T> {
+    /// Returns the Node at the top of the Stack.
+    pub(crate) fn top<'g>(&self, guard: &'g Guard) -> Shared<'g, Node<T>> {
+        self.top.load(Acquire, guard)
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        // The Stack has become unreachable, therefore pinning is unnecessary.
+        let guard = unsafe { crate::ebr::unprotected() };
+        let mut current = self.top.swap(Shared::null(), Relaxed, guard);
+        while !current.is_null() {
+            let node = unsafe { current.into_owned() };
+            current = node.next().load(Relaxed, guard);
+            drop(node);
+        }
+    }
+}
+
+/// StackDrain yields clones of the values detached from a [`Stack`].
+///
+/// It is returned by [`Stack::pop_all`].
+pub struct StackDrain<T: Clone + Send + 'static> {
+    current: *const Node<T>,
+}
+
+impl<T: Clone + Send + 'static> Iterator for StackDrain<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current.is_null() {
+            return None;
+        }
+        let guard = crate::ebr::pin();
+        let node = Shared::from(self.current);
+        let node_ref = unsafe { node.deref() };
+        let value = node_ref.value().clone();
+        self.current = node_ref.next().load(Relaxed, &guard).as_raw();
+        // Other threads may still be reading the Node.
+        unsafe { guard.defer_destroy(node) };
+        Some(value)
+    }
+}
+
+impl<T: Clone + Send + 'static> FusedIterator for StackDrain<T> {}
+
+impl<T: Clone + Send + 'static> Drop for StackDrain<T> {
+    fn drop(&mut self) {
+        let guard = crate::ebr::pin();
+        while !self.current.is_null() {
+            let node = Shared::from(self.current);
+            self.current = unsafe { node.deref() }
+                .next()
+                .load(Relaxed, &guard)
+                .as_raw();
+            // Other threads may still be reading the Node.
+            unsafe { guard.defer_destroy(node) };
+        }
+    }
+}
+
+// The detached Nodes are owned by StackDrain, and other threads may only read the values.
+unsafe impl<T: Clone + Send + Sync + 'static> Send for StackDrain<T> {}
@@ -3,7 +3,7 @@ pub mod leaf;
 pub mod leaf_node;
 pub mod node;
 
-use crossbeam_epoch::{Atomic, Guard, Owned};
+use crate::ebr::{Atomic, Guard, Owned, Ref};
 use error::{InsertError, RemoveError, SearchError};
 use leaf::{Leaf, LeafScanner};
 use node::Node;
@@ -11,9 +11,10 @@ use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::fmt;
 use std::iter::FusedIterator;
-use std::ops::Bound::{Excluded, Included, Unbounded};
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
 use std::ops::RangeBounds;
 use std::sync::atomic::Ordering::{AcqRel, Acquire};
+use std::sync::Mutex;
 
 /// A scalable concurrent B+ tree.
 ///
@@ -36,6 +37,12 @@ where
     V: Clone + Send + Sync,
 {
     root: Atomic<Node<K, V>>,
+    /// Serializes pop operations.
+    ///
+    /// An entry removed from a leaf being split may have been copied to a new leaf, and the copy
+    /// is then removed by the retry; pop operations must not race with each other in order for
+    /// exactly one of them to take the entry.
+    pop_lock: Mutex<()>,
 }
 
 impl<K, V> Default for TreeIndex<K, V>
@@ -78,6 +85,7 @@ where
     pub fn new() -> TreeIndex<K, V> {
         TreeIndex {
             root: Atomic::null(),
+            pop_lock: Mutex::new(()),
         }
     }
 
@@ -100,7 +108,7 @@ where
     /// ```
     pub fn insert(&self, mut key: K, mut value: V) -> Result<(), (K, V)> {
         loop {
-            let guard = crossbeam_epoch::pin();
+            let guard = crate::ebr::pin();
             let mut root_node = self.root.load(Acquire, &guard);
             if root_node.is_null() {
                 let new_root = Owned::new(Node::new_leaf_node());
@@ -155,14 +163,14 @@ where
         Q: Ord + ?Sized,
     {
         let mut has_been_removed = false;
-        let guard = crossbeam_epoch::pin();
+        let guard = crate::ebr::pin();
         let mut root_node = self.root.load(Acquire, &guard);
         loop {
             if root_node.is_null() {
                 return has_been_removed;
             }
             let root_node_ref = unsafe { root_node.deref() };
-            match root_node_ref.remove(key, &guard) {
+            match root_node_ref.remove(key, &mut |leaf: &Leaf<K, V>| leaf.remove(key), &guard) {
                 Ok(removed) => return removed || has_been_removed,
                 Err(remove_error) => match remove_error {
                     RemoveError::Empty(removed) => {
@@ -186,6 +194,88 @@ where
         }
     }
 
+    /// Removes all the key-value pairs in the given range.
+    ///
+    /// It returns the number of removed key-value pairs. Instead of looking up each key from the
+    /// root, it removes all the key-value pairs in the range from a leaf at once, and leaves that
+    /// become empty are unlinked from the TreeIndex in the same manner as [`TreeIndex::remove`].
+    /// Unlinked leaves and nodes are reclaimed only after all the readers that might be reading
+    /// them are gone. Key-value pairs inserted into the range during the operation may or may
+    /// not be removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// for key in 0..1024 {
+    ///     assert!(treeindex.insert(key, 10).is_ok());
+    /// }
+    ///
+    /// assert_eq!(treeindex.remove_range(..512), 512);
+    /// assert_eq!(treeindex.remove_range(768..=1023), 256);
+    /// assert_eq!(treeindex.remove_range(..), 256);
+    /// assert_eq!(treeindex.len(), 0);
+    /// ```
+    pub fn remove_range<R: RangeBounds<K>>(&self, range: R) -> usize {
+        let mut num_removed = 0;
+        let mut cursor: Option<K> = None;
+        let guard = crate::ebr::pin();
+        // Keys that have been removed by an attempt that has to be retried; the same keys may
+        // have been copied into new leaves, and therefore they are not counted twice.
+        let mut retried: Vec<*const K> = Vec::new();
+        loop {
+            let start_bound = match cursor.as_ref() {
+                Some(key) => Excluded(key),
+                None => range.start_bound(),
+            };
+            let first_key = match self.range((start_bound, range.end_bound())).next() {
+                Some((key, _)) => key.clone(),
+                None => return num_removed,
+            };
+            loop {
+                let root_node = self.root.load(Acquire, &guard);
+                if root_node.is_null() {
+                    return num_removed;
+                }
+                let mut attempted: Vec<*const K> = Vec::new();
+                let mut remover = |leaf: &Leaf<K, V>| {
+                    let (removed, full, empty) = leaf.remove_range(&range);
+                    for index in (0..leaf::ARRAY_SIZE).filter(|index| removed & (1 << index) != 0) {
+                        let key_ref = leaf.read(index).0;
+                        if !retried
+                            .iter()
+                            .any(|key_ptr| unsafe { &**key_ptr }.cmp(key_ref) == Ordering::Equal)
+                        {
+                            num_removed += 1;
+                        }
+                        attempted.push(key_ref as *const K);
+                    }
+                    (removed != 0, full, empty)
+                };
+                match unsafe { root_node.deref() }.remove(&first_key, &mut remover, &guard) {
+                    Ok(_) => {
+                        retried.clear();
+                        break;
+                    }
+                    Err(RemoveError::Empty(_)) => {
+                        retried.clear();
+                        if Node::remove_root(&self.root, true, &guard) {
+                            break;
+                        }
+                    }
+                    Err(RemoveError::Retry(_)) => {
+                        // The leaves are not dropped while the guard is held.
+                        retried.append(&mut attempted);
+                        std::thread::yield_now();
+                    }
+                }
+            }
+            cursor.replace(first_key);
+        }
+    }
+
     /// Reads a key-value pair.
     ///
     /// # Examples
@@ -208,7 +298,7 @@ where
         K: Borrow<Q>,
         Q: Ord + ?Sized,
     {
-        let guard = crossbeam_epoch::pin();
+        let guard = crate::ebr::pin();
         loop {
             let root_node = self.root.load(Acquire, &guard);
             if root_node.is_null() {
@@ -233,6 +323,197 @@ where
         }
     }
 
+    /// Returns a [`Ref`] to the value associated with the key.
+    ///
+    /// The value is not copied; the [`Ref`] stays valid while the [`Guard`] is alive, even if the
+    /// key-value pair is removed in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// Returns None if the key does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// assert!(treeindex.insert(1, 10).is_ok());
+    ///
+    /// let guard = scc::pin();
+    /// let value = treeindex.peek(&1, &guard).unwrap();
+    /// assert!(treeindex.remove(&1));
+    /// assert_eq!(*value, 10);
+    /// assert!(treeindex.peek(&1, &guard).is_none());
+    /// ```
+    pub fn peek<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Option<Ref<'g, V>>
+    where
+        K: Borrow<Q>,
+        K: 'g,
+        Q: Ord + ?Sized,
+    {
+        loop {
+            let root_node = self.root.load(Acquire, guard);
+            if root_node.is_null() {
+                return None;
+            }
+            match unsafe { root_node.deref().search(key, guard) } {
+                Ok(result) => return result.map(|value| Ref::new(value, guard)),
+                Err(err) => match err {
+                    SearchError::Empty => return None,
+                    SearchError::Retry => {
+                        std::thread::yield_now();
+                        continue;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Reads the key-value pair of which the key is the greatest among those that are less than
+    /// or equal to the given key.
+    ///
+    /// It never locks any leaves. If the key-value pair is removed while being read, the closure
+    /// still gets the key-value pair that was the answer at some point during the call.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// assert!(treeindex.insert(10, 1).is_ok());
+    /// assert!(treeindex.insert(20, 2).is_ok());
+    ///
+    /// assert!(treeindex.read_floor(&5, |key, value| *value).is_none());
+    /// assert_eq!(treeindex.read_floor(&10, |key, value| *value), Some(1));
+    /// assert_eq!(treeindex.read_floor(&15, |key, value| *key), Some(10));
+    /// assert_eq!(treeindex.read_floor(&25, |key, value| *key), Some(20));
+    /// ```
+    pub fn read_floor<R, F: FnOnce(&K, &V) -> R>(&self, key: &K, f: F) -> Option<R> {
+        self.read_max_within(Included(key), f)
+    }
+
+    /// Reads the key-value pair of which the key is the least among those that are greater than
+    /// or equal to the given key.
+    ///
+    /// It never locks any leaves. If the key-value pair is removed while being read, the closure
+    /// still gets the key-value pair that was the answer at some point during the call.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// assert!(treeindex.insert(10, 1).is_ok());
+    /// assert!(treeindex.insert(20, 2).is_ok());
+    ///
+    /// assert_eq!(treeindex.read_ceiling(&5, |key, value| *key), Some(10));
+    /// assert_eq!(treeindex.read_ceiling(&10, |key, value| *value), Some(1));
+    /// assert_eq!(treeindex.read_ceiling(&15, |key, value| *key), Some(20));
+    /// assert!(treeindex.read_ceiling(&25, |key, value| *value).is_none());
+    /// ```
+    pub fn read_ceiling<R, F: FnOnce(&K, &V) -> R>(&self, key: &K, f: F) -> Option<R> {
+        self.range((Included(key), Unbounded))
+            .next()
+            .map(|(key, value)| f(key, value))
+    }
+
+    /// Reads the key-value pair of which the key is the least in the TreeIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// assert!(treeindex.read_first(|key, value| *key).is_none());
+    ///
+    /// assert!(treeindex.insert(2, 20).is_ok());
+    /// assert!(treeindex.insert(1, 10).is_ok());
+    /// assert_eq!(treeindex.read_first(|key, value| *value), Some(10));
+    /// ```
+    pub fn read_first<R, F: FnOnce(&K, &V) -> R>(&self, f: F) -> Option<R> {
+        self.range(..).next().map(|(key, value)| f(key, value))
+    }
+
+    /// Reads the key-value pair of which the key is the greatest in the TreeIndex.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// assert!(treeindex.read_last(|key, value| *key).is_none());
+    ///
+    /// assert!(treeindex.insert(2, 20).is_ok());
+    /// assert!(treeindex.insert(1, 10).is_ok());
+    /// assert_eq!(treeindex.read_last(|key, value| *value), Some(20));
+    /// ```
+    pub fn read_last<R, F: FnOnce(&K, &V) -> R>(&self, f: F) -> Option<R> {
+        self.read_max_within(Unbounded, f)
+    }
+
+    /// Removes the key-value pair of which the key is the least in the TreeIndex, and returns it.
+    ///
+    /// No two pop operations return the same key-value pair, and a key-value pair that is
+    /// smaller than the returned one is not in the TreeIndex at the moment the returned one is
+    /// removed unless it is being inserted. Pop operations are serialized; other operations are
+    /// not blocked by them.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// assert!(treeindex.insert(2, 20).is_ok());
+    /// assert!(treeindex.insert(1, 10).is_ok());
+    ///
+    /// assert_eq!(treeindex.pop_first(), Some((1, 10)));
+    /// assert_eq!(treeindex.pop_first(), Some((2, 20)));
+    /// assert!(treeindex.pop_first().is_none());
+    /// ```
+    pub fn pop_first(&self) -> Option<(K, V)> {
+        let _lock = self.pop_lock.lock().unwrap_or_else(|err| err.into_inner());
+        loop {
+            let key = self.read_first(|key, _| key.clone())?;
+            if let Some(entry) = self.pop_entry(&key) {
+                return Some(entry);
+            }
+        }
+    }
+
+    /// Removes the key-value pair of which the key is the greatest in the TreeIndex, and returns
+    /// it.
+    ///
+    /// It provides the same guarantees as [`TreeIndex::pop_first`].
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// assert!(treeindex.insert(2, 20).is_ok());
+    /// assert!(treeindex.insert(1, 10).is_ok());
+    ///
+    /// assert_eq!(treeindex.pop_last(), Some((2, 20)));
+    /// assert_eq!(treeindex.pop_last(), Some((1, 10)));
+    /// assert!(treeindex.pop_last().is_none());
+    /// ```
+    pub fn pop_last(&self) -> Option<(K, V)> {
+        let _lock = self.pop_lock.lock().unwrap_or_else(|err| err.into_inner());
+        loop {
+            let key = self.read_last(|key, _| key.clone())?;
+            if let Some(entry) = self.pop_entry(&key) {
+                return Some(entry);
+            }
+        }
+    }
+
     /// Clears the TreeIndex.
     ///
     /// # Examples
@@ -252,7 +533,7 @@ where
     /// assert_eq!(result, 0);
     /// ```
     pub fn clear(&self) {
-        let guard = crossbeam_epoch::pin();
+        let guard = crate::ebr::pin();
         Node::remove_root(&self.root, false, &guard);
     }
 
@@ -295,7 +576,7 @@ where
     /// assert_eq!(result, 1);
     /// ```
     pub fn depth(&self) -> usize {
-        let guard = crossbeam_epoch::pin();
+        let guard = crate::ebr::pin();
         let root_node = self.root.load(Acquire, &guard);
         if !root_node.is_null() {
             unsafe { root_node.deref().depth(1, &guard) }
@@ -304,6 +585,71 @@ where
         }
     }
 
+    /// Returns the statistics of the TreeIndex.
+    ///
+    /// It traverses all the nodes and leaves without locking them, therefore the statistics may
+    /// not be consistent if the TreeIndex is being modified.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// for key in 0..16u64 {
+    ///     assert!(treeindex.insert(key, 10).is_ok());
+    /// }
+    ///
+    /// let statistics = treeindex.statistics();
+    /// assert_eq!(statistics.num_nodes(), 1);
+    /// assert_eq!(statistics.depth(), treeindex.depth());
+    /// assert_eq!(statistics.num_entries(), 16);
+    /// assert!(statistics.average_leaf_fill() > 0.0);
+    /// assert_eq!(statistics.num_retired(), 0);
+    /// ```
+    pub fn statistics(&self) -> TreeStatistics {
+        let mut statistics = TreeStatistics::default();
+        let guard = crate::ebr::pin();
+        let root_node = self.root.load(Acquire, &guard);
+        if let Some(root_node_ref) = unsafe { root_node.as_ref() } {
+            root_node_ref.statistics(1, &mut statistics, &guard);
+        }
+        statistics
+    }
+
+    /// Validates the structure of the TreeIndex.
+    ///
+    /// It checks that the keys in each node and leaf are in order, the keys in each node and leaf
+    /// are within the bounds set by the keys in its ancestors, and no retired node or leaf is
+    /// reachable from the root. It is only meaningful when no other threads are modifying the
+    /// TreeIndex.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error identifying the first node or leaf that violates an invariant.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// for key in 0..1024u64 {
+    ///     assert!(treeindex.insert(key, 10).is_ok());
+    /// }
+    ///
+    /// assert!(treeindex.validate().is_ok());
+    /// ```
+    #[cfg(any(test, feature = "debug-tools"))]
+    pub fn validate(&self) -> Result<(), TreeValidationError> {
+        let guard = crate::ebr::pin();
+        let root_node = self.root.load(Acquire, &guard);
+        if let Some(root_node_ref) = unsafe { root_node.as_ref() } {
+            root_node_ref.validate(None, None, &guard)?;
+        }
+        Ok(())
+    }
+
     /// Returns a Scanner.
     ///
     /// The returned Scanner starts scanning from the minimum key-value pair.
@@ -329,7 +675,7 @@ where
     /// assert_eq!(scanner.next().unwrap(), (&3, &13));
     /// assert!(scanner.next().is_none());
     /// ```
-    pub fn iter(&self) -> Scanner<K, V> {
+    pub fn iter(&self) -> Scanner<'_, K, V> {
         Scanner::new(self)
     }
 
@@ -363,11 +709,71 @@ where
     ///     scanned += 1;
     /// }
     /// assert_eq!(scanned, 5);
-
+    ///
+    /// let mut prev = 9;
+    /// for entry in treeindex.range(..=8).rev() {
+    ///     assert_eq!(*entry.0, prev - 1);
+    ///     prev = *entry.0;
+    /// }
+    /// assert_eq!(prev, 0);
     /// ```
-    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<K, V, R> {
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V, R> {
         Range::new(self, range)
     }
+
+    /// Reads the key-value pair of which the key is the greatest within the given upper bound.
+    fn read_max_within<R, F: FnOnce(&K, &V) -> R>(&self, bound: Bound<&K>, f: F) -> Option<R> {
+        let guard = crate::ebr::pin();
+        loop {
+            let root_node = self.root.load(Acquire, &guard);
+            if root_node.is_null() {
+                return None;
+            }
+            match unsafe { root_node.deref() }.max_within(bound, &guard) {
+                Ok(result) => return result.map(|(key, value)| f(key, value)),
+                Err(err) => match err {
+                    SearchError::Empty => return None,
+                    SearchError::Retry => {
+                        std::thread::yield_now();
+                        continue;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Removes the key-value pair associated with the key, and returns the removed key-value pair.
+    ///
+    /// It must be called while the pop lock is held.
+    fn pop_entry(&self, key: &K) -> Option<(K, V)> {
+        let mut popped = None;
+        let guard = crate::ebr::pin();
+        loop {
+            let root_node = self.root.load(Acquire, &guard);
+            if root_node.is_null() {
+                return popped;
+            }
+            let mut remover = |leaf: &Leaf<K, V>| {
+                let (removed, full, empty) = leaf.remove_range(&(Included(key), Included(key)));
+                if removed != 0 && popped.is_none() {
+                    // An entry removed by a previous attempt may have been copied to a new leaf,
+                    // and the copy is removed without being returned.
+                    let (key, value) = leaf.read(removed.trailing_zeros() as usize);
+                    popped.replace((key.clone(), value.clone()));
+                }
+                (removed != 0, full, empty)
+            };
+            match unsafe { root_node.deref() }.remove(key, &mut remover, &guard) {
+                Ok(_) => return popped,
+                Err(RemoveError::Empty(_)) => {
+                    if Node::remove_root(&self.root, true, &guard) {
+                        return popped;
+                    }
+                }
+                Err(RemoveError::Retry(_)) => std::thread::yield_now(),
+            }
+        }
+    }
 }
 
 impl<K, V> TreeIndex<K, V>
@@ -390,7 +796,7 @@ where
     /// ```
     pub fn print<T: std::io::Write>(&self, output: &mut T) -> std::io::Result<()> {
         output.write_fmt(format_args!("digraph {{\n"))?;
-        let guard = crossbeam_epoch::pin();
+        let guard = crate::ebr::pin();
         let root_node = self.root.load(Acquire, &guard);
         if !root_node.is_null() {
             unsafe { root_node.deref().print(output, 1, &guard) }?
@@ -406,11 +812,83 @@ where
 {
     fn drop(&mut self) {
         // The TreeIndex has become unreachable, therefore pinning is unnecessary.
-        let guard = unsafe { crossbeam_epoch::unprotected() };
+        let guard = unsafe { crate::ebr::unprotected() };
         Node::remove_root(&self.root, false, guard);
     }
 }
 
+/// TreeStatistics is a snapshot of the structure of a TreeIndex, returned by
+/// [`TreeIndex::statistics`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TreeStatistics {
+    num_nodes: usize,
+    num_children: usize,
+    num_leaves: usize,
+    num_entries: usize,
+    num_retired: usize,
+    depth: usize,
+}
+
+impl TreeStatistics {
+    /// Returns the number of internal and leaf nodes.
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    /// Returns the number of leaves.
+    pub fn num_leaves(&self) -> usize {
+        self.num_leaves
+    }
+
+    /// Returns the number of valid entries in the leaves.
+    pub fn num_entries(&self) -> usize {
+        self.num_entries
+    }
+
+    /// Returns the number of nodes and leaves that have been retired but are still reachable.
+    ///
+    /// Retired nodes and leaves are unlinked by subsequent remove operations.
+    pub fn num_retired(&self) -> usize {
+        self.num_retired
+    }
+
+    /// Returns the depth of the TreeIndex.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the average ratio of the number of children to the capacity of a node.
+    pub fn average_node_fill(&self) -> f64 {
+        if self.num_nodes == 0 {
+            return 0.0;
+        }
+        self.num_children as f64 / (self.num_nodes * (leaf::ARRAY_SIZE + 1)) as f64
+    }
+
+    /// Returns the average ratio of the number of valid entries to the capacity of a leaf.
+    pub fn average_leaf_fill(&self) -> f64 {
+        if self.num_leaves == 0 {
+            return 0.0;
+        }
+        self.num_entries as f64 / (self.num_leaves * leaf::ARRAY_SIZE) as f64
+    }
+}
+
+/// TreeValidationError is the invariant violation found by [`TreeIndex::validate`].
+///
+/// Each variant carries the identifier of the node or leaf that violates the invariant; the
+/// identifier is the memory address of the node or leaf.
+#[cfg(any(test, feature = "debug-tools"))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TreeValidationError {
+    /// Unordered: the keys are not in order.
+    Unordered(usize),
+    /// SeparatorViolated: a key is not within the bounds set by the keys in the ancestors.
+    SeparatorViolated(usize),
+    /// RetiredReachable: the retired node or leaf is reachable from the root.
+    RetiredReachable(usize),
+}
+
 /// Scanner scans all the key-value pairs in the TreeIndex.
 ///
 /// It is guaranteed to visit all the key-value pairs that outlive the Scanner,
@@ -434,7 +912,7 @@ where
         Scanner::<'t, K, V> {
             tree,
             leaf_scanner: None,
-            guard: crossbeam_epoch::pin(),
+            guard: crate::ebr::pin(),
         }
     }
 }
@@ -497,6 +975,8 @@ where
 /// Range represents a range of keys in the TreeIndex.
 ///
 /// It is identical to Scanner except that it does not traverse keys outside of the given range.
+/// It also implements DoubleEndedIterator, therefore the keys in the range can be scanned in
+/// descending order by [`Iterator::rev`].
 pub struct Range<'t, K, V, R>
 where
     K: Clone + Ord + Send + Sync,
@@ -508,6 +988,9 @@ where
     range: R,
     check_lower_bound: bool,
     check_upper_bound: bool,
+    front_key: Option<&'t K>,
+    back_key: Option<&'t K>,
+    finished: bool,
     guard: Guard,
 }
 
@@ -524,7 +1007,10 @@ where
             range,
             check_lower_bound: true,
             check_upper_bound: false,
-            guard: crossbeam_epoch::pin(),
+            front_key: None,
+            back_key: None,
+            finished: false,
+            guard: crate::ebr::pin(),
         }
     }
 
@@ -615,16 +1101,8 @@ where
         }
         None
     }
-}
 
-impl<'t, K, V, R> Iterator for Range<'t, K, V, R>
-where
-    K: Clone + Ord + Send + Sync,
-    V: Clone + Send + Sync,
-    R: RangeBounds<K>,
-{
-    type Item = (&'t K, &'t V);
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_bounded(&mut self) -> Option<(&'t K, &'t V)> {
         while let Some((key_ref, value_ref)) = self.next_unbounded() {
             if self.check_lower_bound {
                 match self.range.start_bound() {
@@ -666,6 +1144,101 @@ where
     }
 }
 
+impl<'t, K, V, R> Iterator for Range<'t, K, V, R>
+where
+    K: Clone + Ord + Send + Sync,
+    V: Clone + Send + Sync,
+    R: RangeBounds<K>,
+{
+    type Item = (&'t K, &'t V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.next_bounded() {
+            Some((key_ref, value_ref)) if !matches!(self.back_key, Some(back_key) if key_ref.cmp(back_key) != Ordering::Less) =>
+            {
+                self.front_key.replace(key_ref);
+                Some((key_ref, value_ref))
+            }
+            _ => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+}
+
+impl<'t, K, V, R> DoubleEndedIterator for Range<'t, K, V, R>
+where
+    K: Clone + Ord + Send + Sync,
+    V: Clone + Send + Sync,
+    R: RangeBounds<K>,
+{
+    /// Returns the largest key-value pair that has yet to be returned.
+    ///
+    /// Each call searches the TreeIndex for the largest key less than the last returned one,
+    /// therefore, all the keys in the range that outlive the Range are returned in descending
+    /// order regardless of concurrent structural modifications.
+    ///
+    /// # Examples
+    /// ```
+    /// use scc::TreeIndex;
+    ///
+    /// let treeindex: TreeIndex<u64, u32> = TreeIndex::new();
+    ///
+    /// for i in 0..10 {
+    ///     assert!(treeindex.insert(i, 10).is_ok());
+    /// }
+    ///
+    /// let keys: Vec<u64> = treeindex.range(4..8).rev().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, vec![7, 6, 5, 4]);
+    ///
+    /// let mut range = treeindex.range(..);
+    /// assert_eq!(range.next(), Some((&0, &10)));
+    /// assert_eq!(range.next_back(), Some((&9, &10)));
+    /// assert_eq!(range.count(), 8);
+    /// ```
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let bound = match self.back_key {
+            Some(back_key) => Excluded(back_key),
+            None => self.range.end_bound(),
+        };
+        loop {
+            let root_node = self.tree.root.load(Acquire, &self.guard);
+            if root_node.is_null() {
+                break;
+            }
+            match unsafe { &*root_node.as_raw() }.max_within(bound, &self.guard) {
+                Ok(Some(entry)) => {
+                    // Prolongs the lifetime as the Rust type system cannot infer the actual lifetime correctly.
+                    let (key_ref, value_ref) =
+                        unsafe { std::mem::transmute::<(&K, &V), (&'t K, &'t V)>(entry) };
+                    let within = match self.range.start_bound() {
+                        Excluded(key) => key_ref.cmp(key) == Ordering::Greater,
+                        Included(key) => key_ref.cmp(key) != Ordering::Less,
+                        Unbounded => true,
+                    };
+                    if within
+                        && !matches!(self.front_key, Some(front_key) if key_ref.cmp(front_key) != Ordering::Greater)
+                    {
+                        self.back_key.replace(key_ref);
+                        return Some((key_ref, value_ref));
+                    }
+                    break;
+                }
+                Ok(None) | Err(SearchError::Empty) => break,
+                Err(SearchError::Retry) => std::thread::yield_now(),
+            }
+        }
+        self.finished = true;
+        None
+    }
+}
+
 impl<'t, K, V, R> FusedIterator for Range<'t, K, V, R>
 where
     K: Clone + Ord + Send + Sync,
@@ -1,9 +1,14 @@
+#[cfg(any(test, feature = "debug-tools"))]
+use super::TreeValidationError;
 use crate::common::linked_list::LinkedList;
-use crossbeam_epoch::{Atomic, Guard};
+use crate::ebr::{Atomic, Guard};
 use std::borrow::Borrow;
+use std::cell::UnsafeCell;
 use std::cmp::Ordering;
 use std::convert::TryInto;
 use std::mem::MaybeUninit;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use std::ops::RangeBounds;
 use std::sync::atomic::AtomicU32;
 use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
 
@@ -30,7 +35,10 @@ const OBSOLETE_MASK: u32 = (RETIRED << 28)
     | RETIRED;
 
 /// Each entry in an EntryArray is never dropped until the Leaf is dropped once constructed.
-pub type EntryArray<K, V> = [MaybeUninit<(K, V)>; ARRAY_SIZE];
+///
+/// The slots are written through a shared reference to the Leaf while other slots are being
+/// read, therefore each slot is wrapped in an UnsafeCell.
+pub type EntryArray<K, V> = [UnsafeCell<MaybeUninit<(K, V)>>; ARRAY_SIZE];
 
 /// Leaf is an ordered array of key-value pairs.
 ///
@@ -54,6 +62,16 @@ where
     metadata: AtomicU32,
 }
 
+// A slot is only written or taken while it is reserved by a writer and not yet made visible
+// through the metadata, or when the Leaf is dropped, therefore readers never read a slot that is
+// being modified.
+unsafe impl<K, V> Sync for Leaf<K, V>
+where
+    K: Clone + Ord + Sync,
+    V: Clone + Sync,
+{
+}
+
 impl<K, V> Leaf<K, V>
 where
     K: Clone + Ord + Sync,
@@ -108,6 +126,23 @@ where
         None
     }
 
+    /// Returns a reference to the max key that is within the given upper bound.
+    pub fn max_within(&self, bound: Bound<&K>) -> Option<(&K, &V)> {
+        let mut max_entry = None;
+        for entry in LeafScanner::new(self) {
+            let within = match bound {
+                Excluded(key) => entry.0.cmp(key) == Ordering::Less,
+                Included(key) => entry.0.cmp(key) != Ordering::Greater,
+                Unbounded => true,
+            };
+            if !within {
+                break;
+            }
+            max_entry.replace(entry);
+        }
+        max_entry
+    }
+
     /// Inserts a key value pair.
     ///
     /// It returns the passed key value pair on failure.
@@ -218,7 +253,7 @@ where
                                 metadata,
                                 new_metadata,
                                 Release,
-                                Relaxed,
+                                Acquire,
                             ) {
                                 Ok(_) => {
                                     removed = true;
@@ -255,6 +290,60 @@ where
         (removed, full, empty)
     }
 
+    /// Removes all the entries within the given range.
+    ///
+    /// It returns a bitmap of the indexes of the removed entries, and whether the leaf is full or
+    /// empty.
+    pub fn remove_range<R: RangeBounds<K>>(&self, range: &R) -> (u32, bool, bool) {
+        let mut metadata = self.metadata.load(Acquire);
+        let mut removed;
+        loop {
+            let mut new_metadata = metadata;
+            removed = 0;
+            for i in 0..ARRAY_SIZE {
+                let rank = Self::rank(i, metadata);
+                if rank > 0
+                    && rank <= ARRAY_SIZE.try_into().unwrap()
+                    && range.contains(self.read(i).0)
+                {
+                    new_metadata =
+                        (new_metadata & (!Self::rank_mask(i))) | Self::rank_bits(i, REMOVED);
+                    removed |= 1 << i;
+                }
+            }
+            if removed == 0 {
+                break;
+            }
+            match self
+                .metadata
+                .compare_exchange(metadata, new_metadata, Release, Acquire)
+            {
+                Ok(_) => {
+                    metadata = new_metadata;
+                    break;
+                }
+                Err(result) => metadata = result,
+            }
+        }
+
+        let mut full = true;
+        let mut empty = true;
+        for i in 0..ARRAY_SIZE {
+            let rank = Self::rank(i, metadata);
+            if full && rank == 0 {
+                full = false;
+            }
+            if empty && rank != 0 && rank <= ARRAY_SIZE.try_into().unwrap() {
+                empty = false;
+            }
+            if !full && !empty {
+                break;
+            }
+        }
+
+        (removed, full, empty)
+    }
+
     /// Returns a value associated with the key.
     pub fn search<Q>(&self, key: &Q) -> Option<&V>
     where
@@ -326,9 +415,7 @@ where
             }
         }
         if max_min_index < ARRAY_SIZE {
-            return (max_min_index, unsafe {
-                &*self.entry_array[max_min_index].as_ptr()
-            });
+            return (max_min_index, unsafe { &*self.entry_ptr(max_min_index) });
         }
         (usize::MAX, std::ptr::null())
     }
@@ -379,13 +466,13 @@ where
         self.metadata.load(Relaxed) == metadata
     }
 
+    /// Returns a pointer to the slot at the given index.
+    fn entry_ptr(&self, index: usize) -> *mut (K, V) {
+        self.entry_array[index].get().cast::<(K, V)>()
+    }
+
     fn write(&self, index: usize, key: K, value: V) {
-        unsafe {
-            let entry_array_ptr = &self.entry_array as *const EntryArray<K, V>;
-            let entry_array_mut_ptr = entry_array_ptr as *mut EntryArray<K, V>;
-            let entry_array_mut_ref = &mut (*entry_array_mut_ptr);
-            entry_array_mut_ref[index].as_mut_ptr().write((key, value))
-        };
+        unsafe { self.entry_ptr(index).write((key, value)) };
     }
 
     pub fn next(&self, index: usize, metadata: u32) -> (usize, *const (K, V)) {
@@ -409,9 +496,7 @@ where
                     }
                 }
                 if next_rank <= ARRAY_SIZE.try_into().unwrap() {
-                    return (next_index, unsafe {
-                        &*self.entry_array[next_index].as_ptr()
-                    });
+                    return (next_index, unsafe { &*self.entry_ptr(next_index) });
                 }
             }
         }
@@ -463,24 +548,63 @@ where
         K: Borrow<Q>,
         Q: Ord + ?Sized,
     {
-        let entry_ref = unsafe { &*self.entry_array[index].as_ptr() };
+        let entry_ref = unsafe { &*self.entry_ptr(index) };
         (*entry_ref.0.borrow()).cmp(key)
     }
 
     fn take(&self, index: usize) -> (K, V) {
-        unsafe {
-            let entry_array_ptr = &self.entry_array as *const EntryArray<K, V>;
-            let entry_array_mut_ptr = entry_array_ptr as *mut EntryArray<K, V>;
-            let entry_array_mut_ref = &mut (*entry_array_mut_ptr);
-            let entry_ptr = &mut entry_array_mut_ref[index] as *mut MaybeUninit<(K, V)>;
-            std::ptr::replace(entry_ptr, MaybeUninit::uninit()).assume_init()
-        }
+        unsafe { self.entry_ptr(index).read() }
     }
 
-    fn read(&self, index: usize) -> (&K, &V) {
-        let entry_ref = unsafe { &*self.entry_array[index].as_ptr() };
+    pub fn read(&self, index: usize) -> (&K, &V) {
+        let entry_ref = unsafe { &*self.entry_ptr(index) };
         (&entry_ref.0, &entry_ref.1)
     }
+
+    /// Returns the number of valid entries.
+    pub fn num_entries(&self) -> usize {
+        LeafScanner::new(self).count()
+    }
+
+    /// Checks if the keys are in order and within the given bounds.
+    ///
+    /// The lower bound is exclusive, and the upper bound is inclusive; `id` identifies the
+    /// leaf, leaf node, or internal node that owns the keys.
+    #[cfg(any(test, feature = "debug-tools"))]
+    pub fn validate_keys(
+        &self,
+        id: usize,
+        lower: Option<&K>,
+        upper: Option<&K>,
+    ) -> Result<(), TreeValidationError> {
+        let mut prev_key: Option<&K> = None;
+        for (key, _) in LeafScanner::new(self) {
+            if matches!(prev_key, Some(prev_key) if prev_key.cmp(key) != Ordering::Less) {
+                return Err(TreeValidationError::Unordered(id));
+            }
+            if matches!(lower, Some(lower) if lower.cmp(key) != Ordering::Less)
+                || matches!(upper, Some(upper) if upper.cmp(key) == Ordering::Less)
+            {
+                return Err(TreeValidationError::SeparatorViolated(id));
+            }
+            prev_key.replace(key);
+        }
+        Ok(())
+    }
+
+    /// Overwrites the max key without adjusting the metadata.
+    #[cfg(test)]
+    pub fn overwrite_max_key(&self, key: K) {
+        let (max_key, _) = self.max().unwrap();
+        let key_ptr = (0..ARRAY_SIZE)
+            .map(|i| unsafe { std::ptr::addr_of_mut!((*self.entry_ptr(i)).0) })
+            .find(|key_ptr| std::ptr::eq(*key_ptr, max_key))
+            .unwrap();
+        unsafe {
+            std::ptr::drop_in_place(key_ptr);
+            std::ptr::write(key_ptr, key);
+        }
+    }
 }
 
 impl<K, V> Drop for Leaf<K, V>
@@ -565,7 +689,7 @@ where
         while let Err(result) =
             self.leaf
                 .metadata
-                .compare_exchange(self.metadata, new_metadata, Release, Relaxed)
+                .compare_exchange(self.metadata, new_metadata, Release, Acquire)
         {
             for i in 0..ARRAY_SIZE {
                 if i == self.index {
@@ -1117,6 +1241,7 @@ mod test {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn update() {
         let num_threads = (ARRAY_SIZE + 1) as usize;
         for _ in 0..256 {
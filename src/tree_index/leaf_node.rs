@@ -1,11 +1,15 @@
 use super::leaf::{LeafScanner, ARRAY_SIZE};
 use super::Leaf;
+use super::TreeStatistics;
+#[cfg(any(test, feature = "debug-tools"))]
+use super::TreeValidationError;
 use super::{InsertError, RemoveError, SearchError};
 use crate::common::linked_list::LinkedList;
-use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+use crate::ebr::{Atomic, Guard, Owned, Shared};
 use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::fmt::Display;
+use std::ops::Bound::{self, Excluded, Included};
 use std::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
 
 /// Leaf node.
@@ -47,7 +51,7 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> LeafNode<K, V> {
     }
 
     /// Searches for an entry associated with the given key.
-    pub fn search<'g, Q>(&self, key: &'g Q, guard: &'g Guard) -> Result<Option<&'g V>, SearchError>
+    pub fn search<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Result<Option<&'g V>, SearchError>
     where
         K: 'g + Borrow<Q>,
         Q: Ord + ?Sized,
@@ -166,6 +170,51 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> LeafNode<K, V> {
         }
     }
 
+    /// Returns the maximum key entry within the given upper bound.
+    pub fn max_within<'g>(
+        &self,
+        bound: Bound<&K>,
+        guard: &'g Guard,
+    ) -> Result<Option<(&'g K, &'g V)>, SearchError> {
+        loop {
+            let scanner = LeafScanner::new(&self.leaves.0);
+            let metadata = scanner.metadata();
+            let mut candidates = Vec::with_capacity(ARRAY_SIZE + 1);
+            let mut bounded = false;
+            for (child_key, child) in scanner {
+                candidates.push(child.load(Acquire, guard));
+                if let Excluded(key) | Included(key) = bound {
+                    if child_key.cmp(key) != Ordering::Less {
+                        // Subsequent leaves only contain keys greater than the bound.
+                        bounded = true;
+                        break;
+                    }
+                }
+            }
+            if !bounded {
+                candidates.push((self.leaves.1).load(Relaxed, guard));
+            }
+            if !(self.leaves.0).validate(metadata) {
+                // Data race resolution - see LeafNode::search.
+                continue;
+            }
+            for (index, child_leaf) in candidates.iter().enumerate().rev() {
+                if child_leaf.is_null() {
+                    if child_leaf.tag() == 1 || index + 1 < candidates.len() || bounded {
+                        // The leaf node is bound to be freed.
+                        return Err(SearchError::Retry);
+                    }
+                    // The TreeIndex is empty.
+                    continue;
+                }
+                if let Some(entry) = unsafe { child_leaf.deref() }.max_within(bound) {
+                    return Ok(Some(entry));
+                }
+            }
+            return Ok(None);
+        }
+    }
+
     /// Inserts a key-value pair.
     pub fn insert(&self, key: K, value: V, guard: &Guard) -> Result<(), InsertError<K, V>> {
         loop {
@@ -247,10 +296,11 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> LeafNode<K, V> {
     }
 
     /// Removes an entry associated with the given key.
-    pub fn remove<Q>(&self, key: &Q, guard: &Guard) -> Result<bool, RemoveError>
+    pub fn remove<Q, F>(&self, key: &Q, remover: &mut F, guard: &Guard) -> Result<bool, RemoveError>
     where
         K: Borrow<Q>,
         Q: Ord + ?Sized,
+        F: FnMut(&Leaf<K, V>) -> (bool, bool, bool),
     {
         loop {
             let result = (self.leaves.0).min_greater_equal(&key);
@@ -265,7 +315,7 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> LeafNode<K, V> {
                     return Err(RemoveError::Retry(false));
                 }
                 let child_leaf_ref = unsafe { child_leaf.deref() };
-                let (removed, full, empty) = child_leaf_ref.remove(key);
+                let (removed, full, empty) = remover(child_leaf_ref);
                 if !full && !empty {
                     return Ok(removed);
                 } else if !empty {
@@ -289,7 +339,7 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> LeafNode<K, V> {
                     continue;
                 }
                 let unbounded_leaf_ref = unsafe { unbounded_shared.deref() };
-                let (removed, full, empty) = unbounded_leaf_ref.remove(key);
+                let (removed, full, empty) = remover(unbounded_leaf_ref);
                 if !full && !empty {
                     return Ok(removed);
                 } else if !empty {
@@ -648,14 +698,81 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> LeafNode<K, V> {
             .new_leaves
             .swap(Shared::null().with_tag(1), Relaxed, &guard);
         if !unused_leaves.is_null() {
+            let unused_leaves = unsafe { unused_leaves.into_owned() };
+            let obsolete_leaf = unused_leaves.origin_leaf_ptr.load(Relaxed, &guard);
             unsafe {
-                let obsolete_leaf = unused_leaves.deref().origin_leaf_ptr.load(Relaxed, &guard);
                 // Makes the leaf unreachable before dropping it.
                 obsolete_leaf.deref().pop_self(0, guard);
                 guard.defer_destroy(obsolete_leaf);
             }
         }
     }
+
+    /// Accumulates the statistics of the leaf node and its leaves.
+    pub fn statistics(&self, depth: usize, statistics: &mut TreeStatistics, guard: &Guard) {
+        statistics.num_nodes += 1;
+        if statistics.depth < depth {
+            statistics.depth = depth;
+        }
+        if self.obsolete(guard) {
+            statistics.num_retired += 1;
+        }
+        let leaves = LeafScanner::new(&self.leaves.0)
+            .map(|(_, child)| child.load(Acquire, guard))
+            .chain(std::iter::once(self.leaves.1.load(Acquire, guard)));
+        for leaf in leaves {
+            if let Some(leaf_ref) = unsafe { leaf.as_ref() } {
+                statistics.num_children += 1;
+                statistics.num_leaves += 1;
+                statistics.num_entries += leaf_ref.num_entries();
+                if leaf_ref.obsolete() {
+                    statistics.num_retired += 1;
+                }
+            }
+        }
+    }
+
+    /// Validates the leaf node and its leaves.
+    ///
+    /// The keys in the leaf node must be greater than `lower`, and less than or equal to `upper`.
+    #[cfg(any(test, feature = "debug-tools"))]
+    pub fn validate(
+        &self,
+        lower: Option<&K>,
+        upper: Option<&K>,
+        guard: &Guard,
+    ) -> Result<(), TreeValidationError> {
+        if self.obsolete(guard) {
+            return Err(TreeValidationError::RetiredReachable(self.id()));
+        }
+        self.leaves.0.validate_keys(self.id(), lower, upper)?;
+        let mut low_key = lower;
+        let leaves = LeafScanner::new(&self.leaves.0)
+            .map(|(key, child)| (Some(key), child.load(Acquire, guard)))
+            .chain(std::iter::once((upper, self.leaves.1.load(Acquire, guard))));
+        for (high_key, leaf) in leaves {
+            if let Some(leaf_ref) = unsafe { leaf.as_ref() } {
+                if leaf_ref.obsolete() {
+                    return Err(TreeValidationError::RetiredReachable(leaf_ref.id()));
+                }
+                leaf_ref.validate_keys(leaf_ref.id(), low_key, high_key)?;
+            }
+            low_key = high_key;
+        }
+        Ok(())
+    }
+
+    /// Overwrites the max key of the first leaf, and returns the identifier of the leaf.
+    #[cfg(test)]
+    pub fn overwrite_first_leaf_max_key(&self, key: K, guard: &Guard) -> usize {
+        let leaf = LeafScanner::new(&self.leaves.0).next().map_or_else(
+            || self.leaves.1.load(Acquire, guard),
+            |(_, child)| child.load(Acquire, guard),
+        );
+        let leaf_ref = unsafe { leaf.deref() };
+        leaf_ref.overwrite_max_key(key);
+        leaf_ref.id()
+    }
 }
 
 impl<K: Clone + Display + Ord + Send + Sync, V: Clone + Display + Send + Sync> LeafNode<K, V> {
@@ -765,14 +882,12 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> Drop for LeafNode<K,
     fn drop(&mut self) {
         debug_assert!(self
             .new_leaves
-            .load(Relaxed, unsafe { crossbeam_epoch::unprotected() })
+            .load(Relaxed, unsafe { crate::ebr::unprotected() })
             .is_null());
 
         // The leaf node has become unreachable, and so have all the children, therefore pinning is unnecessary.
         for entry in LeafScanner::new(&self.leaves.0) {
-            let child = entry
-                .1
-                .load(Acquire, unsafe { crossbeam_epoch::unprotected() });
+            let child = entry.1.load(Acquire, unsafe { crate::ebr::unprotected() });
             if !child.is_null() {
                 unsafe {
                     drop(child.into_owned());
@@ -782,7 +897,7 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> Drop for LeafNode<K,
         let unbounded_shared = self
             .leaves
             .1
-            .load(Acquire, unsafe { crossbeam_epoch::unprotected() });
+            .load(Acquire, unsafe { crate::ebr::unprotected() });
         if !unbounded_shared.is_null() {
             unsafe {
                 drop(unbounded_shared.into_owned());
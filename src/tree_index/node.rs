@@ -1,11 +1,15 @@
 use super::leaf::{LeafScanner, ARRAY_SIZE};
 use super::leaf_node::{LeafNode, LeafNodeLocker};
 use super::Leaf;
+use super::TreeStatistics;
+#[cfg(any(test, feature = "debug-tools"))]
+use super::TreeValidationError;
 use super::{InsertError, RemoveError, SearchError};
-use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+use crate::ebr::{Atomic, Guard, Owned, Shared};
 use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::fmt::Display;
+use std::ops::Bound::{self, Excluded, Included};
 use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
 
 /// Node types.
@@ -59,8 +63,49 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> Node<K, V> {
         }
     }
 
+    /// Accumulates the statistics of the node and its descendants.
+    pub fn statistics(&self, depth: usize, statistics: &mut TreeStatistics, guard: &Guard) {
+        match &self.entry {
+            NodeType::Internal(internal_node) => internal_node.statistics(depth, statistics, guard),
+            NodeType::Leaf(leaf_node) => leaf_node.statistics(depth, statistics, guard),
+        }
+    }
+
+    /// Validates the node and its descendants.
+    ///
+    /// The keys in the node must be greater than `lower`, and less than or equal to `upper`.
+    #[cfg(any(test, feature = "debug-tools"))]
+    pub fn validate(
+        &self,
+        lower: Option<&K>,
+        upper: Option<&K>,
+        guard: &Guard,
+    ) -> Result<(), TreeValidationError> {
+        match &self.entry {
+            NodeType::Internal(internal_node) => internal_node.validate(lower, upper, guard),
+            NodeType::Leaf(leaf_node) => leaf_node.validate(lower, upper, guard),
+        }
+    }
+
+    /// Overwrites the max key of the first leaf, and returns the identifier of the leaf.
+    #[cfg(test)]
+    pub fn overwrite_first_leaf_max_key(&self, key: K, guard: &Guard) -> usize {
+        match &self.entry {
+            NodeType::Internal(internal_node) => {
+                let child = LeafScanner::new(&internal_node.children.0)
+                    .next()
+                    .map_or_else(
+                        || internal_node.children.1.load(Acquire, guard),
+                        |(_, child)| child.load(Acquire, guard),
+                    );
+                unsafe { child.deref() }.overwrite_first_leaf_max_key(key, guard)
+            }
+            NodeType::Leaf(leaf_node) => leaf_node.overwrite_first_leaf_max_key(key, guard),
+        }
+    }
+
     /// Searches for an entry associated with the given key.
-    pub fn search<'g, Q>(&self, key: &'g Q, guard: &'g Guard) -> Result<Option<&'g V>, SearchError>
+    pub fn search<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Result<Option<&'g V>, SearchError>
     where
         K: 'g + Borrow<Q>,
         Q: Ord + ?Sized,
@@ -91,6 +136,18 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> Node<K, V> {
         }
     }
 
+    /// Returns the maximum key entry within the given upper bound.
+    pub fn max_within<'g>(
+        &self,
+        bound: Bound<&K>,
+        guard: &'g Guard,
+    ) -> Result<Option<(&'g K, &'g V)>, SearchError> {
+        match &self.entry {
+            NodeType::Internal(internal_node) => internal_node.max_within(bound, guard),
+            NodeType::Leaf(leaf_node) => leaf_node.max_within(bound, guard),
+        }
+    }
+
     /// Inserts a key-value pair.
     ///
     /// It is a recursive call, and therefore stack-overflow may occur.
@@ -102,14 +159,18 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> Node<K, V> {
     }
 
     /// Removes an entry associated with the given key.
-    pub fn remove<Q>(&self, key: &Q, guard: &Guard) -> Result<bool, RemoveError>
+    ///
+    /// `remover` removes entries from the leaf that the key belongs to, and returns whether any
+    /// entries were removed, the leaf is full, and the leaf is empty.
+    pub fn remove<Q, F>(&self, key: &Q, remover: &mut F, guard: &Guard) -> Result<bool, RemoveError>
     where
         K: Borrow<Q>,
         Q: Ord + ?Sized,
+        F: FnMut(&Leaf<K, V>) -> (bool, bool, bool),
     {
         match &self.entry {
-            NodeType::Internal(internal_node) => internal_node.remove(key, guard),
-            NodeType::Leaf(leaf_node) => leaf_node.remove(key, guard),
+            NodeType::Internal(internal_node) => internal_node.remove(key, remover, guard),
+            NodeType::Leaf(leaf_node) => leaf_node.remove(key, remover, guard),
         }
     }
 
@@ -301,6 +362,51 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> InternalNode<K, V> {
         depth
     }
 
+    /// Accumulates the statistics of the internal node and its descendants.
+    fn statistics(&self, depth: usize, statistics: &mut TreeStatistics, guard: &Guard) {
+        statistics.num_nodes += 1;
+        if self.obsolete(guard) {
+            statistics.num_retired += 1;
+        }
+        let children = LeafScanner::new(&self.children.0)
+            .map(|(_, child)| child.load(Acquire, guard))
+            .chain(std::iter::once(self.children.1.load(Acquire, guard)));
+        for child in children {
+            if let Some(child_ref) = unsafe { child.as_ref() } {
+                statistics.num_children += 1;
+                child_ref.statistics(depth + 1, statistics, guard);
+            }
+        }
+    }
+
+    /// Validates the internal node and its descendants.
+    #[cfg(any(test, feature = "debug-tools"))]
+    fn validate(
+        &self,
+        lower: Option<&K>,
+        upper: Option<&K>,
+        guard: &Guard,
+    ) -> Result<(), TreeValidationError> {
+        if self.obsolete(guard) {
+            return Err(TreeValidationError::RetiredReachable(self.id()));
+        }
+        self.children.0.validate_keys(self.id(), lower, upper)?;
+        let mut low_key = lower;
+        let children = LeafScanner::new(&self.children.0)
+            .map(|(key, child)| (Some(key), child.load(Acquire, guard)))
+            .chain(std::iter::once((
+                upper,
+                self.children.1.load(Acquire, guard),
+            )));
+        for (high_key, child) in children {
+            if let Some(child_ref) = unsafe { child.as_ref() } {
+                child_ref.validate(low_key, high_key, guard)?;
+            }
+            low_key = high_key;
+        }
+        Ok(())
+    }
+
     /// Checks if the internal node is obsolete.
     fn obsolete(&self, guard: &Guard) -> bool {
         if self.children.0.obsolete() {
@@ -312,7 +418,7 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> InternalNode<K, V> {
     }
 
     /// Searches for an entry associated with the given key.
-    fn search<'g, Q>(&self, key: &'g Q, guard: &'g Guard) -> Result<Option<&'g V>, SearchError>
+    fn search<'g, Q>(&self, key: &Q, guard: &'g Guard) -> Result<Option<&'g V>, SearchError>
     where
         K: 'g + Borrow<Q>,
         Q: Ord + ?Sized,
@@ -422,6 +528,47 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> InternalNode<K, V> {
         }
     }
 
+    /// Returns the maximum key entry within the given upper bound.
+    fn max_within<'g>(
+        &self,
+        bound: Bound<&K>,
+        guard: &'g Guard,
+    ) -> Result<Option<(&'g K, &'g V)>, SearchError> {
+        loop {
+            let scanner = LeafScanner::new(&self.children.0);
+            let metadata = scanner.metadata();
+            let mut candidates = Vec::with_capacity(ARRAY_SIZE + 1);
+            let mut bounded = false;
+            for (child_key, child) in scanner {
+                candidates.push(child.load(Acquire, guard));
+                if let Excluded(key) | Included(key) = bound {
+                    if child_key.cmp(key) != Ordering::Less {
+                        // Subsequent nodes only contain keys greater than the bound.
+                        bounded = true;
+                        break;
+                    }
+                }
+            }
+            if !bounded {
+                candidates.push((self.children.1).load(Acquire, guard));
+            }
+            if !(self.children.0).validate(metadata) {
+                // Data race resolution - see LeafNode::search.
+                continue;
+            }
+            for child_node in candidates.iter().rev() {
+                if child_node.is_null() {
+                    // child_node being null indicates that the node is bound to be freed.
+                    return Err(SearchError::Retry);
+                }
+                if let Some(entry) = unsafe { child_node.deref() }.max_within(bound, guard)? {
+                    return Ok(Some(entry));
+                }
+            }
+            return Ok(None);
+        }
+    }
+
     /// Inserts a key-value pair.
     fn insert(&self, key: K, value: V, guard: &Guard) -> Result<(), InsertError<K, V>> {
         // Possible data race: the node is being split, for instance,
@@ -497,10 +644,11 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> InternalNode<K, V> {
     }
 
     /// Removes an entry associated with the given key.
-    fn remove<Q>(&self, key: &Q, guard: &Guard) -> Result<bool, RemoveError>
+    fn remove<Q, F>(&self, key: &Q, remover: &mut F, guard: &Guard) -> Result<bool, RemoveError>
     where
         K: Borrow<Q>,
         Q: Ord + ?Sized,
+        F: FnMut(&Leaf<K, V>) -> (bool, bool, bool),
     {
         loop {
             let result = (self.children.0).min_greater_equal(&key);
@@ -514,7 +662,7 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> InternalNode<K, V> {
                     // child_node being null indicates that the node is bound to be freed.
                     return Err(RemoveError::Retry(false));
                 }
-                return match unsafe { child_node.deref().remove(key, guard) } {
+                return match unsafe { child_node.deref().remove(key, remover, guard) } {
                     Ok(removed) => Ok(removed),
                     Err(remove_error) => match remove_error {
                         RemoveError::Empty(removed) => self.coalesce(removed, guard),
@@ -529,7 +677,7 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> InternalNode<K, V> {
                     // Data race resolution - see LeafNode::search.
                     continue;
                 }
-                return match unsafe { unbounded_shared.deref().remove(key, guard) } {
+                return match unsafe { unbounded_shared.deref().remove(key, remover, guard) } {
                     Ok(removed) => Ok(removed),
                     Err(remove_error) => match remove_error {
                         RemoveError::Empty(removed) => self.coalesce(removed, guard),
@@ -909,14 +1057,12 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> Drop for InternalNode
     fn drop(&mut self) {
         debug_assert!(self
             .new_children
-            .load(Relaxed, unsafe { crossbeam_epoch::unprotected() })
+            .load(Relaxed, unsafe { crate::ebr::unprotected() })
             .is_null());
 
         // The internal node has become unreachable, and so have all the children, therefore pinning is unnecessary.
         for entry in LeafScanner::new(&self.children.0) {
-            let child = entry
-                .1
-                .load(Acquire, unsafe { crossbeam_epoch::unprotected() });
+            let child = entry.1.load(Acquire, unsafe { crate::ebr::unprotected() });
             if !child.is_null() {
                 drop(unsafe { child.into_owned() });
             }
@@ -924,7 +1070,7 @@ impl<K: Clone + Ord + Send + Sync, V: Clone + Send + Sync> Drop for InternalNode
         let unbounded_child = self
             .children
             .1
-            .load(Acquire, unsafe { crossbeam_epoch::unprotected() });
+            .load(Acquire, unsafe { crate::ebr::unprotected() });
         if !unbounded_child.is_null() {
             drop(unsafe { unbounded_child.into_owned() });
         }
@@ -1078,6 +1224,7 @@ where
 
 #[cfg(test)]
 mod test {
+    use super::super::TreeIndex;
     use super::*;
     use std::sync::atomic::AtomicBool;
     use std::sync::{Arc, Barrier, Mutex};
@@ -1089,7 +1236,7 @@ mod test {
         let range = 16384;
         let barrier = Arc::new(Barrier::new(num_threads));
         let node = Arc::new(Node::new_leaf_node());
-        assert!(node.insert(0, 0, &crossbeam_epoch::pin()).is_ok());
+        assert!(node.insert(0, 0, &crate::ebr::pin()).is_ok());
         let inserted = Arc::new(Mutex::new(Vec::new()));
         inserted.lock().unwrap().push(0);
         let full = Arc::new(AtomicBool::new(false));
@@ -1104,7 +1251,7 @@ mod test {
                 let first_key = thread_id * range + 1;
                 barrier_copied.wait();
                 for key in first_key..(first_key + range) {
-                    let guard = crossbeam_epoch::pin();
+                    let guard = crate::ebr::pin();
                     loop {
                         match node_copied.insert(key, key, &guard) {
                             Ok(()) => {
@@ -1141,7 +1288,7 @@ mod test {
             handle.join().unwrap();
         }
 
-        let guard = crossbeam_epoch::pin();
+        let guard = crate::ebr::pin();
         if full.load(Relaxed) {
             node.rollback(&guard);
         }
@@ -1158,4 +1305,53 @@ mod test {
             println!("iterated: {}", iterated);
         }
     }
+
+    #[test]
+    fn validate() {
+        for corrupt_order in [true, false] {
+            let tree: TreeIndex<usize, usize> = TreeIndex::new();
+            for key in 0..4096 {
+                assert!(tree.insert(key, key).is_ok());
+            }
+            assert!(tree.validate().is_ok());
+            let guard = crate::ebr::pin();
+            let root_node = tree.root.load(Acquire, &guard);
+            // The smallest key in the first leaf is 0, and the largest key in the tree is 4095.
+            let key = if corrupt_order { 0 } else { 4096 };
+            let leaf_id = unsafe { root_node.deref() }.overwrite_first_leaf_max_key(key, &guard);
+            if corrupt_order {
+                assert_eq!(
+                    tree.validate(),
+                    Err(TreeValidationError::Unordered(leaf_id))
+                );
+            } else {
+                assert_eq!(
+                    tree.validate(),
+                    Err(TreeValidationError::SeparatorViolated(leaf_id))
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn statistics() {
+        let tree: TreeIndex<usize, usize> = TreeIndex::new();
+        let mut prev_depth = 0;
+        for exponent in 1..18 {
+            for key in (1 << (exponent - 1))..(1 << exponent) {
+                assert!(tree.insert(key, key).is_ok());
+            }
+            let statistics = tree.statistics();
+            assert_eq!(statistics.num_entries(), (1 << exponent) - 1);
+            assert_eq!(statistics.num_retired(), 0);
+            assert_eq!(statistics.depth(), tree.depth());
+            // Sequential insertions leave leaves half full, and the depth grows logarithmically.
+            assert!(exponent < 4 || statistics.average_leaf_fill() >= 0.45);
+            assert!(statistics.depth() >= prev_depth);
+            assert!(statistics.depth() <= 1 + exponent / 2);
+            prev_depth = statistics.depth();
+        }
+        assert!(tree.validate().is_ok());
+    }
 }
@@ -3,13 +3,22 @@ mod hashmap_test {
     use proptest::prelude::*;
     use proptest::strategy::{Strategy, ValueTree};
     use proptest::test_runner::TestRunner;
-    use scc::HashMap;
+    use scc::{
+        ArrayAllocator, CasError, DuplicateKeyPolicy, Event, HashMap, RemoveIfResult, ShrinkPolicy,
+        TryReserveError,
+    };
+    use serde::de::value::MapDeserializer;
+    use serde::de::DeserializeSeed;
+    use std::alloc::{alloc, dealloc, Layout};
+    use std::collections::hash_map::RandomState;
     use std::collections::BTreeSet;
-    use std::hash::{Hash, Hasher};
+    use std::hash::{BuildHasher, Hash, Hasher};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
     use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
-    use std::sync::atomic::{AtomicU64, AtomicUsize};
-    use std::sync::{Arc, Barrier};
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+    use std::sync::{Arc, Barrier, Mutex};
     use std::thread;
+    use std::time::{Duration, Instant};
 
     proptest! {
         #[test]
@@ -19,23 +28,23 @@ mod hashmap_test {
 
             let result1 = hashmap.insert(key, 0);
             assert!(result1.is_ok());
-            if let Ok(result) = result1 {
+            if let Ok(mut result) = result1 {
                 assert_eq!(result.get(), (&key, &mut 0));
             }
 
             let result2 = hashmap.insert(key, 0);
             assert!(result2.is_err());
-            if let Err((result, _, _)) = result2 {
+            if let Err((mut result, _, _)) = result2 {
                 assert_eq!(result.get(), (&key, &mut 0));
             }
 
-            let result3 = hashmap.upsert(key, 1);
+            let mut result3 = hashmap.upsert(key, 1);
             assert_eq!(result3.get(), (&key, &mut 1));
             drop(result3);
 
             let result4 = hashmap.insert(key, 10);
             assert!(result4.is_err());
-            if let Err((result, _, _)) = result4 {
+            if let Err((mut result, _, _)) = result4 {
                 assert_eq!(result.get(), (&key, &mut 1));
                 *result.get().1 = 2;
             }
@@ -60,7 +69,7 @@ mod hashmap_test {
 
             let result9 = hashmap.insert(key + 2, 10);
             assert!(result9.is_ok());
-            if let Ok(result) = result9 {
+            if let Ok(mut result) = result9 {
                 assert_eq!(result.get(), (&(key + 2), &mut 10));
                 result.erase();
             }
@@ -168,225 +177,3983 @@ mod hashmap_test {
         }
     }
 
-    struct Data<'a> {
-        data: u64,
-        checker: &'a AtomicUsize,
+    #[test]
+    fn get_or_insert_with() {
+        let num_threads = 8;
+        let data_size = 1024;
+        let hashmap: Arc<HashMap<u64, u64>> = Arc::new(Default::default());
+        let num_constructed = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for _ in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let num_constructed_copied = num_constructed.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for key in 0..data_size {
+                    let mut accessor = hashmap_copied.get_or_insert_with(key, || {
+                        num_constructed_copied.fetch_add(1, Relaxed);
+                        0
+                    });
+                    *accessor.get().1 += 1;
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(num_constructed.load(Relaxed), data_size as usize);
+        for key in 0..data_size {
+            assert_eq!(
+                hashmap.read(&key, |_, value| *value),
+                Some(num_threads as u64)
+            );
+        }
     }
 
-    impl<'a> Data<'a> {
-        fn new(data: u64, checker: &'a AtomicUsize) -> Data<'a> {
-            checker.fetch_add(1, Relaxed);
-            Data {
-                data: data,
-                checker: checker,
-            }
+    #[test]
+    fn compare_exchange() {
+        let num_threads = 8;
+        let num_increments = 4096;
+        let hashmap: Arc<HashMap<u64, u64>> = Arc::new(Default::default());
+        assert!(hashmap.insert(0, 0).is_ok());
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for _ in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for _ in 0..num_increments {
+                    loop {
+                        let current = hashmap_copied.read(&0, |_, value| *value).unwrap();
+                        match hashmap_copied.compare_exchange(
+                            &0,
+                            |value| *value == current,
+                            current + 1,
+                        ) {
+                            Ok(previous) => {
+                                assert_eq!(previous, current);
+                                break;
+                            }
+                            Err(CasError::Mismatch(value)) => assert_eq!(value, current + 1),
+                            Err(CasError::NotFound(_)) => unreachable!(),
+                        }
+                    }
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
         }
+        assert_eq!(
+            hashmap.read(&0, |_, value| *value),
+            Some((num_threads * num_increments) as u64)
+        );
+        assert_eq!(
+            hashmap.compare_exchange(&1, |_| true, 1),
+            Err(CasError::NotFound(1))
+        );
     }
 
-    impl<'a> Clone for Data<'a> {
-        fn clone(&self) -> Self {
-            Data::new(self.data, self.checker)
+    #[test]
+    fn any() {
+        let num_threads = 4;
+        let data_size = 4096;
+        let hashmap: Arc<HashMap<u64, u64>> = Arc::new(Default::default());
+        assert!(hashmap.insert(u64::MAX, 1).is_ok());
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let stopped_copied = stopped.clone();
+            thread_handles.push(thread::spawn(move || {
+                // Keeps growing and shrinking the HashMap.
+                let first_key = data_size * thread_id as u64;
+                while !stopped_copied.load(Relaxed) {
+                    for key in first_key..(first_key + data_size) {
+                        assert!(hashmap_copied.insert(key, 0).is_ok());
+                    }
+                    for key in first_key..(first_key + data_size) {
+                        assert_eq!(hashmap_copied.remove(&key), Some(0));
+                    }
+                }
+            }));
+        }
+        for _ in 0..256 {
+            assert_eq!(
+                hashmap.any(|_, value| *value == 1, |key, _| *key),
+                Some(u64::MAX)
+            );
+            assert!(hashmap.any(|_, value| *value == 2, |_, _| ()).is_none());
+        }
+        stopped.store(true, Release);
+        for handle in thread_handles {
+            handle.join().unwrap();
         }
     }
 
-    impl<'a> Drop for Data<'a> {
-        fn drop(&mut self) {
-            self.checker.fetch_sub(1, Relaxed);
+    #[test]
+    fn clear_chunked() {
+        let data_size = 1 << 18;
+        let budget = 64;
+        // The array is never shrunk, therefore a pass has to claim all the Cells of an array at
+        // least as large as the current one.
+        let hashmap: Arc<HashMap<u64, String>> =
+            Arc::new(HashMap::default().with_shrink_policy(ShrinkPolicy::Never));
+        for key in 0..data_size {
+            assert!(hashmap.insert(key, key.to_string()).is_ok());
         }
-    }
+        let num_cells = hashmap.capacity() / 32;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let hashmap_copied = hashmap.clone();
+        let stopped_copied = stopped.clone();
+        let thread_handle = thread::spawn(move || {
+            // Keeps inserting keys colliding with the Cells being cleared.
+            let mut key = data_size;
+            while !stopped_copied.load(Relaxed) {
+                let _result = hashmap_copied.insert(key, key.to_string());
+                key += 1;
+            }
+        });
 
-    impl<'a> Eq for Data<'a> {}
+        let mut num_calls = 0;
+        while hashmap.clear_chunked(budget) {
+            num_calls += 1;
+        }
+        num_calls += 1;
 
-    impl<'a> Hash for Data<'a> {
-        fn hash<H: Hasher>(&self, state: &mut H) {
-            self.data.hash(state);
+        // Each call claims at most `budget` Cells.
+        assert!(num_calls >= num_cells / budget);
+
+        stopped.store(true, Release);
+        thread_handle.join().unwrap();
+        for key in 0..data_size {
+            assert!(!hashmap.contains(&key));
         }
     }
 
-    impl<'a> PartialEq for Data<'a> {
-        fn eq(&self, other: &Self) -> bool {
-            self.data == other.data
+    #[test]
+    fn debug() {
+        let hashmap: HashMap<u64, u64> = Default::default();
+        assert_eq!(format!("{:?}", hashmap), "{}");
+        let mut output = String::new();
+        assert!(hashmap.fmt_entries(&mut output, 0).is_ok());
+        assert_eq!(output, "{}");
+
+        for key in 0..32 {
+            assert!(hashmap.insert(key, key * 2).is_ok());
         }
-    }
+        let output = format!("{:?}", hashmap);
+        assert!(output.starts_with('{') && output.ends_with('}'));
+        let mut entries: Vec<&str> = output[1..(output.len() - 1)].split(", ").collect();
+        entries.sort_unstable_by_key(|entry| {
+            entry.split(": ").next().unwrap().parse::<u64>().unwrap()
+        });
+        let expected: Vec<String> = (0..32).map(|key| format!("{}: {}", key, key * 2)).collect();
+        assert_eq!(entries, expected);
 
-    proptest! {
-        #[test]
-        fn insert(key in 0u64..16) {
-            let range = 1024;
-            let checker = AtomicUsize::new(0);
-            let hashmap: HashMap<Data, Data> = Default::default();
-            for d in key..(key + range) {
-                let result = hashmap.insert(Data::new(d, &checker), Data::new(d, &checker));
-                assert!(result.is_ok());
-                drop(result);
-                let result = hashmap.upsert(Data::new(d, &checker), Data::new(d + 1, &checker));
-                (*result.get().1) = Data::new(d + 2, &checker);
-            }
+        // The output is truncated once the number of entries exceeds the limit.
+        assert!(hashmap.insert(32, 64).is_ok());
+        let output = format!("{:?}", hashmap);
+        assert!(output.ends_with(", ..}"));
+        assert_eq!(output.matches(": ").count(), 32);
+        for max_entries in [0, 1, 16, 33, 34] {
+            let mut output = String::new();
+            assert!(hashmap.fmt_entries(&mut output, max_entries).is_ok());
+            assert_eq!(output.matches(": ").count(), max_entries.min(33));
+            assert_eq!(output.ends_with("..}"), max_entries < 33);
+        }
 
-            for d in (key + range)..(key + range + range) {
-                let result = hashmap.insert(Data::new(d, &checker), Data::new(d, &checker));
-                assert!(result.is_ok());
-                drop(result);
-                let result = hashmap.upsert(Data::new(d, &checker), Data::new(d + 1, &checker));
-                (*result.get().1) = Data::new(d + 2, &checker);
+        let mut summary = String::new();
+        assert!(hashmap.fmt_summary(&mut summary).is_ok());
+        assert_eq!(
+            summary,
+            format!(
+                "HashMap {{ capacity: {}, len: 33, resizing: false }}",
+                hashmap.capacity()
+            )
+        );
+    }
+
+    #[test]
+    fn prune() {
+        let data_size = 65536;
+        let hashmap: Arc<HashMap<u64, u64>> = Arc::new(Default::default());
+        for key in 0..data_size {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        let stopped = Arc::new(AtomicBool::new(false));
+        let hashmap_copied = hashmap.clone();
+        let stopped_copied = stopped.clone();
+        let thread_handle = thread::spawn(move || {
+            // Keeps growing and shrinking the HashMap in order to trigger resizing.
+            while !stopped_copied.load(Relaxed) {
+                for key in data_size..(data_size * 2) {
+                    assert!(hashmap_copied.insert(key, key).is_ok());
+                }
+                for key in data_size..(data_size * 2) {
+                    assert_eq!(hashmap_copied.remove(&key), Some(key));
+                }
             }
+        });
 
-            let result = hashmap.retain(|k, _| k.data < key + range);
-            assert_eq!(result, (range as usize, range as usize));
+        let mut pruned = Vec::new();
+        let (_, removed) = hashmap.prune(
+            |key, _| *key >= data_size || *key % 3 != 0,
+            |key, value| pruned.push((key, value)),
+        );
+        stopped.store(true, Release);
+        thread_handle.join().unwrap();
 
-            assert_eq!(hashmap.len() as u64, range);
-            let mut found_keys = 0;
-            for iter in hashmap.iter() {
-                assert!(iter.0.data < key + range);
-                assert!(iter.0.data >= key);
-                found_keys += 1;
-            }
-            assert_eq!(found_keys, range);
-            assert_eq!(checker.load(Relaxed) as u64, range * 2);
-            for d in key..(key + range) {
-                let result = hashmap.get(&Data::new(d, &checker));
-                result.unwrap().erase();
-            }
-            assert_eq!(checker.load(Relaxed), 0);
+        assert_eq!(removed, pruned.len());
+        pruned.sort_unstable();
+        let expected: Vec<(u64, u64)> = (0..data_size)
+            .filter(|key| key % 3 == 0)
+            .map(|key| (key, key))
+            .collect();
+        assert_eq!(pruned, expected);
+        for key in 0..data_size {
+            assert_eq!(
+                hashmap.read(&key, |_, value| *value),
+                if key % 3 == 0 { None } else { Some(key) }
+            );
+        }
+    }
 
-            for d in key..(key + range) {
-                let result = hashmap.insert(Data::new(d, &checker), Data::new(d, &checker));
-                assert!(result.is_ok());
-                drop(result);
-                let result = hashmap.upsert(Data::new(d, &checker), Data::new(d + 1, &checker));
-               (*result.get().1) = Data::new(d + 2, &checker);
+    #[test]
+    fn non_clone_value() {
+        struct NonClone<'a> {
+            value: u64,
+            checker: &'a AtomicUsize,
+        }
+        impl<'a> Drop for NonClone<'a> {
+            fn drop(&mut self) {
+                self.checker.fetch_sub(1, Relaxed);
             }
-            let result = hashmap.clear();
-            assert_eq!(result, range as usize);
-            assert_eq!(checker.load(Relaxed), 0);
+        }
 
-            for d in key..(key + range) {
-                let result = hashmap.insert(Data::new(d, &checker), Data::new(d, &checker));
-                assert!(result.is_ok());
-                drop(result);
-                let result = hashmap.upsert(Data::new(d, &checker), Data::new(d + 1, &checker));
-                (*result.get().1) = Data::new(d + 2, &checker);
-            }
-            assert_eq!(checker.load(Relaxed) as u64, range * 2);
-            drop(hashmap);
-            assert_eq!(checker.load(Relaxed), 0);
+        let data_size = 4096;
+        let checker = AtomicUsize::new(0);
+        let hashmap: HashMap<u64, NonClone> = Default::default();
+        for key in 0..data_size {
+            checker.fetch_add(1, Relaxed);
+            let value = NonClone {
+                value: key,
+                checker: &checker,
+            };
+            assert!(hashmap.insert(key, value).is_ok());
         }
+        assert!(hashmap.capacity() >= data_size as usize);
+        for key in 0..data_size {
+            assert_eq!(hashmap.read(&key, |_, value| value.value), Some(key));
+        }
+        assert_eq!(
+            hashmap.retain(|key, _| key % 2 == 0),
+            ((data_size / 2) as usize, (data_size / 2) as usize)
+        );
+        for key in (0..data_size).step_by(4) {
+            assert_eq!(hashmap.remove(&key).map(|value| value.value), Some(key));
+        }
+        assert_eq!(checker.load(Relaxed), (data_size / 4) as usize);
+        drop(hashmap);
+        assert_eq!(checker.load(Relaxed), 0);
     }
-}
 
-#[cfg(test)]
-mod hashindex_test {
-    use proptest::strategy::{Strategy, ValueTree};
-    use proptest::test_runner::TestRunner;
-    use scc::HashIndex;
-    use std::collections::BTreeSet;
-    use std::sync::atomic::AtomicU64;
-    use std::sync::atomic::Ordering::{Acquire, Release};
-    use std::sync::{Arc, Barrier};
-    use std::thread;
+    #[test]
+    fn send_sync() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+
+        assert_send::<HashMap<u64, u64>>();
+        assert_sync::<HashMap<u64, u64>>();
+        assert_send::<HashMap<String, Box<u64>>>();
+        assert_sync::<HashMap<String, Box<u64>>>();
+        assert_send::<HashMap<u64, std::cell::Cell<u64>>>();
+        assert_send::<HashMap<u64, std::sync::mpsc::Sender<u64>>>();
+        assert_sync::<HashMap<u64, std::sync::Mutex<u64>>>();
+    }
 
     #[test]
-    fn string_key() {
-        let hashindex1: HashIndex<String, u32> = Default::default();
-        let hashindex2: HashIndex<u32, String> = Default::default();
-        let mut checker1 = BTreeSet::new();
-        let mut checker2 = BTreeSet::new();
-        let mut runner = TestRunner::default();
-        let test_size = 4096;
-        for i in 0..test_size {
-            let prop_str = "[a-z]{1,16}".new_tree(&mut runner).unwrap();
-            let str_val = prop_str.current();
-            if hashindex1.insert(str_val.clone(), i).is_ok() {
-                checker1.insert((str_val.clone(), i));
-            }
-            let str_borrowed = str_val.as_str();
-            assert!(hashindex1.read(str_borrowed, |_, _| ()).is_some());
+    fn estimated_len() {
+        let data_size = 16384;
+        let num_samples = 64;
+        let mean_variance = |hashmap: &HashMap<u64, u64>| {
+            let estimates: Vec<f64> = (0..num_samples)
+                .map(|_| hashmap.estimated_len() as f64)
+                .collect();
+            let mean = estimates.iter().sum::<f64>() / num_samples as f64;
+            let variance = estimates
+                .iter()
+                .map(|estimate| (estimate - mean) * (estimate - mean))
+                .sum::<f64>()
+                / num_samples as f64;
+            (mean, variance)
+        };
 
-            if hashindex2.insert(i, str_val.clone()).is_ok() {
-                checker2.insert((i, str_val.clone()));
-            }
+        let deterministic: HashMap<u64, u64> = HashMap::default().with_len_sampling(8, true);
+        let random: HashMap<u64, u64> = HashMap::default().with_len_sampling(8, false);
+        for key in 0..data_size {
+            assert!(deterministic.insert(key, key).is_ok());
+            assert!(random.insert(key, key).is_ok());
         }
-        assert_eq!(hashindex1.len(), checker1.len());
-        assert_eq!(hashindex2.len(), checker2.len());
-        for iter in checker1 {
-            assert!(hashindex1.remove(iter.0.as_str()));
+        assert!(deterministic.statistics().len_sampling_deterministic());
+        assert!(!random.statistics().len_sampling_deterministic());
+        assert_eq!(random.statistics().len_sampling_cells(), 8);
+
+        let (mean, variance) = mean_variance(&deterministic);
+        assert_eq!(variance, 0.0);
+        assert!(mean > (data_size / 2) as f64 && mean < (data_size * 2) as f64);
+
+        let (mean, variance) = mean_variance(&random);
+        assert!(variance > 0.0);
+        assert!(mean > (data_size / 2) as f64 && mean < (data_size * 2) as f64);
+
+        // Sampling all the Cells yields the exact number.
+        let exact: HashMap<u64, u64> = HashMap::default().with_len_sampling(usize::MAX, false);
+        for key in 0..data_size {
+            assert!(exact.insert(key, key).is_ok());
         }
-        for iter in checker2 {
-            assert!(hashindex2.remove(&iter.0));
+        assert_eq!(mean_variance(&exact), (data_size as f64, 0.0));
+    }
+
+    #[test]
+    fn max_load_factor() {
+        let data_size = 40000;
+        let sparse: HashMap<u64, u64> = HashMap::default().with_max_load_factor(0.5);
+        let dense: HashMap<u64, u64> = HashMap::default().with_max_load_factor(0.95);
+        for key in 0..data_size {
+            assert!(sparse.insert(key, key).is_ok());
+            assert!(dense.insert(key, key).is_ok());
+        }
+        assert_eq!(sparse.capacity(), 131072);
+        assert_eq!(dense.capacity(), 65536);
+        for key in 0..data_size {
+            assert_eq!(sparse.read(&key, |_, value| *value), Some(key));
+            assert_eq!(dense.read(&key, |_, value| *value), Some(key));
         }
-        assert_eq!(hashindex1.len(), 0);
-        assert_eq!(hashindex2.len(), 0);
     }
 
     #[test]
-    fn visitor() {
+    #[should_panic(expected = "the maximum load factor must be in (0, 1]")]
+    fn max_load_factor_out_of_range() {
+        let _hashmap: HashMap<u64, u64> = HashMap::default().with_max_load_factor(1.5);
+    }
+
+    #[test]
+    fn partial_eq() {
         let data_size = 4096;
-        for _ in 0..64 {
-            let hashindex: Arc<HashIndex<u64, u64>> = Arc::new(Default::default());
-            let hashindex_copied = hashindex.clone();
-            let barrier = Arc::new(Barrier::new(2));
-            let barrier_copied = barrier.clone();
-            let inserted = Arc::new(AtomicU64::new(0));
-            let inserted_copied = inserted.clone();
-            let removed = Arc::new(AtomicU64::new(data_size));
-            let removed_copied = removed.clone();
-            let thread_handle = thread::spawn(move || {
-                // test insert
-                for _ in 0..2 {
-                    barrier_copied.wait();
-                    let mut scanned = 0;
-                    let mut checker = BTreeSet::new();
-                    let max = inserted_copied.load(Acquire);
-                    for iter in hashindex_copied.iter() {
-                        scanned += 1;
-                        checker.insert(*iter.0);
-                    }
-                    println!("scanned: {}, max: {}", scanned, max);
-                    for key in 0..max {
-                        assert!(checker.contains(&key));
-                    }
-                }
-                // test remove
-                for _ in 0..2 {
-                    barrier_copied.wait();
-                    let mut scanned = 0;
-                    let max = removed_copied.load(Acquire);
-                    for iter in hashindex_copied.iter() {
-                        scanned += 1;
-                        assert!(*iter.0 < max);
-                    }
-                    println!("scanned: {}, max: {}", scanned, max);
-                }
-            });
-            // insert
-            barrier.wait();
-            for i in 0..data_size {
-                if i == data_size / 2 {
-                    barrier.wait();
-                }
-                assert!(hashindex.insert(i, i).is_ok());
-                inserted.store(i, Release);
-            }
-            // remove
-            barrier.wait();
-            for i in (0..data_size).rev() {
-                if i == data_size / 2 {
-                    barrier.wait();
-                }
-                assert!(hashindex.remove(&i));
-                removed.store(i, Release);
-            }
-            thread_handle.join().unwrap();
+        let hashmap1: HashMap<u64, u64> = Default::default();
+        let hashmap2: HashMap<u64, u64> = HashMap::new(data_size as usize, Default::default());
+        for key in 0..data_size {
+            assert!(hashmap1.insert(key, key).is_ok());
+        }
+        for key in (0..data_size).rev() {
+            assert!(hashmap2.insert(key, key).is_ok());
         }
+        assert!(hashmap1 == hashmap1);
+        assert!(hashmap1 == hashmap2);
+        assert!(hashmap2 == hashmap1);
+
+        // Differs only in a single value.
+        assert!(hashmap2.upsert(7, 0).get() == (&7, &mut 0));
+        assert!(hashmap1 != hashmap2);
+        assert!(hashmap2 != hashmap1);
+        assert!(hashmap2.upsert(7, 7).get() == (&7, &mut 7));
+        assert!(hashmap1 == hashmap2);
+
+        // Differs in a single key with the same length.
+        assert_eq!(hashmap2.remove(&7), Some(7));
+        assert!(hashmap2.insert(data_size, 7).is_ok());
+        assert_eq!(hashmap1.len(), hashmap2.len());
+        assert!(hashmap1 != hashmap2);
+
+        // Differs in length.
+        assert_eq!(hashmap2.remove(&data_size), Some(7));
+        assert!(hashmap1 != hashmap2);
+        assert!(hashmap2 != hashmap1);
     }
-}
+
+    #[test]
+    fn read_only_iterators() {
+        let data_size = 4096;
+        let hashmap: HashMap<u64, u64> = Default::default();
+        for key in 0..data_size {
+            assert!(hashmap.insert(key, key * 2).is_ok());
+        }
+        let keys: BTreeSet<u64> = hashmap.keys().collect();
+        assert_eq!(keys, (0..data_size).collect());
+        let values: BTreeSet<u64> = hashmap.values().collect();
+        assert_eq!(values, (0..data_size).map(|key| key * 2).collect());
+        let entries: BTreeSet<(u64, u64)> = hashmap.entries().collect();
+        assert_eq!(entries, (0..data_size).map(|key| (key, key * 2)).collect());
+
+        let mut keys = hashmap.keys();
+        assert_eq!(keys.by_ref().count(), data_size as usize);
+        assert!(keys.next().is_none());
+        assert_eq!(keys.size_hint(), (0, Some(0)));
+
+        // The lower bound is the number of buffered entries, and the upper bound is based on the
+        // sampled length.
+        let estimated_len = hashmap.estimated_len();
+        let mut entries = hashmap.entries();
+        assert_eq!(entries.size_hint(), (0, Some(estimated_len)));
+        assert!(entries.next().is_some());
+        let (lower, upper) = entries.size_hint();
+        assert!(lower > 0);
+        assert_eq!(upper, Some((estimated_len - 1).max(lower)));
+    }
+
+    #[test]
+    fn read_only_iterators_resize() {
+        let data_size = 16384;
+        let hashmap: Arc<HashMap<u64, u64>> = Arc::new(Default::default());
+        for key in 0..data_size {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        let hashmap_cloned = hashmap.clone();
+        let finished = Arc::new(AtomicBool::new(false));
+        let finished_cloned = finished.clone();
+        let thread_handle = thread::spawn(move || {
+            // Repeatedly grows and shrinks the HashMap without touching the first data_size keys.
+            while !finished_cloned.load(Acquire) {
+                for key in data_size..data_size * 8 {
+                    assert!(hashmap_cloned.insert(key, key).is_ok());
+                }
+                for key in data_size..data_size * 8 {
+                    assert_eq!(hashmap_cloned.remove(&key), Some(key));
+                }
+            }
+        });
+        for _ in 0..16 {
+            let keys: BTreeSet<u64> = hashmap.keys().collect();
+            for key in 0..data_size {
+                assert!(keys.contains(&key), "{}", key);
+            }
+        }
+        finished.store(true, Release);
+        assert!(thread_handle.join().is_ok());
+    }
+
+    #[test]
+    fn lock_entry() {
+        let num_threads = 8;
+        let num_iterations = 256;
+        let hashmap: Arc<HashMap<u64, u64>> = Arc::new(Default::default());
+        let owners: Arc<Vec<AtomicUsize>> = Arc::new((0..4).map(|_| AtomicUsize::new(0)).collect());
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let owners_copied = owners.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for i in 0..num_iterations {
+                    let key = ((thread_id + i) % owners_copied.len()) as u64;
+                    let entry_guard = hashmap_copied.lock_entry(key);
+                    // No other threads can reserve the key at the same time.
+                    assert_eq!(owners_copied[key as usize].swap(thread_id + 1, Relaxed), 0);
+                    let count = entry_guard.existing().map_or(0, |count| *count);
+                    thread::yield_now();
+                    assert_eq!(owners_copied[key as usize].swap(0, Relaxed), thread_id + 1);
+                    if count == 0 {
+                        assert!(entry_guard.insert(1).is_ok());
+                    } else if let Err((mut accessor, _, _)) = entry_guard.insert(0) {
+                        *accessor.get().1 = count + 1;
+                    } else {
+                        unreachable!();
+                    }
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        let mut total = 0;
+        for key in 0..owners.len() as u64 {
+            total += hashmap.read(&key, |_, count| *count).unwrap();
+        }
+        assert_eq!(total, (num_threads * num_iterations) as u64);
+    }
+
+    #[test]
+    fn transfer_swap_values() {
+        let num_threads = 8;
+        let num_iterations = 4096;
+        let num_keys = 16;
+        let num_values = 8;
+        let hashmap: Arc<HashMap<usize, usize>> = Arc::new(Default::default());
+        for value in 0..num_values {
+            assert!(hashmap.insert(value, value).is_ok());
+        }
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for i in 0..num_iterations {
+                    // Neighboring threads work on overlapping pairs of keys.
+                    let a = (thread_id + i) % num_keys;
+                    let b = (thread_id + i * 7 + 1) % num_keys;
+                    if i % 3 == 0 {
+                        hashmap_copied.swap_values(&a, &b);
+                    } else {
+                        let result = hashmap_copied.transfer(&a, b);
+                        assert!(a != b || result.is_err());
+                    }
+                    if i % 64 == 0 {
+                        // Resizes the HashMap.
+                        let offset = num_keys * (thread_id + 1);
+                        for key in offset..offset + num_keys {
+                            assert!(hashmap_copied.insert(key, key).is_ok());
+                        }
+                        for key in offset..offset + num_keys {
+                            assert_eq!(hashmap_copied.remove(&key), Some(key));
+                        }
+                    }
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        let mut values = BTreeSet::new();
+        for key in 0..num_keys {
+            if let Some(value) = hashmap.read(&key, |_, value| *value) {
+                assert!(values.insert(value));
+            }
+        }
+        assert_eq!(values.len(), num_values);
+        assert_eq!(hashmap.len(), num_values);
+    }
+
+    #[test]
+    fn bucket_introspection() {
+        let hashmap: HashMap<u64, u64> = Default::default();
+        assert_eq!(hashmap.array_generation(), 0);
+        for key in 0..4096 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        let generation = hashmap.array_generation();
+        assert!(generation > 0);
+
+        let mut buckets: std::collections::BTreeMap<usize, BTreeSet<u64>> = Default::default();
+        for key in 0..4096 {
+            buckets
+                .entry(hashmap.bucket_of(&key))
+                .or_default()
+                .insert(key);
+        }
+        for (bucket, keys) in buckets {
+            let mut scanned = BTreeSet::new();
+            hashmap.for_each_in_bucket(bucket, |key, value| {
+                assert_eq!(key, value);
+                assert!(scanned.insert(*key));
+            });
+            assert_eq!(scanned, keys);
+        }
+        assert_eq!(hashmap.array_generation(), generation);
+
+        let num_buckets = hashmap.capacity() / 32;
+        let mut num_visited = 0;
+        hashmap.for_each_in_bucket(num_buckets, |_, _| num_visited += 1);
+        assert_eq!(num_visited, 0);
+
+        assert!(hashmap.try_reserve(1 << 16).is_ok());
+        assert_eq!(hashmap.array_generation(), generation + 1);
+    }
+
+    #[test]
+    fn shrink_policy() {
+        let num_keys = 1 << 14;
+        let trajectory = |shrink_policy: ShrinkPolicy| {
+            let hashmap: HashMap<u64, u64> = HashMap::default().with_shrink_policy(shrink_policy);
+            for key in 0..num_keys {
+                assert!(hashmap.insert(key, key).is_ok());
+            }
+            let mut capacities = vec![hashmap.capacity()];
+
+            // Leaves a single key in each Cell, and then removes the rest of the keys.
+            let mut buckets = BTreeSet::new();
+            let (remaining, removed): (Vec<u64>, Vec<u64>) =
+                (0..num_keys).partition(|key| buckets.insert(hashmap.bucket_of(key)));
+            for key in removed {
+                assert_eq!(hashmap.remove(&key), Some(key));
+            }
+            capacities.push(hashmap.capacity());
+            for key in remaining {
+                assert_eq!(hashmap.remove(&key), Some(key));
+            }
+            capacities.push(hashmap.capacity());
+            capacities
+        };
+
+        let never = trajectory(ShrinkPolicy::Never);
+        assert_eq!(never[1], never[0]);
+        assert_eq!(never[2], never[0]);
+
+        let on_insert_sampling = trajectory(ShrinkPolicy::OnInsertSampling);
+        assert_eq!(on_insert_sampling[1], on_insert_sampling[0]);
+        assert!(on_insert_sampling[2] < on_insert_sampling[0]);
+
+        let aggressive = trajectory(ShrinkPolicy::Aggressive { idle_ops: 64 });
+        assert!(aggressive[1] < aggressive[0]);
+        assert!(aggressive[2] <= aggressive[1]);
+    }
+
+    #[test]
+    fn unit_value() {
+        let num_threads = 8;
+        let num_keys = 4096;
+        let hashmap: Arc<HashMap<usize, ()>> = Arc::new(Default::default());
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let range = thread_id * num_keys..(thread_id + 1) * num_keys;
+                for key in range.clone() {
+                    assert!(hashmap_copied.insert(key, ()).is_ok());
+                    assert!(hashmap_copied.insert(key, ()).is_err());
+                }
+                for key in range.clone() {
+                    assert_eq!(hashmap_copied.read(&key, |k, _| *k), Some(key));
+                    if key % 2 == 0 {
+                        assert_eq!(hashmap_copied.remove(&key), Some(()));
+                    }
+                }
+                for key in range {
+                    assert_eq!(hashmap_copied.contains(&key), key % 2 == 1);
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(hashmap.len(), num_threads * num_keys / 2);
+
+        let hashmap = Arc::try_unwrap(hashmap).ok().unwrap();
+        assert_eq!(
+            hashmap.retain(|key, _| key % 4 == 1).1,
+            num_threads * num_keys / 4
+        );
+        assert_eq!(hashmap.keys().count(), num_threads * num_keys / 4);
+        assert!(hashmap.iter().all(|(key, _)| key % 4 == 1));
+        assert!(hashmap.transfer(&1, 0).is_ok());
+        assert!(hashmap.swap_values(&0, &5));
+        hashmap.upsert(2, ());
+        assert_eq!(hashmap.drain().filter(|(key, _)| key % 4 != 1).count(), 2);
+        assert_eq!(hashmap.len(), 0);
+
+        let std_hashmap: std::collections::HashMap<usize, ()> =
+            (0..num_keys).map(|key| (key, ())).collect();
+        let hashmap: HashMap<usize, (), RandomState> = HashMap::from(std_hashmap);
+        let mut keys: Vec<usize> = hashmap.into_iter().map(|(key, _)| key).collect();
+        keys.sort_unstable();
+        assert!(keys.into_iter().eq(0..num_keys));
+    }
+
+    #[test]
+    fn event_hook() {
+        let num_threads = 4;
+        let data_size = 1024;
+        let hashmap: Arc<HashMap<u64, u64>> = Arc::new(Default::default());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_cloned = events.clone();
+        hashmap.set_event_hook(move |event: Event<&u64, &u64>| {
+            let event = match event {
+                Event::Inserted(key, value) => Event::Inserted(*key, *value),
+                Event::Updated(key, value) => Event::Updated(*key, *value),
+                Event::Removed(key, value) => Event::Removed(*key, *value),
+            };
+            events_cloned.lock().unwrap().push(event);
+        });
+        let replay = |std_hashmap: &mut std::collections::HashMap<u64, u64>| {
+            for event in events.lock().unwrap().drain(..) {
+                match event {
+                    Event::Inserted(key, value) => {
+                        assert!(std_hashmap.insert(key, value).is_none())
+                    }
+                    Event::Updated(key, value) => assert!(std_hashmap.insert(key, value).is_some()),
+                    Event::Removed(key, value) => assert_eq!(std_hashmap.remove(&key), Some(value)),
+                }
+            }
+        };
+
+        // Each thread mutates a disjoint set of keys, so that events on the same key are
+        // delivered in order.
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let range = (thread_id * data_size) as u64..((thread_id + 1) * data_size) as u64;
+                for key in range {
+                    assert!(hashmap_copied.insert(key, 0).is_ok());
+                    if key % 2 == 0 {
+                        hashmap_copied.upsert(key, 1);
+                    }
+                    if key % 3 == 0 {
+                        *hashmap_copied.get(&key).unwrap().get().1 = 2;
+                    }
+                    if key % 5 == 0 {
+                        assert!(hashmap_copied.compare_exchange(&key, |_| true, 3).is_ok());
+                    }
+                    if key % 7 == 0 {
+                        assert!(hashmap_copied.remove(&key).is_some());
+                    } else if key % 11 == 0 {
+                        let result = hashmap_copied.remove_if(&key, |value| {
+                            *value += 10;
+                            false
+                        });
+                        assert!(result.is_none());
+                    }
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        let removed = hashmap.retain(|key, _| key % 13 != 0).1;
+        assert!(removed > 0);
+
+        let mut std_hashmap = std::collections::HashMap::new();
+        replay(&mut std_hashmap);
+        assert_eq!(std_hashmap.len(), hashmap.len());
+        for (key, value) in hashmap.entries() {
+            assert_eq!(std_hashmap.get(&key), Some(&value));
+        }
+
+        assert!(hashmap.clear() > 0);
+        replay(&mut std_hashmap);
+        assert!(std_hashmap.is_empty());
+
+        hashmap.clear_event_hook();
+        assert!(hashmap.insert(0, 0).is_ok());
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn try_reserve() {
+        let num_threads = 4;
+        let data_size = 4096;
+        let hashmap: Arc<HashMap<u64, u64>> = Arc::new(Default::default());
+        assert_eq!(
+            hashmap.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(hashmap.capacity(), 64);
+
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let range = (thread_id * data_size) as u64..((thread_id + 1) * data_size) as u64;
+                for key in range.clone() {
+                    if key % 1024 == 0 {
+                        assert!(hashmap_copied.try_reserve(data_size).is_ok());
+                    }
+                    assert!(hashmap_copied.insert(key, key).is_ok());
+                }
+                for key in range {
+                    assert_eq!(hashmap_copied.read(&key, |_, value| *value), Some(key));
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(hashmap.len(), num_threads * data_size);
+        assert!(hashmap.capacity() >= num_threads * data_size);
+    }
+
+    #[derive(Default)]
+    struct IdentityHasher {
+        hash: u64,
+    }
+
+    impl Hasher for IdentityHasher {
+        fn finish(&self) -> u64 {
+            self.hash
+        }
+        fn write(&mut self, _: &[u8]) {
+            unreachable!();
+        }
+        fn write_u64(&mut self, i: u64) {
+            self.hash = i;
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct IdentityBuildHasher;
+
+    impl BuildHasher for IdentityBuildHasher {
+        type Hasher = IdentityHasher;
+        fn build_hasher(&self) -> Self::Hasher {
+            IdentityHasher::default()
+        }
+    }
+
+    #[test]
+    fn overflow_cell() {
+        // All the keys have the same hash value, and therefore belong to the same Cell.
+        #[derive(Debug, Eq, PartialEq)]
+        struct Collider(u64);
+
+        impl Hash for Collider {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                state.write_u64(0);
+            }
+        }
+
+        let data_size = 1024;
+        let hashmap: HashMap<Collider, u64, IdentityBuildHasher> =
+            HashMap::new(64, IdentityBuildHasher);
+        let initial_capacity = hashmap.capacity();
+        for key in 0..data_size {
+            assert!(hashmap.insert(Collider(key), key).is_ok());
+        }
+        assert_eq!(hashmap.capacity(), initial_capacity);
+        assert_eq!(hashmap.len(), data_size as usize);
+        for key in 0..data_size {
+            assert_eq!(hashmap.read(&Collider(key), |_, v| *v), Some(key));
+        }
+        assert!(hashmap.read(&Collider(data_size), |_, v| *v).is_none());
+
+        for key in (0..data_size).filter(|key| key % 4 != 0) {
+            assert_eq!(hashmap.remove(&Collider(key)), Some(key));
+        }
+        assert_eq!(hashmap.capacity(), initial_capacity);
+        for key in 0..data_size {
+            assert_eq!(
+                hashmap.read(&Collider(key), |_, v| *v).is_some(),
+                key % 4 == 0
+            );
+        }
+
+        // Growing the array rehashes the overflowing entries.
+        assert!(hashmap.try_reserve(data_size as usize * 4).is_ok());
+        assert!(hashmap.capacity() > initial_capacity);
+        for key in data_size..data_size * 2 {
+            assert!(hashmap.insert(Collider(key), key).is_ok());
+        }
+        for key in 0..data_size * 2 {
+            assert_eq!(
+                hashmap.read(&Collider(key), |_, v| *v).is_some(),
+                key % 4 == 0 || key >= data_size
+            );
+        }
+        assert_eq!(hashmap.len(), (data_size / 4 + data_size) as usize);
+    }
+
+    #[test]
+    fn rehash_budget() {
+        // With a zero budget, operations do not take Cells in the old array, and therefore the
+        // driver has to relocate every single Cell, moving at most 8 key-value pairs per call.
+        let hashmap: HashMap<usize, usize> = HashMap::default().with_rehash_budget(0);
+        assert!(!hashmap.rehash(1));
+        let capacity = hashmap.capacity();
+        let mut num_keys = 0;
+        while hashmap.capacity() == capacity {
+            assert!(hashmap.insert(num_keys, num_keys).is_ok());
+            num_keys += 1;
+        }
+        for key in 0..num_keys * 2 {
+            drop(hashmap.upsert(key, key));
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+        let mut num_calls = 1;
+        while hashmap.rehash(1) {
+            num_calls += 1;
+        }
+        assert!(num_calls >= capacity / 32);
+        assert!(num_calls <= capacity / 32 + num_keys / 8);
+        assert!(!hashmap.rehash(1));
+        for key in 0..num_keys * 2 {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+
+        // A driver thread keeps relocating Cells while the writers are inserting keys.
+        let num_threads = 4;
+        let num_keys = 16384;
+        let hashmap: HashMap<usize, usize> = HashMap::default().with_rehash_budget(0);
+        let num_finished = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let (hashmap, num_finished) = (&hashmap, &num_finished);
+                s.spawn(move || {
+                    for key in thread_id * num_keys..(thread_id + 1) * num_keys {
+                        assert!(hashmap.insert(key, key).is_ok());
+                    }
+                    num_finished.fetch_add(1, Release);
+                });
+            }
+            s.spawn(|| {
+                while num_finished.load(Acquire) != num_threads {
+                    if !hashmap.rehash(4) {
+                        thread::yield_now();
+                    }
+                }
+                while hashmap.rehash(4) {}
+            });
+        });
+        assert!(!hashmap.rehash(1));
+        assert!(hashmap.capacity() >= num_threads * num_keys);
+        for key in 0..num_threads * num_keys {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+    }
+
+    #[test]
+    fn grow_pause() {
+        static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+        struct CountingAllocator;
+
+        unsafe impl ArrayAllocator for CountingAllocator {
+            fn allocate(layout: Layout) -> *mut u8 {
+                ALLOCATED.fetch_add(layout.size(), Relaxed);
+                unsafe { alloc(layout) }
+            }
+            unsafe fn deallocate(ptr: *mut u8, layout: Layout) {
+                ALLOCATED.fetch_sub(layout.size(), Relaxed);
+                dealloc(ptr, layout);
+            }
+        }
+
+        // Replacing the array does not touch the Cells of the new array, therefore neither the
+        // memory allocated by the replacement nor its pause depends on the new capacity. The
+        // array grows by at most 64x at a time, so the last step is measured in isolation.
+        let grow = |capacity: usize| {
+            let hashmap: HashMap<usize, usize, RandomState, CountingAllocator> =
+                HashMap::new_in(64, RandomState::new()).with_rehash_budget(0);
+            for key in 0..64 {
+                assert!(hashmap.insert(key, key).is_ok());
+            }
+            assert!(hashmap.try_reserve(capacity / 64).is_ok());
+            while hashmap.rehash(64) {}
+            let allocated = ALLOCATED.load(Relaxed);
+            let start_time = Instant::now();
+            assert!(hashmap.try_reserve(capacity).is_ok());
+            let pause = start_time.elapsed();
+            let allocated = ALLOCATED.load(Relaxed) - allocated;
+            assert!(hashmap.capacity() >= capacity);
+            for key in 0..64 {
+                assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+            }
+            (allocated, pause)
+        };
+        let (small_allocated, small_pause) = grow(1 << 18);
+        let (large_allocated, large_pause) = grow(1 << 30);
+        assert_eq!(small_allocated, large_allocated);
+        assert!(
+            large_pause < small_pause * 64 + Duration::from_millis(100),
+            "{:?} {:?}",
+            large_pause,
+            small_pause
+        );
+    }
+
+    #[test]
+    fn insert_during_migration() {
+        // Writers insert keys that belong to the Cells being relocated by a driver thread, half
+        // of which are already in the old array.
+        let num_threads = 3;
+        let num_keys = 8192;
+        for _ in 0..4 {
+            let hashmap: HashMap<usize, usize> = HashMap::default().with_rehash_budget(0);
+            for key in 0..num_keys {
+                assert!(hashmap.insert(key, key).is_ok());
+            }
+            while hashmap.rehash(1024) {}
+            assert!(hashmap.try_reserve(num_keys * 8).is_ok());
+            let num_inserted = AtomicUsize::new(0);
+            let barrier = Barrier::new(num_threads + 1);
+            thread::scope(|s| {
+                for thread_id in 0..num_threads {
+                    let (hashmap, num_inserted, barrier) = (&hashmap, &num_inserted, &barrier);
+                    s.spawn(move || {
+                        barrier.wait();
+                        // Every thread tries to insert every key, starting at different offsets.
+                        for i in 0..num_keys * 2 {
+                            let key = (i + thread_id * num_keys / 2) % (num_keys * 2);
+                            if hashmap.insert(key, key).is_ok() {
+                                assert!(key >= num_keys);
+                                num_inserted.fetch_add(1, Relaxed);
+                            }
+                        }
+                    });
+                }
+                s.spawn(|| {
+                    barrier.wait();
+                    while hashmap.rehash(1) {
+                        thread::yield_now();
+                    }
+                });
+            });
+            assert_eq!(num_inserted.load(Relaxed), num_keys);
+            while hashmap.rehash(1024) {}
+            assert_eq!(hashmap.len(), num_keys * 2);
+            let mut keys = BTreeSet::new();
+            for (k, v) in hashmap.iter() {
+                assert_eq!(k, v);
+                assert!(keys.insert(*k));
+            }
+            assert_eq!(keys.len(), num_keys * 2);
+        }
+    }
+
+    #[test]
+    fn max_resizing_factor() {
+        // Keys inserted while the old array is left behind overflow the Cells, and a confident
+        // size estimate lets the array catch up with fewer resizes.
+        let bulk_load = |max_resizing_factor: usize| {
+            let hashmap: HashMap<usize, usize> = HashMap::default()
+                .with_rehash_budget(0)
+                .with_max_resizing_factor(max_resizing_factor);
+            let num_keys = hashmap.capacity() * 500;
+            for key in 0..num_keys {
+                assert!(hashmap.insert(key, key).is_ok());
+            }
+            let mut key = num_keys;
+            loop {
+                while hashmap.rehash(1024) {}
+                if hashmap.capacity() * 7 / 8 >= hashmap.len() {
+                    break;
+                }
+                // An insertion into an overflowing Cell resizes the array.
+                assert!(hashmap.insert(key, key).is_ok());
+                key += 1;
+            }
+            for key in 0..key {
+                assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+            }
+            hashmap.array_generation()
+        };
+        let adaptive = bulk_load(1024);
+        let fixed = bulk_load(64);
+        assert!(adaptive < fixed);
+    }
+
+    #[test]
+    fn memory_footprint() {
+        let hashmap: HashMap<usize, usize> = HashMap::default().with_rehash_budget(0);
+        let empty = hashmap.memory_footprint();
+        assert_eq!(empty.old_array_bytes(), 0);
+        assert_eq!(empty.overflow_bytes(), 0);
+
+        // The old array is accounted for until all the Cells in it are relocated.
+        let capacity = hashmap.capacity();
+        let mut num_keys = 0;
+        while hashmap.capacity() == capacity {
+            assert!(hashmap.insert(num_keys, num_keys).is_ok());
+            num_keys += 1;
+        }
+        let resizing = hashmap.memory_footprint();
+        assert!(resizing.current_array_bytes() > empty.current_array_bytes());
+        assert!(resizing.old_array_bytes() > 0);
+        while hashmap.rehash(1) {}
+        let grown = hashmap.memory_footprint();
+        assert_eq!(grown.old_array_bytes(), 0);
+        assert!(grown.current_array_bytes() > empty.current_array_bytes());
+
+        // Removing all the keys shrinks the array.
+        for key in 0..num_keys {
+            assert_eq!(hashmap.remove(&key), Some(key));
+        }
+        while hashmap.rehash(1) {}
+        let shrunk = hashmap.memory_footprint();
+        assert!(shrunk.current_array_bytes() < grown.current_array_bytes());
+        assert_eq!(shrunk.old_array_bytes(), 0);
+
+        // Colliding keys are stored in linked DataArrays.
+        #[derive(Debug, Eq, PartialEq)]
+        struct Collider(u64);
+
+        impl Hash for Collider {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                state.write_u64(0);
+            }
+        }
+
+        let data_size = 1024;
+        let hashmap: HashMap<Collider, u64, IdentityBuildHasher> =
+            HashMap::new(0, IdentityBuildHasher);
+        for key in 0..data_size {
+            assert!(hashmap.insert(Collider(key), key).is_ok());
+        }
+        let overflowed = hashmap.memory_footprint();
+        assert!(overflowed.overflow_bytes() > overflowed.current_array_bytes());
+        for key in 0..data_size {
+            assert_eq!(hashmap.remove(&Collider(key)), Some(key));
+        }
+        assert_eq!(hashmap.memory_footprint().overflow_bytes(), 0);
+    }
+
+    #[test]
+    fn resize_back_to_back() {
+        // The second reservation relocates the old array left behind by the first one before
+        // growing the array.
+        let hashmap: HashMap<usize, usize> = HashMap::default().with_rehash_budget(0);
+        let ticket = hashmap.reserve(1024).unwrap();
+        assert!(hashmap.capacity() >= 1024);
+        for key in 0..64 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        assert!(hashmap.memory_footprint().old_array_bytes() > 0);
+        let another_ticket = hashmap.reserve(65536).unwrap();
+        assert!(hashmap.capacity() >= 65536);
+        for key in 0..64 {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+        drop(another_ticket);
+        drop(ticket);
+        assert!(hashmap.capacity() < 1024);
+        for key in 0..64 {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+
+        // Writers flood the HashMap while another thread keeps reserving and releasing capacity,
+        // which resizes the array before the previous resize completes.
+        let num_threads = 4;
+        let num_keys = 16384;
+        let hashmap: HashMap<usize, usize> = HashMap::default();
+        let num_finished = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let (hashmap, num_finished) = (&hashmap, &num_finished);
+                s.spawn(move || {
+                    let start = thread_id * num_keys;
+                    for key in start..start + num_keys {
+                        assert!(hashmap.insert(key, key).is_ok());
+                        assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+                        let inserted = start + (key - start) / 2;
+                        assert_eq!(hashmap.read(&inserted, |_, v| *v), Some(inserted));
+                    }
+                    num_finished.fetch_add(1, Release);
+                });
+            }
+            s.spawn(|| {
+                let mut reserve = 1024;
+                while num_finished.load(Acquire) != num_threads {
+                    let ticket = hashmap.reserve(reserve);
+                    assert!(ticket.is_some());
+                    reserve = if reserve >= 65536 { 1024 } else { reserve * 4 };
+                }
+            });
+        });
+        assert_eq!(hashmap.len(), num_threads * num_keys);
+        for key in 0..num_threads * num_keys {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+        while hashmap.rehash(1) {}
+        assert_eq!(hashmap.memory_footprint().old_array_bytes(), 0);
+    }
+
+    #[test]
+    fn cooperative_rehash() {
+        // Threads relocating the old array in parallel claim disjoint chunks of Cells, and the
+        // thread relocating the last chunk drops the old array.
+        let num_threads = 4;
+        let num_keys = 1 << 16;
+        let hashmap: HashMap<usize, usize> = HashMap::default().with_rehash_budget(0);
+        assert!(hashmap.try_reserve(num_keys).is_ok());
+        while hashmap.rehash(1024) {}
+        for key in 0..num_keys {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        assert!(hashmap.try_reserve(num_keys * 8).is_ok());
+        assert!(hashmap.memory_footprint().old_array_bytes() > 0);
+        let num_finished = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..num_threads {
+                s.spawn(|| {
+                    while hashmap.rehash(1) {}
+                    num_finished.fetch_add(1, Release);
+                });
+            }
+            s.spawn(|| {
+                while num_finished.load(Acquire) != num_threads {
+                    for key in (0..num_keys).step_by(97) {
+                        assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+                    }
+                }
+            });
+        });
+        assert!(!hashmap.rehash(1));
+        assert_eq!(hashmap.memory_footprint().old_array_bytes(), 0);
+        assert_eq!(hashmap.len(), num_keys);
+        for key in 0..num_keys {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+
+        // Threads that find the array being resized help relocate the Cells.
+        let hashmap: HashMap<usize, usize> = HashMap::default();
+        thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let hashmap = &hashmap;
+                s.spawn(move || {
+                    for key in thread_id * num_keys..(thread_id + 1) * num_keys {
+                        assert!(hashmap.insert(key, key).is_ok());
+                    }
+                });
+            }
+        });
+        assert_eq!(hashmap.len(), num_threads * num_keys);
+        for key in 0..num_threads * num_keys {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+    }
+
+    #[test]
+    fn resize_dampening() {
+        // The number of entries swings between 1/32 and 7/8 of the grown capacity, which makes
+        // the array grow and shrink back and forth unless resizing is dampened.
+        let sawtooth = |hashmap: &HashMap<usize, usize>| {
+            let mut generations = Vec::new();
+            for _ in 0..16 {
+                for key in 0..1024 {
+                    assert!(hashmap.insert(key, key).is_ok());
+                }
+                for key in 32..1024 {
+                    assert_eq!(hashmap.remove(&key), Some(key));
+                }
+                generations.push(hashmap.array_generation());
+                for key in 0..32 {
+                    assert_eq!(hashmap.remove(&key), Some(key));
+                }
+            }
+            generations
+        };
+
+        let oscillating = sawtooth(
+            &HashMap::default().with_shrink_policy(ShrinkPolicy::Aggressive { idle_ops: 16 }),
+        );
+        assert!(oscillating[15] - oscillating[0] >= 15);
+
+        let dampened = sawtooth(
+            &HashMap::default()
+                .with_shrink_policy(ShrinkPolicy::Aggressive { idle_ops: 16 })
+                .with_resize_dampening(Duration::from_secs(3600)),
+        );
+        assert_eq!(dampened[15], dampened[0]);
+    }
+
+    #[test]
+    fn small_capacity() {
+        let hashmap: HashMap<usize, usize, RandomState> = HashMap::new(4, RandomState::new());
+        assert_eq!(hashmap.capacity(), 32);
+
+        // An empty small HashMap allocates a single Cell.
+        let footprint = |capacity| {
+            HashMap::<usize, usize, RandomState>::new(capacity, RandomState::new())
+                .memory_footprint()
+                .current_array_bytes()
+        };
+        assert_eq!(footprint(0), footprint(4));
+        let cell_size = footprint(64) - footprint(4);
+        assert_eq!(footprint(128) - footprint(64), cell_size * 2);
+
+        // The array grows from a single Cell.
+        let num_keys = 4096;
+        for key in 0..num_keys {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        assert!(hashmap.capacity() >= num_keys);
+        assert!(hashmap.array_generation() > 0);
+        assert_eq!(hashmap.len(), num_keys);
+        for key in 0..num_keys {
+            assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+        }
+
+        // The array shrinks back to a single Cell.
+        for key in 0..num_keys {
+            assert_eq!(hashmap.remove(&key), Some(key));
+        }
+        while hashmap.rehash(1) {}
+        assert_eq!(hashmap.len(), 0);
+        assert_eq!(hashmap.capacity(), 32);
+    }
+
+    #[test]
+    fn array_allocator() {
+        static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+        static NUM_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+        struct CountingAllocator;
+
+        unsafe impl ArrayAllocator for CountingAllocator {
+            fn allocate(layout: Layout) -> *mut u8 {
+                ALLOCATED.fetch_add(layout.size(), Relaxed);
+                NUM_ALLOCATIONS.fetch_add(1, Relaxed);
+                unsafe { alloc(layout) }
+            }
+            unsafe fn deallocate(ptr: *mut u8, layout: Layout) {
+                ALLOCATED.fetch_sub(layout.size(), Relaxed);
+                dealloc(ptr, layout);
+            }
+        }
+
+        let hashmap: HashMap<usize, usize, RandomState, CountingAllocator> =
+            HashMap::new_in(4096, RandomState::new());
+        assert!(ALLOCATED.load(Relaxed) > 0);
+
+        // The Cells and DataArrays are allocated through the allocator, therefore the bytes that
+        // did not go through it stay the same.
+        let unaccounted = |hashmap: &HashMap<usize, usize, RandomState, CountingAllocator>| {
+            let footprint = hashmap.memory_footprint();
+            footprint.current_array_bytes() + footprint.overflow_bytes() - ALLOCATED.load(Relaxed)
+        };
+        let initial_unaccounted = unaccounted(&hashmap);
+        let num_allocations = NUM_ALLOCATIONS.load(Relaxed);
+        for key in 0..1024 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        assert_eq!(hashmap.array_generation(), 0);
+        assert!(NUM_ALLOCATIONS.load(Relaxed) > num_allocations);
+        assert_eq!(unaccounted(&hashmap), initial_unaccounted);
+
+        // Resizing and removing entries retire memory, which is returned to the allocator.
+        for key in 1024..65536 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        for key in 0..65536 {
+            if key % 4 != 0 {
+                assert_eq!(hashmap.remove(&key), Some(key));
+            }
+        }
+        drop(hashmap);
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while ALLOCATED.load(Relaxed) != 0 {
+            assert!(
+                Instant::now() < deadline,
+                "{} bytes leaked",
+                ALLOCATED.load(Relaxed)
+            );
+            scc::ebr::pin().flush();
+        }
+    }
+
+    #[test]
+    fn old_array_reclamation() {
+        static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+        struct CountingAllocator;
+
+        unsafe impl ArrayAllocator for CountingAllocator {
+            fn allocate(layout: Layout) -> *mut u8 {
+                ALLOCATED.fetch_add(layout.size(), Relaxed);
+                unsafe { alloc(layout) }
+            }
+            unsafe fn deallocate(ptr: *mut u8, layout: Layout) {
+                ALLOCATED.fetch_sub(layout.size(), Relaxed);
+                dealloc(ptr, layout);
+            }
+        }
+
+        let hashmap: HashMap<usize, usize, RandomState, CountingAllocator> =
+            HashMap::new_in(64, RandomState::new()).with_rehash_budget(0);
+        let live_bytes = |hashmap: &HashMap<usize, usize, RandomState, CountingAllocator>| {
+            let footprint = hashmap.memory_footprint();
+            footprint.current_array_bytes() + footprint.overflow_bytes()
+        };
+
+        // The CellArray and its segment table are not allocated through the allocator.
+        let unaccounted = live_bytes(&hashmap) - ALLOCATED.load(Relaxed);
+        for key in 0..1024 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        while hashmap.rehash(1) {}
+
+        // The array is resized, and the old array is kept until all its Cells are relocated.
+        hashmap.try_reserve(1 << 16).unwrap();
+        assert!(hashmap.has_old_array());
+        assert!(ALLOCATED.load(Relaxed) + unaccounted > live_bytes(&hashmap));
+        while hashmap.rehash(1) {}
+        assert!(!hashmap.has_old_array());
+        assert_eq!(hashmap.len(), 1024);
+
+        // Pinning the epoch is sufficient for the old array to be reclaimed.
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while ALLOCATED.load(Relaxed) + unaccounted != live_bytes(&hashmap) {
+            assert!(Instant::now() < deadline, "the old array is not reclaimed");
+            drop(scc::ebr::pin());
+        }
+    }
+
+    #[test]
+    fn read_optimistic() {
+        // Each value consists of identical words, and therefore a torn read is detectable.
+        let num_keys = 64;
+        let num_writers = 4;
+        let num_readers = 4;
+        let hashmap: HashMap<usize, [usize; 8]> = HashMap::default();
+        let writers_done = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for writer_id in 0..num_writers {
+                let hashmap = &hashmap;
+                let writers_done = &writers_done;
+                s.spawn(move || {
+                    for round in 0..65536 {
+                        let key = (writer_id + round) % num_keys;
+                        if round % 8 == 0 {
+                            hashmap.remove(&key);
+                        } else {
+                            drop(hashmap.upsert(key, [round; 8]));
+                        }
+                    }
+                    writers_done.fetch_add(1, Relaxed);
+                });
+            }
+            for _ in 0..num_readers {
+                let hashmap = &hashmap;
+                let writers_done = &writers_done;
+                s.spawn(move || {
+                    while writers_done.load(Relaxed) != num_writers {
+                        for key in 0..num_keys {
+                            hashmap.read_optimistic(&key, |k, v| {
+                                assert_eq!(*k, key);
+                                assert!(v.iter().all(|word| *word == v[0]), "{:?}", v);
+                            });
+                        }
+                    }
+                });
+            }
+        });
+        for key in 0..num_keys {
+            assert_eq!(
+                hashmap.read_optimistic(&key, |_, v| *v),
+                hashmap.read(&key, |_, v| *v)
+            );
+        }
+    }
+
+    #[test]
+    fn raw_hash() {
+        // Interned identifiers of which the hash value is the identifier itself.
+        #[derive(Debug, Eq, Hash, PartialEq)]
+        struct Id(u64);
+
+        let data_size = 65536;
+        let hashmap: HashMap<Id, u64, IdentityBuildHasher> = HashMap::new(0, IdentityBuildHasher);
+        let initial_capacity = hashmap.capacity();
+        for id in 0..data_size {
+            assert!(hashmap.insert(Id(id), id).is_ok());
+            if id % 1024 == 0 {
+                for id in 0..=id {
+                    assert_eq!(
+                        hashmap.read_with_hash(id, |key| key.0 == id, |_, value| *value),
+                        Some(id)
+                    );
+                }
+            }
+        }
+        assert!(hashmap.capacity() > initial_capacity);
+
+        for id in 0..data_size {
+            let accessor = hashmap.entry_with_hash(id, |key| key.0 == id);
+            *accessor.unwrap().get().1 += 1;
+            assert!(hashmap
+                .read_with_hash(id + data_size, |key| key.0 == id + data_size, |_, _| ())
+                .is_none());
+        }
+
+        // Shrinks the HashMap.
+        let capacity = hashmap.capacity();
+        for id in 0..(data_size - 16) {
+            assert_eq!(hashmap.remove(&Id(id)), Some(id + 1));
+        }
+        assert!(hashmap.capacity() < capacity);
+        for id in (data_size - 16)..data_size {
+            assert_eq!(
+                hashmap.read_with_hash(id, |key| key.0 == id, |_, value| *value),
+                Some(id + 1)
+            );
+            assert_eq!(
+                hashmap
+                    .entry_with_hash(id, |key| key.0 == id)
+                    .map(|accessor| accessor.erase()),
+                Some(Some(id + 1))
+            );
+        }
+        assert_eq!(hashmap.len(), 0);
+    }
+
+    #[derive(Default)]
+    struct SkewedHasher {
+        hash: u64,
+    }
+
+    impl Hasher for SkewedHasher {
+        fn finish(&self) -> u64 {
+            self.hash
+        }
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.hash = self.hash.wrapping_add(*byte as u64);
+            }
+        }
+        fn write_u64(&mut self, i: u64) {
+            // Most of the keys are mapped to four hash values.
+            self.hash = if i % 16 == 0 { i } else { i % 4 };
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct SkewedBuildHasher;
+
+    impl BuildHasher for SkewedBuildHasher {
+        type Hasher = SkewedHasher;
+        fn build_hasher(&self) -> Self::Hasher {
+            SkewedHasher::default()
+        }
+    }
+
+    #[test]
+    fn par_for_each_mut() {
+        let num_threads = 4;
+        let data_size = 16384;
+        let hashmap: Arc<HashMap<u64, u64, SkewedBuildHasher>> =
+            Arc::new(HashMap::new(data_size as usize, SkewedBuildHasher));
+        for key in 0..data_size {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let stopped_copied = stopped.clone();
+            thread_handles.push(thread::spawn(move || {
+                // Keeps inserting and removing keys that are not in the original population, so
+                // that the HashMap keeps trying to grow and shrink.
+                let first_key = data_size * (thread_id as u64 + 1);
+                while !stopped_copied.load(Relaxed) {
+                    for key in first_key..(first_key + data_size / 2) {
+                        assert!(hashmap_copied.insert(key, u64::MAX).is_ok());
+                    }
+                    for key in first_key..(first_key + data_size / 2) {
+                        assert_eq!(hashmap_copied.remove(&key), Some(u64::MAX));
+                    }
+                }
+            }));
+        }
+
+        for round in 1..=4 {
+            let num_visits: Vec<AtomicUsize> =
+                (0..data_size).map(|_| AtomicUsize::new(0)).collect();
+            hashmap.par_for_each_mut(|key, value| {
+                if *key < data_size {
+                    assert_eq!(*value, *key + round - 1);
+                    *value += 1;
+                    num_visits[*key as usize].fetch_add(1, Relaxed);
+                }
+            });
+            assert!(num_visits.iter().all(|n| n.load(Relaxed) == 1));
+        }
+
+        stopped.store(true, Release);
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        for key in 0..data_size {
+            assert_eq!(hashmap.read(&key, |_, value| *value), Some(key + 4));
+        }
+    }
+
+    struct Data<'a> {
+        data: u64,
+        checker: &'a AtomicUsize,
+    }
+
+    impl<'a> Data<'a> {
+        fn new(data: u64, checker: &'a AtomicUsize) -> Data<'a> {
+            checker.fetch_add(1, Relaxed);
+            Data {
+                data: data,
+                checker: checker,
+            }
+        }
+    }
+
+    impl<'a> Clone for Data<'a> {
+        fn clone(&self) -> Self {
+            Data::new(self.data, self.checker)
+        }
+    }
+
+    impl<'a> Drop for Data<'a> {
+        fn drop(&mut self) {
+            self.checker.fetch_sub(1, Relaxed);
+        }
+    }
+
+    impl<'a> Eq for Data<'a> {}
+
+    impl<'a> Hash for Data<'a> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.data.hash(state);
+        }
+    }
+
+    impl<'a> PartialEq for Data<'a> {
+        fn eq(&self, other: &Self) -> bool {
+            self.data == other.data
+        }
+    }
+
+    #[test]
+    fn remove_if() {
+        let checker = AtomicUsize::new(0);
+        let hashmap: HashMap<Data, Data> = Default::default();
+        for d in 0..1024 {
+            assert!(hashmap
+                .insert(Data::new(d, &checker), Data::new(d, &checker))
+                .is_ok());
+        }
+        assert_eq!(checker.load(Relaxed), 2048);
+
+        // Updates made by a declining predicate are kept.
+        for d in 0..1024 {
+            let result = hashmap.remove_if_result(&Data::new(d, &checker), |value| {
+                value.data += 1;
+                value.data % 2 == 0 && value.data <= 512
+            });
+            if d % 2 == 1 && d < 512 {
+                assert!(matches!(result, RemoveIfResult::Removed(value) if value.data == d + 1));
+            } else {
+                assert!(matches!(result, RemoveIfResult::Declined));
+            }
+        }
+        assert_eq!(hashmap.len(), 768);
+        assert_eq!(checker.load(Relaxed), 1536);
+
+        for d in 0..1024 {
+            let result = hashmap.remove_if(&Data::new(d, &checker), |value| {
+                assert_eq!(value.data, d + 1);
+                true
+            });
+            if d % 2 == 1 && d < 512 {
+                assert!(result.is_none());
+            } else {
+                assert_eq!(result.map(|value| value.data), Some(d + 1));
+            }
+            assert!(matches!(
+                hashmap.remove_if_result(&Data::new(d, &checker), |_| true),
+                RemoveIfResult::NotFound
+            ));
+        }
+        assert_eq!(hashmap.len(), 0);
+        assert_eq!(checker.load(Relaxed), 0);
+    }
+
+    #[test]
+    fn std_conversion() {
+        let data_size = 4096;
+        let checker = AtomicUsize::new(0);
+        let mut std_hashmap = std::collections::HashMap::new();
+        for d in 0..data_size {
+            std_hashmap.insert(Data::new(d, &checker), Data::new(d + 1, &checker));
+        }
+        assert_eq!(checker.load(Relaxed), (data_size * 2) as usize);
+
+        let hashmap: HashMap<Data, Data, RandomState> = HashMap::from(std_hashmap);
+        assert_eq!(checker.load(Relaxed), (data_size * 2) as usize);
+        assert_eq!(hashmap.len(), data_size as usize);
+        assert!(hashmap.capacity() >= data_size as usize);
+        for d in 0..data_size {
+            assert_eq!(
+                hashmap.read(&Data::new(d, &checker), |_, value| value.data),
+                Some(d + 1)
+            );
+        }
+        assert!(hashmap
+            .insert(Data::new(data_size, &checker), Data::new(0, &checker))
+            .is_ok());
+
+        let std_hashmap = hashmap.into_std();
+        assert_eq!(checker.load(Relaxed), ((data_size + 1) * 2) as usize);
+        assert_eq!(std_hashmap.len(), (data_size + 1) as usize);
+        for (key, value) in std_hashmap.iter() {
+            assert_eq!((key.data + 1) % (data_size + 1), value.data);
+        }
+        drop(std_hashmap);
+        assert_eq!(checker.load(Relaxed), 0);
+    }
+
+    #[test]
+    fn into_iter() {
+        let data_size = 4096;
+        let checker = AtomicUsize::new(0);
+        let hashmap: HashMap<Data, Data> = Default::default();
+        for d in 0..data_size {
+            assert!(hashmap
+                .insert(Data::new(d, &checker), Data::new(d + 1, &checker))
+                .is_ok());
+        }
+        assert_eq!(checker.load(Relaxed), (data_size * 2) as usize);
+
+        let mut keys = BTreeSet::new();
+        for (key, value) in hashmap.into_iter() {
+            assert_eq!(key.data + 1, value.data);
+            assert!(keys.insert(key.data));
+        }
+        assert_eq!(keys.len(), data_size as usize);
+        assert_eq!(checker.load(Relaxed), 0);
+
+        // The remainder of a partially consumed iterator is dropped along with it.
+        let hashmap: HashMap<Data, Data> = Default::default();
+        for d in 0..data_size {
+            assert!(hashmap
+                .insert(Data::new(d, &checker), Data::new(d + 1, &checker))
+                .is_ok());
+        }
+        let mut iter = hashmap.into_iter();
+        let taken: Vec<(Data, Data)> = iter.by_ref().take(data_size as usize / 2).collect();
+        assert_eq!(checker.load(Relaxed), (data_size * 2) as usize);
+        drop(iter);
+        assert_eq!(checker.load(Relaxed), taken.len() * 2);
+        drop(taken);
+        assert_eq!(checker.load(Relaxed), 0);
+    }
+
+    #[test]
+    fn drain() {
+        let data_size = 4096;
+        let checker = AtomicUsize::new(0);
+        let hashmap: HashMap<Data, Data> = Default::default();
+        for d in 0..data_size {
+            assert!(hashmap
+                .insert(Data::new(d, &checker), Data::new(d + 1, &checker))
+                .is_ok());
+        }
+
+        // The remainder of a partially consumed iterator stays in the HashMap.
+        let taken: Vec<(Data, Data)> = hashmap.drain().take(data_size as usize / 2).collect();
+        assert_eq!(taken.len(), data_size as usize / 2);
+        assert_eq!(hashmap.len(), data_size as usize / 2);
+        assert_eq!(checker.load(Relaxed), (data_size * 2) as usize);
+        for (key, value) in taken.iter() {
+            assert_eq!(key.data + 1, value.data);
+            assert!(!hashmap.contains(key));
+        }
+        drop(taken);
+        assert_eq!(checker.load(Relaxed), data_size as usize);
+
+        let mut drain = hashmap.drain();
+        let mut num_drained = 0;
+        for (key, value) in drain.by_ref() {
+            assert_eq!(key.data + 1, value.data);
+            num_drained += 1;
+        }
+        assert!(drain.next().is_none());
+        drop(drain);
+        assert_eq!(num_drained, data_size as usize / 2);
+        assert_eq!(hashmap.len(), 0);
+        assert_eq!(checker.load(Relaxed), 0);
+    }
+
+    #[test]
+    fn replace() {
+        let num_threads = 8;
+        let num_iterations = 4096;
+        static CHECKER: AtomicUsize = AtomicUsize::new(0);
+        let checker = &CHECKER;
+        let hashmap: Arc<HashMap<u64, Data<'static>>> = Arc::new(Default::default());
+        assert!(hashmap.insert(0, Data::new(0, checker)).is_ok());
+        assert!(matches!(
+            hashmap.replace(1, Data::new(1, checker)),
+            Err((1, value)) if value.data == 1
+        ));
+        assert_eq!(checker.load(Relaxed), 1);
+
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashmap_copied = hashmap.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for _ in 0..num_iterations {
+                    if thread_id % 2 == 0 {
+                        // The value is replaced with an even number.
+                        let previous = hashmap_copied
+                            .replace(0, Data::new(thread_id as u64 * 2, checker))
+                            .ok()
+                            .unwrap();
+                        drop(previous);
+                    } else {
+                        // The value is turned into an odd number, and then back into an even one.
+                        let mut accessor = hashmap_copied.get(&0).unwrap();
+                        let value = accessor.get().1;
+                        assert_eq!(value.data % 2, 0);
+                        value.data += 1;
+                        thread::yield_now();
+                        value.data += 1;
+                    }
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(checker.load(Relaxed), 1);
+        drop(hashmap);
+        assert_eq!(checker.load(Relaxed), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn insert(key in 0u64..16) {
+            let range = 1024;
+            let checker = AtomicUsize::new(0);
+            let hashmap: HashMap<Data, Data> = Default::default();
+            for d in key..(key + range) {
+                let result = hashmap.insert(Data::new(d, &checker), Data::new(d, &checker));
+                assert!(result.is_ok());
+                drop(result);
+                let mut result = hashmap.upsert(Data::new(d, &checker), Data::new(d + 1, &checker));
+                (*result.get().1) = Data::new(d + 2, &checker);
+            }
+
+            for d in (key + range)..(key + range + range) {
+                let result = hashmap.insert(Data::new(d, &checker), Data::new(d, &checker));
+                assert!(result.is_ok());
+                drop(result);
+                let mut result = hashmap.upsert(Data::new(d, &checker), Data::new(d + 1, &checker));
+                (*result.get().1) = Data::new(d + 2, &checker);
+            }
+
+            let result = hashmap.retain(|k, _| k.data < key + range);
+            assert_eq!(result, (range as usize, range as usize));
+
+            assert_eq!(hashmap.len() as u64, range);
+            let mut found_keys = 0;
+            for iter in hashmap.iter() {
+                assert!(iter.0.data < key + range);
+                assert!(iter.0.data >= key);
+                found_keys += 1;
+            }
+            assert_eq!(found_keys, range);
+            assert_eq!(checker.load(Relaxed) as u64, range * 2);
+            for d in key..(key + range) {
+                let result = hashmap.get(&Data::new(d, &checker));
+                result.unwrap().erase();
+            }
+            assert_eq!(checker.load(Relaxed), 0);
+
+            for d in key..(key + range) {
+                let result = hashmap.insert(Data::new(d, &checker), Data::new(d, &checker));
+                assert!(result.is_ok());
+                drop(result);
+                let mut result = hashmap.upsert(Data::new(d, &checker), Data::new(d + 1, &checker));
+               (*result.get().1) = Data::new(d + 2, &checker);
+            }
+            let result = hashmap.clear();
+            assert_eq!(result, range as usize);
+            assert_eq!(checker.load(Relaxed), 0);
+
+            for d in key..(key + range) {
+                let result = hashmap.insert(Data::new(d, &checker), Data::new(d, &checker));
+                assert!(result.is_ok());
+                drop(result);
+                let mut result = hashmap.upsert(Data::new(d, &checker), Data::new(d + 1, &checker));
+                (*result.get().1) = Data::new(d + 2, &checker);
+            }
+            assert_eq!(checker.load(Relaxed) as u64, range * 2);
+            drop(hashmap);
+            assert_eq!(checker.load(Relaxed), 0);
+        }
+    }
+
+    /// Asserts that the Cell for the key can be locked from another thread.
+    fn assert_cell_usable(hashmap: &Arc<HashMap<u64, u64, RandomState>>, key: u64) {
+        let hashmap = hashmap.clone();
+        thread::spawn(move || {
+            let existing = hashmap.read(&key, |_, v| *v);
+            drop(hashmap.upsert(key, u64::MAX));
+            assert_eq!(hashmap.remove(&key), Some(u64::MAX));
+            if let Some(value) = existing {
+                assert!(hashmap.insert(key, value).is_ok());
+            }
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn unwind_safety() {
+        let hashmap: Arc<HashMap<u64, u64, RandomState>> =
+            Arc::new(HashMap::new(64, RandomState::new()));
+        let panicking_hook = Arc::new(AtomicBool::new(false));
+        let num_removed = Arc::new(AtomicUsize::new(0));
+        let panicking_hook_clone = panicking_hook.clone();
+        let num_removed_clone = num_removed.clone();
+        hashmap.set_event_hook(move |event: Event<&u64, &u64>| {
+            if let Event::Removed(_, _) = event {
+                num_removed_clone.fetch_add(1, Relaxed);
+                assert!(!panicking_hook_clone.load(Relaxed));
+            }
+        });
+        let panics = |f: &dyn Fn()| catch_unwind(AssertUnwindSafe(f)).is_err();
+        for key in 0..64 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+
+        // Readers.
+        assert!(panics(&|| {
+            hashmap.read(&1, |_, _| panic!());
+        }));
+        assert_cell_usable(&hashmap, 1);
+        assert!(panics(&|| {
+            hashmap.any(|k, _| *k == 2 && panic!(), |_, _| ());
+        }));
+        assert_cell_usable(&hashmap, 2);
+
+        // Writers: nothing is applied if the closure panics before the HashMap is modified.
+        assert!(panics(&|| {
+            drop(hashmap.emplace(64, || panic!()));
+        }));
+        assert!(!hashmap.contains(&64));
+        assert_cell_usable(&hashmap, 64);
+        assert!(panics(&|| {
+            hashmap.remove_if(&3, |_| panic!());
+        }));
+        assert_eq!(hashmap.read(&3, |_, v| *v), Some(3));
+        assert_cell_usable(&hashmap, 3);
+        assert!(panics(&|| {
+            drop(hashmap.compare_exchange(&4, |_| panic!(), 0));
+        }));
+        assert_eq!(hashmap.read(&4, |_, v| *v), Some(4));
+        assert_cell_usable(&hashmap, 4);
+
+        // Updates made through an Accessor before the panic are kept.
+        assert!(panics(&|| {
+            let mut accessor = hashmap.get(&5).unwrap();
+            *accessor.get().1 = 55;
+            panic!();
+        }));
+        assert_eq!(hashmap.read(&5, |_, v| *v), Some(55));
+        assert_cell_usable(&hashmap, 5);
+        assert!(panics(&|| {
+            let _entry_guard = hashmap.lock_entry(65);
+            panic!();
+        }));
+        assert_cell_usable(&hashmap, 65);
+
+        // Key-value pairs removed before the panic are reported.
+        num_removed.store(0, Relaxed);
+        assert!(panics(&|| {
+            hashmap.retain(|k, _| {
+                assert_ne!(*k, 32);
+                *k % 2 == 0
+            });
+        }));
+        assert_eq!(num_removed.load(Relaxed), 64 - hashmap.len());
+        for key in 0..64 {
+            assert_cell_usable(&hashmap, key);
+        }
+        num_removed.store(0, Relaxed);
+        let num_sunk = AtomicUsize::new(0);
+        let len = hashmap.len();
+        assert!(panics(&|| {
+            hashmap.prune(
+                |_, _| false,
+                |k, _| {
+                    num_sunk.fetch_add(1, Relaxed);
+                    assert_ne!(k, 48);
+                },
+            );
+        }));
+        assert_eq!(num_removed.load(Relaxed), len - hashmap.len());
+        assert_eq!(num_sunk.load(Relaxed), len - hashmap.len());
+        for key in 0..64 {
+            assert_cell_usable(&hashmap, key);
+        }
+
+        // All the key-value pairs are cleared even if the event hook panics.
+        for key in 0..64 {
+            drop(hashmap.upsert(key, key));
+        }
+        num_removed.store(0, Relaxed);
+        panicking_hook.store(true, Relaxed);
+        assert!(panics(&|| {
+            hashmap.clear();
+        }));
+        panicking_hook.store(false, Relaxed);
+        assert_eq!(num_removed.load(Relaxed), 64);
+        assert_eq!(hashmap.len(), 0);
+        for key in 0..64 {
+            assert_cell_usable(&hashmap, key);
+        }
+    }
+
+    #[test]
+    fn deserialize_into() {
+        let data_size = 1 << 16;
+        let source: HashMap<u64, u64> = HashMap::default();
+        for key in 0..data_size {
+            assert!(source.insert(key, key * 2).is_ok());
+        }
+        let json = serde_json::to_string(&source).unwrap();
+        let deserialized: HashMap<u64, u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, source);
+
+        // The target is pre-sized, and has a custom build hasher and an event hook.
+        let target: HashMap<u64, u64, RandomState> =
+            HashMap::new(data_size as usize * 2, RandomState::new())
+                .with_shrink_policy(ShrinkPolicy::Never);
+        let num_inserted = Arc::new(AtomicUsize::new(0));
+        let num_updated = Arc::new(AtomicUsize::new(0));
+        let num_inserted_clone = num_inserted.clone();
+        let num_updated_clone = num_updated.clone();
+        target.set_event_hook(move |event: Event<&u64, &u64>| match event {
+            Event::Inserted(_, _) => {
+                num_inserted_clone.fetch_add(1, Relaxed);
+            }
+            Event::Updated(_, _) => {
+                num_updated_clone.fetch_add(1, Relaxed);
+            }
+            Event::Removed(_, _) => (),
+        });
+        for key in 0..16 {
+            assert!(target.insert(key, 0).is_ok());
+        }
+        assert!(target.insert(data_size, 0).is_ok());
+        let capacity = target.capacity();
+        let array_generation = target.array_generation();
+        num_inserted.store(0, Relaxed);
+
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        assert_eq!(target.deserialize_into(&mut deserializer).unwrap(), 16);
+        assert_eq!(num_inserted.load(Relaxed), data_size as usize - 16);
+        assert_eq!(num_updated.load(Relaxed), 16);
+        assert_eq!(target.len(), data_size as usize + 1);
+        assert_eq!(target.capacity(), capacity);
+        assert_eq!(target.array_generation(), array_generation);
+        for key in 0..data_size {
+            assert_eq!(target.read(&key, |_, v| *v), Some(key * 2));
+        }
+        assert_eq!(target.read(&data_size, |_, v| *v), Some(0));
+
+        // Existing values are kept, or deserialization fails at the first duplicate key.
+        num_updated.store(0, Relaxed);
+        for key in 0..16 {
+            drop(target.upsert(key, 0));
+        }
+        num_updated.store(0, Relaxed);
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let seed = target.deserialize_seed(DuplicateKeyPolicy::Keep);
+        assert_eq!(
+            seed.deserialize(&mut deserializer).unwrap(),
+            data_size as usize
+        );
+        assert_eq!(num_updated.load(Relaxed), 0);
+        assert_eq!(target.read(&0, |_, v| *v), Some(0));
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let seed = target.deserialize_seed(DuplicateKeyPolicy::Reject);
+        assert!(seed.deserialize(&mut deserializer).is_err());
+        assert_eq!(num_updated.load(Relaxed), 0);
+        assert_eq!(target.len(), data_size as usize + 1);
+    }
+
+    #[test]
+    fn deserialize_size_hint() {
+        // An iterator claiming far more entries than it yields.
+        struct Lying(std::ops::Range<u64>);
+        impl Iterator for Lying {
+            type Item = (u64, u64);
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0.next().map(|key| (key, key))
+            }
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (1 << 40, Some(1 << 40))
+            }
+        }
+
+        // The untrusted size hint does not make the HashMap reserve memory without bound.
+        let hashmap: HashMap<u64, u64> = HashMap::default();
+        let deserializer = MapDeserializer::<_, serde::de::value::Error>::new(Lying(0..16));
+        assert_eq!(hashmap.deserialize_into(deserializer).unwrap(), 0);
+        assert_eq!(hashmap.len(), 16);
+        assert!(hashmap.capacity() <= 1 << 17, "{}", hashmap.capacity());
+    }
+}
+
+#[cfg(test)]
+mod hashset_test {
+    use scc::HashSet;
+    use std::collections::BTreeSet;
+    use std::hash::{Hash, Hasher};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    fn membership() {
+        let num_threads = 8;
+        let num_keys = 4096;
+        let hashset: Arc<HashSet<usize>> = Arc::new(Default::default());
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashset_copied = hashset.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                // Threads share half of the keys with their neighbors.
+                let start = thread_id * num_keys / 2;
+                for key in start..start + num_keys {
+                    let _result = hashset_copied.insert(key);
+                    assert!(hashset_copied.contains(&key));
+                }
+                barrier_copied.wait();
+                // Each thread exclusively removes even keys in the first half of its range.
+                for key in start..start + num_keys / 2 {
+                    if key % 2 == 0 {
+                        assert!(hashset_copied.remove(&key));
+                        assert!(!hashset_copied.contains(&key));
+                    } else {
+                        assert_eq!(hashset_copied.insert(key), Err(key));
+                    }
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        let num_inserted = (num_threads + 1) * num_keys / 2;
+        let num_remaining = num_inserted - num_threads * num_keys / 4;
+        assert_eq!(hashset.len(), num_remaining);
+        let mut num_visited = 0;
+        hashset.for_each(|key| {
+            assert!(*key < num_inserted);
+            assert!(key % 2 == 1 || *key >= num_threads * num_keys / 2);
+            num_visited += 1;
+        });
+        assert_eq!(num_visited, num_remaining);
+        assert_eq!(hashset.iter().count(), num_remaining);
+        let num_odd = num_inserted / 2;
+        assert_eq!(
+            hashset.retain(|key| key % 2 == 1),
+            (num_odd, num_remaining - num_odd)
+        );
+        assert_eq!(hashset.clear(), num_odd);
+        assert!(hashset.is_empty());
+    }
+
+    struct Interned {
+        name: &'static str,
+        payload: usize,
+    }
+
+    impl PartialEq for Interned {
+        fn eq(&self, other: &Self) -> bool {
+            self.name == other.name
+        }
+    }
+
+    impl Eq for Interned {}
+
+    impl Hash for Interned {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.name.hash(state);
+        }
+    }
+
+    #[test]
+    fn replace_take() {
+        let hashset: HashSet<Interned> = Default::default();
+        let interned = |payload| Interned {
+            name: "one",
+            payload,
+        };
+        assert!(hashset.replace(interned(0)).is_none());
+        assert_eq!(hashset.replace(interned(1)).unwrap().payload, 0);
+        assert_eq!(hashset.replace(interned(2)).unwrap().payload, 1);
+        assert_eq!(hashset.len(), 1);
+        assert_eq!(hashset.take(&interned(3)).unwrap().payload, 2);
+        assert!(hashset.take(&interned(4)).is_none());
+        assert!(hashset.is_empty());
+
+        let num_keys = 4096;
+        let hashset: HashSet<usize> = Default::default();
+        for key in 0..num_keys {
+            assert!(hashset.replace(key).is_none());
+        }
+        for key in 0..num_keys {
+            assert_eq!(hashset.replace(key), Some(key));
+        }
+        assert_eq!(hashset.len(), num_keys);
+        for key in 0..num_keys {
+            assert_eq!(hashset.take(&key), Some(key));
+        }
+        assert!(hashset.is_empty());
+    }
+
+    #[test]
+    fn serde() {
+        let hashset: HashSet<String> = (0..1024).map(|key| key.to_string()).collect();
+        let json = serde_json::to_string(&hashset).unwrap();
+        let deserialized: HashSet<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.len(), hashset.len());
+        hashset.for_each(|key| assert!(deserialized.contains(key)));
+
+        let empty: HashSet<u64> = serde_json::from_str("[]").unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(serde_json::to_string(&empty).unwrap(), "[]");
+
+        let duplicates: HashSet<u64> = serde_json::from_str("[1, 2, 1]").unwrap();
+        assert_eq!(duplicates.len(), 2);
+        assert!(serde_json::from_str::<HashSet<u64>>("{}").is_err());
+    }
+
+    #[test]
+    fn from_iter() {
+        let num_keys = 1_000_000;
+        let hashset: HashSet<usize> = (0..num_keys).collect();
+        assert_eq!(hashset.len(), num_keys);
+        let capacity = hashset.capacity();
+        assert!(capacity >= num_keys && capacity <= num_keys * 4);
+
+        let mut hashset: HashSet<usize> = Default::default();
+        hashset.extend(0..num_keys / 2);
+        hashset.extend((0..num_keys).collect::<Vec<_>>().iter());
+        assert_eq!(hashset.len(), num_keys);
+    }
+
+    #[test]
+    fn any() {
+        let num_threads = 4;
+        let data_size = 4096;
+        let hashset: Arc<HashSet<u64>> = Arc::new(Default::default());
+        assert!(hashset.insert(u64::MAX).is_ok());
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashset_copied = hashset.clone();
+            let stopped_copied = stopped.clone();
+            thread_handles.push(thread::spawn(move || {
+                // Keeps growing and shrinking the HashSet.
+                let first_key = data_size * thread_id as u64;
+                while !stopped_copied.load(Relaxed) {
+                    for key in first_key..(first_key + data_size) {
+                        assert!(hashset_copied.insert(key).is_ok());
+                    }
+                    for key in first_key..(first_key + data_size) {
+                        assert!(hashset_copied.remove(&key));
+                    }
+                }
+            }));
+        }
+        for _ in 0..256 {
+            assert_eq!(
+                hashset.any(|key| *key == u64::MAX, |key| *key),
+                Some(u64::MAX)
+            );
+            assert!(hashset.any(|key| *key == u64::MAX - 1, |_| ()).is_none());
+            assert!(!hashset.is_empty());
+        }
+        stopped.store(true, Relaxed);
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn retain_estimated_len() {
+        let data_size = 16384;
+        let hashset: HashSet<u64> = HashSet::default().with_len_sampling(usize::MAX, true);
+        assert!(hashset.is_empty());
+        for key in 0..data_size {
+            assert!(hashset.insert(key).is_ok());
+        }
+        assert_eq!(hashset.estimated_len(), data_size as usize);
+        assert_eq!(
+            hashset.retain(|key| key % 4 == 0),
+            (data_size as usize / 4, data_size as usize * 3 / 4)
+        );
+        assert_eq!(hashset.estimated_len(), data_size as usize / 4);
+        assert!(hashset.any(|key| key % 4 != 0, |_| ()).is_none());
+        assert_eq!(hashset.retain(|_| false), (0, data_size as usize / 4));
+        assert!(hashset.is_empty());
+        assert_eq!(hashset.estimated_len(), 0);
+    }
+
+    #[test]
+    fn set_algebra() {
+        let num_keys = 65536;
+        // (range of the first set, range of the second set): overlapping, disjoint, subset.
+        let cases = [
+            (0..num_keys, num_keys / 2..num_keys * 3 / 2),
+            (0..num_keys, num_keys..num_keys * 2),
+            (num_keys / 4..num_keys / 2, 0..num_keys),
+        ];
+        for (first_range, second_range) in cases.iter() {
+            let first: HashSet<usize> = Default::default();
+            let second: HashSet<usize> = Default::default();
+            let target: HashSet<usize> = Default::default();
+            for key in first_range.clone() {
+                assert!(first.insert(key).is_ok());
+            }
+            for key in second_range.clone() {
+                assert!(second.insert(key).is_ok());
+            }
+            let first_keys: BTreeSet<usize> = first_range.clone().collect();
+            let second_keys: BTreeSet<usize> = second_range.clone().collect();
+
+            let expected: BTreeSet<usize> = first_keys.union(&second_keys).cloned().collect();
+            assert_eq!(first.union_into(&second, &target), expected.len());
+            assert_eq!(target.len(), expected.len());
+            target.for_each(|key| assert!(expected.contains(key)));
+            assert_eq!(target.union_into(&first, &target), 0);
+
+            let expected: BTreeSet<usize> =
+                first_keys.intersection(&second_keys).cloned().collect();
+            for (this, that) in [(&first, &second), (&second, &first)].iter() {
+                let mut result = BTreeSet::new();
+                this.intersection_for_each(that, |key| assert!(result.insert(*key)));
+                assert_eq!(result, expected);
+            }
+
+            for (this, that, this_keys, that_keys) in [
+                (&first, &second, &first_keys, &second_keys),
+                (&second, &first, &second_keys, &first_keys),
+            ]
+            .iter()
+            {
+                let expected: BTreeSet<usize> = this_keys.difference(that_keys).cloned().collect();
+                let mut result = BTreeSet::new();
+                this.difference_for_each(that, |key| assert!(result.insert(*key)));
+                assert_eq!(result, expected);
+            }
+
+            let mut num_self_intersection = 0;
+            first.intersection_for_each(&first, |key| {
+                assert!(first.contains(key));
+                num_self_intersection += 1;
+            });
+            assert_eq!(num_self_intersection, first_keys.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod hashcache_test {
+    use scc::HashCache;
+    use std::collections::hash_map::RandomState;
+    use std::future::Future;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+    use std::sync::{Arc, Barrier};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn capacity() {
+        let num_threads = 8;
+        let num_keys = 65536;
+        let hashcache: Arc<HashCache<usize, usize, RandomState>> =
+            Arc::new(HashCache::new(1024, RandomState::new()));
+        let capacity = hashcache.capacity();
+        assert_eq!(capacity, 1024);
+        let barrier = Arc::new(Barrier::new(num_threads + 1));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashcache_copied = hashcache.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let mut num_evicted = 0;
+                for key in (thread_id..num_keys).step_by(num_threads) {
+                    match hashcache_copied.put(key, key) {
+                        Ok(Some((evicted_key, evicted_value))) => {
+                            assert_eq!(evicted_key, evicted_value);
+                            num_evicted += 1;
+                        }
+                        Ok(None) => (),
+                        Err(_) => unreachable!(),
+                    }
+                    if key % 7 == 0 {
+                        // The key may have been evicted by another thread.
+                        if let Some(value) = hashcache_copied.get(&key, |_, value| *value) {
+                            assert_eq!(value, key);
+                        }
+                    }
+                }
+                num_evicted
+            }));
+        }
+        let hashcache_copied = hashcache.clone();
+        let stopped_copied = stopped.clone();
+        let checker = thread::spawn(move || {
+            // The number of entries never exceeds the capacity.
+            while !stopped_copied.load(Relaxed) {
+                assert!(hashcache_copied.len() <= capacity);
+            }
+        });
+        barrier.wait();
+        let mut num_evicted = 0;
+        for handle in thread_handles {
+            num_evicted += handle.join().unwrap();
+        }
+        stopped.store(true, Relaxed);
+        checker.join().unwrap();
+        assert_eq!(hashcache.capacity(), capacity);
+        assert_eq!(hashcache.len() + num_evicted, num_keys);
+        let len = hashcache.len();
+        assert!(len <= capacity);
+        assert_eq!(hashcache.clear(), len);
+        assert!(hashcache.is_empty());
+    }
+
+    #[test]
+    fn hot_keys() {
+        let hashcache: HashCache<usize, usize, RandomState> =
+            HashCache::new(1024, RandomState::new());
+        let num_hot_keys = 64;
+        for key in 0..num_hot_keys {
+            assert!(hashcache.put(key, key).is_ok());
+        }
+        for key in num_hot_keys..num_hot_keys * 256 {
+            assert!(hashcache.put(key, key).is_ok());
+            if key % 4 == 0 {
+                for hot_key in 0..num_hot_keys {
+                    assert_eq!(hashcache.get(&hot_key, |_, value| *value), Some(hot_key));
+                }
+            }
+        }
+        // Cold keys are evicted, whereas peek does not keep them alive.
+        let num_cold_keys = (num_hot_keys..num_hot_keys * 256)
+            .filter(|key| hashcache.peek(key, |_, _| ()).is_some())
+            .count();
+        assert!(num_cold_keys + num_hot_keys <= hashcache.capacity());
+        assert_eq!(hashcache.remove(&0), Some(0));
+        assert!(!hashcache.contains(&0));
+    }
+
+    #[test]
+    fn eviction_hook() {
+        let num_threads = 8;
+        let num_keys = 65536;
+        let hashcache: Arc<HashCache<usize, usize, RandomState>> =
+            Arc::new(HashCache::new(256, RandomState::new()));
+        let num_evicted = Arc::new(AtomicUsize::new(0));
+        let num_evicted_cloned = num_evicted.clone();
+        let hashcache_cloned = hashcache.clone();
+        hashcache.set_eviction_hook(move |key, value| {
+            assert_eq!(key, value);
+            // The Cell is not locked when the hook is invoked.
+            assert!(hashcache_cloned.peek(key, |_, _| ()).is_none());
+            num_evicted_cloned.fetch_add(1, Relaxed);
+        });
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let hashcache_copied = hashcache.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let (mut num_returned, mut num_removed) = (0, 0);
+                for key in (thread_id..num_keys).step_by(num_threads) {
+                    if let Some((evicted_key, _)) = hashcache_copied.put(key, key).unwrap() {
+                        assert_ne!(evicted_key, key);
+                        num_returned += 1;
+                    }
+                    if key % 5 == 0 && hashcache_copied.remove(&key).is_some() {
+                        num_removed += 1;
+                    }
+                    if key % 4096 == 0 {
+                        hashcache_copied.get(&key, |_, _| ());
+                    }
+                }
+                (num_returned, num_removed)
+            }));
+        }
+        let (mut num_returned, mut num_removed) = (0, 0);
+        for handle in thread_handles {
+            let (returned, removed) = handle.join().unwrap();
+            num_returned += returned;
+            num_removed += removed;
+        }
+        assert_eq!(num_evicted.load(Relaxed), num_returned);
+        let num_live = hashcache.len();
+        assert_eq!(num_keys, num_live + num_evicted.load(Relaxed) + num_removed);
+
+        // Key-value pairs removed by clear are reported as well.
+        assert_eq!(hashcache.clear(), num_live);
+        assert_eq!(num_keys, num_evicted.load(Relaxed) + num_removed);
+
+        hashcache.clear_eviction_hook();
+        assert!(hashcache.put(0, 0).is_ok());
+        assert_eq!(hashcache.clear(), 1);
+        assert_eq!(num_keys, num_evicted.load(Relaxed) + num_removed);
+    }
+
+    fn manual_time_source() -> (Arc<AtomicU64>, impl Fn() -> Instant + Send + Sync + 'static) {
+        let base = Instant::now();
+        let elapsed = Arc::new(AtomicU64::new(0));
+        let elapsed_cloned = elapsed.clone();
+        (elapsed, move || {
+            base + Duration::from_millis(elapsed_cloned.load(Relaxed))
+        })
+    }
+
+    /// Polls the futures on the current thread until all of them are complete, parking the thread
+    /// while all of them are pending.
+    fn block_on_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+        struct ThreadWaker(thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+        let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+        loop {
+            for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+                if output.is_none() {
+                    if let Poll::Ready(result) = future.as_mut().poll(&mut context) {
+                        output.replace(result);
+                    }
+                }
+            }
+            if outputs.iter().all(Option::is_some) {
+                return outputs.into_iter().map(Option::unwrap).collect();
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn ttl() {
+        let (elapsed, time_source) = manual_time_source();
+        let hashcache: HashCache<usize, usize, RandomState> =
+            HashCache::new(64, RandomState::new()).with_time_source(time_source);
+        let num_expired = Arc::new(AtomicUsize::new(0));
+        let num_expired_cloned = num_expired.clone();
+        hashcache.set_eviction_hook(move |_, _| {
+            num_expired_cloned.fetch_add(1, Relaxed);
+        });
+        let ttl = Duration::from_millis(100);
+
+        // The key-value pair expires exactly at the boundary.
+        assert_eq!(hashcache.put_with_ttl(1, 1, ttl), Ok(None));
+        assert!(hashcache.put(2, 2).is_ok());
+        elapsed.store(99, Relaxed);
+        assert_eq!(hashcache.get(&1, |_, value| *value), Some(1));
+        assert_eq!(hashcache.peek(&1, |_, value| *value), Some(1));
+        assert_eq!(hashcache.put_with_ttl(1, 10, ttl), Err((1, 10)));
+        elapsed.store(100, Relaxed);
+        assert!(hashcache.peek(&1, |_, _| ()).is_none());
+        assert_eq!(num_expired.load(Relaxed), 1);
+        assert!(hashcache.get(&1, |_, _| ()).is_none());
+        assert!(!hashcache.contains(&1));
+        assert_eq!(num_expired.load(Relaxed), 1);
+        assert_eq!(hashcache.len(), 1);
+
+        // Putting an expired key refreshes the key-value pair.
+        assert_eq!(hashcache.put_with_ttl(3, 3, ttl), Ok(None));
+        elapsed.store(200, Relaxed);
+        assert_eq!(hashcache.put_with_ttl(3, 30, ttl), Ok(None));
+        assert_eq!(num_expired.load(Relaxed), 2);
+        elapsed.store(299, Relaxed);
+        assert_eq!(hashcache.get(&3, |_, value| *value), Some(30));
+        elapsed.store(300, Relaxed);
+        assert!(hashcache.put(3, 300).is_ok());
+        assert_eq!(num_expired.load(Relaxed), 3);
+        elapsed.store(1 << 40, Relaxed);
+        assert_eq!(hashcache.get(&3, |_, value| *value), Some(300));
+        assert_eq!(hashcache.get(&2, |_, value| *value), Some(2));
+        assert_eq!(hashcache.remove(&3), Some(300));
+        assert_eq!(num_expired.load(Relaxed), 3);
+    }
+
+    #[test]
+    fn ttl_eviction_order() {
+        let (elapsed, time_source) = manual_time_source();
+        let hashcache: HashCache<usize, usize, RandomState> =
+            HashCache::new(64, RandomState::new()).with_time_source(time_source);
+        let capacity = hashcache.capacity();
+
+        // Odd keys expire, and even keys are read after they have been put.
+        for key in 0..capacity * 16 {
+            if key % 2 == 0 {
+                assert!(hashcache.put(key, key).is_ok());
+                hashcache.get(&key, |_, _| ());
+            } else {
+                assert!(hashcache
+                    .put_with_ttl(key, key, Duration::from_millis(10))
+                    .is_ok());
+            }
+        }
+        assert_eq!(hashcache.len(), capacity);
+        elapsed.store(10, Relaxed);
+
+        // Expired key-value pairs are evicted ahead of the least recently used ones.
+        for key in capacity * 16..capacity * 16 + 4 {
+            if let Some((evicted_key, _)) = hashcache.put(key, key).unwrap() {
+                assert_eq!(evicted_key % 2, 1);
+            }
+        }
+        assert_eq!(hashcache.len(), capacity);
+    }
+
+    #[test]
+    fn purge_expired() {
+        let (elapsed, time_source) = manual_time_source();
+        let hashcache: HashCache<usize, usize, RandomState> =
+            HashCache::new(1024, RandomState::new()).with_time_source(time_source);
+        let num_cells = hashcache.capacity() / 32;
+        let num_purged = Arc::new(AtomicUsize::new(0));
+        let num_purged_cloned = num_purged.clone();
+        hashcache.set_eviction_hook(move |key, _| {
+            assert_eq!(key % 3, 0);
+            num_purged_cloned.fetch_add(1, Relaxed);
+        });
+        let mut num_expiring = 0;
+        for key in 0..256 {
+            if key % 3 == 0 {
+                if hashcache
+                    .put_with_ttl(key, key, Duration::from_millis(1))
+                    .unwrap()
+                    .is_none()
+                {
+                    num_expiring += 1;
+                }
+            } else {
+                assert!(hashcache.put(key, key).unwrap().is_none());
+            }
+        }
+        assert_eq!(hashcache.purge_expired(num_cells), 0);
+        elapsed.store(1, Relaxed);
+
+        // The cursor advances, therefore the whole array is examined in chunks.
+        let mut num_removed = 0;
+        for _ in 0..num_cells / 4 {
+            num_removed += hashcache.purge_expired(4);
+        }
+        assert_eq!(num_removed, num_expiring);
+        assert_eq!(num_purged.load(Relaxed), num_expiring);
+        assert_eq!(hashcache.len(), 256 - num_expiring);
+        assert_eq!(hashcache.purge_expired(0), 0);
+    }
+
+    #[test]
+    fn set_capacity() {
+        let hashcache: HashCache<usize, usize, RandomState> =
+            HashCache::new(1024, RandomState::new());
+        let num_evicted = Arc::new(AtomicUsize::new(0));
+        let num_evicted_cloned = num_evicted.clone();
+        hashcache.set_eviction_hook(move |key, value| {
+            assert_eq!(key, value);
+            num_evicted_cloned.fetch_add(1, Relaxed);
+        });
+        let mut num_inserted = 0;
+        for key in 0..4096 {
+            assert!(hashcache.put(key, key).is_ok());
+            num_inserted += 1;
+        }
+        assert_eq!(hashcache.capacity_bound(), hashcache.capacity());
+        assert_eq!(hashcache.estimated_len(), hashcache.len());
+        let num_entries = hashcache.len();
+        assert!(num_entries > 256);
+
+        // Shrinking converges below the new bound within a bounded number of puts.
+        assert!(hashcache.set_capacity(256).is_ok());
+        assert_eq!(hashcache.capacity(), 1024);
+        for key in 4096..4096 + num_entries {
+            assert!(hashcache.put(key, key).is_ok());
+            num_inserted += 1;
+        }
+        assert!(hashcache.len() <= 256);
+        assert_eq!(hashcache.estimated_len(), hashcache.len());
+        assert_eq!(num_inserted, hashcache.len() + num_evicted.load(Relaxed));
+        for key in 4096 + num_entries..8192 {
+            assert!(hashcache.put(key, key).is_ok());
+            num_inserted += 1;
+            assert!(hashcache.estimated_len() <= 256);
+        }
+
+        // Growing raises the bound, and resizes the array.
+        assert!(hashcache.set_capacity(8192).is_ok());
+        assert!(hashcache.capacity() >= 8192);
+        assert_eq!(hashcache.capacity_bound(), 8192);
+        for key in 8192..12288 {
+            assert!(hashcache.put(key, key).is_ok());
+            num_inserted += 1;
+        }
+        assert!(hashcache.len() > 1024);
+        assert_eq!(hashcache.estimated_len(), hashcache.len());
+        assert_eq!(num_inserted, hashcache.len() + num_evicted.load(Relaxed));
+    }
+
+    #[test]
+    fn get_or_put_with() {
+        let num_threads = 100;
+        let hashcache: Arc<HashCache<usize, usize, RandomState>> =
+            Arc::new(HashCache::new(1024, RandomState::new()));
+        let num_constructed = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for _ in 0..num_threads {
+            let hashcache_copied = hashcache.clone();
+            let num_constructed_copied = num_constructed.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                hashcache_copied.get_or_put_with(
+                    7,
+                    || {
+                        num_constructed_copied.fetch_add(1, Relaxed);
+                        thread::sleep(Duration::from_millis(10));
+                        77
+                    },
+                    |value| *value,
+                )
+            }));
+        }
+        let mut num_missed = 0;
+        for handle in thread_handles {
+            let (value, missed) = handle.join().unwrap();
+            assert_eq!(value, 77);
+            if missed {
+                num_missed += 1;
+            }
+        }
+        assert_eq!(num_constructed.load(Relaxed), 1);
+        assert_eq!(num_missed, 1);
+        assert_eq!(hashcache.len(), 1);
+        assert_eq!(hashcache.estimated_len(), 1);
+
+        // An expired key-value pair is constructed again.
+        let (elapsed, time_source) = manual_time_source();
+        let hashcache: HashCache<usize, usize, RandomState> =
+            HashCache::new(64, RandomState::new()).with_time_source(time_source);
+        let num_evicted = Arc::new(AtomicUsize::new(0));
+        let num_evicted_cloned = num_evicted.clone();
+        hashcache.set_eviction_hook(move |_, _| {
+            num_evicted_cloned.fetch_add(1, Relaxed);
+        });
+        assert!(hashcache
+            .put_with_ttl(1, 1, Duration::from_millis(10))
+            .is_ok());
+        assert_eq!(
+            hashcache.get_or_put_with(1, || 2, |value| *value),
+            (1, false)
+        );
+        elapsed.store(10, Relaxed);
+        assert_eq!(
+            hashcache.get_or_put_with(1, || 3, |value| *value),
+            (3, true)
+        );
+        assert_eq!(num_evicted.load(Relaxed), 1);
+        assert_eq!(hashcache.estimated_len(), 1);
+
+        // Full Cells evict key-value pairs to make room.
+        let mut num_inserted = 2;
+        for key in 2..4096 {
+            let (value, missed) = hashcache.get_or_put_with(key, || key, |value| *value);
+            assert_eq!(value, key);
+            assert!(missed);
+            num_inserted += 1;
+        }
+        assert!(hashcache.len() <= hashcache.capacity());
+        assert_eq!(hashcache.estimated_len(), hashcache.len());
+        assert_eq!(num_inserted, hashcache.len() + num_evicted.load(Relaxed));
+    }
+
+    #[test]
+    fn get_or_put_with_async() {
+        let num_threads = 4;
+        let num_tasks = 100;
+        let hashcache: HashCache<usize, usize, RandomState> =
+            HashCache::new(1024, RandomState::new());
+        let num_constructed = AtomicUsize::new(0);
+        let barrier = Barrier::new(num_threads);
+        let results: Vec<(usize, bool)> = thread::scope(|s| {
+            let thread_handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    // The futures are sent to the thread that runs them.
+                    let futures: Vec<_> = (0..num_tasks / num_threads)
+                        .map(|_| {
+                            hashcache.get_or_put_with_async(
+                                7,
+                                || {
+                                    num_constructed.fetch_add(1, Relaxed);
+                                    thread::sleep(Duration::from_millis(10));
+                                    77
+                                },
+                                |value| *value,
+                            )
+                        })
+                        .collect();
+                    let barrier = &barrier;
+                    s.spawn(move || {
+                        barrier.wait();
+                        block_on_all(futures)
+                    })
+                })
+                .collect();
+            thread_handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        assert_eq!(results.len(), num_tasks);
+        assert!(results.iter().all(|(value, _)| *value == 77));
+        assert_eq!(results.iter().filter(|(_, missed)| *missed).count(), 1);
+        assert_eq!(num_constructed.load(Relaxed), 1);
+        assert_eq!(hashcache.len(), 1);
+        assert_eq!(hashcache.estimated_len(), 1);
+    }
+
+    #[test]
+    fn unwind_safety() {
+        let hashcache: Arc<HashCache<usize, usize, RandomState>> =
+            Arc::new(HashCache::new(64, RandomState::new()));
+        let panicking_hook = Arc::new(AtomicBool::new(false));
+        let num_evicted = Arc::new(AtomicUsize::new(0));
+        let panicking_hook_clone = panicking_hook.clone();
+        let num_evicted_clone = num_evicted.clone();
+        hashcache.set_eviction_hook(move |_, _| {
+            num_evicted_clone.fetch_add(1, Relaxed);
+            assert!(!panicking_hook_clone.load(Relaxed));
+        });
+        let panics = |f: &dyn Fn()| catch_unwind(AssertUnwindSafe(f)).is_err();
+        let assert_cell_usable = |key: usize| {
+            let hashcache = hashcache.clone();
+            thread::spawn(move || {
+                let existing = hashcache.peek(&key, |_, v| *v);
+                hashcache.remove(&key);
+                assert!(hashcache.put(key, key).is_ok());
+                assert_eq!(hashcache.get(&key, |_, v| *v), Some(key));
+                assert_eq!(hashcache.remove(&key), Some(key));
+                if let Some(value) = existing {
+                    assert!(hashcache.put(key, value).is_ok());
+                }
+            })
+            .join()
+            .unwrap();
+        };
+        for key in 0..16 {
+            assert!(hashcache.put(key, key).is_ok());
+        }
+
+        // Readers.
+        assert!(panics(&|| {
+            hashcache.get(&1, |_, _| panic!());
+        }));
+        assert_cell_usable(1);
+        assert!(panics(&|| {
+            hashcache.peek(&2, |_, _| panic!());
+        }));
+        assert_cell_usable(2);
+
+        // Nothing is inserted if `init` panics, and the inserted key-value pair is kept if
+        // `reader` panics.
+        assert!(panics(&|| {
+            hashcache.get_or_put_with(100, || panic!(), |_| ());
+        }));
+        assert!(!hashcache.contains(&100));
+        assert_eq!(hashcache.estimated_len(), hashcache.len());
+        assert_cell_usable(100);
+        assert!(panics(&|| {
+            hashcache.get_or_put_with(101, || 101, |_| panic!());
+        }));
+        assert_eq!(hashcache.peek(&101, |_, v| *v), Some(101));
+        assert_eq!(hashcache.estimated_len(), hashcache.len());
+        assert_cell_usable(101);
+
+        // Evicted key-value pairs are accounted for even if the eviction hook panics.
+        panicking_hook.store(true, Relaxed);
+        let mut num_inserted = hashcache.len();
+        num_evicted.store(0, Relaxed);
+        for key in 1024..4096 {
+            drop(catch_unwind(AssertUnwindSafe(|| {
+                assert!(hashcache.put(key, key).is_ok());
+            })));
+            num_inserted += 1;
+        }
+        assert!(num_evicted.load(Relaxed) > 0);
+        assert_eq!(num_inserted, hashcache.len() + num_evicted.load(Relaxed));
+        assert_eq!(hashcache.estimated_len(), hashcache.len());
+        panicking_hook.store(false, Relaxed);
+        for key in 1024..1088 {
+            assert_cell_usable(key);
+        }
+        panicking_hook.store(true, Relaxed);
+        for key in 4096..4160 {
+            drop(catch_unwind(AssertUnwindSafe(|| {
+                let _result = hashcache.put_with_ttl(key, key, Duration::ZERO);
+            })));
+        }
+        assert!(panics(&|| {
+            hashcache.purge_expired(hashcache.capacity());
+        }));
+        assert_eq!(hashcache.estimated_len(), hashcache.len());
+        assert!(panics(&|| {
+            hashcache.clear();
+        }));
+        assert_eq!(hashcache.len(), 0);
+        assert_eq!(hashcache.estimated_len(), 0);
+        panicking_hook.store(false, Relaxed);
+        for key in 0..64 {
+            assert_cell_usable(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod hashindex_test {
+    use proptest::strategy::{Strategy, ValueTree};
+    use proptest::test_runner::TestRunner;
+    use rayon::prelude::*;
+    use scc::ebr::Domain;
+    use scc::{ArrayAllocator, DuplicateKeyPolicy, HashIndex, TryReserveError};
+    use serde::de::DeserializeSeed;
+    use std::alloc::{alloc, dealloc, Layout};
+    use std::collections::hash_map::RandomState;
+    use std::collections::BTreeSet;
+    use std::hash::{Hash, Hasher};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn array_allocator() {
+        static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+        struct CountingAllocator;
+
+        unsafe impl ArrayAllocator for CountingAllocator {
+            fn allocate(layout: Layout) -> *mut u8 {
+                ALLOCATED.fetch_add(layout.size(), Relaxed);
+                unsafe { alloc(layout) }
+            }
+            unsafe fn deallocate(ptr: *mut u8, layout: Layout) {
+                ALLOCATED.fetch_sub(layout.size(), Relaxed);
+                dealloc(ptr, layout);
+            }
+        }
+
+        let hashindex: HashIndex<usize, usize, RandomState, 32, CountingAllocator> =
+            HashIndex::new_in(64, RandomState::new());
+        assert!(hashindex.insert(usize::MAX, 0).is_ok());
+        let initial_allocated = ALLOCATED.load(Relaxed);
+        assert!(initial_allocated > 0);
+        for key in 0..16384 {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        assert!(ALLOCATED.load(Relaxed) > initial_allocated);
+        for key in 0..16384 {
+            if key % 2 == 0 {
+                assert!(hashindex.remove(&key));
+            }
+        }
+        drop(hashindex);
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while ALLOCATED.load(Relaxed) != 0 {
+            assert!(
+                Instant::now() < deadline,
+                "{} bytes leaked",
+                ALLOCATED.load(Relaxed)
+            );
+            scc::ebr::pin().flush();
+        }
+    }
+
+    #[test]
+    fn lazy_array() {
+        static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+        struct CountingAllocator;
+
+        unsafe impl ArrayAllocator for CountingAllocator {
+            fn allocate(layout: Layout) -> *mut u8 {
+                ALLOCATED.fetch_add(layout.size(), Relaxed);
+                unsafe { alloc(layout) }
+            }
+            unsafe fn deallocate(ptr: *mut u8, layout: Layout) {
+                ALLOCATED.fetch_sub(layout.size(), Relaxed);
+                dealloc(ptr, layout);
+            }
+        }
+
+        // The resizing flag occupies a dedicated cache line.
+        assert!(std::mem::size_of::<HashIndex<u64, u64>>() <= 256);
+
+        let hashindex: HashIndex<u64, u64, RandomState, 32, CountingAllocator> =
+            HashIndex::new_in(64, RandomState::new());
+        assert_eq!(ALLOCATED.load(Relaxed), 0);
+        assert_eq!(hashindex.capacity(), 64);
+        assert_eq!(hashindex.len(), 0);
+        assert_eq!(hashindex.memory_footprint().current_array_bytes(), 0);
+        assert_eq!(hashindex.array_generation(), 0);
+        assert!(!hashindex.has_old_array());
+        assert_eq!(hashindex.cell(0, 0).map(Iterator::count), Some(0));
+        assert!(hashindex.read(&1, |_, v| *v).is_none());
+        assert!(!hashindex.remove(&1));
+        assert!(!hashindex.rehash(1));
+        assert_eq!(hashindex.iter().count(), 0);
+        assert_eq!(hashindex.clear(), 0);
+        assert_eq!(ALLOCATED.load(Relaxed), 0);
+        drop(hashindex);
+        assert_eq!(ALLOCATED.load(Relaxed), 0);
+
+        for _ in 0..64 {
+            let num_threads = 4;
+            let hashindex: Arc<HashIndex<usize, usize>> = Arc::new(HashIndex::default());
+            let barrier = Arc::new(Barrier::new(num_threads));
+            let mut thread_handles = Vec::with_capacity(num_threads);
+            for thread_id in 0..num_threads {
+                let hashindex_copied = hashindex.clone();
+                let barrier_copied = barrier.clone();
+                thread_handles.push(thread::spawn(move || {
+                    barrier_copied.wait();
+                    for key in thread_id * 16..(thread_id + 1) * 16 {
+                        assert!(hashindex_copied.insert(key, key).is_ok());
+                    }
+                }));
+            }
+            for handle in thread_handles {
+                handle.join().unwrap();
+            }
+            assert_eq!(hashindex.len(), num_threads * 16);
+            for key in 0..num_threads * 16 {
+                assert_eq!(hashindex.read(&key, |_, v| *v), Some(key));
+            }
+        }
+    }
+
+    #[test]
+    fn overflow_cell() {
+        // All the keys have the same hash value, and therefore belong to the same Cell.
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        struct Collider(u64);
+
+        impl Hash for Collider {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                state.write_u64(0);
+            }
+        }
+
+        let data_size = 1024;
+        let hashindex: HashIndex<Collider, u64> = Default::default();
+        let initial_capacity = hashindex.capacity();
+        for key in 0..data_size {
+            assert!(hashindex.insert(Collider(key), key).is_ok());
+        }
+        assert_eq!(hashindex.capacity(), initial_capacity);
+        for key in 0..data_size {
+            assert_eq!(hashindex.read(&Collider(key), |_, v| *v), Some(key));
+        }
+        for key in (0..data_size).filter(|key| key % 2 == 0) {
+            assert!(hashindex.remove(&Collider(key)));
+        }
+        for key in 0..data_size {
+            assert_eq!(
+                hashindex.read(&Collider(key), |_, v| *v).is_some(),
+                key % 2 == 1
+            );
+        }
+        assert_eq!(hashindex.capacity(), initial_capacity);
+        assert_eq!(hashindex.len(), data_size as usize / 2);
+    }
+
+    #[test]
+    fn string_key() {
+        let hashindex1: HashIndex<String, u32> = Default::default();
+        let hashindex2: HashIndex<u32, String> = Default::default();
+        let mut checker1 = BTreeSet::new();
+        let mut checker2 = BTreeSet::new();
+        let mut runner = TestRunner::default();
+        let test_size = 4096;
+        for i in 0..test_size {
+            let prop_str = "[a-z]{1,16}".new_tree(&mut runner).unwrap();
+            let str_val = prop_str.current();
+            if hashindex1.insert(str_val.clone(), i).is_ok() {
+                checker1.insert((str_val.clone(), i));
+            }
+            let str_borrowed = str_val.as_str();
+            assert!(hashindex1.read(str_borrowed, |_, _| ()).is_some());
+
+            if hashindex2.insert(i, str_val.clone()).is_ok() {
+                checker2.insert((i, str_val.clone()));
+            }
+        }
+        assert_eq!(hashindex1.len(), checker1.len());
+        assert_eq!(hashindex2.len(), checker2.len());
+        for iter in checker1 {
+            assert!(hashindex1.remove(iter.0.as_str()));
+        }
+        for iter in checker2 {
+            assert!(hashindex2.remove(&iter.0));
+        }
+        assert_eq!(hashindex1.len(), 0);
+        assert_eq!(hashindex2.len(), 0);
+    }
+
+    #[test]
+    fn try_reserve() {
+        let data_size = 4096;
+        let hashindex: HashIndex<u64, u64> = Default::default();
+        assert_eq!(
+            hashindex.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        for key in 0..data_size {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        assert!(hashindex.try_reserve(data_size as usize * 4).is_ok());
+        assert!(hashindex.capacity() >= data_size as usize * 5);
+        for key in 0..data_size {
+            assert_eq!(hashindex.read(&key, |_, value| *value), Some(key));
+        }
+    }
+
+    #[test]
+    fn visitor() {
+        let data_size = 4096;
+        for _ in 0..64 {
+            let hashindex: Arc<HashIndex<u64, u64>> = Arc::new(Default::default());
+            let hashindex_copied = hashindex.clone();
+            let barrier = Arc::new(Barrier::new(2));
+            let barrier_copied = barrier.clone();
+            let inserted = Arc::new(AtomicU64::new(0));
+            let inserted_copied = inserted.clone();
+            let removed = Arc::new(AtomicU64::new(data_size));
+            let removed_copied = removed.clone();
+            let thread_handle = thread::spawn(move || {
+                // test insert
+                for _ in 0..2 {
+                    barrier_copied.wait();
+                    let mut scanned = 0;
+                    let mut checker = BTreeSet::new();
+                    let max = inserted_copied.load(Acquire);
+                    for iter in hashindex_copied.iter() {
+                        scanned += 1;
+                        checker.insert(*iter.0);
+                    }
+                    println!("scanned: {}, max: {}", scanned, max);
+                    for key in 0..max {
+                        assert!(checker.contains(&key));
+                    }
+                }
+                // test remove
+                for _ in 0..2 {
+                    barrier_copied.wait();
+                    let mut scanned = 0;
+                    let max = removed_copied.load(Acquire);
+                    for iter in hashindex_copied.iter() {
+                        scanned += 1;
+                        assert!(*iter.0 < max);
+                    }
+                    println!("scanned: {}, max: {}", scanned, max);
+                }
+            });
+            // insert
+            barrier.wait();
+            for i in 0..data_size {
+                if i == data_size / 2 {
+                    barrier.wait();
+                }
+                assert!(hashindex.insert(i, i).is_ok());
+                inserted.store(i, Release);
+            }
+            // remove
+            barrier.wait();
+            for i in (0..data_size).rev() {
+                if i == data_size / 2 {
+                    barrier.wait();
+                }
+                assert!(hashindex.remove(&i));
+                removed.store(i, Release);
+            }
+            thread_handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn cell_width() {
+        fn run<const CELL: usize>() {
+            let num_threads = 4;
+            let data_size = 16384;
+            let hashindex: HashIndex<u64, u64, RandomState, CELL> =
+                HashIndex::new(64, RandomState::new());
+            let initial_capacity = hashindex.capacity();
+            thread::scope(|s| {
+                for thread_id in 0..num_threads {
+                    let hashindex = &hashindex;
+                    s.spawn(move || {
+                        let range = thread_id * data_size..(thread_id + 1) * data_size;
+                        for key in range.clone() {
+                            assert!(hashindex.insert(key, key).is_ok());
+                        }
+                        for key in range.clone() {
+                            assert_eq!(hashindex.read(&key, |_, v| *v), Some(key));
+                            assert!(hashindex.insert(key, key).is_err());
+                        }
+                        for key in range.clone().filter(|key| key % 2 == 0) {
+                            assert!(hashindex.remove(&key));
+                        }
+                        for key in range {
+                            assert_eq!(hashindex.read(&key, |_, v| *v).is_some(), key % 2 == 1);
+                        }
+                    });
+                }
+            });
+            let num_entries = (num_threads * data_size / 2) as usize;
+            assert_eq!(hashindex.len(), num_entries);
+            assert_eq!(hashindex.iter().count(), num_entries);
+            assert!(hashindex.capacity() > initial_capacity);
+            for key in (0..num_threads * data_size).filter(|key| key % 2 == 1) {
+                assert!(hashindex.remove(&key));
+            }
+            assert_eq!(hashindex.len(), 0);
+        }
+        run::<8>();
+        run::<32>();
+        run::<64>();
+    }
+
+    #[test]
+    fn rehash_budget() {
+        let hashindex: HashIndex<usize, usize, RandomState> =
+            HashIndex::new(0, RandomState::new()).with_rehash_budget(0);
+        assert!(!hashindex.rehash(1));
+        let capacity = hashindex.capacity();
+        let mut num_keys = 0;
+        while hashindex.capacity() == capacity {
+            assert!(hashindex.insert(num_keys, num_keys).is_ok());
+            num_keys += 1;
+        }
+        for key in 0..num_keys * 2 {
+            assert_eq!(hashindex.insert(key, key).is_ok(), key >= num_keys);
+            assert_eq!(hashindex.read(&key, |_, v| *v), Some(key));
+        }
+        let mut num_calls = 1;
+        while hashindex.rehash(1) {
+            num_calls += 1;
+        }
+        assert_eq!(num_calls, capacity / 32);
+        assert!(!hashindex.rehash(1));
+        for key in 0..num_keys * 2 {
+            assert_eq!(hashindex.read(&key, |_, v| *v), Some(key));
+        }
+        assert_eq!(hashindex.len(), num_keys * 2);
+    }
+
+    #[test]
+    fn insert_during_migration() {
+        // Writers insert keys that belong to the Cells being killed by a driver thread and by a
+        // remover, half of which are already in the old array.
+        let num_threads = 3;
+        let num_keys = 8192;
+        for _ in 0..4 {
+            let hashindex: HashIndex<usize, usize, RandomState> =
+                HashIndex::new(0, RandomState::new()).with_rehash_budget(0);
+            for key in 0..num_keys {
+                assert!(hashindex.insert(key, key).is_ok());
+            }
+            while hashindex.rehash(1024) {}
+            assert!(hashindex.try_reserve(num_keys * 8).is_ok());
+            let num_inserted = AtomicUsize::new(0);
+            let barrier = Barrier::new(num_threads + 2);
+            thread::scope(|s| {
+                for thread_id in 0..num_threads {
+                    let (hashindex, num_inserted, barrier) = (&hashindex, &num_inserted, &barrier);
+                    s.spawn(move || {
+                        barrier.wait();
+                        // Every thread tries to insert every key, starting at different offsets.
+                        for i in 0..num_keys * 2 {
+                            let key = (i + thread_id * num_keys / 2) % (num_keys * 2);
+                            if hashindex.insert(key, key).is_ok() {
+                                assert!(key >= num_keys);
+                                num_inserted.fetch_add(1, Relaxed);
+                            }
+                        }
+                    });
+                }
+                s.spawn(|| {
+                    barrier.wait();
+                    while hashindex.rehash(1) {
+                        thread::yield_now();
+                    }
+                });
+                s.spawn(|| {
+                    // Removing a key that does not exist kills the Cell in the old array.
+                    barrier.wait();
+                    for key in (num_keys * 4..num_keys * 5).rev() {
+                        assert!(!hashindex.remove(&key));
+                    }
+                });
+            });
+            assert_eq!(num_inserted.load(Relaxed), num_keys);
+            while hashindex.rehash(1024) {}
+            assert_eq!(hashindex.len(), num_keys * 2);
+            let mut keys = BTreeSet::new();
+            for (k, v) in hashindex.iter() {
+                assert_eq!(k, v);
+                assert!(keys.insert(*k));
+            }
+            assert_eq!(keys.len(), num_keys * 2);
+        }
+    }
+
+    #[test]
+    fn cell_scan() {
+        // Scans all the Cells, and restarts if the array is replaced in the meantime.
+        fn scan(hashindex: &HashIndex<u64, u64>) -> BTreeSet<u64> {
+            'restart: loop {
+                let generation = hashindex.array_generation();
+                let mut keys = BTreeSet::new();
+                for index in 0..hashindex.cell_count() {
+                    match hashindex.cell(generation, index) {
+                        Some(entries) => keys.extend(entries.map(|(k, v)| {
+                            assert_eq!(k, v);
+                            *k
+                        })),
+                        None => continue 'restart,
+                    }
+                }
+                if hashindex.array_generation() == generation {
+                    return keys;
+                }
+            }
+        }
+
+        let hashindex: HashIndex<u64, u64> = Default::default();
+        let generation = hashindex.array_generation();
+        assert!(hashindex.cell(generation, hashindex.cell_count()).is_none());
+        assert!(hashindex.cell(generation + 1, 0).is_none());
+        for key in 0..4096 {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        let keys = scan(&hashindex);
+        assert_eq!(keys.len(), 4096);
+        assert_eq!(keys, hashindex.iter().map(|(k, _)| *k).collect());
+
+        let generation = hashindex.array_generation();
+        assert!(hashindex.try_reserve(1 << 16).is_ok());
+        assert_ne!(hashindex.array_generation(), generation);
+        assert!(hashindex.cell(generation, 0).is_none());
+        assert_eq!(scan(&hashindex), keys);
+
+        // Scans the HashIndex while it is being resized.
+        let hashindex: HashIndex<u64, u64> = Default::default();
+        for key in 0..4096 {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        thread::scope(|s| {
+            s.spawn(|| {
+                for key in 4096..65536 {
+                    assert!(hashindex.insert(key, key).is_ok());
+                }
+            });
+            for _ in 0..16 {
+                let keys = scan(&hashindex);
+                assert!((0..4096).all(|key| keys.contains(&key)));
+            }
+        });
+        assert_eq!(
+            scan(&hashindex),
+            hashindex.iter().map(|(k, _)| *k).collect::<BTreeSet<u64>>()
+        );
+    }
+    #[test]
+    fn guard_reuse() {
+        let hashindex: HashIndex<u64, u64> = HashIndex::default();
+        for key in 0..4096 {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        thread::scope(|s| {
+            s.spawn(|| {
+                for batch in 0..64 {
+                    let guard = scc::pin();
+                    for key in (4096 + batch * 1024)..(4096 + (batch + 1) * 1024) {
+                        assert!(hashindex.insert_with_guard(key, key, &guard).is_ok());
+                        assert_eq!(hashindex.read_with(&key, |_, v| *v, &guard), Some(key));
+                        if key % 2 == 0 {
+                            assert!(hashindex.remove_with_guard(&key, &guard));
+                        }
+                    }
+                }
+            });
+            for _ in 0..16 {
+                let guard = scc::pin();
+                let keys: BTreeSet<u64> = hashindex.iter_with(&guard).map(|(k, _)| *k).collect();
+                assert!((0..4096).all(|key| keys.contains(&key)));
+                assert!((0..4096).all(|key| hashindex.read_with(&key, |_, _| (), &guard).is_some()));
+            }
+        });
+        let guard = scc::pin();
+        assert_eq!(hashindex.iter_with(&guard).count(), 4096 + 64 * 512);
+        assert!(!hashindex.remove_with_guard(&4096, &guard));
+        assert!(hashindex.remove_with_guard(&4097, &guard));
+        assert!(hashindex.read_with(&4097, |_, _| (), &guard).is_none());
+    }
+    #[test]
+    fn flush_after_clear() {
+        static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+        struct D;
+        impl D {
+            fn new() -> D {
+                LIVE.fetch_add(1, Relaxed);
+                D
+            }
+        }
+        impl Clone for D {
+            fn clone(&self) -> Self {
+                D::new()
+            }
+        }
+        impl Drop for D {
+            fn drop(&mut self) {
+                LIVE.fetch_sub(1, Relaxed);
+            }
+        }
+
+        let num_values = 65536;
+        let hashindex: HashIndex<usize, D> = HashIndex::default();
+        for key in 0..num_values {
+            assert!(hashindex.insert(key, D::new()).is_ok());
+        }
+        assert_eq!(hashindex.clear(), num_values);
+        assert!(scc::ebr::pending_garbage_hint() > 0);
+
+        // No other thread needs to be pinned for the values to be dropped.
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut reclaimed = 0;
+        while LIVE.load(Relaxed) != 0 {
+            assert!(Instant::now() < deadline, "values are not reclaimed");
+            reclaimed += scc::flush();
+        }
+        assert!(reclaimed > 0);
+    }
+
+    #[test]
+    fn reclaim_after_unpin() {
+        #[derive(Clone)]
+        struct D(Arc<AtomicUsize>);
+        impl Drop for D {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Relaxed);
+            }
+        }
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let hashindex: HashIndex<usize, D> = HashIndex::default();
+        assert!(hashindex.insert(0, D(dropped.clone())).is_ok());
+
+        // A reader pinned before the removal keeps the value alive.
+        let guard = scc::pin();
+        assert!(hashindex.read_with(&0, |_, _| (), &guard).is_some());
+        thread::scope(|s| {
+            s.spawn(|| {
+                assert!(hashindex.remove(&0));
+                for _ in 0..256 {
+                    scc::ebr::pin().flush();
+                }
+            });
+        });
+        for _ in 0..256 {
+            guard.flush();
+        }
+        assert_eq!(dropped.load(Relaxed), 0);
+
+        drop(guard);
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while dropped.load(Relaxed) == 0 {
+            assert!(Instant::now() < deadline, "the value is not reclaimed");
+            scc::ebr::pin().flush();
+        }
+        assert_eq!(dropped.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn peek_ref() {
+        fn total(values: &[scc::Ref<'_, String>]) -> usize {
+            values.iter().map(|v| v.len()).sum()
+        }
+
+        let hashindex: HashIndex<usize, String> = HashIndex::default();
+        for k in 0..16 {
+            assert!(hashindex.insert(k, k.to_string()).is_ok());
+        }
+
+        let guard = scc::pin();
+        let values: Vec<scc::Ref<String>> =
+            (0..16).filter_map(|k| hashindex.peek(&k, &guard)).collect();
+        assert_eq!(values.len(), 16);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for k in 0..16 {
+                    assert!(hashindex.remove(&k));
+                }
+            });
+        });
+        assert!(hashindex.peek(&0, &guard).is_none());
+        for (k, v) in values.iter().enumerate() {
+            assert_eq!(**v, k.to_string());
+        }
+        assert_eq!(total(&values), 10 + 6 * 2);
+    }
+
+    #[test]
+    fn guard_across_drop() {
+        struct D(usize, Arc<AtomicUsize>);
+        impl D {
+            fn new(value: usize, live: &Arc<AtomicUsize>) -> D {
+                live.fetch_add(1, Relaxed);
+                D(value, live.clone())
+            }
+        }
+        impl Clone for D {
+            fn clone(&self) -> Self {
+                D::new(self.0, &self.1)
+            }
+        }
+        impl Drop for D {
+            fn drop(&mut self) {
+                self.1.fetch_sub(1, Relaxed);
+            }
+        }
+
+        let live = Arc::new(AtomicUsize::new(0));
+        let hashindex: Arc<HashIndex<usize, D>> = Arc::new(HashIndex::default());
+        for key in 0..64 {
+            assert!(hashindex.insert(key, D::new(key, &live)).is_ok());
+        }
+
+        // The reader keeps dereferencing the values after the HashIndex is dropped.
+        let (peeked_tx, peeked_rx) = std::sync::mpsc::channel();
+        let (dropped_tx, dropped_rx) = std::sync::mpsc::channel::<()>();
+        let hashindex_copied = hashindex.clone();
+        let live_copied = live.clone();
+        let reader = thread::spawn(move || {
+            let guard = scc::pin();
+            let values: Vec<scc::Ref<D>> = (0..64)
+                .filter_map(|key| hashindex_copied.peek(&key, &guard))
+                .collect();
+            drop(hashindex_copied);
+            peeked_tx.send(()).unwrap();
+            dropped_rx.recv().unwrap();
+            for _ in 0..16 {
+                for (key, value) in values.iter().enumerate() {
+                    assert_eq!(value.0, key);
+                }
+                assert!(live_copied.load(Relaxed) >= 64);
+                scc::ebr::pin().flush();
+            }
+        });
+        peeked_rx.recv().unwrap();
+        drop(hashindex);
+        for _ in 0..16 {
+            scc::flush();
+        }
+        assert!(live.load(Relaxed) >= 64);
+        dropped_tx.send(()).unwrap();
+        reader.join().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while live.load(Relaxed) != 0 {
+            assert!(Instant::now() < deadline, "values are not reclaimed");
+            scc::flush();
+        }
+    }
+
+    #[test]
+    fn drop_quiescent() {
+        struct D(Arc<AtomicUsize>);
+        impl Clone for D {
+            fn clone(&self) -> Self {
+                D(self.0.clone())
+            }
+        }
+        impl Drop for D {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Relaxed);
+            }
+        }
+
+        // No thread is pinned in the domain, therefore the values are dropped right away.
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let hashindex: HashIndex<usize, D, RandomState> =
+            HashIndex::new_with_domain(Domain::new(), 64, RandomState::new());
+        assert!(hashindex.insert(0, D(dropped.clone())).is_ok());
+        drop(hashindex);
+        assert_eq!(dropped.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn domain() {
+        struct D(Arc<AtomicUsize>);
+        impl D {
+            fn new(live: &Arc<AtomicUsize>) -> D {
+                live.fetch_add(1, Relaxed);
+                D(live.clone())
+            }
+        }
+        impl Clone for D {
+            fn clone(&self) -> Self {
+                D::new(&self.0)
+            }
+        }
+        impl Drop for D {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Relaxed);
+            }
+        }
+
+        let domain_a = Domain::new();
+        let domain_b = Domain::new();
+        let live_a = Arc::new(AtomicUsize::new(0));
+        let live_b = Arc::new(AtomicUsize::new(0));
+        let hashindex_a: HashIndex<usize, D, RandomState> =
+            HashIndex::new_with_domain(domain_a.clone(), 64, RandomState::new());
+        let hashindex_b: HashIndex<usize, D, RandomState> =
+            HashIndex::new_with_domain(domain_b.clone(), 64, RandomState::new());
+        for key in 0..64 {
+            assert!(hashindex_a.insert(key, D::new(&live_a)).is_ok());
+            assert!(hashindex_b.insert(key, D::new(&live_b)).is_ok());
+        }
+
+        // A reader is stuck in domain A and in the global domain.
+        let (domain_a, hashindex_a) = (&domain_a, &hashindex_a);
+        let stuck = thread::scope(|s| {
+            let (stuck_tx, stuck_rx) = std::sync::mpsc::channel();
+            let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+            s.spawn(move || {
+                let guard_a = domain_a.pin();
+                let _global_guard = scc::pin();
+                assert!(hashindex_a.read_with(&0, |_, _| (), &guard_a).is_some());
+                stuck_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+            });
+            stuck_rx.recv().unwrap();
+
+            assert_eq!(hashindex_a.clear(), 64);
+            assert_eq!(hashindex_b.clear(), 64);
+            let deadline = Instant::now() + Duration::from_secs(60);
+            while live_b.load(Relaxed) != 0 {
+                assert!(Instant::now() < deadline, "values are not reclaimed");
+                domain_b.flush();
+            }
+            for _ in 0..16 {
+                hashindex_a.domain().flush();
+            }
+            let stuck = live_a.load(Relaxed);
+            release_tx.send(()).unwrap();
+            stuck
+        });
+        assert!(stuck >= 64);
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while live_a.load(Relaxed) != 0 {
+            assert!(Instant::now() < deadline, "values are not reclaimed");
+            hashindex_a.domain().flush();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "another reclamation domain")]
+    fn domain_guard_mismatch() {
+        let hashindex: HashIndex<usize, usize, RandomState> =
+            HashIndex::new_with_domain(Domain::new(), 64, RandomState::new());
+        hashindex.read_with(&0, |_, _| (), &scc::pin());
+    }
+
+    #[test]
+    fn reclamation_statistics() {
+        struct D(Arc<AtomicUsize>);
+        impl D {
+            fn new(live: &Arc<AtomicUsize>) -> D {
+                live.fetch_add(1, Relaxed);
+                D(live.clone())
+            }
+        }
+        impl Clone for D {
+            fn clone(&self) -> Self {
+                D::new(&self.0)
+            }
+        }
+        impl Drop for D {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Relaxed);
+            }
+        }
+
+        let live = Arc::new(AtomicUsize::new(0));
+        let pinned: HashIndex<usize, D> = HashIndex::default();
+        let unpinned: HashIndex<usize, D> = HashIndex::default();
+        for key in 0..64 {
+            assert!(pinned.insert(key, D::new(&live)).is_ok());
+            assert!(unpinned.insert(key, D::new(&live)).is_ok());
+        }
+        let before = scc::ebr::reclamation_statistics();
+
+        // Values removed while the thread is pinned stay pending.
+        let guard = scc::pin();
+        assert_eq!(pinned.clear(), 64);
+        let statistics = scc::ebr::reclamation_statistics();
+        assert!(statistics.pending_objects() > 0);
+        assert!(statistics.pending_bytes() > 0);
+
+        // Values removed by an exited thread are handed over to the global list.
+        thread::scope(|s| {
+            s.spawn(|| assert_eq!(unpinned.clear(), 64));
+        });
+        assert!(scc::ebr::reclamation_statistics().pending_objects() > 1);
+        for _ in 0..16 {
+            guard.flush();
+        }
+        assert!(live.load(Relaxed) >= 128);
+
+        drop(guard);
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while live.load(Relaxed) != 0 {
+            assert!(Instant::now() < deadline, "values are not reclaimed");
+            scc::flush();
+        }
+        let after = scc::ebr::reclamation_statistics();
+        assert!(after.epoch_advancements() >= before.epoch_advancements() + 2);
+    }
+
+    #[test]
+    fn unwind_safety() {
+        let hashindex: Arc<HashIndex<usize, usize, RandomState>> =
+            Arc::new(HashIndex::new(64, RandomState::new()));
+        for key in 0..64 {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            hashindex.read(&1, |_, _| panic!());
+        }));
+        assert!(result.is_err());
+        let hashindex_clone = hashindex.clone();
+        thread::spawn(move || {
+            assert!(hashindex_clone.remove(&1));
+            assert!(hashindex_clone.insert(1, 2).is_ok());
+            assert_eq!(hashindex_clone.read(&1, |_, v| *v), Some(2));
+        })
+        .join()
+        .unwrap();
+    }
+    #[test]
+    fn deserialize_into() {
+        let data_size = 1 << 16;
+        let source: HashIndex<u64, u64> = HashIndex::default();
+        for key in 0..data_size {
+            assert!(source.insert(key, key * 2).is_ok());
+        }
+        let json = serde_json::to_string(&source).unwrap();
+        let deserialized: HashIndex<u64, u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.len(), data_size as usize);
+        for key in 0..data_size {
+            assert_eq!(deserialized.read(&key, |_, v| *v), Some(key * 2));
+        }
+
+        // The pre-sized target is not resized.
+        let target: HashIndex<u64, u64, RandomState> =
+            HashIndex::new(data_size as usize * 2, RandomState::new());
+        for key in 0..16 {
+            assert!(target.insert(key, 0).is_ok());
+        }
+        let capacity = target.capacity();
+        let array_generation = target.array_generation();
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let seed = target.deserialize_seed(DuplicateKeyPolicy::Keep);
+        assert_eq!(seed.deserialize(&mut deserializer).unwrap(), 16);
+        assert_eq!(target.len(), data_size as usize);
+        assert_eq!(target.capacity(), capacity);
+        assert_eq!(target.array_generation(), array_generation);
+        assert_eq!(target.read(&0, |_, v| *v), Some(0));
+        assert_eq!(target.read(&16, |_, v| *v), Some(32));
+
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        assert_eq!(
+            target.deserialize_into(&mut deserializer).unwrap(),
+            data_size as usize
+        );
+        for key in 0..data_size {
+            assert_eq!(target.read(&key, |_, v| *v), Some(key * 2));
+        }
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let seed = target.deserialize_seed(DuplicateKeyPolicy::Reject);
+        assert!(seed.deserialize(&mut deserializer).is_err());
+        assert_eq!(target.len(), data_size as usize);
+    }
+
+    #[test]
+    fn par_collect() {
+        let data_size = 1 << 16;
+        let hashindex: HashIndex<u64, u64> = (0..data_size)
+            .into_par_iter()
+            .map(|key| (key, key * 2))
+            .collect();
+        assert_eq!(hashindex.len(), data_size as usize);
+        // Reserved upfront in steps of at most 64x: 4096, and then 262144.
+        assert_eq!(hashindex.array_generation(), 2);
+        for key in 0..data_size {
+            assert_eq!(hashindex.read(&key, |_, v| *v), Some(key * 2));
+        }
+
+        // The length of a filtered parallel iterator is unknown, and existing keys are kept.
+        let mut hashindex = hashindex;
+        hashindex.par_extend(
+            (0..data_size * 2)
+                .into_par_iter()
+                .filter(|key| key % 2 == 0)
+                .map(|key| (key, u64::MAX)),
+        );
+        assert_eq!(hashindex.len(), data_size as usize * 3 / 2);
+        for key in 0..data_size * 2 {
+            let expected = if key < data_size {
+                Some(key * 2)
+            } else if key % 2 == 0 {
+                Some(u64::MAX)
+            } else {
+                None
+            };
+            assert_eq!(hashindex.read(&key, |_, v| *v), expected);
+        }
+    }
+
+    #[test]
+    fn par_extend_reclaims() {
+        // A single worker extends the HashIndex with a long split; the arrays replaced on the way
+        // are reclaimed during the extension instead of being held back until the split ends.
+        let data_size = 1 << 18;
+        let domain = Domain::new();
+        let mut hashindex: HashIndex<u64, u64, RandomState> =
+            HashIndex::new_with_domain(domain.clone(), 64, RandomState::new());
+        let retired = AtomicBool::new(false);
+        let reclaimed = AtomicBool::new(false);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            hashindex.par_extend((0..data_size).into_par_iter().filter(|_| true).map(|key| {
+                if key % 4096 == 4095 {
+                    domain.flush();
+                    let pending = domain.reclamation_statistics().pending_objects();
+                    if pending != 0 {
+                        retired.store(true, Relaxed);
+                    } else if retired.load(Relaxed) {
+                        reclaimed.store(true, Relaxed);
+                    }
+                }
+                (key, key)
+            }));
+        });
+        assert_eq!(hashindex.len(), data_size as usize);
+        assert!(reclaimed.load(Relaxed));
+    }
+}
+
+#[cfg(test)]
+mod hashindexset_test {
+    use scc::HashIndexSet;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    fn read_during_updates() {
+        let num_readers = 4;
+        let num_writers = 4;
+        let num_stable_keys = 1024;
+        let num_keys_per_writer = 16384;
+        let hashindexset: Arc<HashIndexSet<String>> = Arc::new(Default::default());
+        for key in 0..num_stable_keys {
+            assert!(hashindexset.insert(key.to_string()).is_ok());
+        }
+        let done = Arc::new(AtomicBool::new(false));
+        let barrier = Arc::new(Barrier::new(num_readers + num_writers));
+        let mut thread_handles = Vec::with_capacity(num_readers + num_writers);
+        for _ in 0..num_readers {
+            let hashindexset_copied = hashindexset.clone();
+            let done_copied = done.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                while !done_copied.load(Relaxed) {
+                    for key in 0..num_stable_keys {
+                        let key = key.to_string();
+                        assert!(hashindexset_copied.contains(&key));
+                        assert_eq!(*hashindexset_copied.peek(&key).unwrap(), key);
+                    }
+                }
+                let num_visited = hashindexset_copied
+                    .iter()
+                    .filter(|key| key.parse::<usize>().unwrap() < num_stable_keys)
+                    .count();
+                assert!(num_visited >= num_stable_keys);
+            }));
+        }
+        for writer_id in 0..num_writers {
+            let hashindexset_copied = hashindexset.clone();
+            let done_copied = done.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let start = num_stable_keys + writer_id * num_keys_per_writer;
+                let range = start..start + num_keys_per_writer;
+                for key in range.clone() {
+                    assert!(hashindexset_copied.insert(key.to_string()).is_ok());
+                }
+                for key in range.clone() {
+                    let key = key.to_string();
+                    let peeked = hashindexset_copied.peek(&key).unwrap();
+                    assert!(hashindexset_copied.remove(&key));
+                    assert!(!hashindexset_copied.contains(&key));
+                    assert_eq!(*peeked, key);
+                }
+                done_copied.store(true, Relaxed);
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(hashindexset.len(), num_stable_keys);
+        assert_eq!(hashindexset.clear(), num_stable_keys);
+        assert!(hashindexset.is_empty());
+    }
+}
 
 #[cfg(test)]
 mod treeindex_test {
     use proptest::strategy::{Strategy, ValueTree};
     use proptest::test_runner::TestRunner;
     use scc::TreeIndex;
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::ops::Bound::{Excluded, Included, Unbounded};
+    use std::ops::RangeBounds;
     use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
     use std::sync::atomic::{AtomicBool, AtomicUsize};
     use std::sync::{Arc, Barrier};
@@ -452,98 +4219,488 @@ mod treeindex_test {
     }
 
     #[test]
-    fn complex() {
+    fn complex() {
+        let range = 4096;
+        let num_threads = 16;
+        let tree: Arc<TreeIndex<usize, usize>> = Arc::new(TreeIndex::new());
+        for t in 0..num_threads {
+            // insert markers
+            tree.insert(t * range, t * range).unwrap();
+        }
+        let stopped: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let barrier = Arc::new(Barrier::new(num_threads + 1));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let tree_copied = tree.clone();
+            let stopped_copied = stopped.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                let first_key = thread_id * range;
+                barrier_copied.wait();
+                while !stopped_copied.load(Relaxed) {
+                    for key in (first_key + 1)..(first_key + range) {
+                        assert!(tree_copied.insert(key, key).is_ok());
+                    }
+                    for key in (first_key + 1)..(first_key + range) {
+                        assert!(tree_copied
+                            .read(&key, |key, value| assert_eq!(key, value))
+                            .is_some());
+                    }
+                    let mut range_scanner = tree_copied.range(first_key..);
+                    let entry = range_scanner.next().unwrap();
+                    assert_eq!(entry, (&first_key, &first_key));
+                    let entry = range_scanner.next().unwrap();
+                    assert_eq!(entry, (&(first_key + 1), &(first_key + 1)));
+                    let entry = range_scanner.next().unwrap();
+                    assert_eq!(entry, (&(first_key + 2), &(first_key + 2)));
+                    let entry = range_scanner.next().unwrap();
+                    assert_eq!(entry, (&(first_key + 3), &(first_key + 3)));
+
+                    let key_at_halfway = first_key + range / 2;
+                    for key in (first_key + 1)..(first_key + range) {
+                        if key == key_at_halfway {
+                            let mut range_scanner = tree_copied.range((first_key + 1)..);
+                            let entry = range_scanner.next().unwrap();
+                            assert_eq!(entry, (&key_at_halfway, &key_at_halfway));
+                            let entry = range_scanner.next().unwrap();
+                            assert_eq!(entry, (&(key_at_halfway + 1), &(key_at_halfway + 1)));
+                        }
+                        assert!(tree_copied.remove(&key));
+                        assert!(!tree_copied.remove(&key));
+                        assert!(tree_copied.read(&(first_key + 1), |_, _| ()).is_none());
+                    }
+                    for key in (first_key + 1)..(first_key + range) {
+                        assert!(tree_copied
+                            .read(&key, |key, value| assert_eq!(key, value))
+                            .is_none());
+                    }
+                }
+            }));
+        }
+        barrier.wait();
+        for i in 0..512 {
+            let mut found_0 = false;
+            let mut found_markers = 0;
+            let mut prev_marker = 0;
+            let mut prev = 0;
+            for iter in tree.iter() {
+                let current = *iter.0;
+                if current % range == 0 {
+                    found_markers += 1;
+                    if current == 0 {
+                        found_0 = true;
+                    }
+                    if current > 0 {
+                        assert_eq!(prev_marker + range, current);
+                    }
+                    prev_marker = current;
+                }
+                assert!(prev == 0 || prev < current);
+                prev = current
+            }
+            if i % 64 == 0 {
+                println!("{} {}", i, found_markers);
+            }
+            assert!(found_0);
+            assert_eq!(found_markers, num_threads);
+        }
+
+        stopped.store(true, Release);
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn overlapping_ranges() {
+        let range = 4096;
+        let marker_interval = 64;
+        let num_threads = 8;
+        let num_iterations = 16;
+        let tree: Arc<TreeIndex<usize, usize>> = Arc::new(TreeIndex::new());
+        for key in (0..range).step_by(marker_interval) {
+            assert!(tree.insert(key, key).is_ok());
+        }
+        let num_net_inserted = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let barrier = Arc::new(Barrier::new(num_threads + 1));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let tree_copied = tree.clone();
+            let num_net_inserted_copied = num_net_inserted.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let (mut num_inserted, mut num_removed) = (0, 0);
+                for iteration in 0..num_iterations {
+                    // Threads visit the same keys in different orders.
+                    let offset = (thread_id * range / num_threads + iteration) % range;
+                    for key in (0..range).map(|key| (key + offset) % range) {
+                        if key % marker_interval == 0 {
+                            assert!(tree_copied.read(&key, |_, value| *value).is_some());
+                            assert!(tree_copied.insert(key, key).is_err());
+                            continue;
+                        }
+                        if tree_copied.insert(key, key).is_ok() {
+                            num_inserted += 1;
+                        }
+                        if let Some(value) = tree_copied.read(&key, |_, value| *value) {
+                            assert_eq!(value, key);
+                        }
+                        if (key + iteration) % 3 == 0 && tree_copied.remove(&key) {
+                            num_removed += 1;
+                        }
+                    }
+                }
+                num_net_inserted_copied.fetch_add(num_inserted - num_removed, Relaxed);
+            }));
+        }
+        let tree_copied = tree.clone();
+        let stopped_copied = stopped.clone();
+        let reader = thread::spawn(move || {
+            while !stopped_copied.load(Acquire) {
+                let mut num_markers = 0;
+                let mut prev = None;
+                for (key, value) in tree_copied.iter() {
+                    assert_eq!(key, value);
+                    assert!(prev.map_or(true, |prev| prev < *key));
+                    if key % marker_interval == 0 {
+                        num_markers += 1;
+                    }
+                    prev.replace(*key);
+                }
+                assert_eq!(num_markers, range / marker_interval);
+                let mut scanner = tree_copied.range(marker_interval..);
+                assert_eq!(scanner.next(), Some((&marker_interval, &marker_interval)));
+            }
+        });
+        barrier.wait();
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        stopped.store(true, Release);
+        reader.join().unwrap();
+        let num_remaining = (0..range)
+            .filter(|key| key % marker_interval != 0)
+            .filter(|key| tree.read(key, |_, _| ()).is_some())
+            .count();
+        assert_eq!(num_remaining, num_net_inserted.load(Relaxed));
+        assert_eq!(tree.len(), num_remaining + range / marker_interval);
+    }
+
+    #[test]
+    fn range_rev() {
+        let range = 4096;
+        let stable_interval = 4;
+        let num_threads = 4;
+        let tree: Arc<TreeIndex<usize, usize>> = Arc::new(TreeIndex::new());
+        for key in (0..range).step_by(stable_interval) {
+            assert!(tree.insert(key, key).is_ok());
+        }
+        let bounds = [
+            (Included(range / 4), Excluded(range * 3 / 4)),
+            (Excluded(range / 4), Included(range * 3 / 4)),
+            (Unbounded, Excluded(range / 2)),
+            (Included(range / 2 + 1), Unbounded),
+        ];
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let tree_copied = tree.clone();
+            let stopped_copied = stopped.clone();
+            thread_handles.push(thread::spawn(move || {
+                // Writers insert and remove keys inside and outside of the ranges.
+                while !stopped_copied.load(Relaxed) {
+                    for key in (0..range * 2).filter(|key| key % stable_interval != 0) {
+                        if key % num_threads == thread_id {
+                            assert!(tree_copied.insert(key, key).is_ok());
+                        }
+                    }
+                    for key in (0..range * 2).filter(|key| key % stable_interval != 0) {
+                        if key % num_threads == thread_id {
+                            assert!(tree_copied.remove(&key));
+                        }
+                    }
+                }
+            }));
+        }
+        for _ in 0..16 {
+            for bound in bounds.iter() {
+                let expected: Vec<usize> = (0..range)
+                    .step_by(stable_interval)
+                    .filter(|key| bound.contains(key))
+                    .collect();
+                let forward: Vec<usize> = tree.range(*bound).map(|(key, _)| *key).collect();
+                assert!(forward.windows(2).all(|keys| keys[0] < keys[1]));
+                assert!(forward.iter().all(|key| bound.contains(key)));
+                assert!(expected
+                    .iter()
+                    .all(|key| forward.binary_search(key).is_ok()));
+
+                let backward: Vec<usize> = tree.range(*bound).rev().map(|(key, _)| *key).collect();
+                assert!(backward.windows(2).all(|keys| keys[0] > keys[1]));
+                assert!(backward.iter().all(|key| bound.contains(key)));
+                assert!(expected
+                    .iter()
+                    .all(|key| backward.binary_search_by(|probe| key.cmp(probe)).is_ok()));
+
+                // Both ends meet without returning the same key twice.
+                let mut both_ends = tree.range(*bound);
+                let mut front = Vec::new();
+                let mut back = Vec::new();
+                loop {
+                    match both_ends.next() {
+                        Some((key, _)) => front.push(*key),
+                        None => break,
+                    }
+                    match both_ends.next_back() {
+                        Some((key, _)) => back.push(*key),
+                        None => break,
+                    }
+                }
+                assert!(both_ends.next().is_none() && both_ends.next_back().is_none());
+                back.reverse();
+                front.extend(back);
+                assert!(front.windows(2).all(|keys| keys[0] < keys[1]));
+                assert!(expected.iter().all(|key| front.binary_search(key).is_ok()));
+            }
+        }
+        stopped.store(true, Relaxed);
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn remove_range() {
+        let tree: TreeIndex<usize, usize> = TreeIndex::new();
+        for key in 0..256 {
+            assert!(tree.insert(key, key).is_ok());
+        }
+        assert_eq!(tree.remove_range(256..), 0);
+        assert_eq!(tree.remove_range((Excluded(16), Included(32))), 16);
+        assert_eq!(tree.remove_range(8..=40), 17);
+        assert_eq!(tree.remove_range(..4), 4);
+        assert_eq!(tree.len(), 256 - 37);
+        assert!(tree.range(..=40).map(|(key, _)| *key).eq(4..8));
+        assert_eq!(tree.remove_range(..), 256 - 37);
+        assert_eq!(tree.len(), 0);
+        for key in 0..256 {
+            assert!(tree.insert(key, key).is_ok());
+        }
+        assert!(tree.iter().map(|(key, _)| *key).eq(0..256));
+
+        let range = 16384;
+        let num_threads = 4;
+        for _ in 0..4 {
+            let tree: Arc<TreeIndex<usize, usize>> = Arc::new(TreeIndex::new());
+            for key in 0..range {
+                assert!(tree.insert(key, key).is_ok());
+            }
+            let stopped = Arc::new(AtomicBool::new(false));
+            let mut thread_handles = Vec::with_capacity(num_threads);
+            for thread_id in 0..num_threads {
+                let tree_copied = tree.clone();
+                let stopped_copied = stopped.clone();
+                thread_handles.push(thread::spawn(move || {
+                    while !stopped_copied.load(Relaxed) {
+                        if thread_id == 0 {
+                            // Splits and merges nodes outside of the range.
+                            for key in range..range * 2 {
+                                assert!(tree_copied.insert(key, key).is_ok());
+                            }
+                            for key in range..range * 2 {
+                                assert!(tree_copied.remove(&key));
+                            }
+                            continue;
+                        }
+                        // Readers scan across the range being removed.
+                        let keys: Vec<usize> = tree_copied
+                            .range(range / 8..range)
+                            .map(|(key, _)| *key)
+                            .collect();
+                        assert!(keys.windows(2).all(|keys| keys[0] < keys[1]));
+                        assert!((range / 8..range / 4)
+                            .chain(range * 3 / 4..range)
+                            .all(|key| keys.binary_search(&key).is_ok()));
+                    }
+                }));
+            }
+            assert_eq!(tree.remove_range(range / 4..range * 3 / 4), range / 2);
+            stopped.store(true, Relaxed);
+            for handle in thread_handles {
+                handle.join().unwrap();
+            }
+            assert!(tree
+                .iter()
+                .map(|(key, _)| *key)
+                .eq((0..range / 4).chain(range * 3 / 4..range)));
+        }
+    }
+
+    #[test]
+    fn floor_ceiling() {
+        let tree: TreeIndex<usize, usize> = TreeIndex::new();
+        assert!(tree.read_floor(&0, |_, _| ()).is_none());
+        assert!(tree.read_ceiling(&0, |_, _| ()).is_none());
+        for key in (16..1024).step_by(2) {
+            assert!(tree.insert(key, key).is_ok());
+        }
+        // Probe smaller than the minimum.
+        assert!(tree.read_floor(&15, |key, _| *key).is_none());
+        assert_eq!(tree.read_ceiling(&0, |key, _| *key), Some(16));
+        // Probe larger than the maximum.
+        assert_eq!(tree.read_floor(&usize::MAX, |key, _| *key), Some(1022));
+        assert!(tree.read_ceiling(&1023, |key, _| *key).is_none());
+        // Exact match and in-between probes.
+        for key in 16..1023 {
+            let floor = key - key % 2;
+            let ceiling = key + key % 2;
+            assert_eq!(tree.read_floor(&key, |key, _| *key), Some(floor));
+            assert_eq!(tree.read_ceiling(&key, |key, _| *key), Some(ceiling));
+        }
+
+        // Randomized comparison against BTreeMap.
+        let mut runner = TestRunner::default();
+        let tree: TreeIndex<usize, usize> = TreeIndex::new();
+        let mut model = BTreeMap::new();
+        for _ in 0..4096 {
+            let key = (0..2048_usize).new_tree(&mut runner).unwrap().current();
+            let op = (0..3_usize).new_tree(&mut runner).unwrap().current();
+            if op == 0 {
+                assert_eq!(tree.remove(&key), model.remove(&key).is_some());
+            } else {
+                assert_eq!(
+                    tree.insert(key, key).is_ok(),
+                    model.insert(key, key).is_none()
+                );
+            }
+            let probe = (0..2048_usize).new_tree(&mut runner).unwrap().current();
+            assert_eq!(
+                tree.read_floor(&probe, |key, _| *key),
+                model.range(..=probe).next_back().map(|(key, _)| *key)
+            );
+            assert_eq!(
+                tree.read_ceiling(&probe, |key, _| *key),
+                model.range(probe..).next().map(|(key, _)| *key)
+            );
+        }
+
+        // Concurrent removal of the boundary keys.
         let range = 4096;
-        let num_threads = 16;
+        let stable_interval = 16;
+        let num_threads = 4;
         let tree: Arc<TreeIndex<usize, usize>> = Arc::new(TreeIndex::new());
-        for t in 0..num_threads {
-            // insert markers
-            tree.insert(t * range, t * range).unwrap();
+        for key in (0..range).step_by(stable_interval) {
+            assert!(tree.insert(key, key).is_ok());
         }
-        let stopped: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
-        let barrier = Arc::new(Barrier::new(num_threads + 1));
+        let stopped = Arc::new(AtomicBool::new(false));
         let mut thread_handles = Vec::with_capacity(num_threads);
         for thread_id in 0..num_threads {
             let tree_copied = tree.clone();
             let stopped_copied = stopped.clone();
-            let barrier_copied = barrier.clone();
             thread_handles.push(thread::spawn(move || {
-                let first_key = thread_id * range;
-                barrier_copied.wait();
                 while !stopped_copied.load(Relaxed) {
-                    for key in (first_key + 1)..(first_key + range) {
-                        assert!(tree_copied.insert(key, key).is_ok());
-                    }
-                    for key in (first_key + 1)..(first_key + range) {
-                        assert!(tree_copied
-                            .read(&key, |key, value| assert_eq!(key, value))
-                            .is_some());
-                    }
-                    let mut range_scanner = tree_copied.range(first_key..);
-                    let entry = range_scanner.next().unwrap();
-                    assert_eq!(entry, (&first_key, &first_key));
-                    let entry = range_scanner.next().unwrap();
-                    assert_eq!(entry, (&(first_key + 1), &(first_key + 1)));
-                    let entry = range_scanner.next().unwrap();
-                    assert_eq!(entry, (&(first_key + 2), &(first_key + 2)));
-                    let entry = range_scanner.next().unwrap();
-                    assert_eq!(entry, (&(first_key + 3), &(first_key + 3)));
-
-                    let key_at_halfway = first_key + range / 2;
-                    for key in (first_key + 1)..(first_key + range) {
-                        if key == key_at_halfway {
-                            let mut range_scanner = tree_copied.range((first_key + 1)..);
-                            let entry = range_scanner.next().unwrap();
-                            assert_eq!(entry, (&key_at_halfway, &key_at_halfway));
-                            let entry = range_scanner.next().unwrap();
-                            assert_eq!(entry, (&(key_at_halfway + 1), &(key_at_halfway + 1)));
+                    for key in (0..range).filter(|key| key % stable_interval != 0) {
+                        if key % num_threads == thread_id {
+                            assert!(tree_copied.insert(key, key).is_ok());
                         }
-                        assert!(tree_copied.remove(&key));
-                        assert!(!tree_copied.remove(&key));
-                        assert!(tree_copied.read(&(first_key + 1), |_, _| ()).is_none());
                     }
-                    for key in (first_key + 1)..(first_key + range) {
-                        assert!(tree_copied
-                            .read(&key, |key, value| assert_eq!(key, value))
-                            .is_none());
+                    for key in (0..range).filter(|key| key % stable_interval != 0) {
+                        if key % num_threads == thread_id {
+                            assert!(tree_copied.remove(&key));
+                        }
                     }
                 }
             }));
         }
-        barrier.wait();
-        for i in 0..512 {
-            let mut found_0 = false;
-            let mut found_markers = 0;
-            let mut prev_marker = 0;
-            let mut prev = 0;
-            for iter in tree.iter() {
-                let current = *iter.0;
-                if current % range == 0 {
-                    found_markers += 1;
-                    if current == 0 {
-                        found_0 = true;
-                    }
-                    if current > 0 {
-                        assert_eq!(prev_marker + range, current);
+        for _ in 0..16 {
+            for probe in 0..range {
+                // The result is bounded by the probe and the closest stable key.
+                let stable_floor = probe - probe % stable_interval;
+                let floor = tree.read_floor(&probe, |key, _| *key).unwrap();
+                assert!(floor >= stable_floor && floor <= probe);
+                let stable_ceiling =
+                    (probe + stable_interval - 1) / stable_interval * stable_interval;
+                match tree.read_ceiling(&probe, |key, _| *key) {
+                    Some(ceiling) => {
+                        assert!(ceiling >= probe && ceiling < range);
+                        assert!(stable_ceiling >= range || ceiling <= stable_ceiling);
                     }
-                    prev_marker = current;
+                    None => assert!(stable_ceiling >= range),
                 }
-                assert!(prev == 0 || prev < current);
-                prev = current
             }
-            if i % 64 == 0 {
-                println!("{} {}", i, found_markers);
-            }
-            assert!(found_0);
-            assert_eq!(found_markers, num_threads);
         }
-
-        stopped.store(true, Release);
+        stopped.store(true, Relaxed);
         for handle in thread_handles {
             handle.join().unwrap();
         }
     }
 
+    #[test]
+    fn pop() {
+        let num_popping_threads = 4;
+        let num_keys = 16384;
+        for pop_first in [true, false] {
+            // Keys are inserted on the opposite side of the popping end.
+            let key_at = move |index: usize| {
+                if pop_first {
+                    index
+                } else {
+                    num_keys * 2 - index
+                }
+            };
+            let tree: Arc<TreeIndex<usize, usize>> = Arc::new(TreeIndex::new());
+            for index in 0..num_keys {
+                assert!(tree.insert(key_at(index), index).is_ok());
+            }
+            let barrier = Arc::new(Barrier::new(num_popping_threads + 1));
+            let mut thread_handles = Vec::with_capacity(num_popping_threads + 1);
+            for _ in 0..num_popping_threads {
+                let tree_copied = tree.clone();
+                let barrier_copied = barrier.clone();
+                thread_handles.push(thread::spawn(move || {
+                    barrier_copied.wait();
+                    let mut popped = Vec::new();
+                    while let Some((key, index)) = if pop_first {
+                        tree_copied.pop_first()
+                    } else {
+                        tree_copied.pop_last()
+                    } {
+                        assert_eq!(key, key_at(index));
+                        if let Some(last) = popped.last() {
+                            // The minimum is never skipped.
+                            assert!(*last < index);
+                        }
+                        popped.push(index);
+                    }
+                    popped
+                }));
+            }
+            let tree_copied = tree.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for index in num_keys..num_keys * 2 {
+                    assert!(tree_copied.insert(key_at(index), index).is_ok());
+                }
+                Vec::new()
+            }));
+            let mut popped = BTreeSet::new();
+            for handle in thread_handles {
+                for index in handle.join().unwrap() {
+                    assert!(popped.insert(index));
+                }
+            }
+            // Popped and remaining keys make up all the inserted keys.
+            for (key, index) in tree.iter() {
+                assert_eq!(*key, key_at(*index));
+                assert!(popped.insert(*index));
+            }
+            assert_eq!(popped.len(), num_keys * 2);
+        }
+    }
+
     #[test]
     fn string_key() {
         let tree1: TreeIndex<String, u32> = Default::default();
@@ -636,3 +4793,444 @@ mod treeindex_test {
         }
     }
 }
+
+#[cfg(test)]
+mod queue_test {
+    use scc::Queue;
+    use std::ptr;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    struct R(&'static AtomicUsize);
+    impl R {
+        fn new(cnt: &'static AtomicUsize) -> R {
+            cnt.fetch_add(1, Relaxed);
+            R(cnt)
+        }
+    }
+    impl Clone for R {
+        fn clone(&self) -> R {
+            R::new(self.0)
+        }
+    }
+    impl Drop for R {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Relaxed);
+        }
+    }
+
+    fn wait_for_reclamation(cnt: &AtomicUsize, expected: usize) {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while cnt.load(Relaxed) != expected {
+            assert!(Instant::now() < deadline, "{} instances", cnt.load(Relaxed));
+            scc::ebr::pin().flush();
+        }
+    }
+
+    #[test]
+    fn mpmc() {
+        let num_producers = 4;
+        let num_consumers = 4;
+        let num_values_per_producer = 65536;
+        let queue: Arc<Queue<(usize, usize)>> = Arc::new(Queue::new());
+        let barrier = Arc::new(Barrier::new(num_producers + num_consumers));
+        let num_popped = Arc::new(AtomicUsize::new(0));
+        let mut producers = Vec::with_capacity(num_producers);
+        for producer_id in 0..num_producers {
+            let queue_copied = queue.clone();
+            let barrier_copied = barrier.clone();
+            producers.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for seq in 0..num_values_per_producer {
+                    queue_copied.push((producer_id, seq));
+                }
+            }));
+        }
+        let mut consumers = Vec::with_capacity(num_consumers);
+        for _ in 0..num_consumers {
+            let queue_copied = queue.clone();
+            let barrier_copied = barrier.clone();
+            let num_popped_copied = num_popped.clone();
+            consumers.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let mut popped = vec![Vec::new(); num_producers];
+                while num_popped_copied.load(Relaxed) < num_producers * num_values_per_producer {
+                    if let Some((producer_id, seq)) = queue_copied.pop() {
+                        // Values pushed by the same producer are popped in order.
+                        if let Some(last) = popped[producer_id].last() {
+                            assert!(*last < seq);
+                        }
+                        popped[producer_id].push(seq);
+                        num_popped_copied.fetch_add(1, Relaxed);
+                    } else if let Some(value) = queue_copied.peek(&scc::pin()) {
+                        assert!(value.0 < num_producers);
+                    }
+                }
+                popped
+            }));
+        }
+        for handle in producers {
+            handle.join().unwrap();
+        }
+        let mut all_popped = vec![Vec::new(); num_producers];
+        for handle in consumers {
+            for (producer_id, seqs) in handle.join().unwrap().into_iter().enumerate() {
+                all_popped[producer_id].extend(seqs);
+            }
+        }
+        for seqs in all_popped.iter_mut() {
+            seqs.sort_unstable();
+            assert_eq!(*seqs, (0..num_values_per_producer).collect::<Vec<_>>());
+        }
+        assert!(queue.is_empty());
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn iter_during_pop() {
+        let num_values = 65536;
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        for value in 0..num_values {
+            queue.push(value);
+        }
+        let queue_copied = queue.clone();
+        let thread_handle = thread::spawn(move || {
+            for value in 0..num_values {
+                assert_eq!(queue_copied.pop(), Some(value));
+            }
+        });
+        loop {
+            let mut prev = None;
+            let mut num_visited = 0;
+            let guard = scc::pin();
+            for value in queue.iter(&guard) {
+                assert!(prev.map_or(true, |prev| prev < *value));
+                prev.replace(*value);
+                num_visited += 1;
+                if num_visited == 64 {
+                    break;
+                }
+            }
+            if num_visited == 0 {
+                break;
+            }
+        }
+        thread_handle.join().unwrap();
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn iter_across_pop() {
+        static INST_CNT: AtomicUsize = AtomicUsize::new(0);
+        let queue: Arc<Queue<R>> = Arc::new(Queue::new());
+        for _ in 0..4 {
+            queue.push(R::new(&INST_CNT));
+        }
+
+        // Popping values does not wait for the references to them to be dropped.
+        let guard = scc::pin();
+        let mut iter = queue.iter(&guard);
+        let front = iter.next().unwrap();
+        let queue_copied = queue.clone();
+        thread::spawn(move || {
+            assert!(queue_copied.pop().is_some());
+            assert!(queue_copied.pop().is_some());
+        })
+        .join()
+        .unwrap();
+
+        // The popped values stay readable while the thread is pinned, and the iterator proceeds
+        // from where it was.
+        assert!(ptr::eq(front.0, &INST_CNT));
+        assert_eq!(iter.count(), 3);
+        assert_eq!(queue.iter(&guard).count(), 2);
+        assert_eq!(queue.len(), 2);
+        drop(guard);
+        drop(queue);
+        wait_for_reclamation(&INST_CNT, 0);
+    }
+
+    #[test]
+    fn no_leak() {
+        static INST_CNT: AtomicUsize = AtomicUsize::new(0);
+        let num_threads = 4;
+        let num_values_per_thread = 4096;
+        let queue: Arc<Queue<R>> = Arc::new(Queue::new());
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for _ in 0..num_threads {
+            let queue_copied = queue.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for _ in 0..num_values_per_thread {
+                    queue_copied.push(R::new(&INST_CNT));
+                    queue_copied.push(R::new(&INST_CNT));
+                    assert!(queue_copied.pop().is_some());
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(queue.len(), num_threads * num_values_per_thread);
+        // The last popped value is kept until the next pop.
+        wait_for_reclamation(&INST_CNT, num_threads * num_values_per_thread + 1);
+        drop(queue);
+        wait_for_reclamation(&INST_CNT, 0);
+    }
+}
+
+#[cfg(test)]
+mod stack_test {
+    use scc::Stack;
+    use std::ptr;
+    use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    struct R(&'static AtomicUsize);
+    impl R {
+        fn new(cnt: &'static AtomicUsize) -> R {
+            cnt.fetch_add(1, Relaxed);
+            R(cnt)
+        }
+    }
+    impl Clone for R {
+        fn clone(&self) -> R {
+            R::new(self.0)
+        }
+    }
+    impl Drop for R {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Relaxed);
+        }
+    }
+
+    fn wait_for_reclamation(cnt: &AtomicUsize, expected: usize) {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while cnt.load(Relaxed) != expected {
+            assert!(Instant::now() < deadline, "{} instances", cnt.load(Relaxed));
+            scc::ebr::pin().flush();
+        }
+    }
+
+    #[test]
+    fn aba() {
+        let num_threads = 8;
+        let num_values = 64;
+        let num_iterations = 16384;
+        let stack: Arc<Stack<usize>> = Arc::new(Stack::new());
+        for value in 0..num_values {
+            stack.push(value);
+        }
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let stack_copied = stack.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for _ in 0..num_iterations {
+                    if thread_id % 4 == 0 {
+                        if let Some(value) = stack_copied.peek(&scc::pin()) {
+                            assert!(*value < num_values);
+                        }
+                        continue;
+                    }
+                    // The same values are pushed back right after being popped, which is prone to
+                    // the ABA problem.
+                    let first = stack_copied.pop();
+                    let second = stack_copied.pop();
+                    if let Some(second) = second {
+                        stack_copied.push(second);
+                    }
+                    if let Some(first) = first {
+                        stack_copied.push(first);
+                    }
+                }
+            }));
+        }
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        let mut values: Vec<usize> = stack.pop_all().collect();
+        values.sort_unstable();
+        assert_eq!(values, (0..num_values).collect::<Vec<_>>());
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn pop_all() {
+        static INST_CNT: AtomicUsize = AtomicUsize::new(0);
+        let num_producers = 4;
+        let num_values_per_producer = 65536;
+        let stack: Arc<Stack<(usize, R)>> = Arc::new(Stack::new());
+        let barrier = Arc::new(Barrier::new(num_producers + 1));
+        let done = Arc::new(AtomicBool::new(false));
+        let num_popped = Arc::new(AtomicUsize::new(0));
+        let mut thread_handles = Vec::with_capacity(num_producers);
+        for producer_id in 0..num_producers {
+            let stack_copied = stack.clone();
+            let barrier_copied = barrier.clone();
+            let num_popped_copied = num_popped.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for seq in 0..num_values_per_producer {
+                    stack_copied.push((
+                        producer_id * num_values_per_producer + seq,
+                        R::new(&INST_CNT),
+                    ));
+                    if seq % 4 == 0 && stack_copied.pop().is_some() {
+                        num_popped_copied.fetch_add(1, Relaxed);
+                    }
+                }
+            }));
+        }
+        let stack_copied = stack.clone();
+        let barrier_copied = barrier.clone();
+        let done_copied = done.clone();
+        let drainer = thread::spawn(move || {
+            barrier_copied.wait();
+            let mut drained = Vec::new();
+            while !done_copied.load(Acquire) {
+                drained.extend(stack_copied.pop_all().map(|(value, _)| value));
+            }
+            drained
+        });
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        done.store(true, Release);
+        let mut drained = drainer.join().unwrap();
+        drained.extend(stack.pop_all().map(|(value, _)| value));
+        let num_drained = drained.len();
+        drained.sort_unstable();
+        drained.dedup();
+        assert_eq!(drained.len(), num_drained);
+        assert_eq!(
+            num_drained + num_popped.load(Relaxed),
+            num_producers * num_values_per_producer
+        );
+        wait_for_reclamation(&INST_CNT, 0);
+
+        // Dropping a partially consumed drain drops the remaining values.
+        for _ in 0..16 {
+            stack.push((0, R::new(&INST_CNT)));
+        }
+        let mut drain = stack.pop_all();
+        assert!(drain.next().is_some());
+        drop(drain);
+        for _ in 0..16 {
+            stack.push((0, R::new(&INST_CNT)));
+        }
+        drop(stack);
+        wait_for_reclamation(&INST_CNT, 0);
+    }
+
+    #[test]
+    fn peek_across_pop() {
+        static INST_CNT: AtomicUsize = AtomicUsize::new(0);
+        let stack: Arc<Stack<R>> = Arc::new(Stack::new());
+        for _ in 0..4 {
+            stack.push(R::new(&INST_CNT));
+        }
+
+        // Popping the value does not wait for the references to it to be dropped.
+        let guard = scc::pin();
+        let top = stack.peek(&guard).unwrap();
+        let stack_copied = stack.clone();
+        thread::spawn(move || {
+            assert!(stack_copied.pop().is_some());
+            assert_eq!(stack_copied.pop_all().count(), 3);
+        })
+        .join()
+        .unwrap();
+
+        // The popped value stays readable while the thread is pinned.
+        assert!(ptr::eq(top.0, &INST_CNT));
+        assert!(stack.peek(&guard).is_none());
+        assert!(INST_CNT.load(Relaxed) >= 1);
+        drop(guard);
+        wait_for_reclamation(&INST_CNT, 0);
+    }
+}
+
+mod bag_test {
+    use scc::Bag;
+    use std::collections::BTreeSet;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    fn mixed() {
+        let num_threads = 32;
+        let num_values_per_thread = 8192;
+        let bag: Arc<Bag<usize>> = Arc::new(Bag::new(8));
+        let barrier = Arc::new(Barrier::new(num_threads));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let bag_copied = bag.clone();
+            let barrier_copied = barrier.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                let mut popped = Vec::new();
+                for seq in 0..num_values_per_thread {
+                    bag_copied.push(thread_id * num_values_per_thread + seq);
+                    if seq % 3 != 0 {
+                        popped.extend(bag_copied.pop());
+                    }
+                    if seq % 64 == 0 {
+                        assert!(bag_copied
+                            .iter(&scc::pin())
+                            .all(|value| *value < num_threads * num_values_per_thread));
+                    }
+                }
+                popped
+            }));
+        }
+        let mut popped = Vec::new();
+        for handle in thread_handles {
+            popped.extend(handle.join().unwrap());
+        }
+        let num_popped = popped.len();
+        assert_eq!(bag.len(), num_threads * num_values_per_thread - num_popped);
+        let mut values: BTreeSet<usize> = popped.into_iter().collect();
+        assert_eq!(values.len(), num_popped);
+        while let Some(value) = bag.pop() {
+            assert!(values.insert(value));
+        }
+        assert_eq!(values.len(), num_threads * num_values_per_thread);
+        assert!(bag.is_empty());
+        assert_eq!(bag.len(), 0);
+    }
+
+    #[test]
+    fn iter_across_pop() {
+        let bag: Arc<Bag<usize>> = Arc::new(Bag::new(1));
+        for value in 0..4 {
+            bag.push(value);
+        }
+
+        // Popping the value does not wait for the references to it to be dropped.
+        let guard = scc::pin();
+        let mut iter = bag.iter(&guard);
+        let first = iter.next().unwrap();
+        assert_eq!(*first, 3);
+        let bag_copied = bag.clone();
+        let popper = thread::spawn(move || bag_copied.pop());
+        assert_eq!(popper.join().unwrap(), Some(3));
+
+        // The popped value stays readable while the thread is pinned, and the iterator proceeds
+        // from where it was.
+        assert_eq!(*first, 3);
+        assert_eq!(iter.map(|value| *value).sum::<usize>(), 3);
+        assert_eq!(bag.iter(&guard).count(), 3);
+        assert_eq!(bag.len(), 3);
+    }
+}
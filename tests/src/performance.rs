@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod benchmark {
-    use scc::{HashIndex, HashMap, TreeIndex};
+    use rayon::prelude::*;
+    use scc::{HashIndex, HashIndexSet, HashMap, HashSet, ShrinkPolicy, TreeIndex};
     use std::collections::hash_map::RandomState;
     use std::hash::{BuildHasher, Hash};
     use std::sync::atomic::AtomicUsize;
@@ -262,7 +263,8 @@ mod benchmark {
     fn hashmap_benchmark() {
         let num_threads_vector = vec![1, 4, 16];
         for num_threads in num_threads_vector {
-            let hashmap: Arc<HashMap<usize, usize, RandomState>> = Arc::new(Default::default());
+            let hashmap: Arc<HashMap<usize, usize, RandomState>> =
+                Arc::new(HashMap::new(64, RandomState::new()));
             let workload_size = 262144;
 
             // 1. insert-local
@@ -407,7 +409,7 @@ mod benchmark {
         let num_threads_vector = vec![1, 4, 16];
         for num_threads in num_threads_vector {
             let hashindex: Arc<HashIndex<String, String, RandomState>> =
-                Arc::new(Default::default());
+                Arc::new(HashIndex::new(64, RandomState::new()));
             let workload_size = 262144;
 
             // 1. insert-local
@@ -692,4 +694,636 @@ mod benchmark {
             );
         }
     }
+
+    #[test]
+    fn hashmap_probe_benchmark() {
+        let num_keys = 1 << 20;
+        let hashmap: HashMap<usize, usize, RandomState> =
+            HashMap::new(num_keys, RandomState::new());
+        for key in 0..num_keys {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        let start_time = Instant::now();
+        for key in 0..num_keys {
+            assert!(hashmap.read(&key, |_, _| ()).is_some());
+        }
+        let duration = start_time.elapsed();
+        println!("hashmap-probe-hit: {:?}, {}", duration, num_keys);
+
+        let start_time = Instant::now();
+        for key in num_keys..num_keys * 2 {
+            assert!(hashmap.read(&key, |_, _| ()).is_none());
+        }
+        let duration = start_time.elapsed();
+        println!("hashmap-probe-miss: {:?}, {}", duration, num_keys);
+
+        let start_time = Instant::now();
+        for key in 0..num_keys {
+            assert!(hashmap.read_optimistic(&key, |_, _| ()).is_some());
+        }
+        let duration = start_time.elapsed();
+        println!("hashmap-probe-hit-optimistic: {:?}, {}", duration, num_keys);
+
+        let start_time = Instant::now();
+        for key in num_keys..num_keys * 2 {
+            assert!(hashmap.read_optimistic(&key, |_, _| ()).is_none());
+        }
+        let duration = start_time.elapsed();
+        println!(
+            "hashmap-probe-miss-optimistic: {:?}, {}",
+            duration, num_keys
+        );
+    }
+
+    #[test]
+    fn hashmap_large_value_benchmark() {
+        // Each value spans eight cache lines, whereas a probe only needs to compare keys.
+        let num_keys = 1 << 16;
+        let hashmap: HashMap<usize, [u8; 512], RandomState> =
+            HashMap::new(num_keys, RandomState::new());
+        for key in 0..num_keys {
+            assert!(hashmap.insert(key, [key as u8; 512]).is_ok());
+        }
+        for hit in [true, false] {
+            let keys = if hit {
+                0..num_keys
+            } else {
+                num_keys..num_keys * 2
+            };
+            let start_time = Instant::now();
+            for _ in 0..16 {
+                for key in keys.clone() {
+                    assert_eq!(hashmap.read(&key, |_, v| v[0]).is_some(), hit);
+                }
+            }
+            let duration = start_time.elapsed();
+            println!(
+                "hashmap-large-value-read: {:?}, {}, {}",
+                duration,
+                hit,
+                num_keys * 16
+            );
+        }
+    }
+
+    #[test]
+    fn hashmap_full_cell_benchmark() {
+        // A single Cell is 90% full, and a key is repeatedly inserted into one of the few free
+        // slots and removed.
+        let num_iterations = 1 << 20;
+        let hashmap: HashMap<usize, usize, RandomState> =
+            HashMap::new(32, RandomState::new()).with_max_load_factor(1.0);
+        for key in 0..29 {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        assert_eq!(hashmap.capacity(), 32);
+        let start_time = Instant::now();
+        for key in 29..29 + num_iterations {
+            assert!(hashmap.insert(key, key).is_ok());
+            assert_eq!(hashmap.remove(&key), Some(key));
+        }
+        let duration = start_time.elapsed();
+        assert_eq!(hashmap.capacity(), 32);
+        println!(
+            "hashmap-full-cell-insert: {:?}, {}",
+            duration, num_iterations
+        );
+    }
+
+    #[test]
+    fn hashmap_sparse_scan_benchmark() {
+        // Only one in eight slots holds an entry after most of the keys are removed.
+        let num_keys = 1 << 18;
+        let hashmap: HashMap<usize, usize, RandomState> =
+            HashMap::new(num_keys, RandomState::new()).with_shrink_policy(ShrinkPolicy::Never);
+        for key in 0..num_keys {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        hashmap.retain(|key, _| key % 8 == 0);
+        let start_time = Instant::now();
+        for _ in 0..64 {
+            assert_eq!(hashmap.retain(|_, _| true), (num_keys / 8, 0));
+        }
+        let duration = start_time.elapsed();
+        println!("hashmap-sparse-scan: {:?}, {}", duration, num_keys / 8);
+    }
+
+    #[test]
+    fn hashmap_hasher_benchmark() {
+        // foldhash is only measured with the `fast-hash` feature, e.g.,
+        // `cargo test --release -p scc-tests --features scc-tests/fast-hash hasher_benchmark`.
+        fn insert_read<H: BuildHasher>(hashmap: HashMap<u64, u64, H>, name: &str) {
+            let num_keys = 1_u64 << 20;
+            let start_time = Instant::now();
+            for key in 0..num_keys {
+                assert!(hashmap.insert(key, key).is_ok());
+            }
+            let duration = start_time.elapsed();
+            println!("hashmap-{}-insert: {:?}, {}", name, duration, num_keys);
+
+            let start_time = Instant::now();
+            for key in 0..num_keys {
+                assert_eq!(hashmap.read(&key, |_, v| *v), Some(key));
+            }
+            let duration = start_time.elapsed();
+            println!("hashmap-{}-read: {:?}, {}", name, duration, num_keys);
+        }
+
+        insert_read(HashMap::new(64, RandomState::new()), "siphash");
+        #[cfg(feature = "fast-hash")]
+        insert_read(HashMap::<u64, u64>::default(), "foldhash");
+    }
+
+    #[test]
+    fn large_map_scan_benchmark() {
+        // The DataArrays are allocated in the order of insertion which is unrelated to the order
+        // of Cells, therefore a scan visits DataArrays scattered across the heap.
+        let num_keys = 1 << 21;
+        let hashmap: HashMap<usize, usize, RandomState> =
+            HashMap::new(num_keys, RandomState::new());
+        let hashindex: HashIndex<usize, usize, RandomState> =
+            HashIndex::new(num_keys, RandomState::new());
+        for key in 0..num_keys {
+            assert!(hashmap.insert(key, key).is_ok());
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+
+        let start_time = Instant::now();
+        for _ in 0..4 {
+            assert_eq!(hashmap.retain(|_, _| true), (num_keys, 0));
+        }
+        let duration = start_time.elapsed();
+        println!("hashmap-large-scan: {:?}, {}", duration, num_keys * 4);
+
+        let start_time = Instant::now();
+        for _ in 0..4 {
+            assert_eq!(hashindex.iter().count(), num_keys);
+        }
+        let duration = start_time.elapsed();
+        println!("hashindex-large-scan: {:?}, {}", duration, num_keys * 4);
+    }
+
+    #[test]
+    fn hashmap_resize_latency_benchmark() {
+        // The HashMap is resized many times while the threads are inserting keys, and each
+        // thread measures the latency of every insertion. A single thread is not preempted by the
+        // others, so its tail latency reflects the relocation work done by each insertion.
+        let num_keys = 1 << 20;
+        for num_threads in [1, 4] {
+            let num_keys_per_thread = num_keys / num_threads;
+            let hashmap: HashMap<usize, usize, RandomState> = HashMap::new(64, RandomState::new());
+            let start_time = Instant::now();
+            let mut latencies = thread::scope(|s| {
+                let workers: Vec<_> = (0..num_threads)
+                    .map(|thread_id| {
+                        let hashmap = &hashmap;
+                        s.spawn(move || {
+                            let range = thread_id * num_keys_per_thread
+                                ..(thread_id + 1) * num_keys_per_thread;
+                            let mut latencies = Vec::with_capacity(num_keys_per_thread);
+                            for key in range {
+                                let insert_start_time = Instant::now();
+                                assert!(hashmap.insert(key, key).is_ok());
+                                latencies.push(insert_start_time.elapsed());
+                            }
+                            latencies
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().unwrap())
+                    .collect::<Vec<_>>()
+            });
+            let duration = start_time.elapsed();
+            latencies.sort_unstable();
+            println!(
+                "hashmap-resize-latency: {:?}, p99.99: {:?}, max: {:?}, {}, {}",
+                duration,
+                latencies[latencies.len() - latencies.len() / 10000 - 1],
+                latencies[latencies.len() - 1],
+                num_threads,
+                num_keys
+            );
+        }
+    }
+
+    #[test]
+    fn hashmap_full_cell_resize_latency_benchmark() {
+        #[derive(Default)]
+        struct GroupHasher(u64);
+        impl std::hash::Hasher for GroupHasher {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+            fn write(&mut self, _: &[u8]) {
+                unreachable!();
+            }
+            fn write_usize(&mut self, i: usize) {
+                self.0 = ((i / 1024) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            }
+        }
+        #[derive(Clone, Default)]
+        struct GroupBuildHasher;
+        impl BuildHasher for GroupBuildHasher {
+            type Hasher = GroupHasher;
+            fn build_hasher(&self) -> GroupHasher {
+                GroupHasher::default()
+            }
+        }
+        let num_keys = 1 << 18;
+        let hashmap: HashMap<usize, usize, GroupBuildHasher> = HashMap::new(64, GroupBuildHasher);
+        let start_time = Instant::now();
+        let mut latencies = Vec::with_capacity(num_keys);
+        for key in 0..num_keys {
+            let insert_start_time = Instant::now();
+            assert!(hashmap.insert(key, key).is_ok());
+            latencies.push(insert_start_time.elapsed());
+        }
+        let duration = start_time.elapsed();
+        latencies.sort_unstable();
+        println!(
+            "hashmap-full-cell-resize-latency: {:?}, p99.99: {:?}, max: {:?}, {}",
+            duration,
+            latencies[latencies.len() - latencies.len() / 10000 - 1],
+            latencies[latencies.len() - 1],
+            num_keys
+        );
+    }
+
+    #[test]
+    fn hashmap_resize_storm_benchmark() {
+        // Each thread repeatedly fills a small HashMap with its own keys and then removes them,
+        // so that the array keeps growing and shrinking, and many threads attempt to resize the
+        // array at the same time.
+        let num_threads = 4;
+        let num_keys_per_thread = 1 << 12;
+        let num_rounds = 16;
+        let hashmap: HashMap<usize, usize, RandomState> = HashMap::new(64, RandomState::new());
+        let start_time = Instant::now();
+        thread::scope(|s| {
+            for thread_id in 0..num_threads {
+                let hashmap = &hashmap;
+                s.spawn(move || {
+                    let range =
+                        thread_id * num_keys_per_thread..(thread_id + 1) * num_keys_per_thread;
+                    for _ in 0..num_rounds {
+                        for key in range.clone() {
+                            assert!(hashmap.insert(key, key).is_ok());
+                        }
+                        for key in range.clone() {
+                            assert!(hashmap.remove(&key).is_some());
+                        }
+                    }
+                });
+            }
+        });
+        let duration = start_time.elapsed();
+        let num_inserts = num_threads * num_keys_per_thread * num_rounds;
+        println!(
+            "hashmap-resize-storm: {:?}, {}, {:.0} inserts/s",
+            duration,
+            num_inserts,
+            num_inserts as f64 / duration.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn hashmap_cooperative_rehash_benchmark() {
+        // The old array left behind by a large resize is relocated by one thread, and then by
+        // four threads claiming disjoint chunks of Cells.
+        let num_keys = 1 << 20;
+        for num_threads in [1, 4] {
+            let hashmap: HashMap<usize, usize, RandomState> =
+                HashMap::new(64, RandomState::new()).with_rehash_budget(0);
+            assert!(hashmap.try_reserve(num_keys).is_ok());
+            for key in 0..num_keys {
+                assert!(hashmap.insert(key, key).is_ok());
+            }
+            assert!(hashmap.try_reserve(num_keys * 8).is_ok());
+            let start_time = Instant::now();
+            thread::scope(|s| {
+                for _ in 0..num_threads {
+                    s.spawn(|| while hashmap.rehash(32) {});
+                }
+            });
+            let duration = start_time.elapsed();
+            println!(
+                "hashmap-cooperative-rehash: {:?}, {}, {}",
+                duration, num_threads, num_keys
+            );
+        }
+    }
+
+    #[test]
+    fn hashmap_long_key_rehash_benchmark() {
+        // Relocating 200-byte keys is dominated by hashing them again unless the `full-hash`
+        // feature stores the hash value of each key.
+        let num_keys = 1 << 18;
+        let keys: Vec<String> = (0..num_keys).map(|key| format!("{:0>200}", key)).collect();
+        let hashmap: HashMap<String, usize, RandomState> =
+            HashMap::new(64, RandomState::new()).with_rehash_budget(0);
+        assert!(hashmap.try_reserve(num_keys).is_ok());
+        for (value, key) in keys.iter().enumerate() {
+            assert!(hashmap.insert(key.clone(), value).is_ok());
+        }
+        assert!(hashmap.try_reserve(num_keys * 8).is_ok());
+        let start_time = Instant::now();
+        while hashmap.rehash(32) {}
+        let duration = start_time.elapsed();
+        println!(
+            "hashmap-long-key-rehash: {:?}, {}, {}",
+            duration,
+            num_keys,
+            cfg!(feature = "full-hash")
+        );
+    }
+
+    #[test]
+    fn hashmap_long_key_probe_benchmark() {
+        // All the keys share the same partial hash value, therefore a probe compares the 200-byte
+        // key of every entry in the Cell unless the `full-hash` feature compares the stored hash
+        // values beforehand.
+        struct ShiftedHasher(std::collections::hash_map::DefaultHasher);
+        impl std::hash::Hasher for ShiftedHasher {
+            fn finish(&self) -> u64 {
+                self.0.finish() << 16
+            }
+            fn write(&mut self, bytes: &[u8]) {
+                self.0.write(bytes);
+            }
+        }
+        #[derive(Clone, Default)]
+        struct ShiftedBuildHasher(RandomState);
+        impl BuildHasher for ShiftedBuildHasher {
+            type Hasher = ShiftedHasher;
+            fn build_hasher(&self) -> ShiftedHasher {
+                ShiftedHasher(self.0.build_hasher())
+            }
+        }
+        let num_keys = 1 << 16;
+        let keys: Vec<String> = (0..num_keys * 2)
+            .map(|key| format!("{:0>200}", key))
+            .collect();
+        let hashmap: HashMap<String, usize, ShiftedBuildHasher> =
+            HashMap::new(num_keys, ShiftedBuildHasher::default()).with_hash_finalizer(false);
+        for (value, key) in keys[..num_keys].iter().enumerate() {
+            assert!(hashmap.insert(key.clone(), value).is_ok());
+        }
+        for hit in [true, false] {
+            let keys = if hit {
+                &keys[..num_keys]
+            } else {
+                &keys[num_keys..]
+            };
+            let start_time = Instant::now();
+            for _ in 0..16 {
+                for key in keys {
+                    assert_eq!(hashmap.read(key, |_, _| ()).is_some(), hit);
+                }
+            }
+            let duration = start_time.elapsed();
+            println!(
+                "hashmap-long-key-probe: {:?}, {}, {}, {}",
+                duration,
+                hit,
+                num_keys * 16,
+                cfg!(feature = "full-hash")
+            );
+        }
+    }
+
+    #[test]
+    fn hashindex_guard_reuse_benchmark() {
+        let num_keys = 1 << 18;
+        let hashindex: HashIndex<usize, usize, RandomState> =
+            HashIndex::new(64, RandomState::new());
+        for key in 0..num_keys {
+            assert!(hashindex.insert(key, key).is_ok());
+        }
+        let start_time = Instant::now();
+        for key in 0..num_keys {
+            assert_eq!(hashindex.read(&key, |_, v| *v), Some(key));
+        }
+        let duration = start_time.elapsed();
+        println!("hashindex-read-pinned: {:?}, {}", duration, num_keys);
+        let start_time = Instant::now();
+        let guard = scc::pin();
+        for key in 0..num_keys {
+            assert_eq!(hashindex.read_with(&key, |_, v| *v, &guard), Some(key));
+        }
+        drop(guard);
+        let duration = start_time.elapsed();
+        println!("hashindex-read-guard-reuse: {:?}, {}", duration, num_keys);
+    }
+
+    #[test]
+    fn hashmap_neighbor_write_benchmark() {
+        // A small HashMap where the Cells read by the readers and the Cells written by the
+        // writers are interleaved.
+        let num_readers = 2;
+        let num_writers = 2;
+        let num_keys = 1024;
+        let hashmap: HashMap<usize, usize, RandomState> = HashMap::new(256, RandomState::new());
+        for key in 0..num_keys {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        for with_writers in [false, true] {
+            let readers_done = AtomicUsize::new(0);
+            let start_time = Instant::now();
+            thread::scope(|s| {
+                for reader_id in 0..num_readers {
+                    let (hashmap, readers_done) = (&hashmap, &readers_done);
+                    s.spawn(move || {
+                        for _ in 0..256 {
+                            for key in (reader_id..num_keys).step_by(num_readers * 2) {
+                                assert!(hashmap.read(&key, |_, _| ()).is_some());
+                            }
+                        }
+                        readers_done.fetch_add(1, Relaxed);
+                    });
+                }
+                for writer_id in 0..num_writers {
+                    let (hashmap, readers_done) = (&hashmap, &readers_done);
+                    s.spawn(move || {
+                        let keys = (num_readers + writer_id..num_keys).step_by(num_writers * 2);
+                        while with_writers && readers_done.load(Relaxed) != num_readers {
+                            for key in keys.clone() {
+                                drop(hashmap.upsert(key, key));
+                            }
+                        }
+                    });
+                }
+            });
+            let duration = start_time.elapsed();
+            println!(
+                "hashmap-neighbor-write: {:?}, writers: {}",
+                duration, with_writers
+            );
+        }
+    }
+
+    #[test]
+    fn hashmap_uncontended_lock_benchmark() {
+        // A single thread takes the exclusive lock and the shared lock of a Cell in turn, which
+        // never makes a writer wait for readers.
+        let num_keys = 1 << 16;
+        let hashmap: HashMap<usize, usize, RandomState> =
+            HashMap::new(num_keys, RandomState::new());
+        for key in 0..num_keys {
+            assert!(hashmap.insert(key, key).is_ok());
+        }
+        let start_time = Instant::now();
+        for _ in 0..16 {
+            for key in 0..num_keys {
+                assert!(hashmap.get(&key).is_some());
+            }
+        }
+        let duration = start_time.elapsed();
+        println!(
+            "hashmap-uncontended-exclusive: {:?}, {}",
+            duration,
+            num_keys * 16
+        );
+        let start_time = Instant::now();
+        for _ in 0..16 {
+            for key in 0..num_keys {
+                assert!(hashmap.read(&key, |_, _| ()).is_some());
+            }
+        }
+        let duration = start_time.elapsed();
+        println!(
+            "hashmap-uncontended-shared: {:?}, {}",
+            duration,
+            num_keys * 16
+        );
+    }
+
+    #[test]
+    fn treeindex_remove_range_benchmark() {
+        let num_keys = 262144;
+        let tree: TreeIndex<usize, usize> = TreeIndex::new();
+        for key in 0..num_keys {
+            assert!(tree.insert(key, key).is_ok());
+        }
+        let start_time = Instant::now();
+        for key in num_keys / 4..num_keys * 3 / 4 {
+            assert!(tree.remove(&key));
+        }
+        let duration = start_time.elapsed();
+        println!("treeindex-remove: {:?}, {}", duration, num_keys / 2);
+
+        let tree: TreeIndex<usize, usize> = TreeIndex::new();
+        for key in 0..num_keys {
+            assert!(tree.insert(key, key).is_ok());
+        }
+        let start_time = Instant::now();
+        assert_eq!(
+            tree.remove_range(num_keys / 4..num_keys * 3 / 4),
+            num_keys / 2
+        );
+        let duration = start_time.elapsed();
+        println!("treeindex-remove-range: {:?}, {}", duration, num_keys / 2);
+    }
+
+    fn read_scalability<S: Send + Sync + 'static>(
+        num_threads: usize,
+        num_keys: usize,
+        set: Arc<S>,
+        contains: fn(&S, &usize) -> bool,
+    ) -> Duration {
+        let barrier = Arc::new(Barrier::new(num_threads + 1));
+        let mut thread_handles = Vec::with_capacity(num_threads);
+        for thread_id in 0..num_threads {
+            let barrier_copied = barrier.clone();
+            let set_copied = set.clone();
+            thread_handles.push(thread::spawn(move || {
+                barrier_copied.wait();
+                for key in 0..num_keys {
+                    assert!(contains(&set_copied, &((key + thread_id) % num_keys)));
+                }
+                barrier_copied.wait();
+            }));
+        }
+        barrier.wait();
+        let start_time = Instant::now();
+        barrier.wait();
+        let duration = start_time.elapsed();
+        for handle in thread_handles {
+            handle.join().unwrap();
+        }
+        duration
+    }
+
+    #[test]
+    fn hashindex_par_collect_benchmark() {
+        let data_size: u64 = 5_000_000;
+
+        let start_time = Instant::now();
+        let sequential: HashIndex<u64, u64> = HashIndex::default();
+        for key in 0..data_size {
+            assert!(sequential.insert(key, key).is_ok());
+        }
+        let sequential_duration = start_time.elapsed();
+        println!("hashindex-insert: {:?}, {}", sequential_duration, data_size);
+        assert_eq!(sequential.len(), data_size as usize);
+        drop(sequential);
+
+        let start_time = Instant::now();
+        let parallel: HashIndex<u64, u64> = (0..data_size)
+            .into_par_iter()
+            .map(|key| (key, key))
+            .collect();
+        let parallel_duration = start_time.elapsed();
+        println!(
+            "hashindex-par-collect: {}, {:?}, {}",
+            rayon::current_num_threads(),
+            parallel_duration,
+            data_size
+        );
+        assert_eq!(parallel.len(), data_size as usize);
+        for key in 0..data_size {
+            assert_eq!(parallel.read(&key, |_, v| *v), Some(key));
+        }
+
+        // Parallel insertion is expected to pay off only if multiple cores are available.
+        if thread::available_parallelism().map_or(1, |n| n.get()) > 1 {
+            assert!(parallel_duration < sequential_duration);
+        }
+    }
+
+    #[test]
+    fn hashindexset_benchmark() {
+        let num_threads_vector = vec![1, 4, 16];
+        let num_keys = 262144;
+        let hashset: Arc<HashSet<usize, RandomState>> =
+            Arc::new(HashSet::new(64, RandomState::new()));
+        let hashindexset: Arc<HashIndexSet<usize, RandomState>> =
+            Arc::new(HashIndexSet::new(64, RandomState::new()));
+        for key in 0..num_keys {
+            assert!(hashset.insert(key).is_ok());
+            assert!(hashindexset.insert(key).is_ok());
+        }
+        for num_threads in num_threads_vector {
+            let duration = read_scalability(num_threads, num_keys, hashset.clone(), |set, key| {
+                set.contains(key)
+            });
+            println!(
+                "hashset-contains: {}, {:?}, {}",
+                num_threads,
+                duration,
+                num_threads * num_keys
+            );
+            let duration =
+                read_scalability(num_threads, num_keys, hashindexset.clone(), |set, key| {
+                    set.contains(key)
+                });
+            println!(
+                "hashindexset-contains: {}, {:?}, {}",
+                num_threads,
+                duration,
+                num_threads * num_keys
+            );
+        }
+    }
 }